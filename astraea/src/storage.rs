@@ -126,6 +126,19 @@ pub trait CollectGarbage {
         -> std::result::Result<GarbageCollectionStats, StoreError>;
 }
 
+#[async_trait]
+pub trait MeasureRoot {
+    /// Sums up the stored size of every tree reachable from the root with the given name. Trees referenced more than
+    /// once are counted only once. A missing root measures zero bytes.
+    async fn measure_root(&self, name: &str) -> std::result::Result<u64, StoreError> {
+        self.measure_roots(&[name]).await
+    }
+
+    /// Like [MeasureRoot::measure_root] for all of the given roots together, so trees that several of them share are
+    /// counted only once.
+    async fn measure_roots(&self, names: &[&str]) -> std::result::Result<u64, StoreError>;
+}
+
 #[derive(Debug)]
 pub struct InMemoryTreeStorage {
     reference_to_tree: Mutex<BTreeMap<BlobDigest, HashedTree>>,
@@ -609,6 +622,37 @@ impl CollectGarbage for SQLiteStorage {
     }
 }
 
+#[async_trait]
+impl MeasureRoot for SQLiteStorage {
+    async fn measure_roots(&self, names: &[&str]) -> std::result::Result<u64, StoreError> {
+        let state_locked = self.state.lock().await;
+        let connection_locked = &state_locked.connection;
+        let placeholders = (1..=names.len())
+            .map(|index| format!("?{index}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        // UNION (unlike UNION ALL) discards duplicates, so shared subtrees are only visited once.
+        let size: i64 = connection_locked
+            .query_row(
+                &format!(
+                    "WITH RECURSIVE reachable(digest) AS (
+                    SELECT target FROM root WHERE name IN ({placeholders})
+                    UNION
+                    SELECT reference.target FROM reachable
+                    JOIN tree ON tree.digest = reachable.digest
+                    JOIN reference ON reference.origin = tree.id
+                )
+                SELECT COALESCE(SUM(LENGTH(tree.tree_blob)), 0) FROM reachable
+                JOIN tree ON tree.digest = reachable.digest"
+                ),
+                rusqlite::params_from_iter(names),
+                |row| -> rusqlite::Result<_> { row.get(0) },
+            )
+            .map_err(|err| StoreError::Rusqlite(format!("{}", &err)))?;
+        Ok(u64::try_from(size).expect("SUM(LENGTH(..)) won't be negative"))
+    }
+}
+
 #[async_trait]
 impl LoadRoot for SQLiteStorage {
     //#[instrument(skip_all)]
@@ -1,7 +1,7 @@
 use crate::{
     storage::{
         CollectGarbage, CommitChanges, GarbageCollectionStats, LoadError, LoadRoot, LoadTree,
-        SQLiteStorage, StoreError, StoreTree, UpdateRoot,
+        MeasureRoot, SQLiteStorage, StoreError, StoreTree, UpdateRoot,
     },
     tree::{BlobDigest, HashedTree, Tree, TreeBlob, TreeChildren},
 };
@@ -250,6 +250,84 @@ async fn test_roots_may_be_equal() {
     assert_eq!(Ok(Some(reference_1)), storage.load_root(name_1).await);
 }
 
+#[test_log::test(tokio::test)]
+async fn test_measure_root() {
+    let connection = rusqlite::Connection::open_in_memory().unwrap();
+    SQLiteStorage::create_schema(&connection).unwrap();
+    let storage = SQLiteStorage::from(connection).unwrap();
+    let name = "test";
+    assert_eq!(Ok(0), storage.measure_root(name).await);
+    let leaf = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::try_from(Bytes::from("leaf")).unwrap(),
+            TreeChildren::empty(),
+        ))))
+        .await
+        .unwrap();
+    // the same leaf is referenced twice, but it is only stored once
+    let root = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::try_from(Bytes::from("root")).unwrap(),
+            TreeChildren::try_from(vec![leaf, leaf]).unwrap(),
+        ))))
+        .await
+        .unwrap();
+    storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::try_from(Bytes::from("unreachable")).unwrap(),
+            TreeChildren::empty(),
+        ))))
+        .await
+        .unwrap();
+    assert_eq!(Ok(0), storage.measure_root(name).await);
+    storage.update_root(name, &root).await.unwrap();
+    assert_eq!(Ok(8), storage.measure_root(name).await);
+    storage.update_root(name, &leaf).await.unwrap();
+    assert_eq!(Ok(4), storage.measure_root(name).await);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_measure_roots() {
+    let connection = rusqlite::Connection::open_in_memory().unwrap();
+    SQLiteStorage::create_schema(&connection).unwrap();
+    let storage = SQLiteStorage::from(connection).unwrap();
+    assert_eq!(Ok(0), storage.measure_roots(&[]).await);
+    assert_eq!(Ok(0), storage.measure_roots(&["a", "b"]).await);
+    let shared = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::try_from(Bytes::from("shared")).unwrap(),
+            TreeChildren::empty(),
+        ))))
+        .await
+        .unwrap();
+    let root_a = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::try_from(Bytes::from("a")).unwrap(),
+            TreeChildren::try_from(vec![shared]).unwrap(),
+        ))))
+        .await
+        .unwrap();
+    let root_b = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::try_from(Bytes::from("bb")).unwrap(),
+            TreeChildren::try_from(vec![shared]).unwrap(),
+        ))))
+        .await
+        .unwrap();
+    storage.update_root("a", &root_a).await.unwrap();
+    storage.update_root("b", &root_b).await.unwrap();
+    storage.update_root("c", &shared).await.unwrap();
+    assert_eq!(Ok(7), storage.measure_root("a").await);
+    assert_eq!(Ok(8), storage.measure_root("b").await);
+    // the shared tree is counted once for all roots together
+    assert_eq!(Ok(9), storage.measure_roots(&["a", "b"]).await);
+    assert_eq!(
+        Ok(9),
+        storage.measure_roots(&["a", "b", "c", "missing"]).await
+    );
+    assert_eq!(Ok(6), storage.measure_roots(&["c"]).await);
+}
+
 #[test_log::test(tokio::test)]
 async fn test_compression_compressible_data() {
     // Test that compressible data works correctly with compression
@@ -0,0 +1,470 @@
+use base64::Engine;
+use dogbox_tree_editor::WallClock;
+use hmac::Mac;
+use md5::Digest;
+use std::collections::BTreeMap;
+use tracing::{debug, info};
+
+/// The realm used by the server binaries unless configured otherwise.
+pub const DEFAULT_REALM: &str = "dogbox";
+
+/// The stored form of a password: MD5(user name:realm:password), also known as HA1. This is the only form that works for
+/// both Basic and Digest authentication. It is also the format of the files produced by Apache's `htdigest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordHash([u8; 16]);
+
+impl PasswordHash {
+    pub fn new(user_name: &str, realm: &str, password: &str) -> Self {
+        Self(md5::Md5::digest(format!("{user_name}:{realm}:{password}")).into())
+    }
+
+    pub fn parse_hex(input: &str) -> Option<Self> {
+        let mut result = [0u8; 16];
+        hex::decode_to_slice(input, &mut result).ok()?;
+        Some(Self(result))
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserListError {
+    InvalidLine { line_number: usize },
+    DuplicateUser { name: String },
+}
+
+impl std::fmt::Display for UserListError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for UserListError {}
+
+/// The users allowed to access the server within one realm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserList {
+    realm: String,
+    users: BTreeMap<String, PasswordHash>,
+}
+
+impl UserList {
+    pub fn new(realm: String) -> Self {
+        Self {
+            realm,
+            users: BTreeMap::new(),
+        }
+    }
+
+    /// Reads the `user:realm:hash` lines of an `htdigest` file. Lines for other realms are ignored.
+    pub fn parse_htdigest(realm: String, content: &str) -> Result<Self, UserListError> {
+        let mut result = Self::new(realm);
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid_line = UserListError::InvalidLine {
+                line_number: index + 1,
+            };
+            let mut parts = line.split(':');
+            let (Some(name), Some(realm), Some(hash), None) =
+                (parts.next(), parts.next(), parts.next(), parts.next())
+            else {
+                return Err(invalid_line);
+            };
+            if realm != result.realm {
+                continue;
+            }
+            let hash = PasswordHash::parse_hex(hash).ok_or(invalid_line)?;
+            result.add_user_with_hash(name.to_string(), hash)?;
+        }
+        Ok(result)
+    }
+
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    pub fn add_user(&mut self, name: String, password: &str) -> Result<(), UserListError> {
+        let hash = PasswordHash::new(&name, &self.realm, password);
+        self.add_user_with_hash(name, hash)
+    }
+
+    pub fn add_user_with_hash(
+        &mut self,
+        name: String,
+        hash: PasswordHash,
+    ) -> Result<(), UserListError> {
+        match self.users.entry(name) {
+            std::collections::btree_map::Entry::Vacant(vacant) => {
+                vacant.insert(hash);
+                Ok(())
+            }
+            std::collections::btree_map::Entry::Occupied(occupied) => {
+                Err(UserListError::DuplicateUser {
+                    name: occupied.key().clone(),
+                })
+            }
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.users.contains_key(name)
+    }
+
+    pub fn user_names(&self) -> impl Iterator<Item = &String> {
+        self.users.keys()
+    }
+
+    fn find(&self, name: &str) -> Option<&PasswordHash> {
+        self.users.get(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthenticationError {
+    MissingCredentials,
+    UnsupportedScheme,
+    MalformedCredentials,
+    WrongCredentials,
+    /// The Digest response was correct, but the client has to repeat it with a new nonce.
+    StaleNonce,
+}
+
+impl std::fmt::Display for AuthenticationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for AuthenticationError {}
+
+type NonceMac = hmac::Hmac<sha2::Sha256>;
+
+/// Checks the Authorization header of requests against a [UserList].
+///
+/// Digest nonces contain their creation time and a MAC of it, so any nonce created by this instance is accepted until
+/// [Authenticator::NONCE_LIFETIME] has passed. To prevent replays, the nonce count of every client nonce has to increase
+/// with each request. A nonce without a client nonce can only be used once.
+pub struct Authenticator {
+    users: UserList,
+    nonce_key: [u8; 32],
+    clock: WallClock,
+    // The key is the nonce and the client nonce, the value is the highest nonce count seen.
+    nonce_counts: std::sync::Mutex<BTreeMap<(String, String), NonceUse>>,
+}
+
+struct NonceUse {
+    created_at: std::time::SystemTime,
+    highest_count: Option<u32>,
+}
+
+impl Authenticator {
+    pub const NONCE_LIFETIME: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+    const NONCE_MAC_LENGTH: usize = 16;
+
+    pub fn new(users: UserList, clock: WallClock) -> Self {
+        Self {
+            users,
+            nonce_key: rand::random(),
+            clock,
+            nonce_counts: std::sync::Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn users(&self) -> &UserList {
+        &self.users
+    }
+
+    /// Returns the name of the authenticated user.
+    pub fn authenticate(
+        &self,
+        method: &hyper::Method,
+        uri: &hyper::Uri,
+        headers: &hyper::HeaderMap,
+    ) -> Result<String, AuthenticationError> {
+        let header = headers
+            .get(hyper::header::AUTHORIZATION)
+            .ok_or(AuthenticationError::MissingCredentials)?
+            .to_str()
+            .map_err(|_| AuthenticationError::MalformedCredentials)?;
+        let (scheme, credentials) = header.split_once(' ').unwrap_or((header, ""));
+        let result = if scheme.eq_ignore_ascii_case("Basic") {
+            self.authenticate_basic(credentials.trim())
+        } else if scheme.eq_ignore_ascii_case("Digest") {
+            self.authenticate_digest(method, uri, credentials)
+        } else {
+            Err(AuthenticationError::UnsupportedScheme)
+        };
+        match &result {
+            Ok(user_name) => debug!("Authenticated user {}", user_name),
+            Err(error) => info!("Authentication failed: {}", error),
+        }
+        result
+    }
+
+    /// The values of the WWW-Authenticate headers for a 401 response. Digest comes first because some clients only look
+    /// at the first challenge.
+    pub fn challenges(&self, is_stale: bool) -> Vec<String> {
+        let realm = self.users.realm();
+        let nonce = self.create_nonce((self.clock)());
+        vec![
+            format!(
+                "Digest realm=\"{realm}\", qop=\"auth\", algorithm=MD5, nonce=\"{nonce}\"{}",
+                if is_stale { ", stale=true" } else { "" }
+            ),
+            format!("Basic realm=\"{realm}\", charset=\"UTF-8\""),
+        ]
+    }
+
+    fn authenticate_basic(&self, credentials: &str) -> Result<String, AuthenticationError> {
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(credentials)
+            .map_err(|_| AuthenticationError::MalformedCredentials)?;
+        let decoded =
+            String::from_utf8(decoded).map_err(|_| AuthenticationError::MalformedCredentials)?;
+        let (user_name, password) = decoded
+            .split_once(':')
+            .ok_or(AuthenticationError::MalformedCredentials)?;
+        let expected = self
+            .users
+            .find(user_name)
+            .ok_or(AuthenticationError::WrongCredentials)?;
+        let actual = PasswordHash::new(user_name, self.users.realm(), password);
+        if constant_time_equals(&expected.0, &actual.0) {
+            Ok(user_name.to_string())
+        } else {
+            Err(AuthenticationError::WrongCredentials)
+        }
+    }
+
+    fn authenticate_digest(
+        &self,
+        method: &hyper::Method,
+        uri: &hyper::Uri,
+        credentials: &str,
+    ) -> Result<String, AuthenticationError> {
+        let parameters = parse_digest_parameters(credentials)
+            .ok_or(AuthenticationError::MalformedCredentials)?;
+        let get = |name: &str| -> Result<&str, AuthenticationError> {
+            parameters
+                .get(name)
+                .map(|value| value.as_str())
+                .ok_or(AuthenticationError::MalformedCredentials)
+        };
+        let user_name = get("username")?;
+        if get("realm")? != self.users.realm() {
+            return Err(AuthenticationError::WrongCredentials);
+        }
+        match parameters.get("algorithm") {
+            None => {}
+            Some(algorithm) if algorithm.eq_ignore_ascii_case("MD5") => {}
+            Some(_) => return Err(AuthenticationError::UnsupportedScheme),
+        }
+        // The response only proves knowledge of the password for this exact request target.
+        let digest_uri = get("uri")?;
+        let request_target = uri
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str())
+            .unwrap_or(uri.path());
+        if digest_uri != request_target && digest_uri != uri.path() {
+            return Err(AuthenticationError::WrongCredentials);
+        }
+        let nonce = get("nonce")?;
+        let quality_of_protection = match parameters.get("qop") {
+            Some(qop) if qop == "auth" => Some((get("nc")?, get("cnonce")?)),
+            Some(_) => return Err(AuthenticationError::UnsupportedScheme),
+            None => None,
+        };
+        let count = match quality_of_protection {
+            Some((nonce_count, _client_nonce)) => Some(
+                u32::from_str_radix(nonce_count, 16)
+                    .map_err(|_| AuthenticationError::MalformedCredentials)?,
+            ),
+            None => None,
+        };
+        let password_hash = self
+            .users
+            .find(user_name)
+            .ok_or(AuthenticationError::WrongCredentials)?;
+        let expected = digest_response(
+            password_hash,
+            nonce,
+            quality_of_protection,
+            method.as_str(),
+            digest_uri,
+        );
+        if !constant_time_equals(expected.as_bytes(), get("response")?.as_bytes()) {
+            return Err(AuthenticationError::WrongCredentials);
+        }
+        let now = (self.clock)();
+        let created_at = self.check_nonce(nonce, now)?;
+        let client_nonce = quality_of_protection.map_or("", |(_count, client_nonce)| client_nonce);
+        self.check_nonce_count(nonce, client_nonce, created_at, count, now)?;
+        Ok(user_name.to_string())
+    }
+
+    /// A client that gets [AuthenticationError::StaleNonce] for a repeated count simply retries with a new nonce.
+    fn check_nonce_count(
+        &self,
+        nonce: &str,
+        client_nonce: &str,
+        created_at: std::time::SystemTime,
+        count: Option<u32>,
+        now: std::time::SystemTime,
+    ) -> Result<(), AuthenticationError> {
+        let mut nonce_counts = self.nonce_counts.lock().unwrap();
+        nonce_counts.retain(|_key, nonce_use| {
+            now.duration_since(nonce_use.created_at)
+                .is_ok_and(|age| age <= Self::NONCE_LIFETIME)
+        });
+        match nonce_counts.entry((nonce.to_string(), client_nonce.to_string())) {
+            std::collections::btree_map::Entry::Vacant(vacant) => {
+                vacant.insert(NonceUse {
+                    created_at,
+                    highest_count: count,
+                });
+                Ok(())
+            }
+            std::collections::btree_map::Entry::Occupied(mut occupied) => {
+                match (occupied.get().highest_count, count) {
+                    (Some(highest), Some(count)) if count > highest => {
+                        occupied.get_mut().highest_count = Some(count);
+                        Ok(())
+                    }
+                    _ => {
+                        info!("Nonce {} was used again", nonce);
+                        Err(AuthenticationError::StaleNonce)
+                    }
+                }
+            }
+        }
+    }
+
+    fn nonce_mac(&self, created_at_seconds: u64) -> NonceMac {
+        let mut mac = NonceMac::new_from_slice(&self.nonce_key).expect("HMAC accepts any key size");
+        mac.update(&created_at_seconds.to_be_bytes());
+        mac
+    }
+
+    fn create_nonce(&self, now: std::time::SystemTime) -> String {
+        let created_at_seconds = now
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mac = self.nonce_mac(created_at_seconds).finalize().into_bytes();
+        format!(
+            "{}{}",
+            hex::encode(created_at_seconds.to_be_bytes()),
+            hex::encode(&mac[..Self::NONCE_MAC_LENGTH])
+        )
+    }
+
+    /// Returns when the nonce was created.
+    fn check_nonce(
+        &self,
+        nonce: &str,
+        now: std::time::SystemTime,
+    ) -> Result<std::time::SystemTime, AuthenticationError> {
+        let mut decoded = [0u8; 8 + Self::NONCE_MAC_LENGTH];
+        hex::decode_to_slice(nonce, &mut decoded)
+            .map_err(|_| AuthenticationError::WrongCredentials)?;
+        let created_at_seconds = u64::from_be_bytes(decoded[..8].try_into().unwrap());
+        self.nonce_mac(created_at_seconds)
+            .verify_truncated_left(&decoded[8..])
+            .map_err(|_| AuthenticationError::WrongCredentials)?;
+        let created_at = std::time::UNIX_EPOCH + std::time::Duration::from_secs(created_at_seconds);
+        match now.duration_since(created_at) {
+            Ok(age) if age > Self::NONCE_LIFETIME => Err(AuthenticationError::StaleNonce),
+            Ok(_) => Ok(created_at),
+            // created by this server in the future according to the clock
+            Err(_) => Err(AuthenticationError::StaleNonce),
+        }
+    }
+}
+
+/// The expected value of the `response` parameter of Digest authentication with `qop=auth` or without `qop`.
+pub fn digest_response(
+    password_hash: &PasswordHash,
+    nonce: &str,
+    nonce_count_and_client_nonce: Option<(&str, &str)>,
+    method: &str,
+    uri: &str,
+) -> String {
+    let ha1 = password_hash.to_hex();
+    let ha2 = hex::encode(md5::Md5::digest(format!("{method}:{uri}")));
+    let input = match nonce_count_and_client_nonce {
+        Some((nonce_count, client_nonce)) => {
+            format!("{ha1}:{nonce}:{nonce_count}:{client_nonce}:auth:{ha2}")
+        }
+        None => format!("{ha1}:{nonce}:{ha2}"),
+    };
+    hex::encode(md5::Md5::digest(input))
+}
+
+/// Parses the comma separated `name=value` and `name="quoted value"` pairs of a Digest Authorization header.
+pub fn parse_digest_parameters(input: &str) -> Option<BTreeMap<String, String>> {
+    let mut result = BTreeMap::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let (name, after_name) = rest.split_once('=')?;
+        let name = name.trim().to_ascii_lowercase();
+        let after_name = after_name.trim_start();
+        let (value, after_value) = match after_name.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut characters = quoted.char_indices();
+                let end = loop {
+                    match characters.next()? {
+                        (index, '"') => break index,
+                        (_, '\\') => value.push(characters.next()?.1),
+                        (_, character) => value.push(character),
+                    }
+                };
+                (value, &quoted[end + 1..])
+            }
+            None => {
+                let end = after_name.find(',').unwrap_or(after_name.len());
+                (after_name[..end].trim().to_string(), &after_name[end..])
+            }
+        };
+        result.insert(name, value);
+        rest = after_value.trim_start();
+        match rest.strip_prefix(',') {
+            Some(after_comma) => rest = after_comma.trim_start(),
+            None if rest.is_empty() => {}
+            None => return None,
+        }
+    }
+    Some(result)
+}
+
+/// A copy of the headers that can be logged. The values of headers that carry credentials are replaced, because Basic
+/// authentication sends the password in plain base64.
+pub fn redact_credentials(headers: &hyper::HeaderMap) -> hyper::HeaderMap {
+    let mut result = headers.clone();
+    for name in [
+        hyper::header::AUTHORIZATION,
+        hyper::header::PROXY_AUTHORIZATION,
+        hyper::header::COOKIE,
+    ] {
+        if let hyper::header::Entry::Occupied(mut entry) = result.entry(name) {
+            for value in entry.iter_mut() {
+                *value = hyper::header::HeaderValue::from_static("<redacted>");
+            }
+        }
+    }
+    result
+}
+
+fn constant_time_equals(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right.iter())
+            .fold(0u8, |difference, (l, r)| difference | (l ^ r))
+            == 0
+}
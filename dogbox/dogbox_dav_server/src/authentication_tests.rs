@@ -0,0 +1,412 @@
+use crate::authentication::{
+    digest_response, parse_digest_parameters, redact_credentials, AuthenticationError,
+    Authenticator, PasswordHash, UserList, UserListError,
+};
+use base64::Engine;
+use std::sync::{Arc, Mutex};
+
+fn test_clock() -> (
+    Arc<Mutex<std::time::SystemTime>>,
+    dogbox_tree_editor::WallClock,
+) {
+    let now = Arc::new(Mutex::new(
+        std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000),
+    ));
+    let clock = {
+        let now = now.clone();
+        Arc::new(move || *now.lock().unwrap())
+    };
+    (now, clock)
+}
+
+fn test_authenticator(clock: dogbox_tree_editor::WallClock) -> Authenticator {
+    let mut users = UserList::new("dogbox".to_string());
+    users.add_user("alice".to_string(), "secret").unwrap();
+    Authenticator::new(users, clock)
+}
+
+fn headers_with_authorization(value: &str) -> hyper::HeaderMap {
+    let mut headers = hyper::HeaderMap::new();
+    headers.insert(
+        hyper::header::AUTHORIZATION,
+        hyper::header::HeaderValue::from_str(value).unwrap(),
+    );
+    headers
+}
+
+fn basic_authorization(user_name: &str, password: &str) -> hyper::HeaderMap {
+    headers_with_authorization(&format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD.encode(format!("{user_name}:{password}"))
+    ))
+}
+
+fn nonce_from_challenge(authenticator: &Authenticator) -> String {
+    let challenges = authenticator.challenges(false);
+    let parameters =
+        parse_digest_parameters(challenges[0].strip_prefix("Digest ").unwrap()).unwrap();
+    parameters.get("nonce").unwrap().clone()
+}
+
+fn digest_authorization(
+    user_name: &str,
+    password: &str,
+    nonce: &str,
+    method: &str,
+    uri: &str,
+) -> hyper::HeaderMap {
+    digest_authorization_with_count(user_name, password, nonce, "00000001", method, uri)
+}
+
+fn digest_authorization_with_count(
+    user_name: &str,
+    password: &str,
+    nonce: &str,
+    nonce_count: &str,
+    method: &str,
+    uri: &str,
+) -> hyper::HeaderMap {
+    let response = digest_response(
+        &PasswordHash::new(user_name, "dogbox", password),
+        nonce,
+        Some((nonce_count, "abcdef")),
+        method,
+        uri,
+    );
+    headers_with_authorization(&format!(
+        "Digest username=\"{user_name}\", realm=\"dogbox\", nonce=\"{nonce}\", uri=\"{uri}\", \
+        qop=auth, nc={nonce_count}, cnonce=\"abcdef\", response=\"{response}\", algorithm=MD5"
+    ))
+}
+
+#[test_log::test]
+fn test_password_hash() {
+    // example from RFC 2617
+    assert_eq!(
+        "939e7578ed9e3c518a452acee763bce9",
+        PasswordHash::new("Mufasa", "testrealm@host.com", "Circle Of Life").to_hex()
+    );
+    assert_eq!(
+        Some(PasswordHash::new(
+            "Mufasa",
+            "testrealm@host.com",
+            "Circle Of Life"
+        )),
+        PasswordHash::parse_hex("939e7578ed9e3c518a452acee763bce9")
+    );
+    assert_eq!(None, PasswordHash::parse_hex("939e"));
+    assert_eq!(
+        None,
+        PasswordHash::parse_hex("x39e7578ed9e3c518a452acee763bce9")
+    );
+}
+
+#[test_log::test]
+fn test_digest_response() {
+    // example from RFC 2617
+    assert_eq!(
+        "6629fae49393a05397450978507c4ef1",
+        digest_response(
+            &PasswordHash::new("Mufasa", "testrealm@host.com", "Circle Of Life"),
+            "dcd98b7102dd2f0e8b11d0f600bfb0c093",
+            Some(("00000001", "0a4f113b")),
+            "GET",
+            "/dir/index.html"
+        )
+    );
+}
+
+#[test_log::test]
+fn test_parse_digest_parameters() {
+    let parameters = parse_digest_parameters(
+        "username=\"Mufasa\",realm=\"a, \\\"b\\\"\" , qop=auth,  nc=00000001",
+    )
+    .unwrap();
+    assert_eq!(
+        vec![
+            ("nc", "00000001"),
+            ("qop", "auth"),
+            ("realm", "a, \"b\""),
+            ("username", "Mufasa"),
+        ],
+        parameters
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(Some(0), parse_digest_parameters("").map(|it| it.len()));
+    assert_eq!(None, parse_digest_parameters("username=\"unterminated"));
+    assert_eq!(None, parse_digest_parameters("username"));
+    assert_eq!(None, parse_digest_parameters("a=\"b\" c=d"));
+}
+
+#[test_log::test]
+fn test_parse_htdigest() {
+    let users = UserList::parse_htdigest(
+        "dogbox".to_string(),
+        &format!(
+            "# comment\nalice:dogbox:{}\n\nbob:other:{}\n",
+            PasswordHash::new("alice", "dogbox", "secret").to_hex(),
+            PasswordHash::new("bob", "other", "secret").to_hex()
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        vec!["alice"],
+        users.user_names().map(|it| it.as_str()).collect::<Vec<_>>()
+    );
+    let mut expected = UserList::new("dogbox".to_string());
+    expected.add_user("alice".to_string(), "secret").unwrap();
+    assert_eq!(expected, users);
+
+    assert_eq!(
+        Err(UserListError::InvalidLine { line_number: 2 }),
+        UserList::parse_htdigest("dogbox".to_string(), "\nalice:dogbox\n")
+    );
+    assert_eq!(
+        Err(UserListError::InvalidLine { line_number: 1 }),
+        UserList::parse_htdigest("dogbox".to_string(), "alice:dogbox:1234")
+    );
+    let hash = PasswordHash::new("alice", "dogbox", "secret").to_hex();
+    assert_eq!(
+        Err(UserListError::DuplicateUser {
+            name: "alice".to_string()
+        }),
+        UserList::parse_htdigest(
+            "dogbox".to_string(),
+            &format!("alice:dogbox:{hash}\nalice:dogbox:{hash}")
+        )
+    );
+}
+
+#[test_log::test]
+fn test_basic_authentication() {
+    let (_now, clock) = test_clock();
+    let authenticator = test_authenticator(clock);
+    let method = hyper::Method::GET;
+    let uri = hyper::Uri::from_static("/a.txt");
+    assert_eq!(
+        Ok("alice".to_string()),
+        authenticator.authenticate(&method, &uri, &basic_authorization("alice", "secret"))
+    );
+    assert_eq!(
+        Err(AuthenticationError::WrongCredentials),
+        authenticator.authenticate(&method, &uri, &basic_authorization("alice", "wrong"))
+    );
+    assert_eq!(
+        Err(AuthenticationError::WrongCredentials),
+        authenticator.authenticate(&method, &uri, &basic_authorization("bob", "secret"))
+    );
+    assert_eq!(
+        Err(AuthenticationError::MissingCredentials),
+        authenticator.authenticate(&method, &uri, &hyper::HeaderMap::new())
+    );
+    assert_eq!(
+        Err(AuthenticationError::MalformedCredentials),
+        authenticator.authenticate(&method, &uri, &headers_with_authorization("Basic ???"))
+    );
+    assert_eq!(
+        Err(AuthenticationError::UnsupportedScheme),
+        authenticator.authenticate(&method, &uri, &headers_with_authorization("Bearer abc"))
+    );
+}
+
+#[test_log::test]
+fn test_digest_authentication() {
+    let (now, clock) = test_clock();
+    let authenticator = test_authenticator(clock);
+    let method = hyper::Method::PUT;
+    let uri = hyper::Uri::from_static("/dir/a.txt");
+    let nonce = nonce_from_challenge(&authenticator);
+    assert_eq!(
+        Ok("alice".to_string()),
+        authenticator.authenticate(
+            &method,
+            &uri,
+            &digest_authorization("alice", "secret", &nonce, "PUT", "/dir/a.txt")
+        )
+    );
+    assert_eq!(
+        Err(AuthenticationError::WrongCredentials),
+        authenticator.authenticate(
+            &method,
+            &uri,
+            &digest_authorization("alice", "wrong", &nonce, "PUT", "/dir/a.txt")
+        )
+    );
+    // a response for a different request
+    assert_eq!(
+        Err(AuthenticationError::WrongCredentials),
+        authenticator.authenticate(
+            &method,
+            &uri,
+            &digest_authorization("alice", "secret", &nonce, "PUT", "/dir/b.txt")
+        )
+    );
+    assert_eq!(
+        Err(AuthenticationError::WrongCredentials),
+        authenticator.authenticate(
+            &method,
+            &uri,
+            &digest_authorization("alice", "secret", &nonce, "GET", "/dir/a.txt")
+        )
+    );
+    // a nonce that the server didn't create
+    let forged_nonce = format!("{}{}", &nonce[..16], "0".repeat(nonce.len() - 16));
+    assert_eq!(
+        Err(AuthenticationError::WrongCredentials),
+        authenticator.authenticate(
+            &method,
+            &uri,
+            &digest_authorization("alice", "secret", &forged_nonce, "PUT", "/dir/a.txt")
+        )
+    );
+    // another instance uses a different key for its nonces
+    let (_other_now, other_clock) = test_clock();
+    assert_eq!(
+        Err(AuthenticationError::WrongCredentials),
+        test_authenticator(other_clock).authenticate(
+            &method,
+            &uri,
+            &digest_authorization("alice", "secret", &nonce, "PUT", "/dir/a.txt")
+        )
+    );
+
+    *now.lock().unwrap() += Authenticator::NONCE_LIFETIME + std::time::Duration::from_secs(1);
+    assert_eq!(
+        Err(AuthenticationError::StaleNonce),
+        authenticator.authenticate(
+            &method,
+            &uri,
+            &digest_authorization("alice", "secret", &nonce, "PUT", "/dir/a.txt")
+        )
+    );
+    let new_nonce = nonce_from_challenge(&authenticator);
+    assert_ne!(nonce, new_nonce);
+    assert_eq!(
+        Ok("alice".to_string()),
+        authenticator.authenticate(
+            &method,
+            &uri,
+            &digest_authorization("alice", "secret", &new_nonce, "PUT", "/dir/a.txt")
+        )
+    );
+}
+
+#[test_log::test]
+fn test_digest_replay() {
+    let (now, clock) = test_clock();
+    let authenticator = test_authenticator(clock);
+    let method = hyper::Method::GET;
+    let uri = hyper::Uri::from_static("/a.txt");
+    let nonce = nonce_from_challenge(&authenticator);
+    let authenticate = |nonce_count: &str| {
+        authenticator.authenticate(
+            &method,
+            &uri,
+            &digest_authorization_with_count(
+                "alice",
+                "secret",
+                &nonce,
+                nonce_count,
+                "GET",
+                "/a.txt",
+            ),
+        )
+    };
+    assert_eq!(Ok("alice".to_string()), authenticate("00000001"));
+    // a captured request can't be sent again
+    assert_eq!(
+        Err(AuthenticationError::StaleNonce),
+        authenticate("00000001")
+    );
+    assert_eq!(Ok("alice".to_string()), authenticate("00000003"));
+    assert_eq!(
+        Err(AuthenticationError::StaleNonce),
+        authenticate("00000002")
+    );
+    assert_eq!(
+        Err(AuthenticationError::MalformedCredentials),
+        authenticate("not hex")
+    );
+
+    // without a client nonce, the nonce can only be used once
+    let response = digest_response(
+        &PasswordHash::new("alice", "dogbox", "secret"),
+        &nonce,
+        None,
+        "GET",
+        "/a.txt",
+    );
+    let without_client_nonce = headers_with_authorization(&format!(
+        "Digest username=\"alice\", realm=\"dogbox\", nonce=\"{nonce}\", uri=\"/a.txt\", \
+        response=\"{response}\""
+    ));
+    assert_eq!(
+        Ok("alice".to_string()),
+        authenticator.authenticate(&method, &uri, &without_client_nonce)
+    );
+    assert_eq!(
+        Err(AuthenticationError::StaleNonce),
+        authenticator.authenticate(&method, &uri, &without_client_nonce)
+    );
+
+    // the counts of expired nonces are forgotten, but the nonces aren't accepted anymore either
+    *now.lock().unwrap() += Authenticator::NONCE_LIFETIME + std::time::Duration::from_secs(1);
+    assert_eq!(
+        Err(AuthenticationError::StaleNonce),
+        authenticate("00000004")
+    );
+}
+
+#[test_log::test]
+fn test_challenges() {
+    let (_now, clock) = test_clock();
+    let authenticator = test_authenticator(clock);
+    let challenges = authenticator.challenges(false);
+    assert_eq!(2, challenges.len());
+    assert!(challenges[0].starts_with("Digest realm=\"dogbox\", qop=\"auth\""));
+    assert!(!challenges[0].contains("stale"));
+    assert_eq!("Basic realm=\"dogbox\", charset=\"UTF-8\"", challenges[1]);
+    assert!(authenticator.challenges(true)[0].ends_with(", stale=true"));
+}
+
+#[test_log::test]
+fn test_redact_credentials() {
+    let mut headers = hyper::HeaderMap::new();
+    headers.insert(
+        hyper::header::AUTHORIZATION,
+        hyper::header::HeaderValue::from_static("Basic YWxpY2U6c2VjcmV0"),
+    );
+    headers.insert(
+        hyper::header::PROXY_AUTHORIZATION,
+        hyper::header::HeaderValue::from_static("Basic YWxpY2U6c2VjcmV0"),
+    );
+    headers.append(
+        hyper::header::COOKIE,
+        hyper::header::HeaderValue::from_static("a=1"),
+    );
+    headers.append(
+        hyper::header::COOKIE,
+        hyper::header::HeaderValue::from_static("b=2"),
+    );
+    headers.insert(
+        hyper::header::CONTENT_LENGTH,
+        hyper::header::HeaderValue::from_static("12"),
+    );
+    let redacted = redact_credentials(&headers);
+    assert_eq!(5, redacted.len());
+    assert_eq!("<redacted>", redacted[hyper::header::AUTHORIZATION]);
+    assert_eq!("<redacted>", redacted[hyper::header::PROXY_AUTHORIZATION]);
+    assert_eq!(
+        vec!["<redacted>", "<redacted>"],
+        redacted
+            .get_all(hyper::header::COOKIE)
+            .iter()
+            .collect::<Vec<_>>()
+    );
+    assert_eq!("12", redacted[hyper::header::CONTENT_LENGTH]);
+    let logged = format!("{redacted:?}");
+    assert!(!logged.contains("YWxpY2U6c2VjcmV0"));
+    assert!(!logged.contains("a=1"));
+}
@@ -0,0 +1,71 @@
+use crate::{file_system::DogBoxFileSystem, lock_system::DogBoxLockSystem};
+use dav_server::{davpath::DavPath, fs::DavFileSystem, ls::DavLockSystem};
+use hyper::{Request, Response};
+use tracing::{debug, info};
+
+/// dav-server answers a COPY of a directory by creating the destination entry by entry. The tree editor can copy a
+/// whole directory at once without reading or storing any content, see [dogbox_tree_editor::TreeEditor::reflink].
+///
+/// Returns [None] for every request that this does not handle, so that dav-server can answer it as usual. That
+/// includes copies with `Depth: 0`, onto existing destinations, with `If` conditions or onto locked destinations.
+pub async fn copy_directory<Body>(
+    request: &Request<Body>,
+    file_system: &DogBoxFileSystem,
+    lock_system: &DogBoxLockSystem,
+    principal: Option<&str>,
+) -> Option<Response<dav_server::body::Body>> {
+    if request.method().as_str() != "COPY" || !is_depth_infinity(request) {
+        return None;
+    }
+    if request.headers().contains_key("If") {
+        return None;
+    }
+    let source = DavPath::new(request.uri().path()).ok()?;
+    let destination = parse_destination(request)?;
+    if !file_system.metadata(&source).await.ok()?.is_dir() {
+        return None;
+    }
+    if file_system.metadata(&destination).await.is_ok() {
+        return None;
+    }
+    if lock_system
+        .check(&destination, principal, false, true, Vec::new())
+        .await
+        .is_err()
+    {
+        return None;
+    }
+    match file_system.reflink(&source, &destination).await {
+        Ok(_) => {
+            info!("Copied directory {} to {}", source, destination);
+            Some(
+                Response::builder()
+                    .status(hyper::StatusCode::CREATED)
+                    .body(dav_server::body::Body::empty())
+                    .unwrap(),
+            )
+        }
+        Err(error) => {
+            debug!(
+                "Could not copy directory {} to {} at once: {:?}",
+                source, destination, error
+            );
+            None
+        }
+    }
+}
+
+/// A missing Depth header means infinity for COPY.
+pub fn is_depth_infinity<Body>(request: &Request<Body>) -> bool {
+    match request.headers().get("Depth") {
+        Some(depth) => depth.as_bytes().eq_ignore_ascii_case(b"infinity"),
+        None => true,
+    }
+}
+
+/// The Destination header is usually an absolute URL, but a path alone is accepted as well.
+pub fn parse_destination<Body>(request: &Request<Body>) -> Option<DavPath> {
+    let destination = request.headers().get("Destination")?.to_str().ok()?;
+    let uri = hyper::Uri::try_from(destination).ok()?;
+    DavPath::new(uri.path()).ok()
+}
@@ -0,0 +1,33 @@
+use crate::directory_copy::{is_depth_infinity, parse_destination};
+use hyper::Request;
+use pretty_assertions::assert_eq;
+
+fn copy_request(headers: &[(&str, &str)]) -> Request<()> {
+    let mut builder = Request::builder().method("COPY").uri("/source/");
+    for (name, value) in headers {
+        builder = builder.header(*name, *value);
+    }
+    builder.body(()).unwrap()
+}
+
+#[test_log::test]
+fn test_is_depth_infinity() {
+    assert!(is_depth_infinity(&copy_request(&[])));
+    assert!(is_depth_infinity(&copy_request(&[("Depth", "infinity")])));
+    assert!(is_depth_infinity(&copy_request(&[("Depth", "Infinity")])));
+    assert!(!is_depth_infinity(&copy_request(&[("Depth", "0")])));
+}
+
+#[test_log::test]
+fn test_parse_destination() {
+    assert_eq!(None, parse_destination(&copy_request(&[])));
+    let parse = |destination: &str| {
+        parse_destination(&copy_request(&[("Destination", destination)]))
+            .map(|path| path.as_pathbuf())
+    };
+    assert_eq!(
+        Some(std::path::PathBuf::from("/a b/c")),
+        parse("http://localhost:1234/a%20b/c")
+    );
+    assert_eq!(Some(std::path::PathBuf::from("/copy")), parse("/copy"));
+}
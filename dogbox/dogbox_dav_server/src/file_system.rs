@@ -1,29 +1,488 @@
+use crate::quota::Quota;
 use async_stream::stream;
 use dav_server::fs::FsError;
+use dogbox_tree::serialization::DeadPropertyName;
+use dogbox_tree::serialization::DeadPropertyValue;
 use dogbox_tree::serialization::DirectoryEntryKind;
+use dogbox_tree::serialization::FileName;
+use dogbox_tree_editor::DeadPropertyChange;
 use dogbox_tree_editor::DirectoryEntryMetaData;
+use dogbox_tree_editor::MutableDirectoryEntry;
 use dogbox_tree_editor::NormalizedPath;
 use dogbox_tree_editor::OpenFile;
 use dogbox_tree_editor::OpenFileReadPermission;
 use dogbox_tree_editor::OpenFileWritePermission;
+use dogbox_tree_editor::PathSplitLeftResult;
+use dogbox_tree_editor::PathSplitRightResult;
+use dogbox_tree_editor::SearchQuery;
+use dogbox_tree_editor::SearchResult;
 use futures::stream::StreamExt;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
 use tracing::warn;
 
+/// A tree with its own root in the storage.
 #[derive(Clone)]
-pub struct DogBoxFileSystem {
+struct Volume {
     editor: Arc<dogbox_tree_editor::TreeEditor>,
+    quota: Arc<Quota>,
+}
+
+impl Volume {
+    fn is_same(&self, other: &Volume) -> bool {
+        Arc::ptr_eq(&self.editor, &other.editor)
+    }
+}
+
+/// The earlier versions of `file.txt` appear as the read-only files `file.txt@versions/1` (the oldest) to
+/// `file.txt@versions/n`. Copying one of them onto `file.txt` restores it. A real entry named `file.txt@versions` takes
+/// precedence.
+pub const VERSIONS_SUFFIX: &str = "@versions";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VersionPath {
+    List,
+    Version(usize),
+}
+
+/// Recognizes the virtual paths for file versions. The returned path is the one of the file itself.
+fn parse_version_path(path: NormalizedPath) -> (NormalizedPath, Option<VersionPath>) {
+    let strip_suffix = |name: &FileName| {
+        name.as_str()
+            .strip_suffix(VERSIONS_SUFFIX)
+            .and_then(|file_name| FileName::try_from(file_name.to_string()).ok())
+    };
+    match path.clone().split_right() {
+        PathSplitRightResult::Root => {}
+        PathSplitRightResult::Entry(parent, leaf) => {
+            if let Some(file_name) = strip_suffix(&leaf) {
+                return (parent.join(file_name), Some(VersionPath::List));
+            }
+            if let Ok(number) = leaf.as_str().parse::<usize>() {
+                if let PathSplitRightResult::Entry(directory, parent_name) = parent.split_right() {
+                    if let Some(file_name) = strip_suffix(&parent_name) {
+                        // Version numbers start at 1. Anything else in the virtual directory doesn't exist.
+                        return (
+                            directory.join(file_name),
+                            Some(VersionPath::Version(number.wrapping_sub(1))),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    (path, None)
+}
+
+/// The files found by a search appear below `/.search/<query>` in the same directories as in the tree. Searching only
+/// covers the home volume. See [SearchQuery] for the syntax. Clients cannot create an entry with this name in the root,
+/// because nothing below it can be changed.
+pub const SEARCH_DIRECTORY_NAME: &str = ".search";
+
+enum SearchPath {
+    /// The directory that contains the queries. It looks empty.
+    Queries,
+    Results {
+        query: FileName,
+        path: NormalizedPath,
+    },
+}
+
+fn parse_search_path(path: &NormalizedPath) -> Option<SearchPath> {
+    let (query, path) = match path.clone().split_left() {
+        PathSplitLeftResult::Root => return None,
+        PathSplitLeftResult::Leaf(name) => {
+            return (name.as_str() == SEARCH_DIRECTORY_NAME).then_some(SearchPath::Queries)
+        }
+        PathSplitLeftResult::Directory(name, rest) => {
+            if name.as_str() != SEARCH_DIRECTORY_NAME {
+                return None;
+            }
+            match rest.split_left() {
+                PathSplitLeftResult::Root => return Some(SearchPath::Queries),
+                PathSplitLeftResult::Leaf(query) => (query, NormalizedPath::root()),
+                PathSplitLeftResult::Directory(query, path) => (query, path),
+            }
+        }
+    };
+    Some(SearchPath::Results { query, path })
+}
+
+/// Where a path of the file system ends up.
+struct ResolvedPath<'a> {
+    volume: &'a Volume,
+    path: NormalizedPath,
+    is_mount_point: bool,
+    version: Option<VersionPath>,
+}
+
+/// The results of the searches of one request by query. A PROPFIND of `/.search/<query>` looks at the results once
+/// for the query itself and once more for every directory it lists.
+type SearchResultsCache = Arc<tokio::sync::Mutex<BTreeMap<FileName, Arc<Vec<SearchResult>>>>>;
+
+#[derive(Clone)]
+pub struct DogBoxFileSystem {
+    home: Volume,
+    // Mounted volumes appear as directories directly below the root of the home volume.
+    mounts: BTreeMap<FileName, Volume>,
+    search_results: SearchResultsCache,
 }
 
 impl DogBoxFileSystem {
-    pub fn new(editor: dogbox_tree_editor::TreeEditor) -> DogBoxFileSystem {
+    pub fn new(editor: dogbox_tree_editor::TreeEditor, quota: Arc<Quota>) -> DogBoxFileSystem {
         DogBoxFileSystem {
-            editor: Arc::new(editor),
+            home: Volume {
+                editor: Arc::new(editor),
+                quota,
+            },
+            mounts: BTreeMap::new(),
+            search_results: SearchResultsCache::default(),
         }
     }
+
+    /// A file system for a single request, which searches for each query at most once.
+    pub fn for_request(&self) -> DogBoxFileSystem {
+        DogBoxFileSystem {
+            home: self.home.clone(),
+            mounts: self.mounts.clone(),
+            search_results: SearchResultsCache::default(),
+        }
+    }
+
+    /// Makes another tree accessible as the directory `name`. It hides an entry of the same name in the home tree.
+    pub fn with_mount(
+        mut self,
+        name: FileName,
+        editor: dogbox_tree_editor::TreeEditor,
+        quota: Arc<Quota>,
+    ) -> DogBoxFileSystem {
+        self.mounts.insert(
+            name,
+            Volume {
+                editor: Arc::new(editor),
+                quota,
+            },
+        );
+        self
+    }
+
+    /// Makes everything written to `path` so far durable. This is what a client gets when it unlocks a resource.
+    pub async fn commit(
+        &self,
+        path: &dav_server::davpath::DavPath,
+    ) -> dav_server::fs::FsResult<astraea::tree::BlobDigest> {
+        let resolved = self.resolve(normalize_path(path)?).await?;
+        if resolved.version.is_some() {
+            return Err(FsError::Forbidden);
+        }
+        resolved
+            .volume
+            .editor
+            .commit(resolved.path)
+            .await
+            .map_err(handle_error)
+    }
+
+    /// Copies a file or a directory in constant time. See [dogbox_tree_editor::TreeEditor::reflink].
+    pub async fn reflink(
+        &self,
+        from: &dav_server::davpath::DavPath,
+        to: &dav_server::davpath::DavPath,
+    ) -> dav_server::fs::FsResult<()> {
+        let (from_resolved, to_path) = self.resolve_pair_for_change(from, to).await?;
+        from_resolved
+            .volume
+            .editor
+            .reflink(from_resolved.path, to_path)
+            .await
+            .map_err(handle_error)
+    }
+
+    /// Finds the volume of `path`, which is the path of the file itself if `version` is set.
+    fn resolve_volume(
+        &self,
+        path: NormalizedPath,
+        version: Option<VersionPath>,
+    ) -> dav_server::fs::FsResult<ResolvedPath<'_>> {
+        if !self.mounts.is_empty() {
+            match path.clone().split_left() {
+                PathSplitLeftResult::Root => {}
+                PathSplitLeftResult::Leaf(name) => {
+                    if let Some(volume) = self.mounts.get(&name) {
+                        if version.is_some() {
+                            info!("Mount point {} has no versions", name);
+                            return Err(FsError::NotFound);
+                        }
+                        return Ok(ResolvedPath {
+                            volume,
+                            path: NormalizedPath::root(),
+                            is_mount_point: true,
+                            version,
+                        });
+                    }
+                }
+                PathSplitLeftResult::Directory(name, rest) => {
+                    if let Some(volume) = self.mounts.get(&name) {
+                        return Ok(ResolvedPath {
+                            volume,
+                            path: rest,
+                            is_mount_point: false,
+                            version,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(ResolvedPath {
+            volume: &self.home,
+            path,
+            is_mount_point: false,
+            version,
+        })
+    }
+
+    async fn resolve(&self, path: NormalizedPath) -> dav_server::fs::FsResult<ResolvedPath<'_>> {
+        let (file_path, version) = parse_version_path(path.clone());
+        let version = match version {
+            Some(version) => version,
+            None => return self.resolve_volume(path, None),
+        };
+        // Real entries with names like `file.txt@versions` hide the versions of `file.txt`.
+        let versions_directory = match (version, path.clone().split_right()) {
+            (VersionPath::Version(_), PathSplitRightResult::Entry(parent, _leaf)) => parent,
+            _ => path.clone(),
+        };
+        if self.exists(versions_directory).await {
+            return self.resolve_volume(path, None);
+        }
+        let resolved = self.resolve_volume(file_path, Some(version))?;
+        match resolved
+            .volume
+            .editor
+            .get_meta_data(resolved.path.clone())
+            .await
+        {
+            Ok(meta_data) if matches!(meta_data.kind, DirectoryEntryKind::File(_)) => Ok(resolved),
+            // Only files have versions.
+            _ => self.resolve_volume(path, None),
+        }
+    }
+
+    async fn exists(&self, path: NormalizedPath) -> bool {
+        match self.resolve_volume(path, None) {
+            Ok(resolved) => {
+                resolved.is_mount_point
+                    || resolved
+                        .volume
+                        .editor
+                        .get_meta_data(resolved.path)
+                        .await
+                        .is_ok()
+            }
+            Err(_) => false,
+        }
+    }
+
+    async fn resolve_for_change(
+        &self,
+        path: &dav_server::davpath::DavPath,
+    ) -> dav_server::fs::FsResult<ResolvedPath<'_>> {
+        let normalized = normalize_path(path)?;
+        if parse_search_path(&normalized).is_some() {
+            info!("Search results like {} cannot be changed", path);
+            return Err(FsError::Forbidden);
+        }
+        let resolved = self.resolve(normalized).await?;
+        if resolved.is_mount_point {
+            info!("Mount point {} cannot be changed", path);
+            return Err(FsError::Forbidden);
+        }
+        if resolved.version.is_some() {
+            info!("Versions of a file like {} cannot be changed", path);
+            return Err(FsError::Forbidden);
+        }
+        Ok(resolved)
+    }
+
+    /// Resolves both paths of a copy or rename. Moving data between volumes is not supported.
+    async fn resolve_pair_for_change(
+        &self,
+        from: &dav_server::davpath::DavPath,
+        to: &dav_server::davpath::DavPath,
+    ) -> dav_server::fs::FsResult<(ResolvedPath<'_>, NormalizedPath)> {
+        let from_resolved = self.resolve_for_change(from).await?;
+        let to_resolved = self.resolve_for_change(to).await?;
+        if !from_resolved.volume.is_same(to_resolved.volume) {
+            info!("Cannot copy or rename {} to {} across volumes", from, to);
+            return Err(FsError::Forbidden);
+        }
+        Ok((from_resolved, to_resolved.path))
+    }
+
+    async fn mount_point_entries(&self) -> dav_server::fs::FsResult<Vec<MutableDirectoryEntry>> {
+        let mut entries = Vec::new();
+        for (name, volume) in self.mounts.iter() {
+            let meta_data = volume
+                .editor
+                .get_meta_data(NormalizedPath::root())
+                .await
+                .map_err(handle_error)?;
+            entries.push(MutableDirectoryEntry::new(
+                name.clone(),
+                DirectoryEntryKind::Directory,
+                meta_data.modified,
+            ));
+        }
+        Ok(entries)
+    }
+
+    async fn search(&self, query: &FileName) -> dav_server::fs::FsResult<Arc<Vec<SearchResult>>> {
+        let mut search_results_locked = self.search_results.lock().await;
+        if let Some(results) = search_results_locked.get(query) {
+            return Ok(results.clone());
+        }
+        let parsed = match SearchQuery::parse(query.as_str()) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                info!("Invalid search query {}: {}", query, error);
+                return Err(FsError::NotFound);
+            }
+        };
+        let results = Arc::new(
+            self.home
+                .editor
+                .search(&parsed)
+                .await
+                .map_err(handle_error)?,
+        );
+        search_results_locked.insert(query.clone(), results.clone());
+        Ok(results)
+    }
+
+    async fn search_meta_data(
+        &self,
+        search_path: SearchPath,
+    ) -> dav_server::fs::FsResult<Box<dyn dav_server::fs::DavMetaData>> {
+        let path = match search_path {
+            SearchPath::Queries => NormalizedPath::root(),
+            SearchPath::Results { query, path } => {
+                let results = self.search(&query).await?;
+                if !results.iter().any(|result| result.path.starts_with(&path)) {
+                    return Err(FsError::NotFound);
+                }
+                path
+            }
+        };
+        let meta_data = self
+            .home
+            .editor
+            .get_meta_data(path)
+            .await
+            .map_err(handle_error)?;
+        Ok(Box::new(DogBoxMetaData { entry: meta_data }))
+    }
+
+    /// Lists the results and the directories leading to them.
+    async fn search_entries(
+        &self,
+        search_path: SearchPath,
+    ) -> dav_server::fs::FsResult<Vec<MutableDirectoryEntry>> {
+        let (query, path) = match search_path {
+            SearchPath::Queries => return Ok(Vec::new()),
+            SearchPath::Results { query, path } => (query, path),
+        };
+        let results = self.search(&query).await?;
+        let mut names = Vec::new();
+        for result in results.iter() {
+            if let Some(remainder) = result.path.replace_prefix(&path, &NormalizedPath::root()) {
+                if let Some(name) = remainder.components().next() {
+                    if names.last() != Some(name) {
+                        names.push(name.clone());
+                    }
+                }
+            }
+        }
+        let mut entries = Vec::new();
+        for name in names {
+            match self
+                .home
+                .editor
+                .get_meta_data(path.clone().join(name.clone()))
+                .await
+            {
+                Ok(meta_data) => entries.push(MutableDirectoryEntry::new(
+                    name,
+                    meta_data.kind,
+                    meta_data.modified,
+                )),
+                // the index only knows the last saved state of the tree
+                Err(error) => debug!("Search result {} is gone: {}", name, error),
+            }
+        }
+        Ok(entries)
+    }
+
+    async fn version_meta_data(
+        &self,
+        resolved: ResolvedPath<'_>,
+        version: VersionPath,
+    ) -> dav_server::fs::FsResult<Box<dyn dav_server::fs::DavMetaData>> {
+        let history = resolved
+            .volume
+            .editor
+            .get_file_history(resolved.path.clone())
+            .await
+            .map_err(handle_error)?;
+        match version {
+            VersionPath::List => {
+                let file_meta_data = resolved
+                    .volume
+                    .editor
+                    .get_meta_data(resolved.path)
+                    .await
+                    .map_err(handle_error)?;
+                Ok(Box::new(DogBoxDirectoryMetaData {
+                    modified: file_meta_data.modified,
+                }))
+            }
+            VersionPath::Version(index) => match history.versions().get(index) {
+                Some(found) => Ok(Box::new(DogBoxFileMetaData {
+                    size: found.size,
+                    modified: found.saved_at,
+                })),
+                None => Err(FsError::NotFound),
+            },
+        }
+    }
+
+    /// Lists the versions as files named by their number.
+    async fn version_entries(
+        &self,
+        resolved: ResolvedPath<'_>,
+    ) -> dav_server::fs::FsResult<Vec<MutableDirectoryEntry>> {
+        let history = resolved
+            .volume
+            .editor
+            .get_file_history(resolved.path)
+            .await
+            .map_err(handle_error)?;
+        Ok(history
+            .versions()
+            .iter()
+            .enumerate()
+            .map(|(index, version)| {
+                MutableDirectoryEntry::new(
+                    FileName::try_from((index + 1).to_string())
+                        .expect("a number is a valid file name"),
+                    DirectoryEntryKind::File(version.size),
+                    version.saved_at,
+                )
+            })
+            .collect())
+    }
 }
 
 fn handle_error(err: dogbox_tree_editor::Error) -> FsError {
@@ -59,6 +518,27 @@ fn handle_error(err: dogbox_tree_editor::Error) -> FsError {
             error!("Saving failed");
             dav_server::fs::FsError::GeneralFailure
         }
+        dogbox_tree_editor::Error::NotPersistent => {
+            error!("Cannot commit because the tree is not persistent");
+            dav_server::fs::FsError::GeneralFailure
+        }
+        dogbox_tree_editor::Error::CommitFailed(message) => {
+            error!("Commit failed: {}", message);
+            dav_server::fs::FsError::GeneralFailure
+        }
+        dogbox_tree_editor::Error::Journal(message) => {
+            error!("Journal failed: {}", message);
+            dav_server::fs::FsError::GeneralFailure
+        }
+        dogbox_tree_editor::Error::ReservedName(name) => {
+            info!("Clients cannot create an entry named {}", name);
+            dav_server::fs::FsError::Forbidden
+        }
+        dogbox_tree_editor::Error::Locked(path) => {
+            // dav-server answers with 423 before it gets here unless the lock was taken in the meantime.
+            info!("{:?} is locked", path);
+            dav_server::fs::FsError::Forbidden
+        }
         dogbox_tree_editor::Error::Deserialization(deserialization_error) => {
             match deserialization_error {
                 dogbox_tree::serialization::DeserializationError::Load(error) => {
@@ -112,6 +592,46 @@ fn handle_error(err: dogbox_tree_editor::Error) -> FsError {
             error!("File or directory already exists: {}", name);
             dav_server::fs::FsError::GeneralFailure
         }
+        dogbox_tree_editor::Error::DeadPropertiesTooLarge { size_in_bytes } => {
+            warn!(
+                "Dead properties would be too large: {} bytes",
+                size_in_bytes
+            );
+            dav_server::fs::FsError::InsufficientStorage
+        }
+        dogbox_tree_editor::Error::VersionNotFound { name, index } => {
+            debug!("Version {} of {} not found", index, name);
+            dav_server::fs::FsError::NotFound
+        }
+        dogbox_tree_editor::Error::RootIsNotARegularFile => {
+            info!("The root directory cannot be used as a regular file");
+            dav_server::fs::FsError::NotFound
+        }
+    }
+}
+
+fn convert_dead_property(
+    name: DeadPropertyName,
+    value: DeadPropertyValue,
+) -> dav_server::fs::DavProp {
+    dav_server::fs::DavProp {
+        name: name.name,
+        prefix: value.prefix,
+        namespace: name.namespace,
+        xml: value.xml,
+    }
+}
+
+fn convert_dead_property_name(property: &dav_server::fs::DavProp) -> DeadPropertyName {
+    DeadPropertyName::new(property.namespace.clone(), property.name.clone())
+}
+
+fn without_content(property: &dav_server::fs::DavProp) -> dav_server::fs::DavProp {
+    dav_server::fs::DavProp {
+        name: property.name.clone(),
+        prefix: property.prefix.clone(),
+        namespace: property.namespace.clone(),
+        xml: None,
     }
 }
 
@@ -206,6 +726,23 @@ impl dav_server::fs::DavDirEntry for DogBoxDirEntry {
     }
 }
 
+/// The editor that a file was opened with. Writes go through it so that they are journaled, and a written file can be
+/// committed when it is closed (see [dogbox_tree_editor::SavePolicy::commit_on_close]).
+struct FileOrigin {
+    editor: Arc<dogbox_tree_editor::TreeEditor>,
+    path: NormalizedPath,
+    commit_on_close: bool,
+}
+
+impl std::fmt::Debug for FileOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileOrigin")
+            .field("path", &self.path)
+            .field("commit_on_close", &self.commit_on_close)
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct DogBoxOpenFile {
     opened_path: relative_path::RelativePathBuf,
@@ -213,6 +750,8 @@ pub(crate) struct DogBoxOpenFile {
     read_permission: Option<Arc<OpenFileReadPermission>>,
     write_permission: Option<Arc<OpenFileWritePermission>>,
     cursor: u64,
+    quota: Arc<Quota>,
+    origin: Option<FileOrigin>,
 }
 
 impl DogBoxOpenFile {
@@ -223,6 +762,7 @@ impl DogBoxOpenFile {
         read_permission: Option<Arc<OpenFileReadPermission>>,
         write_permission: Option<Arc<OpenFileWritePermission>>,
         cursor: u64,
+        quota: Arc<Quota>,
     ) -> Self {
         Self {
             opened_path,
@@ -230,6 +770,8 @@ impl DogBoxOpenFile {
             read_permission,
             write_permission,
             cursor,
+            quota,
+            origin: None,
         }
     }
 }
@@ -254,6 +796,15 @@ impl dav_server::fs::DavFile for DogBoxOpenFile {
     }
 
     fn write_bytes(&mut self, buf: bytes::Bytes) -> dav_server::fs::FsFuture<'_, ()> {
+        if self.write_permission.is_some() && !self.quota.try_reserve(buf.len() as u64) {
+            info!(
+                "Refusing to write {} bytes to {} because the quota of {:?} bytes would be exceeded",
+                buf.len(),
+                &self.opened_path,
+                self.quota.limit_in_bytes()
+            );
+            return Box::pin(async move { Err(FsError::InsufficientStorage) });
+        }
         let write_at = self.cursor;
         let maybe_new_cursor = self.cursor.checked_add(buf.len() as u64);
         match maybe_new_cursor {
@@ -263,10 +814,18 @@ impl dav_server::fs::DavFile for DogBoxOpenFile {
         let open_file = self.handle.clone();
         Box::pin(async move {
             match &self.write_permission {
-                Some(writeable) => match open_file.write_bytes(writeable, write_at, buf).await {
-                    Ok(result) => Ok(result),
-                    Err(error) => Err(handle_error(error)),
-                },
+                Some(writeable) => {
+                    let written = match &self.origin {
+                        Some(origin) => {
+                            origin
+                                .editor
+                                .write_file(&origin.path, &open_file, writeable, write_at, buf)
+                                .await
+                        }
+                        None => open_file.write_bytes(writeable, write_at, buf).await,
+                    };
+                    written.map_err(handle_error)
+                }
                 None => {
                     warn!("Disallowed writing to a file that has not been opened for writing.");
                     Err(FsError::Forbidden)
@@ -323,9 +882,28 @@ impl dav_server::fs::DavFile for DogBoxOpenFile {
     fn flush(&mut self) -> dav_server::fs::FsFuture<'_, ()> {
         Box::pin(async {
             match self.handle.flush().await {
-                Ok(_) => Ok(()),
+                Ok(_) => {}
                 Err(_error) => todo!(),
             }
+            // dav-server flushes once at the end of a PUT, so this is where the file is closed.
+            if let (
+                Some(
+                    origin @ FileOrigin {
+                        commit_on_close: true,
+                        ..
+                    },
+                ),
+                Some(_),
+            ) = (&self.origin, &self.write_permission)
+            {
+                let digest = origin
+                    .editor
+                    .commit(origin.path.clone())
+                    .await
+                    .map_err(handle_error)?;
+                debug!("Committed {} on close: {}", &self.opened_path, &digest);
+            }
+            Ok(())
         })
     }
 }
@@ -358,7 +936,9 @@ fn convert_path(
     }
 }
 
-fn normalize_path(path: &dav_server::davpath::DavPath) -> dav_server::fs::FsResult<NormalizedPath> {
+pub(crate) fn normalize_path(
+    path: &dav_server::davpath::DavPath,
+) -> dav_server::fs::FsResult<NormalizedPath> {
     let converted_path = convert_path(path)?;
     match NormalizedPath::try_from(converted_path) {
         Ok(success) => Ok(success),
@@ -398,8 +978,92 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
         }
         Box::pin(async move {
             let converted_path = convert_path(path)?;
-            let normalized_path = normalize_path(path)?;
-            let open_file = match self.editor.open_file(normalized_path, creation_mode).await {
+            let normalized = normalize_path(path)?;
+            if let Some(search_path) = parse_search_path(&normalized) {
+                let (query, result_path) = match search_path {
+                    SearchPath::Queries => return Err(FsError::Forbidden),
+                    SearchPath::Results { query, path } => (query, path),
+                };
+                if options.write || options.truncate || options.create_new {
+                    info!("Search results like {} are read-only", path);
+                    return Err(FsError::Forbidden);
+                }
+                if !self
+                    .search(&query)
+                    .await?
+                    .iter()
+                    .any(|result| result.path == result_path)
+                {
+                    return Err(FsError::NotFound);
+                }
+                let open_file = self
+                    .home
+                    .editor
+                    .open_file(
+                        result_path,
+                        dogbox_tree_editor::FileCreationMode::open_existing(),
+                    )
+                    .await
+                    .map_err(handle_error)?;
+                let read_permission = Some(open_file.get_read_permission());
+                return Ok(Box::new(DogBoxOpenFile {
+                    opened_path: converted_path.to_owned(),
+                    handle: open_file,
+                    cursor: 0,
+                    read_permission,
+                    write_permission: None,
+                    quota: self.home.quota.clone(),
+                    origin: None,
+                }) as Box<dyn dav_server::fs::DavFile>);
+            }
+            let resolved = self.resolve(normalized).await?;
+            match resolved.version {
+                Some(VersionPath::Version(index)) => {
+                    if options.write || options.truncate || options.create_new {
+                        info!("Versions of a file like {} are read-only", path);
+                        return Err(FsError::Forbidden);
+                    }
+                    let open_file = resolved
+                        .volume
+                        .editor
+                        .open_version(resolved.path, index)
+                        .await
+                        .map_err(handle_error)?;
+                    let read_permission = Some(open_file.get_read_permission());
+                    return Ok(Box::new(DogBoxOpenFile {
+                        opened_path: converted_path.to_owned(),
+                        handle: open_file,
+                        cursor: 0,
+                        read_permission,
+                        write_permission: None,
+                        quota: resolved.volume.quota.clone(),
+                        origin: None,
+                    }) as Box<dyn dav_server::fs::DavFile>);
+                }
+                Some(VersionPath::List) => return Err(FsError::Forbidden),
+                None => {}
+            }
+            let origin = FileOrigin {
+                editor: resolved.volume.editor.clone(),
+                path: resolved.path.clone(),
+                commit_on_close: options.write
+                    && resolved.volume.editor.save_policy().commit_on_close,
+            };
+            let opened = match options.write {
+                true => resolved
+                    .volume
+                    .editor
+                    .open_file_for_writing(resolved.path, creation_mode)
+                    .await
+                    .map(|(open_file, write_permission)| (open_file, Some(write_permission))),
+                false => resolved
+                    .volume
+                    .editor
+                    .open_file(resolved.path, creation_mode)
+                    .await
+                    .map(|open_file| (open_file, None)),
+            };
+            let (open_file, write_permission) = match opened {
                 Ok(success) => success,
                 Err(error) => {
                     info!("Could not open file {}: {}", path, error);
@@ -410,13 +1074,13 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
                 true => Some(open_file.get_read_permission()),
                 false => None,
             };
-            let write_permission = match options.write {
-                true => Some(open_file.get_write_permission()),
-                false => None,
-            };
             if options.truncate {
                 match &write_permission {
-                    Some(writeable) => open_file.truncate(writeable).await.map_err(handle_error)?,
+                    Some(writeable) => origin
+                        .editor
+                        .resize_file(&origin.path, &open_file, writeable, 0)
+                        .await
+                        .map_err(handle_error)?,
                     None => return Err(FsError::Forbidden),
                 }
             }
@@ -426,6 +1090,8 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
                 cursor: 0,
                 read_permission,
                 write_permission,
+                quota: resolved.volume.quota.clone(),
+                origin: Some(origin),
             });
             Ok(result as Box<dyn dav_server::fs::DavFile>)
         })
@@ -439,14 +1105,52 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
     {
         debug!("Read dir {}", path);
         Box::pin(async move {
-            let normalized_path = normalize_path(path)?;
-            let mut directory = match self.editor.read_directory(normalized_path).await {
+            let normalized = normalize_path(path)?;
+            if let Some(search_path) = parse_search_path(&normalized) {
+                let entries = self.search_entries(search_path).await?;
+                return Ok(
+                    Box::pin(futures::stream::iter(entries.into_iter().map(|entry| {
+                        Ok(Box::new(DogBoxDirEntry { info: entry })
+                            as Box<dyn dav_server::fs::DavDirEntry>)
+                    })))
+                        as dav_server::fs::FsStream<Box<dyn dav_server::fs::DavDirEntry>>,
+                );
+            }
+            let resolved = self.resolve(normalized).await?;
+            match resolved.version {
+                Some(VersionPath::List) => {
+                    let entries = self.version_entries(resolved).await?;
+                    return Ok(
+                        Box::pin(futures::stream::iter(entries.into_iter().map(|entry| {
+                            Ok(Box::new(DogBoxDirEntry { info: entry })
+                                as Box<dyn dav_server::fs::DavDirEntry>)
+                        })))
+                            as dav_server::fs::FsStream<Box<dyn dav_server::fs::DavDirEntry>>,
+                    );
+                }
+                Some(VersionPath::Version(_)) => return Err(FsError::NotImplemented),
+                None => {}
+            }
+            let mount_point_entries =
+                if resolved.volume.is_same(&self.home) && resolved.path == NormalizedPath::root() {
+                    self.mount_point_entries().await?
+                } else {
+                    Vec::new()
+                };
+            let mut directory = match resolved.volume.editor.read_directory(resolved.path).await {
                 Ok(success) => success,
                 Err(error) => return Err(handle_error(error)),
             };
             Ok(Box::pin(stream! {
                 while let Some(entry) = directory.next().await {
                     debug!("Directory entry {:?}", entry);
+                    if mount_point_entries.iter().any(|mount_point| mount_point.name == entry.name) {
+                        debug!("Entry {:?} is hidden by a mount point", &entry.name);
+                        continue;
+                    }
+                    yield Ok(Box::new(DogBoxDirEntry{info: entry,}) as Box<dyn dav_server::fs::DavDirEntry>);
+                }
+                for entry in mount_point_entries {
                     yield Ok(Box::new(DogBoxDirEntry{info: entry,}) as Box<dyn dav_server::fs::DavDirEntry>);
                 }
             })
@@ -461,8 +1165,15 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
         path: &'a dav_server::davpath::DavPath,
     ) -> dav_server::fs::FsFuture<'a, Box<dyn dav_server::fs::DavMetaData>> {
         Box::pin(async move {
-            let normalized_path = normalize_path(path)?;
-            match self.editor.get_meta_data(normalized_path).await {
+            let normalized = normalize_path(path)?;
+            if let Some(search_path) = parse_search_path(&normalized) {
+                return self.search_meta_data(search_path).await;
+            }
+            let resolved = self.resolve(normalized).await?;
+            if let Some(version) = resolved.version {
+                return self.version_meta_data(resolved, version).await;
+            }
+            match resolved.volume.editor.get_meta_data(resolved.path).await {
                 Ok(success) => {
                     debug!("Metadata {}: {:?}", path, &success);
                     Ok(Box::new(DogBoxMetaData { entry: success })
@@ -486,8 +1197,8 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
     ) -> dav_server::fs::FsFuture<'a, ()> {
         info!("Create directory {}", path);
         Box::pin(async move {
-            let normalized_path = normalize_path(path)?;
-            match self.editor.create_directory(normalized_path).await {
+            let resolved = self.resolve_for_change(path).await?;
+            match resolved.volume.editor.create_directory(resolved.path).await {
                 Ok(success) => Ok(success),
                 Err(error) => Err(handle_error(error)),
             }
@@ -500,8 +1211,8 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
     ) -> dav_server::fs::FsFuture<'a, ()> {
         info!("Removing directory {}", path);
         Box::pin(async move {
-            let normalized_path = normalize_path(path)?;
-            match self.editor.remove(normalized_path).await {
+            let resolved = self.resolve_for_change(path).await?;
+            match resolved.volume.editor.remove(resolved.path).await {
                 Ok(_) => Ok(()),
                 Err(error) => Err(handle_error(error)),
             }
@@ -514,8 +1225,8 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
     ) -> dav_server::fs::FsFuture<'a, ()> {
         info!("Removing file {}", path);
         Box::pin(async move {
-            let normalized_path = normalize_path(path)?;
-            match self.editor.remove(normalized_path).await {
+            let resolved = self.resolve_for_change(path).await?;
+            match resolved.volume.editor.remove(resolved.path).await {
                 Ok(_) => Ok(()),
                 Err(error) => Err(handle_error(error)),
             }
@@ -529,11 +1240,11 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
     ) -> dav_server::fs::FsFuture<'a, ()> {
         debug!("Rename {} to {}", from, to);
         Box::pin(async move {
-            let from_normalized_path = normalize_path(from)?;
-            let to_normalized_path = normalize_path(to)?;
-            match self
+            let (from_resolved, to_path) = self.resolve_pair_for_change(from, to).await?;
+            match from_resolved
+                .volume
                 .editor
-                .rename(from_normalized_path, to_normalized_path)
+                .rename(from_resolved.path, to_path)
                 .await
             {
                 Ok(_) => Ok(()),
@@ -550,11 +1261,31 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
     ) -> dav_server::fs::FsFuture<'a, ()> {
         info!("Copy {} to {}", from, to);
         Box::pin(async move {
-            let from_normalized_path = normalize_path(from)?;
-            let to_normalized_path = normalize_path(to)?;
-            match self
+            let version_resolved = self.resolve(normalize_path(from)?).await?;
+            if let Some(VersionPath::Version(index)) = version_resolved.version {
+                let to_resolved = self.resolve_for_change(to).await?;
+                if !version_resolved.volume.is_same(to_resolved.volume)
+                    || version_resolved.path != to_resolved.path
+                {
+                    info!(
+                        "A version can only be copied onto its own file, not to {}",
+                        to
+                    );
+                    return Err(FsError::Forbidden);
+                }
+                info!("Restoring version {} of {}", index + 1, to);
+                return version_resolved
+                    .volume
+                    .editor
+                    .restore_version(version_resolved.path, index)
+                    .await
+                    .map_err(handle_error);
+            }
+            let (from_resolved, to_path) = self.resolve_pair_for_change(from, to).await?;
+            match from_resolved
+                .volume
                 .editor
-                .copy(from_normalized_path, to_normalized_path)
+                .copy(from_resolved.path, to_path)
                 .await
             {
                 Ok(_) => Ok(()),
@@ -572,31 +1303,128 @@ impl dav_server::fs::DavFileSystem for DogBoxFileSystem {
 
     fn patch_props<'a>(
         &'a self,
-        _path: &'a dav_server::davpath::DavPath,
-        _patch: Vec<(bool, dav_server::fs::DavProp)>,
+        path: &'a dav_server::davpath::DavPath,
+        patch: Vec<(bool, dav_server::fs::DavProp)>,
     ) -> dav_server::fs::FsFuture<'a, Vec<(hyper::StatusCode, dav_server::fs::DavProp)>> {
-        Box::pin(core::future::ready(Err(FsError::NotImplemented)))
+        debug!("Patch properties of {}", path);
+        Box::pin(async move {
+            let normalized = normalize_path(path)?;
+            if parse_search_path(&normalized).is_some() {
+                info!("Search results like {} cannot be changed", path);
+                return Err(FsError::Forbidden);
+            }
+            let resolved = self.resolve(normalized).await?;
+            if resolved.version.is_some() {
+                info!("Versions of a file like {} have no properties", path);
+                return Err(FsError::Forbidden);
+            }
+            let changes = patch
+                .iter()
+                .map(|(is_set, property)| {
+                    let name = convert_dead_property_name(property);
+                    if *is_set {
+                        DeadPropertyChange::Set(
+                            name,
+                            DeadPropertyValue::new(property.prefix.clone(), property.xml.clone()),
+                        )
+                    } else {
+                        DeadPropertyChange::Remove(name)
+                    }
+                })
+                .collect();
+            // PROPPATCH is atomic, so every property gets the same status.
+            let status = match resolved
+                .volume
+                .editor
+                .patch_dead_properties(resolved.path, changes)
+                .await
+            {
+                Ok(_) => hyper::StatusCode::OK,
+                Err(dogbox_tree_editor::Error::DeadPropertiesTooLarge { size_in_bytes }) => {
+                    info!(
+                        "Rejecting properties of {} because they would be too large ({} bytes)",
+                        path, size_in_bytes
+                    );
+                    hyper::StatusCode::INSUFFICIENT_STORAGE
+                }
+                Err(error) => return Err(handle_error(error)),
+            };
+            Ok(patch
+                .iter()
+                .map(|(_is_set, property)| (status, without_content(property)))
+                .collect())
+        })
     }
 
     fn get_props<'a>(
         &'a self,
-        _path: &'a dav_server::davpath::DavPath,
-        _do_content: bool,
+        path: &'a dav_server::davpath::DavPath,
+        do_content: bool,
     ) -> dav_server::fs::FsFuture<'a, Vec<dav_server::fs::DavProp>> {
-        Box::pin(core::future::ready(Ok(vec![])))
+        Box::pin(async move {
+            let normalized = normalize_path(path)?;
+            if parse_search_path(&normalized).is_some() {
+                return Ok(Vec::new());
+            }
+            let resolved = self.resolve(normalized).await?;
+            if resolved.version.is_some() {
+                return Ok(Vec::new());
+            }
+            let properties = resolved
+                .volume
+                .editor
+                .get_dead_properties(resolved.path)
+                .await
+                .map_err(handle_error)?;
+            Ok(properties
+                .entries()
+                .iter()
+                .map(|(name, value)| {
+                    let property = convert_dead_property(name.clone(), value.clone());
+                    if do_content {
+                        property
+                    } else {
+                        without_content(&property)
+                    }
+                })
+                .collect())
+        })
     }
 
     //#[instrument(skip(self))]
     fn get_prop<'a>(
         &'a self,
-        _path: &'a dav_server::davpath::DavPath,
-        _prop: dav_server::fs::DavProp,
+        path: &'a dav_server::davpath::DavPath,
+        prop: dav_server::fs::DavProp,
     ) -> dav_server::fs::FsFuture<'a, Vec<u8>> {
-        Box::pin(core::future::ready(Err(FsError::NotImplemented)))
+        Box::pin(async move {
+            let normalized = normalize_path(path)?;
+            if parse_search_path(&normalized).is_some() {
+                return Err(FsError::NotFound);
+            }
+            let resolved = self.resolve(normalized).await?;
+            if resolved.version.is_some() {
+                return Err(FsError::NotFound);
+            }
+            let properties = resolved
+                .volume
+                .editor
+                .get_dead_properties(resolved.path)
+                .await
+                .map_err(handle_error)?;
+            match properties.get(&convert_dead_property_name(&prop)) {
+                Some(value) => value.xml.clone().ok_or(FsError::NotFound),
+                None => Err(FsError::NotFound),
+            }
+        })
     }
 
     //#[instrument(skip(self))]
     fn get_quota(&self) -> dav_server::fs::FsFuture<'_, (u64, Option<u64>)> {
-        Box::pin(core::future::ready(Err(FsError::NotImplemented)))
+        // dav_server expects the total amount of space and calculates the available space itself.
+        Box::pin(core::future::ready(Ok((
+            self.home.quota.used_in_bytes(),
+            self.home.quota.limit_in_bytes(),
+        ))))
     }
 }
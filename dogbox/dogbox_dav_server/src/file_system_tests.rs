@@ -1,7 +1,16 @@
-use crate::file_system::{DogBoxFileSystem, DogBoxOpenFile};
+use crate::{
+    file_system::{DogBoxFileSystem, DogBoxOpenFile},
+    quota::Quota,
+};
 use astraea::{storage::InMemoryTreeStorage, tree::BlobDigest};
-use dav_server::{fakels::FakeLs, fs::DavFile, DavHandler};
-use dogbox_tree_editor::{OpenDirectory, OpenFile};
+use dav_server::{
+    davpath::DavPath,
+    fakels::FakeLs,
+    fs::{DavFile, DavFileSystem, ReadDirMeta},
+    DavHandler,
+};
+use dogbox_tree_editor::{FileCreationMode, NormalizedPath, OpenDirectory, OpenFile};
+use futures::StreamExt;
 use hyper::{body, server::conn::http1, Request};
 use hyper_util::rt::TokioIo;
 use pretty_assertions::assert_eq;
@@ -38,6 +47,7 @@ async fn test_dav_access() {
                 ),
                 None,
             ),
+            Arc::new(Quota::new(None)),
         )))
         .locksystem(FakeLs::new())
         .build_handler();
@@ -143,6 +153,7 @@ async fn test_seek_operations() {
             None,
             None,
             0,
+            Arc::new(Quota::new(None)),
         );
         assert_eq!(0, file.seek(SeekFrom::Start(0)).await.unwrap());
         assert_eq!(1, file.seek(SeekFrom::Start(1)).await.unwrap());
@@ -196,6 +207,7 @@ async fn test_seek_and_write() {
             None,
             Some(handle.get_write_permission()),
             0,
+            Arc::new(Quota::new(None)),
         );
         file.write_bytes(bytes::Bytes::from("test")).await.unwrap();
         let new_size = 4;
@@ -246,6 +258,37 @@ async fn test_seek_and_write() {
     }
 }
 
+#[test_log::test(tokio::test)]
+async fn test_write_respects_quota() {
+    let data = Vec::new();
+    let last_known_digest = BlobDigest::hash(&data);
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let handle = Arc::new(OpenFile::new(
+        dogbox_tree_editor::OpenFileContentBuffer::from_data(data, last_known_digest, 0, 1)
+            .unwrap(),
+        storage,
+        test_clock(),
+    ));
+    let quota = Arc::new(Quota::new(Some(6)));
+    let mut file = DogBoxOpenFile::new(
+        relative_path::RelativePathBuf::from_path("test").unwrap(),
+        handle.clone(),
+        None,
+        Some(handle.get_write_permission()),
+        0,
+        quota.clone(),
+    );
+    file.write_bytes(bytes::Bytes::from("test")).await.unwrap();
+    assert_eq!(
+        Err(dav_server::fs::FsError::InsufficientStorage),
+        file.write_bytes(bytes::Bytes::from("test")).await
+    );
+    assert_eq!(4, handle.size().await);
+    // the volume's quota counts the bytes as pending
+    assert!(!quota.try_reserve(3));
+    assert!(quota.try_reserve(2));
+}
+
 #[test_log::test(tokio::test)]
 async fn test_seek_beyond_the_end() {
     let data = Vec::new();
@@ -269,6 +312,7 @@ async fn test_seek_beyond_the_end() {
         None,
         Some(handle.get_write_permission()),
         0,
+        Arc::new(Quota::new(None)),
     );
     assert_eq!(
         1_000_000,
@@ -290,12 +334,8 @@ async fn test_seek_beyond_the_end() {
                 "2323bc1a2df91f7230a225952e162f6629cf435e53404e9cdd727a2d94e4f909"
             ),
             concat!(
-                "33cb1f71a103e2bf443c27930a2fb9871b028a614de0c3acb3cc486074ef9dbd",
-                "d1b4b776727729f4708548185682be845d1b081243105fff5bd4e11bea5fed6f"
-            ),
-            concat!(
-                "708d4258f26a6d99a6cc10532bd66134f46fd537d51db057c5a083cf8994f07e",
-                "740534b6f795c49aa35513a65e3da7a5518fe163da200e24af0701088b290daa"
+                "770782ebd95a031bf92ce919b9653b09d376c1c8bda3664d1db781d255259ec2",
+                "b1303e2d5f2b6a4ced3b5f1dfcf5b5c6bf8d2c3c407c7874725581a2cd3bf827"
             ),
             concat!(
                 "053449bd3fcab54840b5d0ca72dceaa77446d6980d52a54f21ac8f6157e3f8f3",
@@ -335,6 +375,7 @@ async fn test_write_out_of_bounds() {
             None,
             Some(handle.get_write_permission()),
             0,
+            Arc::new(Quota::new(None)),
         );
 
         assert_eq!(
@@ -369,3 +410,91 @@ async fn test_write_out_of_bounds() {
         assert_eq!(expected_digests, storage.digests().await);
     }
 }
+
+async fn write_and_save(
+    editor: &dogbox_tree_editor::TreeEditor,
+    root: &OpenDirectory,
+    root_status: &mut tokio::sync::watch::Receiver<dogbox_tree_editor::OpenDirectoryStatus>,
+    name: &str,
+) {
+    let path = NormalizedPath::try_from(relative_path::RelativePath::new(name)).unwrap();
+    let opened = editor
+        .open_file(path.clone(), FileCreationMode::create())
+        .await
+        .unwrap();
+    let write_permission = opened.get_write_permission();
+    editor
+        .write_file(
+            &path,
+            &opened,
+            &write_permission,
+            0,
+            bytes::Bytes::from_static(b"content"),
+        )
+        .await
+        .unwrap();
+    drop(write_permission);
+    opened.notify_dropped_write_permission();
+    opened.flush().await.unwrap();
+    root_status
+        .wait_for(|status| !status.digest.is_digest_up_to_date)
+        .await
+        .unwrap();
+    assert!(
+        root.request_save()
+            .await
+            .unwrap()
+            .digest
+            .is_digest_up_to_date
+    );
+}
+
+async fn list_names(file_system: &DogBoxFileSystem, path: &str) -> Vec<String> {
+    let mut entries = file_system
+        .read_dir(&DavPath::new(path).unwrap(), ReadDirMeta::Data)
+        .await
+        .unwrap();
+    let mut names = Vec::new();
+    while let Some(entry) = entries.next().await {
+        names.push(String::from_utf8(entry.unwrap().name()).unwrap());
+    }
+    names
+}
+
+#[test_log::test(tokio::test)]
+async fn test_search_results_are_cached_per_request() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let root = Arc::new(
+        OpenDirectory::create_directory(
+            std::path::PathBuf::from("/"),
+            storage,
+            Arc::new(test_clock),
+            1,
+        )
+        .await
+        .unwrap(),
+    );
+    let mut root_status = root.watch().await;
+    let editor = dogbox_tree_editor::TreeEditor::new(root.clone(), None);
+    let file_system = DogBoxFileSystem::new(
+        dogbox_tree_editor::TreeEditor::new(root.clone(), None),
+        Arc::new(Quota::new(None)),
+    );
+    write_and_save(&editor, &root, &mut root_status, "hello_a.txt").await;
+
+    let request = file_system.for_request();
+    assert_eq!(
+        vec!["hello_a.txt"],
+        list_names(&request, "/.search/hello").await
+    );
+    write_and_save(&editor, &root, &mut root_status, "hello_b.txt").await;
+    // the request keeps the results it has already seen
+    assert_eq!(
+        vec!["hello_a.txt"],
+        list_names(&request, "/.search/hello").await
+    );
+    assert_eq!(
+        vec!["hello_a.txt", "hello_b.txt"],
+        list_names(&file_system.for_request(), "/.search/hello").await
+    );
+}
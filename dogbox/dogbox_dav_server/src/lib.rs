@@ -1,41 +1,189 @@
 use astraea::{
-    storage::{CollectGarbage, CommitChanges, LoadRoot, SQLiteStorage, UpdateRoot},
+    storage::{CollectGarbage, CommitChanges, LoadRoot, MeasureRoot, SQLiteStorage, UpdateRoot},
     tree::TREE_BLOB_MAX_LENGTH,
 };
-use dav_server::{fakels::FakeLs, DavHandler};
+use authentication::{redact_credentials, AuthenticationError, Authenticator};
+use dav_server::{DavConfig, DavHandler};
+use dogbox_tree::serialization::FileName;
 use dogbox_tree_editor::{
-    DigestStatus, OpenDirectory, OpenDirectoryStatus, OpenFileStats, WallClock,
+    holding_locks, DigestStatus, Journal, NamedRoot, OpenDirectory, OpenDirectoryStatus,
+    OpenFileStats, SavePolicy, TrashRetention, TreeEditor, WallClock,
 };
 use file_system::DogBoxFileSystem;
-use hyper::{body, server::conn::http1, Request};
+use hyper::{body, server::conn::http1, Request, Response};
 use hyper_util::rt::TokioIo;
+use lock_system::{submitted_lock_tokens, DogBoxLockSystem, EditorLocks, VolumeLocks};
 use pretty_assertions::assert_eq;
 use pretty_assertions::assert_ne;
-use std::{convert::Infallible, net::SocketAddr, path::Path, pin::Pin, sync::Arc};
+pub use quota::Quota;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+};
 use tokio::{
-    io::AsyncWriteExt,
-    net::{TcpListener, TcpStream},
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
     runtime::Handle,
 };
 use tracing::{debug, error, info, warn};
+pub mod authentication;
+
+#[cfg(test)]
+mod authentication_tests;
+
+mod directory_copy;
+
+#[cfg(test)]
+mod directory_copy_tests;
+
 mod file_system;
 
 #[cfg(test)]
 mod file_system_tests;
 
+mod lock_system;
+
+#[cfg(test)]
+mod lock_system_tests;
+
+mod quota;
+
+#[cfg(test)]
+mod quota_tests;
+
+pub mod tls;
+
+#[cfg(test)]
+mod tls_tests;
+
 #[cfg(test)]
 mod lib_tests;
 
-async fn serve_connection(
-    stream: TcpStream,
+/// How clients have to identify themselves and whether connections are encrypted.
+#[derive(Clone, Default)]
+pub struct DavServerSecurity {
+    /// Every request has to be authenticated if this is set. Otherwise anyone can access the server.
+    pub authenticator: Option<Arc<Authenticator>>,
+    /// Connections are HTTPS if this is set. Otherwise they are plain HTTP.
+    pub tls_acceptor: Option<tokio_rustls::TlsAcceptor>,
+}
+
+impl DavServerSecurity {
+    /// Anonymous access over plain HTTP. This is only appropriate for listening on localhost.
+    pub fn none() -> Self {
+        Self::default()
+    }
+}
+
+fn unauthorized_response(
+    authenticator: &Authenticator,
+    error: AuthenticationError,
+) -> Response<dav_server::body::Body> {
+    let mut builder = Response::builder().status(hyper::StatusCode::UNAUTHORIZED);
+    for challenge in authenticator.challenges(error == AuthenticationError::StaleNonce) {
+        builder = builder.header(hyper::header::WWW_AUTHENTICATE, challenge);
+    }
+    builder
+        .body(dav_server::body::Body::from("Unauthorized"))
+        .unwrap()
+}
+
+/// A [DavHandler] together with the file and lock system behind it, so that some requests can be answered without it.
+struct DavEndpoint {
+    handler: DavHandler,
+    file_system: DogBoxFileSystem,
+    lock_system: DogBoxLockSystem,
+}
+
+impl DavEndpoint {
+    async fn handle(
+        &self,
+        request: Request<body::Incoming>,
+        principal: Option<String>,
+    ) -> Response<dav_server::body::Body> {
+        let lock_tokens = submitted_lock_tokens(request.headers());
+        holding_locks(lock_tokens, async move {
+            if let Some(response) = directory_copy::copy_directory(
+                &request,
+                &self.file_system,
+                &self.lock_system,
+                principal.as_deref(),
+            )
+            .await
+            {
+                return response;
+            }
+            let config = DavConfig::new().filesystem(Box::new(self.file_system.for_request()));
+            let config = match principal {
+                Some(principal) => config.principal(principal),
+                None => config,
+            };
+            self.handler.handle_with(config, request).await
+        })
+        .await
+    }
+}
+
+/// The file system of a [DavHandler] is fixed, so every user gets their own handler. The handlers of the members of a
+/// shared folder share the locks in it (see [VolumeLocks]).
+enum DavHandlers {
+    Anonymous(Arc<DavEndpoint>),
+    PerUser {
+        authenticator: Arc<Authenticator>,
+        handlers: BTreeMap<String, Arc<DavEndpoint>>,
+    },
+}
+
+impl DavHandlers {
+    async fn handle(&self, request: Request<body::Incoming>) -> Response<dav_server::body::Body> {
+        match self {
+            DavHandlers::Anonymous(dav_server) => dav_server.handle(request, None).await,
+            DavHandlers::PerUser {
+                authenticator,
+                handlers,
+            } => {
+                match authenticator.authenticate(request.method(), request.uri(), request.headers())
+                {
+                    Ok(user_name) => match handlers.get(&user_name) {
+                        Some(dav_server) => dav_server.handle(request, Some(user_name)).await,
+                        None => {
+                            error!("User {} has no DAV handler", &user_name);
+                            Response::builder()
+                                .status(hyper::StatusCode::INTERNAL_SERVER_ERROR)
+                                .body(dav_server::body::Body::empty())
+                                .unwrap()
+                        }
+                    },
+                    Err(error) => unauthorized_response(authenticator, error),
+                }
+            }
+        }
+    }
+}
+
+async fn serve_connection<Stream>(
+    stream: Stream,
     remote_endpoint: &SocketAddr,
-    dav_server: Arc<DavHandler>,
-) {
+    dav_handlers: Arc<DavHandlers>,
+) where
+    Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
     let make_service = move |request: Request<body::Incoming>| {
-        debug!("Request from {}: {:?}", remote_endpoint, &request);
-        let dav_server = dav_server.clone();
+        debug!(
+            "Request from {}: {} {} {:?} {:?}",
+            remote_endpoint,
+            request.method(),
+            request.uri(),
+            request.version(),
+            &redact_credentials(request.headers())
+        );
+        let dav_handlers = dav_handlers.clone();
         async move {
-            let response = dav_server.handle(request).await;
+            let response = dav_handlers.handle(request).await;
             debug!("Response to {}: {:?}", remote_endpoint, &response.headers());
             Ok::<_, Infallible>(response)
         }
@@ -57,7 +205,8 @@ async fn serve_connection(
 
 async fn handle_tcp_connections(
     listener: TcpListener,
-    dav_server: Arc<DavHandler>,
+    dav_handlers: Arc<DavHandlers>,
+    tls_acceptor: Option<tokio_rustls::TlsAcceptor>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     loop {
         let (mut stream, remote_endpoint) = listener.accept().await?;
@@ -84,10 +233,24 @@ async fn handle_tcp_connections(
                 continue;
             }
         }
-        let dav_server = dav_server.clone();
-        tokio::task::spawn(
-            async move { serve_connection(stream, &remote_endpoint, dav_server).await },
-        );
+        let dav_handlers = dav_handlers.clone();
+        let tls_acceptor = tls_acceptor.clone();
+        tokio::task::spawn(async move {
+            match tls_acceptor {
+                Some(tls_acceptor) => match tls_acceptor.accept(stream).await {
+                    Ok(tls_stream) => {
+                        serve_connection(tls_stream, &remote_endpoint, dav_handlers).await
+                    }
+                    Err(error) => {
+                        info!(
+                            "TLS handshake with {} failed: {:?}",
+                            &remote_endpoint, &error
+                        );
+                    }
+                },
+                None => serve_connection(stream, &remote_endpoint, dav_handlers).await,
+            }
+        });
     }
 }
 
@@ -97,20 +260,6 @@ pub enum SaveStatus {
     Saving,
 }
 
-async fn save_root_regularly(root: Arc<OpenDirectory>, auto_save_interval: std::time::Duration) {
-    loop {
-        debug!("Time to check if root needs to be saved.");
-        let save_result = root.request_save().await;
-        match save_result {
-            Ok(_status) => {}
-            Err(error) => {
-                error!("request_save failed with {:?}", &error);
-            }
-        }
-        tokio::time::sleep(auto_save_interval).await;
-    }
-}
-
 async fn drop_all_read_caches_regularly(
     root: Arc<OpenDirectory>,
     drop_interval: std::time::Duration,
@@ -127,6 +276,17 @@ async fn drop_all_read_caches_regularly(
     }
 }
 
+async fn expire_trash_regularly(editor: &TreeEditor, expiry_interval: std::time::Duration) {
+    loop {
+        match editor.expire_trash().await {
+            Ok(0) => {}
+            Ok(expired_count) => info!("Deleted {} entries from the trash", expired_count),
+            Err(error) => error!("expire_trash failed with {:?}", &error),
+        }
+        tokio::time::sleep(expiry_interval).await;
+    }
+}
+
 fn log_differences_between_digest_status(old: &DigestStatus, new: &DigestStatus) {
     if old.is_digest_up_to_date != new.is_digest_up_to_date {
         info!(
@@ -188,14 +348,26 @@ fn log_differences_between_file_stats(old: &OpenFileStats, new: &OpenFileStats)
             &old.bytes_unflushed_count, &new.bytes_unflushed_count
         );
     }
+    if old.prefetch != new.prefetch {
+        info!(
+            "Root prefetch changed from {:?} to {:?} (hit rate {:?})",
+            &old.prefetch,
+            &new.prefetch,
+            new.prefetch.hit_rate()
+        );
+    }
 }
 
+/// Persists the root with `editor` whenever its digest changes, which also drops the persisted changes from its journal.
+#[allow(clippy::too_many_arguments)]
 async fn persist_root_on_change(
     root: Arc<OpenDirectory>,
-    root_name: &str,
-    blob_storage_update: &(dyn UpdateRoot + Sync),
+    editor: &TreeEditor,
     blob_storage_commit: Arc<dyn CommitChanges + Sync + Send>,
     blob_storage_collect_garbage: Arc<dyn CollectGarbage + Sync + Send>,
+    blob_storage_measure: Arc<dyn MeasureRoot + Sync + Send>,
+    measured_root_names: &[String],
+    quota: Arc<Quota>,
     save_status_sender: tokio::sync::mpsc::Sender<SaveStatus>,
 ) {
     let mut number_of_no_changes_in_a_row: u64 = 0;
@@ -226,15 +398,12 @@ async fn persist_root_on_change(
             {
                 debug!("Root status changed, but the last known digest stays the same.");
             } else {
-                match blob_storage_update
-                    .update_root(root_name, &root_status.digest.last_known_digest)
-                    .await
-                {
-                    Ok(()) => {
-                        debug!("Successfully updated root");
+                match editor.persist().await {
+                    Ok(digest) => {
+                        debug!("Successfully persisted root {}", &digest);
                     }
                     Err(err) => {
-                        error!("Failed to update root in storage: {:?}", err);
+                        error!("Failed to persist root: {:?}", err);
                     }
                 }
                 tokio::task::spawn_blocking({
@@ -242,7 +411,6 @@ async fn persist_root_on_change(
                     let blob_storage_collect_garbage = blob_storage_collect_garbage.clone();
                     move || {
                         Handle::current().block_on(async move {
-                            blob_storage_commit.commit_changes().await.expect("TODO");
                             let gc_stats = blob_storage_collect_garbage
                                 .collect_some_garbage()
                                 .await
@@ -250,11 +418,14 @@ async fn persist_root_on_change(
                             if gc_stats.trees_collected > 0 {
                                 info!("Garbage collected {} trees", gc_stats.trees_collected);
                             }
+                            // The root might not have been persisted, so nothing else commits the deletions.
+                            blob_storage_commit.commit_changes().await.expect("TODO");
                         });
                     }
                 })
                 .await
                 .unwrap();
+                update_quota_usage(&*blob_storage_measure, measured_root_names, &quota).await;
             }
             let save_status = if root_status.digest.is_digest_up_to_date {
                 assert_eq!(0, root_status.open_files.bytes_unflushed_count);
@@ -307,12 +478,247 @@ async fn persist_root_on_change(
     }
 }
 
+/// The quota is shared by all served roots, so every measurement covers all of them.
+async fn update_quota_usage(
+    blob_storage_measure: &dyn MeasureRoot,
+    root_names: &[String],
+    quota: &Quota,
+) {
+    let names: Vec<&str> = root_names.iter().map(|name| name.as_str()).collect();
+    match blob_storage_measure.measure_roots(&names).await {
+        Ok(used_in_bytes) => {
+            debug!("Roots {:?} use {} bytes", root_names, used_in_bytes);
+            quota.set_measured_usage(used_in_bytes);
+        }
+        Err(error) => {
+            error!(
+                "Could not measure the size of roots {:?}: {:?}",
+                root_names, &error
+            );
+        }
+    }
+}
+
+/// The name of the root in the storage that is served when authentication is disabled.
+pub const ANONYMOUS_ROOT_NAME: &str = "latest";
+
+/// The name of the root in the storage that holds the home directory of an authenticated user.
+pub fn user_root_name(user_name: &str) -> String {
+    format!("user/{user_name}")
+}
+
+pub fn shared_folder_root_name(folder_name: &FileName) -> String {
+    format!("shared/{folder_name}")
+}
+
+/// A folder with its own root in the storage that appears as a directory in the home directory of each member.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedFolder {
+    pub name: FileName,
+    pub members: BTreeSet<String>,
+}
+
+/// A root of the storage that the server edits and persists.
+struct ServedRoot {
+    name: String,
+    directory: Arc<OpenDirectory>,
+    quota: Arc<Quota>,
+    storage: Arc<SQLiteStorage>,
+    save_policy: SavePolicy,
+    trash_retention: Option<TrashRetention>,
+    // shared by all editors of the root
+    journal: Arc<Journal>,
+    // shared by the lock systems of all users who can access the root
+    locks: VolumeLocks,
+    // where the root appears in the directories of the users if it is a shared folder
+    mount_point: Option<FileName>,
+}
+
+impl ServedRoot {
+    fn create_tree_editor(&self) -> TreeEditor {
+        let editor = TreeEditor::new(self.directory.clone(), None)
+            .with_persistence(
+                self.save_policy.clone(),
+                Arc::new(NamedRoot::new(self.storage.clone(), self.name.clone())),
+            )
+            .with_journal(self.journal.clone())
+            .with_locks(Arc::new(EditorLocks::new(
+                self.locks.clone(),
+                self.mount_point.clone(),
+                self.directory.get_clock().clone(),
+            )));
+        match self.trash_retention {
+            Some(retention) => editor.with_trash(retention),
+            None => editor,
+        }
+    }
+}
+
+/// The journals of the roots are kept in this directory next to the database. SQLite has its own `-journal` file there.
+pub fn journal_directory(database_file_name: &Path) -> PathBuf {
+    let mut name = database_file_name.as_os_str().to_owned();
+    name.push(".journals");
+    PathBuf::from(name)
+}
+
+/// Root names contain slashes, so they are escaped to get a unique file name for the journal of each root.
+pub fn journal_file_name(root_name: &str) -> String {
+    format!(
+        "{}.journal",
+        root_name.replace('%', "%25").replace('/', "%2F")
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn load_or_create_root(
+    blob_storage_database: &Arc<SQLiteStorage>,
+    journal_directory: &Path,
+    root_name: String,
+    mount_point: Option<FileName>,
+    modified_default: std::time::SystemTime,
+    clock: WallClock,
+    quota: &Arc<Quota>,
+    save_policy: &SavePolicy,
+    trash_retention: Option<TrashRetention>,
+) -> Result<ServedRoot, Box<dyn std::error::Error + Send + Sync>> {
+    let open_file_write_buffer_in_blocks = save_policy.open_file_write_buffer_in_blocks;
+    let root_path = std::path::PathBuf::from("/");
+    let (directory, digest): (Arc<OpenDirectory>, _) = match blob_storage_database
+        .load_root(&root_name)
+        .await?
+    {
+        Some(found) => {
+            (
+                OpenDirectory::load_directory(
+                root_path,
+                blob_storage_database.clone(), &found, modified_default, clock, open_file_write_buffer_in_blocks).await.unwrap(/*TODO*/),
+                found,
+            )
+        }
+        None => {
+            info!("Creating root {}", &root_name);
+            let dir = Arc::new(
+                OpenDirectory::create_directory(root_path,blob_storage_database.clone(), clock,
+                open_file_write_buffer_in_blocks)
+                .await
+                .unwrap(/*TODO*/),
+            );
+            let status = dir.request_save().await.unwrap();
+            assert!(status.digest.is_digest_up_to_date);
+            blob_storage_database
+                .update_root(&root_name, &status.digest.last_known_digest)
+                .await?;
+            blob_storage_database.commit_changes().await.unwrap();
+            (dir, status.digest.last_known_digest)
+        }
+    };
+    let (journal, changes) = Journal::open(
+        journal_directory.join(journal_file_name(&root_name)),
+        &digest,
+    )
+    .await?;
+    let served_root = ServedRoot {
+        name: root_name,
+        directory,
+        quota: quota.clone(),
+        storage: blob_storage_database.clone(),
+        save_policy: save_policy.clone(),
+        trash_retention,
+        journal: Arc::new(journal),
+        locks: VolumeLocks::new(),
+        mount_point,
+    };
+    if !changes.is_empty() {
+        let change_count = changes.len();
+        let editor = served_root.create_tree_editor();
+        let replayed = editor.replay(changes).await;
+        info!(
+            "Replayed {} of {} changes from the journal of root {}",
+            replayed, change_count, &served_root.name
+        );
+        editor.persist().await?;
+    }
+    Ok(served_root)
+}
+
+async fn maintain_root(
+    served_root: ServedRoot,
+    served_root_names: Arc<Vec<String>>,
+    save_status_sender: tokio::sync::mpsc::Sender<SaveStatus>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let editor = served_root.create_tree_editor();
+    let ServedRoot {
+        name: _,
+        directory: root,
+        quota,
+        storage: blob_storage_database,
+        save_policy: _,
+        trash_retention: _,
+        journal: _,
+        locks: _,
+        mount_point: _,
+    } = served_root;
+    tokio::try_join!(
+        async {
+            editor.save_regularly().await;
+            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+        },
+        async {
+            expire_trash_regularly(&editor, std::time::Duration::from_secs(60 * 60)).await;
+            Ok(())
+        },
+        {
+            let root = root.clone();
+            async move {
+                drop_all_read_caches_regularly(root, std::time::Duration::from_secs(27)).await;
+                Ok(())
+            }
+        },
+        {
+            let editor = &editor;
+            async move {
+                persist_root_on_change(
+                    root,
+                    editor,
+                    blob_storage_database.clone(),
+                    blob_storage_database.clone(),
+                    blob_storage_database.clone(),
+                    &served_root_names,
+                    quota,
+                    save_status_sender,
+                )
+                .await;
+                Ok(())
+            }
+        }
+    )
+    .map(|_| ())
+}
+
+/// Serves the root [ANONYMOUS_ROOT_NAME] to everyone if `security` has no authenticator. Otherwise every user of the
+/// authenticator gets their own root (see [user_root_name]) which contains the `shared_folders` they are a member of.
+/// All roots are stored in the same database, so identical content is only stored once. When authentication is enabled
+/// for a database that only has the anonymous root, its content becomes the home directory of the first user by name.
+///
+/// All served roots share one quota of `quota_limit_in_bytes`. Content that several roots have in common counts once.
+/// Deleting over WebDAV moves entries into the trash of their root unless `trash_retention` is [None]. The trash still
+/// counts towards the quota until the entries expire or are deleted from the trash.
+///
+/// Changes are journaled in [journal_directory] until they are persisted. The journals are replayed before any request
+/// is served, so that changes which were not persisted before a crash are not lost.
+///
+/// The returned map contains every served root by name.
+#[allow(clippy::too_many_arguments)]
 pub async fn run_dav_server(
     listener: TcpListener,
     database_file_name: &Path,
     modified_default: std::time::SystemTime,
     clock: WallClock,
-    auto_save_interval: std::time::Duration,
+    save_policy: SavePolicy,
+    trash_retention: Option<TrashRetention>,
+    quota_limit_in_bytes: Option<u64>,
+    security: DavServerSecurity,
+    shared_folders: Vec<SharedFolder>,
 ) -> Result<
     (
         tokio::sync::mpsc::Receiver<SaveStatus>,
@@ -323,7 +729,7 @@ pub async fn run_dav_server(
                 >,
             >,
         >,
-        Arc<OpenDirectory>,
+        BTreeMap<String, Arc<OpenDirectory>>,
     ),
     Box<dyn std::error::Error + Send + Sync>,
 > {
@@ -355,85 +761,161 @@ pub async fn run_dav_server(
         debug!("Created SQL schema in {}", &database_file_name.display());
     }
     let blob_storage_database = Arc::new(SQLiteStorage::from(sqlite_connection)?);
-    let root_name = "latest";
-    let open_file_write_buffer_in_blocks = 200;
-    let root_path = std::path::PathBuf::from("/");
-    let root: Arc<OpenDirectory> = match blob_storage_database.load_root(root_name).await? {
-        Some(found) => {
-            OpenDirectory::load_directory(
-                root_path,
-                blob_storage_database.clone(), &found, modified_default, clock, open_file_write_buffer_in_blocks).await.unwrap(/*TODO*/)
-        }
-        None => {
-            let dir = Arc::new(
-                OpenDirectory::create_directory(root_path,blob_storage_database.clone(), clock,
-                open_file_write_buffer_in_blocks)
-                .await
-                .unwrap(/*TODO*/),
-            );
-            let status = dir.request_save().await.unwrap();
-            assert!(status.digest.is_digest_up_to_date);
-            blob_storage_database
-                .update_root(root_name, &status.digest.last_known_digest)
-                .await?;
-            blob_storage_database.commit_changes().await.unwrap();
-            dir
-        }
+    let journal_directory = journal_directory(database_file_name);
+    std::fs::create_dir_all(&journal_directory)?;
+    let quota = Arc::new(Quota::new(quota_limit_in_bytes));
+    let load_root = |root_name: String, mount_point: Option<FileName>| {
+        load_or_create_root(
+            &blob_storage_database,
+            &journal_directory,
+            root_name,
+            mount_point,
+            modified_default,
+            clock.clone(),
+            &quota,
+            &save_policy,
+            trash_retention,
+        )
     };
-    let tree_editor = dogbox_tree_editor::TreeEditor::new(root.clone(), None);
-    let dav_server = Arc::new(
-        DavHandler::builder()
-            .filesystem(Box::new(DogBoxFileSystem::new(tree_editor)))
-            .locksystem(FakeLs::new())
-            .build_handler(),
-    );
-    let (save_status_sender, save_status_receiver) = tokio::sync::mpsc::channel(6);
-    let result = {
-        let root = root.clone();
-        async move {
-            let root = root.clone();
-            let join_result = tokio::try_join!(
-                {
-                    let root = root.clone();
-                    async move {
-                        save_root_regularly(root.clone(), auto_save_interval).await;
-                        Ok(())
-                    }
-                },
+    let mut served_roots = Vec::new();
+    let dav_handlers = match &security.authenticator {
+        Some(authenticator) => {
+            for shared_folder in shared_folders.iter() {
+                if let Some(unknown) = shared_folder
+                    .members
+                    .iter()
+                    .find(|member| !authenticator.users().contains(member))
                 {
-                    let root = root.clone();
-                    async move {
-                        drop_all_read_caches_regularly(
-                            root.clone(),
-                            std::time::Duration::from_secs(27),
-                        )
-                        .await;
-                        Ok(())
-                    }
-                },
+                    return Err(Box::from(format!(
+                        "Shared folder {} has a member {} who is not a user",
+                        shared_folder.name, unknown
+                    )));
+                }
+            }
+            if let Some(first_user) = authenticator.users().user_names().next() {
+                let mut has_user_roots = false;
+                for user_name in authenticator.users().user_names() {
+                    has_user_roots |= blob_storage_database
+                        .load_root(&user_root_name(user_name))
+                        .await?
+                        .is_some();
+                }
+                if !has_user_roots
+                    && blob_storage_database
+                        .load_root(ANONYMOUS_ROOT_NAME)
+                        .await?
+                        .is_some()
                 {
-                    let root = root.clone();
-                    let blob_storage_database = blob_storage_database.clone();
-                    async move {
-                        persist_root_on_change(
-                            root,
-                            root_name,
-                            &*blob_storage_database,
-                            blob_storage_database.clone(),
-                            blob_storage_database.clone(),
-                            save_status_sender,
-                        )
-                        .await;
-                        Ok(())
+                    // Loading replays the journal of the anonymous root, so that no change is lost.
+                    drop(load_root(ANONYMOUS_ROOT_NAME.to_string(), None).await?);
+                    let digest = blob_storage_database
+                        .load_root(ANONYMOUS_ROOT_NAME)
+                        .await?
+                        .expect("the anonymous root was just loaded");
+                    blob_storage_database
+                        .update_root(&user_root_name(first_user), &digest)
+                        .await?;
+                    blob_storage_database.commit_changes().await?;
+                    warn!(
+                        "Authentication is enabled, so the anonymous root {} is not served anymore. Its content {} \
+                         became the home directory of user {}.",
+                        ANONYMOUS_ROOT_NAME, &digest, first_user
+                    );
+                }
+            }
+            let mut shared_roots = Vec::new();
+            for shared_folder in shared_folders.iter() {
+                shared_roots.push(
+                    load_root(
+                        shared_folder_root_name(&shared_folder.name),
+                        Some(shared_folder.name.clone()),
+                    )
+                    .await?,
+                );
+            }
+            let mut handlers = BTreeMap::new();
+            for user_name in authenticator.users().user_names() {
+                let home = load_root(user_root_name(user_name), None).await?;
+                let mut file_system =
+                    DogBoxFileSystem::new(home.create_tree_editor(), home.quota.clone());
+                let mut lock_system = DogBoxLockSystem::new(home.directory.get_clock().clone())
+                    .with_home(home.locks.clone());
+                for (shared_folder, shared_root) in shared_folders.iter().zip(shared_roots.iter()) {
+                    if shared_folder.members.contains(user_name) {
+                        file_system = file_system.with_mount(
+                            shared_folder.name.clone(),
+                            shared_root.create_tree_editor(),
+                            shared_root.quota.clone(),
+                        );
+                        lock_system = lock_system
+                            .with_mount(shared_folder.name.clone(), shared_root.locks.clone());
                     }
-                },
-                async move {
-                    handle_tcp_connections(listener, dav_server).await.unwrap();
-                    Ok(())
                 }
-            );
-            join_result.map(|_| ())
+                handlers.insert(
+                    user_name.clone(),
+                    create_dav_endpoint(file_system, lock_system),
+                );
+                served_roots.push(home);
+            }
+            served_roots.extend(shared_roots);
+            DavHandlers::PerUser {
+                authenticator: authenticator.clone(),
+                handlers,
+            }
+        }
+        None => {
+            if !shared_folders.is_empty() {
+                warn!(
+                    "Shared folders are ignored because there are no users without authentication"
+                );
+            }
+            let root = load_root(ANONYMOUS_ROOT_NAME.to_string(), None).await?;
+            let file_system = DogBoxFileSystem::new(root.create_tree_editor(), root.quota.clone());
+            let lock_system = DogBoxLockSystem::new(root.directory.get_clock().clone())
+                .with_home(root.locks.clone());
+            let handler = create_dav_endpoint(file_system, lock_system);
+            served_roots.push(root);
+            DavHandlers::Anonymous(handler)
         }
     };
-    Ok((save_status_receiver, Box::pin(result), root))
+    let directories = served_roots
+        .iter()
+        .map(|root| (root.name.clone(), root.directory.clone()))
+        .collect();
+    let served_root_names: Arc<Vec<String>> =
+        Arc::new(served_roots.iter().map(|root| root.name.clone()).collect());
+    update_quota_usage(&*blob_storage_database, &served_root_names, &quota).await;
+    let (save_status_sender, save_status_receiver) = tokio::sync::mpsc::channel(6);
+    let maintaining_roots = futures::future::try_join_all(served_roots.into_iter().map(|root| {
+        Box::pin(maintain_root(
+            root,
+            served_root_names.clone(),
+            save_status_sender.clone(),
+        ))
+    }));
+    let result = async move {
+        let join_result = tokio::try_join!(maintaining_roots, async move {
+            handle_tcp_connections(listener, Arc::new(dav_handlers), security.tls_acceptor)
+                .await
+                .unwrap();
+            Ok(())
+        });
+        join_result.map(|_| ())
+    };
+    Ok((save_status_receiver, Box::pin(result), directories))
+}
+
+fn create_dav_endpoint(
+    file_system: DogBoxFileSystem,
+    lock_system: Box<DogBoxLockSystem>,
+) -> Arc<DavEndpoint> {
+    let lock_system = lock_system.with_commit_on_unlock(file_system.clone());
+    Arc::new(DavEndpoint {
+        handler: DavHandler::builder()
+            .filesystem(Box::new(file_system.clone()))
+            .locksystem(lock_system.clone())
+            .build_handler(),
+        file_system,
+        lock_system: *lock_system,
+    })
 }
@@ -1,9 +1,19 @@
-use crate::run_dav_server;
-use astraea::tree::TREE_BLOB_MAX_LENGTH;
-use dogbox_tree_editor::{OpenDirectory, WallClock};
+use crate::{
+    authentication::{Authenticator, UserList},
+    run_dav_server, shared_folder_root_name, user_root_name, DavServerSecurity, SharedFolder,
+    ANONYMOUS_ROOT_NAME,
+};
+use astraea::{
+    storage::{LoadRoot, SQLiteStorage},
+    tree::TREE_BLOB_MAX_LENGTH,
+};
+use dogbox_tree_editor::{
+    FileCreationMode, NormalizedPath, OpenDirectory, SavePolicy, TrashRetention, TreeEditor,
+    WallClock,
+};
 use pretty_assertions::assert_eq;
 use reqwest_dav::{list_cmd::ListEntity, Auth, Client, ClientBuilder, Depth};
-use std::{future::Future, net::SocketAddr, pin::Pin, sync::Arc};
+use std::{collections::BTreeMap, future::Future, net::SocketAddr, pin::Pin, sync::Arc};
 use tokio::net::TcpListener;
 use tracing::info;
 
@@ -65,21 +75,31 @@ async fn run_dav_server_instance<'t>(
     verify_changes: &impl Fn(Client) -> Pin<Box<dyn Future<Output = ()> + 't>>,
     modified_default: std::time::SystemTime,
     clock: WallClock,
+    quota_limit_in_bytes: Option<u64>,
 ) {
     let address = SocketAddr::from(([127, 0, 0, 1], 0));
     let listener = TcpListener::bind(address).await.unwrap();
     let actual_address = listener.local_addr().unwrap();
     let server_url = format!("http://{actual_address}");
-    let (mut save_status_receiver, server, root_directory) = run_dav_server(
+    let (mut save_status_receiver, server, mut roots) = run_dav_server(
         listener,
         database_file_name,
         modified_default,
         clock,
         // don't waste time with the tests (more than 0 seconds to avoid wasting too many CPU cycles)
-        std::time::Duration::from_millis(1),
+        SavePolicy {
+            interval: std::time::Duration::from_millis(1),
+            ..SavePolicy::default()
+        },
+        None,
+        quota_limit_in_bytes,
+        DavServerSecurity::none(),
+        Vec::new(),
     )
     .await
     .unwrap();
+    assert_eq!(1, roots.len());
+    let root_directory = roots.remove(ANONYMOUS_ROOT_NAME).unwrap();
     let client_side_testing = async move {
         if let Some(change_files2) = change_files {
             change_files2(create_client(server_url.clone())).await;
@@ -133,6 +153,14 @@ async fn run_dav_server_instance<'t>(
 async fn test_fresh_dav_server<'t>(
     change_files: Option<ChangeFilesFunction<'t>>,
     verify_changes: &impl Fn(Client) -> UnitFuture<'t>,
+) {
+    test_fresh_dav_server_with_quota(change_files, verify_changes, None).await
+}
+
+async fn test_fresh_dav_server_with_quota<'t>(
+    change_files: Option<ChangeFilesFunction<'t>>,
+    verify_changes: &impl Fn(Client) -> UnitFuture<'t>,
+    quota_limit_in_bytes: Option<u64>,
 ) {
     let clock = Arc::new(|| {
         std::time::SystemTime::UNIX_EPOCH
@@ -153,6 +181,7 @@ async fn test_fresh_dav_server<'t>(
             &verify_changes,
             modified_default,
             clock.clone(),
+            quota_limit_in_bytes,
         )
         .await;
     }
@@ -167,6 +196,7 @@ async fn test_fresh_dav_server<'t>(
         &verify_changes,
         modified_default,
         clock,
+        quota_limit_in_bytes,
     )
     .await;
     assert!(std::fs::exists(&database_file_name).unwrap());
@@ -1044,3 +1074,1571 @@ async fn test_copy_directory() {
     };
     test_fresh_dav_server(Some(Box::new(change_files)), &verify_changes).await
 }
+
+async fn set_dead_property(client: &Client, path: &str, value: &str) {
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propertyupdate xmlns:D="DAV:" xmlns:Z="http://example.com/ns/">
+  <D:set><D:prop><Z:author>{value}</Z:author></D:prop></D:set>
+</D:propertyupdate>"#
+    );
+    let response = client
+        .start_request(reqwest::Method::from_bytes(b"PROPPATCH").unwrap(), path)
+        .await
+        .unwrap()
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::MULTI_STATUS, response.status());
+    let response_body = response.text().await.unwrap();
+    assert!(response_body.contains("200 OK"), "{}", response_body);
+}
+
+async fn get_dead_property(client: &Client, path: &str) -> String {
+    let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:" xmlns:Z="http://example.com/ns/">
+  <D:prop><Z:author/></D:prop>
+</D:propfind>"#;
+    let response = client
+        .start_request(reqwest::Method::from_bytes(b"PROPFIND").unwrap(), path)
+        .await
+        .unwrap()
+        .header("Depth", "0")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::MULTI_STATUS, response.status());
+    response.text().await.unwrap()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_dead_properties() {
+    let change_files = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            client.mkcol("dir").await.unwrap();
+            client.put("dir/A.txt", "content").await.unwrap();
+            set_dead_property(&client, "dir", "directory author").await;
+            set_dead_property(&client, "dir/A.txt", "file author").await;
+            client.cp("dir/A.txt", "B.txt").await.unwrap();
+            client.mv("B.txt", "C.txt").await.unwrap();
+        })
+    };
+    let verify_changes = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            for (path, expected) in [
+                ("dir", "directory author"),
+                ("dir/A.txt", "file author"),
+                ("C.txt", "file author"),
+            ] {
+                let response = get_dead_property(&client, path).await;
+                assert!(response.contains(&format!(">{expected}</")), "{}", response);
+            }
+        })
+    };
+    test_fresh_dav_server(Some(Box::new(change_files)), &verify_changes).await
+}
+
+/// The tree editor doesn't know about locks, so every request that changes the tree has to be refused before it
+/// reaches the editor.
+#[test_log::test(tokio::test)]
+async fn test_lock_covers_every_change() {
+    let change_files = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            client.mkcol("locked").await.unwrap();
+            client.mkcol("outside").await.unwrap();
+            client.put("locked/A.txt", "original").await.unwrap();
+            client.put("outside/C.txt", "copied").await.unwrap();
+            client.put("B.txt", "outside").await.unwrap();
+            let _lock_token = lock_exclusively(&client, "locked").await;
+
+            let destination = |path: &str| format!("{}/{}", client.host, path);
+            let attempts = [
+                (reqwest::Method::PUT, "locked/A.txt", None),
+                (reqwest::Method::PUT, "locked/new.txt", None),
+                (reqwest::Method::DELETE, "locked/A.txt", None),
+                (
+                    reqwest::Method::from_bytes(b"MKCOL").unwrap(),
+                    "locked/new",
+                    None,
+                ),
+                (
+                    reqwest::Method::from_bytes(b"MOVE").unwrap(),
+                    "B.txt",
+                    Some(destination("locked/B.txt")),
+                ),
+                (
+                    reqwest::Method::from_bytes(b"MOVE").unwrap(),
+                    "locked/A.txt",
+                    Some(destination("A.txt")),
+                ),
+                (
+                    reqwest::Method::from_bytes(b"COPY").unwrap(),
+                    "B.txt",
+                    Some(destination("locked/B.txt")),
+                ),
+                // answered by the directory copy without dav-server
+                (
+                    reqwest::Method::from_bytes(b"COPY").unwrap(),
+                    "outside",
+                    Some(destination("locked/outside")),
+                ),
+            ];
+            for (method, path, destination) in attempts {
+                let mut request = client.start_request(method.clone(), path).await.unwrap();
+                if method == reqwest::Method::PUT {
+                    request = request.body("changed");
+                }
+                if let Some(destination) = destination {
+                    request = request.header("Destination", destination);
+                }
+                assert_eq!(
+                    reqwest::StatusCode::LOCKED,
+                    request.send().await.unwrap().status(),
+                    "{} {}",
+                    method,
+                    path
+                );
+            }
+            let response = client
+                .start_request(
+                    reqwest::Method::from_bytes(b"PROPPATCH").unwrap(),
+                    "locked/A.txt",
+                )
+                .await
+                .unwrap()
+                .body(
+                    r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propertyupdate xmlns:D="DAV:" xmlns:Z="http://example.com/ns/">
+  <D:set><D:prop><Z:author>someone</Z:author></D:prop></D:set>
+</D:propertyupdate>"#,
+                )
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(reqwest::StatusCode::LOCKED, response.status());
+        })
+    };
+    let verify_changes = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            assert_eq!(
+                vec!["/locked/", "/locked/A.txt"],
+                list_names(&list_directory(&client, "locked").await)
+            );
+            assert_eq!(
+                b"original".to_vec(),
+                get_content(&client, "locked/A.txt").await
+            );
+            assert_eq!(b"outside".to_vec(), get_content(&client, "B.txt").await);
+        })
+    };
+    test_fresh_dav_server(Some(Box::new(change_files)), &verify_changes).await
+}
+
+async fn lock_exclusively(client: &Client, path: &str) -> String {
+    let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:lockinfo xmlns:D="DAV:">
+  <D:lockscope><D:exclusive/></D:lockscope>
+  <D:locktype><D:write/></D:locktype>
+  <D:owner>first client</D:owner>
+</D:lockinfo>"#;
+    let response = client
+        .start_request(reqwest::Method::from_bytes(b"LOCK").unwrap(), path)
+        .await
+        .unwrap()
+        .header("Timeout", "Second-600")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::OK, response.status());
+    let lock_token = response.headers()["Lock-Token"].to_str().unwrap();
+    lock_token
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string()
+}
+
+async fn put_with_lock_token(
+    client: &Client,
+    path: &str,
+    content: &'static str,
+    lock_token: Option<&str>,
+) -> reqwest::StatusCode {
+    let mut request = client
+        .start_request(reqwest::Method::PUT, path)
+        .await
+        .unwrap()
+        .body(content);
+    if let Some(lock_token) = lock_token {
+        request = request.header("If", format!("(<{lock_token}>)"));
+    }
+    request.send().await.unwrap().status()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_lock_prevents_overwriting() {
+    let change_files = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            client.put("A.txt", "original").await.unwrap();
+            let lock_token = lock_exclusively(&client, "A.txt").await;
+
+            // a second client without the lock token must not be able to change the file
+            assert_eq!(
+                reqwest::StatusCode::LOCKED,
+                put_with_lock_token(&client, "A.txt", "overwritten", None).await
+            );
+            assert_eq!(
+                reqwest::StatusCode::LOCKED,
+                client
+                    .start_request(reqwest::Method::DELETE, "A.txt")
+                    .await
+                    .unwrap()
+                    .send()
+                    .await
+                    .unwrap()
+                    .status()
+            );
+            // a second exclusive lock is refused as well
+            let response = client
+                .start_request(reqwest::Method::from_bytes(b"LOCK").unwrap(), "A.txt")
+                .await
+                .unwrap()
+                .body(
+                    r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:lockinfo xmlns:D="DAV:">
+  <D:lockscope><D:exclusive/></D:lockscope>
+  <D:locktype><D:write/></D:locktype>
+</D:lockinfo>"#,
+                )
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(reqwest::StatusCode::LOCKED, response.status());
+
+            assert_eq!(
+                reqwest::StatusCode::NO_CONTENT,
+                put_with_lock_token(&client, "A.txt", "changed", Some(&lock_token)).await
+            );
+
+            let response = client
+                .start_request(reqwest::Method::from_bytes(b"UNLOCK").unwrap(), "A.txt")
+                .await
+                .unwrap()
+                .header("Lock-Token", format!("<{lock_token}>"))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(reqwest::StatusCode::NO_CONTENT, response.status());
+            // anyone can write again after unlocking
+            assert_eq!(
+                reqwest::StatusCode::NO_CONTENT,
+                put_with_lock_token(&client, "A.txt", "unlocked", None).await
+            );
+        })
+    };
+    let verify_changes = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            let root_listed = client.list("", Depth::Number(1)).await.unwrap();
+            assert_eq!(2, root_listed.len());
+            expect_directory(&root_listed[0], "/");
+            expect_file(
+                &client,
+                &root_listed[1],
+                "/A.txt",
+                "unlocked".as_bytes(),
+                "text/plain",
+            )
+            .await;
+        })
+    };
+    test_fresh_dav_server(Some(Box::new(change_files)), &verify_changes).await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_lock_creates_a_missing_file_for_its_holder() {
+    let change_files = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            let response = client
+                .start_request(reqwest::Method::from_bytes(b"LOCK").unwrap(), "A.txt")
+                .await
+                .unwrap()
+                .body(
+                    r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:lockinfo xmlns:D="DAV:">
+  <D:lockscope><D:exclusive/></D:lockscope>
+  <D:locktype><D:write/></D:locktype>
+</D:lockinfo>"#,
+                )
+                .send()
+                .await
+                .unwrap();
+            // the tree editor lets the request that took the lock create the file
+            assert_eq!(reqwest::StatusCode::CREATED, response.status());
+            let lock_token = response.headers()["Lock-Token"]
+                .to_str()
+                .unwrap()
+                .trim_start_matches('<')
+                .trim_end_matches('>')
+                .to_string();
+            assert_eq!(
+                reqwest::StatusCode::LOCKED,
+                put_with_lock_token(&client, "A.txt", "overwritten", None).await
+            );
+            assert_eq!(
+                reqwest::StatusCode::NO_CONTENT,
+                put_with_lock_token(&client, "A.txt", "locked", Some(&lock_token)).await
+            );
+        })
+    };
+    let verify_changes = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            assert_eq!(b"locked".to_vec(), get_content(&client, "A.txt").await);
+        })
+    };
+    test_fresh_dav_server(Some(Box::new(change_files)), &verify_changes).await
+}
+
+/// Reads a file from the root as it was last committed to the database, ignoring anything the server only has in
+/// memory.
+async fn read_persisted_file(database_file_name: &std::path::Path, path: &str) -> Option<Vec<u8>> {
+    let storage = Arc::new(
+        SQLiteStorage::from(rusqlite::Connection::open(database_file_name).unwrap()).unwrap(),
+    );
+    let digest = storage.load_root(ANONYMOUS_ROOT_NAME).await.unwrap()?;
+    let clock: WallClock = Arc::new(std::time::SystemTime::now);
+    let root = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage,
+        &digest,
+        clock(),
+        clock,
+        1,
+    )
+    .await
+    .unwrap();
+    let editor = TreeEditor::new(root, None);
+    let opened = editor
+        .open_file(
+            NormalizedPath::try_from(relative_path::RelativePath::new(path)).unwrap(),
+            FileCreationMode::open_existing(),
+        )
+        .await
+        .ok()?;
+    let size = opened.size().await;
+    Some(
+        opened
+            .read_bytes(&opened.get_read_permission(), 0, size as usize)
+            .await
+            .unwrap()
+            .to_vec(),
+    )
+}
+
+async fn run_dav_server_with_policies<'t>(
+    save_policy: SavePolicy,
+    trash_retention: Option<TrashRetention>,
+    test: impl FnOnce(Client, std::path::PathBuf, Arc<OpenDirectory>) -> UnitFuture<'t>,
+) {
+    let clock: WallClock = Arc::new(std::time::SystemTime::now);
+    let temporary_directory = tempfile::tempdir().unwrap();
+    let database_file_name = temporary_directory.path().join("dogbox_dav_server.sqlite");
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let server_url = format!("http://{}", listener.local_addr().unwrap());
+    let (_save_status_receiver, server, roots) = run_dav_server(
+        listener,
+        &database_file_name,
+        clock(),
+        clock,
+        save_policy,
+        trash_retention,
+        None,
+        DavServerSecurity::none(),
+        Vec::new(),
+    )
+    .await
+    .unwrap();
+    tokio::select! {
+        result = server => {
+            panic!("Server isn't expected to exit: {result:?}");
+        }
+        _ = test(
+            create_client(server_url),
+            database_file_name.clone(),
+            roots.get(ANONYMOUS_ROOT_NAME).unwrap().clone(),
+        ) => {
+        }
+    };
+}
+
+/// The server doesn't save on its own during the test, so that only commits make changes durable.
+fn policy_without_regular_saving(commit_on_close: bool) -> SavePolicy {
+    SavePolicy {
+        interval: std::time::Duration::from_secs(3600),
+        commit_on_close,
+        ..SavePolicy::default()
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_unlock_commits() {
+    run_dav_server_with_policies(
+        policy_without_regular_saving(false),
+        None,
+        |client, database_file_name, _root| {
+            Box::pin(async move {
+                client.mkcol("docs").await.unwrap();
+                client.put("docs/A.txt", "original").await.unwrap();
+                let lock_token = lock_exclusively(&client, "docs/A.txt").await;
+                assert_eq!(
+                    reqwest::StatusCode::NO_CONTENT,
+                    put_with_lock_token(&client, "docs/A.txt", "locked", Some(&lock_token)).await
+                );
+                let response = client
+                    .start_request(
+                        reqwest::Method::from_bytes(b"UNLOCK").unwrap(),
+                        "docs/A.txt",
+                    )
+                    .await
+                    .unwrap()
+                    .header("Lock-Token", format!("<{lock_token}>"))
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(reqwest::StatusCode::NO_CONTENT, response.status());
+                assert_eq!(
+                    Some(b"locked".to_vec()),
+                    read_persisted_file(&database_file_name, "docs/A.txt").await
+                );
+            })
+        },
+    )
+    .await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_commit_on_close() {
+    run_dav_server_with_policies(
+        policy_without_regular_saving(true),
+        None,
+        |client, database_file_name, _root| {
+            Box::pin(async move {
+                client.put("A.txt", "first").await.unwrap();
+                assert_eq!(
+                    Some(b"first".to_vec()),
+                    read_persisted_file(&database_file_name, "A.txt").await
+                );
+                client.put("A.txt", "second").await.unwrap();
+                assert_eq!(
+                    Some(b"second".to_vec()),
+                    read_persisted_file(&database_file_name, "A.txt").await
+                );
+            })
+        },
+    )
+    .await
+}
+
+async fn get_quota(client: &Client) -> (u64, u64) {
+    let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:">
+  <D:prop><D:quota-used-bytes/><D:quota-available-bytes/></D:prop>
+</D:propfind>"#;
+    let response = client
+        .start_request(reqwest::Method::from_bytes(b"PROPFIND").unwrap(), "")
+        .await
+        .unwrap()
+        .header("Depth", "0")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(reqwest::StatusCode::MULTI_STATUS, response.status());
+    let text = response.text().await.unwrap();
+    let parse_property = |name: &str| -> u64 {
+        let start = text.find(&format!("{name}>")).unwrap() + name.len() + 1;
+        let end = start + text[start..].find('<').unwrap();
+        text[start..end].parse().unwrap()
+    };
+    (
+        parse_property("quota-used-bytes"),
+        parse_property("quota-available-bytes"),
+    )
+}
+
+#[test_log::test(tokio::test)]
+async fn test_quota() {
+    let quota_limit = 10_000;
+    let content = random_bytes(5_000);
+    let change_files = {
+        let content = content.clone();
+        move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+            Box::pin(async move {
+                // an empty root directory doesn't take up much space
+                let (used, available) = get_quota(&client).await;
+                assert!(used < 100, "{}", used);
+                assert_eq!(quota_limit, used + available);
+                client.put("A.txt", content).await.unwrap();
+                // the usage is measured after the root has been persisted
+                let mut used = 0;
+                for _ in 0..1000 {
+                    used = get_quota(&client).await.0;
+                    if used >= 5_000 {
+                        break;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+                assert!(used >= 5_000, "{}", used);
+
+                let response = client
+                    .start_request(reqwest::Method::PUT, "B.txt")
+                    .await
+                    .unwrap()
+                    .body(random_bytes(8_000))
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(reqwest::StatusCode::INSUFFICIENT_STORAGE, response.status());
+            })
+        }
+    };
+    let verify_changes = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        let content = content.clone();
+        Box::pin(async move {
+            let root_listed = client.list("", Depth::Number(1)).await.unwrap();
+            assert_eq!(3, root_listed.len());
+            expect_directory(&root_listed[0], "/");
+            expect_file(&client, &root_listed[1], "/A.txt", &content, "text/plain").await;
+            // the file was created before the first write failed
+            expect_file(&client, &root_listed[2], "/B.txt", &[], "text/plain").await;
+            let (used, available) = get_quota(&client).await;
+            assert!(used >= 5_000, "{}", used);
+            assert_eq!(quota_limit, used + available);
+        })
+    };
+    test_fresh_dav_server_with_quota(
+        Some(Box::new(change_files)),
+        &verify_changes,
+        Some(quota_limit),
+    )
+    .await
+}
+
+fn create_client_with_auth(server_url: String, auth: Auth) -> Client {
+    ClientBuilder::new()
+        .set_host(server_url)
+        .set_auth(auth)
+        .set_agent(
+            reqwest::Client::builder()
+                .use_rustls_tls()
+                .tls_built_in_root_certs(false)
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap()
+}
+
+async fn run_secured_dav_server<'t>(
+    security: DavServerSecurity,
+    shared_folders: Vec<SharedFolder>,
+    scheme: &str,
+    test: impl FnOnce(String, BTreeMap<String, Arc<OpenDirectory>>) -> UnitFuture<'t>,
+) {
+    run_secured_dav_server_with_quota(security, shared_folders, scheme, None, test).await
+}
+
+async fn run_secured_dav_server_with_quota<'t>(
+    security: DavServerSecurity,
+    shared_folders: Vec<SharedFolder>,
+    scheme: &str,
+    quota_limit_in_bytes: Option<u64>,
+    test: impl FnOnce(String, BTreeMap<String, Arc<OpenDirectory>>) -> UnitFuture<'t>,
+) {
+    let clock: WallClock = Arc::new(std::time::SystemTime::now);
+    let temporary_directory = tempfile::tempdir().unwrap();
+    let database_file_name = temporary_directory.path().join("dogbox_dav_server.sqlite");
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+        .await
+        .unwrap();
+    let server_url = format!("{scheme}://{}", listener.local_addr().unwrap());
+    let (_save_status_receiver, server, roots) = run_dav_server(
+        listener,
+        &database_file_name,
+        clock(),
+        clock,
+        SavePolicy {
+            interval: std::time::Duration::from_millis(1),
+            ..SavePolicy::default()
+        },
+        None,
+        quota_limit_in_bytes,
+        security,
+        shared_folders,
+    )
+    .await
+    .unwrap();
+    tokio::select! {
+        result = server => {
+            panic!("Server isn't expected to exit: {result:?}");
+        }
+        _ = test(server_url, roots) => {
+        }
+    };
+}
+
+fn test_authenticator() -> Arc<Authenticator> {
+    let mut users = UserList::new("dogbox".to_string());
+    users.add_user("alice".to_string(), "secret").unwrap();
+    Arc::new(Authenticator::new(
+        users,
+        Arc::new(std::time::SystemTime::now),
+    ))
+}
+
+#[test_log::test(tokio::test)]
+async fn test_authentication_required() {
+    run_secured_dav_server(
+        DavServerSecurity {
+            authenticator: Some(test_authenticator()),
+            tls_acceptor: None,
+        },
+        Vec::new(),
+        "http",
+        |server_url, _roots| {
+            Box::pin(async move {
+                let http_client = reqwest::Client::builder()
+                    .use_rustls_tls()
+                    .tls_built_in_root_certs(false)
+                    .build()
+                    .unwrap();
+                let anonymous_response = http_client
+                    .get(format!("{server_url}/"))
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    reqwest::StatusCode::UNAUTHORIZED,
+                    anonymous_response.status()
+                );
+                let challenges: Vec<&str> = anonymous_response
+                    .headers()
+                    .get_all(reqwest::header::WWW_AUTHENTICATE)
+                    .iter()
+                    .map(|value| value.to_str().unwrap())
+                    .collect();
+                assert_eq!(2, challenges.len());
+                assert!(challenges[0].starts_with("Digest "));
+                assert!(challenges[1].starts_with("Basic "));
+
+                let wrong_password_response = http_client
+                    .get(format!("{server_url}/"))
+                    .basic_auth("alice", Some("wrong"))
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    reqwest::StatusCode::UNAUTHORIZED,
+                    wrong_password_response.status()
+                );
+                let wrong_client = create_client_with_auth(
+                    server_url.clone(),
+                    Auth::Basic("alice".to_string(), "wrong".to_string()),
+                );
+                assert!(wrong_client.list("/", Depth::Number(1)).await.is_err());
+
+                let basic_client = create_client_with_auth(
+                    server_url.clone(),
+                    Auth::Basic("alice".to_string(), "secret".to_string()),
+                );
+                basic_client.put("/a.txt", "basic").await.unwrap();
+                assert_eq!(
+                    "basic",
+                    basic_client
+                        .get("/a.txt")
+                        .await
+                        .unwrap()
+                        .text()
+                        .await
+                        .unwrap()
+                );
+
+                let digest_client = create_client_with_auth(
+                    server_url,
+                    Auth::Digest("alice".to_string(), "secret".to_string()),
+                );
+                digest_client.put("/b.txt", "digest").await.unwrap();
+                assert_eq!(
+                    "digest",
+                    digest_client
+                        .get("/b.txt")
+                        .await
+                        .unwrap()
+                        .text()
+                        .await
+                        .unwrap()
+                );
+                assert_eq!(3, list_directory(&digest_client, "/").await.len());
+            })
+        },
+    )
+    .await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_tls() {
+    run_secured_dav_server(
+        DavServerSecurity {
+            authenticator: Some(test_authenticator()),
+            tls_acceptor: Some(
+                crate::tls::create_tls_acceptor(
+                    crate::tls_tests::TEST_CERTIFICATE_PEM.as_bytes(),
+                    crate::tls_tests::TEST_PRIVATE_KEY_PEM.as_bytes(),
+                )
+                .unwrap(),
+            ),
+        },
+        Vec::new(),
+        "https",
+        |server_url, _roots| {
+            Box::pin(async move {
+                let client = ClientBuilder::new()
+                    .set_host(server_url.clone())
+                    .set_auth(Auth::Basic("alice".to_string(), "secret".to_string()))
+                    .set_agent(
+                        reqwest::Client::builder()
+                            .use_rustls_tls()
+                            .tls_built_in_root_certs(false)
+                            .add_root_certificate(
+                                reqwest::Certificate::from_pem(
+                                    crate::tls_tests::TEST_CERTIFICATE_PEM.as_bytes(),
+                                )
+                                .unwrap(),
+                            )
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+                    .unwrap();
+                client.put("/a.txt", "encrypted").await.unwrap();
+                assert_eq!(
+                    "encrypted",
+                    client.get("/a.txt").await.unwrap().text().await.unwrap()
+                );
+
+                // A client that doesn't trust the certificate can't connect.
+                let untrusting_client = create_client_with_auth(
+                    server_url,
+                    Auth::Basic("alice".to_string(), "secret".to_string()),
+                );
+                assert!(untrusting_client.get("/a.txt").await.is_err());
+            })
+        },
+    )
+    .await;
+}
+
+fn list_names(entities: &[ListEntity]) -> Vec<String> {
+    entities
+        .iter()
+        .map(|entity| match entity {
+            ListEntity::File(file) => file.href.clone(),
+            ListEntity::Folder(folder) => folder.href.clone(),
+        })
+        .collect()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_per_user_roots() {
+    let mut users = UserList::new("dogbox".to_string());
+    for name in ["alice", "bob", "carol"] {
+        users.add_user(name.to_string(), "secret").unwrap();
+    }
+    let team = dogbox_tree::serialization::FileName::try_from("team".to_string()).unwrap();
+    run_secured_dav_server(
+        DavServerSecurity {
+            authenticator: Some(Arc::new(Authenticator::new(
+                users,
+                Arc::new(std::time::SystemTime::now),
+            ))),
+            tls_acceptor: None,
+        },
+        vec![SharedFolder {
+            name: team.clone(),
+            members: ["alice".to_string(), "bob".to_string()].into(),
+        }],
+        "http",
+        |server_url, roots| {
+            Box::pin(async move {
+                assert_eq!(
+                    vec![
+                        shared_folder_root_name(&team),
+                        user_root_name("alice"),
+                        user_root_name("bob"),
+                        user_root_name("carol"),
+                    ],
+                    roots.keys().cloned().collect::<Vec<_>>()
+                );
+                let client_for = |name: &str| {
+                    create_client_with_auth(
+                        server_url.clone(),
+                        Auth::Basic(name.to_string(), "secret".to_string()),
+                    )
+                };
+                let alice = client_for("alice");
+                let bob = client_for("bob");
+                let carol = client_for("carol");
+                let bob_digest_before = roots[&user_root_name("bob")]
+                    .latest_status()
+                    .digest
+                    .last_known_digest;
+
+                alice.put("/private.txt", "alice only").await.unwrap();
+                alice.put("/team/shared.txt", "for the team").await.unwrap();
+
+                assert_eq!(
+                    vec!["/", "/private.txt", "/team/"],
+                    list_names(&list_directory(&alice, "/").await)
+                );
+                assert_eq!(
+                    vec!["/", "/team/"],
+                    list_names(&list_directory(&bob, "/").await)
+                );
+                assert_eq!(vec!["/"], list_names(&list_directory(&carol, "/").await));
+                assert!(bob.get("/private.txt").await.is_err());
+                assert_eq!(
+                    "for the team",
+                    bob.get("/team/shared.txt")
+                        .await
+                        .unwrap()
+                        .text()
+                        .await
+                        .unwrap()
+                );
+                assert!(carol.get("/team/shared.txt").await.is_err());
+
+                // Moving between the home directory and a shared folder is not supported.
+                assert!(alice.mv("/private.txt", "/team/private.txt").await.is_err());
+                assert_eq!(
+                    "for the team",
+                    alice
+                        .get("/team/shared.txt")
+                        .await
+                        .unwrap()
+                        .text()
+                        .await
+                        .unwrap()
+                );
+                // Deleting the shared folder only deletes its content. The mount point itself stays.
+                assert!(bob.delete("/team").await.is_err());
+                assert_eq!(
+                    vec!["/team/"],
+                    list_names(&list_directory(&alice, "/team").await)
+                );
+
+                // Saving Alice's changes doesn't change Bob's root.
+                for name in [
+                    user_root_name("alice"),
+                    user_root_name("bob"),
+                    shared_folder_root_name(&team),
+                ] {
+                    assert!(
+                        roots[&name]
+                            .request_save()
+                            .await
+                            .unwrap()
+                            .digest
+                            .is_digest_up_to_date
+                    );
+                }
+                assert_eq!(
+                    bob_digest_before,
+                    roots[&user_root_name("bob")]
+                        .latest_status()
+                        .digest
+                        .last_known_digest
+                );
+                assert_ne!(
+                    bob_digest_before,
+                    roots[&user_root_name("alice")]
+                        .latest_status()
+                        .digest
+                        .last_known_digest
+                );
+            })
+        },
+    )
+    .await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_quota_is_shared_by_all_roots() {
+    let quota_limit = 10_000;
+    let mut users = UserList::new("dogbox".to_string());
+    for name in ["alice", "bob"] {
+        users.add_user(name.to_string(), "secret").unwrap();
+    }
+    let team = dogbox_tree::serialization::FileName::try_from("team".to_string()).unwrap();
+    run_secured_dav_server_with_quota(
+        DavServerSecurity {
+            authenticator: Some(Arc::new(Authenticator::new(
+                users,
+                Arc::new(std::time::SystemTime::now),
+            ))),
+            tls_acceptor: None,
+        },
+        vec![SharedFolder {
+            name: team,
+            members: ["alice".to_string(), "bob".to_string()].into(),
+        }],
+        "http",
+        Some(quota_limit),
+        |server_url, _roots| {
+            Box::pin(async move {
+                let client_for = |name: &str| {
+                    create_client_with_auth(
+                        server_url.clone(),
+                        Auth::Basic(name.to_string(), "secret".to_string()),
+                    )
+                };
+                let alice = client_for("alice");
+                let bob = client_for("bob");
+                let content = random_bytes(4_000);
+                alice.put("/private.txt", content.clone()).await.unwrap();
+                // identical content in another root is stored only once
+                bob.put("/team/copy.txt", content).await.unwrap();
+                let mut used = 0;
+                for _ in 0..1000 {
+                    used = get_quota(&bob).await.0;
+                    if used >= 4_000 {
+                        break;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+                assert!((4_000..8_000).contains(&used), "{}", used);
+                // the usage may grow a little when the other root is persisted
+                let (alice_used, alice_available) = get_quota(&alice).await;
+                assert!((used..8_000).contains(&alice_used), "{}", alice_used);
+                assert_eq!(quota_limit, alice_used + alice_available);
+
+                // Each root on its own would still have room for this.
+                let response = bob
+                    .start_request(reqwest::Method::PUT, "private.txt")
+                    .await
+                    .unwrap()
+                    .body(random_bytes(7_000))
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(reqwest::StatusCode::INSUFFICIENT_STORAGE, response.status());
+            })
+        },
+    )
+    .await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_mount_point_has_no_versions() {
+    let team = dogbox_tree::serialization::FileName::try_from("team".to_string()).unwrap();
+    run_secured_dav_server(
+        DavServerSecurity {
+            authenticator: Some(test_authenticator()),
+            tls_acceptor: None,
+        },
+        vec![SharedFolder {
+            name: team,
+            members: ["alice".to_string()].into(),
+        }],
+        "http",
+        |server_url, _roots| {
+            Box::pin(async move {
+                let alice = create_client_with_auth(
+                    server_url,
+                    Auth::Basic("alice".to_string(), "secret".to_string()),
+                );
+                assert!(alice
+                    .list("/team@versions", Depth::Number(1))
+                    .await
+                    .is_err());
+                assert!(alice.get("/team@versions/1").await.is_err());
+                assert!(alice.cp("/team@versions/1", "/team").await.is_err());
+                // the server is still there
+                assert_eq!(
+                    vec!["/", "/team/"],
+                    list_names(&list_directory(&alice, "/").await)
+                );
+            })
+        },
+    )
+    .await;
+}
+
+async fn wait_for_version_count(client: &Client, versions_directory: &str, count: usize) {
+    for _ in 0..1000 {
+        // the directory itself is listed, too
+        if list_directory(client, versions_directory).await.len() == count + 1 {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("{versions_directory} didn't get {count} versions");
+}
+
+async fn get_content(client: &Client, path: &str) -> Vec<u8> {
+    let response = client.get(path).await.unwrap();
+    response.bytes().await.unwrap().to_vec()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_file_versions() {
+    let change_files = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            client.put("A.txt", "one").await.unwrap();
+            wait_for_version_count(&client, "A.txt@versions", 0).await;
+            // The first content has to be saved before it can become a version. There is a race condition here, but
+            // the server saves every millisecond, so it's acceptable for this test.
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            client.put("A.txt", "two").await.unwrap();
+            wait_for_version_count(&client, "A.txt@versions", 1).await;
+            assert_eq!(
+                b"one".to_vec(),
+                get_content(&client, "A.txt@versions/1").await
+            );
+
+            // versions are read-only
+            let response = client
+                .start_request(reqwest::Method::PUT, "A.txt@versions/1")
+                .await
+                .unwrap()
+                .body("three")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(reqwest::StatusCode::FORBIDDEN, response.status());
+            let response = client
+                .start_request(reqwest::Method::DELETE, "A.txt@versions/1")
+                .await
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(reqwest::StatusCode::FORBIDDEN, response.status());
+
+            client.cp("A.txt@versions/1", "A.txt").await.unwrap();
+            assert_eq!(b"one".to_vec(), get_content(&client, "A.txt").await);
+            wait_for_version_count(&client, "A.txt@versions", 2).await;
+        })
+    };
+    let verify_changes = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            // the versions don't show up in the parent directory
+            let root_listed = client.list("", Depth::Number(1)).await.unwrap();
+            assert_eq!(vec!["/", "/A.txt"], list_names(&root_listed));
+            assert_eq!(b"one".to_vec(), get_content(&client, "A.txt").await);
+            let versions_listed = list_directory(&client, "A.txt@versions").await;
+            // the server encodes the @ in its hrefs
+            assert_eq!(
+                vec![
+                    "/A.txt%40versions/",
+                    "/A.txt%40versions/1",
+                    "/A.txt%40versions/2"
+                ],
+                list_names(&versions_listed)
+            );
+            assert_eq!(
+                b"two".to_vec(),
+                get_content(&client, "A.txt@versions/2").await
+            );
+            let response = client
+                .start_request(reqwest::Method::GET, "A.txt@versions/3")
+                .await
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(reqwest::StatusCode::NOT_FOUND, response.status());
+        })
+    };
+    test_fresh_dav_server(Some(Box::new(change_files)), &verify_changes).await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_real_entries_named_like_versions() {
+    let change_files = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            // a directory that happens to look like the versions of a file that doesn't exist
+            client.mkcol("dir@versions").await.unwrap();
+            client.put("dir@versions/1", "in directory").await.unwrap();
+            // a real entry wins even after the file appears
+            client.put("B.txt@versions", "real").await.unwrap();
+            client.put("B.txt", "b").await.unwrap();
+        })
+    };
+    let verify_changes = |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            assert_eq!(
+                b"in directory".to_vec(),
+                get_content(&client, "dir@versions/1").await
+            );
+            assert_eq!(
+                vec!["/dir%40versions/", "/dir%40versions/1"],
+                list_names(&list_directory(&client, "dir@versions").await)
+            );
+            assert_eq!(
+                b"real".to_vec(),
+                get_content(&client, "B.txt@versions").await
+            );
+            assert_eq!(
+                vec!["/", "/B.txt", "/B.txt%40versions", "/dir%40versions/"],
+                list_names(&list_directory(&client, "/").await)
+            );
+        })
+    };
+    test_fresh_dav_server(Some(Box::new(change_files)), &verify_changes).await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_search_directory() {
+    let change_files = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            client.mkcol("docs").await.unwrap();
+            client.put("docs/a.txt", "hello world").await.unwrap();
+            client.put("b.txt", "goodbye").await.unwrap();
+            // the search only sees saved changes
+            for _ in 0..1000 {
+                if list_directory(&client, ".search/hello").await.len() == 2 {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+    };
+    let verify_changes = move |client: Client| -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(async move {
+            // the search directory doesn't show up in the root
+            let root_listed = client.list("", Depth::Number(1)).await.unwrap();
+            assert_eq!(vec!["/", "/b.txt", "/docs/"], list_names(&root_listed));
+            assert_eq!(
+                vec!["/.search/"],
+                list_names(&list_directory(&client, ".search").await)
+            );
+            assert_eq!(
+                vec!["/.search/hello/", "/.search/hello/docs/"],
+                list_names(&list_directory(&client, ".search/hello").await)
+            );
+            assert_eq!(
+                vec!["/.search/hello/docs/", "/.search/hello/docs/a.txt"],
+                list_names(&list_directory(&client, ".search/hello/docs").await)
+            );
+            assert_eq!(
+                vec!["/.search/txt/", "/.search/txt/b.txt", "/.search/txt/docs/"],
+                list_names(&list_directory(&client, ".search/txt").await)
+            );
+            assert_eq!(
+                b"hello world".to_vec(),
+                get_content(&client, ".search/hello/docs/a.txt").await
+            );
+            let response = client
+                .start_request(reqwest::Method::GET, ".search/hello/b.txt")
+                .await
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(reqwest::StatusCode::NOT_FOUND, response.status());
+            let response = client
+                .start_request(reqwest::Method::PUT, ".search/hello/docs/a.txt")
+                .await
+                .unwrap()
+                .body("changed")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(reqwest::StatusCode::FORBIDDEN, response.status());
+        })
+    };
+    test_fresh_dav_server(Some(Box::new(change_files)), &verify_changes).await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_delete_moves_into_trash() {
+    run_dav_server_with_policies(
+        SavePolicy::default(),
+        Some(TrashRetention::default()),
+        |client, _database_file_name, _root| {
+            Box::pin(async move {
+                client.mkcol("docs").await.unwrap();
+                client.put("docs/a.txt", "hello").await.unwrap();
+                client.delete("docs").await.unwrap();
+                assert_eq!(
+                    vec!["/", "/.trash/"],
+                    list_names(&list_directory(&client, "").await)
+                );
+                // the content of a directory is deleted before the directory itself
+                assert_eq!(
+                    vec!["/.trash/", "/.trash/a.txt", "/.trash/docs/"],
+                    list_names(&list_directory(&client, ".trash").await)
+                );
+                assert_eq!(
+                    b"hello".to_vec(),
+                    get_content(&client, ".trash/a.txt").await
+                );
+                // clients can see where an entry was deleted from
+                let response = client
+                    .start_request(
+                        reqwest::Method::from_bytes(b"PROPFIND").unwrap(),
+                        ".trash/a.txt",
+                    )
+                    .await
+                    .unwrap()
+                    .header("Depth", "0")
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(reqwest::StatusCode::MULTI_STATUS, response.status());
+                assert!(response.text().await.unwrap().contains(">docs/a.txt</"));
+
+                // deleting from the trash is permanent
+                client.delete(".trash/a.txt").await.unwrap();
+                assert_eq!(
+                    vec!["/.trash/", "/.trash/docs/"],
+                    list_names(&list_directory(&client, ".trash").await)
+                );
+            })
+        },
+    )
+    .await
+}
+
+#[test_log::test(tokio::test)]
+async fn test_reserved_names_cannot_be_created() {
+    run_dav_server_with_policies(
+        SavePolicy::default(),
+        Some(TrashRetention::default()),
+        |client, _database_file_name, _root| {
+            Box::pin(async move {
+                client.put("a.txt", "hello").await.unwrap();
+                for reserved in [".trash", ".search"] {
+                    for (method, path, destination) in [
+                        (
+                            reqwest::Method::from_bytes(b"MKCOL").unwrap(),
+                            reserved,
+                            None,
+                        ),
+                        (reqwest::Method::PUT, reserved, None),
+                        (
+                            reqwest::Method::from_bytes(b"MOVE").unwrap(),
+                            "a.txt",
+                            Some(reserved),
+                        ),
+                        (
+                            reqwest::Method::from_bytes(b"COPY").unwrap(),
+                            "a.txt",
+                            Some(reserved),
+                        ),
+                    ] {
+                        let mut request = client.start_request(method.clone(), path).await.unwrap();
+                        if let Some(destination) = destination {
+                            request = request
+                                .header("Destination", format!("{}/{}", client.host, destination));
+                        }
+                        let status = request.send().await.unwrap().status();
+                        // replacing the search directory fails with a multi status
+                        assert!(
+                            ![
+                                reqwest::StatusCode::CREATED,
+                                reqwest::StatusCode::NO_CONTENT
+                            ]
+                            .contains(&status),
+                            "{} {} {:?}: {}",
+                            method,
+                            path,
+                            destination,
+                            status
+                        );
+                    }
+                }
+                assert_eq!(
+                    vec!["/", "/a.txt"],
+                    list_names(&list_directory(&client, "").await)
+                );
+            })
+        },
+    )
+    .await
+}
+
+async fn save_until_up_to_date(root: &OpenDirectory) {
+    loop {
+        if root
+            .request_save()
+            .await
+            .unwrap()
+            .digest
+            .is_digest_up_to_date
+        {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_copy_directory_stores_no_content() {
+    run_dav_server_with_policies(
+        policy_without_regular_saving(false),
+        None,
+        |client, _database_file_name, root| {
+            Box::pin(async move {
+                client.mkcol("big").await.unwrap();
+                client.mkcol("big/nested").await.unwrap();
+                let large: Vec<u8> = (0..(3 * TREE_BLOB_MAX_LENGTH))
+                    .map(|index| (index % 251) as u8)
+                    .collect();
+                client.put("big/large.bin", large.clone()).await.unwrap();
+                client.put("big/nested/deep.txt", "deep").await.unwrap();
+                save_until_up_to_date(&root).await;
+                let storage = root.get_storage();
+                let trees_before = storage.approximate_tree_count().await.unwrap();
+
+                let response = client
+                    .start_request(reqwest::Method::from_bytes(b"COPY").unwrap(), "big")
+                    .await
+                    .unwrap()
+                    .header("Destination", format!("{}/copy", client.host))
+                    .header("Depth", "infinity")
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(reqwest::StatusCode::CREATED, response.status());
+                save_until_up_to_date(&root).await;
+                // Only the root directory that lists the copy is new. The server collects the trees of old roots in
+                // the background, so there can be fewer trees than before.
+                let trees_after = storage.approximate_tree_count().await.unwrap();
+                assert!(
+                    trees_after <= trees_before + 1,
+                    "{} trees before, {} trees after",
+                    trees_before,
+                    trees_after
+                );
+                assert_eq!(large, get_content(&client, "copy/large.bin").await);
+                assert_eq!(
+                    b"deep".to_vec(),
+                    get_content(&client, "copy/nested/deep.txt").await
+                );
+
+                // dav-server still answers copies that don't include the content
+                let response = client
+                    .start_request(reqwest::Method::from_bytes(b"COPY").unwrap(), "big")
+                    .await
+                    .unwrap()
+                    .header("Destination", format!("{}/copy", client.host))
+                    .header("Depth", "0")
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(reqwest::StatusCode::NO_CONTENT, response.status());
+                assert_eq!(
+                    vec!["/copy/", "/copy/large.bin", "/copy/nested/"],
+                    list_names(&list_directory(&client, "copy").await)
+                );
+            })
+        },
+    )
+    .await
+}
+
+/// Runs the server on a runtime of its own. Sending to the returned channel kills the server with all of its tasks and
+/// connections like a crash would, without saving or committing anything. The thread ends once everything is gone.
+fn start_crashable_server(
+    database_file_name: std::path::PathBuf,
+    save_policy: SavePolicy,
+    security: DavServerSecurity,
+) -> (
+    String,
+    tokio::sync::oneshot::Sender<()>,
+    std::thread::JoinHandle<()>,
+) {
+    let (url_sender, url_receiver) = std::sync::mpsc::channel();
+    let (kill_sender, kill_receiver) = tokio::sync::oneshot::channel::<()>();
+    let thread = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .await
+                .unwrap();
+            url_sender
+                .send(format!("http://{}", listener.local_addr().unwrap()))
+                .unwrap();
+            let clock: WallClock = Arc::new(std::time::SystemTime::now);
+            let (_save_status_receiver, server, _roots) = run_dav_server(
+                listener,
+                &database_file_name,
+                clock(),
+                clock,
+                save_policy,
+                None,
+                None,
+                security,
+                Vec::new(),
+            )
+            .await
+            .unwrap();
+            tokio::select! {
+                result = server => {
+                    panic!("Server isn't expected to exit: {result:?}");
+                }
+                _ = kill_receiver => {}
+            }
+        });
+        runtime.shutdown_timeout(std::time::Duration::from_secs(10));
+    });
+    (url_receiver.recv().unwrap(), kill_sender, thread)
+}
+
+async fn crash_server(
+    kill_sender: tokio::sync::oneshot::Sender<()>,
+    thread: std::thread::JoinHandle<()>,
+) {
+    kill_sender.send(()).unwrap();
+    tokio::task::spawn_blocking(move || thread.join().unwrap())
+        .await
+        .unwrap();
+}
+
+#[test_log::test(tokio::test)]
+async fn test_recover_unsaved_writes_after_crash() {
+    use tokio::io::AsyncWriteExt;
+    let temporary_directory = tempfile::tempdir().unwrap();
+    let database_file_name = temporary_directory.path().join("dogbox_dav_server.sqlite");
+    let (server_url, kill_sender, thread) = start_crashable_server(
+        database_file_name.clone(),
+        policy_without_regular_saving(false),
+        DavServerSecurity::none(),
+    );
+    let client = create_client(server_url.clone());
+    client.mkcol("docs").await.unwrap();
+    client.put("docs/A.txt", "acknowledged").await.unwrap();
+    // the server crashes in the middle of another upload
+    let mut interrupted =
+        tokio::net::TcpStream::connect(server_url.strip_prefix("http://").unwrap())
+            .await
+            .unwrap();
+    interrupted
+        .write_all(
+            b"PUT /docs/B.txt HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1000000\r\n\r\npartial",
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    crash_server(kill_sender, thread).await;
+    drop(interrupted);
+    assert_eq!(
+        None,
+        read_persisted_file(&database_file_name, "docs/A.txt").await
+    );
+    assert!(std::fs::exists(
+        crate::journal_directory(&database_file_name)
+            .join(crate::journal_file_name(ANONYMOUS_ROOT_NAME))
+    )
+    .unwrap());
+
+    let (server_url, kill_sender, thread) = start_crashable_server(
+        database_file_name.clone(),
+        policy_without_regular_saving(false),
+        DavServerSecurity::none(),
+    );
+    let client = create_client(server_url);
+    assert_eq!(
+        "acknowledged",
+        client
+            .get("docs/A.txt")
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap()
+    );
+    // the part of the interrupted upload that arrived may have been recovered as well
+    let response = client.get("docs/B.txt").await.unwrap();
+    if response.status().is_success() {
+        let content = response.bytes().await.unwrap();
+        assert!(b"partial".starts_with(&content), "{content:?}");
+    }
+    // replaying persisted the recovered changes
+    assert_eq!(
+        Some(b"acknowledged".to_vec()),
+        read_persisted_file(&database_file_name, "docs/A.txt").await
+    );
+    crash_server(kill_sender, thread).await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_enabling_authentication_keeps_anonymous_content() {
+    let temporary_directory = tempfile::tempdir().unwrap();
+    let database_file_name = temporary_directory.path().join("dogbox_dav_server.sqlite");
+    let (server_url, kill_sender, thread) = start_crashable_server(
+        database_file_name.clone(),
+        policy_without_regular_saving(false),
+        DavServerSecurity::none(),
+    );
+    // only journaled, not persisted yet
+    create_client(server_url)
+        .put("before.txt", "anonymous")
+        .await
+        .unwrap();
+    crash_server(kill_sender, thread).await;
+
+    let mut users = UserList::new("dogbox".to_string());
+    for name in ["alice", "bob"] {
+        users.add_user(name.to_string(), "secret").unwrap();
+    }
+    let security = DavServerSecurity {
+        authenticator: Some(Arc::new(Authenticator::new(
+            users,
+            Arc::new(std::time::SystemTime::now),
+        ))),
+        tls_acceptor: None,
+    };
+    let client_for = |server_url: &str, name: &str| {
+        create_client_with_auth(
+            server_url.to_string(),
+            Auth::Basic(name.to_string(), "secret".to_string()),
+        )
+    };
+    let (server_url, kill_sender, thread) = start_crashable_server(
+        database_file_name.clone(),
+        policy_without_regular_saving(false),
+        security.clone(),
+    );
+    // the first user by name gets the content
+    let alice = client_for(&server_url, "alice");
+    assert_eq!(
+        b"anonymous".to_vec(),
+        get_content(&alice, "before.txt").await
+    );
+    alice.put("after.txt", "alice").await.unwrap();
+    assert_eq!(
+        vec!["/"],
+        list_names(&list_directory(&client_for(&server_url, "bob"), "/").await)
+    );
+    crash_server(kill_sender, thread).await;
+
+    // the anonymous root doesn't replace the home directory again
+    let (server_url, kill_sender, thread) = start_crashable_server(
+        database_file_name,
+        policy_without_regular_saving(false),
+        security,
+    );
+    assert_eq!(
+        vec!["/", "/after.txt", "/before.txt"],
+        list_names(&list_directory(&client_for(&server_url, "alice"), "/").await)
+    );
+    crash_server(kill_sender, thread).await;
+}
@@ -0,0 +1,484 @@
+use crate::file_system::{normalize_path, DogBoxFileSystem};
+use dav_server::{
+    davpath::DavPath,
+    ls::{DavLock, DavLockSystem, LsFuture},
+};
+use dogbox_tree::serialization::FileName;
+use dogbox_tree_editor::{
+    hold_lock, CheckLocks, LockTokens, NormalizedPath, PathSplitLeftResult, WallClock,
+};
+use futures::FutureExt;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, error, info};
+
+struct ActiveLock {
+    path: NormalizedPath,
+    lock: DavLock,
+}
+
+impl ActiveLock {
+    /// Whether this lock applies to `path` itself, i.e. it is set on the path or on a parent with depth infinity.
+    fn covers(&self, path: &NormalizedPath) -> bool {
+        self.path == *path || (self.lock.deep && path.starts_with(&self.path))
+    }
+
+    /// Whether a deep operation on `path` would touch the resource this lock is set on.
+    fn is_below(&self, path: &NormalizedPath) -> bool {
+        self.path.starts_with(path)
+    }
+
+    fn is_held(&self, principal: Option<&str>, ignore_principal: bool, tokens: &[&str]) -> bool {
+        tokens.iter().any(|token| *token == self.lock.token)
+            && (ignore_principal || principal == self.lock.principal.as_deref())
+    }
+}
+
+/// Holding one of the shared locks is enough to modify a resource, but every exclusive lock has to be held.
+fn find_conflict<'t>(
+    relevant_locks: impl Iterator<Item = &'t ActiveLock>,
+    is_held: impl Fn(&ActiveLock) -> bool,
+) -> Option<&'t DavLock> {
+    let mut holds_any_lock = false;
+    let mut first_shared_conflict: Option<&DavLock> = None;
+    for active in relevant_locks {
+        if is_held(active) {
+            holds_any_lock = true;
+        } else if !active.lock.shared {
+            return Some(&active.lock);
+        } else {
+            first_shared_conflict.get_or_insert(&active.lock);
+        }
+    }
+    match holds_any_lock {
+        true => None,
+        false => first_shared_conflict,
+    }
+}
+
+struct LockTable {
+    // The key is the lock token.
+    locks: BTreeMap<String, ActiveLock>,
+}
+
+impl LockTable {
+    fn remove_expired(&mut self, now: std::time::SystemTime) {
+        self.locks
+            .retain(|token, active| match active.lock.timeout_at {
+                Some(timeout_at) => {
+                    let is_alive = timeout_at > now;
+                    if !is_alive {
+                        info!("Lock {} on {} expired", token, &active.lock.path);
+                    }
+                    is_alive
+                }
+                None => true,
+            });
+    }
+
+    fn relevant_locks<'t>(
+        &'t self,
+        path: &'t NormalizedPath,
+        deep: bool,
+    ) -> impl Iterator<Item = &'t ActiveLock> + 't {
+        self.locks
+            .values()
+            .filter(move |active| active.covers(path) || (deep && active.is_below(path)))
+    }
+}
+
+/// The locks on the resources of one volume. Every lock system that serves the volume shares them, so that locks in a
+/// shared folder conflict with the locks of the other members.
+#[derive(Clone)]
+pub struct VolumeLocks {
+    table: Arc<Mutex<LockTable>>,
+}
+
+impl VolumeLocks {
+    pub fn new() -> VolumeLocks {
+        VolumeLocks {
+            table: Arc::new(Mutex::new(LockTable {
+                locks: BTreeMap::new(),
+            })),
+        }
+    }
+}
+
+impl Default for VolumeLocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lets the tree editors of a volume enforce its locks. The volume of a shared folder is edited without its mount point,
+/// but the paths of its locks start with it (see [DogBoxLockSystem]).
+pub struct EditorLocks {
+    volume: VolumeLocks,
+    mount_point: Option<FileName>,
+    clock: WallClock,
+}
+
+impl EditorLocks {
+    pub fn new(volume: VolumeLocks, mount_point: Option<FileName>, clock: WallClock) -> Self {
+        Self {
+            volume,
+            mount_point,
+            clock,
+        }
+    }
+}
+
+impl CheckLocks for EditorLocks {
+    fn check(
+        &self,
+        path: &NormalizedPath,
+        deep: bool,
+        held: &LockTokens,
+    ) -> std::result::Result<(), String> {
+        let path = match &self.mount_point {
+            Some(mount_point) => path.components().fold(
+                NormalizedPath::root().join(mount_point.clone()),
+                |parent, name| parent.join(name.clone()),
+            ),
+            None => path.clone(),
+        };
+        let mut table = self.volume.table.lock().unwrap();
+        table.remove_expired((self.clock)());
+        match find_conflict(table.relevant_locks(&path, deep), |active| {
+            held.contains(&active.lock.token)
+        }) {
+            Some(conflict) => Err(conflict.token.clone()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The lock tokens that a request submits in its `If` header. dav-server evaluates the conditions themselves, so the
+/// tokens are only needed to let the tree editor know which locks the request holds.
+pub fn submitted_lock_tokens(headers: &hyper::HeaderMap) -> LockTokens {
+    let mut tokens = Vec::new();
+    for value in headers.get_all("If") {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        // Lock tokens are in angle brackets inside of the parentheses. Angle brackets outside of them tag a resource.
+        let mut in_list = false;
+        let mut rest = value;
+        while let Some(index) = rest.find(['(', ')', '<']) {
+            let (delimiter, after) = (rest.as_bytes()[index], &rest[index + 1..]);
+            rest = after;
+            match delimiter {
+                b'(' => in_list = true,
+                b')' => in_list = false,
+                _ => {
+                    let Some(end) = rest.find('>') else {
+                        break;
+                    };
+                    if in_list {
+                        tokens.push(rest[..end].to_string());
+                    }
+                    rest = &rest[end + 1..];
+                }
+            }
+        }
+    }
+    LockTokens::new(tokens)
+}
+
+/// Keeps track of WebDAV locks per [NormalizedPath] so that the same resource is identified by the same key
+/// regardless of how the client spelled the URL. Locks are not persisted. They expire according to the [WallClock]
+/// of the tree editor.
+///
+/// Locks below a mount point are kept in the [VolumeLocks] of the mounted volume. Every member mounts a shared folder
+/// under the same name, so the paths of these locks mean the same for everyone.
+///
+/// dav-server checks the locks for every method that changes a resource. The tree editors of the volumes check them
+/// again with [EditorLocks] for every change, whoever makes it. A request holds the locks that it submits in its `If`
+/// header (see [submitted_lock_tokens]) and the lock that it takes.
+#[derive(Clone)]
+pub struct DogBoxLockSystem {
+    home: VolumeLocks,
+    mounts: BTreeMap<FileName, VolumeLocks>,
+    clock: WallClock,
+    commit_on_unlock: Option<DogBoxFileSystem>,
+}
+
+impl DogBoxLockSystem {
+    pub fn new(clock: WallClock) -> Box<DogBoxLockSystem> {
+        Box::new(DogBoxLockSystem {
+            home: VolumeLocks::new(),
+            mounts: BTreeMap::new(),
+            clock,
+            commit_on_unlock: None,
+        })
+    }
+
+    /// Uses the locks of the home volume instead of locks of its own.
+    pub fn with_home(mut self: Box<DogBoxLockSystem>, locks: VolumeLocks) -> Box<DogBoxLockSystem> {
+        self.home = locks;
+        self
+    }
+
+    /// Keeps the locks below the directory `name` in the locks of the volume mounted there.
+    pub fn with_mount(
+        mut self: Box<DogBoxLockSystem>,
+        name: FileName,
+        locks: VolumeLocks,
+    ) -> Box<DogBoxLockSystem> {
+        self.mounts.insert(name, locks);
+        self
+    }
+
+    /// Unlocking a resource makes the changes to it durable, so that a client knows its work is safe once it lets go.
+    pub fn with_commit_on_unlock(
+        mut self: Box<DogBoxLockSystem>,
+        file_system: DogBoxFileSystem,
+    ) -> Box<DogBoxLockSystem> {
+        self.commit_on_unlock = Some(file_system);
+        self
+    }
+
+    /// The volumes whose locks can be relevant for `path` and the index of the one that new locks on `path` go into. A
+    /// deep lock on the root is kept in the home volume, but it also covers the mounted volumes.
+    fn volumes_for(&self, path: &NormalizedPath) -> (Vec<&VolumeLocks>, usize) {
+        let mount = match path.clone().split_left() {
+            PathSplitLeftResult::Root => return (self.all_volumes(), 0),
+            PathSplitLeftResult::Leaf(name) => self.mounts.get(&name),
+            PathSplitLeftResult::Directory(name, _rest) => self.mounts.get(&name),
+        };
+        let volumes: Vec<&VolumeLocks> = std::iter::once(&self.home).chain(mount).collect();
+        let primary = volumes.len() - 1;
+        (volumes, primary)
+    }
+
+    fn all_volumes(&self) -> Vec<&VolumeLocks> {
+        std::iter::once(&self.home)
+            .chain(self.mounts.values())
+            .collect()
+    }
+
+    /// The tables are always locked starting with the home volume and then in the order of the mount names. Every user
+    /// has their own home volume and shared folders have the same name for all members, so the order is the same in
+    /// every lock system.
+    fn lock_tables<'t>(
+        &self,
+        volumes: Vec<&'t VolumeLocks>,
+    ) -> Vec<std::sync::MutexGuard<'t, LockTable>> {
+        let now = (self.clock)();
+        volumes
+            .into_iter()
+            .map(|volume| {
+                let mut table = volume.table.lock().unwrap();
+                table.remove_expired(now);
+                table
+            })
+            .collect()
+    }
+
+    fn generate_token() -> String {
+        let random: u128 = rand::random();
+        format!(
+            "opaquelocktoken:{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            (random >> 96) as u32,
+            (random >> 80) as u16,
+            (random >> 64) as u16,
+            (random >> 48) as u16,
+            random & 0xffff_ffff_ffff
+        )
+    }
+}
+
+impl std::fmt::Debug for DogBoxLockSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DogBoxLockSystem").finish()
+    }
+}
+
+impl DavLockSystem for DogBoxLockSystem {
+    fn lock(
+        &self,
+        path: &DavPath,
+        principal: Option<&str>,
+        owner: Option<&xmltree::Element>,
+        timeout: Option<std::time::Duration>,
+        shared: bool,
+        deep: bool,
+    ) -> LsFuture<'_, Result<DavLock, DavLock>> {
+        let normalized_path = match normalize_path(path) {
+            Ok(success) => success,
+            // Such a path can't exist in the tree editor. The trait only allows us to refuse a lock by reporting a
+            // conflicting one.
+            Err(_) => {
+                return futures::future::ready(Err(DavLock {
+                    token: String::new(),
+                    path: path.clone(),
+                    principal: None,
+                    owner: None,
+                    timeout_at: None,
+                    timeout: None,
+                    shared: false,
+                    deep: false,
+                }))
+                .boxed()
+            }
+        };
+        let (volumes, primary) = self.volumes_for(&normalized_path);
+        let mut tables = self.lock_tables(volumes);
+        // Shared locks only conflict with exclusive locks. Exclusive locks conflict with everything.
+        if let Some(conflict) = tables
+            .iter()
+            .flat_map(|table| table.relevant_locks(&normalized_path, deep))
+            .find(|active| !(shared && active.lock.shared))
+        {
+            debug!(
+                "Cannot lock {} because of existing lock {}",
+                path, &conflict.lock.token
+            );
+            return futures::future::ready(Err(conflict.lock.clone())).boxed();
+        }
+        let lock = DavLock {
+            token: Self::generate_token(),
+            path: path.clone(),
+            principal: principal.map(|principal| principal.to_string()),
+            owner: owner.cloned(),
+            timeout_at: timeout.map(|timeout| (self.clock)() + timeout),
+            timeout,
+            shared,
+            deep,
+        };
+        info!(
+            "Locked {} with {} (shared: {}, deep: {})",
+            path, &lock.token, shared, deep
+        );
+        // dav-server creates the locked file after taking the lock.
+        hold_lock(lock.token.clone());
+        tables[primary].locks.insert(
+            lock.token.clone(),
+            ActiveLock {
+                path: normalized_path,
+                lock: lock.clone(),
+            },
+        );
+        futures::future::ready(Ok(lock)).boxed()
+    }
+
+    fn unlock(&self, path: &DavPath, token: &str) -> LsFuture<'_, Result<(), ()>> {
+        let result = match normalize_path(path) {
+            Ok(normalized_path) => {
+                let mut tables = self.lock_tables(self.all_volumes());
+                match tables
+                    .iter_mut()
+                    .find(|table| table.locks.contains_key(token))
+                {
+                    Some(table) if table.locks[token].covers(&normalized_path) => {
+                        info!("Unlocked {} ({})", &table.locks[token].lock.path, token);
+                        table.locks.remove(token);
+                        Ok(())
+                    }
+                    _ => Err(()),
+                }
+            }
+            Err(_) => Err(()),
+        };
+        match (result, &self.commit_on_unlock) {
+            (Ok(()), Some(file_system)) => {
+                let path = path.clone();
+                Box::pin(async move {
+                    // The lock is gone either way, so a failed commit doesn't fail the UNLOCK.
+                    match file_system.commit(&path).await {
+                        Ok(digest) => info!("Committed {} on unlock: {}", path, &digest),
+                        Err(error) => error!("Could not commit {} on unlock: {:?}", path, error),
+                    }
+                    Ok(())
+                })
+            }
+            (result, _) => futures::future::ready(result).boxed(),
+        }
+    }
+
+    fn refresh(
+        &self,
+        path: &DavPath,
+        token: &str,
+        timeout: Option<std::time::Duration>,
+    ) -> LsFuture<'_, Result<DavLock, ()>> {
+        let result = match normalize_path(path) {
+            Ok(normalized_path) => {
+                let now = (self.clock)();
+                let mut tables = self.lock_tables(self.all_volumes());
+                match tables
+                    .iter_mut()
+                    .find_map(|table| table.locks.get_mut(token))
+                {
+                    Some(active) if active.covers(&normalized_path) => {
+                        active.lock.timeout = timeout;
+                        active.lock.timeout_at = timeout.map(|timeout| now + timeout);
+                        Ok(active.lock.clone())
+                    }
+                    _ => Err(()),
+                }
+            }
+            Err(_) => Err(()),
+        };
+        futures::future::ready(result).boxed()
+    }
+
+    fn check(
+        &self,
+        path: &DavPath,
+        principal: Option<&str>,
+        ignore_principal: bool,
+        deep: bool,
+        submitted_tokens: Vec<&str>,
+    ) -> LsFuture<'_, Result<(), DavLock>> {
+        let normalized_path = match normalize_path(path) {
+            Ok(success) => success,
+            Err(_) => return futures::future::ready(Ok(())).boxed(),
+        };
+        let (volumes, _primary) = self.volumes_for(&normalized_path);
+        let tables = self.lock_tables(volumes);
+        let result = match find_conflict(
+            tables
+                .iter()
+                .flat_map(|table| table.relevant_locks(&normalized_path, deep)),
+            |active| active.is_held(principal, ignore_principal, &submitted_tokens),
+        ) {
+            Some(conflict) => Err(conflict.clone()),
+            None => Ok(()),
+        };
+        futures::future::ready(result).boxed()
+    }
+
+    fn discover(&self, path: &DavPath) -> LsFuture<'_, Vec<DavLock>> {
+        let result = match normalize_path(path) {
+            Ok(normalized_path) => {
+                let (volumes, _primary) = self.volumes_for(&normalized_path);
+                self.lock_tables(volumes)
+                    .iter()
+                    .flat_map(|table| table.relevant_locks(&normalized_path, false))
+                    .map(|active| active.lock.clone())
+                    .collect()
+            }
+            Err(_) => Vec::new(),
+        };
+        futures::future::ready(result).boxed()
+    }
+
+    fn delete(&self, path: &DavPath) -> LsFuture<'_, Result<(), ()>> {
+        let result = match normalize_path(path) {
+            Ok(normalized_path) => {
+                let (volumes, _primary) = self.volumes_for(&normalized_path);
+                for mut table in self.lock_tables(volumes) {
+                    table
+                        .locks
+                        .retain(|_token, active| !active.is_below(&normalized_path));
+                }
+                Ok(())
+            }
+            Err(_) => Err(()),
+        };
+        futures::future::ready(result).boxed()
+    }
+}
@@ -0,0 +1,417 @@
+use crate::lock_system::{submitted_lock_tokens, DogBoxLockSystem, EditorLocks, VolumeLocks};
+use dav_server::{davpath::DavPath, ls::DavLockSystem};
+use dogbox_tree_editor::{CheckLocks, LockTokens, NormalizedPath};
+use std::sync::{Arc, Mutex};
+
+fn test_clock() -> (
+    Arc<Mutex<std::time::SystemTime>>,
+    dogbox_tree_editor::WallClock,
+) {
+    let now = Arc::new(Mutex::new(std::time::SystemTime::UNIX_EPOCH));
+    let clock = {
+        let now = now.clone();
+        Arc::new(move || *now.lock().unwrap())
+    };
+    (now, clock)
+}
+
+fn path(input: &str) -> DavPath {
+    DavPath::new(input).unwrap()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_exclusive_lock_conflicts() {
+    let (_now, clock) = test_clock();
+    let lock_system = DogBoxLockSystem::new(clock);
+    let lock = lock_system
+        .lock(&path("/a.txt"), None, None, None, false, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        lock.token,
+        lock_system
+            .lock(&path("/a.txt"), None, None, None, false, false)
+            .await
+            .unwrap_err()
+            .token
+    );
+    assert_eq!(
+        lock.token,
+        lock_system
+            .lock(&path("/a.txt"), None, None, None, true, false)
+            .await
+            .unwrap_err()
+            .token
+    );
+    // the same resource spelled differently
+    assert!(lock_system
+        .lock(&path("//a.txt"), None, None, None, false, false)
+        .await
+        .is_err());
+    // other resources are not affected
+    lock_system
+        .lock(&path("/b.txt"), None, None, None, false, false)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        lock.token,
+        lock_system
+            .check(&path("/a.txt"), None, false, false, vec![])
+            .await
+            .unwrap_err()
+            .token
+    );
+    lock_system
+        .check(&path("/a.txt"), None, false, false, vec![&lock.token])
+        .await
+        .unwrap();
+    lock_system
+        .check(&path("/c.txt"), None, false, false, vec![])
+        .await
+        .unwrap();
+
+    lock_system
+        .unlock(&path("/a.txt"), &lock.token)
+        .await
+        .unwrap();
+    assert_eq!(
+        Err(()),
+        lock_system.unlock(&path("/a.txt"), &lock.token).await
+    );
+    lock_system
+        .check(&path("/a.txt"), None, false, false, vec![])
+        .await
+        .unwrap();
+}
+
+#[test_log::test(tokio::test)]
+async fn test_shared_locks() {
+    let (_now, clock) = test_clock();
+    let lock_system = DogBoxLockSystem::new(clock);
+    let first = lock_system
+        .lock(&path("/a.txt"), None, None, None, true, false)
+        .await
+        .unwrap();
+    let second = lock_system
+        .lock(&path("/a.txt"), None, None, None, true, false)
+        .await
+        .unwrap();
+    assert_ne!(first.token, second.token);
+    assert!(lock_system
+        .lock(&path("/a.txt"), None, None, None, false, false)
+        .await
+        .is_err());
+    assert!(lock_system
+        .check(&path("/a.txt"), None, false, false, vec![])
+        .await
+        .is_err());
+    lock_system
+        .check(&path("/a.txt"), None, false, false, vec![&second.token])
+        .await
+        .unwrap();
+    assert_eq!(2, lock_system.discover(&path("/a.txt")).await.len());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_deep_lock() {
+    let (_now, clock) = test_clock();
+    let lock_system = DogBoxLockSystem::new(clock);
+    let child = lock_system
+        .lock(&path("/dir/a.txt"), None, None, None, false, false)
+        .await
+        .unwrap();
+    // a deep lock would cover the already locked child
+    assert_eq!(
+        child.token,
+        lock_system
+            .lock(&path("/dir/"), None, None, None, false, true)
+            .await
+            .unwrap_err()
+            .token
+    );
+    // a shallow lock on the directory doesn't
+    let shallow = lock_system
+        .lock(&path("/dir/"), None, None, None, false, false)
+        .await
+        .unwrap();
+    lock_system
+        .unlock(&path("/dir/"), &shallow.token)
+        .await
+        .unwrap();
+    lock_system
+        .unlock(&path("/dir/a.txt"), &child.token)
+        .await
+        .unwrap();
+
+    let deep = lock_system
+        .lock(&path("/dir/"), None, None, None, false, true)
+        .await
+        .unwrap();
+    assert!(lock_system
+        .check(&path("/dir/sub/b.txt"), None, false, false, vec![])
+        .await
+        .is_err());
+    lock_system
+        .check(
+            &path("/dir/sub/b.txt"),
+            None,
+            false,
+            false,
+            vec![&deep.token],
+        )
+        .await
+        .unwrap();
+    // a deep check of the parent finds the lock below
+    assert!(lock_system
+        .check(&path("/"), None, false, true, vec![])
+        .await
+        .is_err());
+    lock_system
+        .check(&path("/"), None, false, false, vec![])
+        .await
+        .unwrap();
+    assert_eq!(1, lock_system.discover(&path("/dir/sub/b.txt")).await.len());
+
+    lock_system.delete(&path("/dir")).await.unwrap();
+    assert!(lock_system.discover(&path("/dir/")).await.is_empty());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_lock_principal() {
+    let (_now, clock) = test_clock();
+    let lock_system = DogBoxLockSystem::new(clock);
+    let lock = lock_system
+        .lock(&path("/a.txt"), Some("alice"), None, None, false, false)
+        .await
+        .unwrap();
+    assert!(lock_system
+        .check(
+            &path("/a.txt"),
+            Some("bob"),
+            false,
+            false,
+            vec![&lock.token]
+        )
+        .await
+        .is_err());
+    lock_system
+        .check(
+            &path("/a.txt"),
+            Some("alice"),
+            false,
+            false,
+            vec![&lock.token],
+        )
+        .await
+        .unwrap();
+    lock_system
+        .check(&path("/a.txt"), Some("bob"), true, false, vec![&lock.token])
+        .await
+        .unwrap();
+}
+
+#[test_log::test(tokio::test)]
+async fn test_lock_timeout() {
+    let (now, clock) = test_clock();
+    let lock_system = DogBoxLockSystem::new(clock);
+    let timeout = std::time::Duration::from_secs(60);
+    let lock = lock_system
+        .lock(&path("/a.txt"), None, None, Some(timeout), false, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        Some(std::time::SystemTime::UNIX_EPOCH + timeout),
+        lock.timeout_at
+    );
+
+    *now.lock().unwrap() += std::time::Duration::from_secs(59);
+    let refreshed = lock_system
+        .refresh(&path("/a.txt"), &lock.token, Some(timeout))
+        .await
+        .unwrap();
+    assert_eq!(
+        Some(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(119)),
+        refreshed.timeout_at
+    );
+
+    *now.lock().unwrap() += std::time::Duration::from_secs(59);
+    assert!(lock_system
+        .check(&path("/a.txt"), None, false, false, vec![])
+        .await
+        .is_err());
+
+    *now.lock().unwrap() += std::time::Duration::from_secs(1);
+    lock_system
+        .check(&path("/a.txt"), None, false, false, vec![])
+        .await
+        .unwrap();
+    assert!(lock_system
+        .refresh(&path("/a.txt"), &lock.token, Some(timeout))
+        .await
+        .is_err());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_locks_in_shared_folder_conflict_between_users() {
+    let (_now, clock) = test_clock();
+    let team = dogbox_tree::serialization::FileName::try_from("team".to_string()).unwrap();
+    let team_locks = VolumeLocks::new();
+    let alice = DogBoxLockSystem::new(clock.clone()).with_mount(team.clone(), team_locks.clone());
+    let bob = DogBoxLockSystem::new(clock).with_mount(team, team_locks);
+
+    let shared_lock = alice
+        .lock(
+            &path("/team/a.txt"),
+            Some("alice"),
+            None,
+            None,
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        shared_lock.token,
+        bob.lock(&path("/team/a.txt"), Some("bob"), None, None, false, false)
+            .await
+            .unwrap_err()
+            .token
+    );
+    assert!(bob
+        .check(&path("/team/a.txt"), Some("bob"), false, false, vec![])
+        .await
+        .is_err());
+    assert_eq!(
+        vec![shared_lock.token.clone()],
+        bob.discover(&path("/team/a.txt"))
+            .await
+            .into_iter()
+            .map(|lock| lock.token)
+            .collect::<Vec<_>>()
+    );
+
+    // the home directories are separate even though the paths are the same
+    alice
+        .lock(&path("/a.txt"), Some("alice"), None, None, false, false)
+        .await
+        .unwrap();
+    let home_lock = bob
+        .lock(&path("/a.txt"), Some("bob"), None, None, false, false)
+        .await
+        .unwrap();
+
+    // a deep operation on the root of a user also sees the locks in the folders they mounted
+    assert_eq!(
+        shared_lock.token,
+        bob.check(&path("/"), Some("bob"), false, true, vec![&home_lock.token])
+            .await
+            .unwrap_err()
+            .token
+    );
+
+    alice
+        .unlock(&path("/team/a.txt"), &shared_lock.token)
+        .await
+        .unwrap();
+    bob.check(&path("/team/a.txt"), Some("bob"), false, false, vec![])
+        .await
+        .unwrap();
+}
+
+fn normalized_path(input: &str) -> NormalizedPath {
+    NormalizedPath::try_from(relative_path::RelativePath::new(input)).unwrap()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_editor_locks() {
+    let (now, clock) = test_clock();
+    let home_locks = VolumeLocks::new();
+    let team = dogbox_tree::serialization::FileName::try_from("team".to_string()).unwrap();
+    let team_locks = VolumeLocks::new();
+    let lock_system = DogBoxLockSystem::new(clock.clone())
+        .with_home(home_locks.clone())
+        .with_mount(team.clone(), team_locks.clone());
+    let home_editor_locks = EditorLocks::new(home_locks, None, clock.clone());
+    let team_editor_locks = EditorLocks::new(team_locks, Some(team), clock);
+
+    let file_lock = lock_system
+        .lock(
+            &path("/a/b.txt"),
+            None,
+            None,
+            Some(std::time::Duration::from_secs(10)),
+            false,
+            false,
+        )
+        .await
+        .unwrap();
+    let nobody = LockTokens::default();
+    let holder = LockTokens::new([file_lock.token.clone()]);
+    assert_eq!(
+        Err(file_lock.token.clone()),
+        home_editor_locks.check(&normalized_path("a/b.txt"), false, &nobody)
+    );
+    assert_eq!(
+        Ok(()),
+        home_editor_locks.check(&normalized_path("a/b.txt"), false, &holder)
+    );
+    assert_eq!(
+        Ok(()),
+        home_editor_locks.check(&normalized_path("a"), false, &nobody)
+    );
+    // removing the directory would remove the locked file
+    assert_eq!(
+        Err(file_lock.token.clone()),
+        home_editor_locks.check(&normalized_path("a"), true, &nobody)
+    );
+
+    // the shared folder is edited without its mount point
+    let team_lock = lock_system
+        .lock(&path("/team/c"), None, None, None, false, true)
+        .await
+        .unwrap();
+    assert_eq!(
+        Err(team_lock.token.clone()),
+        team_editor_locks.check(&normalized_path("c/d.txt"), false, &nobody)
+    );
+    assert_eq!(
+        Ok(()),
+        home_editor_locks.check(&normalized_path("c/d.txt"), false, &nobody)
+    );
+    assert_eq!(
+        Err(team_lock.token.clone()),
+        team_editor_locks.check(&normalized_path(""), true, &nobody)
+    );
+
+    // expired locks don't count
+    *now.lock().unwrap() += std::time::Duration::from_secs(11);
+    assert_eq!(
+        Ok(()),
+        home_editor_locks.check(&normalized_path("a/b.txt"), false, &nobody)
+    );
+}
+
+#[test_log::test]
+fn test_submitted_lock_tokens() {
+    let mut headers = hyper::HeaderMap::new();
+    assert_eq!(LockTokens::default(), submitted_lock_tokens(&headers));
+    headers.append(
+        "If",
+        hyper::header::HeaderValue::from_static(
+            "<http://localhost/a.txt> (<opaquelocktoken:a> [\"etag\"]) (Not <opaquelocktoken:b>)",
+        ),
+    );
+    headers.append(
+        "If",
+        hyper::header::HeaderValue::from_static("(<opaquelocktoken:c>)"),
+    );
+    assert_eq!(
+        LockTokens::new([
+            "opaquelocktoken:a".to_string(),
+            "opaquelocktoken:b".to_string(),
+            "opaquelocktoken:c".to_string(),
+        ]),
+        submitted_lock_tokens(&headers)
+    );
+}
@@ -0,0 +1,58 @@
+use std::sync::Mutex;
+
+#[derive(Debug, Default)]
+struct QuotaUsage {
+    used_in_bytes: u64,
+    pending_in_bytes: u64,
+}
+
+/// Keeps track of how much storage the tree under the root takes up and how much it may take up.
+///
+/// The used bytes are measured in the storage after every persisted root change, so they count deduplicated data.
+/// Bytes written in between two measurements are counted as pending until the next measurement replaces them.
+#[derive(Debug)]
+pub struct Quota {
+    limit_in_bytes: Option<u64>,
+    usage: Mutex<QuotaUsage>,
+}
+
+impl Quota {
+    pub fn new(limit_in_bytes: Option<u64>) -> Self {
+        Self {
+            limit_in_bytes,
+            usage: Mutex::new(QuotaUsage::default()),
+        }
+    }
+
+    pub fn limit_in_bytes(&self) -> Option<u64> {
+        self.limit_in_bytes
+    }
+
+    pub fn used_in_bytes(&self) -> u64 {
+        self.usage.lock().unwrap().used_in_bytes
+    }
+
+    pub fn available_in_bytes(&self) -> Option<u64> {
+        self.limit_in_bytes
+            .map(|limit| limit.saturating_sub(self.used_in_bytes()))
+    }
+
+    pub fn set_measured_usage(&self, used_in_bytes: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.used_in_bytes = used_in_bytes;
+        usage.pending_in_bytes = 0;
+    }
+
+    /// Returns false if writing `additional_bytes` would exceed the limit. Otherwise the bytes are counted as pending.
+    pub fn try_reserve(&self, additional_bytes: u64) -> bool {
+        let mut usage = self.usage.lock().unwrap();
+        let pending_in_bytes = usage.pending_in_bytes.saturating_add(additional_bytes);
+        if let Some(limit) = self.limit_in_bytes {
+            if usage.used_in_bytes.saturating_add(pending_in_bytes) > limit {
+                return false;
+            }
+        }
+        usage.pending_in_bytes = pending_in_bytes;
+        true
+    }
+}
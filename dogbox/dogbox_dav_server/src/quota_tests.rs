@@ -0,0 +1,33 @@
+use crate::quota::Quota;
+
+#[test_log::test]
+fn test_unlimited_quota() {
+    let quota = Quota::new(None);
+    assert_eq!(None, quota.available_in_bytes());
+    assert!(quota.try_reserve(u64::MAX));
+    assert!(quota.try_reserve(u64::MAX));
+    quota.set_measured_usage(123);
+    assert_eq!(123, quota.used_in_bytes());
+    assert_eq!(None, quota.available_in_bytes());
+}
+
+#[test_log::test]
+fn test_limited_quota() {
+    let quota = Quota::new(Some(100));
+    assert_eq!(Some(100), quota.available_in_bytes());
+    assert!(quota.try_reserve(60));
+    assert!(!quota.try_reserve(41));
+    assert!(quota.try_reserve(40));
+    assert!(!quota.try_reserve(1));
+
+    // a new measurement replaces the pending bytes
+    quota.set_measured_usage(30);
+    assert_eq!(30, quota.used_in_bytes());
+    assert_eq!(Some(70), quota.available_in_bytes());
+    assert!(quota.try_reserve(70));
+    assert!(!quota.try_reserve(1));
+
+    quota.set_measured_usage(150);
+    assert_eq!(Some(0), quota.available_in_bytes());
+    assert!(!quota.try_reserve(1));
+}
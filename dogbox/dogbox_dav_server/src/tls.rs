@@ -0,0 +1,55 @@
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use std::{path::Path, sync::Arc};
+use tokio_rustls::{rustls, TlsAcceptor};
+
+#[derive(Debug)]
+pub enum TlsConfigurationError {
+    Io(std::io::Error),
+    Pem(rustls_pki_types::pem::Error),
+    Rustls(rustls::Error),
+    NoCertificate,
+}
+
+impl std::fmt::Display for TlsConfigurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for TlsConfigurationError {}
+
+/// Creates an acceptor for HTTPS connections from a PEM encoded certificate chain (leaf certificate first) and the PEM
+/// encoded private key of the leaf certificate.
+pub fn create_tls_acceptor(
+    certificate_chain_pem: &[u8],
+    private_key_pem: &[u8],
+) -> Result<TlsAcceptor, TlsConfigurationError> {
+    let certificate_chain = CertificateDer::pem_slice_iter(certificate_chain_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(TlsConfigurationError::Pem)?;
+    if certificate_chain.is_empty() {
+        return Err(TlsConfigurationError::NoCertificate);
+    }
+    let private_key =
+        PrivateKeyDer::from_pem_slice(private_key_pem).map_err(TlsConfigurationError::Pem)?;
+    let mut config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(TlsConfigurationError::Rustls)?
+    .with_no_client_auth()
+    .with_single_cert(certificate_chain, private_key)
+    .map_err(TlsConfigurationError::Rustls)?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+pub fn load_tls_acceptor(
+    certificate_chain_file: &Path,
+    private_key_file: &Path,
+) -> Result<TlsAcceptor, TlsConfigurationError> {
+    let certificate_chain_pem =
+        std::fs::read(certificate_chain_file).map_err(TlsConfigurationError::Io)?;
+    let private_key_pem = std::fs::read(private_key_file).map_err(TlsConfigurationError::Io)?;
+    create_tls_acceptor(&certificate_chain_pem, &private_key_pem)
+}
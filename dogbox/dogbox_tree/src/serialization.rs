@@ -1,10 +1,10 @@
 use astraea::{
     storage::{LoadError, LoadStoreTree},
-    tree::BlobDigest,
+    tree::{BlobDigest, HashedTree, Tree, TreeBlob, TreeChildren},
 };
 use serde::{Deserialize, Serialize};
 use sorted_tree::prolly_tree_editable_node::{self, Iterator};
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::Arc};
 use tracing::debug;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
@@ -122,14 +122,260 @@ pub enum DirectoryEntryKind {
     File(u64),
 }
 
+/// Identifies a WebDAV dead property by its XML namespace and local name.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct DeadPropertyName {
+    pub namespace: Option<String>,
+    pub name: String,
+}
+
+impl DeadPropertyName {
+    pub fn new(namespace: Option<String>, name: String) -> Self {
+        Self { namespace, name }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct DeadPropertyValue {
+    /// the XML prefix the client used for the namespace
+    pub prefix: Option<String>,
+    /// raw XML content of the property
+    pub xml: Option<Vec<u8>>,
+}
+
+impl DeadPropertyValue {
+    pub fn new(prefix: Option<String>, xml: Option<Vec<u8>>) -> Self {
+        Self { prefix, xml }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DeadPropertiesError {
+    TooLarge { size_in_bytes: usize },
+}
+
+impl std::fmt::Display for DeadPropertiesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for DeadPropertiesError {}
+
+pub const MODIFIED_PROPERTY_NAMESPACE: &str = "urn:nonlocality:dogbox";
+pub const MODIFIED_PROPERTY_PREFIX: &str = "dogbox";
+pub const MODIFIED_PROPERTY_NAME: &str = "modified";
+
+pub fn modified_property_name() -> DeadPropertyName {
+    DeadPropertyName::new(
+        Some(MODIFIED_PROPERTY_NAMESPACE.to_string()),
+        MODIFIED_PROPERTY_NAME.to_string(),
+    )
+}
+
+/// Properties that clients attach to files and directories. We don't interpret them, we only store them.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct DeadProperties {
+    entries: BTreeMap<DeadPropertyName, DeadPropertyValue>,
+}
+
+impl DeadProperties {
+    /// The properties are stored inline in the directory listing, so we have to make sure that a prolly tree node with a
+    /// long file name and these properties still fits into a tree blob.
+    pub const MAX_SIZE_IN_BYTES: usize = 16_000;
+
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &BTreeMap<DeadPropertyName, DeadPropertyValue> {
+        &self.entries
+    }
+
+    pub fn get(&self, name: &DeadPropertyName) -> Option<&DeadPropertyValue> {
+        self.entries.get(name)
+    }
+
+    pub fn set(&mut self, name: DeadPropertyName, value: DeadPropertyValue) {
+        self.entries.insert(name, value);
+    }
+
+    pub fn remove(&mut self, name: &DeadPropertyName) -> Option<DeadPropertyValue> {
+        self.entries.remove(name)
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        postcard::to_stdvec(self)
+            .expect("serializing properties into a Vec should always succeed")
+            .len()
+    }
+
+    pub fn check_size(&self) -> std::result::Result<(), DeadPropertiesError> {
+        let size_in_bytes = self.size_in_bytes();
+        if size_in_bytes > Self::MAX_SIZE_IN_BYTES {
+            Err(DeadPropertiesError::TooLarge { size_in_bytes })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The modification time that was preserved when the entry was imported from a local directory. It is stored like
+    /// any other dead property, so WebDAV clients see it as `{urn:nonlocality:dogbox}modified`.
+    pub fn modified(&self) -> Option<std::time::SystemTime> {
+        let xml = self.get(&modified_property_name())?.xml.as_ref()?;
+        let xml = std::str::from_utf8(xml).ok()?;
+        let (_start_tag, rest) = xml.split_once('>')?;
+        let (content, _end_tag) = rest.split_once('<')?;
+        let (seconds, nanoseconds) = content.split_once('.')?;
+        let since_epoch =
+            std::time::Duration::new(seconds.parse().ok()?, nanoseconds.parse().ok()?);
+        std::time::SystemTime::UNIX_EPOCH.checked_add(since_epoch)
+    }
+
+    /// Times before the Unix epoch are stored as the epoch.
+    pub fn set_modified(&mut self, modified: std::time::SystemTime) {
+        let since_epoch = modified
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let xml = format!(
+            "<{MODIFIED_PROPERTY_PREFIX}:{MODIFIED_PROPERTY_NAME} xmlns:{MODIFIED_PROPERTY_PREFIX}=\"{MODIFIED_PROPERTY_NAMESPACE}\">{}.{:09}</{MODIFIED_PROPERTY_PREFIX}:{MODIFIED_PROPERTY_NAME}>",
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos()
+        );
+        self.set(
+            modified_property_name(),
+            DeadPropertyValue::new(
+                Some(MODIFIED_PROPERTY_PREFIX.to_string()),
+                Some(xml.into_bytes()),
+            ),
+        );
+    }
+}
+
+/// An earlier content of a file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FileVersion {
+    pub content: BlobDigest,
+    pub size: u64,
+    /// when this content was saved for the first time
+    pub saved_at: std::time::SystemTime,
+}
+
+impl FileVersion {
+    pub fn new(content: BlobDigest, size: u64, saved_at: std::time::SystemTime) -> Self {
+        Self {
+            content,
+            size,
+            saved_at,
+        }
+    }
+}
+
+/// The earlier versions of a file, oldest first.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct FileHistory {
+    versions: Vec<FileVersion>,
+}
+
+impl FileHistory {
+    /// The history is stored inline in the directory listing like [DeadProperties], so its size has to be limited, too.
+    pub const MAX_VERSIONS: usize = 64;
+
+    pub fn new() -> Self {
+        Self {
+            versions: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+
+    pub fn versions(&self) -> &[FileVersion] {
+        &self.versions
+    }
+
+    /// Appends a version and drops the oldest ones if there are more than [FileHistory::MAX_VERSIONS].
+    pub fn push(&mut self, version: FileVersion) {
+        self.versions.push(version);
+        self.keep_newest(Self::MAX_VERSIONS);
+    }
+
+    pub fn keep_newest(&mut self, count: usize) {
+        let excess = self.versions.len().saturating_sub(count);
+        self.versions.drain(..excess);
+    }
+
+    pub fn remove_saved_before(&mut self, oldest_allowed: std::time::SystemTime) {
+        self.versions
+            .retain(|version| version.saved_at >= oldest_allowed);
+    }
+}
+
+/// How a directory entry is serialized in the prolly tree. The first two variants are encoded exactly like
+/// [DirectoryEntryKind], so directories stored before dead properties existed can still be loaded and keep their digests.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DirectoryEntryContent {
+    Directory,
+    File(u64),
+    DirectoryWithProperties(DeadProperties),
+    FileWithProperties(u64, DeadProperties),
+    /// The reference of the entry points to a history tree (see [FileHistory]), so the digest of the current content is
+    /// stored here.
+    FileWithHistory(u64, DeadProperties, BlobDigest, FileHistory),
+}
+
+impl DirectoryEntryContent {
+    pub fn new(kind: DirectoryEntryKind, properties: DeadProperties) -> Self {
+        match (kind, properties.is_empty()) {
+            (DirectoryEntryKind::Directory, true) => DirectoryEntryContent::Directory,
+            (DirectoryEntryKind::File(size), true) => DirectoryEntryContent::File(size),
+            (DirectoryEntryKind::Directory, false) => {
+                DirectoryEntryContent::DirectoryWithProperties(properties)
+            }
+            (DirectoryEntryKind::File(size), false) => {
+                DirectoryEntryContent::FileWithProperties(size, properties)
+            }
+        }
+    }
+
+    pub fn split(self) -> (DirectoryEntryKind, DeadProperties) {
+        match self {
+            DirectoryEntryContent::Directory => {
+                (DirectoryEntryKind::Directory, DeadProperties::new())
+            }
+            DirectoryEntryContent::File(size) => {
+                (DirectoryEntryKind::File(size), DeadProperties::new())
+            }
+            DirectoryEntryContent::DirectoryWithProperties(properties) => {
+                (DirectoryEntryKind::Directory, properties)
+            }
+            DirectoryEntryContent::FileWithProperties(size, properties)
+            | DirectoryEntryContent::FileWithHistory(size, properties, _, _) => {
+                (DirectoryEntryKind::File(size), properties)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DirectoryEntry {
     pub kind: DirectoryEntryKind,
+    pub properties: DeadProperties,
     pub child: sorted_tree::sorted_tree::TreeReference,
+    pub history: FileHistory,
+    /// The tree that references the current and all earlier contents of a file with a history.
+    pub history_tree: Option<BlobDigest>,
 }
 
 impl sorted_tree::sorted_tree::NodeValue for DirectoryEntry {
-    type Content = DirectoryEntryKind;
+    type Content = DirectoryEntryContent;
 
     fn has_child(_content: &Self::Content) -> bool {
         // Each directory entry points to either a file or a subdirectory. Both are represented by a child reference.
@@ -138,32 +384,89 @@ impl sorted_tree::sorted_tree::NodeValue for DirectoryEntry {
 
     fn from_content(content: Self::Content, child: &Option<BlobDigest>) -> Self {
         match child {
-            Some(reference) => DirectoryEntry {
-                kind: content,
-                child: sorted_tree::sorted_tree::TreeReference::new(*reference),
+            Some(reference) => match content {
+                DirectoryEntryContent::FileWithHistory(size, properties, current, history) => {
+                    DirectoryEntry {
+                        kind: DirectoryEntryKind::File(size),
+                        properties,
+                        child: sorted_tree::sorted_tree::TreeReference::new(current),
+                        history,
+                        history_tree: Some(*reference),
+                    }
+                }
+                _ => {
+                    let (kind, properties) = content.split();
+                    DirectoryEntry {
+                        kind,
+                        properties,
+                        child: sorted_tree::sorted_tree::TreeReference::new(*reference),
+                        history: FileHistory::new(),
+                        history_tree: None,
+                    }
+                }
             },
             None => unreachable!("DirectoryEntry must have a child reference"),
         }
     }
 
     fn to_content(&self) -> Self::Content {
-        self.kind
+        match (self.kind, self.history_tree) {
+            (DirectoryEntryKind::File(size), Some(_)) => DirectoryEntryContent::FileWithHistory(
+                size,
+                self.properties.clone(),
+                *self.child.reference(),
+                self.history.clone(),
+            ),
+            _ => DirectoryEntryContent::new(self.kind, self.properties.clone()),
+        }
     }
 
     fn get_reference(&self) -> Option<BlobDigest> {
-        Some(*self.child.reference())
+        Some(self.history_tree.unwrap_or(*self.child.reference()))
     }
 }
 
 impl DirectoryEntry {
     pub fn new(
         kind: DirectoryEntryKind,
+        properties: DeadProperties,
         content: sorted_tree::sorted_tree::TreeReference,
     ) -> DirectoryEntry {
         DirectoryEntry {
             kind,
+            properties,
             child: content,
+            history: FileHistory::new(),
+            history_tree: None,
+        }
+    }
+
+    /// Stores the history tree of a file with earlier versions, so that the garbage collector keeps them.
+    pub async fn with_history(
+        self,
+        history: FileHistory,
+        storage: &(dyn LoadStoreTree + Send + Sync),
+    ) -> std::result::Result<DirectoryEntry, Box<dyn std::error::Error>> {
+        if history.is_empty() {
+            return Ok(self);
         }
+        assert!(matches!(self.kind, DirectoryEntryKind::File(_)));
+        let references = std::iter::once(*self.child.reference())
+            .chain(history.versions().iter().map(|version| version.content))
+            .collect();
+        let children = TreeChildren::try_from(references)
+            .expect("the number of versions is limited to fit into a tree");
+        let history_tree = storage
+            .store_tree(&HashedTree::from(Arc::new(Tree::new(
+                TreeBlob::empty(),
+                children,
+            ))))
+            .await?;
+        Ok(DirectoryEntry {
+            history,
+            history_tree: Some(history_tree),
+            ..self
+        })
     }
 }
 
@@ -187,18 +490,19 @@ impl std::error::Error for DeserializationError {}
 type ProllyTree = prolly_tree_editable_node::EditableNode<FileName, DirectoryEntry>;
 
 pub async fn serialize_directory(
-    entries: &BTreeMap<FileName, (DirectoryEntryKind, BlobDigest)>,
+    entries: &BTreeMap<FileName, (DirectoryEntryKind, BlobDigest, DeadProperties, FileHistory)>,
     storage: &(dyn LoadStoreTree + Send + Sync),
 ) -> std::result::Result<BlobDigest, Box<dyn std::error::Error>> {
     let mut prolly_tree = ProllyTree::new();
-    for (name, (kind, digest)) in entries.iter() {
-        prolly_tree
-            .insert(
-                name.clone(),
-                DirectoryEntry::new(*kind, sorted_tree::sorted_tree::TreeReference::new(*digest)),
-                storage,
-            )
-            .await?;
+    for (name, (kind, digest, properties, history)) in entries.iter() {
+        let entry = DirectoryEntry::new(
+            *kind,
+            properties.clone(),
+            sorted_tree::sorted_tree::TreeReference::new(*digest),
+        )
+        .with_history(history.clone(), storage)
+        .await?;
+        prolly_tree.insert(name.clone(), entry, storage).await?;
     }
     debug!("Serializing directory with {} entries", entries.len());
     prolly_tree.save(storage).await
@@ -207,12 +511,23 @@ pub async fn serialize_directory(
 pub async fn deserialize_directory(
     storage: &(dyn LoadStoreTree + Send + Sync),
     digest: &BlobDigest,
-) -> Result<BTreeMap<FileName, (DirectoryEntryKind, BlobDigest)>, Box<dyn std::error::Error>> {
+) -> Result<
+    BTreeMap<FileName, (DirectoryEntryKind, BlobDigest, DeadProperties, FileHistory)>,
+    Box<dyn std::error::Error>,
+> {
     let mut prolly_tree = ProllyTree::load(digest, storage).await?;
     let mut result = BTreeMap::new();
     let mut iterator = Iterator::new(&mut prolly_tree, storage);
     while let Some((name, entry)) = iterator.next().await? {
-        result.insert(name, (entry.kind, *entry.child.reference()));
+        result.insert(
+            name,
+            (
+                entry.kind,
+                *entry.child.reference(),
+                entry.properties,
+                entry.history,
+            ),
+        );
     }
     debug!("Deserialized directory with {} entries", result.len());
     Ok(result)
@@ -223,3 +538,12 @@ pub struct SegmentedBlob {
     // redundant size info to detect inconsistencies
     pub size_in_bytes: u64,
 }
+
+/// Stored after [SegmentedBlob] if some of the segments are holes. A hole is a segment of zeros that has no child
+/// reference, so nothing has to be stored for it. Segmented blobs without holes don't have this part at all and are
+/// serialized exactly like before holes existed.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SegmentedBlobHoles {
+    /// ascending positions of the holes among all segments of the tree
+    pub positions: Vec<u32>,
+}
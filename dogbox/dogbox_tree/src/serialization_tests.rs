@@ -1,7 +1,9 @@
 use crate::serialization::{
-    deserialize_directory, serialize_directory, DirectoryEntryKind, FileName, FileNameContent,
-    FileNameError,
+    deserialize_directory, modified_property_name, serialize_directory, DeadProperties,
+    DeadPropertiesError, DeadPropertyName, DeadPropertyValue, DirectoryEntryContent,
+    DirectoryEntryKind, FileHistory, FileName, FileNameContent, FileNameError, FileVersion,
 };
+use astraea::storage::LoadTree;
 use astraea::tree::{BlobDigest, TREE_MAX_CHILDREN};
 use pretty_assertions::assert_eq;
 use std::collections::BTreeMap;
@@ -104,9 +106,19 @@ async fn test_deserialize_directory() {
                 let content = i.to_be_bytes();
                 let digest = BlobDigest::hash(&content);
                 if i.is_multiple_of(3) {
-                    (DirectoryEntryKind::Directory, digest)
+                    (
+                        DirectoryEntryKind::Directory,
+                        digest,
+                        DeadProperties::new(),
+                        FileHistory::new(),
+                    )
                 } else {
-                    (DirectoryEntryKind::File(content.len() as u64), digest)
+                    (
+                        DirectoryEntryKind::File(content.len() as u64),
+                        digest,
+                        DeadProperties::new(),
+                        FileHistory::new(),
+                    )
                 }
             })
         })
@@ -123,3 +135,199 @@ async fn test_deserialize_directory() {
     let deserialized = deserialize_directory(&storage, &digest).await.unwrap();
     assert_eq!(original, deserialized);
 }
+
+fn example_dead_properties() -> DeadProperties {
+    let mut properties = DeadProperties::new();
+    properties.set(
+        DeadPropertyName::new(
+            Some("http://example.com/neon/litmus/".to_string()),
+            "prop0".to_string(),
+        ),
+        DeadPropertyValue::new(Some("ns1".to_string()), Some(b"value0".to_vec())),
+    );
+    properties.set(
+        DeadPropertyName::new(None, "no-namespace".to_string()),
+        DeadPropertyValue::new(None, None),
+    );
+    properties
+}
+
+#[test_log::test(tokio::test)]
+async fn test_deserialize_directory_with_dead_properties() {
+    let storage = astraea::storage::InMemoryTreeStorage::new(Mutex::new(BTreeMap::new()));
+    let original = BTreeMap::from([
+        (
+            FileName::try_from("dir").unwrap(),
+            (
+                DirectoryEntryKind::Directory,
+                BlobDigest::hash(&[1]),
+                example_dead_properties(),
+                FileHistory::new(),
+            ),
+        ),
+        (
+            FileName::try_from("file.txt").unwrap(),
+            (
+                DirectoryEntryKind::File(12),
+                BlobDigest::hash(&[2]),
+                example_dead_properties(),
+                FileHistory::new(),
+            ),
+        ),
+        (
+            FileName::try_from("without_properties.txt").unwrap(),
+            (
+                DirectoryEntryKind::File(0),
+                BlobDigest::hash(&[3]),
+                DeadProperties::new(),
+                FileHistory::new(),
+            ),
+        ),
+    ]);
+    let digest = serialize_directory(&original, &storage).await.unwrap();
+    let deserialized = deserialize_directory(&storage, &digest).await.unwrap();
+    assert_eq!(original, deserialized);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_deserialize_directory_with_file_history() {
+    let storage = astraea::storage::InMemoryTreeStorage::new(Mutex::new(BTreeMap::new()));
+    let mut history = FileHistory::new();
+    let saved_at = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(100);
+    history.push(FileVersion::new(BlobDigest::hash(&[10]), 10, saved_at));
+    history.push(FileVersion::new(
+        BlobDigest::hash(&[11]),
+        11,
+        saved_at + std::time::Duration::from_secs(1),
+    ));
+    let original = BTreeMap::from([
+        (
+            FileName::try_from("file.txt").unwrap(),
+            (
+                DirectoryEntryKind::File(12),
+                BlobDigest::hash(&[12]),
+                example_dead_properties(),
+                history,
+            ),
+        ),
+        (
+            FileName::try_from("without_history.txt").unwrap(),
+            (
+                DirectoryEntryKind::File(0),
+                BlobDigest::hash(&[3]),
+                DeadProperties::new(),
+                FileHistory::new(),
+            ),
+        ),
+    ]);
+    let digest = serialize_directory(&original, &storage).await.unwrap();
+    // the directory itself and the history tree
+    assert_eq!(2, storage.number_of_trees().await);
+    let deserialized = deserialize_directory(&storage, &digest).await.unwrap();
+    assert_eq!(original, deserialized);
+
+    let directory_tree = storage.load_tree(&digest).await.unwrap().hash().unwrap();
+    let history_tree_digest = directory_tree.tree().children().references()[0];
+    let history_tree = storage
+        .load_tree(&history_tree_digest)
+        .await
+        .unwrap()
+        .hash()
+        .unwrap();
+    assert_eq!(
+        &[
+            BlobDigest::hash(&[12]),
+            BlobDigest::hash(&[10]),
+            BlobDigest::hash(&[11])
+        ],
+        history_tree.tree().children().references()
+    );
+}
+
+#[test_log::test]
+fn test_file_history_limits() {
+    let mut history = FileHistory::new();
+    let start = std::time::SystemTime::UNIX_EPOCH;
+    for index in 0..(FileHistory::MAX_VERSIONS as u64 + 3) {
+        history.push(FileVersion::new(
+            BlobDigest::hash(&index.to_be_bytes()),
+            index,
+            start + std::time::Duration::from_secs(index),
+        ));
+    }
+    assert_eq!(FileHistory::MAX_VERSIONS, history.versions().len());
+    assert_eq!(3, history.versions()[0].size);
+
+    history.keep_newest(10);
+    assert_eq!(
+        (FileHistory::MAX_VERSIONS as u64 - 7..FileHistory::MAX_VERSIONS as u64 + 3)
+            .collect::<Vec<_>>(),
+        history
+            .versions()
+            .iter()
+            .map(|version| version.size)
+            .collect::<Vec<_>>()
+    );
+
+    history.remove_saved_before(
+        start + std::time::Duration::from_secs(FileHistory::MAX_VERSIONS as u64),
+    );
+    assert_eq!(3, history.versions().len());
+    history.remove_saved_before(start + std::time::Duration::from_secs(1000));
+    assert!(history.is_empty());
+}
+
+#[test_log::test]
+fn test_directory_entry_content_is_compatible_with_directory_entry_kind() {
+    for kind in [
+        DirectoryEntryKind::Directory,
+        DirectoryEntryKind::File(1234),
+    ] {
+        let content = DirectoryEntryContent::new(kind, DeadProperties::new());
+        assert_eq!(
+            postcard::to_stdvec(&kind).unwrap(),
+            postcard::to_stdvec(&content).unwrap()
+        );
+        assert_eq!((kind, DeadProperties::new()), content.split());
+    }
+}
+
+#[test_log::test]
+fn test_dead_properties_check_size() {
+    let mut properties = example_dead_properties();
+    assert_eq!(Ok(()), properties.check_size());
+    properties.set(
+        DeadPropertyName::new(None, "large".to_string()),
+        DeadPropertyValue::new(None, Some(vec![b'a'; DeadProperties::MAX_SIZE_IN_BYTES])),
+    );
+    assert_eq!(
+        Err(DeadPropertiesError::TooLarge {
+            size_in_bytes: properties.size_in_bytes()
+        }),
+        properties.check_size()
+    );
+}
+
+#[test_log::test]
+fn test_dead_properties_modified() {
+    let mut properties = example_dead_properties();
+    assert_eq!(None, properties.modified());
+    let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::new(1_700_000_000, 5);
+    properties.set_modified(modified);
+    assert_eq!(Some(modified), properties.modified());
+    assert_eq!(
+        Some(
+            &b"<dogbox:modified xmlns:dogbox=\"urn:nonlocality:dogbox\">1700000000.000000005</dogbox:modified>"[..]
+        ),
+        properties
+            .get(&modified_property_name())
+            .unwrap()
+            .xml
+            .as_deref()
+    );
+    properties.set_modified(std::time::SystemTime::UNIX_EPOCH - std::time::Duration::from_secs(1));
+    assert_eq!(
+        Some(std::time::SystemTime::UNIX_EPOCH),
+        properties.modified()
+    );
+}
@@ -0,0 +1,327 @@
+use crate::{Error, NormalizedPath, OpenDirectoryStatus, PathSplitLeftResult, Result, Stream};
+use astraea::{storage::LoadStoreTree, tree::BlobDigest};
+use async_stream::stream;
+use dogbox_tree::serialization::{deserialize_directory, DirectoryEntryKind, FileName};
+use std::{collections::BTreeMap, pin::Pin, sync::Arc};
+use tracing::{debug, warn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeChangeKind {
+    Created,
+    Written,
+    /// The entry was moved from another path without changing its content.
+    Renamed {
+        from: NormalizedPath,
+    },
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeChange {
+    pub path: NormalizedPath,
+    pub kind: TreeChangeKind,
+    /// For removals, this is the kind of the entry before it was removed.
+    pub entry_kind: DirectoryEntryKind,
+    /// The digest after the change. Removed entries don't have one.
+    pub digest: Option<BlobDigest>,
+}
+
+impl TreeChange {
+    pub fn new(
+        path: NormalizedPath,
+        kind: TreeChangeKind,
+        entry_kind: DirectoryEntryKind,
+        digest: Option<BlobDigest>,
+    ) -> Self {
+        Self {
+            path,
+            kind,
+            entry_kind,
+            digest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum EntrySnapshot {
+    File(u64, BlobDigest),
+    Directory(Arc<DirectorySnapshot>),
+}
+
+impl EntrySnapshot {
+    fn kind(&self) -> DirectoryEntryKind {
+        match self {
+            EntrySnapshot::File(size, _digest) => DirectoryEntryKind::File(*size),
+            EntrySnapshot::Directory(_directory) => DirectoryEntryKind::Directory,
+        }
+    }
+
+    fn digest(&self) -> BlobDigest {
+        match self {
+            EntrySnapshot::File(_size, digest) => *digest,
+            EntrySnapshot::Directory(directory) => directory.digest,
+        }
+    }
+}
+
+/// The state of a saved directory including all of its descendants. Unchanged subdirectories are shared between
+/// snapshots, so the previous state stays available even after the storage collected it as garbage.
+#[derive(Debug, PartialEq)]
+pub(crate) struct DirectorySnapshot {
+    digest: BlobDigest,
+    entries: BTreeMap<FileName, EntrySnapshot>,
+}
+
+type SnapshotFuture<'a, T> = Pin<Box<dyn std::future::Future<Output = Result<T>> + Send + 'a>>;
+
+fn load_snapshot<'a>(
+    storage: &'a (dyn LoadStoreTree + Send + Sync),
+    digest: BlobDigest,
+    previous: Option<Arc<DirectorySnapshot>>,
+) -> SnapshotFuture<'a, Arc<DirectorySnapshot>> {
+    Box::pin(async move {
+        if let Some(previous) = &previous {
+            if previous.digest == digest {
+                return Ok(previous.clone());
+            }
+        }
+        let deserialized = deserialize_directory(storage, &digest)
+            .await
+            .map_err(|error| Error::OtherDeserializationError(error.to_string()))?;
+        let mut entries = BTreeMap::new();
+        for (name, (kind, child_digest, _properties, _history)) in deserialized {
+            let entry =
+                match kind {
+                    DirectoryEntryKind::Directory => {
+                        let previous_child = previous.as_ref().and_then(|previous| match previous
+                            .entries
+                            .get(&name)
+                        {
+                            Some(EntrySnapshot::Directory(directory)) => Some(directory.clone()),
+                            _ => None,
+                        });
+                        EntrySnapshot::Directory(
+                            load_snapshot(storage, child_digest, previous_child).await?,
+                        )
+                    }
+                    DirectoryEntryKind::File(size) => EntrySnapshot::File(size, child_digest),
+                };
+            entries.insert(name, entry);
+        }
+        Ok(Arc::new(DirectorySnapshot { digest, entries }))
+    })
+}
+
+/// Loads the snapshot of the directory at `path` below the directory `digest`. Only the directories on the way are
+/// loaded in addition to the subtree. Returns `None` if there is no directory at `path`.
+pub(crate) fn load_subtree<'a>(
+    storage: &'a (dyn LoadStoreTree + Send + Sync),
+    digest: BlobDigest,
+    path: NormalizedPath,
+    previous: Option<Arc<DirectorySnapshot>>,
+) -> SnapshotFuture<'a, Option<Arc<DirectorySnapshot>>> {
+    Box::pin(async move {
+        let (head, rest) = match path.split_left() {
+            PathSplitLeftResult::Root => {
+                return Ok(Some(load_snapshot(storage, digest, previous).await?))
+            }
+            PathSplitLeftResult::Leaf(head) => (head, NormalizedPath::root()),
+            PathSplitLeftResult::Directory(head, rest) => (head, rest),
+        };
+        let deserialized = deserialize_directory(storage, &digest)
+            .await
+            .map_err(|error| Error::OtherDeserializationError(error.to_string()))?;
+        match deserialized.get(&head) {
+            Some((DirectoryEntryKind::Directory, child_digest, _properties, _history)) => {
+                load_subtree(storage, *child_digest, rest, previous).await
+            }
+            Some((DirectoryEntryKind::File(_), _, _, _)) | None => Ok(None),
+        }
+    })
+}
+
+#[derive(Default)]
+struct Differences {
+    removed: Vec<(NormalizedPath, EntrySnapshot)>,
+    created: Vec<(NormalizedPath, EntrySnapshot)>,
+    written: Vec<TreeChange>,
+}
+
+fn find_differences(
+    old: Option<&DirectorySnapshot>,
+    new: Option<&DirectorySnapshot>,
+    path: &NormalizedPath,
+    differences: &mut Differences,
+) {
+    if let (Some(old), Some(new)) = (old, new) {
+        if old.digest == new.digest {
+            return;
+        }
+    }
+    let empty = BTreeMap::new();
+    let old_entries = old.map(|old| &old.entries).unwrap_or(&empty);
+    let new_entries = new.map(|new| &new.entries).unwrap_or(&empty);
+    for (name, old_entry) in old_entries.iter() {
+        let entry_path = path.clone().join(name.clone());
+        match (old_entry, new_entries.get(name)) {
+            (_, None) => differences.removed.push((entry_path, old_entry.clone())),
+            (
+                EntrySnapshot::Directory(old_directory),
+                Some(EntrySnapshot::Directory(new_directory)),
+            ) => find_differences(
+                Some(old_directory),
+                Some(new_directory),
+                &entry_path,
+                differences,
+            ),
+            (
+                EntrySnapshot::File(_, old_digest),
+                Some(new_entry @ EntrySnapshot::File(_, new_digest)),
+            ) => {
+                if old_digest != new_digest {
+                    differences.written.push(TreeChange::new(
+                        entry_path,
+                        TreeChangeKind::Written,
+                        new_entry.kind(),
+                        Some(*new_digest),
+                    ));
+                }
+            }
+            (_, Some(new_entry)) => {
+                differences
+                    .removed
+                    .push((entry_path.clone(), old_entry.clone()));
+                differences.created.push((entry_path, new_entry.clone()));
+            }
+        }
+    }
+    for (name, new_entry) in new_entries.iter() {
+        if !old_entries.contains_key(name) {
+            differences
+                .created
+                .push((path.clone().join(name.clone()), new_entry.clone()));
+        }
+    }
+}
+
+fn report_created(path: NormalizedPath, entry: &EntrySnapshot, changes: &mut Vec<TreeChange>) {
+    changes.push(TreeChange::new(
+        path.clone(),
+        TreeChangeKind::Created,
+        entry.kind(),
+        Some(entry.digest()),
+    ));
+    if let EntrySnapshot::Directory(directory) = entry {
+        for (name, child) in directory.entries.iter() {
+            report_created(path.clone().join(name.clone()), child, changes);
+        }
+    }
+}
+
+/// Describes how to get from `old` to `new`. An entry that disappeared in one place and appeared with the same kind
+/// and digest somewhere else is reported as a rename. Content-addressing can't tell identical entries apart, so for
+/// example one of several empty files may be reported as the origin of a rename.
+pub(crate) fn compare_snapshots(
+    old: Option<&DirectorySnapshot>,
+    new: Option<&DirectorySnapshot>,
+    path: &NormalizedPath,
+) -> Vec<TreeChange> {
+    let mut differences = Differences::default();
+    find_differences(old, new, path, &mut differences);
+    let mut renamed = Vec::new();
+    let mut created = Vec::new();
+    let mut removed = differences.removed;
+    for (created_path, created_entry) in differences.created {
+        match removed
+            .iter()
+            .position(|(_path, removed_entry)| *removed_entry == created_entry)
+        {
+            Some(index) => {
+                let (from, _entry) = removed.remove(index);
+                renamed.push(TreeChange::new(
+                    created_path,
+                    TreeChangeKind::Renamed { from },
+                    created_entry.kind(),
+                    Some(created_entry.digest()),
+                ));
+            }
+            None => report_created(created_path, &created_entry, &mut created),
+        }
+    }
+    // Removals come first because a removed entry may have had the same path as a renamed or created one.
+    let mut changes: Vec<TreeChange> = removed
+        .into_iter()
+        .map(|(removed_path, removed_entry)| {
+            TreeChange::new(
+                removed_path,
+                TreeChangeKind::Removed,
+                removed_entry.kind(),
+                None,
+            )
+        })
+        .collect();
+    changes.extend(renamed);
+    changes.extend(created);
+    changes.extend(differences.written);
+    changes
+}
+
+/// Reports the changes below `directory` every time a new version of the root has been saved. Changes that haven't
+/// been saved yet are not visible. The stream ends when the root directory is dropped.
+pub async fn watch_changes(
+    storage: Arc<dyn LoadStoreTree + Send + Sync>,
+    mut root_status: tokio::sync::watch::Receiver<OpenDirectoryStatus>,
+    directory: NormalizedPath,
+) -> Stream<TreeChange> {
+    // The current state has to be loaded before returning, otherwise changes made before polling the stream for the
+    // first time would be missed.
+    let mut previous_root_digest = root_status.borrow_and_update().digest.last_known_digest;
+    let mut previous = match load_subtree(
+        storage.as_ref(),
+        previous_root_digest,
+        directory.clone(),
+        None,
+    )
+    .await
+    {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            warn!(
+                "Could not load the initial state of the watched directory: {}",
+                error
+            );
+            None
+        }
+    };
+    Box::pin(stream! {
+        while root_status.changed().await.is_ok() {
+            let root_digest = root_status.borrow_and_update().digest.last_known_digest;
+            if root_digest == previous_root_digest {
+                continue;
+            }
+            let current = match load_subtree(
+                storage.as_ref(),
+                root_digest,
+                directory.clone(),
+                previous.clone(),
+            )
+            .await
+            {
+                Ok(loaded) => loaded,
+                Err(error) => {
+                    // The root may have changed again and the storage may have collected this version already. The
+                    // next version will be compared with the last one that could be loaded.
+                    warn!("Could not load root {} to find changes: {}", &root_digest, error);
+                    continue;
+                }
+            };
+            previous_root_digest = root_digest;
+            let changes = compare_snapshots(previous.as_deref(), current.as_deref(), &directory);
+            debug!("Found {} changes in root {}", changes.len(), &root_digest);
+            previous = current;
+            for change in changes {
+                yield change;
+            }
+        }
+    })
+}
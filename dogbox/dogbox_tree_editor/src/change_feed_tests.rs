@@ -0,0 +1,141 @@
+use crate::{
+    test_helpers::{create_editor, path, save_after_change, write_file},
+    NormalizedPath, Stream, TreeChange, TreeChangeKind,
+};
+use dogbox_tree::serialization::DirectoryEntryKind;
+use futures::StreamExt;
+use pretty_assertions::assert_eq;
+
+async fn next_changes(changes: &mut Stream<TreeChange>, count: usize) -> Vec<TreeChange> {
+    let mut result = Vec::new();
+    while result.len() < count {
+        match tokio::time::timeout(std::time::Duration::from_secs(10), changes.next()).await {
+            Ok(Some(change)) => result.push(change),
+            Ok(None) => panic!("The change feed ended unexpectedly"),
+            Err(_elapsed) => panic!("Expected {count} changes, but only got {result:?}"),
+        }
+    }
+    result
+}
+
+async fn expect_no_more_changes(changes: &mut Stream<TreeChange>) {
+    if let Ok(change) =
+        tokio::time::timeout(std::time::Duration::from_millis(50), changes.next()).await
+    {
+        panic!("Unexpected change: {change:?}");
+    }
+}
+
+fn change_kinds(changes: &[TreeChange]) -> Vec<(NormalizedPath, TreeChangeKind)> {
+    changes
+        .iter()
+        .map(|change| (change.path.clone(), change.kind.clone()))
+        .collect()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_change_feed_create_write_rename_remove() {
+    let (root, editor) = create_editor().await;
+    let mut root_status = root.watch().await;
+    let mut changes = editor.watch_changes(NormalizedPath::root()).await;
+
+    editor.create_directory(path("dir")).await.unwrap();
+    write_file(&editor, "dir/a.txt", b"hello").await;
+    save_after_change(&root, &mut root_status).await;
+    let created = next_changes(&mut changes, 2).await;
+    assert_eq!(
+        vec![
+            (path("dir"), TreeChangeKind::Created),
+            (path("dir/a.txt"), TreeChangeKind::Created),
+        ],
+        change_kinds(&created)
+    );
+    assert_eq!(DirectoryEntryKind::File(5), created[1].entry_kind);
+    let content_digest = created[1].digest.unwrap();
+
+    write_file(&editor, "dir/a.txt", b"world").await;
+    save_after_change(&root, &mut root_status).await;
+    let written = next_changes(&mut changes, 1).await;
+    assert_eq!(
+        vec![(path("dir/a.txt"), TreeChangeKind::Written)],
+        change_kinds(&written)
+    );
+    assert_ne!(Some(content_digest), written[0].digest);
+
+    editor
+        .rename(path("dir/a.txt"), path("b.txt"))
+        .await
+        .unwrap();
+    save_after_change(&root, &mut root_status).await;
+    let renamed = next_changes(&mut changes, 1).await;
+    assert_eq!(
+        vec![TreeChange::new(
+            path("b.txt"),
+            TreeChangeKind::Renamed {
+                from: path("dir/a.txt")
+            },
+            DirectoryEntryKind::File(5),
+            written[0].digest,
+        )],
+        renamed
+    );
+
+    editor.remove(path("dir")).await.unwrap();
+    save_after_change(&root, &mut root_status).await;
+    assert_eq!(
+        vec![TreeChange::new(
+            path("dir"),
+            TreeChangeKind::Removed,
+            DirectoryEntryKind::Directory,
+            None,
+        )],
+        next_changes(&mut changes, 1).await
+    );
+    expect_no_more_changes(&mut changes).await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_change_feed_of_subdirectory() {
+    let (root, editor) = create_editor().await;
+    let mut root_status = root.watch().await;
+    editor.create_directory(path("watched")).await.unwrap();
+    save_after_change(&root, &mut root_status).await;
+    let mut changes = editor.watch_changes(path("watched")).await;
+
+    write_file(&editor, "elsewhere.txt", b"ignored").await;
+    save_after_change(&root, &mut root_status).await;
+    expect_no_more_changes(&mut changes).await;
+
+    editor
+        .copy(path("elsewhere.txt"), path("watched/copy.txt"))
+        .await
+        .unwrap();
+    save_after_change(&root, &mut root_status).await;
+    assert_eq!(
+        vec![(path("watched/copy.txt"), TreeChangeKind::Created)],
+        change_kinds(&next_changes(&mut changes, 1).await)
+    );
+
+    // removing the watched directory looks like removing everything in it
+    editor.remove(path("watched")).await.unwrap();
+    save_after_change(&root, &mut root_status).await;
+    assert_eq!(
+        vec![(path("watched/copy.txt"), TreeChangeKind::Removed)],
+        change_kinds(&next_changes(&mut changes, 1).await)
+    );
+    expect_no_more_changes(&mut changes).await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_change_feed_ends_with_the_root() {
+    let (root, editor) = create_editor().await;
+    let mut changes = editor.watch_changes(NormalizedPath::root()).await;
+    drop(editor);
+    drop(root);
+    assert_eq!(
+        None,
+        tokio::time::timeout(std::time::Duration::from_secs(10), changes.next())
+            .await
+            .unwrap()
+    );
+}
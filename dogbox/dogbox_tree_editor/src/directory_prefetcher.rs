@@ -0,0 +1,217 @@
+use astraea::{
+    storage::{DelayedHashedTree, LoadError, LoadStoreTree, LoadTree, StoreError, StoreTree},
+    tree::{BlobDigest, HashedTree},
+};
+use async_trait::async_trait;
+use cached::Cached;
+use dogbox_tree::serialization::FileName;
+use std::{collections::VecDeque, sync::Arc};
+use tracing::{debug, warn};
+
+/// How many prefetched trees a directory keeps until they are read.
+const MAX_PREFETCHED_TREES: usize = 64;
+
+/// How many files ahead of a streak of files opened in listing order are prefetched at most.
+const MAX_PREFETCH_LENGTH: usize = 16;
+
+/// Files up to this size are prefetched together with their small siblings.
+pub const SMALL_FILE_MAX_SIZE: u64 = 16 * 1024;
+
+const MAX_SMALL_SIBLINGS_PREFETCHED: usize = 8;
+
+const MAX_RECENTLY_OPENED_TRACKED: usize = 8;
+
+/// Counts prefetched trees (file blocks or directory nodes) and how many of them were actually read afterwards.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PrefetchStats {
+    pub prefetched: u64,
+    pub hits: u64,
+}
+
+impl PrefetchStats {
+    pub fn new(prefetched: u64, hits: u64) -> Self {
+        Self { prefetched, hits }
+    }
+
+    pub fn add(&mut self, other: &PrefetchStats) {
+        self.prefetched += other.prefetched;
+        self.hits += other.hits;
+    }
+
+    /// [None] if nothing has been prefetched yet.
+    pub fn hit_rate(&self) -> Option<f64> {
+        if self.prefetched == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / self.prefetched as f64)
+        }
+    }
+}
+
+/// A regular file next to the one that is being opened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiblingFile {
+    pub name: FileName,
+    pub size: u64,
+    /// [None] if the file is open already, so there is nothing to prefetch.
+    pub digest: Option<BlobDigest>,
+}
+
+/// Prefetches content based on how the files of one directory are accessed:
+/// * when files are opened in listing order, the files that come next are prefetched
+/// * opening a small file prefetches its small siblings
+/// * listing the directory warms the nodes of its subdirectories
+///
+/// The [crate::Prefetcher] of every open file takes care of the blocks within that file. The directory passes this
+/// prefetcher as the storage to its files and subdirectories, so that prefetched trees are found when they are loaded.
+#[derive(Debug)]
+pub struct DirectoryPrefetcher {
+    next: Arc<dyn LoadStoreTree + Send + Sync>,
+    prefetched: tokio::sync::Mutex<cached::stores::SizedCache<BlobDigest, HashedTree>>,
+    recently_opened: std::sync::Mutex<VecDeque<FileName>>,
+    stats: std::sync::Mutex<PrefetchStats>,
+}
+
+impl DirectoryPrefetcher {
+    pub fn new(next: Arc<dyn LoadStoreTree + Send + Sync>) -> Self {
+        Self {
+            next,
+            prefetched: tokio::sync::Mutex::new(cached::stores::SizedCache::with_size(
+                MAX_PREFETCHED_TREES,
+            )),
+            recently_opened: std::sync::Mutex::new(VecDeque::new()),
+            stats: std::sync::Mutex::new(PrefetchStats::default()),
+        }
+    }
+
+    pub fn stats(&self) -> PrefetchStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// Remembers that `opened` was opened and decides what to prefetch because of that. `before` and `after` are the
+    /// neighbouring regular files in listing order, nearest first.
+    pub fn find_files_to_prefetch(
+        &self,
+        opened: &SiblingFile,
+        before: &[SiblingFile],
+        after: &[SiblingFile],
+    ) -> Vec<BlobDigest> {
+        let streak_length = {
+            let mut recently_opened = self.recently_opened.lock().unwrap();
+            let previous_in_streak = recently_opened
+                .iter()
+                .rev()
+                .zip(before.iter())
+                .take_while(|(previous, sibling)| **previous == sibling.name)
+                .count();
+            recently_opened.push_back(opened.name.clone());
+            if recently_opened.len() > MAX_RECENTLY_OPENED_TRACKED {
+                recently_opened.pop_front();
+            }
+            previous_in_streak + 1
+        };
+        let mut digests = Vec::new();
+        if streak_length >= 2 {
+            let prefetch_length = std::cmp::min(MAX_PREFETCH_LENGTH, streak_length * 2);
+            debug!(
+                "{} files were opened in listing order, prefetching {} more",
+                streak_length, prefetch_length
+            );
+            digests.extend(
+                after
+                    .iter()
+                    .take(prefetch_length)
+                    .filter_map(|sibling| sibling.digest),
+            );
+        }
+        if opened.size <= SMALL_FILE_MAX_SIZE {
+            let nearest_first = after
+                .iter()
+                .map(Some)
+                .chain(std::iter::repeat(None))
+                .zip(before.iter().map(Some).chain(std::iter::repeat(None)))
+                .take_while(|pair| *pair != (None, None))
+                .flat_map(|(after, before)| after.into_iter().chain(before));
+            digests.extend(
+                nearest_first
+                    .filter(|sibling| sibling.size <= SMALL_FILE_MAX_SIZE)
+                    .filter_map(|sibling| sibling.digest)
+                    .take(MAX_SMALL_SIBLINGS_PREFETCHED),
+            );
+        }
+        let mut seen = std::collections::BTreeSet::new();
+        digests.retain(|digest| seen.insert(*digest));
+        digests
+    }
+
+    /// Loads the trees without waiting for them.
+    pub fn prefetch_in_background(self: &Arc<Self>, digests: Vec<BlobDigest>) {
+        if digests.is_empty() {
+            return;
+        }
+        let prefetcher = self.clone();
+        tokio::spawn(async move {
+            futures::future::join_all(digests.iter().map(|digest| prefetcher.prefetch(digest)))
+                .await;
+        });
+    }
+
+    async fn prefetch(&self, digest: &BlobDigest) {
+        if self.prefetched.lock().await.cache_get(digest).is_some() {
+            return;
+        }
+        let loaded = match self.next.load_tree(digest).await {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                warn!("Could not prefetch {}: {:?}", digest, error);
+                return;
+            }
+        };
+        match loaded.hash() {
+            Some(hashed_tree) => {
+                self.prefetched.lock().await.cache_set(*digest, hashed_tree);
+                self.stats.lock().unwrap().prefetched += 1;
+            }
+            None => warn!("Could not prefetch {} because of a hash mismatch", digest),
+        }
+    }
+
+    /// Returns the number of prefetched trees that were dropped without having been read.
+    pub async fn drop_all_read_caches(&self) -> usize {
+        let mut prefetched_locked = self.prefetched.lock().await;
+        let dropped = prefetched_locked.cache_size();
+        prefetched_locked.cache_clear();
+        dropped
+    }
+}
+
+#[async_trait]
+impl LoadTree for DirectoryPrefetcher {
+    async fn load_tree(
+        &self,
+        reference: &BlobDigest,
+    ) -> std::result::Result<DelayedHashedTree, LoadError> {
+        // Whoever reads a prefetched tree keeps it, so we don't have to.
+        let found = self.prefetched.lock().await.cache_remove(reference);
+        match found {
+            Some(hashed_tree) => {
+                self.stats.lock().unwrap().hits += 1;
+                Ok(DelayedHashedTree::immediate(hashed_tree))
+            }
+            None => self.next.load_tree(reference).await,
+        }
+    }
+
+    async fn approximate_tree_count(&self) -> std::result::Result<u64, StoreError> {
+        self.next.approximate_tree_count().await
+    }
+}
+
+#[async_trait]
+impl StoreTree for DirectoryPrefetcher {
+    async fn store_tree(&self, tree: &HashedTree) -> std::result::Result<BlobDigest, StoreError> {
+        self.next.store_tree(tree).await
+    }
+}
+
+impl LoadStoreTree for DirectoryPrefetcher {}
@@ -0,0 +1,264 @@
+use crate::{
+    directory_prefetcher::{SiblingFile, SMALL_FILE_MAX_SIZE},
+    DirectoryPrefetcher, FileCreationMode, NormalizedPath, OpenDirectory, PrefetchStats,
+    TreeEditor, WallClock,
+};
+use astraea::{
+    storage::{InMemoryTreeStorage, LoadStoreTree, LoadTree, StoreTree},
+    tree::{BlobDigest, HashedTree, Tree, TreeBlob, TreeChildren, TREE_BLOB_MAX_LENGTH},
+};
+use dogbox_tree::serialization::FileName;
+use pretty_assertions::assert_eq;
+use std::{sync::Arc, time::SystemTime};
+
+fn name(name: &str) -> FileName {
+    FileName::try_from(name).unwrap()
+}
+
+fn path(path: &str) -> NormalizedPath {
+    NormalizedPath::try_from(relative_path::RelativePath::new(path)).unwrap()
+}
+
+fn digest(value: u8) -> BlobDigest {
+    BlobDigest::new(&[value; 64])
+}
+
+fn sibling(file_name: &str, size: u64, digest_value: Option<u8>) -> SiblingFile {
+    SiblingFile {
+        name: name(file_name),
+        size,
+        digest: digest_value.map(digest),
+    }
+}
+
+const LARGE: u64 = SMALL_FILE_MAX_SIZE + 1;
+
+fn create_prefetcher() -> DirectoryPrefetcher {
+    DirectoryPrefetcher::new(Arc::new(InMemoryTreeStorage::empty()))
+}
+
+async fn wait_for_prefetched(prefetch_stats: impl Fn() -> PrefetchStats, expected: u64) {
+    for _ in 0..1000 {
+        if prefetch_stats().prefetched >= expected {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+    panic!("Prefetching did not finish: {:?}", prefetch_stats());
+}
+
+#[test_log::test]
+fn test_prefetch_stats_hit_rate() {
+    assert_eq!(None, PrefetchStats::default().hit_rate());
+    let mut stats = PrefetchStats::new(4, 1);
+    stats.add(&PrefetchStats::new(4, 3));
+    assert_eq!(PrefetchStats::new(8, 4), stats);
+    assert_eq!(Some(0.5), stats.hit_rate());
+}
+
+#[test_log::test]
+fn test_find_files_to_prefetch_single_large_file() {
+    let prefetcher = create_prefetcher();
+    assert_eq!(
+        Vec::<BlobDigest>::new(),
+        prefetcher.find_files_to_prefetch(
+            &sibling("b", LARGE, Some(2)),
+            &[sibling("a", LARGE, Some(1))],
+            &[sibling("c", LARGE, Some(3))],
+        )
+    );
+}
+
+#[test_log::test]
+fn test_find_files_to_prefetch_listing_order() {
+    let prefetcher = create_prefetcher();
+    let files: Vec<SiblingFile> = (0..10)
+        .map(|index| sibling(&format!("{index}"), LARGE, Some(index)))
+        .collect();
+    let neighbours = |index: usize| {
+        let mut before = files[..index].to_vec();
+        before.reverse();
+        (before, files[(index + 1)..].to_vec())
+    };
+    let (before, after) = neighbours(0);
+    assert_eq!(
+        Vec::<BlobDigest>::new(),
+        prefetcher.find_files_to_prefetch(&files[0], &before, &after)
+    );
+    let (before, after) = neighbours(1);
+    assert_eq!(
+        vec![digest(2), digest(3), digest(4), digest(5)],
+        prefetcher.find_files_to_prefetch(&files[1], &before, &after)
+    );
+    let (before, after) = neighbours(2);
+    assert_eq!(
+        (3..9).map(digest).collect::<Vec<_>>(),
+        prefetcher.find_files_to_prefetch(&files[2], &before, &after)
+    );
+    // jumping elsewhere ends the streak
+    let (before, after) = neighbours(7);
+    assert_eq!(
+        Vec::<BlobDigest>::new(),
+        prefetcher.find_files_to_prefetch(&files[7], &before, &after)
+    );
+}
+
+#[test_log::test]
+fn test_find_files_to_prefetch_small_siblings() {
+    let prefetcher = create_prefetcher();
+    assert_eq!(
+        vec![digest(3), digest(2), digest(1)],
+        prefetcher.find_files_to_prefetch(
+            &sibling("c", 10, Some(10)),
+            &[
+                sibling("b", 10, Some(2)),
+                sibling("a", 10, Some(1)),
+                sibling("0", LARGE, Some(0)),
+            ],
+            &[
+                sibling("d", 10, Some(3)),
+                // open already
+                sibling("e", 10, None),
+                sibling("f", LARGE, Some(4)),
+            ],
+        )
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_prefetched_tree_is_loaded_once() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let tree = HashedTree::from(Arc::new(Tree::new(
+        TreeBlob::try_from(bytes::Bytes::from_static(b"hello")).unwrap(),
+        TreeChildren::empty(),
+    )));
+    let reference = storage.store_tree(&tree).await.unwrap();
+    let prefetcher = Arc::new(DirectoryPrefetcher::new(storage));
+    prefetcher.prefetch_in_background(vec![reference]);
+    wait_for_prefetched(|| prefetcher.stats(), 1).await;
+    for _ in 0..2 {
+        let loaded = prefetcher.load_tree(&reference).await.unwrap();
+        assert_eq!(Some(tree.clone()), loaded.hash());
+    }
+    // the second load went to the storage
+    assert_eq!(PrefetchStats::new(1, 1), prefetcher.stats());
+    assert_eq!(0, prefetcher.drop_all_read_caches().await);
+}
+
+async fn create_saved_directory(
+    files: &[(&str, usize)],
+    subdirectories: &[&str],
+) -> (Arc<OpenDirectory>, TreeEditor) {
+    let clock: WallClock = Arc::new(|| SystemTime::UNIX_EPOCH);
+    let storage: Arc<dyn LoadStoreTree + Send + Sync> = Arc::new(InMemoryTreeStorage::empty());
+    let created = Arc::new(
+        OpenDirectory::create_directory(
+            std::path::PathBuf::from("/"),
+            storage.clone(),
+            clock.clone(),
+            1,
+        )
+        .await
+        .unwrap(),
+    );
+    let editor = TreeEditor::new(created.clone(), None);
+    for subdirectory in subdirectories {
+        editor.create_directory(path(subdirectory)).await.unwrap();
+    }
+    for (file_name, size) in files {
+        let opened = editor
+            .open_file(path(file_name), FileCreationMode::create_new())
+            .await
+            .unwrap();
+        opened
+            .write_bytes(
+                &opened.get_write_permission(),
+                0,
+                bytes::Bytes::from(vec![b'x'; *size]),
+            )
+            .await
+            .unwrap();
+        opened.flush().await.unwrap();
+    }
+    let status = created.request_save().await.unwrap();
+    assert!(status.digest.is_digest_up_to_date);
+    let loaded = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage,
+        &status.digest.last_known_digest,
+        SystemTime::UNIX_EPOCH,
+        clock,
+        1,
+    )
+    .await
+    .unwrap();
+    (loaded.clone(), TreeEditor::new(loaded, None))
+}
+
+async fn read_whole_file(editor: &TreeEditor, file_name: &str) -> usize {
+    let opened = editor
+        .open_file(path(file_name), FileCreationMode::open_existing())
+        .await
+        .unwrap();
+    let read_permission = opened.get_read_permission();
+    let mut position = 0;
+    loop {
+        let read = opened
+            .read_bytes(&read_permission, position as u64, TREE_BLOB_MAX_LENGTH)
+            .await
+            .unwrap();
+        if read.is_empty() {
+            return position;
+        }
+        position += read.len();
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_opening_small_file_prefetches_siblings() {
+    let (directory, editor) =
+        create_saved_directory(&[("a", 10), ("b", 20), ("c", 30), ("d", 40)], &[]).await;
+    assert_eq!(10, read_whole_file(&editor, "a").await);
+    wait_for_prefetched(|| directory.prefetch_stats(), 3).await;
+    assert_eq!(20, read_whole_file(&editor, "b").await);
+    assert_eq!(30, read_whole_file(&editor, "c").await);
+    assert_eq!(PrefetchStats::new(3, 2), directory.prefetch_stats());
+    let status = directory.request_save().await.unwrap();
+    assert_eq!(PrefetchStats::new(3, 2), status.open_files.prefetch);
+    // "d" was never read
+    assert_eq!(
+        1,
+        directory.drop_all_read_caches().await.hashed_trees_dropped
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_listing_warms_subdirectories() {
+    let (directory, editor) = create_saved_directory(&[("x/a", 1)], &["x", "y"]).await;
+    use futures::StreamExt;
+    let listed: Vec<FileName> = directory
+        .read()
+        .await
+        .map(|entry| entry.name)
+        .collect()
+        .await;
+    assert_eq!(vec![name("x"), name("y")], listed);
+    wait_for_prefetched(|| directory.prefetch_stats(), 2).await;
+    let _listing = editor.read_directory(path("x")).await.unwrap();
+    assert_eq!(PrefetchStats::new(2, 1), directory.prefetch_stats());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_sequential_read_reports_block_prefetch_hits() {
+    let block_count = 8;
+    let (directory, editor) =
+        create_saved_directory(&[("large", block_count * TREE_BLOB_MAX_LENGTH)], &[]).await;
+    assert_eq!(
+        block_count * TREE_BLOB_MAX_LENGTH,
+        read_whole_file(&editor, "large").await
+    );
+    let status = directory.request_save().await.unwrap();
+    let prefetch = status.open_files.prefetch;
+    assert!(prefetch.hits > 0, "{prefetch:?}");
+    assert!(prefetch.hits <= prefetch.prefetched, "{prefetch:?}");
+}
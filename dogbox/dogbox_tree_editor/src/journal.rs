@@ -0,0 +1,370 @@
+use crate::{
+    DeadPropertyChange, Error, FileCreationMode, NormalizedPath, OpenDirectory, PersistRoot, Result,
+};
+use astraea::tree::BlobDigest;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
+
+/// A change that [crate::TreeEditor] applied to its tree. Replaying the entries in the order they were recorded
+/// repeats the changes on the root that was persisted before them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum JournalEntry {
+    OpenFile {
+        path: NormalizedPath,
+        creation_mode: FileCreationMode,
+    },
+    WriteFile {
+        path: NormalizedPath,
+        position: u64,
+        content: Vec<u8>,
+    },
+    ResizeFile {
+        path: NormalizedPath,
+        size: u64,
+    },
+    CreateDirectory {
+        path: NormalizedPath,
+    },
+    Copy {
+        from: NormalizedPath,
+        to: NormalizedPath,
+        replace_existing: bool,
+    },
+    Rename {
+        from: NormalizedPath,
+        to: NormalizedPath,
+    },
+    RemovePermanently {
+        path: NormalizedPath,
+    },
+    RestoreVersion {
+        path: NormalizedPath,
+        index: usize,
+    },
+    PatchDeadProperties {
+        path: NormalizedPath,
+        changes: Vec<DeadPropertyChange>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) enum JournalRecord {
+    /// The persisted root with this digest contains every change recorded before.
+    Checkpoint(BlobDigest),
+    Change(JournalEntry),
+}
+
+/// Every record is prefixed with its length, so that a record torn by a crash can be recognized at the end of the file.
+pub(crate) fn encode_record(record: &JournalRecord) -> Vec<u8> {
+    let content = postcard::to_allocvec(record).unwrap();
+    let mut encoded = Vec::with_capacity(4 + content.len());
+    encoded.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    encoded.extend_from_slice(&content);
+    encoded
+}
+
+/// Stops at the first record that is incomplete or cannot be decoded.
+pub(crate) fn decode_records(mut encoded: &[u8]) -> Vec<JournalRecord> {
+    let mut records = Vec::new();
+    while !encoded.is_empty() {
+        if encoded.len() < 4 {
+            warn!("Ignoring {} bytes of a torn journal record", encoded.len());
+            break;
+        }
+        let length = u32::from_le_bytes(encoded[..4].try_into().unwrap()) as usize;
+        let content = match encoded[4..].get(..length) {
+            Some(content) => content,
+            None => {
+                warn!("Ignoring {} bytes of a torn journal record", encoded.len());
+                break;
+            }
+        };
+        match postcard::from_bytes(content) {
+            Ok(record) => records.push(record),
+            Err(error) => {
+                warn!("Ignoring the journal from an invalid record on: {}", error);
+                break;
+            }
+        }
+        encoded = &encoded[(4 + length)..];
+    }
+    records
+}
+
+/// Returns the changes recorded after the last checkpoint of `root`, or [None] if there is no such checkpoint.
+pub(crate) fn find_changes_after_checkpoint(
+    records: &[JournalRecord],
+    root: &BlobDigest,
+) -> Option<Vec<JournalEntry>> {
+    let checkpoint_index = records
+        .iter()
+        .rposition(|record| record == &JournalRecord::Checkpoint(*root))?;
+    Some(
+        records[(checkpoint_index + 1)..]
+            .iter()
+            .filter_map(|record| match record {
+                JournalRecord::Checkpoint(_) => None,
+                JournalRecord::Change(entry) => Some(entry.clone()),
+            })
+            .collect(),
+    )
+}
+
+/// The journal is only compacted once it grew beyond this size, so that checkpoints don't keep changes waiting for
+/// the file.
+const COMPACTION_THRESHOLD_IN_BYTES: u64 = 1024 * 1024;
+
+fn journal_error(error: std::io::Error) -> Error {
+    Error::Journal(error.to_string())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+async fn open_for_appending(path: &Path) -> Result<tokio::fs::File> {
+    tokio::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .await
+        .map_err(journal_error)
+}
+
+/// Replaces the file at `path` with `records` without ever leaving a partially written file behind.
+async fn rewrite(path: &Path, records: &[JournalRecord]) -> Result<JournalFile> {
+    let temporary_path = with_suffix(path, ".rewriting");
+    let mut encoded = Vec::new();
+    for record in records {
+        encoded.extend(encode_record(record));
+    }
+    let mut temporary = tokio::fs::File::create(&temporary_path)
+        .await
+        .map_err(journal_error)?;
+    temporary.write_all(&encoded).await.map_err(journal_error)?;
+    temporary.sync_data().await.map_err(journal_error)?;
+    drop(temporary);
+    tokio::fs::rename(&temporary_path, path)
+        .await
+        .map_err(journal_error)?;
+    Ok(JournalFile {
+        file: open_for_appending(path).await?,
+        size: encoded.len() as u64,
+        appended_in_bytes: 0,
+    })
+}
+
+struct JournalFile {
+    file: tokio::fs::File,
+    size: u64,
+    // Counts the bytes ever appended, unlike `size` which compaction resets. Tells what a sync has covered.
+    appended_in_bytes: u64,
+}
+
+/// A write-ahead journal of the changes that [crate::TreeEditor] applies in memory, so that changes which were not
+/// persisted yet survive a crash of the process or of the system. Changes are recorded after they were applied and
+/// synced to the disk before the change completes, so a change is only lost if the crash happens before it was
+/// acknowledged. Changes that are recorded concurrently share a sync.
+///
+/// [crate::TreeEditor::persist] saves and persists the root at a checkpoint and drops the changes that the persisted
+/// root contains. All editors of the same root have to share one journal.
+pub struct Journal {
+    path: PathBuf,
+    file: tokio::sync::Mutex<JournalFile>,
+    // Changes hold the gate shared while they are applied and recorded. A checkpoint holds it exclusively while it
+    // saves, so that the saved root contains exactly the changes recorded before the checkpoint.
+    gate: tokio::sync::RwLock<()>,
+    // Roots have to be persisted in the order of their checkpoints. Holds the root that was persisted last.
+    checkpointing: tokio::sync::Mutex<BlobDigest>,
+    // How many of the appended bytes are on the disk. Only one sync runs at a time, and the records that were appended
+    // in the meantime don't need another one.
+    synced_in_bytes: tokio::sync::Mutex<u64>,
+}
+
+impl Journal {
+    /// Opens the journal at `path` or creates it. Returns the changes that were recorded after `root` was persisted
+    /// and have to be replayed with [crate::TreeEditor::replay].
+    ///
+    /// A journal without a checkpoint of `root` belongs to a different root. It is kept with the suffix `.orphaned`
+    /// instead of being replayed.
+    pub async fn open(path: PathBuf, root: &BlobDigest) -> Result<(Journal, Vec<JournalEntry>)> {
+        let records = match tokio::fs::read(&path).await {
+            Ok(content) => decode_records(&content),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(journal_error(error)),
+        };
+        let changes = match find_changes_after_checkpoint(&records, root) {
+            Some(changes) => changes,
+            None if records.is_empty() => Vec::new(),
+            None => {
+                let orphaned_path = with_suffix(&path, ".orphaned");
+                warn!(
+                    "Journal {} has no checkpoint of root {}, moving it to {}",
+                    path.display(),
+                    root,
+                    orphaned_path.display()
+                );
+                tokio::fs::rename(&path, &orphaned_path)
+                    .await
+                    .map_err(journal_error)?;
+                Vec::new()
+            }
+        };
+        info!(
+            "Journal {} has {} changes to replay on root {}",
+            path.display(),
+            changes.len(),
+            root
+        );
+        let mut compacted = vec![JournalRecord::Checkpoint(*root)];
+        compacted.extend(changes.iter().cloned().map(JournalRecord::Change));
+        let file = rewrite(&path, &compacted).await?;
+        Ok((
+            Journal {
+                path,
+                file: tokio::sync::Mutex::new(file),
+                gate: tokio::sync::RwLock::new(()),
+                checkpointing: tokio::sync::Mutex::new(*root),
+                synced_in_bytes: tokio::sync::Mutex::new(0),
+            },
+            changes,
+        ))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns how many bytes were appended up to and including `record`.
+    async fn append(&self, record: &JournalRecord) -> Result<u64> {
+        let encoded = encode_record(record);
+        let mut file_locked = self.file.lock().await;
+        file_locked
+            .file
+            .write_all(&encoded)
+            .await
+            .map_err(journal_error)?;
+        file_locked.file.flush().await.map_err(journal_error)?;
+        file_locked.size += encoded.len() as u64;
+        file_locked.appended_in_bytes += encoded.len() as u64;
+        Ok(file_locked.appended_in_bytes)
+    }
+
+    /// Makes sure that the first `appended_in_bytes` bytes are on the disk.
+    async fn sync(&self, appended_in_bytes: u64) -> Result<()> {
+        let mut synced_locked = self.synced_in_bytes.lock().await;
+        if *synced_locked >= appended_in_bytes {
+            return Ok(());
+        }
+        // Appending continues while syncing, so a duplicate handle syncs everything that was appended so far.
+        let (file, appended_so_far) = {
+            let file_locked = self.file.lock().await;
+            (
+                file_locked.file.try_clone().await.map_err(journal_error)?,
+                file_locked.appended_in_bytes,
+            )
+        };
+        file.sync_data().await.map_err(journal_error)?;
+        *synced_locked = appended_so_far;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub(crate) async fn unsynced_in_bytes(&self) -> u64 {
+        let appended_in_bytes = self.file.lock().await.appended_in_bytes;
+        appended_in_bytes - *self.synced_in_bytes.lock().await
+    }
+
+    /// Records `entry` once `applying` succeeded. Returns after the record is on the disk.
+    pub(crate) async fn record<T>(
+        &self,
+        entry: JournalEntry,
+        applying: impl core::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let (result, appended_in_bytes) = {
+            let _gate = self.gate.read().await;
+            let result = applying.await?;
+            let appended_in_bytes = self.append(&JournalRecord::Change(entry)).await?;
+            (result, appended_in_bytes)
+        };
+        // Syncing after releasing the gate lets a checkpoint continue meanwhile.
+        self.sync(appended_in_bytes).await?;
+        Ok(result)
+    }
+
+    /// Saves `root` completely, persists it and drops the changes that it contains from the journal. Changes that
+    /// cancel each other out don't have to be persisted, so the root is only persisted if it changed.
+    pub(crate) async fn checkpoint(
+        &self,
+        root: &OpenDirectory,
+        persistence: &(dyn PersistRoot + Send + Sync),
+    ) -> Result<BlobDigest> {
+        let mut persisted_locked = self.checkpointing.lock().await;
+        let (digest, appended_in_bytes) = {
+            let _gate = self.gate.write().await;
+            let digest = save_completely(root).await?;
+            (
+                digest,
+                self.append(&JournalRecord::Checkpoint(digest)).await?,
+            )
+        };
+        if *persisted_locked == digest {
+            debug!("Root {} is persisted already", &digest);
+        } else {
+            // The checkpoint has to be on the disk before the root is persisted, so that the journal can be matched
+            // with the persisted root after a crash. Syncing after releasing the gate lets changes continue meanwhile.
+            self.sync(appended_in_bytes).await?;
+            persistence.persist_root(&digest).await?;
+            *persisted_locked = digest;
+        }
+        self.compact(&digest).await?;
+        Ok(digest)
+    }
+
+    async fn compact(&self, checkpoint: &BlobDigest) -> Result<()> {
+        let mut file_locked = self.file.lock().await;
+        if file_locked.size < COMPACTION_THRESHOLD_IN_BYTES {
+            return Ok(());
+        }
+        let content = tokio::fs::read(&self.path).await.map_err(journal_error)?;
+        let records = decode_records(&content);
+        let changes = match find_changes_after_checkpoint(&records, checkpoint) {
+            Some(changes) => changes,
+            None => {
+                return Err(Error::Journal(format!(
+                    "Checkpoint {} is missing in {}",
+                    checkpoint,
+                    self.path.display()
+                )))
+            }
+        };
+        debug!(
+            "Compacting journal {} from {} records to {}",
+            self.path.display(),
+            records.len(),
+            changes.len() + 1
+        );
+        let mut compacted = vec![JournalRecord::Checkpoint(*checkpoint)];
+        compacted.extend(changes.into_iter().map(JournalRecord::Change));
+        let appended_in_bytes = file_locked.appended_in_bytes;
+        *file_locked = rewrite(&self.path, &compacted).await?;
+        // The rewritten file is on the disk already, including everything that was appended before.
+        file_locked.appended_in_bytes = appended_in_bytes;
+        Ok(())
+    }
+}
+
+async fn save_completely(root: &OpenDirectory) -> Result<BlobDigest> {
+    // Nothing changes while the gate is held, so the second save should be complete at the latest.
+    for _ in 0..3 {
+        let status = root.request_save().await?;
+        if status.digest.is_digest_up_to_date {
+            return Ok(status.digest.last_known_digest);
+        }
+    }
+    Err(Error::SaveFailed)
+}
@@ -0,0 +1,227 @@
+use crate::{
+    journal::{decode_records, encode_record, find_changes_after_checkpoint, JournalRecord},
+    test_helpers::{create_editor, create_storage, path, write_file},
+    FileCreationMode, Journal, JournalEntry, NamedRoot, OpenDirectory, PersistRoot, SavePolicy,
+    TreeEditor, WallClock,
+};
+use astraea::{
+    storage::{LoadRoot, SQLiteStorage},
+    tree::BlobDigest,
+};
+use dogbox_tree::serialization::DirectoryEntryKind;
+use pretty_assertions::assert_eq;
+use std::{sync::Arc, time::SystemTime};
+
+const ROOT_NAME: &str = "test";
+
+fn create_directory_entry(name: &str) -> JournalEntry {
+    JournalEntry::CreateDirectory { path: path(name) }
+}
+
+#[test_log::test]
+fn test_decode_records_ignores_torn_record() {
+    let records = vec![
+        JournalRecord::Checkpoint(BlobDigest::hash(b"root")),
+        JournalRecord::Change(JournalEntry::WriteFile {
+            path: path("a/b"),
+            position: 3,
+            content: b"hello".to_vec(),
+        }),
+    ];
+    let mut encoded = Vec::new();
+    for record in records.iter() {
+        encoded.extend(encode_record(record));
+    }
+    assert_eq!(records, decode_records(&encoded));
+    let torn = encode_record(&JournalRecord::Change(create_directory_entry("c")));
+    encoded.extend_from_slice(&torn[..(torn.len() - 1)]);
+    assert_eq!(records, decode_records(&encoded));
+}
+
+#[test_log::test]
+fn test_find_changes_after_last_checkpoint() {
+    let first = BlobDigest::hash(b"first");
+    let second = BlobDigest::hash(b"second");
+    let records = vec![
+        JournalRecord::Checkpoint(first),
+        JournalRecord::Change(create_directory_entry("a")),
+        JournalRecord::Checkpoint(second),
+        JournalRecord::Change(create_directory_entry("b")),
+        JournalRecord::Checkpoint(first),
+        JournalRecord::Change(create_directory_entry("c")),
+    ];
+    assert_eq!(
+        Some(vec![create_directory_entry("c")]),
+        find_changes_after_checkpoint(&records, &first)
+    );
+    assert_eq!(
+        Some(vec![
+            create_directory_entry("b"),
+            create_directory_entry("c")
+        ]),
+        find_changes_after_checkpoint(&records, &second)
+    );
+    assert_eq!(
+        None,
+        find_changes_after_checkpoint(&records, &BlobDigest::hash(b"other"))
+    );
+}
+
+fn persistence(storage: &Arc<SQLiteStorage>) -> Arc<NamedRoot<SQLiteStorage>> {
+    Arc::new(NamedRoot::new(storage.clone(), ROOT_NAME.to_string()))
+}
+
+/// Loads the persisted root like a restarted process would and replays the journal on it.
+async fn open_persisted(
+    storage: &Arc<SQLiteStorage>,
+    journal_path: &std::path::Path,
+) -> (TreeEditor, usize) {
+    let clock: WallClock = Arc::new(|| SystemTime::UNIX_EPOCH);
+    let digest = storage.load_root(ROOT_NAME).await.unwrap().unwrap();
+    let root = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage.clone(),
+        &digest,
+        SystemTime::UNIX_EPOCH,
+        clock,
+        1,
+    )
+    .await
+    .unwrap();
+    let (journal, changes) = Journal::open(journal_path.to_path_buf(), &digest)
+        .await
+        .unwrap();
+    let editor = TreeEditor::new(root, None)
+        .with_persistence(SavePolicy::default(), persistence(storage))
+        .with_journal(Arc::new(journal));
+    let replayed = editor.replay(changes).await;
+    (editor, replayed)
+}
+
+async fn create_persisted_root(storage: &Arc<SQLiteStorage>) {
+    let clock: WallClock = Arc::new(|| SystemTime::UNIX_EPOCH);
+    let root =
+        OpenDirectory::create_directory(std::path::PathBuf::from("/"), storage.clone(), clock, 1)
+            .await
+            .unwrap();
+    let status = root.request_save().await.unwrap();
+    persistence(storage)
+        .persist_root(&status.digest.last_known_digest)
+        .await
+        .unwrap();
+}
+
+async fn read_file(editor: &TreeEditor, name: &str) -> Vec<u8> {
+    let opened = editor
+        .open_file(path(name), FileCreationMode::open_existing())
+        .await
+        .unwrap();
+    let size = opened.size().await;
+    opened
+        .read_bytes(&opened.get_read_permission(), 0, size as usize)
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_replay_changes_that_were_not_persisted() {
+    let temporary_directory = tempfile::tempdir().unwrap();
+    let journal_path = temporary_directory.path().join("test.journal");
+    let storage = create_storage();
+    create_persisted_root(&storage).await;
+    {
+        let (editor, replayed) = open_persisted(&storage, &journal_path).await;
+        assert_eq!(0, replayed);
+        editor.create_directory(path("docs")).await.unwrap();
+        write_file(&editor, "docs/a.txt", b"hello").await;
+        editor
+            .rename(path("docs/a.txt"), path("docs/b.txt"))
+            .await
+            .unwrap();
+        editor
+            .copy(path("docs/b.txt"), path("c.txt"))
+            .await
+            .unwrap();
+        // the process crashes without persisting
+    }
+    let (editor, replayed) = open_persisted(&storage, &journal_path).await;
+    assert_eq!(5, replayed);
+    assert_eq!(b"hello".to_vec(), read_file(&editor, "docs/b.txt").await);
+    assert_eq!(b"hello".to_vec(), read_file(&editor, "c.txt").await);
+    assert!(editor.get_meta_data(path("docs/a.txt")).await.is_err());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_persist_drops_changes_from_journal() {
+    let temporary_directory = tempfile::tempdir().unwrap();
+    let journal_path = temporary_directory.path().join("test.journal");
+    let storage = create_storage();
+    create_persisted_root(&storage).await;
+    {
+        let (editor, _) = open_persisted(&storage, &journal_path).await;
+        write_file(&editor, "a.txt", b"persisted").await;
+        let digest = editor.persist().await.unwrap();
+        assert_eq!(Some(digest), storage.load_root(ROOT_NAME).await.unwrap());
+        editor.create_directory(path("later")).await.unwrap();
+    }
+    let (editor, replayed) = open_persisted(&storage, &journal_path).await;
+    assert_eq!(1, replayed);
+    assert_eq!(b"persisted".to_vec(), read_file(&editor, "a.txt").await);
+    assert_eq!(
+        DirectoryEntryKind::Directory,
+        editor.get_meta_data(path("later")).await.unwrap().kind
+    );
+    // replaying again after persisting the replayed changes does nothing
+    editor.persist().await.unwrap();
+    drop(editor);
+    let (_editor, replayed) = open_persisted(&storage, &journal_path).await;
+    assert_eq!(0, replayed);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_journal_of_another_root_is_not_replayed() {
+    let temporary_directory = tempfile::tempdir().unwrap();
+    let journal_path = temporary_directory.path().join("test.journal");
+    let storage = create_storage();
+    create_persisted_root(&storage).await;
+    {
+        let (editor, _) = open_persisted(&storage, &journal_path).await;
+        editor.create_directory(path("lost")).await.unwrap();
+    }
+    let (journal, changes) = Journal::open(journal_path.clone(), &BlobDigest::hash(b"other"))
+        .await
+        .unwrap();
+    assert_eq!(Vec::<JournalEntry>::new(), changes);
+    drop(journal);
+    let mut orphaned_path = journal_path.into_os_string();
+    orphaned_path.push(".orphaned");
+    assert!(std::fs::exists(orphaned_path).unwrap());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_changes_are_synced_before_they_complete() {
+    let temporary_directory = tempfile::tempdir().unwrap();
+    let journal_path = temporary_directory.path().join("test.journal");
+    let storage = create_storage();
+    create_persisted_root(&storage).await;
+    let digest = storage.load_root(ROOT_NAME).await.unwrap().unwrap();
+    let (journal, _) = Journal::open(journal_path, &digest).await.unwrap();
+    let journal = Arc::new(journal);
+    let (_root, editor) = create_editor().await;
+    let editor = editor.with_journal(journal.clone());
+    editor.create_directory(path("a")).await.unwrap();
+    assert_eq!(0, journal.unsynced_in_bytes().await);
+    // concurrent changes can share a sync
+    futures::future::join_all((0..10).map(|index| {
+        let editor = &editor;
+        async move {
+            editor
+                .create_directory(path(&format!("a/{index}")))
+                .await
+                .unwrap();
+        }
+    }))
+    .await;
+    assert_eq!(0, journal.unsynced_in_bytes().await);
+}
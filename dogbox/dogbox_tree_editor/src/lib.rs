@@ -3,6 +3,24 @@
 #[cfg(test)]
 mod benchmarks;
 
+#[cfg(test)]
+mod test_helpers;
+
+mod change_feed;
+
+mod directory_prefetcher;
+
+#[cfg(test)]
+mod directory_prefetcher_tests;
+
+#[cfg(test)]
+mod change_feed_tests;
+
+mod search_index;
+
+#[cfg(test)]
+mod search_index_tests;
+
 #[cfg(test)]
 mod lib_tests;
 
@@ -11,12 +29,37 @@ mod segmented_blob;
 #[cfg(test)]
 mod segmented_blob_tests;
 
+pub mod local_directory;
+
+mod journal;
+
+#[cfg(test)]
+mod journal_tests;
+
+mod locks;
+
+#[cfg(test)]
+mod locks_tests;
+
+mod save_policy;
+
+#[cfg(test)]
+mod save_policy_tests;
+
+mod trash;
+
+#[cfg(test)]
+mod trash_tests;
+
+#[cfg(test)]
+mod local_directory_tests;
+
 pub mod sqlite;
 
 #[cfg(test)]
 mod sqlite_tests;
 
-use crate::segmented_blob::{load_segmented_blob, save_segmented_blob};
+use crate::segmented_blob::{is_hole, load_segmented_blob, save_segmented_blob, Segment};
 use astraea::{
     storage::{LoadStoreTree, StoreError},
     tree::{BlobDigest, HashedTree, Tree, TreeBlob, TreeChildren, TREE_BLOB_MAX_LENGTH},
@@ -24,13 +67,22 @@ use astraea::{
 use async_stream::stream;
 use bytes::Buf;
 use cached::Cached;
+pub use change_feed::{TreeChange, TreeChangeKind};
 use derivative::Derivative;
+use directory_prefetcher::SiblingFile;
+pub use directory_prefetcher::{DirectoryPrefetcher, PrefetchStats};
 use dogbox_tree::serialization::{
-    self, deserialize_directory, serialize_directory, DeserializationError, DirectoryEntryKind,
-    FileName, FileNameError,
+    self, deserialize_directory, serialize_directory, DeadProperties, DeadPropertyName,
+    DeadPropertyValue, DeserializationError, DirectoryEntryKind, FileHistory, FileName,
+    FileNameError, FileVersion,
 };
-use futures::future::join_all;
+use futures::{future::join_all, StreamExt};
+pub use journal::{Journal, JournalEntry};
+pub use locks::{hold_lock, holding_locks, CheckLocks, LockTokens};
 use pretty_assertions::assert_eq;
+pub use save_policy::{NamedRoot, PersistRoot, SavePolicy};
+pub use search_index::{SearchQuery, SearchQueryError, SearchResult};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
     pin::Pin,
@@ -38,6 +90,7 @@ use std::{
 };
 use tokio::sync::{Mutex, MutexGuard};
 use tracing::{debug, error, info, warn};
+pub use trash::{TrashEntry, TrashRetention, TRASH_DIRECTORY_NAME};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Error {
@@ -60,6 +113,25 @@ pub enum Error {
     FileRemoved,
     InvalidArgument(String),
     FileAlreadyExists(FileName),
+    DeadPropertiesTooLarge {
+        size_in_bytes: usize,
+    },
+    VersionNotFound {
+        name: FileName,
+        index: usize,
+    },
+    /// The root directory was used where only a file makes sense, for example to get a version history.
+    RootIsNotARegularFile,
+    /// [TreeEditor::commit] was called without a [PersistRoot].
+    NotPersistent,
+    CommitFailed(String),
+    /// Reading or writing the [Journal] failed.
+    Journal(String),
+    /// Only the tree editor itself may create an entry with this name, like [TRASH_DIRECTORY_NAME] while the trash is
+    /// enabled.
+    ReservedName(FileName),
+    /// Someone else holds a lock on the path, see [CheckLocks].
+    Locked(NormalizedPath),
 }
 
 impl std::fmt::Display for Error {
@@ -172,6 +244,25 @@ impl NamedEntry {
         }
     }
 
+    fn as_sibling_file(&self, name: &FileName) -> Option<SiblingFile> {
+        match self {
+            NamedEntry::NotOpen(meta_data, digest) => match meta_data.kind {
+                DirectoryEntryKind::Directory => None,
+                DirectoryEntryKind::File(size) => Some(SiblingFile {
+                    name: name.clone(),
+                    size,
+                    digest: Some(*digest),
+                }),
+            },
+            NamedEntry::OpenRegularFile(_, receiver) => Some(SiblingFile {
+                name: name.clone(),
+                size: receiver.borrow().size,
+                digest: None,
+            }),
+            NamedEntry::OpenSubdirectory(_, _) => None,
+        }
+    }
+
     fn get_status(&self) -> NamedEntryStatus {
         match self {
             NamedEntry::NotOpen(directory_entry_meta_data, blob_digest) => {
@@ -354,6 +445,8 @@ pub struct OpenFileStats {
     pub files_open_for_writing_count: usize,
     pub files_unflushed_count: usize,
     pub bytes_unflushed_count: u64,
+    /// Covers the blocks prefetched within open files and the trees prefetched by the open directories.
+    pub prefetch: PrefetchStats,
 }
 
 impl OpenFileStats {
@@ -363,6 +456,7 @@ impl OpenFileStats {
         files_open_for_writing_count: usize,
         files_unflushed_count: usize,
         bytes_unflushed_count: u64,
+        prefetch: PrefetchStats,
     ) -> Self {
         Self {
             files_open_count,
@@ -370,6 +464,7 @@ impl OpenFileStats {
             files_open_for_writing_count,
             files_unflushed_count,
             bytes_unflushed_count,
+            prefetch,
         }
     }
 
@@ -379,6 +474,7 @@ impl OpenFileStats {
         self.files_open_for_writing_count += other.files_open_for_writing_count;
         self.files_unflushed_count += other.files_unflushed_count;
         self.bytes_unflushed_count += other.bytes_unflushed_count;
+        self.prefetch.add(&other.prefetch);
     }
 }
 
@@ -406,7 +502,7 @@ impl OpenDirectoryStatus {
     }
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct FileCreationMode {
     pub fail_if_exists: bool,
     pub fail_if_not_exists: bool,
@@ -435,10 +531,50 @@ impl FileCreationMode {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DeadPropertyChange {
+    Set(DeadPropertyName, DeadPropertyValue),
+    Remove(DeadPropertyName),
+}
+
+/// Limits how many earlier versions of each file are kept. A version is also kept if it is older than `max_age` until the
+/// directory is saved again. `max_versions` can't be larger than [FileHistory::MAX_VERSIONS].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VersionRetention {
+    pub max_versions: usize,
+    pub max_age: Option<std::time::Duration>,
+}
+
+impl VersionRetention {
+    pub fn disabled() -> Self {
+        Self {
+            max_versions: 0,
+            max_age: None,
+        }
+    }
+}
+
+impl Default for VersionRetention {
+    fn default() -> Self {
+        Self {
+            max_versions: 10,
+            max_age: Some(std::time::Duration::from_secs(30 * 24 * 60 * 60)),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct OpenDirectoryMutableState {
     // TODO: support really big directories. We may not be able to hold all entries in memory at the same time.
     names: BTreeMap<FileName, NamedEntry>,
+    /// only contains entries that have at least one property
+    dead_properties: BTreeMap<FileName, DeadProperties>,
+    /// only contains files that have at least one earlier version
+    file_histories: BTreeMap<FileName, FileHistory>,
+    /// The content of each file as of the last save while it was not open for writing. It becomes an earlier version
+    /// when a different content gets saved. Intermediate states of a file that is being written never become versions.
+    saved_contents: BTreeMap<FileName, FileVersion>,
+    version_retention: VersionRetention,
     has_unsaved_changes: bool,
     last_accessed_at: std::time::SystemTime,
 }
@@ -446,16 +582,123 @@ struct OpenDirectoryMutableState {
 impl OpenDirectoryMutableState {
     fn new(
         names: BTreeMap<FileName, NamedEntry>,
+        dead_properties: BTreeMap<FileName, DeadProperties>,
         has_unsaved_changes: bool,
         last_accessed_at: std::time::SystemTime,
     ) -> Self {
         Self {
             names,
+            dead_properties,
+            file_histories: BTreeMap::new(),
+            saved_contents: BTreeMap::new(),
+            version_retention: VersionRetention::default(),
             has_unsaved_changes,
             last_accessed_at,
         }
     }
 
+    fn forget_versions(&mut self, name: &FileName) {
+        self.file_histories.remove(name);
+        self.saved_contents.remove(name);
+    }
+
+    /// Moves the history of a file to a new name (which may be in a different directory).
+    fn take_versions(&mut self, name: &FileName) -> (FileHistory, Option<FileVersion>) {
+        (
+            self.file_histories.remove(name).unwrap_or_default(),
+            self.saved_contents.remove(name),
+        )
+    }
+
+    fn put_versions(
+        &mut self,
+        name: FileName,
+        (history, saved_content): (FileHistory, Option<FileVersion>),
+    ) {
+        if history.is_empty() {
+            self.file_histories.remove(&name);
+        } else {
+            self.file_histories.insert(name.clone(), history);
+        }
+        match saved_content {
+            Some(saved_content) => self.saved_contents.insert(name, saved_content),
+            None => self.saved_contents.remove(&name),
+        };
+    }
+
+    /// Called for every file when the directory is saved.
+    fn record_saved_content(
+        &mut self,
+        name: &FileName,
+        content: BlobDigest,
+        size: u64,
+        is_open_for_writing: bool,
+        now: std::time::SystemTime,
+    ) {
+        if is_open_for_writing {
+            return;
+        }
+        match self.saved_contents.get(name) {
+            Some(previous) if previous.content == content => {}
+            Some(previous) => {
+                let previous = *previous;
+                if self.version_retention.max_versions > 0 {
+                    debug!("Keeping the previous content of {} as a version", name);
+                    self.file_histories
+                        .entry(name.clone())
+                        .or_default()
+                        .push(previous);
+                }
+                self.saved_contents
+                    .insert(name.clone(), FileVersion::new(content, size, now));
+                self.record_modification(name, now);
+            }
+            None => {
+                self.saved_contents
+                    .insert(name.clone(), FileVersion::new(content, size, now));
+            }
+        }
+    }
+
+    /// The modification time of an entry is only known to the directory. It is also written into the dead property
+    /// that an import leaves behind, so that the property doesn't keep the time of the imported content.
+    fn record_modification(&mut self, name: &FileName, modified: std::time::SystemTime) {
+        match self.names.get_mut(name) {
+            Some(NamedEntry::NotOpen(meta_data, _digest)) => meta_data.modified = modified,
+            Some(NamedEntry::OpenRegularFile(open_file, _receiver)) => {
+                open_file.set_modified(modified)
+            }
+            Some(NamedEntry::OpenSubdirectory(..)) | None => {}
+        }
+        if let Some(properties) = self.dead_properties.get_mut(name) {
+            if properties.modified().is_some() {
+                properties.set_modified(modified);
+            }
+        }
+    }
+
+    fn apply_version_retention(&mut self, now: std::time::SystemTime) {
+        let retention = self.version_retention;
+        for history in self.file_histories.values_mut() {
+            history.keep_newest(retention.max_versions);
+            if let Some(max_age) = retention.max_age {
+                if let Some(oldest_allowed) = now.checked_sub(max_age) {
+                    history.remove_saved_before(oldest_allowed);
+                }
+            }
+        }
+        self.file_histories
+            .retain(|_name, history| !history.is_empty());
+    }
+
+    fn set_dead_properties(&mut self, name: FileName, properties: DeadProperties) {
+        if properties.is_empty() {
+            self.dead_properties.remove(&name);
+        } else {
+            self.dead_properties.insert(name, properties);
+        }
+    }
+
     pub fn record_access(&mut self, accessed_at: std::time::SystemTime) {
         self.last_accessed_at = accessed_at;
     }
@@ -474,6 +717,8 @@ pub struct OpenDirectory {
     original_path: std::path::PathBuf,
     state: tokio::sync::Mutex<OpenDirectoryMutableState>,
     storage: Arc<dyn LoadStoreTree + Send + Sync>,
+    /// Files and subdirectories opened from here load through this, so that they find what was prefetched for them.
+    prefetcher: Arc<DirectoryPrefetcher>,
     change_event_sender: tokio::sync::watch::Sender<OpenDirectoryStatus>,
     _change_event_receiver: tokio::sync::watch::Receiver<OpenDirectoryStatus>,
     modified: std::time::SystemTime,
@@ -493,17 +738,23 @@ impl OpenDirectory {
         open_file_write_buffer_in_blocks: usize,
     ) -> Self {
         let has_unsaved_changes = !digest.is_digest_up_to_date;
-        let (change_event_sender, change_event_receiver) = tokio::sync::watch::channel(
-            OpenDirectoryStatus::new(digest, 1, 0, OpenFileStats::new(0, 0, 0, 0, 0)),
-        );
+        let (change_event_sender, change_event_receiver) =
+            tokio::sync::watch::channel(OpenDirectoryStatus::new(
+                digest,
+                1,
+                0,
+                OpenFileStats::new(0, 0, 0, 0, 0, PrefetchStats::default()),
+            ));
         let last_accessed_at = (clock)();
         Self {
             original_path,
             state: Mutex::new(OpenDirectoryMutableState::new(
                 names,
+                BTreeMap::new(),
                 has_unsaved_changes,
                 last_accessed_at,
             )),
+            prefetcher: Arc::new(DirectoryPrefetcher::new(storage.clone())),
             storage,
             change_event_sender,
             _change_event_receiver: change_event_receiver,
@@ -534,6 +785,19 @@ impl OpenDirectory {
         state_locked.record_access((self.clock)());
         let snapshot = state_locked.names.clone();
         debug!("Reading directory with {} entries", snapshot.len());
+        let closed_subdirectories = snapshot
+            .values()
+            .filter_map(|entry| match entry {
+                NamedEntry::NotOpen(meta_data, digest) => match meta_data.kind {
+                    DirectoryEntryKind::Directory => Some(*digest),
+                    DirectoryEntryKind::File(_) => None,
+                },
+                NamedEntry::OpenRegularFile(_, _) | NamedEntry::OpenSubdirectory(_, _) => None,
+            })
+            .take(Self::MAX_SUBDIRECTORIES_WARMED_ON_READ)
+            .collect();
+        self.prefetcher
+            .prefetch_in_background(closed_subdirectories);
         Box::pin(stream! {
             for cached_entry in snapshot {
                 let meta_data = cached_entry.1.get_meta_data().await;
@@ -542,6 +806,35 @@ impl OpenDirectory {
         })
     }
 
+    const MAX_SUBDIRECTORIES_WARMED_ON_READ: usize = 16;
+
+    const MAX_SIBLINGS_CONSIDERED_FOR_PREFETCHING: usize = 16;
+
+    fn prefetch_around(&self, names: &BTreeMap<FileName, NamedEntry>, opened: &SiblingFile) {
+        let before: Vec<SiblingFile> = names
+            .range(..opened.name.clone())
+            .rev()
+            .filter_map(|(name, entry)| entry.as_sibling_file(name))
+            .take(Self::MAX_SIBLINGS_CONSIDERED_FOR_PREFETCHING)
+            .collect();
+        let after: Vec<SiblingFile> = names
+            .range((
+                std::ops::Bound::Excluded(opened.name.clone()),
+                std::ops::Bound::Unbounded,
+            ))
+            .filter_map(|(name, entry)| entry.as_sibling_file(name))
+            .take(Self::MAX_SIBLINGS_CONSIDERED_FOR_PREFETCHING)
+            .collect();
+        let digests = self
+            .prefetcher
+            .find_files_to_prefetch(opened, &before, &after);
+        self.prefetcher.prefetch_in_background(digests);
+    }
+
+    pub fn prefetch_stats(&self) -> PrefetchStats {
+        self.prefetcher.stats()
+    }
+
     pub async fn get_meta_data(&self, name: &FileName) -> Result<DirectoryEntryMetaData> {
         let mut state_locked = self.state.lock().await;
         state_locked.record_access((self.clock)());
@@ -562,6 +855,15 @@ impl OpenDirectory {
     ) -> Result<Arc<OpenFile>> {
         let mut state_locked = self.state.lock().await;
         state_locked.record_access((self.clock)());
+        if !creation_mode.fail_if_exists {
+            if let Some(opened) = state_locked
+                .names
+                .get(name)
+                .and_then(|entry| entry.as_sibling_file(name))
+            {
+                self.prefetch_around(&state_locked.names, &opened);
+            }
+        }
         match state_locked.names.get_mut(name) {
             Some(found) => {
                 if creation_mode.fail_if_exists {
@@ -587,8 +889,8 @@ impl OpenDirectory {
                                         length,
                                         self.open_file_write_buffer_in_blocks,
                                     ),
-                                    self.storage.clone(),
-                                    self.modified,
+                                    self.prefetcher.clone(),
+                                    meta_data.modified,
                                 ));
                                 let receiver = open_file.watch().await;
                                 let mut new_entry =
@@ -669,7 +971,30 @@ impl OpenDirectory {
         clock: WallClock,
         open_file_write_buffer_in_blocks: usize,
     ) -> Result<Arc<OpenDirectory>> {
-        let deserialized_directory = match deserialize_directory(storage.as_ref(), digest).await {
+        Self::load_directory_through(
+            original_path,
+            storage.clone(),
+            storage.as_ref(),
+            digest,
+            modified,
+            clock,
+            open_file_write_buffer_in_blocks,
+        )
+        .await
+    }
+
+    /// Reads the directory from `loader`, which can be the prefetcher of the parent directory, but keeps `storage` for
+    /// everything else.
+    async fn load_directory_through(
+        original_path: std::path::PathBuf,
+        storage: Arc<dyn LoadStoreTree + Send + Sync>,
+        loader: &(dyn LoadStoreTree + Send + Sync),
+        digest: &BlobDigest,
+        modified: std::time::SystemTime,
+        clock: WallClock,
+        open_file_write_buffer_in_blocks: usize,
+    ) -> Result<Arc<OpenDirectory>> {
+        let deserialized_directory = match deserialize_directory(loader, digest).await {
             Ok(deserialized_directory) => deserialized_directory,
             Err(error) => {
                 let message = format!("Failed to deserialize directory: {}", error);
@@ -678,14 +1003,27 @@ impl OpenDirectory {
             }
         };
         let mut entries = BTreeMap::new();
+        let mut dead_properties = BTreeMap::new();
+        let mut file_histories = BTreeMap::new();
+        let mut saved_contents = BTreeMap::new();
         for maybe_entry in deserialized_directory {
-            let (name, (kind, digest)) = maybe_entry;
+            let (name, (kind, digest, properties, history)) = maybe_entry;
+            let entry_modified = properties.modified().unwrap_or(modified);
+            if !properties.is_empty() {
+                dead_properties.insert(name.clone(), properties);
+            }
+            if !history.is_empty() {
+                file_histories.insert(name.clone(), history);
+            }
+            if let DirectoryEntryKind::File(size) = kind {
+                saved_contents.insert(name.clone(), FileVersion::new(digest, size, entry_modified));
+            }
             entries.insert(
                 name,
-                NamedEntry::NotOpen(DirectoryEntryMetaData::new(kind, modified), digest),
+                NamedEntry::NotOpen(DirectoryEntryMetaData::new(kind, entry_modified), digest),
             );
         }
-        Ok(Arc::new(OpenDirectory::new(
+        let mut directory = OpenDirectory::new(
             original_path,
             DigestStatus::new(*digest, true),
             entries,
@@ -693,7 +1031,12 @@ impl OpenDirectory {
             modified,
             clock,
             open_file_write_buffer_in_blocks,
-        )))
+        );
+        let state = directory.state.get_mut();
+        state.dead_properties = dead_properties;
+        state.file_histories = file_histories;
+        state.saved_contents = saved_contents;
+        Ok(Arc::new(directory))
     }
 
     async fn open_subdirectory(
@@ -702,19 +1045,24 @@ impl OpenDirectory {
     ) -> Result<Arc<OpenDirectory>> {
         let mut state_locked = self.state.lock().await;
         state_locked.record_access((self.clock)());
+        let version_retention = state_locked.version_retention;
         match state_locked.names.get_mut(&name) {
             Some(found) => match found {
                 NamedEntry::NotOpen(meta_data, digest) => match meta_data.kind {
                     DirectoryEntryKind::Directory => {
-                        let subdirectory = Self::load_directory(
+                        let subdirectory = Self::load_directory_through(
                             self.original_path.join(name.to_string()),
                             self.storage.clone(),
+                            self.prefetcher.as_ref(),
                             digest,
-                            self.modified,
+                            meta_data.modified,
                             self.clock.clone(),
                             self.open_file_write_buffer_in_blocks,
                         )
                         .await?;
+                        subdirectory
+                            .set_version_retention(version_retention)
+                            .await?;
                         let receiver = subdirectory.watch().await;
                         let mut new_entry =
                             NamedEntry::OpenSubdirectory(subdirectory.clone(), receiver);
@@ -830,6 +1178,9 @@ impl OpenDirectory {
                     self.open_file_write_buffer_in_blocks,
                 )
                 .await?;
+                directory
+                    .set_version_retention(state_locked.version_retention)
+                    .await?;
                 let receiver = directory.watch().await;
                 self.clone().insert_entry(
                     &mut state_locked,
@@ -847,6 +1198,8 @@ impl OpenDirectory {
         state_locked.record_access((self.clock)());
         match state_locked.names.remove(name_here) {
             Some(removed_entry) => {
+                state_locked.dead_properties.remove(name_here);
+                state_locked.forget_versions(name_here);
                 removed_entry.close_after_removal().await;
             }
             None => {
@@ -871,6 +1224,35 @@ impl OpenDirectory {
         there: &OpenDirectory,
         name_there: &FileName,
     ) -> Result<()> {
+        self.copy_entry(name_here, there, name_there, true).await
+    }
+
+    /// The copy refers to the content digest of the original, so this takes the same time for any size of file or
+    /// directory. An open subdirectory is saved first if it has unsaved changes.
+    async fn copy_entry(
+        self: Arc<OpenDirectory>,
+        name_here: &FileName,
+        there: &OpenDirectory,
+        name_there: &FileName,
+        replace_existing: bool,
+    ) -> Result<()> {
+        // Saving locks the whole subtree which can contain `there`, so it has to happen before we lock anything.
+        let open_subdirectory = match self.state.lock().await.names.get(name_here) {
+            Some(NamedEntry::OpenSubdirectory(subdirectory, _)) => Some(subdirectory.clone()),
+            _ => None,
+        };
+        let saved_subdirectory = match open_subdirectory {
+            Some(subdirectory) => {
+                // Change notifications of the entries arrive asynchronously, so we make sure that everything is saved.
+                subdirectory
+                    .expect_changes(NormalizedPath::root(), true)
+                    .await?;
+                let status = subdirectory.request_save().await?;
+                Some((subdirectory, status.digest.last_known_digest))
+            }
+            None => None,
+        };
+
         let mut state_locked: MutexGuard<'_, _>;
         let mut state_there_locked: Option<MutexGuard<'_, _>>;
 
@@ -899,6 +1281,15 @@ impl OpenDirectory {
             Some(_) => {}
             None => return Err(Error::NotFound(name_here.clone())),
         }
+        if !replace_existing {
+            let names_there = match state_there_locked {
+                Some(ref value) => &value.names,
+                None => &state_locked.names,
+            };
+            if names_there.contains_key(name_there) {
+                return Err(Error::FileAlreadyExists(name_there.clone()));
+            }
+        }
 
         debug!(
             "Copying from {} to {} sending a change event to the directory.",
@@ -906,14 +1297,31 @@ impl OpenDirectory {
         );
 
         let old_entry = state_locked.names.get(name_here).unwrap();
-        let new_entry = Self::copy_named_entry(old_entry, self.clock.clone()).await?;
+        let new_entry =
+            Self::copy_named_entry(old_entry, saved_subdirectory, self.clock.clone()).await?;
+        let properties = state_locked
+            .dead_properties
+            .get(name_here)
+            .cloned()
+            .unwrap_or_default();
+        // A copy is a new file, so it starts without earlier versions.
         match state_there_locked {
-            Some(ref mut value) => {
-                Self::write_into_directory(self.clone(), value, name_there, new_entry)
-            }
-            None => {
-                Self::write_into_directory(self.clone(), &mut state_locked, name_there, new_entry)
-            }
+            Some(ref mut value) => Self::write_into_directory(
+                self.clone(),
+                value,
+                name_there,
+                new_entry,
+                properties,
+                Default::default(),
+            ),
+            None => Self::write_into_directory(
+                self.clone(),
+                &mut state_locked,
+                name_there,
+                new_entry,
+                properties,
+                Default::default(),
+            ),
         }
 
         if let Some(mut state_there_locked_present) = state_there_locked {
@@ -926,6 +1334,7 @@ impl OpenDirectory {
 
     async fn copy_named_entry(
         original: &NamedEntry,
+        saved_subdirectory: Option<(Arc<OpenDirectory>, BlobDigest)>,
         clock: WallClock,
     ) -> std::result::Result<NamedEntry, Error> {
         match original {
@@ -944,7 +1353,18 @@ impl OpenDirectory {
                     status.digest.last_known_digest,
                 ))
             }
-            NamedEntry::OpenSubdirectory(_arc, _receiver) => todo!(),
+            NamedEntry::OpenSubdirectory(subdirectory, _receiver) => match saved_subdirectory {
+                Some((saved, digest)) if Arc::ptr_eq(&saved, subdirectory) => {
+                    Ok(NamedEntry::NotOpen(
+                        DirectoryEntryMetaData::new(DirectoryEntryKind::Directory, clock()),
+                        digest,
+                    ))
+                }
+                _ => {
+                    warn!("The directory was replaced while it was saved for copying it");
+                    Err(Error::SaveFailed)
+                }
+            },
         }
     }
 
@@ -989,11 +1409,22 @@ impl OpenDirectory {
         );
 
         let (_obsolete_name, entry) = /*TODO: stop watching the entry*/ state_locked.names.remove_entry(name_here).unwrap();
+        let properties = state_locked
+            .dead_properties
+            .remove(name_here)
+            .unwrap_or_default();
+        let versions = state_locked.take_versions(name_here);
         match state_there_locked {
-            Some(ref mut value) => self.clone().write_into_directory(value, name_there, entry),
-            None => self
+            Some(ref mut value) => self
                 .clone()
-                .write_into_directory(&mut state_locked, name_there, entry),
+                .write_into_directory(value, name_there, entry, properties, versions),
+            None => self.clone().write_into_directory(
+                &mut state_locked,
+                name_there,
+                entry,
+                properties,
+                versions,
+            ),
         }
 
         Self::notify_about_change(&mut state_locked, &self.change_event_sender).await;
@@ -1008,6 +1439,8 @@ impl OpenDirectory {
         state: &mut MutexGuard<'_, OpenDirectoryMutableState>,
         name_there: &FileName,
         entry: NamedEntry,
+        properties: DeadProperties,
+        versions: (FileHistory, Option<FileVersion>),
     ) {
         match state.names.get_mut(name_there) {
             Some(existing_name) => *existing_name = entry,
@@ -1015,6 +1448,169 @@ impl OpenDirectory {
                 self.insert_entry(state, name_there.clone(), entry);
             }
         };
+        state.set_dead_properties(name_there.clone(), properties);
+        state.put_versions(name_there.clone(), versions);
+    }
+
+    pub async fn get_dead_properties(&self, name: &FileName) -> Result<DeadProperties> {
+        let mut state_locked = self.state.lock().await;
+        state_locked.record_access((self.clock)());
+        if !state_locked.names.contains_key(name) {
+            return Err(Error::NotFound(name.clone()));
+        }
+        Ok(state_locked
+            .dead_properties
+            .get(name)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    /// Applies all of the changes or none of them.
+    pub async fn patch_dead_properties(
+        &self,
+        name: &FileName,
+        changes: Vec<DeadPropertyChange>,
+    ) -> Result<()> {
+        let mut state_locked = self.state.lock().await;
+        state_locked.record_access((self.clock)());
+        if !state_locked.names.contains_key(name) {
+            return Err(Error::NotFound(name.clone()));
+        }
+        let mut properties = state_locked
+            .dead_properties
+            .get(name)
+            .cloned()
+            .unwrap_or_default();
+        for change in changes {
+            match change {
+                DeadPropertyChange::Set(property_name, value) => {
+                    properties.set(property_name, value)
+                }
+                DeadPropertyChange::Remove(property_name) => {
+                    properties.remove(&property_name);
+                }
+            }
+        }
+        match properties.check_size() {
+            Ok(_) => {}
+            Err(serialization::DeadPropertiesError::TooLarge { size_in_bytes }) => {
+                warn!(
+                    "Cannot store dead properties of {} because they would be too large ({} bytes)",
+                    name, size_in_bytes
+                );
+                return Err(Error::DeadPropertiesTooLarge { size_in_bytes });
+            }
+        }
+        debug!(
+            "Changing the dead properties of {} sends a change event for the directory.",
+            name
+        );
+        state_locked.set_dead_properties(name.clone(), properties);
+        Self::notify_about_change(&mut state_locked, &self.change_event_sender).await;
+        Ok(())
+    }
+
+    /// Applies to this directory and all of its subdirectories including the ones opened later.
+    pub fn set_version_retention<'t>(&'t self, retention: VersionRetention) -> Future<'t, ()> {
+        Box::pin(async move {
+            if retention.max_versions > FileHistory::MAX_VERSIONS {
+                return Err(Error::InvalidArgument(format!(
+                    "Cannot keep {} versions of a file, the maximum is {}",
+                    retention.max_versions,
+                    FileHistory::MAX_VERSIONS
+                )));
+            }
+            let mut state_locked = self.state.lock().await;
+            state_locked.version_retention = retention;
+            for entry in state_locked.names.values() {
+                if let NamedEntry::OpenSubdirectory(subdirectory, _) = entry {
+                    subdirectory.set_version_retention(retention).await?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// The earlier versions of a file, oldest first. Versions are only recorded when the directory is saved.
+    pub async fn get_file_history(&self, name: &FileName) -> Result<FileHistory> {
+        let mut state_locked = self.state.lock().await;
+        state_locked.record_access((self.clock)());
+        match state_locked.names.get(name) {
+            Some(entry) => match entry.get_meta_data().await.kind {
+                DirectoryEntryKind::Directory => {
+                    Err(Error::CannotOpenDirectoryAsRegularFile(name.clone()))
+                }
+                DirectoryEntryKind::File(_) => Ok(state_locked
+                    .file_histories
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_default()),
+            },
+            None => Err(Error::NotFound(name.clone())),
+        }
+    }
+
+    async fn find_version(
+        state_locked: &OpenDirectoryMutableState,
+        name: &FileName,
+        index: usize,
+    ) -> Result<FileVersion> {
+        match state_locked.names.get(name) {
+            Some(entry) => match entry.get_meta_data().await.kind {
+                DirectoryEntryKind::Directory => {
+                    Err(Error::CannotOpenDirectoryAsRegularFile(name.clone()))
+                }
+                DirectoryEntryKind::File(_) => state_locked
+                    .file_histories
+                    .get(name)
+                    .and_then(|history| history.versions().get(index))
+                    .copied()
+                    .ok_or_else(|| Error::VersionNotFound {
+                        name: name.clone(),
+                        index,
+                    }),
+            },
+            None => Err(Error::NotFound(name.clone())),
+        }
+    }
+
+    /// Opens an earlier version of a file. The result is not connected to the directory, so writing to it has no effect.
+    pub async fn open_version(&self, name: &FileName, index: usize) -> Result<Arc<OpenFile>> {
+        let mut state_locked = self.state.lock().await;
+        state_locked.record_access((self.clock)());
+        let version = Self::find_version(&state_locked, name, index).await?;
+        Ok(Arc::new(OpenFile::new(
+            OpenFileContentBuffer::from_storage(
+                version.content,
+                version.size,
+                self.open_file_write_buffer_in_blocks,
+            ),
+            self.storage.clone(),
+            version.saved_at,
+        )))
+    }
+
+    /// Makes an earlier version the current content of the file. The replaced content becomes a version itself.
+    pub async fn restore_version(
+        self: Arc<OpenDirectory>,
+        name: &FileName,
+        index: usize,
+    ) -> Result<()> {
+        let mut state_locked = self.state.lock().await;
+        state_locked.record_access((self.clock)());
+        let version = Self::find_version(&state_locked, name, index).await?;
+        info!(
+            "Restoring version {} of {} sends a change event for the directory.",
+            index, name
+        );
+        let restored = NamedEntry::NotOpen(
+            DirectoryEntryMetaData::new(DirectoryEntryKind::File(version.size), (self.clock)()),
+            version.content,
+        );
+        let replaced = std::mem::replace(state_locked.names.get_mut(name).unwrap(), restored);
+        replaced.close_after_removal().await;
+        Self::notify_about_change(&mut state_locked, &self.change_event_sender).await;
+        Ok(())
     }
 
     pub async fn watch(&self) -> tokio::sync::watch::Receiver<OpenDirectoryStatus> {
@@ -1029,11 +1625,90 @@ impl OpenDirectory {
                 &mut state_locked,
                 self.storage.as_ref(),
                 &self.original_path,
+                (self.clock)(),
+                self.prefetcher.stats(),
             )
             .await
         })
     }
 
+    /// Makes sure that the next [OpenDirectory::request_save] saves every directory from this one down to `path` even
+    /// if the change notifications of their entries are still on the way. With `recursive`, this includes every open
+    /// directory below `path`.
+    fn expect_changes<'t>(
+        self: &'t Arc<OpenDirectory>,
+        path: NormalizedPath,
+        recursive: bool,
+    ) -> Future<'t, ()> {
+        Box::pin(async move {
+            let next = match path.split_left() {
+                PathSplitLeftResult::Root => None,
+                PathSplitLeftResult::Leaf(name) => Some((name, NormalizedPath::root())),
+                PathSplitLeftResult::Directory(name, tail) => Some((name, tail)),
+            };
+            let subdirectories: Vec<Arc<OpenDirectory>> = {
+                let mut state_locked = self.state.lock().await;
+                Self::notify_about_change(&mut state_locked, &self.change_event_sender).await;
+                if next.is_none() && recursive {
+                    state_locked
+                        .names
+                        .values()
+                        .filter_map(|entry| match entry {
+                            NamedEntry::OpenSubdirectory(directory, _receiver) => {
+                                Some(directory.clone())
+                            }
+                            _ => None,
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                }
+            };
+            match next {
+                Some((name, tail)) => {
+                    let subdirectory = self.clone().open_subdirectory(name).await?;
+                    subdirectory.expect_changes(tail, recursive).await
+                }
+                None => {
+                    for subdirectory in subdirectories {
+                        subdirectory
+                            .expect_changes(NormalizedPath::root(), true)
+                            .await?;
+                    }
+                    Ok(())
+                }
+            }
+        })
+    }
+
+    /// Sums up the bytes that open files below this directory haven't stored yet. Unlike the status, this doesn't have
+    /// to wait for the next save.
+    pub fn count_unsaved_bytes<'t>(&'t self) -> Future<'t, u64> {
+        Box::pin(async move {
+            let mut unsaved_bytes = 0;
+            let subdirectories: Vec<Arc<OpenDirectory>> = {
+                let state_locked = self.state.lock().await;
+                let mut subdirectories = Vec::new();
+                for entry in state_locked.names.values() {
+                    match entry {
+                        NamedEntry::NotOpen(_meta_data, _digest) => {}
+                        NamedEntry::OpenRegularFile(_open_file, receiver) => {
+                            unsaved_bytes += receiver.borrow().bytes_unflushed_count;
+                        }
+                        NamedEntry::OpenSubdirectory(directory, _receiver) => {
+                            subdirectories.push(directory.clone());
+                        }
+                    }
+                }
+                subdirectories
+            };
+            for subdirectory in subdirectories {
+                unsaved_bytes += subdirectory.count_unsaved_bytes().await?;
+            }
+            Ok(unsaved_bytes)
+        })
+    }
+
     async fn notify_about_change(
         state_locked: &mut OpenDirectoryMutableState,
         change_event_sender: &tokio::sync::watch::Sender<OpenDirectoryStatus>,
@@ -1060,23 +1735,26 @@ impl OpenDirectory {
         state_locked: &mut OpenDirectoryMutableState,
         storage: &(dyn LoadStoreTree + Send + Sync),
         original_path: &std::path::Path,
+        now: std::time::SystemTime,
+        prefetch: PrefetchStats,
     ) -> Result<OpenDirectoryStatus> {
         let digest: Option<BlobDigest> =
-            Self::consider_saving(state_locked, storage, original_path).await?;
-        Ok(Self::update_status(change_event_sender, state_locked, digest).await)
+            Self::consider_saving(state_locked, storage, original_path, now).await?;
+        Ok(Self::update_status(change_event_sender, state_locked, digest, prefetch).await)
     }
 
     async fn consider_saving(
         state_locked: &mut OpenDirectoryMutableState,
         storage: &(dyn LoadStoreTree + Send + Sync),
         original_path: &std::path::Path,
+        now: std::time::SystemTime,
     ) -> Result<Option<BlobDigest>> {
         if state_locked.has_unsaved_changes {
             debug!("We should save this directory.");
             for entry in state_locked.names.iter() {
                 entry.1.request_save().await?;
             }
-            let saved = match Self::save(state_locked, storage).await {
+            let saved = match Self::save(state_locked, storage, now).await {
                 Ok(saved) => saved,
                 Err(error) => {
                     error!(
@@ -1101,12 +1779,16 @@ impl OpenDirectory {
         change_event_sender: &tokio::sync::watch::Sender<OpenDirectoryStatus>,
         state_locked: &mut OpenDirectoryMutableState,
         new_digest: Option<BlobDigest>,
+        prefetch: PrefetchStats,
     ) -> OpenDirectoryStatus {
         let mut directories_open_count: usize= /*count self*/ 1;
         let mut directories_unsaved_count: usize = 0;
-        let mut open_files = OpenFileStats::new(0, 0, 0, 0, 0);
+        let mut open_files = OpenFileStats::new(0, 0, 0, 0, 0, prefetch);
         let mut are_children_up_to_date = true;
         for entry in state_locked.names.iter_mut() {
+            if let NamedEntry::OpenRegularFile(open_file, _) = entry.1 {
+                open_files.prefetch.add(&open_file.prefetch_stats());
+            }
             let named_entry_status = entry.1.get_status();
             match named_entry_status {
                 NamedEntryStatus::Closed(_directory_entry_kind, _blob_digest) => {}
@@ -1176,30 +1858,55 @@ impl OpenDirectory {
     async fn save(
         state_locked: &mut OpenDirectoryMutableState,
         storage: &(dyn LoadStoreTree + Send + Sync),
+        now: std::time::SystemTime,
     ) -> std::result::Result<BlobDigest, Box<dyn std::error::Error>> {
-        let mut entries: BTreeMap<FileName, (DirectoryEntryKind, BlobDigest)> = BTreeMap::new();
+        let mut saved_files = Vec::new();
+        let mut entries: BTreeMap<
+            FileName,
+            (DirectoryEntryKind, BlobDigest, DeadProperties, FileHistory),
+        > = BTreeMap::new();
         for entry in state_locked.names.iter_mut() {
             let name = entry.0;
             let named_entry_status = entry.1.get_status();
-            let (kind, digest) = match named_entry_status {
+            let (kind, digest, is_open_for_writing) = match named_entry_status {
                 NamedEntryStatus::Closed(directory_entry_kind, blob_digest) => {
-                    (directory_entry_kind, blob_digest)
+                    (directory_entry_kind, blob_digest, false)
                 }
                 NamedEntryStatus::Open(open_named_entry_status) => match open_named_entry_status {
                     OpenNamedEntryStatus::Directory(open_directory_status) => (
                         serialization::DirectoryEntryKind::Directory,
                         open_directory_status.digest.last_known_digest,
+                        false,
                     ),
                     OpenNamedEntryStatus::File(open_file_status) => (
                         serialization::DirectoryEntryKind::File(
                             open_file_status.last_known_digest_file_size,
                         ),
                         open_file_status.digest.last_known_digest,
+                        open_file_status.is_open_for_writing,
                     ),
                 },
             };
-            entries.insert(name.clone(), (kind, digest));
-        }
+            if let DirectoryEntryKind::File(size) = kind {
+                saved_files.push((name.clone(), digest, size, is_open_for_writing));
+            }
+            entries.insert(
+                name.clone(),
+                (kind, digest, DeadProperties::new(), FileHistory::new()),
+            );
+        }
+        for (name, digest, size, is_open_for_writing) in saved_files {
+            state_locked.record_saved_content(&name, digest, size, is_open_for_writing, now);
+        }
+        state_locked.apply_version_retention(now);
+        for (name, (_kind, _digest, properties, history)) in entries.iter_mut() {
+            if let Some(found) = state_locked.dead_properties.get(name) {
+                *properties = found.clone();
+            }
+            if let Some(found) = state_locked.file_histories.get(name) {
+                *history = found.clone();
+            }
+        }
         serialize_directory(&entries, storage).await
     }
 
@@ -1207,7 +1914,7 @@ impl OpenDirectory {
 
     pub async fn drop_all_read_caches(&self) -> CacheDropStats {
         let mut state_locked = self.state.lock().await;
-        let mut result = CacheDropStats::new(0, 0, 0, 0);
+        let mut result = CacheDropStats::new(self.prefetcher.drop_all_read_caches().await, 0, 0, 0);
         for (_name, entry) in state_locked.names.iter_mut() {
             result.add(&entry.drop_all_read_caches().await);
         }
@@ -1241,7 +1948,7 @@ pub enum PathSplitRightResult {
     Entry(NormalizedPath, FileName),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct NormalizedPath {
     components: VecDeque<FileName>,
 }
@@ -1269,6 +1976,16 @@ impl NormalizedPath {
         }
     }
 
+    /// Returns true if `prefix` is this path itself or one of its ancestors.
+    pub fn starts_with(&self, prefix: &NormalizedPath) -> bool {
+        prefix.components.len() <= self.components.len()
+            && prefix
+                .components
+                .iter()
+                .zip(self.components.iter())
+                .all(|(left, right)| left == right)
+    }
+
     pub fn split_left(mut self) -> PathSplitLeftResult {
         let head = match self.components.pop_front() {
             Some(head) => head,
@@ -1293,6 +2010,38 @@ impl NormalizedPath {
         };
         PathSplitRightResult::Entry(self, tail)
     }
+
+    pub fn join(mut self, name: FileName) -> NormalizedPath {
+        self.components.push_back(name);
+        self
+    }
+
+    pub fn file_name(&self) -> Option<&FileName> {
+        self.components.back()
+    }
+
+    pub fn components(&self) -> impl Iterator<Item = &FileName> {
+        self.components.iter()
+    }
+
+    /// Returns the path with `prefix` exchanged for `replacement`, or `None` if the path doesn't start with `prefix`.
+    pub fn replace_prefix(
+        &self,
+        prefix: &NormalizedPath,
+        replacement: &NormalizedPath,
+    ) -> Option<NormalizedPath> {
+        if !self.starts_with(prefix) {
+            return None;
+        }
+        let mut components = replacement.components.clone();
+        components.extend(
+            self.components
+                .iter()
+                .skip(prefix.components.len())
+                .cloned(),
+        );
+        Some(NormalizedPath { components })
+    }
 }
 
 #[derive(PartialEq, Debug, Copy, Clone, PartialOrd, Ord, Eq)]
@@ -1369,10 +2118,19 @@ impl std::fmt::Debug for LoadedBlock {
     }
 }
 
+static ZEROES: [u8; TREE_BLOB_MAX_LENGTH] = [0u8; TREE_BLOB_MAX_LENGTH];
+
+/// The content of [OpenFileContentBlock::Hole]. Reading from a hole doesn't allocate anything.
+fn zero_block() -> bytes::Bytes {
+    bytes::Bytes::from_static(&ZEROES)
+}
+
 #[derive(Debug, PartialEq)]
 pub enum OpenFileContentBlock {
     NotLoaded(BlobDigest, u16),
     Loaded(LoadedBlock),
+    /// [TREE_BLOB_MAX_LENGTH] zeros that are neither stored nor kept in memory (see [Segment::Hole])
+    Hole,
 }
 
 impl OpenFileContentBlock {
@@ -1388,7 +2146,7 @@ impl OpenFileContentBlock {
                     Self::load(&blob_digest, size, storage).await
                 }))
             }
-            OpenFileContentBlock::Loaded(_loaded_block) => None,
+            OpenFileContentBlock::Loaded(_) | OpenFileContentBlock::Hole => None,
         }
     }
 
@@ -1401,6 +2159,7 @@ impl OpenFileContentBlock {
                 LoadedBlock::KnownDigest(_hashed_tree) => todo!(),
                 LoadedBlock::UnknownDigest(_vec) => todo!(),
             },
+            OpenFileContentBlock::Hole => todo!(),
         }
         *self = OpenFileContentBlock::Loaded(LoadedBlock::KnownDigest(prepared));
     }
@@ -1464,7 +2223,7 @@ impl OpenFileContentBlock {
                 let loaded = Self::load(blob_digest, *size, storage).await?;
                 *self = OpenFileContentBlock::Loaded(LoadedBlock::KnownDigest(loaded));
             }
-            OpenFileContentBlock::Loaded(_) => {}
+            OpenFileContentBlock::Loaded(_) | OpenFileContentBlock::Hole => {}
         }
         Ok(match self {
             OpenFileContentBlock::NotLoaded(_blob_digest, _) => panic!(),
@@ -1472,6 +2231,7 @@ impl OpenFileContentBlock {
                 LoadedBlock::KnownDigest(hashed_tree) => hashed_tree.tree().blob().content.clone(),
                 LoadedBlock::UnknownDigest(vec) => bytes::Bytes::copy_from_slice(vec),
             },
+            OpenFileContentBlock::Hole => zero_block(),
         })
     }
 
@@ -1485,9 +2245,18 @@ impl OpenFileContentBlock {
                 *self = OpenFileContentBlock::Loaded(LoadedBlock::KnownDigest(loaded));
             }
             OpenFileContentBlock::Loaded(_) => {}
+            OpenFileContentBlock::Hole => {
+                // only the block that is written to is allocated
+                *self = OpenFileContentBlock::Loaded(LoadedBlock::UnknownDigest(vec![
+                    0u8;
+                    TREE_BLOB_MAX_LENGTH
+                ]));
+            }
         }
         match self {
-            OpenFileContentBlock::NotLoaded(_blob_digest, _) => panic!(),
+            OpenFileContentBlock::NotLoaded(_, _) | OpenFileContentBlock::Hole => {
+                panic!()
+            }
             OpenFileContentBlock::Loaded(loaded) => match loaded {
                 LoadedBlock::KnownDigest(hashed_tree) => {
                     *loaded =
@@ -1497,7 +2266,9 @@ impl OpenFileContentBlock {
             },
         }
         match self {
-            OpenFileContentBlock::NotLoaded(_blob_digest, _) => panic!(),
+            OpenFileContentBlock::NotLoaded(_, _) | OpenFileContentBlock::Hole => {
+                panic!()
+            }
             OpenFileContentBlock::Loaded(loaded) => match loaded {
                 LoadedBlock::KnownDigest(_hashed_tree) => {
                     panic!()
@@ -1550,6 +2321,9 @@ impl OpenFileContentBlock {
                 new_size, max_size
             )));
         }
+        if (*self == OpenFileContentBlock::Hole) && (new_size == TREE_BLOB_MAX_LENGTH) {
+            return Ok(());
+        }
         let data = self.access_content_for_writing(storage).await?;
         let current_size = data.len();
         if new_size < current_size {
@@ -1565,14 +2339,27 @@ impl OpenFileContentBlock {
         &mut self,
         is_allowed_to_calculate_digest: bool,
         storage: Arc<dyn LoadStoreTree + Send + Sync>,
-    ) -> std::result::Result<Option<BlobDigest>, StoreError> {
+    ) -> std::result::Result<Option<Segment>, StoreError> {
         match self {
-            OpenFileContentBlock::NotLoaded(blob_digest, _) => Ok(Some(*blob_digest)),
+            OpenFileContentBlock::NotLoaded(blob_digest, _) => {
+                Ok(Some(Segment::Stored(*blob_digest)))
+            }
+            OpenFileContentBlock::Hole => Ok(Some(Segment::Hole)),
             OpenFileContentBlock::Loaded(loaded) => {
                 let hashed_tree = match loaded {
-                    LoadedBlock::KnownDigest(hashed_tree) => hashed_tree.clone(),
+                    LoadedBlock::KnownDigest(hashed_tree) => {
+                        if is_hole(hashed_tree.tree().blob().as_slice()) {
+                            *self = OpenFileContentBlock::Hole;
+                            return Ok(Some(Segment::Hole));
+                        }
+                        hashed_tree.clone()
+                    }
                     LoadedBlock::UnknownDigest(vec) => {
                         assert!(vec.len() <= TREE_BLOB_MAX_LENGTH);
+                        if is_hole(vec) {
+                            *self = OpenFileContentBlock::Hole;
+                            return Ok(Some(Segment::Hole));
+                        }
                         if !is_allowed_to_calculate_digest {
                             return Ok(None);
                         }
@@ -1589,7 +2376,7 @@ impl OpenFileContentBlock {
                 assert_eq!(hashed_tree.digest(), &result);
                 // free the memory
                 *self = OpenFileContentBlock::NotLoaded(result, size);
-                Ok(Some(result))
+                Ok(Some(Segment::Stored(result)))
             }
         }
     }
@@ -1597,6 +2384,7 @@ impl OpenFileContentBlock {
     pub fn size(&self) -> u16 {
         match self {
             OpenFileContentBlock::NotLoaded(_blob_digest, size) => *size,
+            OpenFileContentBlock::Hole => TREE_BLOB_MAX_LENGTH as u16,
             OpenFileContentBlock::Loaded(loaded) => match loaded {
                 LoadedBlock::KnownDigest(hashed_tree) => hashed_tree.tree().blob().len(),
                 LoadedBlock::UnknownDigest(vec) => vec.len() as u16,
@@ -1606,7 +2394,9 @@ impl OpenFileContentBlock {
 
     async fn drop_all_read_caches(&mut self) -> CacheDropStats {
         match self {
-            OpenFileContentBlock::NotLoaded(_blob_digest, _) => CacheDropStats::new(0, 0, 0, 0),
+            OpenFileContentBlock::NotLoaded(_, _) | OpenFileContentBlock::Hole => {
+                CacheDropStats::new(0, 0, 0, 0)
+            }
             OpenFileContentBlock::Loaded(loaded_block) => match loaded_block {
                 LoadedBlock::KnownDigest(hashed_tree) => {
                     // free some memory:
@@ -1656,9 +2446,18 @@ impl StreakDirection {
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct AccessOrderLowerIsMoreRecent(pub usize);
 
+#[derive(Debug, PartialEq, Default)]
+struct PrefetchHitTracking {
+    /// Blocks that were loaded because of a prediction and have not been requested since.
+    prefetched_blocks: BTreeSet<u64>,
+    stats: PrefetchStats,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Prefetcher {
     last_explicitly_requested_blocks: cached::stores::SizedCache<u64, ()>,
+    // boxed to keep loaded file contents from growing much larger than unloaded ones
+    hit_tracking: Box<PrefetchHitTracking>,
 }
 
 impl Default for Prefetcher {
@@ -1671,6 +2470,36 @@ impl Prefetcher {
     pub fn new() -> Self {
         Self {
             last_explicitly_requested_blocks: cached::stores::SizedCache::with_size(16),
+            hit_tracking: Box::default(),
+        }
+    }
+
+    const MAX_PREFETCHED_BLOCKS_TRACKED: usize = 64;
+
+    pub fn stats(&self) -> PrefetchStats {
+        self.hit_tracking.stats
+    }
+
+    fn record_explicitly_requested_blocks(&mut self, requested: std::ops::Range<u64>) {
+        let tracking = &mut self.hit_tracking;
+        let hits = tracking
+            .prefetched_blocks
+            .range(requested)
+            .copied()
+            .collect::<Vec<_>>();
+        for hit in hits {
+            tracking.prefetched_blocks.remove(&hit);
+            tracking.stats.hits += 1;
+        }
+    }
+
+    fn record_prefetched_block(&mut self, block_index: u64) {
+        let tracking = &mut self.hit_tracking;
+        if tracking.prefetched_blocks.insert(block_index) {
+            tracking.stats.prefetched += 1;
+        }
+        while tracking.prefetched_blocks.len() > Self::MAX_PREFETCHED_BLOCKS_TRACKED {
+            tracking.prefetched_blocks.pop_first();
         }
     }
 
@@ -1782,6 +2611,7 @@ impl Prefetcher {
         explicitly_requested_blocks_right_now: std::ops::Range<u64>,
         storage: Arc<dyn LoadStoreTree + Send + Sync>,
     ) {
+        self.record_explicitly_requested_blocks(explicitly_requested_blocks_right_now.clone());
         for index in explicitly_requested_blocks_right_now
             .clone()
             .take(self.max_number_of_blocks_tracked())
@@ -1793,7 +2623,9 @@ impl Prefetcher {
 
         let mut blocks_to_load = blocks_to_prefetch;
         blocks_to_load.extend(
-            explicitly_requested_blocks_right_now.take(self.max_number_of_blocks_tracked()),
+            explicitly_requested_blocks_right_now
+                .clone()
+                .take(self.max_number_of_blocks_tracked()),
         );
 
         let futures: Vec<Future<(u64, Result<HashedTree>)>> = blocks_to_load
@@ -1827,10 +2659,16 @@ impl Prefetcher {
             };
             let block = &mut blocks[block_index as usize];
             block.set_prepare_for_reading_result(prepared);
+            if !explicitly_requested_blocks_right_now.contains(&block_index) {
+                self.record_prefetched_block(block_index);
+            }
         }
     }
 }
 
+/// Files made of more blocks than this are stored as a tree of segmented blobs.
+pub(crate) const SEGMENTED_BLOB_MAX_CHILDREN_PER_TREE: usize = 20;
+
 #[derive(Debug, PartialEq)]
 pub struct OpenFileContentBufferLoaded {
     size: u64,
@@ -1880,7 +2718,7 @@ impl OpenFileContentBufferLoaded {
         let mut skipped = 0;
         while let Some(index) = self.dirty_blocks.get(skipped) {
             let block = &mut self.blocks[*index];
-            let block_stored: Option<BlobDigest> = block.try_store(false, storage.clone()).await?;
+            let block_stored: Option<Segment> = block.try_store(false, storage.clone()).await?;
             match block_stored {
                 Some(_) => {
                     self.dirty_blocks.pop_front();
@@ -1922,11 +2760,10 @@ impl OpenFileContentBufferLoaded {
         self.verify_integrity();
         self.dirty_blocks.clear();
         assert!(!blocks_stored.is_empty());
-        let max_children_per_tree = 20;
         let reference = save_segmented_blob(
             &blocks_stored,
             total_size_in_bytes,
-            max_children_per_tree,
+            SEGMENTED_BLOB_MAX_CHILDREN_PER_TREE,
             storage.as_ref(),
         )
         .await?;
@@ -1970,17 +2807,9 @@ impl OpenFileContentBufferLoaded {
                 .await?;
         }
         if new_number_of_blocks > self.blocks.len() {
-            let filler = HashedTree::from(Arc::new(Tree::new(
-                TreeBlob::try_from(bytes::Bytes::from(vec![0u8; TREE_BLOB_MAX_LENGTH])).unwrap(),
-                TreeChildren::empty(),
-            )));
-            assert!(new_number_of_blocks >= 1);
-            for index in self.blocks.len()..(new_number_of_blocks - 1) {
-                self.dirty_blocks.push_back(index);
-            }
-            self.blocks.resize_with(new_number_of_blocks, || {
-                OpenFileContentBlock::Loaded(LoadedBlock::KnownDigest(filler.clone()))
-            });
+            // Holes don't have to be stored, so they are never dirty.
+            self.blocks
+                .resize_with(new_number_of_blocks, || OpenFileContentBlock::Hole);
         } else if new_number_of_blocks < self.blocks.len() {
             self.blocks.truncate(new_number_of_blocks);
             // remove dirty blocks that don't exist anymore
@@ -2148,6 +2977,18 @@ impl OpenFileContentBuffer {
         }
     }
 
+    /// Starts from zero again whenever the content is loaded.
+    pub fn prefetch_stats(&self) -> PrefetchStats {
+        match self {
+            OpenFileContentBuffer::NotLoaded {
+                digest: _,
+                size: _,
+                write_buffer_in_blocks: _,
+            } => PrefetchStats::default(),
+            OpenFileContentBuffer::Loaded(loaded) => loaded.prefetcher.stats(),
+        }
+    }
+
     pub fn unsaved_blocks(&self) -> u64 {
         match self {
             OpenFileContentBuffer::NotLoaded {
@@ -2216,9 +3057,17 @@ impl OpenFileContentBuffer {
                             directory_entry_size: *size,
                         });
                     }
-                    let full_blocks = segments.iter().take(segments.len() - 1).map(|reference| {
-                        OpenFileContentBlock::NotLoaded(*reference, TREE_BLOB_MAX_LENGTH as u16)
-                    });
+                    let full_blocks =
+                        segments
+                            .iter()
+                            .take(segments.len() - 1)
+                            .map(|segment| match segment {
+                                Segment::Stored(reference) => OpenFileContentBlock::NotLoaded(
+                                    *reference,
+                                    TREE_BLOB_MAX_LENGTH as u16,
+                                ),
+                                Segment::Hole => OpenFileContentBlock::Hole,
+                            });
                     let full_blocks_size = full_blocks.len() as u64 * TREE_BLOB_MAX_LENGTH as u64;
                     if full_blocks_size > *size {
                         todo!()
@@ -2227,12 +3076,18 @@ impl OpenFileContentBuffer {
                     if final_block_size > TREE_BLOB_MAX_LENGTH as u64 {
                         todo!()
                     }
-                    full_blocks
-                        .chain(std::iter::once(OpenFileContentBlock::NotLoaded(
-                            *segments.last().unwrap(),
-                            final_block_size as u16,
-                        )))
-                        .collect()
+                    let final_block = match segments.last().unwrap() {
+                        Segment::Stored(reference) => {
+                            OpenFileContentBlock::NotLoaded(*reference, final_block_size as u16)
+                        }
+                        Segment::Hole => {
+                            if final_block_size != TREE_BLOB_MAX_LENGTH as u64 {
+                                return Err(Error::FileSizeMismatch);
+                            }
+                            OpenFileContentBlock::Hole
+                        }
+                    };
+                    full_blocks.chain(std::iter::once(final_block)).collect()
                 };
                 *self = Self::Loaded(OpenFileContentBufferLoaded {
                     size: *size,
@@ -2277,7 +3132,11 @@ impl OpenFileContentBuffer {
             );
             loaded
                 .prefetcher
-                .prefetch(blocks, first_block_index..last_block_index, storage.clone())
+                .prefetch(
+                    blocks,
+                    first_block_index..(last_block_index + 1),
+                    storage.clone(),
+                )
                 .await;
         }
 
@@ -2347,7 +3206,11 @@ impl OpenFileContentBuffer {
 
         let first_block_index = position / (TREE_BLOB_MAX_LENGTH as u64);
         if first_block_index >= (loaded.blocks.len() as u64) {
-            if let Some(last_block) = loaded.blocks.last_mut() {
+            if let Some(last_block) = loaded
+                .blocks
+                .last_mut()
+                .filter(|last_block| last_block.size() < TREE_BLOB_MAX_LENGTH as u16)
+            {
                 let filler = TREE_BLOB_MAX_LENGTH - last_block.size() as usize;
                 let write_result = last_block
                     .write(
@@ -2359,21 +3222,8 @@ impl OpenFileContentBuffer {
                 assert!(write_result.remaining.is_empty());
                 loaded.dirty_blocks.push_back(loaded.blocks.len() - 1);
             }
-            if first_block_index > (loaded.blocks.len() as u64) {
-                // We only need to calculate this block once and can use it many times in the loop below.
-                let filler = HashedTree::from(Arc::new(Tree::new(
-                    TreeBlob::try_from(bytes::Bytes::from(vec![0u8; TREE_BLOB_MAX_LENGTH]))
-                        .unwrap(),
-                    TreeChildren::empty(),
-                )));
-                while first_block_index > (loaded.blocks.len() as u64) {
-                    loaded.dirty_blocks.push_back(loaded.blocks.len());
-                    loaded
-                        .blocks
-                        .push(OpenFileContentBlock::Loaded(LoadedBlock::KnownDigest(
-                            filler.clone(),
-                        )));
-                }
+            while first_block_index > (loaded.blocks.len() as u64) {
+                loaded.blocks.push(OpenFileContentBlock::Hole);
             }
         }
 
@@ -2519,9 +3369,12 @@ pub struct OpenFile {
     state: tokio::sync::Mutex<OpenFileMutableState>,
     change_event_sender: tokio::sync::watch::Sender<OpenFileStatus>,
     _change_event_receiver: tokio::sync::watch::Receiver<OpenFileStatus>,
-    modified: std::time::SystemTime,
+    // updated by the directory when it saves a changed content
+    modified: std::sync::Mutex<std::time::SystemTime>,
     read_permission: Arc<OpenFileReadPermission>,
     write_permission: Arc<OpenFileWritePermission>,
+    /// Survives the content being dropped from the read cache.
+    prefetch_stats: std::sync::Mutex<PrefetchStats>,
 }
 
 impl OpenFile {
@@ -2546,14 +3399,23 @@ impl OpenFile {
             }),
             change_event_sender: sender,
             _change_event_receiver: receiver,
-            modified,
+            modified: std::sync::Mutex::new(modified),
             read_permission: Arc::new(OpenFileReadPermission {}),
             write_permission: Arc::new(OpenFileWritePermission {}),
+            prefetch_stats: std::sync::Mutex::new(PrefetchStats::default()),
         }
     }
 
+    pub fn prefetch_stats(&self) -> PrefetchStats {
+        *self.prefetch_stats.lock().unwrap()
+    }
+
     pub fn modified(&self) -> std::time::SystemTime {
-        self.modified
+        *self.modified.lock().unwrap()
+    }
+
+    fn set_modified(&self, modified: std::time::SystemTime) {
+        *self.modified.lock().unwrap() = modified;
     }
 
     pub async fn size(&self) -> u64 {
@@ -2561,7 +3423,7 @@ impl OpenFile {
     }
 
     pub async fn get_meta_data(&self) -> FileMetaData {
-        FileMetaData::new(self.size().await, self.modified)
+        FileMetaData::new(self.size().await, self.modified())
     }
 
     pub async fn request_save(&self) -> std::result::Result<OpenFileStatus, Error> {
@@ -2643,6 +3505,10 @@ impl OpenFile {
     }
 
     pub fn notify_dropped_write_permission(&self) {
+        self.notify_changed_write_permission();
+    }
+
+    fn notify_changed_write_permission(&self) {
         self.change_event_sender.send_if_modified(|status| {
             let is_open_for_writing = Self::is_open_for_writing(&self.write_permission);
             if status.is_open_for_writing == is_open_for_writing {
@@ -2722,11 +3588,17 @@ impl OpenFile {
                     return Err(Error::FileRemoved);
                 }
             };
-            let read_result = state_locked
-                .content
-                .read(position, count, storage)
-                .await
-                .inspect(|bytes_read| debug!("Read {} bytes", bytes_read.len()))?;
+            let stats_before = state_locked.content.prefetch_stats();
+            let read_result = state_locked.content.read(position, count, storage).await;
+            let stats_after = state_locked.content.prefetch_stats();
+            self.prefetch_stats.lock().unwrap().add(&PrefetchStats::new(
+                stats_after
+                    .prefetched
+                    .saturating_sub(stats_before.prefetched),
+                stats_after.hits.saturating_sub(stats_before.hits),
+            ));
+            let read_result =
+                read_result.inspect(|bytes_read| debug!("Read {} bytes", bytes_read.len()))?;
             assert!(read_result.len() <= count);
             Ok(read_result)
         })
@@ -2819,6 +3691,14 @@ pub struct TreeEditor {
     root: Arc<OpenDirectory>,
     empty_directory_digest: Mutex<Option<BlobDigest>>,
     empty_file_digest: Mutex<Option<BlobDigest>>,
+    search_index: Mutex<search_index::SearchIndex>,
+    save_policy: SavePolicy,
+    persistence: Option<Arc<dyn PersistRoot + Send + Sync>>,
+    journal: Option<Arc<Journal>>,
+    trash_retention: Option<TrashRetention>,
+    // Moving entries into the trash and out again has to find a free name first.
+    trash_lock: Mutex<()>,
+    locks: Option<Arc<dyn CheckLocks + Send + Sync>>,
 }
 
 impl TreeEditor {
@@ -2827,7 +3707,307 @@ impl TreeEditor {
             root,
             empty_directory_digest: Mutex::new(empty_directory_digest),
             empty_file_digest: Mutex::new(None),
+            search_index: Mutex::new(search_index::SearchIndex::new()),
+            save_policy: SavePolicy::default(),
+            persistence: None,
+            journal: None,
+            trash_retention: None,
+            trash_lock: Mutex::new(()),
+            locks: None,
+        }
+    }
+
+    /// [TreeEditor::remove] moves entries into [TRASH_DIRECTORY_NAME] instead of deleting them.
+    pub fn with_trash(mut self, retention: TrashRetention) -> TreeEditor {
+        self.trash_retention = Some(retention);
+        self
+    }
+
+    /// [TreeEditor::save_regularly] follows `save_policy` and [TreeEditor::commit] makes the root durable with
+    /// `persistence`.
+    pub fn with_persistence(
+        mut self,
+        save_policy: SavePolicy,
+        persistence: Arc<dyn PersistRoot + Send + Sync>,
+    ) -> TreeEditor {
+        self.save_policy = save_policy;
+        self.persistence = Some(persistence);
+        self
+    }
+
+    /// Records every change in `journal` until it is persisted, see [Journal].
+    pub fn with_journal(mut self, journal: Arc<Journal>) -> TreeEditor {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Refuses every change that `locks` don't allow with [Error::Locked]. The changes hold the locks of the
+    /// surrounding [holding_locks] scope.
+    pub fn with_locks(mut self, locks: Arc<dyn CheckLocks + Send + Sync>) -> TreeEditor {
+        self.locks = Some(locks);
+        self
+    }
+
+    pub fn save_policy(&self) -> &SavePolicy {
+        &self.save_policy
+    }
+
+    /// Saves the tree according to the [SavePolicy] until the future is dropped. Saving alone doesn't make the changes
+    /// durable.
+    pub async fn save_regularly(&self) {
+        let mut unsaved_since = None;
+        loop {
+            let now = (self.root.get_clock())();
+            if self.root.latest_status().digest.is_digest_up_to_date {
+                unsaved_since = None;
+            } else {
+                unsaved_since.get_or_insert(now);
+            }
+            let unsaved_bytes = match self.save_policy.dirty_bytes_threshold {
+                Some(_) => match self.root.count_unsaved_bytes().await {
+                    Ok(unsaved_bytes) => unsaved_bytes,
+                    Err(error) => {
+                        error!("Could not count the unsaved bytes: {:?}", &error);
+                        0
+                    }
+                },
+                None => 0,
+            };
+            if self
+                .save_policy
+                .should_save(unsaved_bytes, unsaved_since, now)
+            {
+                debug!("Time to save the root.");
+                match self.root.request_save().await {
+                    Ok(status) => {
+                        if status.digest.is_digest_up_to_date {
+                            unsaved_since = None;
+                        }
+                    }
+                    Err(error) => {
+                        error!("request_save failed with {:?}", &error);
+                    }
+                }
+            }
+            tokio::time::sleep(self.save_policy.interval).await;
+        }
+    }
+
+    /// Saves everything that was written to `path` so far and makes it durable like an fsync would. Returns the digest
+    /// of the persisted root.
+    pub async fn commit(&self, path: NormalizedPath) -> Result<BlobDigest> {
+        if self.persistence.is_none() {
+            return Err(Error::NotPersistent);
         }
+        let (directory_path, recursive) = match self.get_meta_data(path.clone()).await?.kind {
+            DirectoryEntryKind::Directory => (path, true),
+            DirectoryEntryKind::File(_size) => match path.split_right() {
+                PathSplitRightResult::Root => unreachable!("The root is a directory."),
+                PathSplitRightResult::Entry(directory_path, _file_name) => (directory_path, false),
+            },
+        };
+        self.root.expect_changes(directory_path, recursive).await?;
+        self.persist().await
+    }
+
+    /// Saves the tree and makes the root durable. With a [Journal], the root is saved completely at a checkpoint and
+    /// the journal drops the changes that the root contains.
+    pub async fn persist(&self) -> Result<BlobDigest> {
+        let persistence = match &self.persistence {
+            Some(persistence) => persistence.clone(),
+            None => return Err(Error::NotPersistent),
+        };
+        let digest = match &self.journal {
+            Some(journal) => journal.checkpoint(&self.root, &*persistence).await?,
+            None => {
+                let status = self.root.request_save().await?;
+                let digest = status.digest.last_known_digest;
+                persistence.persist_root(&digest).await?;
+                digest
+            }
+        };
+        info!("Committed root {}", &digest);
+        Ok(digest)
+    }
+
+    /// Applies the changes that [Journal::open] found after a crash without recording them again. Changes that cannot
+    /// be applied anymore, like a write to a file that was renamed while it was open, are skipped. Returns how many
+    /// changes were applied.
+    pub async fn replay(&self, entries: Vec<JournalEntry>) -> usize {
+        let entry_count = entries.len();
+        let mut applied = 0;
+        for (index, entry) in entries.into_iter().enumerate() {
+            let applying = match self.check_change_locks(&entry) {
+                Ok(()) => self.apply(entry),
+                Err(error) => Box::pin(std::future::ready(Err(error))),
+            };
+            match applying.await {
+                Ok(()) => applied += 1,
+                Err(error) => {
+                    warn!(
+                        "Could not replay change {} of {}: {:?}",
+                        index + 1,
+                        entry_count,
+                        &error
+                    );
+                }
+            }
+        }
+        applied
+    }
+
+    fn change<'a>(&'a self, entry: JournalEntry) -> Future<'a, ()> {
+        Box::pin(async move {
+            self.check_change_locks(&entry)?;
+            match &self.journal {
+                Some(journal) => journal.record(entry.clone(), self.apply(entry)).await,
+                None => self.apply(entry).await,
+            }
+        })
+    }
+
+    fn check_locks(&self, path: &NormalizedPath, deep: bool) -> Result<()> {
+        match &self.locks {
+            Some(locks) => locks
+                .check(path, deep, &locks::held_lock_tokens())
+                .map_err(|token| {
+                    info!("Cannot change {:?} because of lock {}", path, token);
+                    Error::Locked(path.clone())
+                }),
+            None => Ok(()),
+        }
+    }
+
+    /// Removing, replacing or moving an entry changes everything below it, so the locks below count as well.
+    fn check_change_locks(&self, entry: &JournalEntry) -> Result<()> {
+        match entry {
+            JournalEntry::OpenFile { path, .. }
+            | JournalEntry::WriteFile { path, .. }
+            | JournalEntry::ResizeFile { path, .. }
+            | JournalEntry::CreateDirectory { path }
+            | JournalEntry::RestoreVersion { path, .. }
+            | JournalEntry::PatchDeadProperties { path, .. } => self.check_locks(path, false),
+            JournalEntry::Copy { to, .. } => self.check_locks(to, true),
+            JournalEntry::Rename { from, to } => {
+                self.check_locks(from, true)?;
+                self.check_locks(to, true)
+            }
+            JournalEntry::RemovePermanently { path } => self.check_locks(path, true),
+        }
+    }
+
+    fn apply<'a>(&'a self, entry: JournalEntry) -> Future<'a, ()> {
+        match entry {
+            JournalEntry::OpenFile {
+                path,
+                creation_mode,
+            } => Box::pin(async move {
+                self.apply_open_file(path, creation_mode).await?;
+                Ok(())
+            }),
+            JournalEntry::WriteFile {
+                path,
+                position,
+                content,
+            } => Box::pin(async move {
+                let file = self
+                    .apply_open_file(path, FileCreationMode::open_existing())
+                    .await?;
+                let write_permission = file.get_write_permission();
+                let result = file
+                    .write_bytes(&write_permission, position, bytes::Bytes::from(content))
+                    .await;
+                drop(write_permission);
+                file.notify_dropped_write_permission();
+                result
+            }),
+            JournalEntry::ResizeFile { path, size } => Box::pin(async move {
+                let file = self
+                    .apply_open_file(path, FileCreationMode::open_existing())
+                    .await?;
+                let write_permission = file.get_write_permission();
+                let result = file.resize(&write_permission, size).await;
+                drop(write_permission);
+                file.notify_dropped_write_permission();
+                result
+            }),
+            JournalEntry::CreateDirectory { path } => self.apply_create_directory(path),
+            JournalEntry::Copy {
+                from,
+                to,
+                replace_existing,
+            } => self.copy_entry(from, to, replace_existing),
+            JournalEntry::Rename { from, to } => self.apply_rename(from, to),
+            JournalEntry::RemovePermanently { path } => self.apply_remove_permanently(path),
+            JournalEntry::RestoreVersion { path, index } => self.apply_restore_version(path, index),
+            JournalEntry::PatchDeadProperties { path, changes } => {
+                self.apply_patch_dead_properties(path, changes)
+            }
+        }
+    }
+
+    /// Writes to `file` that was opened at `path` with [TreeEditor::open_file] and records the write in the [Journal].
+    pub async fn write_file(
+        &self,
+        path: &NormalizedPath,
+        file: &OpenFile,
+        write_permission: &OpenFileWritePermission,
+        position: u64,
+        content: bytes::Bytes,
+    ) -> Result<()> {
+        self.check_locks(path, false)?;
+        match &self.journal {
+            Some(journal) => {
+                let entry = JournalEntry::WriteFile {
+                    path: path.clone(),
+                    position,
+                    content: content.to_vec(),
+                };
+                journal
+                    .record(entry, file.write_bytes(write_permission, position, content))
+                    .await
+            }
+            None => file.write_bytes(write_permission, position, content).await,
+        }
+    }
+
+    /// Resizes `file` like [TreeEditor::write_file] writes to it.
+    pub async fn resize_file(
+        &self,
+        path: &NormalizedPath,
+        file: &OpenFile,
+        write_permission: &OpenFileWritePermission,
+        size: u64,
+    ) -> Result<()> {
+        self.check_locks(path, false)?;
+        match &self.journal {
+            Some(journal) => {
+                let entry = JournalEntry::ResizeFile {
+                    path: path.clone(),
+                    size,
+                };
+                journal
+                    .record(entry, file.resize(write_permission, size))
+                    .await
+            }
+            None => file.resize(write_permission, size).await,
+        }
+    }
+
+    /// Finds files in the last saved version of the tree. The index is updated with the changes since the previous
+    /// search, so the first search takes longer.
+    pub async fn search(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        let root_digest = self.root.latest_status().digest.last_known_digest;
+        let mut index_locked = self.search_index.lock().await;
+        index_locked
+            .update(self.root.storage.as_ref(), &root_digest)
+            .await?;
+        let trash_path = trash::trash_directory_path();
+        Ok(index_locked
+            .search(query)
+            .into_iter()
+            .filter(|result| !result.path.starts_with(&trash_path))
+            .collect())
     }
 
     pub async fn read_directory(
@@ -2841,6 +4021,16 @@ impl TreeEditor {
         Ok(directory.read().await)
     }
 
+    /// A feed of the changes anywhere below `directory`. Changes become visible when the root has been saved.
+    pub async fn watch_changes(&self, directory: NormalizedPath) -> Stream<TreeChange> {
+        change_feed::watch_changes(
+            self.root.storage.clone(),
+            self.root.watch().await,
+            directory,
+        )
+        .await
+    }
+
     pub fn get_meta_data<'a>(&self, path: NormalizedPath) -> Future<'a, DirectoryEntryMetaData> {
         match path.split_right() {
             PathSplitRightResult::Root => Box::pin(std::future::ready(Ok(
@@ -2862,6 +4052,76 @@ impl TreeEditor {
         &'a self,
         path: NormalizedPath,
         creation_mode: FileCreationMode,
+    ) -> Future<'a, Arc<OpenFile>> {
+        if creation_mode != FileCreationMode::open_existing() {
+            if let Err(error) = self.check_name_is_not_reserved(&path) {
+                return Box::pin(std::future::ready(Err(error)));
+            }
+        }
+        // only opening a file that might not exist yet can change the tree
+        let may_create = creation_mode != FileCreationMode::open_existing();
+        Box::pin(async move {
+            if may_create {
+                self.check_locks(&path, false)?;
+            }
+            match &self.journal {
+                Some(journal) if may_create => {
+                    journal
+                        .record(
+                            JournalEntry::OpenFile {
+                                path: path.clone(),
+                                creation_mode,
+                            },
+                            self.apply_open_file(path, creation_mode),
+                        )
+                        .await
+                }
+                _ => self.apply_open_file(path, creation_mode).await,
+            }
+        })
+    }
+
+    /// Like [TreeEditor::open_file], but the file counts as open for writing before the opening is recorded in the
+    /// [Journal]. Otherwise a checkpoint could save the new file as a version before anything was written to it. The
+    /// write permission is only granted to the holders of the locks on `path`.
+    pub fn open_file_for_writing<'a>(
+        &'a self,
+        path: NormalizedPath,
+        creation_mode: FileCreationMode,
+    ) -> Future<'a, (Arc<OpenFile>, Arc<OpenFileWritePermission>)> {
+        if creation_mode != FileCreationMode::open_existing() {
+            if let Err(error) = self.check_name_is_not_reserved(&path) {
+                return Box::pin(std::future::ready(Err(error)));
+            }
+        }
+        let opening = {
+            let path = path.clone();
+            async move {
+                self.check_locks(&path, false)?;
+                let file = self.apply_open_file(path, creation_mode).await?;
+                let write_permission = file.get_write_permission();
+                file.notify_changed_write_permission();
+                Ok((file, write_permission))
+            }
+        };
+        match &self.journal {
+            Some(journal) if creation_mode != FileCreationMode::open_existing() => {
+                Box::pin(journal.record(
+                    JournalEntry::OpenFile {
+                        path,
+                        creation_mode,
+                    },
+                    opening,
+                ))
+            }
+            _ => Box::pin(opening),
+        }
+    }
+
+    fn apply_open_file<'a>(
+        &'a self,
+        path: NormalizedPath,
+        creation_mode: FileCreationMode,
     ) -> Future<'a, Arc<OpenFile>> {
         match path.split_right() {
             PathSplitRightResult::Root => todo!(),
@@ -2932,6 +4192,23 @@ impl TreeEditor {
     }
 
     pub fn create_directory<'a>(&'a self, path: NormalizedPath) -> Future<'a, ()> {
+        if let Err(error) = self.check_name_is_not_reserved(&path) {
+            return Box::pin(std::future::ready(Err(error)));
+        }
+        self.change(JournalEntry::CreateDirectory { path })
+    }
+
+    /// The trash directory is created by [TreeEditor::remove], so that it only contains deleted entries.
+    fn check_name_is_not_reserved(&self, path: &NormalizedPath) -> Result<()> {
+        if self.trash_retention.is_some() && *path == trash::trash_directory_path() {
+            return Err(Error::ReservedName(
+                FileName::try_from(TRASH_DIRECTORY_NAME).unwrap(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn apply_create_directory<'a>(&'a self, path: NormalizedPath) -> Future<'a, ()> {
         match path.split_right() {
             PathSplitRightResult::Root => todo!(),
             PathSplitRightResult::Entry(directory_path, file_name) => {
@@ -2953,7 +4230,41 @@ impl TreeEditor {
         }
     }
 
+    /// Replaces an existing entry at `to`. See [TreeEditor::reflink] for how expensive this is.
     pub fn copy<'a>(&'a self, from: NormalizedPath, to: NormalizedPath) -> Future<'a, ()> {
+        if let Err(error) = self.check_name_is_not_reserved(&to) {
+            return Box::pin(std::future::ready(Err(error)));
+        }
+        self.change(JournalEntry::Copy {
+            from,
+            to,
+            replace_existing: true,
+        })
+    }
+
+    /// Copies a file or a whole directory in constant time: the copy refers to the same content digest as the original,
+    /// so no content is read or stored, no matter how large the original is. Only the directories containing `to`
+    /// have to be saved again, and an open directory at `from` is saved first if it has unsaved changes.
+    ///
+    /// The two entries share their content like a reflink, not like a hard link: changing one of them later does not
+    /// change the other. Fails with [Error::FileAlreadyExists] instead of replacing an existing entry at `to`.
+    pub fn reflink<'a>(&'a self, from: NormalizedPath, to: NormalizedPath) -> Future<'a, ()> {
+        if let Err(error) = self.check_name_is_not_reserved(&to) {
+            return Box::pin(std::future::ready(Err(error)));
+        }
+        self.change(JournalEntry::Copy {
+            from,
+            to,
+            replace_existing: false,
+        })
+    }
+
+    fn copy_entry<'a>(
+        &'a self,
+        from: NormalizedPath,
+        to: NormalizedPath,
+        replace_existing: bool,
+    ) -> Future<'a, ()> {
         let opening_directory_from = match from.split_right() {
             PathSplitRightResult::Root => {
                 return Box::pin(std::future::ready(Err(Error::CannotRename)))
@@ -2976,16 +4287,24 @@ impl TreeEditor {
             let directory_from = maybe_directory_from?;
             let directory_to = maybe_directory_to?;
             directory_from
-                .copy(
+                .copy_entry(
                     &opening_directory_from.1,
                     &directory_to,
                     &opening_directory_to.1,
+                    replace_existing,
                 )
                 .await
         })
     }
 
     pub fn rename<'a>(&'a self, from: NormalizedPath, to: NormalizedPath) -> Future<'a, ()> {
+        if let Err(error) = self.check_name_is_not_reserved(&to) {
+            return Box::pin(std::future::ready(Err(error)));
+        }
+        self.change(JournalEntry::Rename { from, to })
+    }
+
+    fn apply_rename<'a>(&'a self, from: NormalizedPath, to: NormalizedPath) -> Future<'a, ()> {
         let opening_directory_from = match from.split_right() {
             PathSplitRightResult::Root => {
                 return Box::pin(std::future::ready(Err(Error::CannotRename)))
@@ -3017,7 +4336,20 @@ impl TreeEditor {
         })
     }
 
+    /// Moves the entry into the trash if there is one. Entries that are in the trash already are deleted permanently.
     pub fn remove<'a>(&'a self, path: NormalizedPath) -> Future<'a, ()> {
+        if self.trash_retention.is_some() && !path.starts_with(&trash::trash_directory_path()) {
+            Box::pin(self.move_to_trash(path))
+        } else {
+            self.remove_permanently(path)
+        }
+    }
+
+    pub fn remove_permanently<'a>(&'a self, path: NormalizedPath) -> Future<'a, ()> {
+        self.change(JournalEntry::RemovePermanently { path })
+    }
+
+    fn apply_remove_permanently<'a>(&'a self, path: NormalizedPath) -> Future<'a, ()> {
         let opening_directory = match path.split_right() {
             PathSplitRightResult::Root => {
                 return Box::pin(std::future::ready(Err(Error::CannotRename)))
@@ -3031,4 +4363,204 @@ impl TreeEditor {
             directory.remove(&opening_directory.1).await
         })
     }
+
+    async fn move_to_trash(&self, path: NormalizedPath) -> Result<()> {
+        let original_name = match path.file_name() {
+            Some(name) => name.clone(),
+            None => return Err(Error::CannotRename),
+        };
+        let _trash_locked = self.trash_lock.lock().await;
+        // fail before creating the trash directory
+        self.get_meta_data(path.clone()).await?;
+        let trash_path = trash::trash_directory_path();
+        self.change(JournalEntry::CreateDirectory {
+            path: trash_path.clone(),
+        })
+        .await?;
+        let mut attempt = 0;
+        let name = loop {
+            let name = trash::trash_name(&original_name, attempt)?;
+            match self
+                .get_meta_data(trash_path.clone().join(name.clone()))
+                .await
+            {
+                Ok(_) => attempt += 1,
+                Err(Error::NotFound(_)) => break name,
+                Err(error) => return Err(error),
+            }
+        };
+        let entry = TrashEntry {
+            name,
+            original_path: path.clone(),
+            deleted_at: (self.root.get_clock())(),
+        };
+        info!("Moving {} into the trash as {}", original_name, &entry.name);
+        self.rename(path, entry.path()).await?;
+        self.patch_dead_properties(entry.path(), entry.set_properties())
+            .await
+    }
+
+    /// Lists the entries that were moved into the trash by [TreeEditor::remove], oldest first.
+    pub async fn list_trash(&self) -> Result<Vec<TrashEntry>> {
+        let trash_path = trash::trash_directory_path();
+        let mut listing = match self.read_directory(trash_path.clone()).await {
+            Ok(listing) => listing,
+            Err(Error::NotFound(_)) => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut entries = Vec::new();
+        while let Some(directory_entry) = listing.next().await {
+            let properties = self
+                .get_dead_properties(trash_path.clone().join(directory_entry.name.clone()))
+                .await?;
+            if let Some(entry) = TrashEntry::from_properties(directory_entry.name, &properties) {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|left, right| {
+            (left.deleted_at, &left.name).cmp(&(right.deleted_at, &right.name))
+        });
+        Ok(entries)
+    }
+
+    /// Moves an entry of the trash back to where it was deleted from. Missing parent directories are created again,
+    /// but an entry that took the place of the deleted one in the meantime is not overwritten.
+    pub async fn restore_from_trash(&self, name: &FileName) -> Result<NormalizedPath> {
+        let _trash_locked = self.trash_lock.lock().await;
+        let path = trash::trash_directory_path().join(name.clone());
+        let properties = self.get_dead_properties(path.clone()).await?;
+        let entry = match TrashEntry::from_properties(name.clone(), &properties) {
+            Some(entry) => entry,
+            None => return Err(Error::NotFound(name.clone())),
+        };
+        match self.get_meta_data(entry.original_path.clone()).await {
+            Ok(_) => {
+                return Err(Error::FileAlreadyExists(
+                    entry.original_path.file_name().unwrap().clone(),
+                ))
+            }
+            Err(Error::NotFound(_)) => {}
+            Err(error) => return Err(error),
+        }
+        let mut parent = NormalizedPath::root();
+        let ancestor_count = entry.original_path.components().count() - 1;
+        for component in entry.original_path.components().take(ancestor_count) {
+            parent = parent.join(component.clone());
+            self.create_directory(parent.clone()).await?;
+        }
+        info!(
+            "Restoring {} from the trash to {:?}",
+            name, &entry.original_path
+        );
+        self.rename(path, entry.original_path.clone()).await?;
+        self.patch_dead_properties(entry.original_path.clone(), TrashEntry::remove_properties())
+            .await?;
+        Ok(entry.original_path)
+    }
+
+    /// Permanently deletes the entries of the trash that are older than the [TrashRetention] allows. Returns how many
+    /// entries were deleted. Locked entries are kept until they are unlocked.
+    pub async fn expire_trash(&self) -> Result<usize> {
+        let retention = match self.trash_retention {
+            Some(retention) => retention,
+            None => return Ok(0),
+        };
+        let now = (self.root.get_clock())();
+        let mut expired_count = 0;
+        for entry in self.list_trash().await? {
+            if retention.is_expired(&entry, now) {
+                info!("Deleting {} from the trash permanently", &entry.name);
+                match self.remove_permanently(entry.path()).await {
+                    Ok(()) => expired_count += 1,
+                    // tried again the next time
+                    Err(Error::Locked(_)) => {}
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+        Ok(expired_count)
+    }
+
+    pub fn get_dead_properties<'a>(&'a self, path: NormalizedPath) -> Future<'a, DeadProperties> {
+        match path.split_right() {
+            PathSplitRightResult::Root => {
+                // The root is not an entry in any directory, so there is no place to store properties for it.
+                Box::pin(std::future::ready(Ok(DeadProperties::new())))
+            }
+            PathSplitRightResult::Entry(directory_path, leaf_name) => Box::pin(async move {
+                let directory = self.root.open_directory(directory_path).await?;
+                directory.get_dead_properties(&leaf_name).await
+            }),
+        }
+    }
+
+    pub fn get_file_history<'a>(&'a self, path: NormalizedPath) -> Future<'a, FileHistory> {
+        match path.split_right() {
+            PathSplitRightResult::Root => {
+                Box::pin(std::future::ready(Err(Error::RootIsNotARegularFile)))
+            }
+            PathSplitRightResult::Entry(directory_path, leaf_name) => Box::pin(async move {
+                let directory = self.root.open_directory(directory_path).await?;
+                directory.get_file_history(&leaf_name).await
+            }),
+        }
+    }
+
+    pub fn open_version<'a>(
+        &'a self,
+        path: NormalizedPath,
+        index: usize,
+    ) -> Future<'a, Arc<OpenFile>> {
+        match path.split_right() {
+            PathSplitRightResult::Root => {
+                Box::pin(std::future::ready(Err(Error::RootIsNotARegularFile)))
+            }
+            PathSplitRightResult::Entry(directory_path, leaf_name) => Box::pin(async move {
+                let directory = self.root.open_directory(directory_path).await?;
+                directory.open_version(&leaf_name, index).await
+            }),
+        }
+    }
+
+    pub fn restore_version<'a>(&'a self, path: NormalizedPath, index: usize) -> Future<'a, ()> {
+        self.change(JournalEntry::RestoreVersion { path, index })
+    }
+
+    fn apply_restore_version<'a>(&'a self, path: NormalizedPath, index: usize) -> Future<'a, ()> {
+        match path.split_right() {
+            PathSplitRightResult::Root => {
+                Box::pin(std::future::ready(Err(Error::RootIsNotARegularFile)))
+            }
+            PathSplitRightResult::Entry(directory_path, leaf_name) => Box::pin(async move {
+                let directory = self.root.open_directory(directory_path).await?;
+                directory.restore_version(&leaf_name, index).await
+            }),
+        }
+    }
+
+    pub fn patch_dead_properties<'a>(
+        &'a self,
+        path: NormalizedPath,
+        changes: Vec<DeadPropertyChange>,
+    ) -> Future<'a, ()> {
+        self.change(JournalEntry::PatchDeadProperties { path, changes })
+    }
+
+    fn apply_patch_dead_properties<'a>(
+        &'a self,
+        path: NormalizedPath,
+        changes: Vec<DeadPropertyChange>,
+    ) -> Future<'a, ()> {
+        match path.split_right() {
+            PathSplitRightResult::Root => {
+                Box::pin(std::future::ready(Err(Error::InvalidArgument(
+                    "The root directory cannot have dead properties".to_string(),
+                ))))
+            }
+            PathSplitRightResult::Entry(directory_path, leaf_name) => Box::pin(async move {
+                let directory = self.root.open_directory(directory_path).await?;
+                directory.patch_dead_properties(&leaf_name, changes).await
+            }),
+        }
+    }
 }
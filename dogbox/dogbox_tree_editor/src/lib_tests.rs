@@ -1,9 +1,10 @@
 use crate::{
-    format_wall_clock, AccessOrderLowerIsMoreRecent, CacheDropStats, DigestStatus,
-    DirectoryEntryKind, DirectoryEntryMetaData, Error, FileCreationMode, LoadedBlock,
+    format_wall_clock, AccessOrderLowerIsMoreRecent, CacheDropStats, DeadPropertyChange,
+    DigestStatus, DirectoryEntryKind, DirectoryEntryMetaData, Error, FileCreationMode, LoadedBlock,
     MutableDirectoryEntry, NamedEntry, NormalizedPath, OpenDirectory, OpenDirectoryStatus,
     OpenFileContentBlock, OpenFileContentBuffer, OpenFileContentBufferLoaded, OpenFileStats,
-    OptimizedWriteBuffer, Prefetcher, StoreChanges, StreakDirection, TreeEditor, WallClock,
+    OptimizedWriteBuffer, PrefetchStats, Prefetcher, StoreChanges, StreakDirection, TreeEditor,
+    WallClock,
 };
 use astraea::storage::{
     CollectGarbage, DelayedHashedTree, GarbageCollectionStats, InMemoryTreeStorage, LoadError,
@@ -15,7 +16,9 @@ use astraea::{
     tree::{BlobDigest, HashedTree, Tree, TreeBlob, TREE_BLOB_MAX_LENGTH},
 };
 use async_trait::async_trait;
-use dogbox_tree::serialization::FileName;
+use dogbox_tree::serialization::{
+    DeadProperties, DeadPropertyName, DeadPropertyValue, FileHistory, FileName,
+};
 use futures::StreamExt;
 use lazy_static::lazy_static;
 use pretty_assertions::assert_eq;
@@ -50,6 +53,33 @@ fn test_normalized_path_from() {
     );
 }
 
+#[test_log::test(test)]
+fn test_normalized_path_starts_with() {
+    let root = NormalizedPath::root();
+    let a = NormalizedPath::try_from(relative_path::RelativePath::new("a")).unwrap();
+    let a_b = NormalizedPath::try_from(relative_path::RelativePath::new("a/b")).unwrap();
+    let ab = NormalizedPath::try_from(relative_path::RelativePath::new("ab")).unwrap();
+    assert!(root.starts_with(&root));
+    assert!(a.starts_with(&root));
+    assert!(a.starts_with(&a));
+    assert!(a_b.starts_with(&a));
+    assert!(!a.starts_with(&a_b));
+    assert!(!ab.starts_with(&a));
+    assert!(!root.starts_with(&a));
+}
+
+#[test_log::test(test)]
+fn test_normalized_path_join() {
+    let a = NormalizedPath::try_from(relative_path::RelativePath::new("a")).unwrap();
+    let a_b = NormalizedPath::try_from(relative_path::RelativePath::new("a/b")).unwrap();
+    let b = FileName::try_from("b".to_string()).unwrap();
+    assert_eq!(a_b, a.join(b.clone()));
+    assert_eq!(
+        NormalizedPath::try_from(relative_path::RelativePath::new("b")).unwrap(),
+        NormalizedPath::root().join(b)
+    );
+}
+
 #[test_log::test(test)]
 fn test_streak_direction() {
     assert_eq!(
@@ -354,7 +384,7 @@ async fn test_open_directory_nothing_happens() {
             ),
             1,
             0,
-            OpenFileStats::new(0, 0, 0, 0, 0),
+            OpenFileStats::new(0, 0, 0, 0, 0, PrefetchStats::default()),
         ),
         status
     );
@@ -681,7 +711,7 @@ async fn test_read_file_after_garbage_collection() {
             ),
             1,
             0,
-            OpenFileStats::new(0, 0, 0, 0, 0),
+            OpenFileStats::new(0, 0, 0, 0, 0, PrefetchStats::default()),
         ),
         &directory_status
     );
@@ -1226,6 +1256,240 @@ async fn test_nested_create_directory() {
     }
 }
 
+fn example_property_name() -> DeadPropertyName {
+    DeadPropertyName::new(
+        Some("http://example.com/neon/litmus/".to_string()),
+        "prop0".to_string(),
+    )
+}
+
+fn example_property_value() -> DeadPropertyValue {
+    DeadPropertyValue::new(Some("ns1".to_string()), Some(b"value0".to_vec()))
+}
+
+fn example_properties() -> DeadProperties {
+    let mut properties = DeadProperties::new();
+    properties.set(example_property_name(), example_property_value());
+    properties
+}
+
+#[test_log::test(tokio::test)]
+async fn test_dead_properties_set_and_remove() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let editor = TreeEditor::new(Arc::new(open_directory_from_entries(vec![], storage)), None);
+    let path = NormalizedPath::try_from(relative_path::RelativePath::new("/test.txt")).unwrap();
+    editor
+        .open_file(path.clone(), FileCreationMode::create_new())
+        .await
+        .unwrap();
+    assert_eq!(
+        DeadProperties::new(),
+        editor.get_dead_properties(path.clone()).await.unwrap()
+    );
+    editor
+        .patch_dead_properties(
+            path.clone(),
+            vec![DeadPropertyChange::Set(
+                example_property_name(),
+                example_property_value(),
+            )],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        example_properties(),
+        editor.get_dead_properties(path.clone()).await.unwrap()
+    );
+    editor
+        .patch_dead_properties(
+            path.clone(),
+            vec![DeadPropertyChange::Remove(example_property_name())],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        DeadProperties::new(),
+        editor.get_dead_properties(path.clone()).await.unwrap()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_dead_properties_not_found() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let editor = TreeEditor::new(Arc::new(open_directory_from_entries(vec![], storage)), None);
+    let path = NormalizedPath::try_from(relative_path::RelativePath::new("/test.txt")).unwrap();
+    let name = FileName::try_from("test.txt").unwrap();
+    assert_eq!(
+        Err(Error::NotFound(name.clone())),
+        editor.get_dead_properties(path.clone()).await
+    );
+    assert_eq!(
+        Err(Error::NotFound(name)),
+        editor
+            .patch_dead_properties(
+                path,
+                vec![DeadPropertyChange::Set(
+                    example_property_name(),
+                    example_property_value(),
+                )],
+            )
+            .await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_dead_properties_too_large() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let editor = TreeEditor::new(Arc::new(open_directory_from_entries(vec![], storage)), None);
+    let path = NormalizedPath::try_from(relative_path::RelativePath::new("/test")).unwrap();
+    editor.create_directory(path.clone()).await.unwrap();
+    let result = editor
+        .patch_dead_properties(
+            path.clone(),
+            vec![
+                DeadPropertyChange::Set(example_property_name(), example_property_value()),
+                DeadPropertyChange::Set(
+                    DeadPropertyName::new(None, "large".to_string()),
+                    DeadPropertyValue::new(
+                        None,
+                        Some(vec![0u8; DeadProperties::MAX_SIZE_IN_BYTES]),
+                    ),
+                ),
+            ],
+        )
+        .await;
+    match result {
+        Err(Error::DeadPropertiesTooLarge { size_in_bytes }) => {
+            assert!(size_in_bytes > DeadProperties::MAX_SIZE_IN_BYTES)
+        }
+        _ => panic!("Unexpected result: {:?}", result),
+    }
+    // none of the changes must have been applied
+    assert_eq!(
+        DeadProperties::new(),
+        editor.get_dead_properties(path).await.unwrap()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_dead_properties_survive_reloading() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let root = Arc::new(
+        OpenDirectory::create_directory(
+            std::path::PathBuf::from("/"),
+            storage.clone(),
+            Arc::new(test_clock),
+            1,
+        )
+        .await
+        .unwrap(),
+    );
+    let editor = TreeEditor::new(root.clone(), None);
+    let directory_path =
+        NormalizedPath::try_from(relative_path::RelativePath::new("/dir")).unwrap();
+    let file_path =
+        NormalizedPath::try_from(relative_path::RelativePath::new("/dir/test.txt")).unwrap();
+    editor
+        .create_directory(directory_path.clone())
+        .await
+        .unwrap();
+    editor
+        .open_file(file_path.clone(), FileCreationMode::create_new())
+        .await
+        .unwrap();
+    for path in [&directory_path, &file_path] {
+        editor
+            .patch_dead_properties(
+                path.clone(),
+                vec![DeadPropertyChange::Set(
+                    example_property_name(),
+                    example_property_value(),
+                )],
+            )
+            .await
+            .unwrap();
+    }
+    let status = root.request_save().await.unwrap();
+    assert!(status.digest.is_digest_up_to_date);
+
+    let reloaded_root = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage,
+        &status.digest.last_known_digest,
+        test_clock(),
+        Arc::new(test_clock),
+        1,
+    )
+    .await
+    .unwrap();
+    let reloaded_editor = TreeEditor::new(reloaded_root, None);
+    for path in [directory_path, file_path] {
+        assert_eq!(
+            example_properties(),
+            reloaded_editor.get_dead_properties(path).await.unwrap()
+        );
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_dead_properties_follow_copy_rename_and_remove() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let editor = TreeEditor::new(Arc::new(open_directory_from_entries(vec![], storage)), None);
+    let original = NormalizedPath::try_from(relative_path::RelativePath::new("/a.txt")).unwrap();
+    let copied = NormalizedPath::try_from(relative_path::RelativePath::new("/b.txt")).unwrap();
+    let renamed = NormalizedPath::try_from(relative_path::RelativePath::new("/dir/c.txt")).unwrap();
+    editor
+        .open_file(original.clone(), FileCreationMode::create_new())
+        .await
+        .unwrap();
+    editor
+        .create_directory(
+            NormalizedPath::try_from(relative_path::RelativePath::new("/dir")).unwrap(),
+        )
+        .await
+        .unwrap();
+    editor
+        .patch_dead_properties(
+            original.clone(),
+            vec![DeadPropertyChange::Set(
+                example_property_name(),
+                example_property_value(),
+            )],
+        )
+        .await
+        .unwrap();
+
+    editor.copy(original.clone(), copied.clone()).await.unwrap();
+    assert_eq!(
+        example_properties(),
+        editor.get_dead_properties(original.clone()).await.unwrap()
+    );
+    assert_eq!(
+        example_properties(),
+        editor.get_dead_properties(copied.clone()).await.unwrap()
+    );
+
+    editor
+        .rename(copied.clone(), renamed.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        example_properties(),
+        editor.get_dead_properties(renamed.clone()).await.unwrap()
+    );
+
+    // a new file with the name of a removed one must not inherit its properties
+    editor.remove(original.clone()).await.unwrap();
+    editor
+        .open_file(original.clone(), FileCreationMode::create_new())
+        .await
+        .unwrap();
+    assert_eq!(
+        DeadProperties::new(),
+        editor.get_dead_properties(original).await.unwrap()
+    );
+}
+
 #[test_log::test(tokio::test)]
 async fn test_open_file_content_buffer_loaded_resize_small() {
     let hashed_tree = HashedTree::from(Arc::new(Tree::new(
@@ -1302,18 +1566,19 @@ async fn test_open_file_content_buffer_loaded_resize_large() {
     assert_eq!(buffer.size, new_size);
     assert_eq!(storage.number_of_trees().await, 1);
     buffer.store_cheap_blocks(storage.clone()).await.unwrap();
-    assert_eq!(storage.number_of_trees().await, 2);
+    // The zeros don't have to be stored because they become holes.
+    assert_eq!(storage.number_of_trees().await, 1);
     assert_eq!(
         StoreChanges::SomeChanges,
         buffer.store_all(storage.clone()).await.unwrap()
     );
-    assert_eq!(storage.number_of_trees().await, 4);
+    assert_eq!(storage.number_of_trees().await, 3);
     let digest = buffer.last_known_digest();
     assert_eq!(
         DigestStatus {
             last_known_digest: BlobDigest::parse_hex_string(concat!(
-                "df68238269cfefdbbc288dc38fb26e54716bd06f2639a4b09505fe840ea33bd8",
-                "4ad74192dbc61eb97e612523524128b980450180ae7e36c690e56686c5fb0e7d"
+                "4eead2b9c073e2a0f5a73e10654bcd1354ce29730bc6e87d21cadad521d1aba3",
+                "a769021585b1e7ff6234f0b71bf4c85401e9bd17be10bc8dd4eee4d6c83f7a2d"
             ))
             .unwrap(),
             is_digest_up_to_date: true,
@@ -1480,8 +1745,8 @@ async fn open_file_content_buffer_write_fill_zero_block() {
         (
             DigestStatus::new(
                 BlobDigest::parse_hex_string(concat!(
-                    "f770468c4e5b38323c05f83229aadcb680a0c3fed112fffdbb7650bc92f26a7e",
-                    "e15e77fca5371b75463401b3bc2893c5aa667ff54d2aa4332ea445352697df99"
+                    "487e381083beebee987ff9090b14ed181207dff46119682e5bd788d64d194ed5",
+                    "e51c5d6b830ad555151d40bd0b6bae86438360912cc2a298fe05f36776dffcb1"
                 ))
                 .unwrap(),
                 true
@@ -1524,8 +1789,8 @@ async fn open_file_content_buffer_store_large_file() {
         (
             DigestStatus::new(
                 BlobDigest::parse_hex_string(concat!(
-                    "d596e30061e719e97823ba69e5037747aaf2353d110a0dfc496407c658b828a4",
-                    "6d3f0cca0b80d8b46a20dbb7c956f9da11c2a9038c20e6dcae7bbd74b9bcc5c9"
+                    "fe7c43312975f4e72c9d931cc0259dff027d14a3419c298bcc8f92594eec175a",
+                    "c2bde7ae76f867f6d55654acc5d748e38f3d7c6bb1118f4f4b2f13654058360b"
                 ))
                 .unwrap(),
                 true
@@ -1672,13 +1937,7 @@ async fn open_file_content_buffer_store() {
     let expected_buffer = OpenFileContentBuffer::Loaded(crate::OpenFileContentBufferLoaded {
         size: TREE_BLOB_MAX_LENGTH as u64 + write_data.len() as u64,
         blocks: vec![
-            OpenFileContentBlock::NotLoaded(
-                calculate_reference(&Tree::new(
-                    TreeBlob::try_from(bytes::Bytes::from(vec![0; TREE_BLOB_MAX_LENGTH])).unwrap(),
-                    TreeChildren::empty(),
-                )),
-                TREE_BLOB_MAX_LENGTH as u16,
-            ),
+            OpenFileContentBlock::Hole,
             OpenFileContentBlock::NotLoaded(
                 calculate_reference(&Tree::new(
                     TreeBlob::try_from(bytes::Bytes::copy_from_slice(write_data.as_bytes()))
@@ -1690,8 +1949,8 @@ async fn open_file_content_buffer_store() {
         ],
         digest: crate::DigestStatus {
             last_known_digest: BlobDigest::parse_hex_string(concat!(
-                "f770468c4e5b38323c05f83229aadcb680a0c3fed112fffdbb7650bc92f26a7e",
-                "e15e77fca5371b75463401b3bc2893c5aa667ff54d2aa4332ea445352697df99"
+                "487e381083beebee987ff9090b14ed181207dff46119682e5bd788d64d194ed5",
+                "e51c5d6b830ad555151d40bd0b6bae86438360912cc2a298fe05f36776dffcb1"
             ))
             .unwrap(),
             is_digest_up_to_date: true,
@@ -1709,17 +1968,13 @@ async fn open_file_content_buffer_store() {
                 "713ddcb3450de2b0b98f2e8b69dbb1a5736b10db787eae6274ea4673b467e692",
                 "6415c7a14651bdfdaa973eaabbcf1814993bdc991e2891a72df2c7de4f8322c5"
             ),
-            concat!(
-                "708d4258f26a6d99a6cc10532bd66134f46fd537d51db057c5a083cf8994f07e",
-                "740534b6f795c49aa35513a65e3da7a5518fe163da200e24af0701088b290daa"
-            ),
             concat!(
                 "f0140e314ee38d4472393680e7a72a81abb36b134b467d90ea943b7aa1ea03bf",
                 "2323bc1a2df91f7230a225952e162f6629cf435e53404e9cdd727a2d94e4f909"
             ),
             concat!(
-                "f770468c4e5b38323c05f83229aadcb680a0c3fed112fffdbb7650bc92f26a7e",
-                "e15e77fca5371b75463401b3bc2893c5aa667ff54d2aa4332ea445352697df99"
+                "487e381083beebee987ff9090b14ed181207dff46119682e5bd788d64d194ed5",
+                "e51c5d6b830ad555151d40bd0b6bae86438360912cc2a298fe05f36776dffcb1"
             ),
         ]
         .map(BlobDigest::parse_hex_string)
@@ -1942,6 +2197,77 @@ fn open_file_content_buffer_resize_shrink(old_size: u64, new_size: u64) {
     });
 }
 
+#[test_log::test(tokio::test)]
+async fn open_file_content_buffer_sparse() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let initial_content = Vec::new();
+    let last_known_digest = BlobDigest::hash(&initial_content);
+    let mut buffer =
+        OpenFileContentBuffer::from_data(initial_content, last_known_digest, 0, 1).unwrap();
+    let block_count = 100;
+    let size = (TREE_BLOB_MAX_LENGTH as u64) * block_count;
+    buffer.resize(size, storage.clone()).await.unwrap();
+    assert_eq!(
+        StoreChanges::SomeChanges,
+        buffer.store_all(storage.clone()).await.unwrap()
+    );
+    // Only the segmented blob itself is stored, not the zeros.
+    let trees_after_resize = storage.number_of_trees().await;
+    assert_eq!(4, trees_after_resize);
+    let (digest_status, digest_size) = buffer.last_known_digest();
+    assert_eq!(size, digest_size);
+
+    let mut loaded = OpenFileContentBuffer::from_storage(digest_status.last_known_digest, size, 1);
+    check_open_file_content_buffer(
+        &mut loaded,
+        bytes::Bytes::from(vec![0u8; size as usize]),
+        storage.clone(),
+    )
+    .await;
+
+    // Writing into a hole only stores the block that was touched.
+    let write_position = (TREE_BLOB_MAX_LENGTH as u64) * 50 + 7;
+    let write_data = bytes::Bytes::from("x");
+    loaded
+        .write(
+            write_position,
+            OptimizedWriteBuffer::from_bytes(write_position, write_data.clone()).await,
+            storage.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        StoreChanges::SomeChanges,
+        loaded.store_all(storage.clone()).await.unwrap()
+    );
+    assert_eq!(size, loaded.size());
+    // the new block, the inner tree containing it and the new root
+    assert_eq!(trees_after_resize + 3, storage.number_of_trees().await);
+    let mut expected_content = vec![0u8; size as usize];
+    expected_content[write_position as usize] = b'x';
+    check_open_file_content_buffer(
+        &mut loaded,
+        bytes::Bytes::from(expected_content),
+        storage.clone(),
+    )
+    .await;
+
+    // truncating and growing again doesn't bring the old content back
+    loaded.resize(0, storage.clone()).await.unwrap();
+    loaded.resize(size, storage.clone()).await.unwrap();
+    check_open_file_content_buffer(
+        &mut loaded,
+        bytes::Bytes::from(vec![0u8; size as usize]),
+        storage.clone(),
+    )
+    .await;
+    loaded.store_all(storage.clone()).await.unwrap();
+    assert_eq!(
+        digest_status.last_known_digest,
+        loaded.last_known_digest().0.last_known_digest
+    );
+}
+
 #[test_case(2)]
 #[test_case(20_000)]
 #[test_case(TREE_BLOB_MAX_LENGTH as u64)]
@@ -1979,3 +2305,506 @@ fn open_file_content_buffer_resize_same(size: u64) {
         check_open_file_content_buffer(&mut buffer, bytes::Bytes::new(), storage.clone()).await;
     });
 }
+
+/// Looks up the digest of the file at `path` in the saved root `root_digest`.
+async fn find_saved_file_digest(
+    storage: &(dyn LoadStoreTree + Send + Sync),
+    root_digest: BlobDigest,
+    path: &NormalizedPath,
+) -> Option<BlobDigest> {
+    let mut digest = root_digest;
+    for name in path.components() {
+        let entries = dogbox_tree::serialization::deserialize_directory(storage, &digest)
+            .await
+            .unwrap();
+        let (_kind, child_digest, _properties, _history) = entries.get(name)?;
+        digest = *child_digest;
+    }
+    Some(digest)
+}
+
+/// Change notifications reach the root asynchronously, so we save until the saved root contains the new content.
+async fn write_and_save(
+    editor: &TreeEditor,
+    root: &OpenDirectory,
+    path: &NormalizedPath,
+    content: &'static [u8],
+) {
+    let opened = editor
+        .open_file(path.clone(), FileCreationMode::create())
+        .await
+        .unwrap();
+    let write_permission = opened.get_write_permission();
+    opened
+        .write_bytes(&write_permission, 0, content.into())
+        .await
+        .unwrap();
+    drop(write_permission);
+    opened.notify_dropped_write_permission();
+    let file_status = opened.flush().await.unwrap();
+    assert!(file_status.digest.is_digest_up_to_date);
+    let storage = root.get_storage();
+    loop {
+        let root_status = root.request_save().await.unwrap();
+        if find_saved_file_digest(storage.as_ref(), root_status.digest.last_known_digest, path)
+            .await
+            == Some(file_status.digest.last_known_digest)
+        {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+}
+
+async fn read_whole_file(opened: &crate::OpenFile) -> bytes::Bytes {
+    let read_permission = opened.get_read_permission();
+    opened.read_bytes(&read_permission, 0, 1000).await.unwrap()
+}
+
+fn adjustable_clock() -> (Arc<std::sync::Mutex<SystemTime>>, WallClock) {
+    let now = Arc::new(std::sync::Mutex::new(
+        SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000),
+    ));
+    let clock: WallClock = {
+        let now = now.clone();
+        Arc::new(move || *now.lock().unwrap())
+    };
+    (now, clock)
+}
+
+#[test_log::test(tokio::test)]
+async fn test_file_versions_open_and_restore() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let (now, clock) = adjustable_clock();
+    let root = Arc::new(
+        OpenDirectory::create_directory(std::path::PathBuf::from("/"), storage.clone(), clock, 1)
+            .await
+            .unwrap(),
+    );
+    let editor = TreeEditor::new(root.clone(), None);
+    let path = NormalizedPath::try_from(relative_path::RelativePath::new("/a.txt")).unwrap();
+    write_and_save(&editor, &root, &path, b"first").await;
+    assert!(editor
+        .get_file_history(path.clone())
+        .await
+        .unwrap()
+        .is_empty());
+
+    // saving without changes doesn't create versions
+    root.request_save().await.unwrap();
+    assert!(editor
+        .get_file_history(path.clone())
+        .await
+        .unwrap()
+        .is_empty());
+
+    let first_saved_at = *now.lock().unwrap();
+    *now.lock().unwrap() += std::time::Duration::from_secs(60);
+    write_and_save(&editor, &root, &path, b"other").await;
+    let history = editor.get_file_history(path.clone()).await.unwrap();
+    assert_eq!(1, history.versions().len());
+    assert_eq!(5, history.versions()[0].size);
+    assert_eq!(first_saved_at, history.versions()[0].saved_at);
+
+    let version = editor.open_version(path.clone(), 0).await.unwrap();
+    assert_eq!(&b"first"[..], &read_whole_file(&version).await[..]);
+    match editor.open_version(path.clone(), 1).await {
+        Err(Error::VersionNotFound { name, index: 1 }) => {
+            assert_eq!(FileName::try_from("a.txt".to_string()).unwrap(), name)
+        }
+        other => panic!("Unexpected result: {other:?}"),
+    }
+
+    editor.restore_version(path.clone(), 0).await.unwrap();
+    let restored = editor
+        .open_file(path.clone(), FileCreationMode::open_existing())
+        .await
+        .unwrap();
+    assert_eq!(&b"first"[..], &read_whole_file(&restored).await[..]);
+
+    // the replaced content becomes a version itself
+    let status = root.request_save().await.unwrap();
+    let history = editor.get_file_history(path.clone()).await.unwrap();
+    assert_eq!(2, history.versions().len());
+    let version = editor.open_version(path.clone(), 1).await.unwrap();
+    assert_eq!(&b"other"[..], &read_whole_file(&version).await[..]);
+
+    let reloaded_root = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage,
+        &status.digest.last_known_digest,
+        test_clock(),
+        Arc::new(test_clock),
+        1,
+    )
+    .await
+    .unwrap();
+    let reloaded_editor = TreeEditor::new(reloaded_root, None);
+    assert_eq!(
+        history,
+        reloaded_editor.get_file_history(path).await.unwrap()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_file_version_keeps_modification_time_after_reload() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let (_now, clock) = adjustable_clock();
+    let root = Arc::new(
+        OpenDirectory::create_directory(
+            std::path::PathBuf::from("/"),
+            storage.clone(),
+            clock.clone(),
+            1,
+        )
+        .await
+        .unwrap(),
+    );
+    let editor = TreeEditor::new(root.clone(), None);
+    let path = NormalizedPath::try_from(relative_path::RelativePath::new("/a.txt")).unwrap();
+    write_and_save(&editor, &root, &path, b"first").await;
+    let imported_modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(500_000);
+    let mut properties = DeadProperties::new();
+    properties.set_modified(imported_modified);
+    let (name, value) = properties.entries().iter().next().unwrap();
+    editor
+        .patch_dead_properties(
+            path.clone(),
+            vec![DeadPropertyChange::Set(name.clone(), value.clone())],
+        )
+        .await
+        .unwrap();
+    let status = root.request_save().await.unwrap();
+
+    // the directory itself gets a different modification time when it is loaded
+    let reloaded_root = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage,
+        &status.digest.last_known_digest,
+        SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(2_000_000),
+        clock,
+        1,
+    )
+    .await
+    .unwrap();
+    let reloaded_editor = TreeEditor::new(reloaded_root.clone(), None);
+    write_and_save(&reloaded_editor, &reloaded_root, &path, b"second").await;
+    let history = reloaded_editor.get_file_history(path).await.unwrap();
+    assert_eq!(1, history.versions().len());
+    assert_eq!(imported_modified, history.versions()[0].saved_at);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_root_has_no_versions() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let (_now, clock) = adjustable_clock();
+    let root = Arc::new(
+        OpenDirectory::create_directory(std::path::PathBuf::from("/"), storage, clock, 1)
+            .await
+            .unwrap(),
+    );
+    let editor = TreeEditor::new(root, None);
+    assert!(matches!(
+        editor.get_file_history(NormalizedPath::root()).await,
+        Err(Error::RootIsNotARegularFile)
+    ));
+    assert!(matches!(
+        editor.open_version(NormalizedPath::root(), 0).await,
+        Err(Error::RootIsNotARegularFile)
+    ));
+    assert!(matches!(
+        editor.restore_version(NormalizedPath::root(), 0).await,
+        Err(Error::RootIsNotARegularFile)
+    ));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_file_versions_retention_above_the_maximum() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let (_now, clock) = adjustable_clock();
+    let root = Arc::new(
+        OpenDirectory::create_directory(std::path::PathBuf::from("/"), storage, clock, 1)
+            .await
+            .unwrap(),
+    );
+    let too_many = crate::VersionRetention {
+        max_versions: FileHistory::MAX_VERSIONS + 1,
+        max_age: None,
+    };
+    assert_eq!(
+        Err(Error::InvalidArgument(format!(
+            "Cannot keep {} versions of a file, the maximum is {}",
+            FileHistory::MAX_VERSIONS + 1,
+            FileHistory::MAX_VERSIONS
+        ))),
+        root.set_version_retention(too_many).await
+    );
+    root.set_version_retention(crate::VersionRetention {
+        max_versions: FileHistory::MAX_VERSIONS,
+        max_age: None,
+    })
+    .await
+    .unwrap();
+}
+
+#[test_log::test(tokio::test)]
+async fn test_file_versions_retention() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let (now, clock) = adjustable_clock();
+    let root = Arc::new(
+        OpenDirectory::create_directory(std::path::PathBuf::from("/"), storage, clock, 1)
+            .await
+            .unwrap(),
+    );
+    root.set_version_retention(crate::VersionRetention {
+        max_versions: 2,
+        max_age: Some(std::time::Duration::from_secs(3600)),
+    })
+    .await
+    .unwrap();
+    let editor = TreeEditor::new(root.clone(), None);
+    editor
+        .create_directory(
+            NormalizedPath::try_from(relative_path::RelativePath::new("/dir")).unwrap(),
+        )
+        .await
+        .unwrap();
+    let path = NormalizedPath::try_from(relative_path::RelativePath::new("/dir/a.txt")).unwrap();
+    for content in [&b"aaaa"[..], b"bbbb", b"cccc", b"dddd"] {
+        *now.lock().unwrap() += std::time::Duration::from_secs(60);
+        write_and_save(&editor, &root, &path, content).await;
+    }
+    let history = editor.get_file_history(path.clone()).await.unwrap();
+    assert_eq!(2, history.versions().len());
+    let version = editor.open_version(path.clone(), 0).await.unwrap();
+    assert_eq!(&b"bbbb"[..], &read_whole_file(&version).await[..]);
+
+    // versions expire when the directory is saved again
+    *now.lock().unwrap() += std::time::Duration::from_secs(3600 + 60);
+    write_and_save(&editor, &root, &path, b"eeee").await;
+    assert!(editor
+        .get_file_history(path.clone())
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_file_versions_follow_rename_but_not_copy() {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let (now, clock) = adjustable_clock();
+    let root = Arc::new(
+        OpenDirectory::create_directory(std::path::PathBuf::from("/"), storage, clock, 1)
+            .await
+            .unwrap(),
+    );
+    let editor = TreeEditor::new(root.clone(), None);
+    let original = NormalizedPath::try_from(relative_path::RelativePath::new("/a.txt")).unwrap();
+    let copied = NormalizedPath::try_from(relative_path::RelativePath::new("/b.txt")).unwrap();
+    let renamed = NormalizedPath::try_from(relative_path::RelativePath::new("/c.txt")).unwrap();
+    write_and_save(&editor, &root, &original, b"first").await;
+    *now.lock().unwrap() += std::time::Duration::from_secs(60);
+    write_and_save(&editor, &root, &original, b"other").await;
+    assert_eq!(
+        1,
+        editor
+            .get_file_history(original.clone())
+            .await
+            .unwrap()
+            .versions()
+            .len()
+    );
+
+    editor.copy(original.clone(), copied.clone()).await.unwrap();
+    assert!(editor.get_file_history(copied).await.unwrap().is_empty());
+
+    editor
+        .rename(original.clone(), renamed.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        1,
+        editor
+            .get_file_history(renamed.clone())
+            .await
+            .unwrap()
+            .versions()
+            .len()
+    );
+
+    editor.remove(renamed.clone()).await.unwrap();
+    write_and_save(&editor, &root, &renamed, b"third").await;
+    assert!(editor.get_file_history(renamed).await.unwrap().is_empty());
+}
+
+async fn read_tree_file(editor: &TreeEditor, path: &str) -> bytes::Bytes {
+    let opened = editor
+        .open_file(
+            NormalizedPath::try_from(relative_path::RelativePath::new(path)).unwrap(),
+            FileCreationMode::open_existing(),
+        )
+        .await
+        .unwrap();
+    let read_permission = opened.get_read_permission();
+    let mut content = Vec::new();
+    loop {
+        let read = opened
+            .read_bytes(&read_permission, content.len() as u64, TREE_BLOB_MAX_LENGTH)
+            .await
+            .unwrap();
+        if read.is_empty() {
+            return content.into();
+        }
+        content.extend_from_slice(&read);
+    }
+}
+
+async fn create_tree_for_reflink() -> (Arc<InMemoryTreeStorage>, Arc<OpenDirectory>, TreeEditor) {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let root = Arc::new(
+        OpenDirectory::create_directory(
+            std::path::PathBuf::from("/"),
+            storage.clone(),
+            Arc::new(test_clock),
+            1,
+        )
+        .await
+        .unwrap(),
+    );
+    let editor = TreeEditor::new(root.clone(), None);
+    for directory in ["/big", "/big/nested"] {
+        editor
+            .create_directory(
+                NormalizedPath::try_from(relative_path::RelativePath::new(directory)).unwrap(),
+            )
+            .await
+            .unwrap();
+    }
+    let large: &'static [u8] = (0..(3 * TREE_BLOB_MAX_LENGTH))
+        .map(|index| (index % 251) as u8)
+        .collect::<Vec<u8>>()
+        .leak();
+    for (path, content) in [
+        ("/big/large.bin", large),
+        ("/big/small.txt", &b"small"[..]),
+        ("/big/nested/deep.txt", &b"deep"[..]),
+    ] {
+        write_and_save(
+            &editor,
+            &root,
+            &NormalizedPath::try_from(relative_path::RelativePath::new(path)).unwrap(),
+            content,
+        )
+        .await;
+    }
+    (storage, root, editor)
+}
+
+#[test_log::test(tokio::test)]
+async fn test_reflink_directory_stores_no_content() {
+    let (storage, root, editor) = create_tree_for_reflink().await;
+    let trees_before = storage.approximate_tree_count().await.unwrap();
+    editor
+        .reflink(
+            NormalizedPath::try_from(relative_path::RelativePath::new("/big")).unwrap(),
+            NormalizedPath::try_from(relative_path::RelativePath::new("/copy")).unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = root.request_save().await.unwrap();
+    assert!(status.digest.is_digest_up_to_date);
+    // only the root directory that lists the copy is new
+    assert_eq!(
+        trees_before + 1,
+        storage.approximate_tree_count().await.unwrap()
+    );
+    assert_eq!(
+        read_tree_file(&editor, "/big/large.bin").await,
+        read_tree_file(&editor, "/copy/large.bin").await
+    );
+    assert_eq!(
+        &b"deep"[..],
+        &read_tree_file(&editor, "/copy/nested/deep.txt").await[..]
+    );
+
+    // the copies diverge when one of them changes
+    write_and_save(
+        &editor,
+        &root,
+        &NormalizedPath::try_from(relative_path::RelativePath::new("/copy/small.txt")).unwrap(),
+        b"changed",
+    )
+    .await;
+    assert_eq!(
+        &b"small"[..],
+        &read_tree_file(&editor, "/big/small.txt").await[..]
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_reflink_does_not_replace() {
+    let (_storage, _root, editor) = create_tree_for_reflink().await;
+    let from =
+        NormalizedPath::try_from(relative_path::RelativePath::new("/big/small.txt")).unwrap();
+    let to = NormalizedPath::try_from(relative_path::RelativePath::new("/big/nested")).unwrap();
+    assert_eq!(
+        Err(Error::FileAlreadyExists(
+            FileName::try_from("nested".to_string()).unwrap()
+        )),
+        editor.reflink(from.clone(), to.clone()).await
+    );
+    // copying replaces
+    editor.copy(from, to).await.unwrap();
+    assert_eq!(
+        &b"small"[..],
+        &read_tree_file(&editor, "/big/nested").await[..]
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_reflink_saves_changes_of_open_directory() {
+    let (_storage, _root, editor) = create_tree_for_reflink().await;
+    let unsaved = editor
+        .open_file(
+            NormalizedPath::try_from(relative_path::RelativePath::new("/big/nested/unsaved.txt"))
+                .unwrap(),
+            FileCreationMode::create_new(),
+        )
+        .await
+        .unwrap();
+    unsaved
+        .write_bytes(
+            &unsaved.get_write_permission(),
+            0,
+            bytes::Bytes::from_static(b"unsaved"),
+        )
+        .await
+        .unwrap();
+    // into its own subdirectory
+    editor
+        .reflink(
+            NormalizedPath::try_from(relative_path::RelativePath::new("/big")).unwrap(),
+            NormalizedPath::try_from(relative_path::RelativePath::new("/big/nested/big")).unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        &b"unsaved"[..],
+        &read_tree_file(&editor, "/big/nested/big/nested/unsaved.txt").await[..]
+    );
+    // the copy was taken before it was put into the original
+    assert_eq!(
+        Err(Error::NotFound(
+            FileName::try_from("big".to_string()).unwrap()
+        )),
+        editor
+            .get_meta_data(
+                NormalizedPath::try_from(relative_path::RelativePath::new(
+                    "/big/nested/big/nested/big"
+                ))
+                .unwrap()
+            )
+            .await
+            .map(|_| ())
+    );
+}
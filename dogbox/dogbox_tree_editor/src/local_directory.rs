@@ -0,0 +1,399 @@
+use crate::{
+    segmented_blob::{is_hole, load_segmented_blob, save_segmented_blob, Segment},
+    SEGMENTED_BLOB_MAX_CHILDREN_PER_TREE,
+};
+use astraea::{
+    storage::{LoadRoot, LoadStoreTree, StoreError, UpdateRoot},
+    tree::{BlobDigest, HashedTree, Tree, TreeBlob, TreeChildren, TREE_BLOB_MAX_LENGTH},
+};
+use dogbox_tree::serialization::{
+    deserialize_directory, serialize_directory, DeadProperties, DirectoryEntryKind, FileHistory,
+    FileName,
+};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+#[derive(Debug)]
+pub enum LocalDirectoryError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Storage(StoreError),
+    Deserialization(String),
+    Serialization(String),
+    FileSizeMismatch {
+        path: PathBuf,
+        directory_entry_size: u64,
+        content_size: u64,
+    },
+}
+
+impl std::fmt::Display for LocalDirectoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for LocalDirectoryError {}
+
+pub type Result<T> = std::result::Result<T, LocalDirectoryError>;
+
+type LocalDirectoryFuture<'a, T> =
+    Pin<Box<dyn std::future::Future<Output = Result<T>> + Send + 'a>>;
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> LocalDirectoryError + '_ {
+    move |error| LocalDirectoryError::Io {
+        path: path.to_path_buf(),
+        error,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImportStats {
+    pub files_stored: u64,
+    /// files that had the same size and modification time as in the previous version of the tree
+    pub files_unchanged: u64,
+    pub directories: u64,
+    /// symbolic links, special files and names that can't be represented in a tree
+    pub entries_ignored: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExportStats {
+    pub files: u64,
+    pub directories: u64,
+}
+
+/// Stores the content of a local file the same way [crate::OpenFile] does, so that importing a file results in the same
+/// digest as writing it through the editor.
+async fn import_file(
+    path: &Path,
+    storage: &(dyn LoadStoreTree + Send + Sync),
+) -> Result<(BlobDigest, u64)> {
+    let mut file = tokio::fs::File::open(path).await.map_err(io_error(path))?;
+    let mut segments = Vec::new();
+    let mut size_in_bytes = 0;
+    loop {
+        let mut block = Vec::with_capacity(TREE_BLOB_MAX_LENGTH);
+        (&mut file)
+            .take(TREE_BLOB_MAX_LENGTH as u64)
+            .read_to_end(&mut block)
+            .await
+            .map_err(io_error(path))?;
+        let block_size = block.len();
+        if block_size == 0 && !segments.is_empty() {
+            break;
+        }
+        size_in_bytes += block_size as u64;
+        if is_hole(&block) {
+            segments.push(Segment::Hole);
+            continue;
+        }
+        let tree = Tree::new(
+            TreeBlob::try_from(bytes::Bytes::from(block)).expect("the block size was limited"),
+            TreeChildren::empty(),
+        );
+        segments.push(Segment::Stored(
+            storage
+                .store_tree(&HashedTree::from(Arc::new(tree)))
+                .await
+                .map_err(LocalDirectoryError::Storage)?,
+        ));
+        if block_size < TREE_BLOB_MAX_LENGTH {
+            break;
+        }
+    }
+    let digest = save_segmented_blob(
+        &segments,
+        size_in_bytes,
+        SEGMENTED_BLOB_MAX_CHILDREN_PER_TREE,
+        storage,
+    )
+    .await
+    .map_err(LocalDirectoryError::Storage)?;
+    Ok((digest, size_in_bytes))
+}
+
+/// Stores the content of the local directory `source` recursively and returns the digest of the new directory. The
+/// modification times of files and directories are preserved as dead properties (see [DeadProperties::modified]).
+///
+/// If `previous` is the digest of an earlier import of the same directory, files with the same size and modification
+/// time are not read again. Dead properties and file histories of entries that still exist are kept. Every directory
+/// is serialized in one go instead of being edited entry by entry.
+pub fn import_directory<'a>(
+    source: &'a Path,
+    storage: &'a (dyn LoadStoreTree + Send + Sync),
+    previous: Option<BlobDigest>,
+    stats: &'a mut ImportStats,
+) -> LocalDirectoryFuture<'a, BlobDigest> {
+    Box::pin(async move {
+        let mut previous_entries = match previous {
+            Some(previous) => deserialize_directory(storage, &previous)
+                .await
+                .map_err(|error| LocalDirectoryError::Deserialization(error.to_string()))?,
+            None => BTreeMap::new(),
+        };
+        let mut entries = BTreeMap::new();
+        let mut reading = tokio::fs::read_dir(source)
+            .await
+            .map_err(io_error(source))?;
+        while let Some(local_entry) = reading.next_entry().await.map_err(io_error(source))? {
+            let local_path = local_entry.path();
+            let name = match local_entry
+                .file_name()
+                .into_string()
+                .ok()
+                .and_then(|name| FileName::try_from(name).ok())
+            {
+                Some(name) => name,
+                None => {
+                    warn!(
+                        "Ignoring {} because its name is not supported",
+                        local_path.display()
+                    );
+                    stats.entries_ignored += 1;
+                    continue;
+                }
+            };
+            let file_type = local_entry
+                .file_type()
+                .await
+                .map_err(io_error(&local_path))?;
+            let metadata = local_entry
+                .metadata()
+                .await
+                .map_err(io_error(&local_path))?;
+            let modified = metadata.modified().map_err(io_error(&local_path))?;
+            let previous_entry = previous_entries.remove(&name);
+            let (mut properties, previous_kind, previous_digest, previous_history) =
+                match previous_entry {
+                    Some((kind, digest, properties, history)) => {
+                        (properties, Some(kind), Some(digest), history)
+                    }
+                    None => (DeadProperties::new(), None, None, FileHistory::new()),
+                };
+            if file_type.is_dir() {
+                let previous_directory = match previous_kind {
+                    Some(DirectoryEntryKind::Directory) => previous_digest,
+                    _ => None,
+                };
+                let digest =
+                    import_directory(&local_path, storage, previous_directory, stats).await?;
+                stats.directories += 1;
+                properties.set_modified(modified);
+                entries.insert(
+                    name,
+                    (
+                        DirectoryEntryKind::Directory,
+                        digest,
+                        properties,
+                        FileHistory::new(),
+                    ),
+                );
+            } else if file_type.is_file() {
+                let history = match previous_kind {
+                    Some(DirectoryEntryKind::File(_)) => previous_history,
+                    _ => FileHistory::new(),
+                };
+                let is_unchanged = previous_kind == Some(DirectoryEntryKind::File(metadata.len()))
+                    && properties.modified() == Some(modified);
+                let (digest, size) = match (is_unchanged, previous_digest) {
+                    (true, Some(previous_digest)) => {
+                        debug!("{} is unchanged", local_path.display());
+                        stats.files_unchanged += 1;
+                        (previous_digest, metadata.len())
+                    }
+                    _ => {
+                        debug!("Importing {}", local_path.display());
+                        stats.files_stored += 1;
+                        import_file(&local_path, storage).await?
+                    }
+                };
+                properties.set_modified(modified);
+                entries.insert(
+                    name,
+                    (DirectoryEntryKind::File(size), digest, properties, history),
+                );
+            } else {
+                warn!(
+                    "Ignoring {} because it is neither a regular file nor a directory",
+                    local_path.display()
+                );
+                stats.entries_ignored += 1;
+            }
+        }
+        serialize_directory(&entries, storage)
+            .await
+            .map_err(|error| LocalDirectoryError::Serialization(error.to_string()))
+    })
+}
+
+/// Imports `source` into the root `root_name` of `storage`. The previous version of the root is used to skip unchanged
+/// files (see [import_directory]). Entries that don't exist locally anymore are removed from the root.
+///
+/// Nothing else should edit the root at the same time. A running DAV server would overwrite the import with its next
+/// save.
+pub async fn import_into_root<S>(
+    source: &Path,
+    storage: &S,
+    root_name: &str,
+) -> Result<(BlobDigest, ImportStats)>
+where
+    S: LoadStoreTree + LoadRoot + UpdateRoot + Send + Sync,
+{
+    let previous = storage
+        .load_root(root_name)
+        .await
+        .map_err(|error| LocalDirectoryError::Deserialization(error.to_string()))?;
+    info!(
+        "Importing {} into root {} (previous version: {:?})",
+        source.display(),
+        root_name,
+        &previous
+    );
+    let mut stats = ImportStats::default();
+    let digest = import_directory(source, storage, previous, &mut stats).await?;
+    storage
+        .update_root(root_name, &digest)
+        .await
+        .map_err(LocalDirectoryError::Storage)?;
+    info!("Imported root {} as {}: {:?}", root_name, &digest, &stats);
+    Ok((digest, stats))
+}
+
+async fn export_file(
+    storage: &(dyn LoadStoreTree + Send + Sync),
+    digest: &BlobDigest,
+    directory_entry_size: u64,
+    destination: &Path,
+) -> Result<()> {
+    let (segments, content_size) = load_segmented_blob(digest, storage)
+        .await
+        .map_err(|error| LocalDirectoryError::Deserialization(error.to_string()))?;
+    if content_size != directory_entry_size {
+        return Err(LocalDirectoryError::FileSizeMismatch {
+            path: destination.to_path_buf(),
+            directory_entry_size,
+            content_size,
+        });
+    }
+    let mut file = tokio::fs::File::create(destination)
+        .await
+        .map_err(io_error(destination))?;
+    for segment in segments.iter() {
+        let segment = match segment {
+            Segment::Stored(segment) => segment,
+            Segment::Hole => {
+                // skipping the zeros makes the local file sparse, too
+                file.seek(std::io::SeekFrom::Current(TREE_BLOB_MAX_LENGTH as i64))
+                    .await
+                    .map_err(io_error(destination))?;
+                continue;
+            }
+        };
+        let loaded = storage
+            .load_tree(segment)
+            .await
+            .map_err(|error| LocalDirectoryError::Deserialization(error.to_string()))?;
+        let hashed = loaded.hash().ok_or_else(|| {
+            LocalDirectoryError::Deserialization(format!("Hash mismatch in {segment}"))
+        })?;
+        file.write_all(hashed.tree().blob().as_slice())
+            .await
+            .map_err(io_error(destination))?;
+    }
+    // a hole at the end of the file is not written otherwise
+    file.set_len(content_size)
+        .await
+        .map_err(io_error(destination))?;
+    file.flush().await.map_err(io_error(destination))?;
+    Ok(())
+}
+
+fn set_modified(path: &Path, modified: std::time::SystemTime) -> std::io::Result<()> {
+    std::fs::File::options()
+        .read(true)
+        .open(path)?
+        .set_modified(modified)
+}
+
+/// Writes the directory `digest` with all of its descendants into the local directory `destination`, which is created
+/// if necessary. `digest` can be the current or any earlier version of a root. Existing local files are overwritten, but
+/// local files that don't exist in the tree are left alone. Preserved modification times are restored.
+pub fn export_directory<'a>(
+    storage: &'a (dyn LoadStoreTree + Send + Sync),
+    digest: BlobDigest,
+    destination: &'a Path,
+    stats: &'a mut ExportStats,
+) -> LocalDirectoryFuture<'a, ()> {
+    Box::pin(async move {
+        tokio::fs::create_dir_all(destination)
+            .await
+            .map_err(io_error(destination))?;
+        let entries = deserialize_directory(storage, &digest)
+            .await
+            .map_err(|error| LocalDirectoryError::Deserialization(error.to_string()))?;
+        for (name, (kind, child_digest, properties, _history)) in entries {
+            let local_path = destination.join(name.as_str());
+            match kind {
+                DirectoryEntryKind::Directory => {
+                    export_directory(storage, child_digest, &local_path, stats).await?;
+                    stats.directories += 1;
+                }
+                DirectoryEntryKind::File(size) => {
+                    debug!("Exporting {}", local_path.display());
+                    export_file(storage, &child_digest, size, &local_path).await?;
+                    stats.files += 1;
+                }
+            }
+            if let Some(modified) = properties.modified() {
+                // Not every platform allows changing the modification time of a directory, but losing it isn't worth
+                // failing the export for.
+                if let Err(error) = set_modified(&local_path, modified) {
+                    warn!(
+                        "Could not set the modification time of {}: {}",
+                        local_path.display(),
+                        error
+                    );
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Exports the current version of the root `root_name` (see [export_directory]). Returns `None` if the root doesn't
+/// exist.
+pub async fn export_root<S>(
+    storage: &S,
+    root_name: &str,
+    destination: &Path,
+) -> Result<Option<(BlobDigest, ExportStats)>>
+where
+    S: LoadStoreTree + LoadRoot + Send + Sync,
+{
+    let digest = match storage
+        .load_root(root_name)
+        .await
+        .map_err(|error| LocalDirectoryError::Deserialization(error.to_string()))?
+    {
+        Some(digest) => digest,
+        None => return Ok(None),
+    };
+    info!(
+        "Exporting root {} ({}) to {}",
+        root_name,
+        &digest,
+        destination.display()
+    );
+    let mut stats = ExportStats::default();
+    export_directory(storage, digest, destination, &mut stats).await?;
+    Ok(Some((digest, stats)))
+}
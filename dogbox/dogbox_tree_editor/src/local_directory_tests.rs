@@ -0,0 +1,350 @@
+use crate::{
+    local_directory::{export_directory, export_root, import_into_root, ExportStats, ImportStats},
+    FileCreationMode, NormalizedPath, OpenDirectory, TreeEditor,
+};
+use astraea::{
+    storage::{SQLiteStorage, UpdateRoot},
+    tree::TREE_BLOB_MAX_LENGTH,
+};
+use pretty_assertions::assert_eq;
+use std::{path::Path, sync::Arc};
+
+fn test_clock() -> std::time::SystemTime {
+    std::time::SystemTime::UNIX_EPOCH
+}
+
+fn create_storage() -> SQLiteStorage {
+    let connection = rusqlite::Connection::open_in_memory().unwrap();
+    SQLiteStorage::create_schema(&connection).unwrap();
+    SQLiteStorage::from(connection).unwrap()
+}
+
+fn time(seconds: u64) -> std::time::SystemTime {
+    std::time::SystemTime::UNIX_EPOCH + std::time::Duration::new(seconds, 123_456_789)
+}
+
+fn write_local_file(path: &Path, content: &[u8], modified: std::time::SystemTime) {
+    std::fs::write(path, content).unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+fn large_content() -> Vec<u8> {
+    (0..(TREE_BLOB_MAX_LENGTH * 3 + 17))
+        .map(|index| (index % 251) as u8)
+        .collect()
+}
+
+fn create_example_directory(path: &Path) {
+    write_local_file(&path.join("empty.txt"), b"", time(1));
+    write_local_file(&path.join("small.txt"), b"hello", time(2));
+    std::fs::create_dir(path.join("sub")).unwrap();
+    write_local_file(&path.join("sub/large.bin"), &large_content(), time(3));
+}
+
+fn read_modified(path: &Path) -> std::time::SystemTime {
+    std::fs::metadata(path).unwrap().modified().unwrap()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_and_export_root() {
+    let source = tempfile::tempdir().unwrap();
+    create_example_directory(source.path());
+    let storage = create_storage();
+    let (digest, import_stats) = import_into_root(source.path(), &storage, "imported")
+        .await
+        .unwrap();
+    assert_eq!(
+        ImportStats {
+            files_stored: 3,
+            files_unchanged: 0,
+            directories: 1,
+            entries_ignored: 0,
+        },
+        import_stats
+    );
+
+    let destination = tempfile::tempdir().unwrap();
+    let exported = destination.path().join("exported");
+    assert_eq!(
+        Some((
+            digest,
+            ExportStats {
+                files: 3,
+                directories: 1
+            }
+        )),
+        export_root(&storage, "imported", &exported).await.unwrap()
+    );
+    assert_eq!(b"", &std::fs::read(exported.join("empty.txt")).unwrap()[..]);
+    assert_eq!(
+        b"hello",
+        &std::fs::read(exported.join("small.txt")).unwrap()[..]
+    );
+    assert_eq!(
+        large_content(),
+        std::fs::read(exported.join("sub/large.bin")).unwrap()
+    );
+    assert_eq!(time(1), read_modified(&exported.join("empty.txt")));
+    assert_eq!(time(2), read_modified(&exported.join("small.txt")));
+    assert_eq!(time(3), read_modified(&exported.join("sub/large.bin")));
+
+    assert_eq!(
+        None,
+        export_root(&storage, "missing", &exported).await.unwrap()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_imported_files_can_be_edited() {
+    let source = tempfile::tempdir().unwrap();
+    create_example_directory(source.path());
+    let storage = Arc::new(create_storage());
+    let (digest, _stats) = import_into_root(source.path(), storage.as_ref(), "imported")
+        .await
+        .unwrap();
+    let root = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage,
+        &digest,
+        test_clock(),
+        Arc::new(test_clock),
+        1,
+    )
+    .await
+    .unwrap();
+    let editor = TreeEditor::new(root, None);
+    let path = NormalizedPath::try_from(relative_path::RelativePath::new("sub/large.bin")).unwrap();
+    assert_eq!(
+        time(3),
+        editor.get_meta_data(path.clone()).await.unwrap().modified
+    );
+    let opened = editor
+        .open_file(path, FileCreationMode::open_existing())
+        .await
+        .unwrap();
+    let read_permission = opened.get_read_permission();
+    let mut content = Vec::new();
+    while content.len() < large_content().len() {
+        let read = opened
+            .read_bytes(&read_permission, content.len() as u64, 100_000)
+            .await
+            .unwrap();
+        assert!(!read.is_empty());
+        content.extend_from_slice(&read);
+    }
+    assert_eq!(large_content(), content);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_edited_files_get_a_new_modification_time() {
+    let source = tempfile::tempdir().unwrap();
+    create_example_directory(source.path());
+    let storage = Arc::new(create_storage());
+    let (digest, _stats) = import_into_root(source.path(), storage.as_ref(), "imported")
+        .await
+        .unwrap();
+    let root = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage.clone(),
+        &digest,
+        test_clock(),
+        Arc::new(|| time(100)),
+        1,
+    )
+    .await
+    .unwrap();
+    let editor = TreeEditor::new(root.clone(), None);
+    let path = NormalizedPath::try_from(relative_path::RelativePath::new("small.txt")).unwrap();
+    let opened = editor
+        .open_file(path.clone(), FileCreationMode::open_existing())
+        .await
+        .unwrap();
+    let write_permission = opened.get_write_permission();
+    // same size as before
+    opened
+        .write_bytes(&write_permission, 0, bytes::Bytes::from_static(b"HELLO"))
+        .await
+        .unwrap();
+    drop(write_permission);
+    opened.notify_dropped_write_permission();
+    opened.flush().await.unwrap();
+    let mut saved_digest = None;
+    for _ in 0..1000 {
+        let status = root.request_save().await.unwrap();
+        if editor.get_meta_data(path.clone()).await.unwrap().modified == time(100) {
+            saved_digest = Some(status.digest.last_known_digest);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    }
+    let saved_digest = saved_digest.expect("the modification time should change when saving");
+    storage
+        .update_root("imported", &saved_digest)
+        .await
+        .unwrap();
+
+    let reloaded = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage.clone(),
+        &saved_digest,
+        test_clock(),
+        Arc::new(test_clock),
+        1,
+    )
+    .await
+    .unwrap();
+    let reloaded_editor = TreeEditor::new(reloaded, None);
+    assert_eq!(
+        time(100),
+        reloaded_editor.get_meta_data(path).await.unwrap().modified
+    );
+    // unchanged files keep the time of the import
+    let unchanged =
+        NormalizedPath::try_from(relative_path::RelativePath::new("empty.txt")).unwrap();
+    assert_eq!(
+        time(1),
+        reloaded_editor
+            .get_meta_data(unchanged)
+            .await
+            .unwrap()
+            .modified
+    );
+
+    // the local file has the same size, but it is not the content of the root anymore
+    let (_digest, stats) = import_into_root(source.path(), storage.as_ref(), "imported")
+        .await
+        .unwrap();
+    assert_eq!(
+        ImportStats {
+            files_stored: 1,
+            files_unchanged: 2,
+            directories: 1,
+            entries_ignored: 0,
+        },
+        stats
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_again_skips_unchanged_files() {
+    let source = tempfile::tempdir().unwrap();
+    create_example_directory(source.path());
+    let storage = create_storage();
+    let (first_digest, _stats) = import_into_root(source.path(), &storage, "imported")
+        .await
+        .unwrap();
+
+    let (digest, stats) = import_into_root(source.path(), &storage, "imported")
+        .await
+        .unwrap();
+    assert_eq!(first_digest, digest);
+    assert_eq!(
+        ImportStats {
+            files_stored: 0,
+            files_unchanged: 3,
+            directories: 1,
+            entries_ignored: 0,
+        },
+        stats
+    );
+
+    // same size, but a different modification time
+    write_local_file(&source.path().join("small.txt"), b"world", time(4));
+    write_local_file(&source.path().join("new.txt"), b"new", time(5));
+    std::fs::remove_file(source.path().join("empty.txt")).unwrap();
+    let (second_digest, stats) = import_into_root(source.path(), &storage, "imported")
+        .await
+        .unwrap();
+    assert_eq!(
+        ImportStats {
+            files_stored: 2,
+            files_unchanged: 1,
+            directories: 1,
+            entries_ignored: 0,
+        },
+        stats
+    );
+
+    let destination = tempfile::tempdir().unwrap();
+    export_root(&storage, "imported", destination.path())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        b"world",
+        &std::fs::read(destination.path().join("small.txt")).unwrap()[..]
+    );
+    assert_eq!(
+        b"new",
+        &std::fs::read(destination.path().join("new.txt")).unwrap()[..]
+    );
+    assert!(!std::fs::exists(destination.path().join("empty.txt")).unwrap());
+
+    // earlier versions of a root can still be exported by digest
+    let snapshot = tempfile::tempdir().unwrap();
+    let mut export_stats = ExportStats::default();
+    export_directory(&storage, first_digest, snapshot.path(), &mut export_stats)
+        .await
+        .unwrap();
+    assert_eq!(
+        b"hello",
+        &std::fs::read(snapshot.path().join("small.txt")).unwrap()[..]
+    );
+    assert_ne!(first_digest, second_digest);
+}
+
+#[cfg(unix)]
+#[test_log::test(tokio::test)]
+async fn test_import_ignores_symbolic_links() {
+    let source = tempfile::tempdir().unwrap();
+    write_local_file(&source.path().join("target.txt"), b"target", time(1));
+    std::os::unix::fs::symlink("target.txt", source.path().join("link.txt")).unwrap();
+    let storage = create_storage();
+    let (_digest, stats) = import_into_root(source.path(), &storage, "imported")
+        .await
+        .unwrap();
+    assert_eq!(
+        ImportStats {
+            files_stored: 1,
+            files_unchanged: 0,
+            directories: 0,
+            entries_ignored: 1,
+        },
+        stats
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_and_export_sparse_file() {
+    let source = tempfile::tempdir().unwrap();
+    let size = (TREE_BLOB_MAX_LENGTH as u64) * 30 + 5;
+    let data_position = (TREE_BLOB_MAX_LENGTH as u64) * 12 + 3;
+    {
+        use std::io::{Seek, Write};
+        let mut file = std::fs::File::create(source.path().join("sparse.bin")).unwrap();
+        file.set_len(size).unwrap();
+        file.seek(std::io::SeekFrom::Start(data_position)).unwrap();
+        file.write_all(b"data").unwrap();
+    }
+    let storage = create_storage();
+    import_into_root(source.path(), &storage, "imported")
+        .await
+        .unwrap();
+
+    let destination = tempfile::tempdir().unwrap();
+    export_root(&storage, "imported", destination.path())
+        .await
+        .unwrap()
+        .unwrap();
+    let mut expected = vec![0u8; size as usize];
+    expected[data_position as usize..data_position as usize + 4].copy_from_slice(b"data");
+    assert_eq!(
+        expected,
+        std::fs::read(destination.path().join("sparse.bin")).unwrap()
+    );
+}
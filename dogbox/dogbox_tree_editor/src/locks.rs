@@ -0,0 +1,56 @@
+use crate::NormalizedPath;
+use std::{cell::RefCell, collections::BTreeSet};
+
+/// Decides whether the locks on a path allow a change. A [crate::TreeEditor] asks before every change (see
+/// [crate::TreeEditor::with_locks]), so a lock protects a resource from every writer of the tree and not only from the
+/// clients of the protocol that took it.
+pub trait CheckLocks {
+    /// Fails with the token of a lock on `path` that the author of the change doesn't hold. The author holds the
+    /// locks whose tokens are in `held`. With `deep`, the locks on the entries below `path` count too.
+    fn check(
+        &self,
+        path: &NormalizedPath,
+        deep: bool,
+        held: &LockTokens,
+    ) -> std::result::Result<(), String>;
+}
+
+/// The tokens of the locks that the author of a change holds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LockTokens {
+    tokens: BTreeSet<String>,
+}
+
+impl LockTokens {
+    pub fn new(tokens: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            tokens: tokens.into_iter().collect(),
+        }
+    }
+
+    pub fn contains(&self, token: &str) -> bool {
+        self.tokens.contains(token)
+    }
+}
+
+tokio::task_local! {
+    static HELD_LOCK_TOKENS: RefCell<LockTokens>;
+}
+
+/// Makes the changes in `future` on behalf of someone who holds the locks with `tokens`, like a WebDAV request that
+/// submits them in its `If` header. Changes outside of such a scope don't hold any lock.
+pub async fn holding_locks<F: std::future::Future>(tokens: LockTokens, future: F) -> F::Output {
+    HELD_LOCK_TOKENS.scope(RefCell::new(tokens), future).await
+}
+
+/// Lets the rest of the current [holding_locks] scope pass the lock with `token`, because the scope has just taken it.
+/// Does nothing outside of a scope.
+pub fn hold_lock(token: String) {
+    let _ = HELD_LOCK_TOKENS.try_with(|held| held.borrow_mut().tokens.insert(token));
+}
+
+pub(crate) fn held_lock_tokens() -> LockTokens {
+    HELD_LOCK_TOKENS
+        .try_with(|held| held.borrow().clone())
+        .unwrap_or_default()
+}
@@ -0,0 +1,264 @@
+use crate::{
+    hold_lock, holding_locks,
+    test_helpers::{create_editor, path, write_file},
+    CheckLocks, DeadPropertyChange, Error, FileCreationMode, LockTokens, NormalizedPath,
+    TrashRetention, TreeEditor,
+};
+use dogbox_tree::serialization::{DeadPropertyName, DeadPropertyValue};
+use pretty_assertions::assert_eq;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+/// Exclusive locks by path without depth.
+#[derive(Default)]
+struct FakeLocks {
+    locks: Mutex<BTreeMap<NormalizedPath, String>>,
+}
+
+impl FakeLocks {
+    fn lock(&self, locked_path: &str, token: &str) {
+        self.locks
+            .lock()
+            .unwrap()
+            .insert(path(locked_path), token.to_string());
+    }
+
+    fn unlock(&self, locked_path: &str) {
+        self.locks.lock().unwrap().remove(&path(locked_path));
+    }
+}
+
+impl CheckLocks for FakeLocks {
+    fn check(
+        &self,
+        path: &NormalizedPath,
+        deep: bool,
+        held: &LockTokens,
+    ) -> std::result::Result<(), String> {
+        match self
+            .locks
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(locked_path, token)| {
+                (*locked_path == path || (deep && locked_path.starts_with(path)))
+                    && !held.contains(token)
+            }) {
+            Some((_, token)) => Err(token.clone()),
+            None => Ok(()),
+        }
+    }
+}
+
+async fn create_locked_editor() -> (Arc<FakeLocks>, TreeEditor) {
+    let (_root, editor) = create_editor().await;
+    let locks = Arc::new(FakeLocks::default());
+    (locks.clone(), editor.with_locks(locks))
+}
+
+fn holder() -> LockTokens {
+    LockTokens::new(["token".to_string()])
+}
+
+fn property_change() -> Vec<DeadPropertyChange> {
+    vec![DeadPropertyChange::Set(
+        DeadPropertyName::new(Some("DAV:".to_string()), "displayname".to_string()),
+        DeadPropertyValue::new(None, Some(b"A".to_vec())),
+    )]
+}
+
+#[test_log::test(tokio::test)]
+async fn test_changes_of_a_locked_file_need_the_lock() {
+    let (locks, editor) = create_locked_editor().await;
+    write_file(&editor, "a.txt", b"original").await;
+    locks.lock("a.txt", "token");
+
+    let locked = || Error::Locked(path("a.txt"));
+    assert_eq!(
+        Some(locked()),
+        editor
+            .open_file(path("a.txt"), FileCreationMode::create())
+            .await
+            .err()
+    );
+    assert_eq!(
+        Some(locked()),
+        editor
+            .open_file_for_writing(path("a.txt"), FileCreationMode::open_existing())
+            .await
+            .err()
+    );
+    assert_eq!(
+        Err(locked()),
+        editor
+            .patch_dead_properties(path("a.txt"), property_change())
+            .await
+    );
+    assert_eq!(
+        Err(locked()),
+        editor.rename(path("a.txt"), path("b.txt")).await
+    );
+    assert_eq!(Err(locked()), editor.remove(path("a.txt")).await);
+    write_file(&editor, "c.txt", b"other").await;
+    assert_eq!(
+        Err(locked()),
+        editor.copy(path("c.txt"), path("a.txt")).await
+    );
+
+    // a write permission that was granted before the file was locked doesn't help either
+    locks.unlock("a.txt");
+    let (opened, write_permission) = editor
+        .open_file_for_writing(path("a.txt"), FileCreationMode::open_existing())
+        .await
+        .unwrap();
+    locks.lock("a.txt", "token");
+    assert_eq!(
+        Err(locked()),
+        editor
+            .write_file(
+                &path("a.txt"),
+                &opened,
+                &write_permission,
+                0,
+                bytes::Bytes::from_static(b"changed"),
+            )
+            .await
+    );
+    assert_eq!(
+        Err(locked()),
+        editor
+            .resize_file(&path("a.txt"), &opened, &write_permission, 0)
+            .await
+    );
+
+    // reading doesn't change anything
+    editor
+        .open_file(path("a.txt"), FileCreationMode::open_existing())
+        .await
+        .unwrap();
+
+    holding_locks(holder(), async {
+        editor
+            .write_file(
+                &path("a.txt"),
+                &opened,
+                &write_permission,
+                0,
+                bytes::Bytes::from_static(b"changed!"),
+            )
+            .await
+            .unwrap();
+        editor
+            .patch_dead_properties(path("a.txt"), property_change())
+            .await
+            .unwrap();
+        editor.rename(path("a.txt"), path("b.txt")).await.unwrap();
+    })
+    .await;
+    drop(write_permission);
+    opened.notify_dropped_write_permission();
+    assert_eq!(
+        b"changed!".to_vec(),
+        opened
+            .read_bytes(&opened.get_read_permission(), 0, 100)
+            .await
+            .unwrap()
+            .to_vec()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_changing_a_directory_needs_the_locks_below() {
+    let (locks, editor) = create_locked_editor().await;
+    editor.create_directory(path("dir")).await.unwrap();
+    write_file(&editor, "dir/a.txt", b"locked").await;
+    locks.lock("dir/a.txt", "token");
+
+    assert_eq!(
+        Err(Error::Locked(path("dir"))),
+        editor.remove(path("dir")).await
+    );
+    assert_eq!(
+        Err(Error::Locked(path("dir"))),
+        editor.rename(path("dir"), path("moved")).await
+    );
+    editor.create_directory(path("other")).await.unwrap();
+    assert_eq!(
+        Err(Error::Locked(path("dir"))),
+        editor.copy(path("other"), path("dir")).await
+    );
+    // the directory itself is not locked
+    editor.create_directory(path("dir/b")).await.unwrap();
+    editor
+        .patch_dead_properties(path("dir"), property_change())
+        .await
+        .unwrap();
+
+    holding_locks(holder(), async {
+        editor.rename(path("dir"), path("moved")).await.unwrap();
+    })
+    .await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_taking_a_lock_holds_it_for_the_rest_of_the_scope() {
+    let (locks, editor) = create_locked_editor().await;
+    hold_lock("token".to_string());
+    holding_locks(LockTokens::default(), async {
+        locks.lock("a.txt", "token");
+        assert_eq!(
+            Some(Error::Locked(path("a.txt"))),
+            editor
+                .open_file(path("a.txt"), FileCreationMode::create())
+                .await
+                .err()
+        );
+        hold_lock("token".to_string());
+        editor
+            .open_file(path("a.txt"), FileCreationMode::create())
+            .await
+            .unwrap();
+    })
+    .await;
+    // neither outside of the scope nor in another scope
+    assert_eq!(
+        Some(Error::Locked(path("a.txt"))),
+        editor
+            .open_file(path("a.txt"), FileCreationMode::create())
+            .await
+            .err()
+    );
+    holding_locks(LockTokens::default(), async {
+        assert_eq!(
+            Some(Error::Locked(path("a.txt"))),
+            editor
+                .open_file(path("a.txt"), FileCreationMode::create())
+                .await
+                .err()
+        );
+    })
+    .await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_expire_trash_keeps_locked_entries() {
+    let (_root, editor) = create_editor().await;
+    let locks = Arc::new(FakeLocks::default());
+    let editor = editor.with_locks(locks.clone()).with_trash(TrashRetention {
+        max_age: Some(std::time::Duration::ZERO),
+    });
+    write_file(&editor, "a.txt", b"deleted").await;
+    editor.remove(path("a.txt")).await.unwrap();
+    let trashed = editor.list_trash().await.unwrap();
+    assert_eq!(1, trashed.len());
+    locks.lock(&format!(".trash/{}", trashed[0].name), "token");
+
+    assert_eq!(Ok(0), editor.expire_trash().await);
+    assert_eq!(trashed, editor.list_trash().await.unwrap());
+
+    locks.unlock(&format!(".trash/{}", trashed[0].name));
+    assert_eq!(Ok(1), editor.expire_trash().await);
+    assert_eq!(Ok(Vec::new()), editor.list_trash().await);
+}
@@ -0,0 +1,97 @@
+use crate::{Error, Result};
+use astraea::{
+    storage::{CommitChanges, UpdateRoot},
+    tree::BlobDigest,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Decides when [crate::TreeEditor::save_regularly] saves the changes in a tree. Saving stores the changed trees, but
+/// only a [PersistRoot] makes them durable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavePolicy {
+    /// How often the tree is checked for unsaved changes.
+    pub interval: std::time::Duration,
+    /// Saves as soon as the open files hold at least this many bytes that haven't been stored yet.
+    pub dirty_bytes_threshold: Option<u64>,
+    /// Saves once changes have been unsaved for this long. A policy without a maximum age and without a
+    /// [SavePolicy::dirty_bytes_threshold] never saves on its own.
+    pub maximum_unsaved_age: Option<std::time::Duration>,
+    /// Closing a file that was written to commits it like an fsync would (see [crate::TreeEditor::commit]).
+    pub commit_on_close: bool,
+    /// How many changed blocks an open file keeps in memory before it stores them.
+    pub open_file_write_buffer_in_blocks: usize,
+}
+
+impl SavePolicy {
+    /// `unsaved_since` is the time when the tree was first seen with unsaved changes. [None] means that everything is
+    /// saved.
+    pub fn should_save(
+        &self,
+        unsaved_bytes: u64,
+        unsaved_since: Option<std::time::SystemTime>,
+        now: std::time::SystemTime,
+    ) -> bool {
+        let too_many_bytes = match self.dirty_bytes_threshold {
+            Some(threshold) => unsaved_bytes >= threshold,
+            None => false,
+        };
+        let too_old = match (self.maximum_unsaved_age, unsaved_since) {
+            (Some(maximum_unsaved_age), Some(since)) => match now.duration_since(since) {
+                Ok(age) => age >= maximum_unsaved_age,
+                // the clock went backwards
+                Err(_) => false,
+            },
+            _ => false,
+        };
+        too_many_bytes || too_old
+    }
+}
+
+impl Default for SavePolicy {
+    fn default() -> Self {
+        Self {
+            interval: std::time::Duration::from_secs(5),
+            dirty_bytes_threshold: None,
+            // every check saves the changes that it finds
+            maximum_unsaved_age: Some(std::time::Duration::ZERO),
+            commit_on_close: false,
+            open_file_write_buffer_in_blocks: 200,
+        }
+    }
+}
+
+/// Makes a saved root durable.
+#[async_trait]
+pub trait PersistRoot {
+    async fn persist_root(&self, digest: &BlobDigest) -> Result<()>;
+}
+
+/// Persists by pointing a named root of the storage to the digest and committing the transaction.
+pub struct NamedRoot<S> {
+    storage: Arc<S>,
+    name: String,
+}
+
+impl<S> NamedRoot<S> {
+    pub fn new(storage: Arc<S>, name: String) -> Self {
+        Self { storage, name }
+    }
+}
+
+#[async_trait]
+impl<S> PersistRoot for NamedRoot<S>
+where
+    S: UpdateRoot + CommitChanges + Send + Sync,
+{
+    async fn persist_root(&self, digest: &BlobDigest) -> Result<()> {
+        self.storage
+            .update_root(&self.name, digest)
+            .await
+            .map_err(Error::Storage)?;
+        self.storage
+            .commit_changes()
+            .await
+            .map_err(|error| Error::CommitFailed(error.to_string()))
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    test_helpers::{create_storage, path, time, write_file},
+    Error, FileCreationMode, NamedRoot, NormalizedPath, OpenDirectory, SavePolicy, TreeEditor,
+    WallClock,
+};
+use astraea::storage::{LoadRoot, SQLiteStorage, UpdateRoot};
+use pretty_assertions::assert_eq;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+#[test_log::test]
+fn test_should_save_by_default() {
+    let policy = SavePolicy::default();
+    assert!(!policy.should_save(0, None, time(0)));
+    assert!(policy.should_save(0, Some(time(0)), time(0)));
+    assert!(policy.should_save(1000, Some(time(0)), time(0)));
+}
+
+#[test_log::test]
+fn test_should_save_without_limits() {
+    let policy = SavePolicy {
+        dirty_bytes_threshold: None,
+        maximum_unsaved_age: None,
+        ..SavePolicy::default()
+    };
+    assert!(!policy.should_save(0, None, time(0)));
+    assert!(!policy.should_save(1000, Some(time(0)), time(1000)));
+}
+
+#[test_log::test]
+fn test_should_save_dirty_bytes_threshold_without_maximum_age() {
+    let policy = SavePolicy {
+        dirty_bytes_threshold: Some(1000),
+        maximum_unsaved_age: None,
+        ..SavePolicy::default()
+    };
+    assert!(!policy.should_save(999, Some(time(0)), time(1000)));
+    assert!(policy.should_save(1000, Some(time(0)), time(0)));
+}
+
+#[test_log::test]
+fn test_should_save_maximum_unsaved_age() {
+    let policy = SavePolicy {
+        maximum_unsaved_age: Some(Duration::from_secs(60)),
+        ..SavePolicy::default()
+    };
+    assert!(!policy.should_save(0, None, time(100)));
+    assert!(!policy.should_save(0, Some(time(100)), time(100)));
+    assert!(!policy.should_save(0, Some(time(100)), time(159)));
+    assert!(policy.should_save(0, Some(time(100)), time(160)));
+    // the clock went backwards
+    assert!(!policy.should_save(0, Some(time(100)), time(50)));
+}
+
+#[test_log::test]
+fn test_should_save_dirty_bytes_threshold() {
+    let policy = SavePolicy {
+        maximum_unsaved_age: Some(Duration::from_secs(60)),
+        dirty_bytes_threshold: Some(1000),
+        ..SavePolicy::default()
+    };
+    assert!(!policy.should_save(999, Some(time(100)), time(100)));
+    assert!(policy.should_save(1000, Some(time(100)), time(100)));
+    assert!(policy.should_save(0, Some(time(100)), time(160)));
+}
+
+const ROOT_NAME: &str = "test";
+
+async fn create_editor(
+    storage: Arc<SQLiteStorage>,
+    clock: WallClock,
+    save_policy: SavePolicy,
+) -> TreeEditor {
+    let root = OpenDirectory::create_directory(
+        std::path::PathBuf::from("/"),
+        storage.clone(),
+        clock,
+        save_policy.open_file_write_buffer_in_blocks,
+    )
+    .await
+    .unwrap();
+    TreeEditor::new(Arc::new(root), None).with_persistence(
+        save_policy,
+        Arc::new(NamedRoot::new(storage, ROOT_NAME.to_string())),
+    )
+}
+
+async fn read_committed_file(storage: Arc<SQLiteStorage>, file_path: &str) -> Option<bytes::Bytes> {
+    let digest = storage.load_root(ROOT_NAME).await.unwrap()?;
+    let root = OpenDirectory::load_directory(
+        std::path::PathBuf::from("/"),
+        storage,
+        &digest,
+        time(0),
+        Arc::new(|| time(0)),
+        1,
+    )
+    .await
+    .unwrap();
+    let editor = TreeEditor::new(root, None);
+    let opened = match editor
+        .open_file(path(file_path), FileCreationMode::open_existing())
+        .await
+    {
+        Ok(opened) => opened,
+        Err(_) => return None,
+    };
+    Some(
+        opened
+            .read_bytes(&opened.get_read_permission(), 0, 1000)
+            .await
+            .unwrap(),
+    )
+}
+
+#[test_log::test(tokio::test)]
+async fn test_commit_file() {
+    let storage = create_storage();
+    let editor = create_editor(storage.clone(), Arc::new(|| time(0)), SavePolicy::default()).await;
+    editor.create_directory(path("a")).await.unwrap();
+    editor.create_directory(path("a/b")).await.unwrap();
+    write_file(&editor, "a/b/file.txt", b"hello").await;
+    let digest = editor.commit(path("a/b/file.txt")).await.unwrap();
+    assert_eq!(Some(digest), storage.load_root(ROOT_NAME).await.unwrap());
+    assert_eq!(
+        Some(bytes::Bytes::from_static(b"hello")),
+        read_committed_file(storage.clone(), "a/b/file.txt").await
+    );
+
+    write_file(&editor, "a/b/file.txt", b"world").await;
+    let second_digest = editor.commit(path("a/b/file.txt")).await.unwrap();
+    assert_ne!(digest, second_digest);
+    assert_eq!(
+        Some(bytes::Bytes::from_static(b"world")),
+        read_committed_file(storage, "a/b/file.txt").await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_commit_directory() {
+    let storage = create_storage();
+    let editor = create_editor(storage.clone(), Arc::new(|| time(0)), SavePolicy::default()).await;
+    editor.create_directory(path("a")).await.unwrap();
+    editor.create_directory(path("a/b")).await.unwrap();
+    write_file(&editor, "a/b/file.txt", b"hello").await;
+    editor.commit(path("a")).await.unwrap();
+    assert_eq!(
+        Some(bytes::Bytes::from_static(b"hello")),
+        read_committed_file(storage, "a/b/file.txt").await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_commit_not_found() {
+    let storage = create_storage();
+    let editor = create_editor(storage.clone(), Arc::new(|| time(0)), SavePolicy::default()).await;
+    assert!(matches!(
+        editor.commit(path("missing.txt")).await,
+        Err(Error::NotFound(_))
+    ));
+    assert_eq!(None, storage.load_root(ROOT_NAME).await.unwrap());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_commit_without_persistence() {
+    let storage = create_storage();
+    let root = OpenDirectory::create_directory(
+        std::path::PathBuf::from("/"),
+        storage,
+        Arc::new(|| time(0)),
+        1,
+    )
+    .await
+    .unwrap();
+    let editor = TreeEditor::new(Arc::new(root), None);
+    assert!(matches!(
+        editor.commit(NormalizedPath::root()).await,
+        Err(Error::NotPersistent)
+    ));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_save_regularly_waits_for_maximum_unsaved_age() {
+    let seconds = Arc::new(AtomicU64::new(100));
+    let clock: WallClock = {
+        let seconds = seconds.clone();
+        Arc::new(move || time(seconds.load(Ordering::SeqCst)))
+    };
+    let storage = create_storage();
+    let editor = create_editor(
+        storage.clone(),
+        clock,
+        SavePolicy {
+            interval: Duration::from_millis(1),
+            maximum_unsaved_age: Some(Duration::from_secs(60)),
+            ..SavePolicy::default()
+        },
+    )
+    .await;
+    let empty_root = editor.root.latest_status().digest.last_known_digest;
+    let saving = editor.save_regularly();
+    let changing = async {
+        write_file(&editor, "file.txt", b"hello").await;
+        // wait for the change to reach the root
+        while editor.root.latest_status().digest.is_digest_up_to_date {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            empty_root,
+            editor.root.latest_status().digest.last_known_digest
+        );
+
+        seconds.store(160, Ordering::SeqCst);
+        while editor.root.latest_status().digest.last_known_digest == empty_root {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    };
+    tokio::select! {
+        _ = saving => panic!("Saving isn't expected to stop"),
+        _ = changing => {}
+    }
+    // saving doesn't persist the root, but the saved tree contains the file
+    storage
+        .update_root(
+            ROOT_NAME,
+            &editor.root.latest_status().digest.last_known_digest,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        Some(bytes::Bytes::from_static(b"hello")),
+        read_committed_file(storage, "file.txt").await
+    );
+}
@@ -0,0 +1,260 @@
+use crate::{
+    change_feed::{compare_snapshots, load_subtree, DirectorySnapshot},
+    NormalizedPath, Result, TreeChange, TreeChangeKind,
+};
+use astraea::{
+    storage::LoadStoreTree,
+    tree::{BlobDigest, TREE_BLOB_MAX_LENGTH},
+};
+use dogbox_tree::serialization::DirectoryEntryKind;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchQueryError {
+    Empty,
+    InvalidSize(String),
+}
+
+impl std::fmt::Display for SearchQueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for SearchQueryError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SearchTerm {
+    /// Matches part of the file name or a whole word of the content.
+    Word(String),
+    NameContains(String),
+    Extension(String),
+    MinimumSize(u64),
+    MaximumSize(u64),
+}
+
+/// All terms of a query have to match. The syntax only uses characters that are allowed in file names, so that a
+/// query can be part of a path:
+/// * `word` finds `word` in the file name or as a word in the content of small text files
+/// * `name=part` finds `part` in the file name
+/// * `ext=txt` finds files with the extension `txt`
+/// * `minsize=100` and `maxsize=100` limit the file size in bytes (inclusive)
+///
+/// Terms are separated by whitespace and case-insensitive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    terms: Vec<SearchTerm>,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> std::result::Result<SearchQuery, SearchQueryError> {
+        let parse_size = |size: &str| {
+            size.parse::<u64>()
+                .map_err(|_| SearchQueryError::InvalidSize(size.to_string()))
+        };
+        let mut terms = Vec::new();
+        for term in input.split_whitespace() {
+            let term = term.to_lowercase();
+            terms.push(match term.split_once('=') {
+                Some(("name", part)) => SearchTerm::NameContains(part.to_string()),
+                Some(("ext", extension)) => {
+                    SearchTerm::Extension(extension.trim_start_matches('.').to_string())
+                }
+                Some(("minsize", size)) => SearchTerm::MinimumSize(parse_size(size)?),
+                Some(("maxsize", size)) => SearchTerm::MaximumSize(parse_size(size)?),
+                _ => SearchTerm::Word(term),
+            });
+        }
+        if terms.is_empty() {
+            return Err(SearchQueryError::Empty);
+        }
+        Ok(SearchQuery { terms })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub path: NormalizedPath,
+    pub size: u64,
+    pub digest: BlobDigest,
+}
+
+#[derive(Debug, Clone)]
+struct IndexedFile {
+    size: u64,
+    digest: BlobDigest,
+    /// lower case
+    name: String,
+    /// lower case words of the content
+    words: BTreeSet<String>,
+}
+
+impl IndexedFile {
+    fn matches(&self, term: &SearchTerm) -> bool {
+        match term {
+            SearchTerm::Word(word) => {
+                self.name.contains(word.as_str()) || self.words.contains(word)
+            }
+            SearchTerm::NameContains(part) => self.name.contains(part.as_str()),
+            SearchTerm::Extension(extension) => self
+                .name
+                .rsplit_once('.')
+                .is_some_and(|(_stem, found)| found == extension),
+            SearchTerm::MinimumSize(minimum) => self.size >= *minimum,
+            SearchTerm::MaximumSize(maximum) => self.size <= *maximum,
+        }
+    }
+}
+
+fn split_into_words(text: &str) -> BTreeSet<String> {
+    text.split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Files up to this size are stored in a single tree, so their content can be indexed without much effort.
+pub const MAX_INDEXED_CONTENT_SIZE: u64 = TREE_BLOB_MAX_LENGTH as u64;
+
+async fn load_words(
+    storage: &(dyn LoadStoreTree + Send + Sync),
+    digest: &BlobDigest,
+    size: u64,
+) -> BTreeSet<String> {
+    if size > MAX_INDEXED_CONTENT_SIZE {
+        return BTreeSet::new();
+    }
+    let loaded = match storage.load_tree(digest).await {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            warn!("Could not load file {} for indexing: {}", digest, error);
+            return BTreeSet::new();
+        }
+    };
+    let hashed = match loaded.hash() {
+        Some(hashed) => hashed,
+        None => {
+            warn!("Hash mismatch while loading file {} for indexing", digest);
+            return BTreeSet::new();
+        }
+    };
+    match std::str::from_utf8(hashed.tree().blob().as_slice()) {
+        Ok(text) => split_into_words(text),
+        Err(_) => {
+            debug!("Not indexing the content of binary file {}", digest);
+            BTreeSet::new()
+        }
+    }
+}
+
+/// Maps the names, sizes and content of all files in a tree to their paths. The index follows the saved versions of
+/// the root and only looks at the subtrees that changed since the last update.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    snapshot: Option<Arc<DirectorySnapshot>>,
+    root_digest: Option<BlobDigest>,
+    files: BTreeMap<NormalizedPath, IndexedFile>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn update(
+        &mut self,
+        storage: &(dyn LoadStoreTree + Send + Sync),
+        root_digest: &BlobDigest,
+    ) -> Result<()> {
+        if self.root_digest.as_ref() == Some(root_digest) {
+            return Ok(());
+        }
+        let current = load_subtree(
+            storage,
+            *root_digest,
+            NormalizedPath::root(),
+            self.snapshot.clone(),
+        )
+        .await?;
+        let changes = compare_snapshots(
+            self.snapshot.as_deref(),
+            current.as_deref(),
+            &NormalizedPath::root(),
+        );
+        info!(
+            "Updating the search index with {} changes for root {}",
+            changes.len(),
+            root_digest
+        );
+        for change in changes {
+            self.apply(storage, change).await;
+        }
+        self.snapshot = current;
+        self.root_digest = Some(*root_digest);
+        Ok(())
+    }
+
+    async fn apply(&mut self, storage: &(dyn LoadStoreTree + Send + Sync), change: TreeChange) {
+        match (change.kind, change.entry_kind) {
+            (TreeChangeKind::Created | TreeChangeKind::Written, DirectoryEntryKind::File(size)) => {
+                let digest = change
+                    .digest
+                    .expect("created and written entries have a digest");
+                let name = match change.path.file_name() {
+                    Some(name) => name.as_str().to_lowercase(),
+                    None => return,
+                };
+                let words = load_words(storage, &digest, size).await;
+                self.files.insert(
+                    change.path,
+                    IndexedFile {
+                        size,
+                        digest,
+                        name,
+                        words,
+                    },
+                );
+            }
+            // the files in a new directory are reported separately
+            (TreeChangeKind::Created | TreeChangeKind::Written, DirectoryEntryKind::Directory) => {}
+            (TreeChangeKind::Renamed { from }, _) => {
+                let moved: Vec<NormalizedPath> = self
+                    .files
+                    .range(from.clone()..)
+                    .take_while(|(path, _file)| path.starts_with(&from))
+                    .map(|(path, _file)| path.clone())
+                    .collect();
+                for old_path in moved {
+                    let mut file = self.files.remove(&old_path).unwrap();
+                    let new_path = old_path
+                        .replace_prefix(&from, &change.path)
+                        .expect("the path starts with the prefix");
+                    if let Some(name) = new_path.file_name() {
+                        file.name = name.as_str().to_lowercase();
+                    }
+                    self.files.insert(new_path, file);
+                }
+            }
+            (TreeChangeKind::Removed, _) => {
+                self.files
+                    .retain(|path, _file| !path.starts_with(&change.path));
+            }
+        }
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> Vec<SearchResult> {
+        self.files
+            .iter()
+            .filter(|(_path, file)| query.terms.iter().all(|term| file.matches(term)))
+            .map(|(path, file)| SearchResult {
+                path: path.clone(),
+                size: file.size,
+                digest: file.digest,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{
+    test_helpers::{create_editor, path, save_after_change, write_file},
+    NormalizedPath, SearchQuery, SearchQueryError, TreeEditor,
+};
+use pretty_assertions::assert_eq;
+
+async fn search(editor: &TreeEditor, query: &str) -> Vec<NormalizedPath> {
+    editor
+        .search(&SearchQuery::parse(query).unwrap())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|result| result.path)
+        .collect()
+}
+
+#[test_log::test]
+fn test_parse_search_query() {
+    assert_eq!(Err(SearchQueryError::Empty), SearchQuery::parse(" \t"));
+    assert_eq!(
+        Err(SearchQueryError::InvalidSize("ten".to_string())),
+        SearchQuery::parse("minsize=ten")
+    );
+    assert_eq!(
+        SearchQuery::parse("ext=txt"),
+        SearchQuery::parse("  EXT=.TXT ")
+    );
+    assert_ne!(SearchQuery::parse("name=a"), SearchQuery::parse("a"));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_search_names_sizes_and_content() {
+    let (root, editor) = create_editor().await;
+    let mut root_status = root.watch().await;
+    editor.create_directory(path("docs")).await.unwrap();
+    write_file(&editor, "docs/Report.txt", b"Quarterly numbers, all good.").await;
+    write_file(&editor, "docs/notes.md", b"numbers\nlater").await;
+    write_file(&editor, "image.png", &[0xff, 0xfe, 0x00, 0x80]).await;
+    save_after_change(&root, &mut root_status).await;
+
+    assert_eq!(
+        vec![path("docs/Report.txt")],
+        search(&editor, "report").await
+    );
+    assert_eq!(
+        vec![path("docs/Report.txt")],
+        search(&editor, "ext=TXT").await
+    );
+    assert_eq!(
+        vec![path("docs/Report.txt"), path("docs/notes.md")],
+        search(&editor, "numbers").await
+    );
+    assert_eq!(
+        vec![path("docs/notes.md")],
+        search(&editor, "numbers maxsize=13").await
+    );
+    assert_eq!(vec![path("image.png")], search(&editor, "name=.png").await);
+    assert_eq!(
+        vec![path("image.png")],
+        search(&editor, "minsize=4 maxsize=4").await
+    );
+    // parts of words in the content don't match
+    assert_eq!(
+        Vec::<NormalizedPath>::new(),
+        search(&editor, "quarter").await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_search_index_follows_changes() {
+    let (root, editor) = create_editor().await;
+    let mut root_status = root.watch().await;
+    editor.create_directory(path("a")).await.unwrap();
+    write_file(&editor, "a/one.txt", b"alpha").await;
+    write_file(&editor, "a/two.txt", b"beta").await;
+    save_after_change(&root, &mut root_status).await;
+    assert_eq!(vec![path("a/one.txt")], search(&editor, "alpha").await);
+
+    // unsaved changes are not visible yet
+    write_file(&editor, "a/one.txt", b"gamma").await;
+    assert_eq!(vec![path("a/one.txt")], search(&editor, "alpha").await);
+    save_after_change(&root, &mut root_status).await;
+    assert_eq!(Vec::<NormalizedPath>::new(), search(&editor, "alpha").await);
+    assert_eq!(vec![path("a/one.txt")], search(&editor, "gamma").await);
+
+    editor.rename(path("a"), path("b")).await.unwrap();
+    save_after_change(&root, &mut root_status).await;
+    assert_eq!(
+        vec![path("b/one.txt"), path("b/two.txt")],
+        search(&editor, "ext=txt").await
+    );
+
+    editor.remove(path("b/two.txt")).await.unwrap();
+    save_after_change(&root, &mut root_status).await;
+    assert_eq!(vec![path("b/one.txt")], search(&editor, "ext=txt").await);
+
+    editor.remove(path("b")).await.unwrap();
+    save_after_change(&root, &mut root_status).await;
+    assert_eq!(
+        Vec::<NormalizedPath>::new(),
+        search(&editor, "ext=txt").await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_search_ignores_content_of_large_files() {
+    let (root, editor) = create_editor().await;
+    let mut root_status = root.watch().await;
+    let mut content = b"needle ".to_vec();
+    content.resize(
+        crate::search_index::MAX_INDEXED_CONTENT_SIZE as usize + 1,
+        b'x',
+    );
+    write_file(&editor, "large.txt", &content).await;
+    save_after_change(&root, &mut root_status).await;
+    assert_eq!(
+        Vec::<NormalizedPath>::new(),
+        search(&editor, "needle").await
+    );
+    assert_eq!(vec![path("large.txt")], search(&editor, "large").await);
+}
@@ -5,32 +5,70 @@ use astraea::{
         TREE_MAX_CHILDREN,
     },
 };
-use dogbox_tree::serialization::{DeserializationError, SegmentedBlob};
-use std::sync::Arc;
+use dogbox_tree::serialization::{DeserializationError, SegmentedBlob, SegmentedBlobHoles};
+use std::{pin::Pin, sync::Arc};
+
+/// A part of a segmented blob. Every segment except the last one is [TREE_BLOB_MAX_LENGTH] bytes long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    Stored(BlobDigest),
+    /// [TREE_BLOB_MAX_LENGTH] zeros that don't have to be stored
+    Hole,
+}
+
+/// Full blocks of zeros are stored as holes, so that a file always gets the same digest no matter how the zeros were
+/// written.
+pub fn is_hole(content: &[u8]) -> bool {
+    content.len() == TREE_BLOB_MAX_LENGTH && content.iter().all(|byte| *byte == 0)
+}
 
 pub async fn save_segmented_blob(
-    segments: &[BlobDigest],
+    segments: &[Segment],
     total_size_in_bytes: u64,
     max_children_per_tree: usize,
     storage: &(dyn StoreTree + Send + Sync),
 ) -> std::result::Result<BlobDigest, StoreError> {
-    save_segmented_blob_impl(
+    if !segments.is_empty() && segments.iter().all(|segment| *segment == Segment::Hole) {
+        // A tree without any children would look like the content of a small file, so we store one block of zeros.
+        let zeros = storage
+            .store_tree(&HashedTree::from(Arc::new(Tree::new(
+                TreeBlob::try_from(bytes::Bytes::from(vec![0u8; TREE_BLOB_MAX_LENGTH])).unwrap(),
+                TreeChildren::empty(),
+            ))))
+            .await?;
+        let mut with_zeros = segments.to_vec();
+        with_zeros[0] = Segment::Stored(zeros);
+        return Box::pin(save_segmented_blob(
+            &with_zeros,
+            total_size_in_bytes,
+            max_children_per_tree,
+            storage,
+        ))
+        .await;
+    }
+    match save_segmented_blob_impl(
         segments,
         TREE_BLOB_MAX_LENGTH as u64,
         total_size_in_bytes,
         max_children_per_tree,
         storage,
     )
-    .await
+    .await?
+    {
+        Segment::Stored(digest) => Ok(digest),
+        Segment::Hole => {
+            unreachable!("At least one segment is stored, so the root is stored, too.")
+        }
+    }
 }
 
 async fn save_segmented_blob_impl(
-    segments: &[BlobDigest],
+    segments: &[Segment],
     segment_capacity: u64,
     total_size_in_bytes: u64,
     max_children_per_tree: usize,
     storage: &(dyn StoreTree + Send + Sync),
-) -> std::result::Result<BlobDigest, StoreError> {
+) -> std::result::Result<Segment, StoreError> {
     assert!(max_children_per_tree >= 2);
     assert!(max_children_per_tree <= TREE_MAX_CHILDREN);
     match segments.len() {
@@ -71,58 +109,137 @@ async fn save_segmented_blob_impl(
             let info = SegmentedBlob {
                 size_in_bytes: total_size_in_bytes,
             };
-            let children = TreeChildren::try_from(segments.to_vec())
-                .expect("The child count was checked above.");
+            let mut references = Vec::new();
+            let mut holes = Vec::new();
+            for (position, segment) in segments.iter().enumerate() {
+                match segment {
+                    Segment::Stored(digest) => references.push(*digest),
+                    Segment::Hole => holes.push(position as u32),
+                }
+            }
+            let mut blob = postcard::to_allocvec(&info).unwrap();
+            if !holes.is_empty() {
+                blob.extend(
+                    postcard::to_allocvec(&SegmentedBlobHoles { positions: holes }).unwrap(),
+                );
+            }
+            let children =
+                TreeChildren::try_from(references).expect("The child count was checked above.");
             let tree = Tree::new(
-                TreeBlob::try_from(bytes::Bytes::from(postcard::to_allocvec(&info).unwrap()))
-                    .unwrap(),
+                TreeBlob::try_from(bytes::Bytes::from(blob)).unwrap(),
                 children,
             );
             let digest = storage
                 .store_tree(&HashedTree::from(Arc::new(tree)))
                 .await?;
-            Ok(digest)
+            Ok(Segment::Stored(digest))
         }
     }
 }
 
-pub async fn load_segmented_blob(
+async fn load_hashed_tree(
     digest: &BlobDigest,
     storage: &(dyn LoadTree + Send + Sync),
-) -> std::result::Result<(Vec<BlobDigest>, u64), DeserializationError> {
+) -> std::result::Result<HashedTree, DeserializationError> {
     let delayed_tree = match storage.load_tree(digest).await {
         Ok(loaded) => loaded,
         Err(error) => return Err(DeserializationError::Load(error)),
     };
-    let hashed_tree = match delayed_tree.hash() {
-        Some(hashed) => hashed,
-        None => return Err(DeserializationError::TreeHashMismatch(*digest)),
-    };
+    match delayed_tree.hash() {
+        Some(hashed) => Ok(hashed),
+        None => Err(DeserializationError::TreeHashMismatch(*digest)),
+    }
+}
+
+pub async fn load_segmented_blob(
+    digest: &BlobDigest,
+    storage: &(dyn LoadTree + Send + Sync),
+) -> std::result::Result<(Vec<Segment>, u64), DeserializationError> {
+    let hashed_tree = load_hashed_tree(digest, storage).await?;
     let tree = hashed_tree.tree().as_ref();
     if tree.children().references().is_empty() {
-        Ok((vec![*digest], tree.blob().as_slice().len() as u64))
+        Ok((
+            vec![Segment::Stored(*digest)],
+            tree.blob().as_slice().len() as u64,
+        ))
     } else {
-        let info: SegmentedBlob =
-            postcard::from_bytes(tree.blob().as_slice()).map_err(DeserializationError::Postcard)?;
-        let capacity = (tree.children().references().len() as u64) * (TREE_BLOB_MAX_LENGTH as u64);
+        load_segments(tree, storage).await
+    }
+}
+
+type LoadSegmentsFuture<'a> = Pin<
+    Box<
+        dyn std::future::Future<
+                Output = std::result::Result<(Vec<Segment>, u64), DeserializationError>,
+            > + Send
+            + 'a,
+    >,
+>;
+
+/// Loads a tree that is known to be part of a segmented blob. Unlike the root, such a tree may have no children at all
+/// if all of its segments are holes.
+fn load_segments<'a>(
+    tree: &'a Tree,
+    storage: &'a (dyn LoadTree + Send + Sync),
+) -> LoadSegmentsFuture<'a> {
+    Box::pin(async move {
+        let (info, rest): (SegmentedBlob, &[u8]) =
+            postcard::take_from_bytes(tree.blob().as_slice())
+                .map_err(DeserializationError::Postcard)?;
+        let holes = if rest.is_empty() {
+            Vec::new()
+        } else {
+            postcard::from_bytes::<SegmentedBlobHoles>(rest)
+                .map_err(DeserializationError::Postcard)?
+                .positions
+        };
+        let references = tree.children().references();
+        let segment_count = references.len() + holes.len();
+        let mut segments = Vec::with_capacity(segment_count);
+        let mut next_references = references.iter();
+        let mut next_holes = holes.iter().peekable();
+        for position in 0..segment_count {
+            if next_holes.peek() == Some(&&(position as u32)) {
+                next_holes.next();
+                segments.push(Segment::Hole);
+            } else {
+                match next_references.next() {
+                    Some(reference) => segments.push(Segment::Stored(*reference)),
+                    None => {
+                        return Err(DeserializationError::Inconsistency(
+                            "Segmented blob has hole positions that are out of order.".to_string(),
+                        ))
+                    }
+                }
+            }
+        }
+        let capacity = (segment_count as u64) * (TREE_BLOB_MAX_LENGTH as u64);
         if info.size_in_bytes <= capacity {
-            let segments = tree.children().references().to_vec();
             return Ok((segments, info.size_in_bytes));
         }
         let mut remaining_size = info.size_in_bytes;
         let mut all_segments = Vec::new();
-        for segment_digest in tree.children().references().iter() {
+        for segment in segments.iter() {
             if remaining_size == 0 {
                 return Err(DeserializationError::Inconsistency(
                     "Segmented blob has more segments than needed for the total size.".to_string(),
                 ));
             }
             if remaining_size <= TREE_BLOB_MAX_LENGTH as u64 {
-                all_segments.push(*segment_digest);
+                all_segments.push(*segment);
                 remaining_size = 0;
             } else {
+                let segment_digest = match segment {
+                    Segment::Stored(digest) => digest,
+                    Segment::Hole => {
+                        return Err(DeserializationError::Inconsistency(
+                            "Segmented blob has a hole where a subtree was expected.".to_string(),
+                        ))
+                    }
+                };
+                let subtree = load_hashed_tree(segment_digest, storage).await?;
                 let (mut loaded_segments, segment_size) =
-                    Box::pin(load_segmented_blob(segment_digest, storage)).await?;
+                    load_segments(subtree.tree().as_ref(), storage).await?;
                 all_segments.append(&mut loaded_segments);
                 remaining_size = match remaining_size.checked_sub(segment_size) {
                     Some(size) => size,
@@ -141,5 +258,5 @@ pub async fn load_segmented_blob(
             ));
         }
         Ok((all_segments, info.size_in_bytes))
-    }
+    })
 }
@@ -2,7 +2,7 @@ use dogbox_tree::serialization::SegmentedBlob;
 use pretty_assertions::assert_eq;
 use std::sync::Arc;
 
-use crate::segmented_blob::{load_segmented_blob, save_segmented_blob};
+use crate::segmented_blob::{load_segmented_blob, save_segmented_blob, Segment};
 use astraea::{
     storage::{InMemoryTreeStorage, LoadTree, StoreTree},
     tree::{BlobDigest, HashedTree, Tree, TreeBlob, TreeChildren, TREE_BLOB_MAX_LENGTH},
@@ -30,7 +30,7 @@ async fn test_save_segmented_blob_1() {
         .await
         .unwrap();
     assert_eq!(1, storage.number_of_trees().await);
-    let original_segments = [segment];
+    let original_segments = [Segment::Stored(segment)];
     let digest = save_segmented_blob(
         &original_segments,
         total_size as u64,
@@ -58,7 +58,7 @@ async fn test_save_segmented_blob_2() {
             "12e712cf05e19dcd622c502a3167027f9ce838094c82cef0fbf853c9b5fe2e22ce1af698fb306feb586019ddadc923f5b8f70a8c004b9f84b451be453930be14"
         )
         .unwrap();
-    let original_segments = [segment_0, segment_1];
+    let original_segments = [Segment::Stored(segment_0), Segment::Stored(segment_1)];
     let total_size = TREE_BLOB_MAX_LENGTH as u64 + 1;
     let digest = save_segmented_blob(
         &original_segments,
@@ -87,7 +87,7 @@ async fn test_save_segmented_blob_5() {
         )
         .unwrap();
     let original_segments = (0..max_children_per_tree)
-        .map(|_| segment)
+        .map(|_| Segment::Stored(segment))
         .collect::<Vec<_>>();
     let total_size = (TREE_BLOB_MAX_LENGTH as u64) * (original_segments.len() as u64);
     let digest = save_segmented_blob(
@@ -117,7 +117,9 @@ async fn test_save_segmented_blob_one_indirection() {
             "77e712cf05e19dcd622c502a3167027f9ce838094c82cef0fbf853c9b5fe2e22ce1af698fb306feb586019ddadc923f5b8f70a8c004b9f84b451be453930be14"
         )
         .unwrap();
-    let original_segments = (0..number_of_segments).map(|_| segment).collect::<Vec<_>>();
+    let original_segments = (0..number_of_segments)
+        .map(|_| Segment::Stored(segment))
+        .collect::<Vec<_>>();
     let total_size = (TREE_BLOB_MAX_LENGTH as u64) * (original_segments.len() as u64);
     let digest = save_segmented_blob(
         &original_segments,
@@ -195,7 +197,9 @@ async fn test_save_segmented_blob_two_indirections() {
             "77e712cf05e19dcd622c502a3167027f9ce838094c82cef0fbf853c9b5fe2e22ce1af698fb306feb586019ddadc923f5b8f70a8c004b9f84b451be453930be14"
         )
         .unwrap();
-    let original_segments = (0..number_of_segments).map(|_| segment).collect::<Vec<_>>();
+    let original_segments = (0..number_of_segments)
+        .map(|_| Segment::Stored(segment))
+        .collect::<Vec<_>>();
     let total_size = (TREE_BLOB_MAX_LENGTH as u64) * (original_segments.len() as u64);
     let digest = save_segmented_blob(
         &original_segments,
@@ -238,3 +242,90 @@ async fn test_save_segmented_blob_two_indirections() {
     assert_eq!(&original_segments, &loaded_segments[..]);
     assert_eq!({ total_size }, loaded_size);
 }
+
+#[test_log::test(tokio::test)]
+async fn test_save_segmented_blob_with_holes() {
+    let storage = InMemoryTreeStorage::empty();
+    let max_children_per_tree = 5;
+    let segment = BlobDigest::parse_hex_string(
+            "77e712cf05e19dcd622c502a3167027f9ce838094c82cef0fbf853c9b5fe2e22ce1af698fb306feb586019ddadc923f5b8f70a8c004b9f84b451be453930be14"
+        )
+        .unwrap();
+    let original_segments = [
+        Segment::Hole,
+        Segment::Stored(segment),
+        Segment::Hole,
+        Segment::Hole,
+        Segment::Stored(segment),
+    ];
+    let total_size = (TREE_BLOB_MAX_LENGTH as u64) * 4 + 1;
+    let digest = save_segmented_blob(
+        &original_segments,
+        total_size,
+        max_children_per_tree,
+        &storage,
+    )
+    .await
+    .unwrap();
+    assert_eq!(1, storage.number_of_trees().await);
+    let root = storage.load_tree(&digest).await.unwrap().hash().unwrap();
+    assert_eq!(&[segment, segment], root.tree().children().references());
+    let (loaded_segments, loaded_size) = load_segmented_blob(&digest, &storage).await.unwrap();
+    assert_eq!(&original_segments, &loaded_segments[..]);
+    assert_eq!({ total_size }, loaded_size);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_save_segmented_blob_only_holes() {
+    let storage = InMemoryTreeStorage::empty();
+    let max_children_per_tree = 5;
+    let original_segments = [Segment::Hole, Segment::Hole, Segment::Hole];
+    let total_size = (TREE_BLOB_MAX_LENGTH as u64) * 3;
+    let digest = save_segmented_blob(
+        &original_segments,
+        total_size,
+        max_children_per_tree,
+        &storage,
+    )
+    .await
+    .unwrap();
+    // the root needs at least one child, so the first block of zeros is stored
+    assert_eq!(2, storage.number_of_trees().await);
+    let (loaded_segments, loaded_size) = load_segmented_blob(&digest, &storage).await.unwrap();
+    assert!(matches!(loaded_segments[0], Segment::Stored(_)));
+    assert_eq!(&original_segments[1..], &loaded_segments[1..]);
+    assert_eq!({ total_size }, loaded_size);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_save_segmented_blob_two_indirections_with_holes() {
+    let max_children_per_tree = 5;
+    let number_of_segments = (max_children_per_tree * max_children_per_tree) + 1;
+    let storage = InMemoryTreeStorage::empty();
+    let segment = BlobDigest::parse_hex_string(
+            "77e712cf05e19dcd622c502a3167027f9ce838094c82cef0fbf853c9b5fe2e22ce1af698fb306feb586019ddadc923f5b8f70a8c004b9f84b451be453930be14"
+        )
+        .unwrap();
+    // only the first and the last segment contain data
+    let original_segments = (0..number_of_segments)
+        .map(|index| {
+            if index == 0 || index == number_of_segments - 1 {
+                Segment::Stored(segment)
+            } else {
+                Segment::Hole
+            }
+        })
+        .collect::<Vec<_>>();
+    let total_size = (TREE_BLOB_MAX_LENGTH as u64) * (original_segments.len() as u64);
+    let digest = save_segmented_blob(
+        &original_segments,
+        total_size,
+        max_children_per_tree,
+        &storage,
+    )
+    .await
+    .unwrap();
+    let (loaded_segments, loaded_size) = load_segmented_blob(&digest, &storage).await.unwrap();
+    assert_eq!(&original_segments, &loaded_segments[..]);
+    assert_eq!({ total_size }, loaded_size);
+}
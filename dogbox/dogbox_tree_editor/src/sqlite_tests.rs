@@ -1,6 +1,7 @@
 use crate::{
     sqlite::{register_vfs, PagesVfs, SyncDirectoryFunction},
-    CacheDropStats, FileCreationMode, NormalizedPath, OpenDirectory, OpenFileStats, TreeEditor,
+    CacheDropStats, FileCreationMode, NormalizedPath, OpenDirectory, OpenFileStats, PrefetchStats,
+    TreeEditor,
 };
 use astraea::{
     storage::{
@@ -348,6 +349,7 @@ async fn test_open_database() {
                 files_open_for_reading_count: 0,
                 files_open_for_writing_count: 0,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -413,6 +415,7 @@ async fn test_open_invalid_file_name() {
                 files_open_for_reading_count: 0,
                 files_open_for_writing_count: 0,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -476,6 +479,7 @@ async fn test_open_failure() {
                 files_open_for_reading_count: 0,
                 files_open_for_writing_count: 0,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -542,6 +546,7 @@ async fn test_open_no_create() {
                 files_open_for_reading_count: 0,
                 files_open_for_writing_count: 0,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -608,6 +613,7 @@ async fn test_reopen_database() {
                 files_open_for_reading_count: 1,
                 files_open_for_writing_count: 1,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -764,6 +770,7 @@ async fn test_sync_directory() {
                 files_open_for_reading_count: 1,
                 files_open_for_writing_count: 1,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
         // We are not sure why, but we have to explicitly save again after closing the database connection.
@@ -925,6 +932,7 @@ async fn test_temp_table() {
                 files_open_for_reading_count: 0,
                 files_open_for_writing_count: 0,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -997,6 +1005,7 @@ async fn test_open_database_with_wal() {
                 files_open_for_reading_count: 1,
                 files_open_for_writing_count: 1,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -1114,6 +1123,7 @@ async fn test_change_page_size() {
                 files_open_for_reading_count: 0,
                 files_open_for_writing_count: 0,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -1189,6 +1199,7 @@ async fn test_storage_read_error() {
                 files_open_for_reading_count: 0,
                 files_open_for_writing_count: 0,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -1326,6 +1337,7 @@ async fn test_storage_write_error_in_sync() {
                 files_open_for_reading_count: 0,
                 files_open_for_writing_count: 0,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -1411,6 +1423,7 @@ async fn test_storage_write_error_in_write_all_at() {
                 files_open_for_reading_count: 1,
                 files_open_for_writing_count: 1,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
@@ -1481,6 +1494,7 @@ async fn test_deleting_open_database_file() {
                 files_open_for_reading_count: 0,
                 files_open_for_writing_count: 0,
                 files_unflushed_count: 0,
+                prefetch: PrefetchStats::default(),
             }
         );
     }
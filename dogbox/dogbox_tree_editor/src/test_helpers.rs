@@ -0,0 +1,76 @@
+use crate::{FileCreationMode, NormalizedPath, OpenDirectory, OpenDirectoryStatus, TreeEditor};
+use astraea::storage::{InMemoryTreeStorage, SQLiteStorage};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+pub(crate) fn test_clock() -> SystemTime {
+    SystemTime::UNIX_EPOCH
+}
+
+pub(crate) fn time(seconds: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+}
+
+pub(crate) fn path(input: &str) -> NormalizedPath {
+    NormalizedPath::try_from(relative_path::RelativePath::new(input)).unwrap()
+}
+
+pub(crate) fn create_storage() -> Arc<SQLiteStorage> {
+    let connection = rusqlite::Connection::open_in_memory().unwrap();
+    SQLiteStorage::create_schema(&connection).unwrap();
+    Arc::new(SQLiteStorage::from(connection).unwrap())
+}
+
+/// An empty tree in memory without persistence.
+pub(crate) async fn create_editor() -> (Arc<OpenDirectory>, TreeEditor) {
+    let storage = Arc::new(InMemoryTreeStorage::empty());
+    let root = Arc::new(
+        OpenDirectory::create_directory(
+            std::path::PathBuf::from("/"),
+            storage,
+            Arc::new(test_clock),
+            1,
+        )
+        .await
+        .unwrap(),
+    );
+    let editor = TreeEditor::new(root.clone(), None);
+    (root, editor)
+}
+
+/// Creates or overwrites the file like a client would, including the record in the journal if there is one.
+pub(crate) async fn write_file(editor: &TreeEditor, file_path: &str, content: &[u8]) {
+    let opened = editor
+        .open_file(path(file_path), FileCreationMode::create())
+        .await
+        .unwrap();
+    let write_permission = opened.get_write_permission();
+    editor
+        .write_file(
+            &path(file_path),
+            &opened,
+            &write_permission,
+            0,
+            bytes::Bytes::copy_from_slice(content),
+        )
+        .await
+        .unwrap();
+    drop(write_permission);
+    opened.notify_dropped_write_permission();
+    opened.flush().await.unwrap();
+}
+
+/// Changes in subdirectories reach the root asynchronously, so we wait for the root to notice them before saving.
+pub(crate) async fn save_after_change(
+    root: &OpenDirectory,
+    root_status: &mut tokio::sync::watch::Receiver<OpenDirectoryStatus>,
+) {
+    root_status
+        .wait_for(|status| !status.digest.is_digest_up_to_date)
+        .await
+        .unwrap();
+    let status = root.request_save().await.unwrap();
+    assert!(status.digest.is_digest_up_to_date);
+}
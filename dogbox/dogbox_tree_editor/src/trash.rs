@@ -0,0 +1,163 @@
+use crate::{DeadPropertyChange, Error, NormalizedPath, Result};
+use dogbox_tree::serialization::{DeadProperties, DeadPropertyName, DeadPropertyValue, FileName};
+use std::time::{Duration, SystemTime};
+
+/// Deleted entries are moved into this directory below the root of the tree.
+pub const TRASH_DIRECTORY_NAME: &str = ".trash";
+
+const TRASH_PROPERTY_NAMESPACE: &str = "urn:x-dogbox:trash";
+const TRASH_PROPERTY_PREFIX: &str = "trash";
+const ORIGINAL_PATH_PROPERTY: &str = "original-path";
+const DELETED_AT_PROPERTY: &str = "deleted-at";
+
+pub fn trash_directory_path() -> NormalizedPath {
+    NormalizedPath::root().join(FileName::try_from(TRASH_DIRECTORY_NAME).unwrap())
+}
+
+/// Decides when [crate::TreeEditor::expire_trash] deletes entries from the trash permanently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrashRetention {
+    /// Entries are kept forever without a maximum age.
+    pub max_age: Option<Duration>,
+}
+
+impl TrashRetention {
+    pub fn is_expired(&self, entry: &TrashEntry, now: SystemTime) -> bool {
+        match self.max_age {
+            Some(max_age) => match now.duration_since(entry.deleted_at) {
+                Ok(age) => age >= max_age,
+                // the clock went backwards
+                Err(_) => false,
+            },
+            None => false,
+        }
+    }
+}
+
+impl Default for TrashRetention {
+    fn default() -> Self {
+        Self {
+            max_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+        }
+    }
+}
+
+/// An entry of the trash directory. The original path and the deletion time are stored as dead properties of the entry,
+/// so WebDAV clients can see them, too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashEntry {
+    /// The name in the trash directory, which can differ from the original name if that was taken already.
+    pub name: FileName,
+    pub original_path: NormalizedPath,
+    pub deleted_at: SystemTime,
+}
+
+impl TrashEntry {
+    pub fn path(&self) -> NormalizedPath {
+        trash_directory_path().join(self.name.clone())
+    }
+
+    /// Returns [None] for entries that were put into the trash directory without deleting them.
+    pub fn from_properties(name: FileName, properties: &DeadProperties) -> Option<TrashEntry> {
+        let read_property = |property: &str| {
+            properties
+                .get(&property_name(property))
+                .and_then(|value| value.xml.as_ref())
+                .and_then(|xml| std::str::from_utf8(xml).ok())
+                .and_then(element_text)
+        };
+        let original_path = NormalizedPath::try_from(relative_path::RelativePath::new(
+            &read_property(ORIGINAL_PATH_PROPERTY)?,
+        ))
+        .ok()?;
+        let deleted_at = SystemTime::UNIX_EPOCH
+            + Duration::from_secs(read_property(DELETED_AT_PROPERTY)?.parse().ok()?);
+        Some(TrashEntry {
+            name,
+            original_path,
+            deleted_at,
+        })
+    }
+
+    pub(crate) fn set_properties(&self) -> Vec<DeadPropertyChange> {
+        let original_path = self
+            .original_path
+            .components()
+            .map(|component| component.as_str())
+            .collect::<Vec<_>>()
+            .join("/");
+        let deleted_at = self
+            .deleted_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_secs())
+            .unwrap_or(0);
+        vec![
+            DeadPropertyChange::Set(
+                property_name(ORIGINAL_PATH_PROPERTY),
+                property_value(ORIGINAL_PATH_PROPERTY, &original_path),
+            ),
+            DeadPropertyChange::Set(
+                property_name(DELETED_AT_PROPERTY),
+                property_value(DELETED_AT_PROPERTY, &deleted_at.to_string()),
+            ),
+        ]
+    }
+
+    pub(crate) fn remove_properties() -> Vec<DeadPropertyChange> {
+        vec![
+            DeadPropertyChange::Remove(property_name(ORIGINAL_PATH_PROPERTY)),
+            DeadPropertyChange::Remove(property_name(DELETED_AT_PROPERTY)),
+        ]
+    }
+}
+
+/// Finds a name for `original_name` in the trash: the original name itself for the first attempt, then `name (1)`,
+/// `name (2)` and so on.
+pub(crate) fn trash_name(original_name: &FileName, attempt: usize) -> Result<FileName> {
+    if attempt == 0 {
+        return Ok(original_name.clone());
+    }
+    let name = format!("{} ({})", original_name.as_str(), attempt);
+    FileName::try_from(name.as_str()).map_err(|error| {
+        Error::InvalidArgument(format!("Cannot name {name} in the trash: {error}"))
+    })
+}
+
+fn property_name(name: &str) -> DeadPropertyName {
+    DeadPropertyName::new(Some(TRASH_PROPERTY_NAMESPACE.to_string()), name.to_string())
+}
+
+/// WebDAV expects the whole XML element of a property, not only its content.
+fn property_value(name: &str, text: &str) -> DeadPropertyValue {
+    let xml = format!(
+        "<{TRASH_PROPERTY_PREFIX}:{name} xmlns:{TRASH_PROPERTY_PREFIX}=\"{TRASH_PROPERTY_NAMESPACE}\">{}</{TRASH_PROPERTY_PREFIX}:{name}>",
+        escape_xml(text)
+    );
+    DeadPropertyValue::new(
+        Some(TRASH_PROPERTY_PREFIX.to_string()),
+        Some(xml.into_bytes()),
+    )
+}
+
+/// Only supports elements that contain nothing but text like the ones from [property_value].
+fn element_text(xml: &str) -> Option<String> {
+    let start = xml.find('>')? + 1;
+    let end = xml.rfind("</")?;
+    let content = xml.get(start..end)?;
+    if content.contains('<') {
+        return None;
+    }
+    Some(unescape_xml(content))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
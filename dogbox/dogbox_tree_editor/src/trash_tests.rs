@@ -0,0 +1,342 @@
+use crate::{
+    test_helpers::{create_storage, path, time, write_file},
+    Error, FileCreationMode, OpenDirectory, TrashEntry, TrashRetention, TreeEditor, WallClock,
+    TRASH_DIRECTORY_NAME,
+};
+use dogbox_tree::serialization::{DeadProperties, FileName};
+use pretty_assertions::assert_eq;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+fn name(name: &str) -> FileName {
+    FileName::try_from(name).unwrap()
+}
+
+async fn create_editor(clock: WallClock, trash_retention: Option<TrashRetention>) -> TreeEditor {
+    let root =
+        OpenDirectory::create_directory(std::path::PathBuf::from("/"), create_storage(), clock, 1)
+            .await
+            .unwrap();
+    let editor = TreeEditor::new(Arc::new(root), None);
+    match trash_retention {
+        Some(retention) => editor.with_trash(retention),
+        None => editor,
+    }
+}
+
+async fn read_file(editor: &TreeEditor, file_path: &str) -> Option<bytes::Bytes> {
+    let opened = editor
+        .open_file(path(file_path), FileCreationMode::open_existing())
+        .await
+        .ok()?;
+    Some(
+        opened
+            .read_bytes(&opened.get_read_permission(), 0, 1000)
+            .await
+            .unwrap(),
+    )
+}
+
+async fn list_names(editor: &TreeEditor, directory: &str) -> Vec<String> {
+    use futures::StreamExt;
+    editor
+        .read_directory(path(directory))
+        .await
+        .unwrap()
+        .map(|entry| entry.name.as_str().to_string())
+        .collect()
+        .await
+}
+
+#[test_log::test]
+fn test_trash_entry_properties_round_trip() {
+    let entry = TrashEntry {
+        name: name("a&b.txt"),
+        original_path: path("docs/a&b.txt"),
+        deleted_at: time(123),
+    };
+    let mut properties = DeadProperties::new();
+    for change in entry.set_properties() {
+        match change {
+            crate::DeadPropertyChange::Set(property_name, value) => {
+                properties.set(property_name, value)
+            }
+            crate::DeadPropertyChange::Remove(_) => panic!(),
+        }
+    }
+    assert_eq!(
+        Some(entry.clone()),
+        TrashEntry::from_properties(entry.name.clone(), &properties)
+    );
+    assert_eq!(
+        None,
+        TrashEntry::from_properties(entry.name, &DeadProperties::new())
+    );
+}
+
+#[test_log::test]
+fn test_trash_retention_is_expired() {
+    let entry = TrashEntry {
+        name: name("a.txt"),
+        original_path: path("a.txt"),
+        deleted_at: time(100),
+    };
+    let retention = TrashRetention {
+        max_age: Some(Duration::from_secs(60)),
+    };
+    assert!(!retention.is_expired(&entry, time(159)));
+    assert!(retention.is_expired(&entry, time(160)));
+    // the clock went backwards
+    assert!(!retention.is_expired(&entry, time(50)));
+    assert!(!TrashRetention { max_age: None }.is_expired(&entry, time(1_000_000)));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_remove_without_trash() {
+    let editor = create_editor(Arc::new(|| time(0)), None).await;
+    write_file(&editor, "a.txt", b"hello").await;
+    editor.remove(path("a.txt")).await.unwrap();
+    assert_eq!(Vec::<String>::new(), list_names(&editor, "").await);
+    assert_eq!(Vec::<TrashEntry>::new(), editor.list_trash().await.unwrap());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_remove_moves_into_trash() {
+    let editor = create_editor(Arc::new(|| time(10)), Some(TrashRetention::default())).await;
+    editor.create_directory(path("docs")).await.unwrap();
+    write_file(&editor, "docs/a.txt", b"hello").await;
+    editor.remove(path("docs/a.txt")).await.unwrap();
+    assert_eq!(Vec::<String>::new(), list_names(&editor, "docs").await);
+    assert_eq!(
+        vec![TRASH_DIRECTORY_NAME.to_string(), "docs".to_string()],
+        list_names(&editor, "").await
+    );
+    assert_eq!(
+        vec![TrashEntry {
+            name: name("a.txt"),
+            original_path: path("docs/a.txt"),
+            deleted_at: time(10),
+        }],
+        editor.list_trash().await.unwrap()
+    );
+    assert_eq!(
+        Some(bytes::Bytes::from_static(b"hello")),
+        read_file(&editor, ".trash/a.txt").await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_trash_name_is_reserved() {
+    let editor = create_editor(Arc::new(|| time(0)), Some(TrashRetention::default())).await;
+    let reserved = || Error::ReservedName(name(TRASH_DIRECTORY_NAME));
+    let trash_path = path(TRASH_DIRECTORY_NAME);
+    assert_eq!(
+        Some(reserved()),
+        editor.create_directory(trash_path.clone()).await.err()
+    );
+    assert_eq!(
+        Some(reserved()),
+        editor
+            .open_file(trash_path.clone(), FileCreationMode::create())
+            .await
+            .err()
+    );
+    write_file(&editor, "a.txt", b"hello").await;
+    assert_eq!(
+        Some(reserved()),
+        editor.rename(path("a.txt"), trash_path.clone()).await.err()
+    );
+    assert_eq!(
+        Some(reserved()),
+        editor.copy(path("a.txt"), trash_path.clone()).await.err()
+    );
+    assert_eq!(
+        Some(reserved()),
+        editor
+            .reflink(path("a.txt"), trash_path.clone())
+            .await
+            .err()
+    );
+    // only at the root
+    editor.create_directory(path("b")).await.unwrap();
+    editor
+        .create_directory(path("b").join(name(TRASH_DIRECTORY_NAME)))
+        .await
+        .unwrap();
+
+    // deleting still creates the trash
+    editor.remove(path("a.txt")).await.unwrap();
+    assert_eq!(1, editor.list_trash().await.unwrap().len());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_trash_name_is_not_reserved_without_trash() {
+    let editor = create_editor(Arc::new(|| time(0)), None).await;
+    editor
+        .create_directory(path(TRASH_DIRECTORY_NAME))
+        .await
+        .unwrap();
+}
+
+#[test_log::test(tokio::test)]
+async fn test_remove_not_found() {
+    let editor = create_editor(Arc::new(|| time(10)), Some(TrashRetention::default())).await;
+    assert_eq!(
+        Err(Error::NotFound(name("a.txt"))),
+        editor.remove(path("a.txt")).await
+    );
+    // the trash is only created when it is needed
+    assert_eq!(Vec::<String>::new(), list_names(&editor, "").await);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_remove_same_name_twice() {
+    let editor = create_editor(Arc::new(|| time(10)), Some(TrashRetention::default())).await;
+    editor.create_directory(path("docs")).await.unwrap();
+    write_file(&editor, "a.txt", b"first").await;
+    write_file(&editor, "docs/a.txt", b"second").await;
+    editor.remove(path("a.txt")).await.unwrap();
+    editor.remove(path("docs/a.txt")).await.unwrap();
+    assert_eq!(
+        vec!["a.txt".to_string(), "a.txt (1)".to_string()],
+        list_names(&editor, ".trash").await
+    );
+    assert_eq!(
+        vec![path("a.txt"), path("docs/a.txt")],
+        editor
+            .list_trash()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.original_path)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_remove_in_trash_is_permanent() {
+    let editor = create_editor(Arc::new(|| time(10)), Some(TrashRetention::default())).await;
+    write_file(&editor, "a.txt", b"hello").await;
+    editor.remove(path("a.txt")).await.unwrap();
+    editor.remove(path(".trash/a.txt")).await.unwrap();
+    assert_eq!(Vec::<TrashEntry>::new(), editor.list_trash().await.unwrap());
+    assert_eq!(Vec::<String>::new(), list_names(&editor, ".trash").await);
+
+    write_file(&editor, "b.txt", b"hello").await;
+    editor.remove_permanently(path("b.txt")).await.unwrap();
+    assert_eq!(Vec::<TrashEntry>::new(), editor.list_trash().await.unwrap());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_restore_from_trash() {
+    let editor = create_editor(Arc::new(|| time(10)), Some(TrashRetention::default())).await;
+    editor.create_directory(path("docs")).await.unwrap();
+    write_file(&editor, "docs/a.txt", b"hello").await;
+    editor.remove(path("docs/a.txt")).await.unwrap();
+    assert_eq!(
+        Ok(path("docs/a.txt")),
+        editor.restore_from_trash(&name("a.txt")).await
+    );
+    assert_eq!(
+        Some(bytes::Bytes::from_static(b"hello")),
+        read_file(&editor, "docs/a.txt").await
+    );
+    assert_eq!(
+        DeadProperties::new(),
+        editor
+            .get_dead_properties(path("docs/a.txt"))
+            .await
+            .unwrap()
+    );
+    assert_eq!(Vec::<TrashEntry>::new(), editor.list_trash().await.unwrap());
+    assert_eq!(
+        Err(Error::NotFound(name("a.txt"))),
+        editor.restore_from_trash(&name("a.txt")).await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_restore_recreates_parent_directories() {
+    let editor = create_editor(Arc::new(|| time(10)), Some(TrashRetention::default())).await;
+    editor.create_directory(path("docs")).await.unwrap();
+    editor.create_directory(path("docs/nested")).await.unwrap();
+    write_file(&editor, "docs/nested/a.txt", b"hello").await;
+    // WebDAV clients delete the content of a directory before the directory itself
+    editor.remove(path("docs/nested/a.txt")).await.unwrap();
+    editor.remove(path("docs/nested")).await.unwrap();
+    editor.remove(path("docs")).await.unwrap();
+    assert_eq!(
+        vec![TRASH_DIRECTORY_NAME.to_string()],
+        list_names(&editor, "").await
+    );
+    editor.restore_from_trash(&name("a.txt")).await.unwrap();
+    assert_eq!(
+        Some(bytes::Bytes::from_static(b"hello")),
+        read_file(&editor, "docs/nested/a.txt").await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_restore_does_not_overwrite() {
+    let editor = create_editor(Arc::new(|| time(10)), Some(TrashRetention::default())).await;
+    write_file(&editor, "a.txt", b"deleted").await;
+    editor.remove(path("a.txt")).await.unwrap();
+    write_file(&editor, "a.txt", b"new").await;
+    assert_eq!(
+        Err(Error::FileAlreadyExists(name("a.txt"))),
+        editor.restore_from_trash(&name("a.txt")).await
+    );
+    assert_eq!(
+        Some(bytes::Bytes::from_static(b"new")),
+        read_file(&editor, "a.txt").await
+    );
+    assert_eq!(1, editor.list_trash().await.unwrap().len());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_expire_trash() {
+    let seconds = Arc::new(AtomicU64::new(100));
+    let clock: WallClock = {
+        let seconds = seconds.clone();
+        Arc::new(move || time(seconds.load(Ordering::SeqCst)))
+    };
+    let editor = create_editor(
+        clock,
+        Some(TrashRetention {
+            max_age: Some(Duration::from_secs(60)),
+        }),
+    )
+    .await;
+    write_file(&editor, "a.txt", b"older").await;
+    editor.remove(path("a.txt")).await.unwrap();
+    seconds.store(130, Ordering::SeqCst);
+    write_file(&editor, "b.txt", b"newer").await;
+    editor.remove(path("b.txt")).await.unwrap();
+    assert_eq!(0, editor.expire_trash().await.unwrap());
+
+    seconds.store(160, Ordering::SeqCst);
+    assert_eq!(1, editor.expire_trash().await.unwrap());
+    assert_eq!(
+        vec![name("b.txt")],
+        editor
+            .list_trash()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["b.txt".to_string()],
+        list_names(&editor, ".trash").await
+    );
+
+    seconds.store(190, Ordering::SeqCst);
+    assert_eq!(1, editor.expire_trash().await.unwrap());
+    assert_eq!(Vec::<TrashEntry>::new(), editor.list_trash().await.unwrap());
+}
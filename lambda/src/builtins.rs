@@ -1,5 +1,153 @@
-use crate::name::NamespaceId;
+use crate::{
+    expressions::{closure_to_deep_tree, DeepExpression, EvaluationError, Expression},
+    name::NamespaceId,
+};
+use astraea::{
+    deep_tree::{DeepTree, DeepTreeChildren},
+    tree::{Tree, TreeBlob},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, sync::Arc};
 
 pub const BUILTINS_NAMESPACE: NamespaceId = NamespaceId([0; 16]);
 pub const UTF8_STRING_TYPE_NAME: &str = "utf8_string";
 pub const LAMBDA_APPLY_METHOD_NAME: &str = "apply";
+
+/// Arithmetic and comparisons of two integers. Integers are trees without children that contain an `i64` encoded with
+/// postcard. Comparisons result in a `Bool`, which is a tree with the single byte 1 or 0.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize)]
+pub enum IntegerOperation {
+    Add,
+    Subtract,
+    Multiply,
+    /// Rounds towards zero.
+    Divide,
+    /// Has the sign of the dividend.
+    Remainder,
+    Equals,
+    NotEquals,
+    LessThan,
+    LessThanOrEquals,
+    GreaterThan,
+    GreaterThanOrEquals,
+}
+
+impl IntegerOperation {
+    pub const ALL: [IntegerOperation; 11] = [
+        IntegerOperation::Add,
+        IntegerOperation::Subtract,
+        IntegerOperation::Multiply,
+        IntegerOperation::Divide,
+        IntegerOperation::Remainder,
+        IntegerOperation::Equals,
+        IntegerOperation::NotEquals,
+        IntegerOperation::LessThan,
+        IntegerOperation::LessThanOrEquals,
+        IntegerOperation::GreaterThan,
+        IntegerOperation::GreaterThanOrEquals,
+    ];
+
+    /// The name of the builtin function in the language.
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntegerOperation::Add => "add",
+            IntegerOperation::Subtract => "subtract",
+            IntegerOperation::Multiply => "multiply",
+            IntegerOperation::Divide => "divide",
+            IntegerOperation::Remainder => "remainder",
+            IntegerOperation::Equals => "equals",
+            IntegerOperation::NotEquals => "not_equals",
+            IntegerOperation::LessThan => "less_than",
+            IntegerOperation::LessThanOrEquals => "less_than_or_equals",
+            IntegerOperation::GreaterThan => "greater_than",
+            IntegerOperation::GreaterThanOrEquals => "greater_than_or_equals",
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        match self {
+            IntegerOperation::Add
+            | IntegerOperation::Subtract
+            | IntegerOperation::Multiply
+            | IntegerOperation::Divide
+            | IntegerOperation::Remainder => false,
+            IntegerOperation::Equals
+            | IntegerOperation::NotEquals
+            | IntegerOperation::LessThan
+            | IntegerOperation::LessThanOrEquals
+            | IntegerOperation::GreaterThan
+            | IntegerOperation::GreaterThanOrEquals => true,
+        }
+    }
+
+    pub fn apply(&self, left: i64, right: i64) -> Result<DeepTree, EvaluationError> {
+        let arithmetic = match self {
+            IntegerOperation::Add => left.checked_add(right),
+            IntegerOperation::Subtract => left.checked_sub(right),
+            IntegerOperation::Multiply => left.checked_mul(right),
+            IntegerOperation::Divide | IntegerOperation::Remainder if right == 0 => {
+                return Err(EvaluationError::DivisionByZero)
+            }
+            IntegerOperation::Divide => left.checked_div(right),
+            IntegerOperation::Remainder => left.checked_rem(right),
+            IntegerOperation::Equals => return Ok(bool_to_tree(left == right)),
+            IntegerOperation::NotEquals => return Ok(bool_to_tree(left != right)),
+            IntegerOperation::LessThan => return Ok(bool_to_tree(left < right)),
+            IntegerOperation::LessThanOrEquals => return Ok(bool_to_tree(left <= right)),
+            IntegerOperation::GreaterThan => return Ok(bool_to_tree(left > right)),
+            IntegerOperation::GreaterThanOrEquals => return Ok(bool_to_tree(left >= right)),
+        };
+        match arithmetic {
+            Some(result) => Ok(integer_to_tree(result)),
+            None => Err(EvaluationError::IntegerOverflow(*self)),
+        }
+    }
+
+    /// The builtin function as a closure that takes the two integers as a tree of two children.
+    pub fn to_closure(&self) -> DeepTree {
+        closure_to_deep_tree(
+            DeepTree::empty(),
+            &DeepExpression(Expression::make_integer_operation(
+                *self,
+                Arc::new(DeepExpression(Expression::make_argument())),
+            )),
+        )
+        .expect("The closure of a builtin is small enough to serialize")
+    }
+}
+
+impl Display for IntegerOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub fn integer_to_tree(value: i64) -> DeepTree {
+    let serialized = postcard::to_allocvec(&value).unwrap(/*TODO*/);
+    DeepTree::new(
+        TreeBlob::try_from(bytes::Bytes::from(serialized)).expect("an integer will always fit"),
+        DeepTreeChildren::empty(),
+    )
+}
+
+pub fn integer_from_tree(tree: &Tree) -> Option<i64> {
+    if !tree.children().references().is_empty() {
+        return None;
+    }
+    match postcard::take_from_bytes::<i64>(tree.blob().as_slice()) {
+        Ok((value, [])) => Some(value),
+        _ => None,
+    }
+}
+
+pub fn bool_to_tree(value: bool) -> DeepTree {
+    DeepTree::new(
+        TreeBlob::try_from(bytes::Bytes::from_static(if value {
+            &[1u8]
+        } else {
+            &[0u8]
+        }))
+        .expect("one byte will always fit"),
+        DeepTreeChildren::empty(),
+    )
+}
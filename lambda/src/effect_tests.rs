@@ -1,6 +1,6 @@
 use crate::expressions::{
-    deserialize_recursively, evaluate, serialize_recursively, DeepExpression, Expression,
-    PrintExpression,
+    deserialize_recursively, evaluate, serialize_recursively, DeepExpression, EvaluationBudget,
+    Expression, PrintExpression,
 };
 use astraea::{deep_tree::DeepTree, storage::InMemoryTreeStorage};
 use pretty_assertions::assert_eq;
@@ -68,9 +68,16 @@ async fn effect() {
         format!("{}", &call_main_digest)
     );
 
-    let main_result = evaluate(&call_main, &*storage, &*storage, &None, &None)
-        .await
-        .unwrap();
+    let main_result = evaluate(
+        &call_main,
+        &*storage,
+        &*storage,
+        &None,
+        &None,
+        &EvaluationBudget::default(),
+    )
+    .await
+    .unwrap();
     assert_eq!(
         concat!(
             "4bcb4ead6334a387f95af13a11a6f33497ddead7689574c07072c11433313324",
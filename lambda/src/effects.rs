@@ -0,0 +1,254 @@
+use crate::{
+    builtins::integer_from_tree,
+    expressions::{call_evaluated_closure, load_hashed_tree, EvaluationBudget, EvaluationError},
+    standard_library::{READ_FILE_EFFECT_TAG, SPAWN_EFFECT_TAG, WRITE_FILE_EFFECT_TAG},
+};
+use astraea::{
+    storage::{LoadError, LoadTree, StoreTree},
+    tree::{BlobDigest, HashedTree, Tree},
+};
+use std::{fmt::Display, sync::Arc};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EffectKind {
+    ConsoleOutput,
+    ReadFile,
+    WriteFile,
+    Spawn,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EffectError {
+    Evaluation(EvaluationError),
+    /// The program resulted in a value that is not one of the effects in [crate::standard_library].
+    NotAnEffect(BlobDigest),
+    /// A message or a path is not a tree containing only UTF-8 text.
+    NotAString(BlobDigest),
+    /// The [EffectHandler] doesn't grant the program this kind of effect.
+    CapabilityDenied(EffectKind),
+    /// The [EffectHandler] failed to perform the effect.
+    Handler(String),
+}
+
+impl Display for EffectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for EffectError {}
+
+impl From<EvaluationError> for EffectError {
+    fn from(error: EvaluationError) -> Self {
+        EffectError::Evaluation(error)
+    }
+}
+
+impl From<LoadError> for EffectError {
+    fn from(error: LoadError) -> Self {
+        EffectError::Evaluation(EvaluationError::Load(error))
+    }
+}
+
+/// Performs effects on behalf of a program. Every method is a capability that is denied unless the handler overrides
+/// it, so a program can only do what its handler was written to allow. Contents are digests of trees in the storage
+/// that the program is evaluated in.
+#[async_trait::async_trait]
+pub trait EffectHandler {
+    async fn console_output(&self, _message: &str) -> Result<(), EffectError> {
+        Err(EffectError::CapabilityDenied(EffectKind::ConsoleOutput))
+    }
+
+    async fn read_file(&self, _path: &str) -> Result<BlobDigest, EffectError> {
+        Err(EffectError::CapabilityDenied(EffectKind::ReadFile))
+    }
+
+    async fn write_file(&self, _path: &str, _content: &BlobDigest) -> Result<(), EffectError> {
+        Err(EffectError::CapabilityDenied(EffectKind::WriteFile))
+    }
+
+    /// Sub-computations run with the same handler and budget as the program that spawns them.
+    fn allows_spawn(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Effect {
+    ConsoleOutput {
+        message: BlobDigest,
+    },
+    ReadFile {
+        path: BlobDigest,
+    },
+    WriteFile {
+        path: BlobDigest,
+        content: BlobDigest,
+    },
+    Spawn {
+        closure: BlobDigest,
+        argument: BlobDigest,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Step {
+    Last(Effect),
+    AndThen {
+        effect: Effect,
+        continuation: BlobDigest,
+    },
+}
+
+/// Interprets the effect trees that a pure program results in. The program itself never performs any I/O.
+pub struct EffectRunner<'t> {
+    load_tree: &'t (dyn LoadTree + Sync),
+    store_tree: &'t (dyn StoreTree + Sync),
+    handler: &'t (dyn EffectHandler + Sync),
+    budget: &'t EvaluationBudget,
+}
+
+impl<'t> EffectRunner<'t> {
+    pub fn new(
+        load_tree: &'t (dyn LoadTree + Sync),
+        store_tree: &'t (dyn StoreTree + Sync),
+        handler: &'t (dyn EffectHandler + Sync),
+        budget: &'t EvaluationBudget,
+    ) -> Self {
+        Self {
+            load_tree,
+            store_tree,
+            handler,
+            budget,
+        }
+    }
+
+    /// Performs the effects one after another and results in the result of the last one.
+    pub async fn run(&self, program_result: &BlobDigest) -> Result<BlobDigest, EffectError> {
+        let mut current = *program_result;
+        loop {
+            match self.decode_step(&current).await? {
+                Step::Last(effect) => return self.perform(effect).await,
+                Step::AndThen {
+                    effect,
+                    continuation,
+                } => {
+                    let result = self.perform(effect).await?;
+                    current = call_evaluated_closure(
+                        &continuation,
+                        &result,
+                        self.load_tree,
+                        self.store_tree,
+                        self.budget,
+                    )
+                    .await?;
+                }
+            }
+        }
+    }
+
+    async fn decode_effect(&self, digest: &BlobDigest) -> Result<Option<Effect>, EffectError> {
+        let loaded = load_hashed_tree(digest, self.load_tree).await?;
+        let tree = loaded.tree();
+        if !tree.blob().as_slice().is_empty() {
+            return Ok(None);
+        }
+        let children = tree.children().references();
+        // A console output has no tag, but every tagged effect has more than one child, so the shapes can't be confused
+        // even if the message happens to look like an integer.
+        let tag = match children {
+            [] => return Ok(None),
+            [message] => return Ok(Some(Effect::ConsoleOutput { message: *message })),
+            [first, ..] => integer_from_tree(load_hashed_tree(first, self.load_tree).await?.tree()),
+        };
+        Ok(match (tag, children) {
+            (Some(READ_FILE_EFFECT_TAG), [_, path]) => Some(Effect::ReadFile { path: *path }),
+            (Some(WRITE_FILE_EFFECT_TAG), [_, path, content]) => Some(Effect::WriteFile {
+                path: *path,
+                content: *content,
+            }),
+            (Some(SPAWN_EFFECT_TAG), [_, closure, argument]) => Some(Effect::Spawn {
+                closure: *closure,
+                argument: *argument,
+            }),
+            _ => None,
+        })
+    }
+
+    async fn decode_step(&self, digest: &BlobDigest) -> Result<Step, EffectError> {
+        if let Some(effect) = self.decode_effect(digest).await? {
+            return Ok(Step::Last(effect));
+        }
+        let loaded = load_hashed_tree(digest, self.load_tree).await?;
+        let tree = loaded.tree();
+        if let (true, [effect, continuation]) = (
+            tree.blob().as_slice().is_empty(),
+            tree.children().references(),
+        ) {
+            if let Some(effect) = self.decode_effect(effect).await? {
+                return Ok(Step::AndThen {
+                    effect,
+                    continuation: *continuation,
+                });
+            }
+        }
+        Err(EffectError::NotAnEffect(*digest))
+    }
+
+    async fn load_string(&self, digest: &BlobDigest) -> Result<String, EffectError> {
+        let loaded = load_hashed_tree(digest, self.load_tree).await?;
+        let tree = loaded.tree();
+        if !tree.children().references().is_empty() {
+            return Err(EffectError::NotAString(*digest));
+        }
+        match std::str::from_utf8(tree.blob().as_slice()) {
+            Ok(success) => Ok(success.to_string()),
+            Err(_) => Err(EffectError::NotAString(*digest)),
+        }
+    }
+
+    async fn store_empty_tree(&self) -> Result<BlobDigest, EffectError> {
+        match self
+            .store_tree
+            .store_tree(&HashedTree::from(Arc::new(Tree::empty())))
+            .await
+        {
+            Ok(success) => Ok(success),
+            Err(error) => Err(EffectError::Evaluation(EvaluationError::Store(error))),
+        }
+    }
+
+    /// Effects that don't produce anything result in an empty tree.
+    async fn perform(&self, effect: Effect) -> Result<BlobDigest, EffectError> {
+        match effect {
+            Effect::ConsoleOutput { message } => {
+                let message = self.load_string(&message).await?;
+                self.handler.console_output(&message).await?;
+                self.store_empty_tree().await
+            }
+            Effect::ReadFile { path } => {
+                let path = self.load_string(&path).await?;
+                self.handler.read_file(&path).await
+            }
+            Effect::WriteFile { path, content } => {
+                let path = self.load_string(&path).await?;
+                self.handler.write_file(&path, &content).await?;
+                self.store_empty_tree().await
+            }
+            Effect::Spawn { closure, argument } => {
+                if !self.handler.allows_spawn() {
+                    return Err(EffectError::CapabilityDenied(EffectKind::Spawn));
+                }
+                let program_result = call_evaluated_closure(
+                    &closure,
+                    &argument,
+                    self.load_tree,
+                    self.store_tree,
+                    self.budget,
+                )
+                .await?;
+                Box::pin(self.run(&program_result)).await
+            }
+        }
+    }
+}
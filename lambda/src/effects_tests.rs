@@ -0,0 +1,337 @@
+use crate::{
+    builtins::integer_to_tree,
+    effects::{EffectError, EffectHandler, EffectKind, EffectRunner},
+    expressions::{
+        closure_to_deep_tree, evaluate, DeepExpression, EvaluationBudget, EvaluationError,
+        Expression,
+    },
+    standard_library::{AndThen, ConsoleOutput, ReadFile, Spawn, WriteFile},
+};
+use astraea::{
+    deep_tree::DeepTree,
+    storage::{InMemoryTreeStorage, StoreTree},
+    tree::{BlobDigest, HashedTree, Tree},
+};
+use pretty_assertions::assert_eq;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Default)]
+struct FakeHandler {
+    console: Mutex<Vec<String>>,
+    files: Mutex<BTreeMap<String, BlobDigest>>,
+    allows_spawn: bool,
+}
+
+#[async_trait::async_trait]
+impl EffectHandler for FakeHandler {
+    async fn console_output(&self, message: &str) -> Result<(), EffectError> {
+        self.console.lock().unwrap().push(message.to_string());
+        Ok(())
+    }
+
+    async fn read_file(&self, path: &str) -> Result<BlobDigest, EffectError> {
+        match self.files.lock().unwrap().get(path) {
+            Some(content) => Ok(*content),
+            None => Err(EffectError::Handler(format!("File not found: {path}"))),
+        }
+    }
+
+    async fn write_file(&self, path: &str, content: &BlobDigest) -> Result<(), EffectError> {
+        self.files
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), *content);
+        Ok(())
+    }
+
+    fn allows_spawn(&self) -> bool {
+        self.allows_spawn
+    }
+}
+
+struct NoCapabilities;
+
+impl EffectHandler for NoCapabilities {}
+
+fn string(value: &str) -> DeepTree {
+    DeepTree::try_from_string(value).unwrap()
+}
+
+fn console_output(message: &str) -> DeepTree {
+    ConsoleOutput {
+        message: string(message),
+    }
+    .to_tree()
+}
+
+fn closure(body: Expression<Arc<DeepExpression>, DeepTree>) -> DeepTree {
+    closure_to_deep_tree(DeepTree::empty(), &DeepExpression(body)).unwrap()
+}
+
+fn literal(value: DeepTree) -> Arc<DeepExpression> {
+    Arc::new(DeepExpression(Expression::make_literal(value)))
+}
+
+async fn run(
+    program_result: &DeepTree,
+    storage: &InMemoryTreeStorage,
+    handler: &(dyn EffectHandler + Sync),
+) -> Result<BlobDigest, EffectError> {
+    let digest = program_result.serialize(storage).await.unwrap();
+    EffectRunner::new(storage, storage, handler, &EvaluationBudget::default())
+        .run(&digest)
+        .await
+}
+
+async fn empty_tree(storage: &InMemoryTreeStorage) -> BlobDigest {
+    storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::empty())))
+        .await
+        .unwrap()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_run_evaluated_program() {
+    let storage = InMemoryTreeStorage::empty();
+    let handler = FakeHandler::default();
+    let main = DeepExpression(Expression::make_apply(
+        Arc::new(DeepExpression(Expression::make_lambda(
+            literal(DeepTree::empty()),
+            literal(console_output("Hello, world!\n")),
+        ))),
+        literal(DeepTree::empty()),
+    ));
+    let program_result = evaluate(
+        &main,
+        &storage,
+        &storage,
+        &None,
+        &None,
+        &EvaluationBudget::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        Ok(empty_tree(&storage).await),
+        EffectRunner::new(&storage, &storage, &handler, &EvaluationBudget::default())
+            .run(&program_result)
+            .await
+    );
+    assert_eq!(
+        vec!["Hello, world!\n".to_string()],
+        *handler.console.lock().unwrap()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_run_and_then() {
+    let storage = InMemoryTreeStorage::empty();
+    let handler = FakeHandler::default();
+    let program_result = AndThen {
+        effect: console_output("Hello, "),
+        continuation: closure(Expression::make_construct_tree(vec![literal(string(
+            "world!",
+        ))])),
+    }
+    .to_tree();
+    assert_eq!(
+        Ok(empty_tree(&storage).await),
+        run(&program_result, &storage, &handler).await
+    );
+    assert_eq!(
+        vec!["Hello, ".to_string(), "world!".to_string()],
+        *handler.console.lock().unwrap()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_print_message_that_looks_like_an_integer() {
+    let storage = InMemoryTreeStorage::empty();
+    let handler = FakeHandler::default();
+    // A single ASCII character is also a valid encoding of a small integer, which must not be mistaken for a tag.
+    let program_result = AndThen {
+        effect: console_output("\n"),
+        continuation: closure(Expression::make_construct_tree(vec![literal(string("!"))])),
+    }
+    .to_tree();
+    assert_eq!(
+        Ok(empty_tree(&storage).await),
+        run(&program_result, &storage, &handler).await
+    );
+    assert_eq!(
+        vec!["\n".to_string(), "!".to_string()],
+        *handler.console.lock().unwrap()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_copy_file() {
+    let storage = InMemoryTreeStorage::empty();
+    let handler = FakeHandler::default();
+    let content = string("content").serialize(&storage).await.unwrap();
+    handler
+        .files
+        .lock()
+        .unwrap()
+        .insert("input".to_string(), content);
+    // The continuation constructs a WriteFile effect from the content it receives.
+    let program_result = AndThen {
+        effect: ReadFile {
+            path: string("input"),
+        }
+        .to_tree(),
+        continuation: closure(Expression::make_construct_tree(vec![
+            literal(integer_to_tree(
+                crate::standard_library::WRITE_FILE_EFFECT_TAG,
+            )),
+            literal(string("output")),
+            Arc::new(DeepExpression(Expression::make_argument())),
+        ])),
+    }
+    .to_tree();
+    assert_eq!(
+        Ok(empty_tree(&storage).await),
+        run(&program_result, &storage, &handler).await
+    );
+    assert_eq!(
+        BTreeMap::from([
+            ("input".to_string(), content),
+            ("output".to_string(), content)
+        ]),
+        *handler.files.lock().unwrap()
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_read_file_result() {
+    let storage = InMemoryTreeStorage::empty();
+    let handler = FakeHandler::default();
+    assert_eq!(
+        Err(EffectError::Handler("File not found: missing".to_string())),
+        run(
+            &ReadFile {
+                path: string("missing")
+            }
+            .to_tree(),
+            &storage,
+            &handler
+        )
+        .await
+    );
+    let content = string("content").serialize(&storage).await.unwrap();
+    run(
+        &WriteFile {
+            path: string("file"),
+            content: string("content"),
+        }
+        .to_tree(),
+        &storage,
+        &handler,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        Ok(content),
+        run(
+            &ReadFile {
+                path: string("file")
+            }
+            .to_tree(),
+            &storage,
+            &handler
+        )
+        .await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_spawn() {
+    let storage = InMemoryTreeStorage::empty();
+    let spawn = Spawn {
+        closure: closure(Expression::make_construct_tree(vec![Arc::new(
+            DeepExpression(Expression::make_argument()),
+        )])),
+        argument: string("from the sub-computation"),
+    }
+    .to_tree();
+    let handler = FakeHandler {
+        allows_spawn: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        Ok(empty_tree(&storage).await),
+        run(&spawn, &storage, &handler).await
+    );
+    assert_eq!(
+        vec!["from the sub-computation".to_string()],
+        *handler.console.lock().unwrap()
+    );
+    assert_eq!(
+        Err(EffectError::CapabilityDenied(EffectKind::Spawn)),
+        run(&spawn, &storage, &FakeHandler::default()).await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_capability_denied() {
+    let storage = InMemoryTreeStorage::empty();
+    for (program_result, expected_kind) in [
+        (console_output("Hello"), EffectKind::ConsoleOutput),
+        (
+            ReadFile {
+                path: string("file"),
+            }
+            .to_tree(),
+            EffectKind::ReadFile,
+        ),
+        (
+            WriteFile {
+                path: string("file"),
+                content: DeepTree::empty(),
+            }
+            .to_tree(),
+            EffectKind::WriteFile,
+        ),
+    ] {
+        assert_eq!(
+            Err(EffectError::CapabilityDenied(expected_kind)),
+            run(&program_result, &storage, &NoCapabilities).await
+        );
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_invalid_effects() {
+    let storage = InMemoryTreeStorage::empty();
+    let handler = FakeHandler::default();
+    let integer = integer_to_tree(42);
+    assert_eq!(
+        Err(EffectError::NotAnEffect(
+            integer.serialize(&storage).await.unwrap()
+        )),
+        run(&integer, &storage, &handler).await
+    );
+    let not_a_string = ConsoleOutput {
+        message: console_output("nested"),
+    };
+    assert_eq!(
+        Err(EffectError::NotAString(
+            not_a_string.message.serialize(&storage).await.unwrap()
+        )),
+        run(&not_a_string.to_tree(), &storage, &handler).await
+    );
+    let continuation_is_not_a_closure = AndThen {
+        effect: console_output("Hello"),
+        continuation: string("not a closure"),
+    }
+    .to_tree();
+    assert_eq!(
+        Err(EffectError::Evaluation(EvaluationError::NotAClosure(
+            string("not a closure").serialize(&storage).await.unwrap()
+        ))),
+        run(&continuation_is_not_a_closure, &storage, &handler).await
+    );
+}
@@ -0,0 +1,105 @@
+use astraea::{
+    storage::{LoadRoot, LoadTree, StoreTree, UpdateRoot},
+    tree::BlobDigest,
+};
+use serde::{Deserialize, Serialize};
+use sorted_tree::{prolly_tree_editable_node::EditableNode, sorted_tree::TreeReference};
+use tokio::sync::Mutex;
+
+/// Identifies the application of a closure to an argument. Both are content-addressed, and evaluation is
+/// deterministic, so the result of an application only depends on these two digests.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct CacheKey {
+    pub closure: BlobDigest,
+    pub argument: BlobDigest,
+}
+
+impl CacheKey {
+    pub fn new(closure: BlobDigest, argument: BlobDigest) -> Self {
+        Self { closure, argument }
+    }
+}
+
+type ProllyTree = EditableNode<CacheKey, TreeReference>;
+
+/// Remembers the results of closure applications in a prolly tree. Saving the cache to a named root lets later runs
+/// skip evaluations that were already done. The results are children of the prolly tree, so they stay alive as long as
+/// the root does.
+#[derive(Debug)]
+pub struct EvaluationCache {
+    entries: Mutex<ProllyTree>,
+}
+
+impl Default for EvaluationCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EvaluationCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(ProllyTree::new()),
+        }
+    }
+
+    /// Starts with an empty cache if the root doesn't exist yet.
+    pub async fn load(
+        root_name: &str,
+        load_root: &(dyn LoadRoot + Sync),
+        load_tree: &(dyn LoadTree + Send + Sync),
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let entries = match load_root.load_root(root_name).await? {
+            Some(digest) => ProllyTree::load(&digest, load_tree).await?,
+            None => ProllyTree::new(),
+        };
+        Ok(Self {
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub async fn save(
+        &self,
+        root_name: &str,
+        store_tree: &(dyn StoreTree + Send + Sync),
+        update_root: &(dyn UpdateRoot + Sync),
+    ) -> Result<BlobDigest, Box<dyn std::error::Error>> {
+        let digest = self.entries.lock().await.save(store_tree).await?;
+        update_root.update_root(root_name, &digest).await?;
+        Ok(digest)
+    }
+
+    pub async fn find(
+        &self,
+        key: &CacheKey,
+        load_tree: &(dyn LoadTree + Send + Sync),
+    ) -> Result<Option<BlobDigest>, Box<dyn std::error::Error>> {
+        Ok(self
+            .entries
+            .lock()
+            .await
+            .find(key, load_tree)
+            .await?
+            .map(|result| *result.reference()))
+    }
+
+    pub async fn insert(
+        &self,
+        key: CacheKey,
+        result: &BlobDigest,
+        load_tree: &(dyn LoadTree + Send + Sync),
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.entries
+            .lock()
+            .await
+            .insert(key, TreeReference::new(*result), load_tree)
+            .await
+    }
+
+    pub async fn count(
+        &self,
+        load_tree: &(dyn LoadTree + Send + Sync),
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        self.entries.lock().await.count(load_tree).await
+    }
+}
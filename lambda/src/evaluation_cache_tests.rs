@@ -0,0 +1,167 @@
+use crate::{
+    builtins::{integer_to_tree, IntegerOperation},
+    evaluation_cache::EvaluationCache,
+    expressions::{
+        apply_evaluated_argument, DeepExpression, EvaluationBudget, EvaluationError, Expression,
+    },
+};
+use astraea::{
+    deep_tree::{DeepTree, DeepTreeChildren},
+    storage::{InMemoryTreeStorage, LoadStoreTree, SQLiteStorage},
+    tree::{BlobDigest, TreeBlob},
+};
+use pretty_assertions::assert_eq;
+use std::sync::Arc;
+
+async fn store_operands(left: i64, right: i64, storage: &dyn LoadStoreTree) -> BlobDigest {
+    DeepTree::new(
+        TreeBlob::empty(),
+        DeepTreeChildren::try_from(vec![integer_to_tree(left), integer_to_tree(right)]).unwrap(),
+    )
+    .serialize(storage)
+    .await
+    .unwrap()
+}
+
+async fn apply_operation(
+    operation: IntegerOperation,
+    operands: &BlobDigest,
+    storage: &(dyn LoadStoreTree + Send + Sync),
+    budget: &EvaluationBudget,
+    cache: Option<&EvaluationCache>,
+) -> Result<BlobDigest, EvaluationError> {
+    apply_evaluated_argument(
+        &DeepExpression(Expression::make_literal(operation.to_closure())),
+        operands,
+        storage,
+        storage,
+        &None,
+        &None,
+        budget,
+        cache,
+    )
+    .await
+}
+
+/// Evaluating the literal callee takes the only step, so calling the closure would exceed this budget.
+fn budget_without_call() -> EvaluationBudget {
+    EvaluationBudget::new(1, 1)
+}
+
+#[test_log::test(tokio::test)]
+async fn test_cache_hit() {
+    let storage = InMemoryTreeStorage::empty();
+    let cache = EvaluationCache::new();
+    let operands = store_operands(2, 3, &storage).await;
+    let expected_result = integer_to_tree(5).serialize(&storage).await.unwrap();
+    assert_eq!(
+        Err(EvaluationError::StepLimitExceeded),
+        apply_operation(
+            IntegerOperation::Add,
+            &operands,
+            &storage,
+            &budget_without_call(),
+            Some(&cache)
+        )
+        .await
+    );
+    assert_eq!(0, cache.count(&storage).await.unwrap());
+    assert_eq!(
+        Ok(expected_result),
+        apply_operation(
+            IntegerOperation::Add,
+            &operands,
+            &storage,
+            &EvaluationBudget::default(),
+            Some(&cache)
+        )
+        .await
+    );
+    assert_eq!(1, cache.count(&storage).await.unwrap());
+    assert_eq!(
+        Ok(expected_result),
+        apply_operation(
+            IntegerOperation::Add,
+            &operands,
+            &storage,
+            &budget_without_call(),
+            Some(&cache)
+        )
+        .await
+    );
+    assert_eq!(
+        Err(EvaluationError::StepLimitExceeded),
+        apply_operation(
+            IntegerOperation::Multiply,
+            &operands,
+            &storage,
+            &budget_without_call(),
+            Some(&cache)
+        )
+        .await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_cache_does_not_remember_errors() {
+    let storage = InMemoryTreeStorage::empty();
+    let cache = EvaluationCache::new();
+    let operands = store_operands(1, 0, &storage).await;
+    for _ in 0..2 {
+        assert_eq!(
+            Err(EvaluationError::DivisionByZero),
+            apply_operation(
+                IntegerOperation::Divide,
+                &operands,
+                &storage,
+                &EvaluationBudget::default(),
+                Some(&cache)
+            )
+            .await
+        );
+    }
+    assert_eq!(0, cache.count(&storage).await.unwrap());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_cache_survives_in_named_root() {
+    let connection = rusqlite::Connection::open_in_memory().unwrap();
+    SQLiteStorage::create_schema(&connection).unwrap();
+    let storage = Arc::new(SQLiteStorage::from(connection).unwrap());
+    let root_name = "evaluation_cache";
+    let operands = store_operands(6, 7, &*storage).await;
+    let expected_result = integer_to_tree(42).serialize(&*storage).await.unwrap();
+    {
+        let cache = EvaluationCache::load(root_name, &*storage, &*storage)
+            .await
+            .unwrap();
+        assert_eq!(0, cache.count(&*storage).await.unwrap());
+        assert_eq!(
+            Ok(expected_result),
+            apply_operation(
+                IntegerOperation::Multiply,
+                &operands,
+                &*storage,
+                &EvaluationBudget::default(),
+                Some(&cache)
+            )
+            .await
+        );
+        cache.save(root_name, &*storage, &*storage).await.unwrap();
+    }
+    let cache = EvaluationCache::load(root_name, &*storage, &*storage)
+        .await
+        .unwrap();
+    assert_eq!(1, cache.count(&*storage).await.unwrap());
+    assert_eq!(
+        Ok(expected_result),
+        apply_operation(
+            IntegerOperation::Multiply,
+            &operands,
+            &*storage,
+            &budget_without_call(),
+            Some(&cache)
+        )
+        .await
+    );
+}
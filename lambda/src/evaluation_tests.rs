@@ -1,5 +1,15 @@
-use crate::expressions::{evaluate, DeepExpression, Expression};
-use astraea::{deep_tree::DeepTree, storage::InMemoryTreeStorage};
+use crate::{
+    builtins::{bool_to_tree, integer_to_tree, IntegerOperation},
+    expressions::{
+        closure_to_deep_tree, deserialize_recursively, evaluate, serialize_recursively, Closure,
+        DeepExpression, EvaluationBudget, EvaluationError, Expression, MatchArm, Pattern,
+    },
+};
+use astraea::{
+    deep_tree::{DeepTree, DeepTreeChildren},
+    storage::InMemoryTreeStorage,
+    tree::TreeBlob,
+};
 use pretty_assertions::assert_eq;
 use std::sync::Arc;
 
@@ -8,9 +18,16 @@ async fn expect_evaluate_result(
     storage: &InMemoryTreeStorage,
     expected_result: &DeepTree,
 ) {
-    let evaluated_digest = evaluate(expression, storage, storage, &None, &None)
-        .await
-        .unwrap();
+    let evaluated_digest = evaluate(
+        expression,
+        storage,
+        storage,
+        &None,
+        &None,
+        &EvaluationBudget::default(),
+    )
+    .await
+    .unwrap();
     let evaluated = DeepTree::deserialize(&evaluated_digest, storage)
         .await
         .unwrap();
@@ -34,3 +51,529 @@ async fn test_lambda_parameter() {
     ));
     expect_evaluate_result(&apply, &storage, &expected_result).await;
 }
+
+fn integer_operation(operation: IntegerOperation, left: i64, right: i64) -> DeepExpression {
+    DeepExpression(Expression::make_apply(
+        Arc::new(DeepExpression(Expression::make_literal(
+            operation.to_closure(),
+        ))),
+        Arc::new(DeepExpression(Expression::make_construct_tree(vec![
+            Arc::new(DeepExpression(Expression::make_literal(integer_to_tree(
+                left,
+            )))),
+            Arc::new(DeepExpression(Expression::make_literal(integer_to_tree(
+                right,
+            )))),
+        ]))),
+    ))
+}
+
+#[test_log::test(tokio::test)]
+async fn test_integer_operations() {
+    let storage = InMemoryTreeStorage::empty();
+    for (operation, left, right, expected_result) in [
+        (IntegerOperation::Add, 2, 3, integer_to_tree(5)),
+        (IntegerOperation::Subtract, 2, 3, integer_to_tree(-1)),
+        (IntegerOperation::Multiply, -4, 3, integer_to_tree(-12)),
+        (IntegerOperation::Divide, -7, 2, integer_to_tree(-3)),
+        (IntegerOperation::Remainder, -7, 2, integer_to_tree(-1)),
+        (IntegerOperation::Equals, 2, 2, bool_to_tree(true)),
+        (IntegerOperation::NotEquals, 2, 2, bool_to_tree(false)),
+        (IntegerOperation::LessThan, 2, 3, bool_to_tree(true)),
+        (IntegerOperation::LessThanOrEquals, 3, 3, bool_to_tree(true)),
+        (IntegerOperation::GreaterThan, 2, 3, bool_to_tree(false)),
+        (
+            IntegerOperation::GreaterThanOrEquals,
+            2,
+            3,
+            bool_to_tree(false),
+        ),
+    ] {
+        expect_evaluate_result(
+            &integer_operation(operation, left, right),
+            &storage,
+            &expected_result,
+        )
+        .await;
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_integer_operation_errors() {
+    let storage = InMemoryTreeStorage::empty();
+    for (operation, left, right, expected_error) in [
+        (
+            IntegerOperation::Add,
+            i64::MAX,
+            1,
+            EvaluationError::IntegerOverflow(IntegerOperation::Add),
+        ),
+        (
+            IntegerOperation::Subtract,
+            i64::MIN,
+            1,
+            EvaluationError::IntegerOverflow(IntegerOperation::Subtract),
+        ),
+        (
+            IntegerOperation::Divide,
+            i64::MIN,
+            -1,
+            EvaluationError::IntegerOverflow(IntegerOperation::Divide),
+        ),
+        (
+            IntegerOperation::Divide,
+            1,
+            0,
+            EvaluationError::DivisionByZero,
+        ),
+        (
+            IntegerOperation::Remainder,
+            1,
+            0,
+            EvaluationError::DivisionByZero,
+        ),
+    ] {
+        assert_eq!(
+            Err(expected_error),
+            evaluate(
+                &integer_operation(operation, left, right),
+                &storage,
+                &storage,
+                &None,
+                &None,
+                &EvaluationBudget::default()
+            )
+            .await
+        );
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_integer_operation_on_string() {
+    let storage = InMemoryTreeStorage::empty();
+    let operands = DeepTree::new(
+        TreeBlob::empty(),
+        DeepTreeChildren::try_from(vec![
+            integer_to_tree(1),
+            DeepTree::try_from_string("one").unwrap(),
+        ])
+        .unwrap(),
+    );
+    let expression = DeepExpression(Expression::make_integer_operation(
+        IntegerOperation::Add,
+        Arc::new(DeepExpression(Expression::make_literal(operands.clone()))),
+    ));
+    let operands_digest = operands.serialize(&storage).await.unwrap();
+    assert_eq!(
+        Err(EvaluationError::InvalidIntegerOperands(operands_digest)),
+        evaluate(
+            &expression,
+            &storage,
+            &storage,
+            &None,
+            &None,
+            &EvaluationBudget::default()
+        )
+        .await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_closure_to_deep_tree() {
+    let storage = InMemoryTreeStorage::empty();
+    let body = DeepExpression(Expression::make_get_child(
+        Arc::new(DeepExpression(Expression::make_argument())),
+        1,
+    ));
+    let environment = DeepTree::try_from_string("environment").unwrap();
+    let expected = Closure::new(
+        environment.serialize(&storage).await.unwrap(),
+        Arc::new(body.clone()),
+    )
+    .serialize(&storage)
+    .await
+    .unwrap();
+    assert_eq!(
+        expected,
+        closure_to_deep_tree(environment, &body)
+            .unwrap()
+            .serialize(&storage)
+            .await
+            .unwrap()
+    );
+}
+
+fn literal(value: DeepTree) -> Arc<DeepExpression> {
+    Arc::new(DeepExpression(Expression::make_literal(value)))
+}
+
+#[test_log::test(tokio::test)]
+async fn test_match_evaluates_only_the_matching_arm() {
+    let storage = InMemoryTreeStorage::empty();
+    let expected_result = DeepTree::try_from_string("yes").unwrap();
+    for (scrutinee, expected_result) in [
+        (true, expected_result.clone()),
+        (false, DeepTree::try_from_string("no").unwrap()),
+    ] {
+        let expression = DeepExpression(Expression::make_match(
+            literal(bool_to_tree(scrutinee)),
+            vec![
+                MatchArm::new(
+                    Pattern::Bool(true),
+                    Arc::new(if scrutinee {
+                        DeepExpression(Expression::make_literal(expected_result.clone()))
+                    } else {
+                        integer_operation(IntegerOperation::Divide, 1, 0)
+                    }),
+                ),
+                MatchArm::new(
+                    Pattern::Bool(false),
+                    Arc::new(if scrutinee {
+                        integer_operation(IntegerOperation::Divide, 1, 0)
+                    } else {
+                        DeepExpression(Expression::make_literal(expected_result.clone()))
+                    }),
+                ),
+            ],
+        ));
+        expect_evaluate_result(&expression, &storage, &expected_result).await;
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_match_tree_bindings() {
+    let storage = InMemoryTreeStorage::empty();
+    let scrutinee = DeepTree::new(
+        TreeBlob::empty(),
+        DeepTreeChildren::try_from(vec![
+            integer_to_tree(1),
+            DeepTree::new(
+                TreeBlob::empty(),
+                DeepTreeChildren::try_from(vec![
+                    integer_to_tree(2),
+                    DeepTree::try_from_string("ignored").unwrap(),
+                ])
+                .unwrap(),
+            ),
+        ])
+        .unwrap(),
+    );
+    let expression = DeepExpression(Expression::make_match(
+        literal(scrutinee),
+        vec![
+            MatchArm::new(
+                Pattern::Tree(vec![Pattern::Integer(0), Pattern::Wildcard]),
+                literal(DeepTree::try_from_string("zero").unwrap()),
+            ),
+            MatchArm::new(
+                Pattern::Tree(vec![
+                    Pattern::Binding,
+                    Pattern::Tree(vec![Pattern::Binding, Pattern::Wildcard]),
+                ]),
+                Arc::new(DeepExpression(Expression::make_lambda(
+                    literal(DeepTree::empty()),
+                    Arc::new(DeepExpression(Expression::make_argument())),
+                ))),
+            ),
+        ],
+    ));
+    let expected_result = DeepTree::new(
+        TreeBlob::empty(),
+        DeepTreeChildren::try_from(vec![integer_to_tree(1), integer_to_tree(2)]).unwrap(),
+    );
+    expect_evaluate_result(&expression, &storage, &expected_result).await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_match_without_matching_arm() {
+    let storage = InMemoryTreeStorage::empty();
+    let scrutinee = integer_to_tree(2);
+    let expression = DeepExpression(Expression::make_match(
+        literal(scrutinee.clone()),
+        vec![
+            MatchArm::new(
+                Pattern::Integer(1),
+                literal(DeepTree::try_from_string("one").unwrap()),
+            ),
+            MatchArm::new(
+                Pattern::Tree(vec![]),
+                literal(DeepTree::try_from_string("empty").unwrap()),
+            ),
+        ],
+    ));
+    assert_eq!(
+        Err(EvaluationError::NoMatchingPattern(
+            scrutinee.serialize(&storage).await.unwrap()
+        )),
+        evaluate(
+            &expression,
+            &storage,
+            &storage,
+            &None,
+            &None,
+            &EvaluationBudget::default()
+        )
+        .await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_match_serialization() {
+    let storage = InMemoryTreeStorage::empty();
+    let expression = DeepExpression(Expression::make_match(
+        Arc::new(DeepExpression(Expression::make_argument())),
+        vec![
+            MatchArm::new(
+                Pattern::Tree(vec![Pattern::Integer(-3), Pattern::Binding]),
+                Arc::new(DeepExpression(Expression::make_environment())),
+            ),
+            MatchArm::new(Pattern::Wildcard, literal(bool_to_tree(false))),
+        ],
+    ));
+    let digest = serialize_recursively(&expression, &storage).await.unwrap();
+    assert_eq!(
+        expression,
+        deserialize_recursively(&digest, &storage).await.unwrap()
+    );
+}
+
+/// `(n) => match n { 0 => 1, _ => multiply(n, $self(subtract(n, 1))) }`
+fn factorial() -> DeepExpression {
+    let argument = Arc::new(DeepExpression(Expression::make_argument()));
+    let recursive_call = DeepExpression(Expression::make_apply(
+        Arc::new(DeepExpression(Expression::make_current_closure())),
+        Arc::new(DeepExpression(Expression::make_integer_operation(
+            IntegerOperation::Subtract,
+            Arc::new(DeepExpression(Expression::make_construct_tree(vec![
+                argument.clone(),
+                literal(integer_to_tree(1)),
+            ]))),
+        ))),
+    ));
+    DeepExpression(Expression::make_lambda(
+        literal(DeepTree::empty()),
+        Arc::new(DeepExpression(Expression::make_match(
+            argument.clone(),
+            vec![
+                MatchArm::new(Pattern::Integer(0), literal(integer_to_tree(1))),
+                MatchArm::new(
+                    Pattern::Wildcard,
+                    Arc::new(DeepExpression(Expression::make_integer_operation(
+                        IntegerOperation::Multiply,
+                        Arc::new(DeepExpression(Expression::make_construct_tree(vec![
+                            argument,
+                            Arc::new(recursive_call),
+                        ]))),
+                    ))),
+                ),
+            ],
+        ))),
+    ))
+}
+
+fn call_factorial(n: i64) -> DeepExpression {
+    DeepExpression(Expression::make_apply(
+        Arc::new(factorial()),
+        literal(integer_to_tree(n)),
+    ))
+}
+
+#[test_log::test(tokio::test)]
+async fn test_recursion() {
+    let storage = InMemoryTreeStorage::empty();
+    expect_evaluate_result(&call_factorial(10), &storage, &integer_to_tree(3628800)).await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_recursion_depth_limit() {
+    let storage = InMemoryTreeStorage::empty();
+    let runaway = DeepExpression(Expression::make_apply(
+        Arc::new(DeepExpression(Expression::make_lambda(
+            literal(DeepTree::empty()),
+            Arc::new(DeepExpression(Expression::make_apply(
+                Arc::new(DeepExpression(Expression::make_current_closure())),
+                Arc::new(DeepExpression(Expression::make_argument())),
+            ))),
+        ))),
+        literal(DeepTree::empty()),
+    ));
+    assert_eq!(
+        Err(EvaluationError::DepthLimitExceeded),
+        evaluate(
+            &runaway,
+            &storage,
+            &storage,
+            &None,
+            &None,
+            &EvaluationBudget::default()
+        )
+        .await
+    );
+    // factorial(3) calls itself until factorial(0), so it needs a depth of 4.
+    assert_eq!(
+        Err(EvaluationError::DepthLimitExceeded),
+        evaluate(
+            &call_factorial(3),
+            &storage,
+            &storage,
+            &None,
+            &None,
+            &EvaluationBudget::new(1000, 3)
+        )
+        .await
+    );
+    assert_eq!(
+        Ok(integer_to_tree(6).serialize(&storage).await.unwrap()),
+        evaluate(
+            &call_factorial(3),
+            &storage,
+            &storage,
+            &None,
+            &None,
+            &EvaluationBudget::new(1000, 4)
+        )
+        .await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_recursion_step_limit() {
+    let storage = InMemoryTreeStorage::empty();
+    let budget = EvaluationBudget::new(1000, 100);
+    evaluate(
+        &call_factorial(10),
+        &storage,
+        &storage,
+        &None,
+        &None,
+        &budget,
+    )
+    .await
+    .unwrap();
+    let used_steps = 1000 - budget.remaining_steps();
+    assert_eq!(
+        Err(EvaluationError::StepLimitExceeded),
+        evaluate(
+            &call_factorial(10),
+            &storage,
+            &storage,
+            &None,
+            &None,
+            &EvaluationBudget::new(used_steps - 1, 100)
+        )
+        .await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_runtime_errors() {
+    let storage = InMemoryTreeStorage::empty();
+    let string = DeepTree::try_from_string("Hello, world!").unwrap();
+    let string_digest = string.serialize(&storage).await.unwrap();
+    for (expression, expected_error) in [
+        (
+            DeepExpression(Expression::make_argument()),
+            EvaluationError::ArgumentUnavailable,
+        ),
+        (
+            DeepExpression(Expression::make_environment()),
+            EvaluationError::EnvironmentUnavailable,
+        ),
+        (
+            DeepExpression(Expression::make_current_closure()),
+            EvaluationError::CurrentClosureUnavailable,
+        ),
+        (
+            DeepExpression(Expression::make_apply(
+                literal(string.clone()),
+                literal(DeepTree::empty()),
+            )),
+            EvaluationError::NotAClosure(string_digest),
+        ),
+        (
+            DeepExpression(Expression::make_get_child(literal(string.clone()), 0)),
+            EvaluationError::ChildIndexOutOfRange {
+                parent: string_digest,
+                index: 0,
+            },
+        ),
+    ] {
+        assert_eq!(
+            Err(expected_error),
+            evaluate(
+                &expression,
+                &storage,
+                &storage,
+                &None,
+                &None,
+                &EvaluationBudget::default()
+            )
+            .await
+        );
+    }
+}
+
+fn construct_two_strings() -> DeepExpression {
+    DeepExpression(Expression::make_construct_tree(vec![
+        literal(DeepTree::try_from_string("Hello").unwrap()),
+        literal(DeepTree::try_from_string("world").unwrap()),
+    ]))
+}
+
+#[test_log::test(tokio::test)]
+async fn test_stored_trees_limit() {
+    let storage = InMemoryTreeStorage::empty();
+    let budget = EvaluationBudget::default().with_max_stored_trees(3);
+    evaluate(
+        &construct_two_strings(),
+        &storage,
+        &storage,
+        &None,
+        &None,
+        &budget,
+    )
+    .await
+    .unwrap();
+    assert_eq!(0, budget.remaining_stored_trees());
+    assert_eq!(
+        Err(EvaluationError::StoredTreesLimitExceeded),
+        evaluate(
+            &construct_two_strings(),
+            &storage,
+            &storage,
+            &None,
+            &None,
+            &EvaluationBudget::default().with_max_stored_trees(2)
+        )
+        .await
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_stored_bytes_limit() {
+    let storage = InMemoryTreeStorage::empty();
+    let budget = EvaluationBudget::default().with_max_stored_bytes(10);
+    evaluate(
+        &construct_two_strings(),
+        &storage,
+        &storage,
+        &None,
+        &None,
+        &budget,
+    )
+    .await
+    .unwrap();
+    assert_eq!(0, budget.remaining_stored_bytes());
+    assert_eq!(
+        Err(EvaluationError::StoredBytesLimitExceeded),
+        evaluate(
+            &construct_two_strings(),
+            &storage,
+            &storage,
+            &None,
+            &None,
+            &EvaluationBudget::default().with_max_stored_bytes(9)
+        )
+        .await
+    );
+}
@@ -1,23 +1,82 @@
+use crate::builtins::{integer_from_tree, IntegerOperation};
+use crate::evaluation_cache::{CacheKey, EvaluationCache};
 use crate::name::Name;
-use astraea::deep_tree::DeepTree;
+use astraea::deep_tree::{DeepTree, DeepTreeChildren};
 use astraea::tree::{
     BlobDigest, HashedTree, ReferenceIndex, Tree, TreeChildren, TreeDeserializationError,
     TreeSerializationError,
 };
 use astraea::{
-    storage::{LoadTree, StoreError, StoreTree},
+    storage::{LoadError, LoadTree, StoreError, StoreTree},
     tree::TreeBlob,
 };
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::future::Future;
 use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
 use std::{pin::Pin, sync::Arc};
 
 pub trait PrintExpression {
     fn print(&self, writer: &mut dyn std::fmt::Write, level: usize) -> std::fmt::Result;
 }
 
+/// Patterns only describe the shape of a value. The names of bindings exist in the source language only.
+#[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Clone, Serialize, Deserialize)]
+pub enum Pattern {
+    /// Matches any value without binding it.
+    Wildcard,
+    /// Matches any value and binds it.
+    Binding,
+    Integer(i64),
+    Bool(bool),
+    /// Matches a tree with an empty blob and exactly one child per pattern.
+    Tree(Vec<Pattern>),
+}
+
+impl Pattern {
+    pub fn count_bindings(&self) -> usize {
+        match self {
+            Pattern::Wildcard => 0,
+            Pattern::Binding => 1,
+            Pattern::Integer(_) => 0,
+            Pattern::Bool(_) => 0,
+            Pattern::Tree(children) => children.iter().map(|child| child.count_bindings()).sum(),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding => write!(f, "$bind"),
+            Pattern::Integer(value) => write!(f, "{value}"),
+            Pattern::Bool(value) => write!(f, "{value}"),
+            Pattern::Tree(children) => {
+                write!(f, "[")?;
+                for child in children {
+                    write!(f, "{child}, ")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Clone, Serialize, Deserialize)]
+pub struct MatchArm<E> {
+    pub pattern: Pattern,
+    pub body: E,
+}
+
+impl<E> MatchArm<E> {
+    pub fn new(pattern: Pattern, body: E) -> Self {
+        Self { pattern, body }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Clone, Serialize, Deserialize)]
 pub enum Expression<E, TreeLike>
 where
@@ -25,12 +84,35 @@ where
     TreeLike: Clone + std::fmt::Debug,
 {
     Literal(TreeLike),
-    Apply { callee: E, argument: E },
+    Apply {
+        callee: E,
+        argument: E,
+    },
     Argument,
     Environment,
-    Lambda { environment: E, body: E },
+    Lambda {
+        environment: E,
+        body: E,
+    },
     ConstructTree(Vec<E>),
-    GetChild { parent: E, index: u16 },
+    GetChild {
+        parent: E,
+        index: u16,
+    },
+    /// `operands` evaluates to a tree of the two integers.
+    IntegerOperation {
+        operation: IntegerOperation,
+        operands: E,
+    },
+    /// Evaluates only the body of the first arm whose pattern matches. If the pattern binds values, the body has to
+    /// evaluate to a closure which is called with the bound values (directly for one, as a tree for several).
+    Match {
+        scrutinee: E,
+        arms: Vec<MatchArm<E>>,
+    },
+    /// The closure whose body is being evaluated. This is how functions refer to themselves, because a closure can't
+    /// contain its own digest.
+    CurrentClosure,
 }
 
 impl<E, V> PrintExpression for Expression<E, V>
@@ -55,6 +137,9 @@ where
             Expression::Environment => {
                 write!(writer, "$env")
             }
+            Expression::CurrentClosure => {
+                write!(writer, "$self")
+            }
             Expression::Lambda { environment, body } => {
                 write!(writer, "$env={{")?;
                 let indented = level + 1;
@@ -77,6 +162,33 @@ where
                 parent.print(writer, level)?;
                 write!(writer, ".{index}")
             }
+            Expression::IntegerOperation {
+                operation,
+                operands,
+            } => {
+                write!(writer, "${operation}(")?;
+                operands.print(writer, level)?;
+                write!(writer, ")")
+            }
+            Expression::Match { scrutinee, arms } => {
+                write!(writer, "$match ")?;
+                scrutinee.print(writer, level)?;
+                write!(writer, " {{")?;
+                let indented = level + 1;
+                for arm in arms {
+                    writeln!(writer)?;
+                    for _ in 0..(indented * 2) {
+                        write!(writer, " ")?;
+                    }
+                    write!(writer, "{} => ", arm.pattern)?;
+                    arm.body.print(writer, indented)?;
+                }
+                writeln!(writer)?;
+                for _ in 0..(level * 2) {
+                    write!(writer, " ")?;
+                }
+                write!(writer, "}}")
+            }
         }
     }
 }
@@ -102,6 +214,10 @@ where
         Expression::Environment
     }
 
+    pub fn make_current_closure() -> Self {
+        Expression::CurrentClosure
+    }
+
     pub fn make_lambda(environment: E, body: E) -> Self {
         Expression::Lambda { environment, body }
     }
@@ -114,6 +230,17 @@ where
         Expression::GetChild { parent, index }
     }
 
+    pub fn make_integer_operation(operation: IntegerOperation, operands: E) -> Self {
+        Expression::IntegerOperation {
+            operation,
+            operands,
+        }
+    }
+
+    pub fn make_match(scrutinee: E, arms: Vec<MatchArm<E>>) -> Self {
+        Expression::Match { scrutinee, arms }
+    }
+
     pub async fn map_child_expressions<
         't,
         Expr: Clone + Display + PrintExpression,
@@ -138,6 +265,7 @@ where
             }),
             Expression::Argument => Ok(Expression::Argument),
             Expression::Environment => Ok(Expression::Environment),
+            Expression::CurrentClosure => Ok(Expression::CurrentClosure),
             Expression::Lambda { environment, body } => Ok(Expression::Lambda {
                 environment: transform_expression(environment).await?,
                 body: transform_expression(body).await?,
@@ -153,6 +281,100 @@ where
                 parent: transform_expression(parent).await?,
                 index: *index,
             }),
+            Expression::IntegerOperation {
+                operation,
+                operands,
+            } => Ok(Expression::IntegerOperation {
+                operation: *operation,
+                operands: transform_expression(operands).await?,
+            }),
+            Expression::Match { scrutinee, arms } => {
+                let scrutinee = transform_expression(scrutinee).await?;
+                let mut transformed_arms = Vec::new();
+                for arm in arms.iter() {
+                    transformed_arms.push(MatchArm::new(
+                        arm.pattern.clone(),
+                        transform_expression(&arm.body).await?,
+                    ));
+                }
+                Ok(Expression::Match {
+                    scrutinee,
+                    arms: transformed_arms,
+                })
+            }
+        }
+    }
+
+    /// Splits the expression into the part that is stored in the blob of its tree and the children of the tree.
+    fn split_references<Child>(
+        &self,
+        from_expression: impl Fn(&E) -> Child,
+        from_tree: impl Fn(&TreeLike) -> Child,
+    ) -> (ReferenceExpression, Vec<Child>) {
+        match self {
+            Expression::Literal(value) => (
+                ReferenceExpression::Literal(ReferenceIndex(0)),
+                vec![from_tree(value)],
+            ),
+            Expression::Apply { callee, argument } => (
+                ReferenceExpression::Apply {
+                    callee: ReferenceIndex(0),
+                    argument: ReferenceIndex(1),
+                },
+                // TODO: deduplicate?
+                vec![from_expression(callee), from_expression(argument)],
+            ),
+            Expression::Argument => (ReferenceExpression::Argument, vec![]),
+            Expression::Environment => (ReferenceExpression::Environment, vec![]),
+            Expression::CurrentClosure => (ReferenceExpression::CurrentClosure, vec![]),
+            Expression::Lambda { environment, body } => (
+                ReferenceExpression::Lambda {
+                    environment: ReferenceIndex(0),
+                    body: ReferenceIndex(1),
+                },
+                vec![from_expression(environment), from_expression(body)],
+            ),
+            Expression::ConstructTree(items) => (
+                ReferenceExpression::ConstructTree(
+                    (0..items.len())
+                        .map(|index| ReferenceIndex(index as u64))
+                        .collect(),
+                ),
+                // TODO: deduplicate?
+                items.iter().map(from_expression).collect(),
+            ),
+            Expression::GetChild { parent, index } => (
+                ReferenceExpression::GetChild {
+                    parent: ReferenceIndex(0),
+                    index: *index,
+                },
+                vec![from_expression(parent)],
+            ),
+            Expression::IntegerOperation {
+                operation,
+                operands,
+            } => (
+                ReferenceExpression::IntegerOperation {
+                    operation: *operation,
+                    operands: ReferenceIndex(0),
+                },
+                vec![from_expression(operands)],
+            ),
+            Expression::Match { scrutinee, arms } => (
+                ReferenceExpression::Match {
+                    scrutinee: ReferenceIndex(0),
+                    arms: arms
+                        .iter()
+                        .enumerate()
+                        .map(|(index, arm)| {
+                            MatchArm::new(arm.pattern.clone(), ReferenceIndex(index as u64 + 1))
+                        })
+                        .collect(),
+                },
+                std::iter::once(from_expression(scrutinee))
+                    .chain(arms.iter().map(|arm| from_expression(&arm.body)))
+                    .collect(),
+            ),
         }
     }
 }
@@ -207,81 +429,67 @@ impl PrintExpression for ReferenceIndex {
 pub fn to_reference_expression(
     expression: &ShallowExpression,
 ) -> (ReferenceExpression, Vec<BlobDigest>) {
-    match expression {
-        Expression::Literal(value) => (
-            ReferenceExpression::Literal(ReferenceIndex(0)),
-            vec![*value],
-        ),
-        Expression::Apply { callee, argument } => (
-            ReferenceExpression::Apply {
-                callee: ReferenceIndex(0),
-                argument: ReferenceIndex(1),
-            },
-            // TODO: deduplicate?
-            vec![*callee, *argument],
-        ),
-        Expression::Argument => (ReferenceExpression::Argument, vec![]),
-        Expression::Environment => (ReferenceExpression::Environment, vec![]),
-        Expression::Lambda { environment, body } => (
-            ReferenceExpression::Lambda {
-                environment: ReferenceIndex(0),
-                body: ReferenceIndex(1),
-            },
-            vec![*environment, *body],
-        ),
-        Expression::ConstructTree(items) => (
-            ReferenceExpression::ConstructTree(
-                (0..items.len())
-                    .map(|index| ReferenceIndex(index as u64))
-                    .collect(),
-            ),
-            // TODO: deduplicate?
-            items.clone(),
-        ),
-        Expression::GetChild { parent, index } => (
-            ReferenceExpression::GetChild {
-                parent: ReferenceIndex(0),
-                index: *index,
-            },
-            vec![*parent],
-        ),
-    }
+    expression.split_references(|child| *child, |child| *child)
 }
 
 pub async fn deserialize_shallow(tree: &Tree) -> Result<ShallowExpression, ()> {
-    let reference_expression: ReferenceExpression = postcard::from_bytes(tree.blob().as_slice())
-        .unwrap(/*TODO*/);
+    let reference_expression: ReferenceExpression =
+        match postcard::from_bytes(tree.blob().as_slice()) {
+            Ok(success) => success,
+            Err(_) => return Err(()),
+        };
+    let resolve =
+        |child: &ReferenceIndex| -> Pin<Box<dyn Future<Output = Result<BlobDigest, ()>>>> {
+            let child = tree.children().references().get(child.0 as usize).copied();
+            Box::pin(async move { child.ok_or(()) })
+        };
     reference_expression
-        .map_child_expressions(
-            &|child: &ReferenceIndex| -> Pin<Box<dyn Future<Output = Result<BlobDigest, ()>>>> {
-                let child = tree.children().references()[child.0 as usize];
-                Box::pin(async move { Ok(child) })
-            },
-            &|child: &ReferenceIndex| -> Pin<Box<dyn Future<Output = Result<BlobDigest, ()>>>> {
-                let child = tree.children().references()[child.0 as usize];
-                Box::pin(async move { Ok(child) })
-            },
-        )
+        .map_child_expressions(&resolve, &resolve)
         .await
 }
 
+/// Loads a tree and makes sure that it matches the digest it was loaded by.
+pub async fn load_hashed_tree(
+    reference: &BlobDigest,
+    load_tree: &(dyn LoadTree + Sync),
+) -> Result<HashedTree, LoadError> {
+    match load_tree.load_tree(reference).await?.hash() {
+        Some(success) => Ok(success),
+        None => Err(LoadError::Inconsistency(
+            *reference,
+            "The loaded tree doesn't match its digest".to_string(),
+        )),
+    }
+}
+
 pub async fn deserialize_recursively(
     root: &BlobDigest,
     load_tree: &(dyn LoadTree + Sync),
-) -> Result<DeepExpression, ()> {
-    let root_loaded = load_tree.load_tree(root).await.unwrap(/*TODO*/).hash().unwrap(/*TODO*/);
-    let shallow = deserialize_shallow(root_loaded.tree()).await?;
+) -> Result<DeepExpression, TreeDeserializationError> {
+    let root_loaded = match load_hashed_tree(root, load_tree).await {
+        Ok(success) => success,
+        Err(error) => return Err(TreeDeserializationError::Load(error)),
+    };
+    let shallow = match deserialize_shallow(root_loaded.tree()).await {
+        Ok(success) => success,
+        Err(()) => {
+            return Err(TreeDeserializationError::Load(LoadError::Inconsistency(
+                *root,
+                "The tree is not an expression".to_string(),
+            )))
+        }
+    };
     let deep = shallow
         .map_child_expressions(
-            &|child: &BlobDigest| -> Pin<Box<dyn Future<Output = Result<Arc<DeepExpression>, ()>>>> {
+            &|child: &BlobDigest| -> Pin<Box<dyn Future<Output = Result<Arc<DeepExpression>, TreeDeserializationError>>>> {
                 let child = *child;
                 Box::pin(async move { deserialize_recursively(&child, load_tree)
                     .await
                     .map(Arc::new) })
             },
-            &|child: &BlobDigest| -> Pin<Box<dyn Future<Output = Result<DeepTree, ()>>>> {
+            &|child: &BlobDigest| -> Pin<Box<dyn Future<Output = Result<DeepTree, TreeDeserializationError>>>> {
                 let child = *child;
-                Box::pin(async move { Ok(DeepTree::deserialize(&child, load_tree).await.unwrap(/*TODO*/)) })
+                Box::pin(async move { DeepTree::deserialize(&child, load_tree).await.map_err(TreeDeserializationError::Load) })
             },
         )
         .await?;
@@ -296,7 +504,27 @@ pub fn expression_to_tree(expression: &ShallowExpression) -> Result<Tree, TreeSe
     };
     let blob = postcard::to_allocvec(&reference_expression).unwrap(/*TODO*/);
     Ok(Tree::new(
-        TreeBlob::try_from(bytes::Bytes::from_owner(blob)).unwrap(/*TODO*/),
+        TreeBlob::try_from(bytes::Bytes::from_owner(blob))?,
+        children,
+    ))
+}
+
+/// Serializes like [serialize_recursively], but into a [DeepTree] that is available without storage.
+pub fn expression_to_deep_tree(
+    expression: &DeepExpression,
+) -> Result<DeepTree, TreeSerializationError> {
+    let (reference_expression, children) = expression.0.split_references(
+        |child| expression_to_deep_tree(child),
+        |tree| Ok(tree.clone()),
+    );
+    let children =
+        match DeepTreeChildren::try_from(children.into_iter().collect::<Result<Vec<_>, _>>()?) {
+            Some(success) => success,
+            None => return Err(TreeSerializationError::TooManyChildren),
+        };
+    let blob = postcard::to_allocvec(&reference_expression).unwrap(/*TODO*/);
+    Ok(DeepTree::new(
+        TreeBlob::try_from(bytes::Bytes::from_owner(blob))?,
         children,
     ))
 }
@@ -387,68 +615,405 @@ impl Closure {
         root: &BlobDigest,
         load_tree: &(dyn LoadTree + Sync),
     ) -> Result<Closure, TreeDeserializationError> {
-        let loaded_root = match load_tree.load_tree(root).await {
+        let loaded_root = match load_hashed_tree(root, load_tree).await {
             Ok(success) => success,
             Err(error) => return Err(TreeDeserializationError::Load(error)),
         };
-        let root_tree = loaded_root.hash().unwrap(/*TODO*/).tree().clone();
+        let root_tree = loaded_root.tree();
         let _closure_blob: ClosureBlob = match postcard::from_bytes(root_tree.blob().as_slice()) {
             Ok(success) => success,
             Err(error) => return Err(TreeDeserializationError::Postcard(error)),
         };
-        let environment_reference = &root_tree.children().references()[0];
-        let body_reference = &root_tree.children().references()[1];
-        let body = deserialize_recursively(body_reference, load_tree).await.unwrap(/*TODO*/);
+        let (environment_reference, body_reference) = match root_tree.children().references() {
+            [environment, body] => (environment, body),
+            _ => {
+                return Err(TreeDeserializationError::Load(LoadError::Inconsistency(
+                    *root,
+                    "A closure has exactly two children".to_string(),
+                )))
+            }
+        };
+        let body = deserialize_recursively(body_reference, load_tree).await?;
         Ok(Closure::new(*environment_reference, Arc::new(body)))
     }
 }
 
-async fn call_method(
+/// Serializes a closure like [Closure::serialize], but for an environment and a body that are known at compile time.
+pub fn closure_to_deep_tree(
+    environment: DeepTree,
     body: &DeepExpression,
+) -> Result<DeepTree, TreeSerializationError> {
+    let closure_blob_bytes = postcard::to_allocvec(&ClosureBlob::new()).unwrap(/*TODO*/);
+    Ok(DeepTree::new(
+        TreeBlob::try_from(bytes::Bytes::from_owner(closure_blob_bytes))?,
+        DeepTreeChildren::try_from(vec![environment, expression_to_deep_tree(body)?])
+            .expect("Two children always fit"),
+    ))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EvaluationError {
+    Store(StoreError),
+    /// The operands of an [IntegerOperation] are not a tree of two integers.
+    InvalidIntegerOperands(BlobDigest),
+    IntegerOverflow(IntegerOperation),
+    DivisionByZero,
+    /// None of the arms of a [Expression::Match] matches this value.
+    NoMatchingPattern(BlobDigest),
+    StepLimitExceeded,
+    DepthLimitExceeded,
+    StoredBytesLimitExceeded,
+    StoredTreesLimitExceeded,
+    Load(LoadError),
+    /// The callee of an application did not evaluate to a closure.
+    NotAClosure(BlobDigest),
+    /// [Expression::Argument] was evaluated outside of a closure.
+    ArgumentUnavailable,
+    /// [Expression::Environment] was evaluated outside of a closure.
+    EnvironmentUnavailable,
+    /// [Expression::CurrentClosure] was evaluated outside of a closure.
+    CurrentClosureUnavailable,
+    ChildIndexOutOfRange {
+        parent: BlobDigest,
+        index: u16,
+    },
+    /// Looking up or remembering a result in the [EvaluationCache] failed.
+    Cache(String),
+}
+
+impl Display for EvaluationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for EvaluationError {}
+
+impl From<StoreError> for EvaluationError {
+    fn from(error: StoreError) -> Self {
+        EvaluationError::Store(error)
+    }
+}
+
+impl From<LoadError> for EvaluationError {
+    fn from(error: LoadError) -> Self {
+        EvaluationError::Load(error)
+    }
+}
+
+pub const DEFAULT_MAX_EVALUATION_STEPS: u64 = 10_000_000;
+pub const DEFAULT_MAX_CALL_DEPTH: u64 = 1000;
+pub const DEFAULT_MAX_STORED_BYTES: u64 = 1024 * 1024 * 1024;
+pub const DEFAULT_MAX_STORED_TREES: u64 = 10_000_000;
+
+/// Limits the work of an evaluation so that runaway recursion results in an error instead of hanging or overflowing
+/// the stack. Every evaluated expression is one step. The depth is the number of closure calls in progress. Every tree
+/// stored during the evaluation counts against the stored trees, and its blob against the stored bytes, even if an
+/// equal tree was already in storage.
+#[derive(Debug)]
+pub struct EvaluationBudget {
+    remaining_steps: AtomicU64,
+    max_depth: u64,
+    remaining_stored_bytes: AtomicU64,
+    remaining_stored_trees: AtomicU64,
+    /// Remembers why [BudgetedStore] refused to store a tree because [StoreTree] can't return an [EvaluationError].
+    exceeded_storage_limit: OnceLock<EvaluationError>,
+}
+
+impl EvaluationBudget {
+    pub fn new(max_steps: u64, max_depth: u64) -> Self {
+        Self {
+            remaining_steps: AtomicU64::new(max_steps),
+            max_depth,
+            remaining_stored_bytes: AtomicU64::new(DEFAULT_MAX_STORED_BYTES),
+            remaining_stored_trees: AtomicU64::new(DEFAULT_MAX_STORED_TREES),
+            exceeded_storage_limit: OnceLock::new(),
+        }
+    }
+
+    pub fn with_max_stored_bytes(self, max_stored_bytes: u64) -> Self {
+        self.remaining_stored_bytes
+            .store(max_stored_bytes, Ordering::Relaxed);
+        self
+    }
+
+    pub fn with_max_stored_trees(self, max_stored_trees: u64) -> Self {
+        self.remaining_stored_trees
+            .store(max_stored_trees, Ordering::Relaxed);
+        self
+    }
+
+    pub fn remaining_steps(&self) -> u64 {
+        self.remaining_steps.load(Ordering::Relaxed)
+    }
+
+    pub fn remaining_stored_bytes(&self) -> u64 {
+        self.remaining_stored_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn remaining_stored_trees(&self) -> u64 {
+        self.remaining_stored_trees.load(Ordering::Relaxed)
+    }
+
+    fn consume_store(&self, tree: &HashedTree) -> std::result::Result<(), EvaluationError> {
+        let take = |remaining: &AtomicU64, amount: u64, error: EvaluationError| match remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                remaining.checked_sub(amount)
+            }) {
+            Ok(_) => Ok(()),
+            Err(_) => {
+                let _ = self.exceeded_storage_limit.set(error.clone());
+                Err(error)
+            }
+        };
+        take(
+            &self.remaining_stored_trees,
+            1,
+            EvaluationError::StoredTreesLimitExceeded,
+        )?;
+        take(
+            &self.remaining_stored_bytes,
+            tree.tree().blob().len() as u64,
+            EvaluationError::StoredBytesLimitExceeded,
+        )
+    }
+
+    /// Storage refusals caused by this budget look like [StoreError::NoSpace] to the evaluation.
+    fn explain(&self, error: EvaluationError) -> EvaluationError {
+        match (&error, self.exceeded_storage_limit.get()) {
+            (EvaluationError::Store(StoreError::NoSpace), Some(exceeded)) => exceeded.clone(),
+            _ => error,
+        }
+    }
+
+    fn consume_step(&self) -> std::result::Result<(), EvaluationError> {
+        match self
+            .remaining_steps
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                remaining.checked_sub(1)
+            }) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(EvaluationError::StepLimitExceeded),
+        }
+    }
+}
+
+impl Default for EvaluationBudget {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_EVALUATION_STEPS, DEFAULT_MAX_CALL_DEPTH)
+    }
+}
+
+/// Counts every stored tree against the [EvaluationBudget].
+struct BudgetedStore<'t> {
+    store_tree: &'t (dyn StoreTree + Sync),
+    budget: &'t EvaluationBudget,
+}
+
+#[async_trait::async_trait]
+impl StoreTree for BudgetedStore<'_> {
+    async fn store_tree(&self, tree: &HashedTree) -> std::result::Result<BlobDigest, StoreError> {
+        if self.budget.consume_store(tree).is_err() {
+            return Err(StoreError::NoSpace);
+        }
+        self.store_tree.store_tree(tree).await
+    }
+}
+
+/// Polls the inner future on a new stack segment when the current one is running out. Every nested call adds several
+/// poll frames, so deep recursion would overflow the stack of the thread long before reaching the depth limit.
+struct GrowStack<F: Future>(Pin<Box<F>>);
+
+impl<F: Future> Future for GrowStack<F> {
+    type Output = F::Output;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        context: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        let inner = &mut self.0;
+        stacker::maybe_grow(256 * 1024, 4 * 1024 * 1024, || inner.as_mut().poll(context))
+    }
+}
+
+/// What the body of the closure that is currently being called can refer to.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    argument: Option<BlobDigest>,
+    environment: Option<BlobDigest>,
+    closure: Option<BlobDigest>,
+    depth: u64,
+}
+
+impl Frame {
+    fn outermost(argument: &Option<BlobDigest>, environment: &Option<BlobDigest>) -> Self {
+        Self {
+            argument: *argument,
+            environment: *environment,
+            closure: None,
+            depth: 0,
+        }
+    }
+}
+
+async fn call_method(
+    closure_digest: &BlobDigest,
+    closure: &Closure,
     argument: &BlobDigest,
-    environment: &BlobDigest,
+    caller: &Frame,
     load_tree: &(dyn LoadTree + Sync),
     store_tree: &(dyn StoreTree + Sync),
-) -> std::result::Result<BlobDigest, StoreError> {
-    Box::pin(evaluate(
-        body,
+    budget: &EvaluationBudget,
+) -> std::result::Result<BlobDigest, EvaluationError> {
+    let depth = caller.depth + 1;
+    if depth > budget.max_depth {
+        return Err(EvaluationError::DepthLimitExceeded);
+    }
+    let frame = Frame {
+        argument: Some(*argument),
+        environment: Some(closure.environment),
+        closure: Some(*closure_digest),
+        depth,
+    };
+    GrowStack(Box::pin(evaluate_in_frame(
+        &closure.body,
         load_tree,
         store_tree,
-        &Some(*argument),
-        &Some(*environment),
-    ))
+        &frame,
+        budget,
+    )))
     .await
 }
 
 pub type ReadVariable =
     dyn Fn(&Name) -> Pin<Box<dyn core::future::Future<Output = BlobDigest> + Send>> + Send + Sync;
 
+/// Looks the result up in the `cache` before calling the closure, and remembers it afterwards. Errors are not cached.
+#[allow(clippy::too_many_arguments)]
 pub async fn apply_evaluated_argument(
     callee: &DeepExpression,
     evaluated_argument: &BlobDigest,
-    load_tree: &(dyn LoadTree + Sync),
+    load_tree: &(dyn LoadTree + Send + Sync),
     store_tree: &(dyn StoreTree + Sync),
     current_lambda_argument: &Option<BlobDigest>,
     current_lambda_environment: &Option<BlobDigest>,
-) -> std::result::Result<BlobDigest, StoreError> {
-    let evaluated_callee = Box::pin(evaluate(
+    budget: &EvaluationBudget,
+    cache: Option<&EvaluationCache>,
+) -> std::result::Result<BlobDigest, EvaluationError> {
+    let store_tree = BudgetedStore { store_tree, budget };
+    let frame = Frame::outermost(current_lambda_argument, current_lambda_environment);
+    let evaluated_callee = Box::pin(evaluate_in_frame(
         callee,
         load_tree,
-        store_tree,
-        current_lambda_argument,
-        current_lambda_environment,
+        &store_tree,
+        &frame,
+        budget,
+    ))
+    .await
+    .map_err(|error| budget.explain(error))?;
+    let cache = match cache {
+        Some(cache) => cache,
+        None => {
+            return call_closure(
+                &evaluated_callee,
+                evaluated_argument,
+                load_tree,
+                &store_tree,
+                &frame,
+                budget,
+            )
+            .await
+            .map_err(|error| budget.explain(error))
+        }
+    };
+    let key = CacheKey::new(evaluated_callee, *evaluated_argument);
+    match cache.find(&key, load_tree).await {
+        Ok(Some(result)) => return Ok(result),
+        Ok(None) => {}
+        Err(error) => return Err(EvaluationError::Cache(error.to_string())),
+    }
+    let result = call_closure(
+        &evaluated_callee,
+        evaluated_argument,
+        load_tree,
+        &store_tree,
+        &frame,
+        budget,
+    )
+    .await
+    .map_err(|error| budget.explain(error))?;
+    match cache.insert(key, &result, load_tree).await {
+        Ok(()) => Ok(result),
+        Err(error) => Err(EvaluationError::Cache(error.to_string())),
+    }
+}
+
+/// Calls a closure that is already in storage, for example a continuation that a program returned.
+pub async fn call_evaluated_closure(
+    closure: &BlobDigest,
+    argument: &BlobDigest,
+    load_tree: &(dyn LoadTree + Sync),
+    store_tree: &(dyn StoreTree + Sync),
+    budget: &EvaluationBudget,
+) -> std::result::Result<BlobDigest, EvaluationError> {
+    call_closure(
+        closure,
+        argument,
+        load_tree,
+        &BudgetedStore { store_tree, budget },
+        &Frame::outermost(&None, &None),
+        budget,
+    )
+    .await
+    .map_err(|error| budget.explain(error))
+}
+
+async fn apply_in_frame(
+    callee: &DeepExpression,
+    evaluated_argument: &BlobDigest,
+    load_tree: &(dyn LoadTree + Sync),
+    store_tree: &(dyn StoreTree + Sync),
+    frame: &Frame,
+    budget: &EvaluationBudget,
+) -> std::result::Result<BlobDigest, EvaluationError> {
+    let evaluated_callee = Box::pin(evaluate_in_frame(
+        callee, load_tree, store_tree, frame, budget,
     ))
     .await?;
-    let closure = match Closure::deserialize(&evaluated_callee, load_tree).await {
+    call_closure(
+        &evaluated_callee,
+        evaluated_argument,
+        load_tree,
+        store_tree,
+        frame,
+        budget,
+    )
+    .await
+}
+
+async fn call_closure(
+    evaluated_callee: &BlobDigest,
+    evaluated_argument: &BlobDigest,
+    load_tree: &(dyn LoadTree + Sync),
+    store_tree: &(dyn StoreTree + Sync),
+    frame: &Frame,
+    budget: &EvaluationBudget,
+) -> std::result::Result<BlobDigest, EvaluationError> {
+    let closure = match Closure::deserialize(evaluated_callee, load_tree).await {
         Ok(success) => success,
-        Err(_) => todo!(),
+        Err(TreeDeserializationError::Load(
+            error @ (LoadError::Rusqlite(_) | LoadError::TreeNotFound(_)),
+        )) => return Err(EvaluationError::Load(error)),
+        Err(_) => return Err(EvaluationError::NotAClosure(*evaluated_callee)),
     };
     call_method(
-        &closure.body,
+        evaluated_callee,
+        &closure,
         evaluated_argument,
-        &closure.environment,
+        frame,
         load_tree,
         store_tree,
+        budget,
     )
     .await
 }
@@ -460,24 +1025,29 @@ pub async fn evaluate_apply(
     store_tree: &(dyn StoreTree + Sync),
     current_lambda_argument: &Option<BlobDigest>,
     current_lambda_environment: &Option<BlobDigest>,
-) -> std::result::Result<BlobDigest, StoreError> {
-    let evaluated_argument = Box::pin(evaluate(
+    budget: &EvaluationBudget,
+) -> std::result::Result<BlobDigest, EvaluationError> {
+    let frame = Frame::outermost(current_lambda_argument, current_lambda_environment);
+    let store_tree = BudgetedStore { store_tree, budget };
+    let evaluated_argument = Box::pin(evaluate_in_frame(
         argument,
         load_tree,
-        store_tree,
-        current_lambda_argument,
-        current_lambda_environment,
+        &store_tree,
+        &frame,
+        budget,
     ))
-    .await?;
-    apply_evaluated_argument(
+    .await
+    .map_err(|error| budget.explain(error))?;
+    apply_in_frame(
         callee,
         &evaluated_argument,
         load_tree,
-        store_tree,
-        current_lambda_argument,
-        current_lambda_environment,
+        &store_tree,
+        &frame,
+        budget,
     )
     .await
+    .map_err(|error| budget.explain(error))
 }
 
 pub async fn evaluate(
@@ -486,41 +1056,72 @@ pub async fn evaluate(
     store_tree: &(dyn StoreTree + Sync),
     current_lambda_argument: &Option<BlobDigest>,
     current_lambda_environment: &Option<BlobDigest>,
-) -> std::result::Result<BlobDigest, StoreError> {
+    budget: &EvaluationBudget,
+) -> std::result::Result<BlobDigest, EvaluationError> {
+    evaluate_in_frame(
+        expression,
+        load_tree,
+        &BudgetedStore { store_tree, budget },
+        &Frame::outermost(current_lambda_argument, current_lambda_environment),
+        budget,
+    )
+    .await
+    .map_err(|error| budget.explain(error))
+}
+
+async fn evaluate_in_frame(
+    expression: &DeepExpression,
+    load_tree: &(dyn LoadTree + Sync),
+    store_tree: &(dyn StoreTree + Sync),
+    frame: &Frame,
+    budget: &EvaluationBudget,
+) -> std::result::Result<BlobDigest, EvaluationError> {
+    budget.consume_step()?;
     match &expression.0 {
-        Expression::Literal(literal_value) => literal_value.serialize(store_tree).await,
+        Expression::Literal(literal_value) => Ok(literal_value.serialize(store_tree).await?),
         Expression::Apply { callee, argument } => {
-            evaluate_apply(
+            let evaluated_argument = Box::pin(evaluate_in_frame(
+                argument, load_tree, store_tree, frame, budget,
+            ))
+            .await?;
+            apply_in_frame(
                 callee,
-                argument,
+                &evaluated_argument,
                 load_tree,
                 store_tree,
-                current_lambda_argument,
-                current_lambda_environment,
+                frame,
+                budget,
             )
             .await
         }
         Expression::Argument => {
-            if let Some(argument) = current_lambda_argument {
-                Ok(*argument)
+            if let Some(argument) = frame.argument {
+                Ok(argument)
             } else {
-                todo!("We are not in a lambda context; argument is not available")
+                Err(EvaluationError::ArgumentUnavailable)
             }
         }
         Expression::Environment => {
-            if let Some(environment) = current_lambda_environment {
-                Ok(*environment)
+            if let Some(environment) = frame.environment {
+                Ok(environment)
+            } else {
+                Err(EvaluationError::EnvironmentUnavailable)
+            }
+        }
+        Expression::CurrentClosure => {
+            if let Some(closure) = frame.closure {
+                Ok(closure)
             } else {
-                todo!("We are not in a lambda context; environment is not available")
+                Err(EvaluationError::CurrentClosureUnavailable)
             }
         }
         Expression::Lambda { environment, body } => {
-            let evaluated_environment = Box::pin(evaluate(
+            let evaluated_environment = Box::pin(evaluate_in_frame(
                 environment,
                 load_tree,
                 store_tree,
-                current_lambda_argument,
-                current_lambda_environment,
+                frame,
+                budget,
             ))
             .await?;
             let closure = Closure::new(evaluated_environment, body.clone());
@@ -530,12 +1131,8 @@ pub async fn evaluate(
         Expression::ConstructTree(arguments) => {
             let mut evaluated_arguments = Vec::new();
             for argument in arguments {
-                let evaluated_argument = Box::pin(evaluate(
-                    argument,
-                    load_tree,
-                    store_tree,
-                    current_lambda_argument,
-                    current_lambda_environment,
+                let evaluated_argument = Box::pin(evaluate_in_frame(
+                    argument, load_tree, store_tree, frame, budget,
                 ))
                 .await?;
                 evaluated_arguments.push(evaluated_argument);
@@ -543,38 +1140,151 @@ pub async fn evaluate(
             let children = match TreeChildren::try_from(evaluated_arguments) {
                 Some(success) => success,
                 None => {
-                    return Err(StoreError::TreeSerializationError(
+                    return Err(EvaluationError::Store(StoreError::TreeSerializationError(
                         TreeSerializationError::TooManyChildren,
-                    ))
+                    )))
                 }
             };
-            store_tree
+            Ok(store_tree
                 .store_tree(&HashedTree::from(Arc::new(Tree::new(
                     TreeBlob::empty(),
                     children,
                 ))))
-                .await
+                .await?)
         }
         Expression::GetChild { parent, index } => {
-            let evaluated_parent = Box::pin(evaluate(
-                parent,
-                load_tree,
-                store_tree,
-                current_lambda_argument,
-                current_lambda_environment,
+            let evaluated_parent = Box::pin(evaluate_in_frame(
+                parent, load_tree, store_tree, frame, budget,
             ))
             .await?;
-            let loaded_parent = load_tree.load_tree(&evaluated_parent).await.unwrap(/*TODO*/);
-            let hashed_tree = loaded_parent
-                .hash()
-                .unwrap(/*TODO*/);
-            let child = hashed_tree
+            let loaded_parent = load_hashed_tree(&evaluated_parent, load_tree).await?;
+            match loaded_parent
                 .tree()
                 .children()
                 .references()
                 .get(*index as usize)
-                .expect("TODO handle out of range error");
-            Ok(*child)
+            {
+                Some(child) => Ok(*child),
+                None => Err(EvaluationError::ChildIndexOutOfRange {
+                    parent: evaluated_parent,
+                    index: *index,
+                }),
+            }
+        }
+        Expression::IntegerOperation {
+            operation,
+            operands,
+        } => {
+            let evaluated_operands = Box::pin(evaluate_in_frame(
+                operands, load_tree, store_tree, frame, budget,
+            ))
+            .await?;
+            let load_integer = |digest: BlobDigest| async move {
+                load_hashed_tree(&digest, load_tree)
+                    .await
+                    .map(|loaded| integer_from_tree(loaded.tree()))
+            };
+            let loaded_operands = load_hashed_tree(&evaluated_operands, load_tree).await?;
+            let (left, right) = match loaded_operands.tree().children().references() {
+                [left, right] => match (load_integer(*left).await?, load_integer(*right).await?) {
+                    (Some(left), Some(right)) => (left, right),
+                    _ => return Err(EvaluationError::InvalidIntegerOperands(evaluated_operands)),
+                },
+                _ => return Err(EvaluationError::InvalidIntegerOperands(evaluated_operands)),
+            };
+            let result = operation.apply(left, right)?;
+            Ok(result.serialize(store_tree).await?)
+        }
+        Expression::Match { scrutinee, arms } => {
+            let evaluated_scrutinee = Box::pin(evaluate_in_frame(
+                scrutinee, load_tree, store_tree, frame, budget,
+            ))
+            .await?;
+            for arm in arms {
+                let mut bound = Vec::new();
+                if !match_pattern(&arm.pattern, &evaluated_scrutinee, load_tree, &mut bound).await?
+                {
+                    continue;
+                }
+                let bound_argument = match bound.len() {
+                    0 => {
+                        return Box::pin(evaluate_in_frame(
+                            &arm.body, load_tree, store_tree, frame, budget,
+                        ))
+                        .await
+                    }
+                    1 => bound[0],
+                    _ => {
+                        let children = match TreeChildren::try_from(bound) {
+                            Some(success) => success,
+                            None => {
+                                return Err(EvaluationError::Store(
+                                    StoreError::TreeSerializationError(
+                                        TreeSerializationError::TooManyChildren,
+                                    ),
+                                ))
+                            }
+                        };
+                        store_tree
+                            .store_tree(&HashedTree::from(Arc::new(Tree::new(
+                                TreeBlob::empty(),
+                                children,
+                            ))))
+                            .await?
+                    }
+                };
+                return apply_in_frame(
+                    &arm.body,
+                    &bound_argument,
+                    load_tree,
+                    store_tree,
+                    frame,
+                    budget,
+                )
+                .await;
+            }
+            Err(EvaluationError::NoMatchingPattern(evaluated_scrutinee))
+        }
+    }
+}
+
+/// Appends the values bound by `pattern` to `bound` if it matches.
+async fn match_pattern(
+    pattern: &Pattern,
+    value: &BlobDigest,
+    load_tree: &(dyn LoadTree + Sync),
+    bound: &mut Vec<BlobDigest>,
+) -> std::result::Result<bool, EvaluationError> {
+    let load = || async { load_hashed_tree(value, load_tree).await };
+    match pattern {
+        Pattern::Wildcard => Ok(true),
+        Pattern::Binding => {
+            bound.push(*value);
+            Ok(true)
+        }
+        Pattern::Integer(expected) => {
+            Ok(integer_from_tree(load().await?.tree()) == Some(*expected))
+        }
+        Pattern::Bool(expected) => {
+            let loaded = load().await?;
+            let tree = loaded.tree();
+            Ok(tree.children().references().is_empty()
+                && tree.blob().as_slice() == [*expected as u8])
+        }
+        Pattern::Tree(children) => {
+            let loaded = load().await?;
+            let tree = loaded.tree();
+            if !tree.blob().as_slice().is_empty()
+                || tree.children().references().len() != children.len()
+            {
+                return Ok(false);
+            }
+            for (child_pattern, child) in children.iter().zip(tree.children().references()) {
+                if !Box::pin(match_pattern(child_pattern, child, load_tree, bound)).await? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
         }
     }
 }
@@ -1,6 +1,7 @@
+use crate::builtins::IntegerOperation;
 use crate::expressions::{
-    to_reference_expression, DeepExpression, Expression, PrintExpression, ReferenceExpression,
-    ShallowExpression,
+    to_reference_expression, DeepExpression, Expression, MatchArm, Pattern, PrintExpression,
+    ReferenceExpression, ShallowExpression,
 };
 use astraea::{
     deep_tree::DeepTree,
@@ -74,3 +75,37 @@ async fn print_shallow_expression() {
         "literal(BlobDigest(\"00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000\"))",
         writer.as_str());
 }
+
+#[test_log::test]
+fn print_integer_operation() {
+    let expression = DeepExpression(Expression::make_integer_operation(
+        IntegerOperation::LessThan,
+        Arc::new(DeepExpression(Expression::make_argument())),
+    ));
+    let mut writer = String::new();
+    expression.print(&mut writer, 0).unwrap();
+    assert_eq!("$less_than($arg)", writer.as_str());
+}
+
+#[test_log::test]
+fn print_match() {
+    let expression = DeepExpression(Expression::make_match(
+        Arc::new(DeepExpression(Expression::make_argument())),
+        vec![
+            MatchArm::new(
+                Pattern::Tree(vec![Pattern::Integer(1), Pattern::Binding]),
+                Arc::new(DeepExpression(Expression::make_environment())),
+            ),
+            MatchArm::new(
+                Pattern::Wildcard,
+                Arc::new(DeepExpression(Expression::make_argument())),
+            ),
+        ],
+    ));
+    let mut writer = String::new();
+    expression.print(&mut writer, 0).unwrap();
+    assert_eq!(
+        "$match $arg {\n  [1, $bind, ] => $env\n  _ => $arg\n}",
+        writer.as_str()
+    );
+}
@@ -1,4 +1,4 @@
-use crate::expressions::{evaluate, DeepExpression, Expression, PrintExpression};
+use crate::expressions::{evaluate, DeepExpression, EvaluationBudget, Expression, PrintExpression};
 use astraea::{deep_tree::DeepTree, storage::InMemoryTreeStorage};
 use pretty_assertions::assert_eq;
 use std::sync::Arc;
@@ -35,9 +35,16 @@ async fn hello_world() {
         Arc::new(lambda_expression),
         Arc::new(DeepExpression(Expression::make_literal(DeepTree::empty()))),
     ));
-    let main_result = evaluate(&call_main, &*storage, &*storage, &None, &None)
-        .await
-        .unwrap();
+    let main_result = evaluate(
+        &call_main,
+        &*storage,
+        &*storage,
+        &None,
+        &None,
+        &EvaluationBudget::default(),
+    )
+    .await
+    .unwrap();
     let serialized_result = DeepTree::deserialize(&main_result, &*storage)
         .await
         .unwrap();
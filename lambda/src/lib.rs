@@ -1,6 +1,8 @@
 #![feature(test)]
 
 pub mod builtins;
+pub mod effects;
+pub mod evaluation_cache;
 pub mod expressions;
 pub mod name;
 pub mod standard_library;
@@ -16,3 +18,9 @@ mod effect_tests;
 
 #[cfg(test)]
 mod evaluation_tests;
+
+#[cfg(test)]
+mod evaluation_cache_tests;
+
+#[cfg(test)]
+mod effects_tests;
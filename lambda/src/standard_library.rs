@@ -1,8 +1,15 @@
+use crate::builtins::integer_to_tree;
 use astraea::{
     deep_tree::{DeepTree, DeepTreeChildren},
     tree::TreeBlob,
 };
 
+/// Effects other than [ConsoleOutput] are trees whose first child is one of these integers. Programs can only construct
+/// trees without a blob, so the kind of effect can't be in the blob.
+pub const READ_FILE_EFFECT_TAG: i64 = 1;
+pub const WRITE_FILE_EFFECT_TAG: i64 = 2;
+pub const SPAWN_EFFECT_TAG: i64 = 3;
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ConsoleOutput {
     pub message: DeepTree,
@@ -28,3 +35,72 @@ impl ConsoleOutput {
         })
     }
 }
+
+fn tagged_effect_tree(tag: i64, arguments: Vec<DeepTree>) -> DeepTree {
+    let mut children = vec![integer_to_tree(tag)];
+    children.extend(arguments);
+    DeepTree::new(
+        TreeBlob::empty(),
+        DeepTreeChildren::try_from(children).expect("A few children always fit"),
+    )
+}
+
+/// Results in the content of the file.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ReadFile {
+    pub path: DeepTree,
+}
+
+impl ReadFile {
+    pub fn to_tree(&self) -> DeepTree {
+        tagged_effect_tree(READ_FILE_EFFECT_TAG, vec![self.path.clone()])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WriteFile {
+    pub path: DeepTree,
+    pub content: DeepTree,
+}
+
+impl WriteFile {
+    pub fn to_tree(&self) -> DeepTree {
+        tagged_effect_tree(
+            WRITE_FILE_EFFECT_TAG,
+            vec![self.path.clone(), self.content.clone()],
+        )
+    }
+}
+
+/// Applies the closure to the argument and runs the effects of the result. Results in the result of the last effect.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Spawn {
+    pub closure: DeepTree,
+    pub argument: DeepTree,
+}
+
+impl Spawn {
+    pub fn to_tree(&self) -> DeepTree {
+        tagged_effect_tree(
+            SPAWN_EFFECT_TAG,
+            vec![self.closure.clone(), self.argument.clone()],
+        )
+    }
+}
+
+/// Performs the effect, then applies the continuation to its result. The continuation results in the next effect.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AndThen {
+    pub effect: DeepTree,
+    pub continuation: DeepTree,
+}
+
+impl AndThen {
+    pub fn to_tree(&self) -> DeepTree {
+        DeepTree::new(
+            TreeBlob::empty(),
+            DeepTreeChildren::try_from(vec![self.effect.clone(), self.continuation.clone()])
+                .expect("Two children always fit"),
+        )
+    }
+}
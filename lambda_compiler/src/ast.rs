@@ -1,11 +1,18 @@
-use crate::{compilation::SourceLocation, tokenization::IntegerBase};
+use crate::{
+    compilation::SourceLocation,
+    tokenization::{IntegerBase, Operator},
+};
+use astraea::tree::BlobDigest;
 use lambda::name::Name;
+use std::fmt::Display;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct LambdaParameter {
     pub name: Name,
     pub source_location: SourceLocation,
     pub type_annotation: Option<Expression>,
+    /// `type T` declares a type parameter. It only exists at compile time and is inferred at every call site.
+    pub is_type_parameter: bool,
 }
 
 impl LambdaParameter {
@@ -18,6 +25,114 @@ impl LambdaParameter {
             name,
             source_location,
             type_annotation,
+            is_type_parameter: false,
+        }
+    }
+
+    pub fn new_type_parameter(name: Name, source_location: SourceLocation) -> Self {
+        Self {
+            name,
+            source_location,
+            type_annotation: None,
+            is_type_parameter: true,
+        }
+    }
+}
+
+/// `true` and `false` are keywords in patterns. Every other identifier binds the matched value.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum Pattern {
+    Wildcard(SourceLocation),
+    Binding(Name, SourceLocation),
+    IntegerLiteral(i64, IntegerBase, SourceLocation),
+    Bool(bool, SourceLocation),
+    Tree(Vec<Pattern>, SourceLocation),
+    /// `Shape.Circle(r)` matches a variant of a tagged union and its fields. The name of the union type is part of the
+    /// pattern so that the variant can't be confused with a binding.
+    Variant(Name, String, Vec<Pattern>, SourceLocation),
+}
+
+impl Pattern {
+    pub fn source_location(&self) -> SourceLocation {
+        match self {
+            Pattern::Wildcard(source_location) => *source_location,
+            Pattern::Binding(_, source_location) => *source_location,
+            Pattern::IntegerLiteral(_, _, source_location) => *source_location,
+            Pattern::Bool(_, source_location) => *source_location,
+            Pattern::Tree(_, source_location) => *source_location,
+            Pattern::Variant(_, _, _, source_location) => *source_location,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expression,
+}
+
+impl MatchArm {
+    pub fn new(pattern: Pattern, body: Expression) -> Self {
+        Self { pattern, body }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct FieldDeclaration {
+    pub name: String,
+    pub location: SourceLocation,
+    pub type_annotation: Expression,
+}
+
+impl FieldDeclaration {
+    pub fn new(name: String, location: SourceLocation, type_annotation: Expression) -> Self {
+        Self {
+            name,
+            location,
+            type_annotation,
+        }
+    }
+}
+
+/// The fields of a variant have no names, they are matched by position.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct VariantDeclaration {
+    pub name: String,
+    pub location: SourceLocation,
+    pub fields: Vec<Expression>,
+}
+
+impl VariantDeclaration {
+    pub fn new(name: String, location: SourceLocation, fields: Vec<Expression>) -> Self {
+        Self {
+            name,
+            location,
+            fields,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum TypeDefinition {
+    /// `struct Point { x: Int y: Int }`
+    Record(Vec<FieldDeclaration>),
+    /// `enum Shape { Circle(Int) Empty }`
+    Union(Vec<VariantDeclaration>),
+}
+
+/// What an `import` refers to. Paths are relative to the importing module (see [crate::modules::resolve_import_path])
+/// and are loaded by a [crate::modules::SourceLoader], digests refer to a [crate::modules::CompiledModule] in storage.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum ModuleReference {
+    Path(String),
+    Digest(BlobDigest),
+}
+
+impl Display for ModuleReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModuleReference::Path(path) => write!(f, "{path}"),
+            ModuleReference::Digest(digest) => write!(f, "{digest}"),
         }
     }
 }
@@ -32,6 +147,7 @@ pub enum Expression {
     },
     Lambda {
         parameters: Vec<LambdaParameter>,
+        return_type: Option<Box<Expression>>,
         body: Box<Expression>,
     },
     ConstructTree(Vec<Expression>, SourceLocation),
@@ -39,12 +155,54 @@ pub enum Expression {
     Let {
         name: Name,
         location: SourceLocation,
+        /// `let rec` makes the name visible in the value, which has to be a lambda with annotated types.
+        recursive: bool,
         value: Box<Expression>,
         body: Box<Expression>,
     },
     TypeOf(Box<Expression>),
     Comment(String, Box<Expression>, SourceLocation),
     IntegerLiteral(i64, IntegerBase, SourceLocation),
+    If {
+        condition: Box<Expression>,
+        then: Box<Expression>,
+        otherwise: Box<Expression>,
+        location: SourceLocation,
+    },
+    Match {
+        scrutinee: Box<Expression>,
+        arms: Vec<MatchArm>,
+        location: SourceLocation,
+    },
+    /// Makes the value exported by a module visible in the body under `name`.
+    Import {
+        name: Name,
+        location: SourceLocation,
+        module: ModuleReference,
+        body: Box<Expression>,
+    },
+    /// Makes the declared type visible in the body under `name`.
+    TypeDeclaration {
+        name: Name,
+        location: SourceLocation,
+        /// A declaration with type parameters like `enum Option(T)` is generic and has to be applied to type arguments.
+        type_parameters: Vec<Name>,
+        definition: TypeDefinition,
+        body: Box<Expression>,
+    },
+    /// `value.field` reads a field of a record. On a tagged union type, it refers to one of its variants instead.
+    GetField {
+        value: Box<Expression>,
+        field: String,
+        location: SourceLocation,
+    },
+    /// `left + right` is a shorthand for calling the builtin of the operator, which can't be shadowed.
+    BinaryOperator {
+        operator: Operator,
+        left: Box<Expression>,
+        right: Box<Expression>,
+        location: SourceLocation,
+    },
 }
 
 impl Expression {
@@ -59,12 +217,19 @@ impl Expression {
             Expression::Let {
                 name: _,
                 location,
+                recursive: _,
                 value: _,
                 body: _,
             } => *location,
             Expression::TypeOf(expression) => expression.source_location(),
             Expression::Comment(_, _, source_location) => *source_location,
             Expression::IntegerLiteral(_, _, source_location) => *source_location,
+            Expression::If { location, .. } => *location,
+            Expression::Match { location, .. } => *location,
+            Expression::Import { location, .. } => *location,
+            Expression::TypeDeclaration { location, .. } => *location,
+            Expression::GetField { location, .. } => *location,
+            Expression::BinaryOperator { location, .. } => *location,
         }
     }
 }
@@ -1,12 +1,14 @@
 use crate::{
+    modules::ModuleCompiler,
     parsing::{parse_expression_tolerantly, pop_next_non_whitespace_token, ParserOutput},
     tokenization::tokenize_default_syntax,
-    type_checking::{check_types_with_default_globals, TypedExpression},
+    type_checking::TypedExpression,
 };
 use arbitrary::Arbitrary;
-use astraea::storage::StoreError;
+use astraea::storage::{InMemoryTreeStorage, StoreError};
 use lambda::name::NamespaceId;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(
     Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Arbitrary,
@@ -78,21 +80,15 @@ pub fn parse_source(source: &str, source_namespace: &NamespaceId) -> ParserOutpu
     result
 }
 
+/// Imports by path fail because there is no [crate::modules::SourceLoader], and imports by digest fail because nothing
+/// is in storage. Use [ModuleCompiler] to compile modules that import other modules.
 pub async fn compile(
     source: &str,
     source_namespace: &NamespaceId,
 ) -> Result<CompilerOutput, StoreError> {
-    let mut parser_output = parse_source(source, source_namespace);
-    match &parser_output.entry_point {
-        Some(entry_point) => {
-            let type_check_result =
-                check_types_with_default_globals(entry_point, *source_namespace).await?;
-            parser_output.errors.extend(type_check_result.errors);
-            Ok(CompilerOutput::new(
-                type_check_result.entry_point,
-                parser_output.errors,
-            ))
-        }
-        None => Ok(CompilerOutput::new(None, parser_output.errors)),
-    }
+    let no_sources = BTreeMap::<String, String>::new();
+    let storage = InMemoryTreeStorage::empty();
+    ModuleCompiler::new(&no_sources, &storage, *source_namespace)
+        .compile_source(source)
+        .await
 }
@@ -2,7 +2,7 @@ use crate::compilation::{compile, CompilerError, CompilerOutput, SourceLocation}
 use crate::type_checking::{DeepType, GenericType, TypedExpression};
 use astraea::deep_tree::DeepTree;
 use lambda::expressions::{DeepExpression, Expression};
-use lambda::name::NamespaceId;
+use lambda::name::{Name, NamespaceId};
 use pretty_assertions::assert_eq;
 use std::sync::Arc;
 
@@ -25,7 +25,7 @@ async fn test_compile_empty_source() {
 #[test_log::test(tokio::test)]
 async fn test_compile_lambda() {
     let empty_tree = Arc::new(DeepExpression(Expression::make_construct_tree(vec![])));
-    let output = compile(r#"(x) => x"#, &TEST_SOURCE_NAMESPACE).await;
+    let output = compile(r#"(x: Any) => x"#, &TEST_SOURCE_NAMESPACE).await;
     let entry_point = TypedExpression::new(
         DeepExpression(Expression::make_lambda(
             empty_tree,
@@ -43,7 +43,7 @@ async fn test_compile_lambda() {
 #[test_log::test(tokio::test)]
 async fn test_compile_multiple_parameters() {
     let empty_tree = Arc::new(DeepExpression(Expression::make_construct_tree(vec![])));
-    let output = compile(r#"(x, y) => y"#, &TEST_SOURCE_NAMESPACE).await;
+    let output = compile(r#"(x: Any, y: Any) => y"#, &TEST_SOURCE_NAMESPACE).await;
     let entry_point = TypedExpression::new(
         DeepExpression(Expression::make_lambda(
             empty_tree,
@@ -64,7 +64,7 @@ async fn test_compile_multiple_parameters() {
 #[test_log::test(tokio::test)]
 async fn test_compile_function_call() {
     let empty_tree = Arc::new(DeepExpression(Expression::make_construct_tree(vec![])));
-    let output = compile(r#"(f) => {(g) => g}(f)"#, &TEST_SOURCE_NAMESPACE).await;
+    let output = compile(r#"(f: Any) => {(g: Any) => g}(f)"#, &TEST_SOURCE_NAMESPACE).await;
     let f = Arc::new(DeepExpression(Expression::make_argument()));
     let g = Arc::new(DeepExpression(Expression::make_argument()));
     let entry_point = TypedExpression::new(
@@ -107,7 +107,7 @@ async fn test_compile_quotes() {
 #[test_log::test(tokio::test)]
 async fn test_compile_callee_is_not_a_function() {
     let output = compile(
-        r#"(print) => print("Hello, world!")"#,
+        r#"(print: String) => print("Hello, world!")"#,
         &TEST_SOURCE_NAMESPACE,
     )
     .await;
@@ -115,7 +115,7 @@ async fn test_compile_callee_is_not_a_function() {
         None,
         vec![CompilerError::new(
             "Callee is not a function".to_string(),
-            SourceLocation::new(0, 11),
+            SourceLocation::new(0, 19),
         )],
     );
     assert_eq!(Ok(expected), output);
@@ -222,7 +222,7 @@ async fn test_compile_tree_construction_nested() {
 #[test_log::test(tokio::test)]
 async fn test_compile_extra_token() {
     let empty_tree = Arc::new(DeepExpression(Expression::make_construct_tree(vec![])));
-    let output = compile(r#"(x) => x)"#, &TEST_SOURCE_NAMESPACE).await;
+    let output = compile(r#"(x: Any) => x)"#, &TEST_SOURCE_NAMESPACE).await;
     let entry_point = TypedExpression::new(
         DeepExpression(Expression::make_lambda(
             empty_tree,
@@ -237,7 +237,7 @@ async fn test_compile_extra_token() {
         Some(entry_point),
         vec![CompilerError::new(
             "Unexpected token after the entry point lambda".to_string(),
-            SourceLocation::new(0, 8),
+            SourceLocation::new(0, 13),
         )],
     );
     assert_eq!(Ok(expected), output);
@@ -265,7 +265,7 @@ async fn test_compile_braces() {
 async fn test_redundant_captures_minimal() {
     // We reference the same outer variable multiple times in a lambda. The environment should only capture the variable once.
     let empty_tree = Arc::new(DeepExpression(Expression::make_construct_tree(vec![])));
-    let output = compile("(a) => () => [a, a]", &TEST_SOURCE_NAMESPACE).await;
+    let output = compile("(a: Any) => () => [a, a]", &TEST_SOURCE_NAMESPACE).await;
     let entry_point = TypedExpression::new(
         DeepExpression(Expression::make_lambda(
             empty_tree,
@@ -305,7 +305,7 @@ async fn test_redundant_captures_complex() {
     // We reference the same outer variable multiple times in a lambda. The environment should only capture the variable once.
     let empty_tree = Arc::new(DeepExpression(Expression::make_construct_tree(vec![])));
     let output = compile(
-        "(a) => (b) => () => [a, a, a, a, b, b, b, b]",
+        "(a: Any) => (b: Any) => () => [a, a, a, a, b, b, b, b]",
         &TEST_SOURCE_NAMESPACE,
     )
     .await;
@@ -384,3 +384,771 @@ async fn test_redundant_captures_complex() {
     let expected = CompilerOutput::new(Some(entry_point), Vec::new());
     assert_eq!(Ok(expected), output);
 }
+
+#[test_log::test(tokio::test)]
+async fn test_compile_if_branches_disagree() {
+    let output = compile(
+        r#"(x: Bool) => if x {"a"} else {1}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Branch type 'DeepType(Integer)' does not agree with the previous branches of type 'DeepType(String)'".to_string(),
+            SourceLocation::new(0, 30),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_if_condition_is_not_bool() {
+    let output = compile(
+        r#"(x: Int) => if x {"a"} else {"b"}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Condition type 'DeepType(Integer)' is not convertible into 'DeepType(Named(Name { namespace: NamespaceId([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]), key: \"Bool\" }))'".to_string(),
+            SourceLocation::new(0, 15),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_match_pattern_type_mismatch() {
+    let output = compile(
+        r#"(x: String) => match x {
+    [a] => a
+    0 => x
+}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![
+            CompilerError::new(
+                "Tree with 1 children pattern can't match a value of type 'DeepType(String)'"
+                    .to_string(),
+                SourceLocation::new(1, 4),
+            ),
+            CompilerError::new(
+                "Integer pattern can't match a value of type 'DeepType(String)'".to_string(),
+                SourceLocation::new(2, 4),
+            ),
+        ],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_match_binds_name_twice() {
+    let output = compile(
+        r#"() => match [1, 2] {
+    [a, a] => a
+}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Name 01020304-0506-0708-090a-0b0c0d0e0f10.a is bound more than once in this pattern"
+                .to_string(),
+            SourceLocation::new(1, 8),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_recursion_without_return_type() {
+    let output = compile(
+        r#"() => {
+    let rec f = (n: Int) => f(n)
+    f(1)
+}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "A recursive function needs type annotations for all of its parameters and its return type".to_string(),
+            SourceLocation::new(1, 12),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_recursion_value_is_not_a_lambda() {
+    let output = compile(
+        r#"() => {
+    let rec f = [f]
+    f
+}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "The value of 'let rec' has to be a lambda".to_string(),
+            SourceLocation::new(1, 16),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_return_type_mismatch() {
+    let output = compile(r#"(n: Int): String => n"#, &TEST_SOURCE_NAMESPACE).await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Body type 'DeepType(Integer)' is not convertible into return type 'DeepType(String)'"
+                .to_string(),
+            SourceLocation::new(0, 20),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_let_named_rec() {
+    let output = compile(
+        r#"() => {
+    let rec = "a"
+    rec
+}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await
+    .unwrap();
+    assert_eq!(Vec::<CompilerError>::new(), output.errors);
+    assert_eq!(
+        DeepType(GenericType::Function {
+            parameters: vec![],
+            return_type: Box::new(DeepType(GenericType::String)),
+        }),
+        output.entry_point.unwrap().type_
+    );
+}
+
+const SHAPE_DECLARATION: &str = r#"enum Shape {
+    Circle(Int)
+    Square(Int)
+    Empty
+}
+"#;
+
+#[test_log::test(tokio::test)]
+async fn test_compile_match_not_exhaustive_variant() {
+    let output = compile(
+        &format!(
+            "{SHAPE_DECLARATION}(shape: Shape) => match shape {{
+    Shape.Circle(r) => r
+    Shape.Empty => 0
+}}"
+        ),
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Match is not exhaustive, Shape.Square(_) is not handled".to_string(),
+            SourceLocation::new(5, 18),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_match_not_exhaustive_nested() {
+    let output = compile(
+        r#"(a: Bool, b: Bool) => match [a, b] {
+    [true, _] => 1
+    [_, true] => 2
+}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Match is not exhaustive, [false, false] is not handled".to_string(),
+            SourceLocation::new(0, 22),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_match_not_exhaustive_integer() {
+    let output = compile(
+        r#"(n: Int) => match n {
+    0 => 1
+}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Match is not exhaustive, _ is not handled".to_string(),
+            SourceLocation::new(0, 12),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_variant_pattern_field_count() {
+    let output = compile(
+        &format!(
+            "{SHAPE_DECLARATION}(shape: Shape) => match shape {{
+    Shape.Circle(a, b) => a
+    _ => 0
+}}"
+        ),
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Expected 1 fields in the pattern of Shape.Circle, but got 2".to_string(),
+            SourceLocation::new(6, 4),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_unknown_variant() {
+    let output = compile(
+        &format!("{SHAPE_DECLARATION}() => Shape.Triangle"),
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Shape has no variant Triangle".to_string(),
+            SourceLocation::new(5, 12),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_unknown_field() {
+    let output = compile(
+        r#"struct Point {
+    x: Int
+}
+(p: Point) => p.y"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await
+    .unwrap();
+    assert_eq!(None, output.entry_point);
+    assert_eq!(1, output.errors.len());
+    assert!(output.errors[0].message.ends_with("has no field 'y'"));
+    assert_eq!(SourceLocation::new(3, 16), output.errors[0].location);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_record_field_type_mismatch() {
+    let output = compile(
+        r#"struct Point {
+    x: Int
+    y: Int
+}
+() => Point(1, "2")"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Argument 2 type 'DeepType(String)' is not convertible into parameter type 'DeepType(Integer)'"
+                .to_string(),
+            SourceLocation::new(4, 15),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_records_are_nominal() {
+    let output = compile(
+        r#"struct Meters {
+    value: Int
+}
+struct Seconds {
+    value: Int
+}
+(m: Meters): Seconds => m"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await
+    .unwrap();
+    assert_eq!(None, output.entry_point);
+    assert_eq!(1, output.errors.len());
+    assert!(output.errors[0]
+        .message
+        .starts_with("Body type 'DeepType(Record"));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_field_declared_twice() {
+    let output = compile(
+        r#"struct Point {
+    x: Int
+    x: String
+}
+Point"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Field x is declared more than once".to_string(),
+            SourceLocation::new(2, 4),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_variant_declared_twice() {
+    let output = compile(
+        r#"enum Choice {
+    A
+    A(Int)
+}
+Choice"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Variant A is declared more than once".to_string(),
+            SourceLocation::new(2, 4),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_too_many_fields() {
+    // identifiers can't contain digits
+    let field_name = |index: usize| -> String {
+        [index / 26 / 26, index / 26 % 26, index % 26]
+            .iter()
+            .map(|letter| char::from(b'a' + *letter as u8))
+            .collect()
+    };
+    let fields: String = (0..1001)
+        .map(|index| format!("    {}: Int\n", field_name(index)))
+        .collect();
+    let output = compile(
+        &format!("struct Big {{\n{fields}}}\nBig"),
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Big has 1001 fields, but a record can have at most 1000".to_string(),
+            SourceLocation::new(0, 7),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_variant_with_too_many_fields() {
+    let fields = vec!["Int"; 1000].join(", ");
+    let output = compile(
+        &format!("enum Big {{\n    Small\n    Huge({fields})\n}}\nBig"),
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Variant Huge has 1000 fields, but a variant can have at most 999".to_string(),
+            SourceLocation::new(2, 4),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_generic_lambda() {
+    let empty_tree = Arc::new(DeepExpression(Expression::make_construct_tree(vec![])));
+    let output = compile(r#"(type T, value: T): T => value"#, &TEST_SOURCE_NAMESPACE).await;
+    let type_parameter = Name::new(TEST_SOURCE_NAMESPACE, "T".to_string());
+    // The type parameter doesn't exist at runtime.
+    let entry_point = TypedExpression::new(
+        DeepExpression(Expression::make_lambda(
+            empty_tree,
+            Arc::new(DeepExpression(Expression::make_argument())),
+        )),
+        DeepType(GenericType::Generic {
+            parameters: vec![type_parameter.clone()],
+            body: Box::new(DeepType(GenericType::Function {
+                parameters: vec![DeepType(GenericType::Variable(type_parameter.clone()))],
+                return_type: Box::new(DeepType(GenericType::Variable(type_parameter))),
+            })),
+        }),
+    );
+    let expected = CompilerOutput::new(Some(entry_point), Vec::new());
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_generic_lambda_instantiated() {
+    let output = compile(
+        r#"let identity = (type T, value: T): T => value
+let f = (x: Int): String => identity(x)
+f"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Body type 'DeepType(Integer)' is not convertible into return type 'DeepType(String)'"
+                .to_string(),
+            SourceLocation::new(1, 28),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_type_parameter_is_opaque() {
+    let output = compile(
+        r#"(type T, value: T): Int => value"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await
+    .unwrap();
+    assert_eq!(None, output.entry_point);
+    assert_eq!(1, output.errors.len());
+    assert!(output.errors[0]
+        .message
+        .starts_with("Body type 'DeepType(Variable("));
+    assert_eq!(SourceLocation::new(0, 27), output.errors[0].location);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_generic_record() {
+    let output = compile(
+        r#"struct Pair(A, B) {
+    first: A
+    second: B
+}
+let pair = Pair(1, "a")
+let swap = (type A, type B, pair: Pair(A, B)): Pair(B, A) => Pair(pair.second, pair.first)
+let first = (swapped: Pair(String, Int)) => swapped.first
+first(swap(pair))"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await
+    .unwrap();
+    assert_eq!(Vec::<CompilerError>::new(), output.errors);
+    assert_eq!(
+        DeepType(GenericType::String),
+        output.entry_point.unwrap().type_
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_generic_argument_mismatch() {
+    let output = compile(
+        r#"enum Option(T) {
+    Some(T)
+    None
+}
+(x: Option(Int)): Option(String) => x"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await
+    .unwrap();
+    assert_eq!(None, output.entry_point);
+    assert_eq!(1, output.errors.len());
+    assert!(output.errors[0]
+        .message
+        .starts_with("Body type 'DeepType(Union"));
+    assert_eq!(SourceLocation::new(4, 36), output.errors[0].location);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_type_argument_count() {
+    let output = compile(
+        r#"enum Option(T) {
+    Some(T)
+    None
+}
+Option(Int, Int)"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Expected 1 type arguments, but got 2".to_string(),
+            SourceLocation::new(4, 0),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_recursive_type_with_other_type_arguments() {
+    let output = compile(
+        r#"enum Nested(T) {
+    More(Nested(Nested(T)))
+}
+Nested"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "A recursive type can only refer to itself with its own type parameters".to_string(),
+            SourceLocation::new(1, 9),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_match_not_exhaustive_generic() {
+    let output = compile(
+        r#"enum Option(T) {
+    Some(T)
+    None
+}
+(x: Option(Int)) => match x {
+    Option.Some(y) => y
+}"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Match is not exhaustive, Option.None is not handled".to_string(),
+            SourceLocation::new(4, 20),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_infer_parameter_types() {
+    let output = compile(r#"(x) => (y) => add(x, y)"#, &TEST_SOURCE_NAMESPACE)
+        .await
+        .unwrap();
+    assert_eq!(Vec::<CompilerError>::new(), output.errors);
+    let integer_function = DeepType(GenericType::Function {
+        parameters: vec![DeepType(GenericType::Integer)],
+        return_type: Box::new(DeepType(GenericType::Integer)),
+    });
+    assert_eq!(
+        DeepType(GenericType::Function {
+            parameters: vec![DeepType(GenericType::Integer)],
+            return_type: Box::new(integer_function),
+        }),
+        output.entry_point.unwrap().type_
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_infer_parameter_type_from_patterns() {
+    let output = compile(
+        &format!(
+            "{SHAPE_DECLARATION}(shape, flag) => match shape {{
+    Shape.Circle(r) => if flag r else 0
+    _ => 0
+}}"
+        ),
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await
+    .unwrap();
+    assert_eq!(Vec::<CompilerError>::new(), output.errors);
+    match output.entry_point.unwrap().type_.0 {
+        GenericType::Function { parameters, .. } => {
+            assert!(matches!(parameters[0].0, GenericType::Union { .. }));
+            assert_eq!(
+                DeepType(GenericType::Named(Name::new(
+                    TEST_SOURCE_NAMESPACE,
+                    "Bool".to_string()
+                ))),
+                parameters[1]
+            );
+        }
+        other => panic!("Expected a function, got {other:?}"),
+    }
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_inferred_parameter_type_is_checked() {
+    let output = compile(
+        r#"let increment = (x) => add(x, 1)
+increment("a")"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Argument type 'DeepType(String)' is not convertible into parameter type 'DeepType(Integer)'"
+                .to_string(),
+            SourceLocation::new(1, 10),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_infer_conflicting_usages() {
+    let output = compile(
+        r#"let text = (s: String) => s
+let f = (x) => [add(x, 1), text(x)]
+f"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parameter x is used as 'DeepType(Integer)' and as 'DeepType(String)'".to_string(),
+            SourceLocation::new(1, 32),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_infer_field_of_unknown_type() {
+    let output = compile(r#"(p) => p.x"#, &TEST_SOURCE_NAMESPACE).await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "The type of parameter p can't be inferred from its usage, so it needs a type annotation"
+                .to_string(),
+            SourceLocation::new(0, 9),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_infer_callee_of_unknown_type() {
+    let output = compile(
+        r#"(print) => print("Hello, world!")"#,
+        &TEST_SOURCE_NAMESPACE,
+    )
+    .await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "The type of parameter print can't be inferred from its usage, so it needs a type annotation"
+                .to_string(),
+            SourceLocation::new(0, 11),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_infer_unused_parameter() {
+    let output = compile(r#"(x, unused) => add(x, 1)"#, &TEST_SOURCE_NAMESPACE).await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "The type of parameter unused can't be inferred from its usage, so it needs a type annotation"
+                .to_string(),
+            SourceLocation::new(0, 4),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_infer_parameter_used_as_any() {
+    let output = compile(r#"(x) => x"#, &TEST_SOURCE_NAMESPACE).await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "The type of parameter x can't be inferred from its usage, so it needs a type annotation"
+                .to_string(),
+            SourceLocation::new(0, 1),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_operator_ignores_shadowed_builtin() {
+    let output = compile("let add = \"\"\n1 + 2", &TEST_SOURCE_NAMESPACE)
+        .await
+        .unwrap();
+    assert_eq!(Vec::<CompilerError>::new(), output.errors);
+    assert_eq!(
+        DeepType(GenericType::Integer),
+        output.entry_point.unwrap().type_
+    );
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_operator_operand_is_not_an_integer() {
+    let output = compile(r#"1 * "a""#, &TEST_SOURCE_NAMESPACE).await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Operand type 'DeepType(String)' of operator '*' is not convertible into 'DeepType(Integer)'"
+                .to_string(),
+            SourceLocation::new(0, 4),
+        )],
+    );
+    assert_eq!(Ok(expected), output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_infer_parameter_types_from_operators() {
+    let output = compile(r#"(x, y) => x % 2 == y"#, &TEST_SOURCE_NAMESPACE)
+        .await
+        .unwrap();
+    assert_eq!(Vec::<CompilerError>::new(), output.errors);
+    assert_eq!(
+        DeepType(GenericType::Function {
+            parameters: vec![
+                DeepType(GenericType::Integer),
+                DeepType(GenericType::Integer)
+            ],
+            return_type: Box::new(DeepType(GenericType::Named(Name::new(
+                TEST_SOURCE_NAMESPACE,
+                "Bool".to_string()
+            )))),
+        }),
+        output.entry_point.unwrap().type_
+    );
+}
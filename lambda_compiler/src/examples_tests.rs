@@ -7,7 +7,10 @@ use astraea::{
     storage::{InMemoryTreeStorage, StoreTree},
     tree::{BlobDigest, HashedTree, Tree, TreeBlob, TreeChildren},
 };
-use lambda::{expressions::apply_evaluated_argument, name::NamespaceId};
+use lambda::{
+    expressions::{apply_evaluated_argument, EvaluationBudget},
+    name::NamespaceId,
+};
 use pretty_assertions::assert_eq;
 use std::sync::Arc;
 
@@ -32,6 +35,8 @@ async fn test_example_evaluation(
         storage,
         &None,
         &None,
+        &EvaluationBudget::default(),
+        None,
     )
     .await
     .unwrap();
@@ -229,3 +234,166 @@ async fn test_type_of() {
         .unwrap();
     test_example(&source, &storage, &expected_result).await;
 }
+
+#[test_log::test(tokio::test)]
+async fn test_arithmetic() {
+    let source = normalize_line_endings(include_str!("../examples/arithmetic.tl"));
+    let storage = InMemoryTreeStorage::empty();
+    let expected_result = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::empty(),
+            TreeChildren::try_from(vec![
+                storage
+                    .store_tree(&HashedTree::from(Arc::new(Tree::from_postcard_integer(42))))
+                    .await
+                    .unwrap(),
+                storage
+                    .store_tree(&HashedTree::from(Arc::new(Tree::from_postcard_integer(-1))))
+                    .await
+                    .unwrap(),
+                storage
+                    .store_tree(&HashedTree::from(Arc::new(Tree::new(
+                        TreeBlob::try_from(bytes::Bytes::from_static(&[1])).unwrap(),
+                        TreeChildren::empty(),
+                    ))))
+                    .await
+                    .unwrap(),
+            ])
+            .unwrap(),
+        ))))
+        .await
+        .unwrap();
+    test_example(&source, &storage, &expected_result).await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_conditionals() {
+    let source = normalize_line_endings(include_str!("../examples/conditionals.tl"));
+    let storage = InMemoryTreeStorage::empty();
+    let mut children = Vec::new();
+    for description in ["zero", "one", "negative", "many"] {
+        children.push(
+            storage
+                .store_tree(&HashedTree::from(Arc::new(
+                    Tree::from_string(description).unwrap(),
+                )))
+                .await
+                .unwrap(),
+        );
+    }
+    children.push(
+        storage
+            .store_tree(&HashedTree::from(Arc::new(Tree::new(
+                TreeBlob::empty(),
+                TreeChildren::try_from(vec![
+                    storage
+                        .store_tree(&HashedTree::from(Arc::new(Tree::from_postcard_integer(2))))
+                        .await
+                        .unwrap(),
+                    storage
+                        .store_tree(&HashedTree::from(Arc::new(Tree::from_string("a").unwrap())))
+                        .await
+                        .unwrap(),
+                ])
+                .unwrap(),
+            ))))
+            .await
+            .unwrap(),
+    );
+    let expected_result = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::empty(),
+            TreeChildren::try_from(children).unwrap(),
+        ))))
+        .await
+        .unwrap();
+    test_example(&source, &storage, &expected_result).await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_recursion() {
+    let source = normalize_line_endings(include_str!("../examples/recursion.tl"));
+    let storage = InMemoryTreeStorage::empty();
+    let expected_result = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::empty(),
+            TreeChildren::try_from(vec![
+                storage
+                    .store_tree(&HashedTree::from(Arc::new(Tree::from_postcard_integer(
+                        3628800,
+                    ))))
+                    .await
+                    .unwrap(),
+                storage
+                    .store_tree(&HashedTree::from(Arc::new(Tree::from_postcard_integer(
+                        5050,
+                    ))))
+                    .await
+                    .unwrap(),
+            ])
+            .unwrap(),
+        ))))
+        .await
+        .unwrap();
+    test_example(&source, &storage, &expected_result).await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_data_types() {
+    let source = normalize_line_endings(include_str!("../examples/data_types.tl"));
+    let storage = InMemoryTreeStorage::empty();
+    let mut children = Vec::new();
+    for area in [12, 12, 0] {
+        children.push(
+            storage
+                .store_tree(&HashedTree::from(Arc::new(Tree::from_postcard_integer(
+                    area,
+                ))))
+                .await
+                .unwrap(),
+        );
+    }
+    let expected_result = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::empty(),
+            TreeChildren::try_from(children).unwrap(),
+        ))))
+        .await
+        .unwrap();
+    test_example(&source, &storage, &expected_result).await;
+}
+
+#[test_log::test(tokio::test)]
+async fn test_generics() {
+    let source = normalize_line_endings(include_str!("../examples/generics.tl"));
+    let storage = InMemoryTreeStorage::empty();
+    let store = |tree: Tree| {
+        let storage = &storage;
+        async move {
+            storage
+                .store_tree(&HashedTree::from(Arc::new(tree)))
+                .await
+                .unwrap()
+        }
+    };
+    let first = Tree::new(
+        TreeBlob::empty(),
+        TreeChildren::try_from(vec![
+            store(Tree::from_postcard_integer(0)).await,
+            store(Tree::from_postcard_integer(1)).await,
+        ])
+        .unwrap(),
+    );
+    let expected_result = store(Tree::new(
+        TreeBlob::empty(),
+        TreeChildren::try_from(vec![
+            store(Tree::from_postcard_integer(5)).await,
+            store(Tree::from_string("a").unwrap()).await,
+            store(Tree::from_postcard_integer(2)).await,
+            store(first).await,
+        ])
+        .unwrap(),
+    ))
+    .await;
+    test_example(&source, &storage, &expected_result).await;
+}
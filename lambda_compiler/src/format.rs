@@ -1,5 +1,5 @@
 use crate::{
-    ast::{Expression, LambdaParameter},
+    ast::{Expression, LambdaParameter, MatchArm, ModuleReference, Pattern, TypeDefinition},
     tokenization::IntegerBase,
 };
 
@@ -30,6 +30,17 @@ where
     W: std::fmt::Write,
 {
     format_expression(callee, indentation_level, writer)?;
+    format_apply_arguments(arguments, indentation_level, writer)
+}
+
+fn format_apply_arguments<W>(
+    arguments: &[Expression],
+    indentation_level: usize,
+    writer: &mut W,
+) -> std::fmt::Result
+where
+    W: std::fmt::Write,
+{
     write!(writer, "(")?;
     for (index, argument) in arguments.iter().enumerate() {
         if index > 0 {
@@ -42,6 +53,7 @@ where
 
 fn format_lambda<W>(
     parameters: &[LambdaParameter],
+    return_type: &Option<Box<Expression>>,
     body: &Expression,
     indentation_level: usize,
     writer: &mut W,
@@ -54,13 +66,21 @@ where
         if index > 0 {
             write!(writer, ", ")?;
         }
+        if parameter.is_type_parameter {
+            write!(writer, "type ")?;
+        }
         write!(writer, "{}", parameter.name.key)?;
         if let Some(type_annotation) = &parameter.type_annotation {
             write!(writer, ": ")?;
             format_expression(type_annotation, indentation_level, writer)?;
         }
     }
-    write!(writer, ") => ")?;
+    write!(writer, ")")?;
+    if let Some(return_type) = return_type {
+        write!(writer, ": ")?;
+        format_expression(return_type, indentation_level, writer)?;
+    }
+    write!(writer, " => ")?;
     format_expression(body, indentation_level, writer)
 }
 
@@ -97,6 +117,114 @@ where
     write!(writer, "}}")
 }
 
+fn format_pattern<W>(pattern: &Pattern, writer: &mut W) -> std::fmt::Result
+where
+    W: std::fmt::Write,
+{
+    match pattern {
+        Pattern::Wildcard(_source_location) => write!(writer, "_"),
+        Pattern::Binding(name, _source_location) => write!(writer, "{}", name.key),
+        Pattern::IntegerLiteral(value, base, _source_location) => {
+            format_integer(*value, *base, writer)
+        }
+        Pattern::Bool(value, _source_location) => write!(writer, "{value}"),
+        Pattern::Tree(children, _source_location) => {
+            write!(writer, "[")?;
+            for (index, child) in children.iter().enumerate() {
+                if index > 0 {
+                    write!(writer, ", ")?;
+                }
+                format_pattern(child, writer)?;
+            }
+            write!(writer, "]")
+        }
+        Pattern::Variant(type_name, variant, fields, _source_location) => {
+            write!(writer, "{}.{variant}", type_name.key)?;
+            if fields.is_empty() {
+                return Ok(());
+            }
+            write!(writer, "(")?;
+            for (index, field) in fields.iter().enumerate() {
+                if index > 0 {
+                    write!(writer, ", ")?;
+                }
+                format_pattern(field, writer)?;
+            }
+            write!(writer, ")")
+        }
+    }
+}
+
+/// Every field or variant goes on its own line like the arms of a `match`.
+fn format_type_definition<W>(
+    definition: &TypeDefinition,
+    indentation_level: usize,
+    writer: &mut W,
+) -> std::fmt::Result
+where
+    W: std::fmt::Write,
+{
+    write!(writer, "{{")?;
+    let inner_indentation_level = indentation_level + 1;
+    let is_empty = match definition {
+        TypeDefinition::Record(fields) => {
+            for field in fields {
+                break_line(inner_indentation_level, writer)?;
+                write!(writer, "{}: ", field.name)?;
+                format_expression(&field.type_annotation, inner_indentation_level, writer)?;
+            }
+            fields.is_empty()
+        }
+        TypeDefinition::Union(variants) => {
+            for variant in variants {
+                break_line(inner_indentation_level, writer)?;
+                write!(writer, "{}", variant.name)?;
+                if !variant.fields.is_empty() {
+                    format_apply_arguments(&variant.fields, inner_indentation_level, writer)?;
+                }
+            }
+            variants.is_empty()
+        }
+    };
+    if !is_empty {
+        break_line(indentation_level, writer)?;
+    }
+    write!(writer, "}}")
+}
+
+fn format_match<W>(
+    scrutinee: &Expression,
+    arms: &[MatchArm],
+    indentation_level: usize,
+    writer: &mut W,
+) -> std::fmt::Result
+where
+    W: std::fmt::Write,
+{
+    write!(writer, "match ")?;
+    format_expression(scrutinee, indentation_level, writer)?;
+    write!(writer, " {{")?;
+    let inner_indentation_level = indentation_level + 1;
+    for arm in arms {
+        break_line(inner_indentation_level, writer)?;
+        format_pattern(&arm.pattern, writer)?;
+        write!(writer, " => ")?;
+        format_expression(&arm.body, inner_indentation_level, writer)?;
+    }
+    break_line(indentation_level, writer)?;
+    write!(writer, "}}")
+}
+
+fn format_integer<W>(value: i64, base: IntegerBase, writer: &mut W) -> std::fmt::Result
+where
+    W: std::fmt::Write,
+{
+    match base {
+        IntegerBase::Decimal => write!(writer, "{value}"),
+        IntegerBase::Hexadecimal => write!(writer, "0x{value:x}"),
+    }
+}
+
 pub fn format_expression<W>(
     expression: &Expression,
     indentation_level: usize,
@@ -113,9 +241,11 @@ where
         Expression::Apply { callee, arguments } => {
             format_apply(callee, arguments, indentation_level, writer)
         }
-        Expression::Lambda { parameters, body } => {
-            format_lambda(parameters, body, indentation_level, writer)
-        }
+        Expression::Lambda {
+            parameters,
+            return_type,
+            body,
+        } => format_lambda(parameters, return_type, body, indentation_level, writer),
         Expression::ConstructTree(children, _) => {
             write!(writer, "[")?;
             for (index, child) in children.iter().enumerate() {
@@ -130,10 +260,15 @@ where
         Expression::Let {
             name,
             location: _,
+            recursive,
             value,
             body,
         } => {
-            write!(writer, "let {} = ", &name.key)?;
+            if *recursive {
+                write!(writer, "let rec {} = ", name.key)?;
+            } else {
+                write!(writer, "let {} = ", &name.key)?;
+            }
             format_expression(value, indentation_level, writer)?;
             break_line(indentation_level, writer)?;
             format_expression(body, indentation_level, writer)
@@ -148,10 +283,85 @@ where
             break_line(indentation_level, writer)?;
             format_expression(expression, indentation_level, writer)
         }
-        Expression::IntegerLiteral(value, base, _source_location) => match base {
-            IntegerBase::Decimal => write!(writer, "{value}"),
-            IntegerBase::Hexadecimal => write!(writer, "0x{value:x}"),
-        },
+        Expression::IntegerLiteral(value, base, _source_location) => {
+            format_integer(*value, *base, writer)
+        }
+        Expression::If {
+            condition,
+            then,
+            otherwise,
+            location: _,
+        } => {
+            write!(writer, "if ")?;
+            format_expression(condition, indentation_level, writer)?;
+            write!(writer, " ")?;
+            format_expression(then, indentation_level, writer)?;
+            write!(writer, " else ")?;
+            format_expression(otherwise, indentation_level, writer)
+        }
+        Expression::Match {
+            scrutinee,
+            arms,
+            location: _,
+        } => format_match(scrutinee, arms, indentation_level, writer),
+        Expression::Import {
+            name,
+            location: _,
+            module,
+            body,
+        } => {
+            write!(writer, "import {} = ", name.key)?;
+            match module {
+                ModuleReference::Path(path) => format_string_literal(path, writer)?,
+                ModuleReference::Digest(digest) => write!(writer, "digest \"{digest}\"")?,
+            }
+            break_line(indentation_level, writer)?;
+            format_expression(body, indentation_level, writer)
+        }
+        Expression::TypeDeclaration {
+            name,
+            location: _,
+            type_parameters,
+            definition,
+            body,
+        } => {
+            match definition {
+                TypeDefinition::Record(_) => write!(writer, "struct {}", name.key)?,
+                TypeDefinition::Union(_) => write!(writer, "enum {}", name.key)?,
+            }
+            if !type_parameters.is_empty() {
+                write!(writer, "(")?;
+                for (index, type_parameter) in type_parameters.iter().enumerate() {
+                    if index > 0 {
+                        write!(writer, ", ")?;
+                    }
+                    write!(writer, "{}", type_parameter.key)?;
+                }
+                write!(writer, ")")?;
+            }
+            write!(writer, " ")?;
+            format_type_definition(definition, indentation_level, writer)?;
+            break_line(indentation_level, writer)?;
+            format_expression(body, indentation_level, writer)
+        }
+        Expression::GetField {
+            value,
+            field,
+            location: _,
+        } => {
+            format_expression(value, indentation_level, writer)?;
+            write!(writer, ".{field}")
+        }
+        Expression::BinaryOperator {
+            operator,
+            left,
+            right,
+            location: _,
+        } => {
+            format_expression(left, indentation_level, writer)?;
+            write!(writer, " {operator} ")?;
+            format_expression(right, indentation_level, writer)
+        }
     }
 }
 
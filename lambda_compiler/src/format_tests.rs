@@ -1,7 +1,11 @@
 use crate::{
-    ast::{Expression, LambdaParameter},
+    ast::{
+        Expression, FieldDeclaration, LambdaParameter, MatchArm, ModuleReference, Pattern,
+        TypeDefinition, VariantDeclaration,
+    },
     compilation::SourceLocation,
     format::format_expression,
+    tokenization::{IntegerBase, Operator},
 };
 use lambda::name::{Name, NamespaceId};
 use pretty_assertions::assert_eq;
@@ -128,6 +132,7 @@ fn test_format_lambda_0_parameters() {
     format_expression(
         &Expression::Lambda {
             parameters: Vec::new(),
+            return_type: None,
             body: Box::new(Expression::Identifier(
                 Name::new(TEST_NAMESPACE, "f".to_string()),
                 // location doesn't matter for this test
@@ -152,6 +157,7 @@ fn test_format_lambda_1_parameter() {
                 SourceLocation { line: 0, column: 0 },
                 None,
             )],
+            return_type: None,
             body: Box::new(Expression::Identifier(
                 Name::new(TEST_NAMESPACE, "f".to_string()),
                 // location doesn't matter for this test
@@ -184,6 +190,7 @@ fn test_format_lambda_2_parameters() {
                     None,
                 ),
             ],
+            return_type: None,
             body: Box::new(Expression::Identifier(
                 Name::new(TEST_NAMESPACE, "f".to_string()),
                 // location doesn't matter for this test
@@ -212,6 +219,7 @@ fn test_format_lambda_type_annotation() {
                     SourceLocation { line: 0, column: 0 },
                 )),
             )],
+            return_type: None,
             body: Box::new(Expression::Identifier(
                 Name::new(TEST_NAMESPACE, "f".to_string()),
                 // location doesn't matter for this test
@@ -305,6 +313,7 @@ fn test_format_braces_multi_line() {
         &Expression::Braces(Box::new(Expression::Let {
             name: Name::new(TEST_NAMESPACE, "a".to_string()),
             location: IRRELEVANT_SOURCE_LOCATION,
+            recursive: false,
             value: Box::new(Expression::Identifier(
                 Name::new(TEST_NAMESPACE, "b".to_string()),
                 IRRELEVANT_SOURCE_LOCATION,
@@ -329,6 +338,7 @@ fn test_format_let() {
             name: Name::new(TEST_NAMESPACE, "a".to_string()),
             // location doesn't matter for this test
             location: SourceLocation { line: 0, column: 0 },
+            recursive: false,
             value: Box::new(Expression::Identifier(
                 Name::new(TEST_NAMESPACE, "b".to_string()),
                 // location doesn't matter for this test
@@ -353,10 +363,12 @@ fn test_format_lambda_let_indentation() {
     format_expression(
         &Expression::Lambda {
             parameters: Vec::new(),
+            return_type: None,
             body: Box::new(Expression::Let {
                 name: Name::new(TEST_NAMESPACE, "a".to_string()),
                 // location doesn't matter for this test
                 location: SourceLocation { line: 0, column: 0 },
+                recursive: false,
                 value: Box::new(Expression::Identifier(
                     Name::new(TEST_NAMESPACE, "b".to_string()),
                     // location doesn't matter for this test
@@ -383,9 +395,11 @@ fn test_format_lambda_brace_let_indentation() {
     format_expression(
         &Expression::Lambda {
             parameters: Vec::new(),
+            return_type: None,
             body: Box::new(Expression::Braces(Box::new(Expression::Let {
                 name: Name::new(TEST_NAMESPACE, "a".to_string()),
                 location: IRRELEVANT_SOURCE_LOCATION,
+                recursive: false,
                 value: Box::new(Expression::Identifier(
                     Name::new(TEST_NAMESPACE, "b".to_string()),
                     IRRELEVANT_SOURCE_LOCATION,
@@ -436,3 +450,330 @@ fn test_format_comment() {
     .unwrap();
     assert_eq!("# test\na", formatted.as_str());
 }
+
+#[test]
+fn test_format_if() {
+    let mut formatted = String::new();
+    format_expression(
+        &Expression::If {
+            condition: Box::new(Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "c".to_string()),
+                IRRELEVANT_SOURCE_LOCATION,
+            )),
+            then: Box::new(Expression::Braces(Box::new(Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "a".to_string()),
+                IRRELEVANT_SOURCE_LOCATION,
+            )))),
+            otherwise: Box::new(Expression::Braces(Box::new(Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "b".to_string()),
+                IRRELEVANT_SOURCE_LOCATION,
+            )))),
+            location: IRRELEVANT_SOURCE_LOCATION,
+        },
+        IRRELEVANT_INDENTATION_LEVEL,
+        &mut formatted,
+    )
+    .unwrap();
+    assert_eq!("if c {a} else {b}", formatted.as_str());
+}
+
+#[test]
+fn test_format_match() {
+    let mut formatted = String::new();
+    format_expression(
+        &Expression::Match {
+            scrutinee: Box::new(Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "x".to_string()),
+                IRRELEVANT_SOURCE_LOCATION,
+            )),
+            arms: vec![
+                MatchArm::new(
+                    Pattern::IntegerLiteral(
+                        255,
+                        crate::tokenization::IntegerBase::Hexadecimal,
+                        IRRELEVANT_SOURCE_LOCATION,
+                    ),
+                    Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "a".to_string()),
+                        IRRELEVANT_SOURCE_LOCATION,
+                    ),
+                ),
+                MatchArm::new(
+                    Pattern::Tree(
+                        vec![
+                            Pattern::Bool(false, IRRELEVANT_SOURCE_LOCATION),
+                            Pattern::Binding(
+                                Name::new(TEST_NAMESPACE, "y".to_string()),
+                                IRRELEVANT_SOURCE_LOCATION,
+                            ),
+                            Pattern::Wildcard(IRRELEVANT_SOURCE_LOCATION),
+                        ],
+                        IRRELEVANT_SOURCE_LOCATION,
+                    ),
+                    Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "y".to_string()),
+                        IRRELEVANT_SOURCE_LOCATION,
+                    ),
+                ),
+            ],
+            location: IRRELEVANT_SOURCE_LOCATION,
+        },
+        1,
+        &mut formatted,
+    )
+    .unwrap();
+    assert_eq!(
+        "match x {\n        0xff => a\n        [false, y, _] => y\n    }",
+        formatted.as_str()
+    );
+}
+
+#[test]
+fn test_format_let_rec_with_return_type() {
+    let mut formatted = String::new();
+    format_expression(
+        &Expression::Let {
+            name: Name::new(TEST_NAMESPACE, "f".to_string()),
+            location: IRRELEVANT_SOURCE_LOCATION,
+            recursive: true,
+            value: Box::new(Expression::Lambda {
+                parameters: vec![LambdaParameter::new(
+                    Name::new(TEST_NAMESPACE, "n".to_string()),
+                    IRRELEVANT_SOURCE_LOCATION,
+                    Some(Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "Int".to_string()),
+                        IRRELEVANT_SOURCE_LOCATION,
+                    )),
+                )],
+                return_type: Some(Box::new(Expression::Identifier(
+                    Name::new(TEST_NAMESPACE, "Int".to_string()),
+                    IRRELEVANT_SOURCE_LOCATION,
+                ))),
+                body: Box::new(Expression::Identifier(
+                    Name::new(TEST_NAMESPACE, "n".to_string()),
+                    IRRELEVANT_SOURCE_LOCATION,
+                )),
+            }),
+            body: Box::new(Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "f".to_string()),
+                IRRELEVANT_SOURCE_LOCATION,
+            )),
+        },
+        0,
+        &mut formatted,
+    )
+    .unwrap();
+    assert_eq!("let rec f = (n: Int): Int => n\nf", formatted.as_str());
+}
+
+#[test]
+fn test_format_import() {
+    let mut formatted = String::new();
+    format_expression(
+        &Expression::Import {
+            name: Name::new(TEST_NAMESPACE, "math".to_string()),
+            location: IRRELEVANT_SOURCE_LOCATION,
+            module: ModuleReference::Path("math.tl".to_string()),
+            body: Box::new(Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "math".to_string()),
+                IRRELEVANT_SOURCE_LOCATION,
+            )),
+        },
+        0,
+        &mut formatted,
+    )
+    .unwrap();
+    assert_eq!("import math = \"math.tl\"\nmath", formatted.as_str());
+}
+
+#[test]
+fn test_format_struct() {
+    let mut formatted = String::new();
+    format_expression(
+        &Expression::TypeDeclaration {
+            name: Name::new(TEST_NAMESPACE, "Point".to_string()),
+            location: IRRELEVANT_SOURCE_LOCATION,
+            type_parameters: Vec::new(),
+            definition: TypeDefinition::Record(vec![
+                FieldDeclaration::new(
+                    "x".to_string(),
+                    IRRELEVANT_SOURCE_LOCATION,
+                    Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "Int".to_string()),
+                        IRRELEVANT_SOURCE_LOCATION,
+                    ),
+                ),
+                FieldDeclaration::new(
+                    "y".to_string(),
+                    IRRELEVANT_SOURCE_LOCATION,
+                    Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "Int".to_string()),
+                        IRRELEVANT_SOURCE_LOCATION,
+                    ),
+                ),
+            ]),
+            body: Box::new(Expression::GetField {
+                value: Box::new(Expression::Identifier(
+                    Name::new(TEST_NAMESPACE, "p".to_string()),
+                    IRRELEVANT_SOURCE_LOCATION,
+                )),
+                field: "x".to_string(),
+                location: IRRELEVANT_SOURCE_LOCATION,
+            }),
+        },
+        1,
+        &mut formatted,
+    )
+    .unwrap();
+    assert_eq!(
+        "struct Point {\n        x: Int\n        y: Int\n    }\n    p.x",
+        formatted.as_str()
+    );
+}
+
+#[test]
+fn test_format_enum() {
+    let mut formatted = String::new();
+    format_expression(
+        &Expression::TypeDeclaration {
+            name: Name::new(TEST_NAMESPACE, "Shape".to_string()),
+            location: IRRELEVANT_SOURCE_LOCATION,
+            type_parameters: Vec::new(),
+            definition: TypeDefinition::Union(vec![
+                VariantDeclaration::new(
+                    "Circle".to_string(),
+                    IRRELEVANT_SOURCE_LOCATION,
+                    vec![Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "Int".to_string()),
+                        IRRELEVANT_SOURCE_LOCATION,
+                    )],
+                ),
+                VariantDeclaration::new("Empty".to_string(), IRRELEVANT_SOURCE_LOCATION, vec![]),
+            ]),
+            body: Box::new(Expression::Match {
+                scrutinee: Box::new(Expression::Identifier(
+                    Name::new(TEST_NAMESPACE, "s".to_string()),
+                    IRRELEVANT_SOURCE_LOCATION,
+                )),
+                arms: vec![
+                    MatchArm::new(
+                        Pattern::Variant(
+                            Name::new(TEST_NAMESPACE, "Shape".to_string()),
+                            "Circle".to_string(),
+                            vec![Pattern::Wildcard(IRRELEVANT_SOURCE_LOCATION)],
+                            IRRELEVANT_SOURCE_LOCATION,
+                        ),
+                        Expression::StringLiteral("c".to_string(), IRRELEVANT_SOURCE_LOCATION),
+                    ),
+                    MatchArm::new(
+                        Pattern::Variant(
+                            Name::new(TEST_NAMESPACE, "Shape".to_string()),
+                            "Empty".to_string(),
+                            vec![],
+                            IRRELEVANT_SOURCE_LOCATION,
+                        ),
+                        Expression::StringLiteral("e".to_string(), IRRELEVANT_SOURCE_LOCATION),
+                    ),
+                ],
+                location: IRRELEVANT_SOURCE_LOCATION,
+            }),
+        },
+        0,
+        &mut formatted,
+    )
+    .unwrap();
+    assert_eq!(
+        "enum Shape {\n    Circle(Int)\n    Empty\n}\nmatch s {\n    Shape.Circle(_) => \"c\"\n    Shape.Empty => \"e\"\n}",
+        formatted.as_str()
+    );
+}
+
+#[test_log::test]
+fn test_format_generic_enum() {
+    let type_parameter = Name::new(TEST_NAMESPACE, "T".to_string());
+    let mut formatted = String::new();
+    format_expression(
+        &Expression::TypeDeclaration {
+            name: Name::new(TEST_NAMESPACE, "Option".to_string()),
+            location: IRRELEVANT_SOURCE_LOCATION,
+            type_parameters: vec![type_parameter.clone()],
+            definition: TypeDefinition::Union(vec![
+                VariantDeclaration::new(
+                    "Some".to_string(),
+                    IRRELEVANT_SOURCE_LOCATION,
+                    vec![Expression::Identifier(
+                        type_parameter,
+                        IRRELEVANT_SOURCE_LOCATION,
+                    )],
+                ),
+                VariantDeclaration::new("None".to_string(), IRRELEVANT_SOURCE_LOCATION, vec![]),
+            ]),
+            body: Box::new(Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "Option".to_string()),
+                IRRELEVANT_SOURCE_LOCATION,
+            )),
+        },
+        0,
+        &mut formatted,
+    )
+    .unwrap();
+    assert_eq!(
+        "enum Option(T) {\n    Some(T)\n    None\n}\nOption",
+        formatted.as_str()
+    );
+}
+
+#[test_log::test]
+fn test_format_lambda_type_parameter() {
+    let type_parameter = Name::new(TEST_NAMESPACE, "T".to_string());
+    let value = Name::new(TEST_NAMESPACE, "value".to_string());
+    let mut formatted = String::new();
+    format_expression(
+        &Expression::Lambda {
+            parameters: vec![
+                LambdaParameter::new_type_parameter(
+                    type_parameter.clone(),
+                    IRRELEVANT_SOURCE_LOCATION,
+                ),
+                LambdaParameter::new(
+                    value.clone(),
+                    IRRELEVANT_SOURCE_LOCATION,
+                    Some(Expression::Identifier(
+                        type_parameter,
+                        IRRELEVANT_SOURCE_LOCATION,
+                    )),
+                ),
+            ],
+            return_type: None,
+            body: Box::new(Expression::Identifier(value, IRRELEVANT_SOURCE_LOCATION)),
+        },
+        0,
+        &mut formatted,
+    )
+    .unwrap();
+    assert_eq!("(type T, value: T) => value", formatted.as_str());
+}
+
+#[test]
+fn test_format_binary_operator() {
+    let mut formatted = String::new();
+    format_expression(
+        &Expression::BinaryOperator {
+            operator: Operator::LessOrEquals,
+            left: Box::new(Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "a".to_string()),
+                IRRELEVANT_SOURCE_LOCATION,
+            )),
+            right: Box::new(Expression::IntegerLiteral(
+                1,
+                IntegerBase::Decimal,
+                IRRELEVANT_SOURCE_LOCATION,
+            )),
+            location: IRRELEVANT_SOURCE_LOCATION,
+        },
+        IRRELEVANT_INDENTATION_LEVEL,
+        &mut formatted,
+    )
+    .unwrap();
+    assert_eq!("a <= 1", formatted.as_str());
+}
@@ -26,5 +26,10 @@ mod examples_tests;
 
 pub mod format;
 
+pub mod modules;
+
+#[cfg(test)]
+mod modules_tests;
+
 #[cfg(test)]
 mod format_tests;
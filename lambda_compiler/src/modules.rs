@@ -0,0 +1,357 @@
+use crate::{
+    ast::{self, ModuleReference},
+    compilation::{parse_source, CompilerOutput},
+    type_checking::{
+        check_types_with_default_globals_and_modules, type_from_deep_tree, type_to_deep_tree,
+        DeepType,
+    },
+};
+use astraea::{
+    deep_tree::DeepTree,
+    storage::{LoadError, LoadStoreTree, LoadTree, StoreError, StoreTree},
+    tree::{BlobDigest, HashedTree, Tree, TreeBlob, TreeChildren, TreeDeserializationError},
+};
+use lambda::{
+    expressions::{
+        deserialize_recursively, load_hashed_tree, serialize_recursively, DeepExpression,
+    },
+    name::NamespaceId,
+};
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+/// Provides the sources of modules that are imported by path. The [ModuleCompiler] only asks for paths that it resolved
+/// with [resolve_import_path].
+pub trait SourceLoader {
+    fn load_source(&self, path: &str) -> Option<String>;
+}
+
+impl SourceLoader for BTreeMap<String, String> {
+    fn load_source(&self, path: &str) -> Option<String> {
+        self.get(path).cloned()
+    }
+}
+
+/// Loads the sources from the files below a directory. Paths that could leave the directory are not found.
+#[derive(Debug, Clone)]
+pub struct DirectorySourceLoader {
+    directory: PathBuf,
+}
+
+impl DirectorySourceLoader {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+}
+
+impl SourceLoader for DirectorySourceLoader {
+    fn load_source(&self, path: &str) -> Option<String> {
+        let relative = std::path::Path::new(path);
+        if !relative
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)))
+        {
+            return None;
+        }
+        std::fs::read_to_string(self.directory.join(relative)).ok()
+    }
+}
+
+/// Resolves the `path` of an import relative to the directory of the `importer`, which is [None] for the source that is
+/// compiled directly. The result is relative to the root of the sources and uses `/` as the separator. Absolute paths
+/// and paths that leave the root with `..` are refused.
+pub fn resolve_import_path(importer: Option<&str>, path: &str) -> Result<String, String> {
+    if path.starts_with('/') || std::path::Path::new(path).is_absolute() {
+        return Err(format!("Module {path} must be imported by a relative path"));
+    }
+    let mut components: Vec<&str> = match importer {
+        Some(importer) => {
+            let mut directory: Vec<&str> = importer.split('/').collect();
+            directory.pop();
+            directory
+        }
+        None => Vec::new(),
+    };
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    return Err(format!("Module {path} is outside of the source directory"));
+                }
+            }
+            name => components.push(name),
+        }
+    }
+    Ok(components.join("/"))
+}
+
+/// The value of a module and the type it exports. Importers only need the type to be checked, so modules are compiled
+/// separately and can be shared by digest.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct CompiledModule {
+    pub expression: DeepExpression,
+    pub type_: DeepType,
+}
+
+impl CompiledModule {
+    pub fn new(expression: DeepExpression, type_: DeepType) -> Self {
+        Self { expression, type_ }
+    }
+
+    /// A module is a tree without a blob whose children are the expression and the type.
+    pub async fn store(
+        &self,
+        store_tree: &(dyn StoreTree + Sync),
+    ) -> Result<BlobDigest, StoreError> {
+        let expression = serialize_recursively(&self.expression, store_tree).await?;
+        let type_ = match type_to_deep_tree(&self.type_) {
+            Ok(success) => success,
+            Err(error) => return Err(StoreError::TreeSerializationError(error)),
+        };
+        let children = TreeChildren::try_from(vec![expression, type_.serialize(store_tree).await?])
+            .expect("Two children always fit");
+        store_tree
+            .store_tree(&HashedTree::from(Arc::new(Tree::new(
+                TreeBlob::empty(),
+                children,
+            ))))
+            .await
+    }
+
+    pub async fn load(
+        digest: &BlobDigest,
+        load_tree: &(dyn LoadTree + Sync),
+    ) -> Result<Self, TreeDeserializationError> {
+        let loaded = match load_hashed_tree(digest, load_tree).await {
+            Ok(success) => success,
+            Err(error) => return Err(TreeDeserializationError::Load(error)),
+        };
+        let (expression, type_) = match (
+            loaded.tree().blob().as_slice(),
+            loaded.tree().children().references(),
+        ) {
+            ([], [expression, type_]) => (expression, type_),
+            _ => {
+                return Err(TreeDeserializationError::Load(LoadError::Inconsistency(
+                    *digest,
+                    "A module has no blob and exactly two children".to_string(),
+                )))
+            }
+        };
+        let expression = deserialize_recursively(expression, load_tree).await?;
+        let type_ = match DeepTree::deserialize(type_, load_tree).await {
+            Ok(success) => type_from_deep_tree(&success),
+            Err(error) => return Err(TreeDeserializationError::Load(error)),
+        };
+        Ok(Self::new(expression, type_))
+    }
+}
+
+fn collect_imports(expression: &ast::Expression, imports: &mut Vec<ModuleReference>) {
+    match expression {
+        ast::Expression::Identifier(_, _) => {}
+        ast::Expression::StringLiteral(_, _) => {}
+        ast::Expression::Apply { callee, arguments } => {
+            collect_imports(callee, imports);
+            for argument in arguments {
+                collect_imports(argument, imports);
+            }
+        }
+        ast::Expression::Lambda {
+            parameters,
+            return_type,
+            body,
+        } => {
+            for parameter in parameters {
+                if let Some(type_annotation) = &parameter.type_annotation {
+                    collect_imports(type_annotation, imports);
+                }
+            }
+            if let Some(return_type) = return_type {
+                collect_imports(return_type, imports);
+            }
+            collect_imports(body, imports);
+        }
+        ast::Expression::ConstructTree(children, _) => {
+            for child in children {
+                collect_imports(child, imports);
+            }
+        }
+        ast::Expression::Braces(content) => collect_imports(content, imports),
+        ast::Expression::Let { value, body, .. } => {
+            collect_imports(value, imports);
+            collect_imports(body, imports);
+        }
+        ast::Expression::TypeOf(content) => collect_imports(content, imports),
+        ast::Expression::Comment(_, content, _) => collect_imports(content, imports),
+        ast::Expression::IntegerLiteral(_, _, _) => {}
+        ast::Expression::If {
+            condition,
+            then,
+            otherwise,
+            ..
+        } => {
+            collect_imports(condition, imports);
+            collect_imports(then, imports);
+            collect_imports(otherwise, imports);
+        }
+        ast::Expression::Match {
+            scrutinee, arms, ..
+        } => {
+            collect_imports(scrutinee, imports);
+            for arm in arms {
+                collect_imports(&arm.body, imports);
+            }
+        }
+        ast::Expression::Import { module, body, .. } => {
+            imports.push(module.clone());
+            collect_imports(body, imports);
+        }
+        ast::Expression::TypeDeclaration {
+            definition, body, ..
+        } => {
+            match definition {
+                ast::TypeDefinition::Record(fields) => {
+                    for field in fields {
+                        collect_imports(&field.type_annotation, imports);
+                    }
+                }
+                ast::TypeDefinition::Union(variants) => {
+                    for field in variants.iter().flat_map(|variant| variant.fields.iter()) {
+                        collect_imports(field, imports);
+                    }
+                }
+            }
+            collect_imports(body, imports);
+        }
+        ast::Expression::GetField { value, .. } => collect_imports(value, imports),
+        ast::Expression::BinaryOperator { left, right, .. } => {
+            collect_imports(left, imports);
+            collect_imports(right, imports);
+        }
+    }
+}
+
+/// Compiles modules together with the modules they import. Every module imported by path is compiled once and stored.
+pub struct ModuleCompiler<'t> {
+    source_loader: &'t dyn SourceLoader,
+    storage: &'t (dyn LoadStoreTree + Sync),
+    namespace: NamespaceId,
+    compiled_paths: BTreeMap<String, Result<CompiledModule, String>>,
+    paths_in_progress: Vec<String>,
+}
+
+impl<'t> ModuleCompiler<'t> {
+    /// All modules share the `namespace`, so that they agree on the default globals like `Bool`.
+    pub fn new(
+        source_loader: &'t dyn SourceLoader,
+        storage: &'t (dyn LoadStoreTree + Sync),
+        namespace: NamespaceId,
+    ) -> Self {
+        Self {
+            source_loader,
+            storage,
+            namespace,
+            compiled_paths: BTreeMap::new(),
+            paths_in_progress: Vec::new(),
+        }
+    }
+
+    /// Results in the digest of the stored [CompiledModule] if there were no errors.
+    pub async fn compile_module(
+        &mut self,
+        source: &str,
+    ) -> Result<(CompilerOutput, Option<BlobDigest>), StoreError> {
+        let output = self.compile_source(source).await?;
+        match (&output.entry_point, output.errors.is_empty()) {
+            (Some(entry_point), true) => {
+                let digest =
+                    CompiledModule::new(entry_point.expression.clone(), entry_point.type_.clone())
+                        .store(self.storage)
+                        .await?;
+                Ok((output, Some(digest)))
+            }
+            _ => Ok((output, None)),
+        }
+    }
+
+    pub async fn compile_source(&mut self, source: &str) -> Result<CompilerOutput, StoreError> {
+        let mut parser_output = parse_source(source, &self.namespace);
+        let entry_point = match &parser_output.entry_point {
+            Some(entry_point) => entry_point,
+            None => return Ok(CompilerOutput::new(None, parser_output.errors)),
+        };
+        let mut imports = Vec::new();
+        collect_imports(entry_point, &mut imports);
+        let mut modules = BTreeMap::new();
+        for reference in imports {
+            if modules.contains_key(&reference) {
+                continue;
+            }
+            let resolved = self.resolve(&reference).await?;
+            modules.insert(reference, resolved);
+        }
+        let type_check_result =
+            check_types_with_default_globals_and_modules(entry_point, self.namespace, modules)
+                .await?;
+        parser_output.errors.extend(type_check_result.errors);
+        Ok(CompilerOutput::new(
+            type_check_result.entry_point,
+            parser_output.errors,
+        ))
+    }
+
+    async fn resolve(
+        &mut self,
+        reference: &ModuleReference,
+    ) -> Result<Result<CompiledModule, String>, StoreError> {
+        match reference {
+            ModuleReference::Path(import_path) => {
+                let importer = self.paths_in_progress.last().map(String::as_str);
+                let path = &match resolve_import_path(importer, import_path) {
+                    Ok(success) => success,
+                    Err(error) => return Ok(Err(error)),
+                };
+                if self.paths_in_progress.contains(path) {
+                    return Ok(Err(format!("Module {path} imports itself")));
+                }
+                if let Some(compiled) = self.compiled_paths.get(path) {
+                    return Ok(compiled.clone());
+                }
+                let source = match self.source_loader.load_source(path) {
+                    Some(success) => success,
+                    None => return Ok(Err(format!("Module {path} not found"))),
+                };
+                self.paths_in_progress.push(path.clone());
+                let output = Box::pin(self.compile_source(&source)).await;
+                self.paths_in_progress.pop();
+                let output = output?;
+                let compiled = match (output.entry_point, output.errors.is_empty()) {
+                    (Some(entry_point), true) => {
+                        let compiled =
+                            CompiledModule::new(entry_point.expression, entry_point.type_);
+                        compiled.store(self.storage).await?;
+                        Ok(compiled)
+                    }
+                    _ => Err(format!(
+                        "Module {path} has errors: {}",
+                        output
+                            .errors
+                            .iter()
+                            .map(|error| format!(
+                                "{}:{}: {}",
+                                error.location.line, error.location.column, error.message
+                            ))
+                            .collect::<Vec<_>>()
+                            .join("; ")
+                    )),
+                };
+                self.compiled_paths.insert(path.clone(), compiled.clone());
+                Ok(compiled)
+            }
+            ModuleReference::Digest(digest) => Ok(CompiledModule::load(digest, self.storage)
+                .await
+                .map_err(|error| format!("Module {digest} could not be loaded: {error}"))),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    compilation::{CompilerError, CompilerOutput, SourceLocation},
+    modules::{
+        resolve_import_path, CompiledModule, DirectorySourceLoader, ModuleCompiler, SourceLoader,
+    },
+};
+use astraea::{
+    storage::{InMemoryTreeStorage, StoreTree},
+    tree::{BlobDigest, HashedTree, Tree, TreeBlob, TreeChildren},
+};
+use lambda::{
+    expressions::{apply_evaluated_argument, EvaluationBudget},
+    name::NamespaceId,
+};
+use pretty_assertions::assert_eq;
+use std::{collections::BTreeMap, sync::Arc};
+
+const TEST_SOURCE_NAMESPACE: NamespaceId =
+    NamespaceId([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
+
+const SQUARE_SOURCE: &str = "(n: Int): Int => multiply(n, n)";
+
+fn sources(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+    entries
+        .iter()
+        .map(|(path, source)| (path.to_string(), source.to_string()))
+        .collect()
+}
+
+async fn evaluate(output: CompilerOutput, storage: &InMemoryTreeStorage) -> BlobDigest {
+    assert_eq!(Vec::<CompilerError>::new(), output.errors);
+    let argument = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::empty())))
+        .await
+        .unwrap();
+    apply_evaluated_argument(
+        &output.entry_point.unwrap().expression,
+        &argument,
+        storage,
+        storage,
+        &None,
+        &None,
+        &EvaluationBudget::default(),
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+async fn store_integer(value: i64, storage: &InMemoryTreeStorage) -> BlobDigest {
+    storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::from_postcard_integer(
+            value,
+        ))))
+        .await
+        .unwrap()
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_by_path() {
+    let loader = sources(&[("square.tl", SQUARE_SOURCE)]);
+    let storage = InMemoryTreeStorage::empty();
+    let mut compiler = ModuleCompiler::new(&loader, &storage, TEST_SOURCE_NAMESPACE);
+    let output = compiler
+        .compile_source("import square = \"square.tl\"\n() => square(7)")
+        .await
+        .unwrap();
+    let evaluated = evaluate(output, &storage).await;
+    assert_eq!(store_integer(49, &storage).await, evaluated);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_nested() {
+    let loader = sources(&[
+        ("square.tl", SQUARE_SOURCE),
+        (
+            "fourth_power.tl",
+            "import square = \"square.tl\"\n(n: Int): Int => square(square(n))",
+        ),
+    ]);
+    let storage = InMemoryTreeStorage::empty();
+    let mut compiler = ModuleCompiler::new(&loader, &storage, TEST_SOURCE_NAMESPACE);
+    let output = compiler
+        .compile_source("import fourth_power = \"fourth_power.tl\"\n() => fourth_power(3)")
+        .await
+        .unwrap();
+    let evaluated = evaluate(output, &storage).await;
+    assert_eq!(store_integer(81, &storage).await, evaluated);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_relative_to_importer() {
+    let loader = sources(&[
+        ("square.tl", "(n: Int): Int => 0"),
+        ("lib/square.tl", SQUARE_SOURCE),
+        (
+            "lib/fourth_power.tl",
+            "import square = \"square.tl\"\n(n: Int): Int => square(square(n))",
+        ),
+        (
+            "lib/nested/cube.tl",
+            "import square = \"../square.tl\"\n(n: Int): Int => multiply(square(n), n)",
+        ),
+    ]);
+    let storage = InMemoryTreeStorage::empty();
+    let mut compiler = ModuleCompiler::new(&loader, &storage, TEST_SOURCE_NAMESPACE);
+    let output = compiler
+        .compile_source(
+            "import fourth_power = \"lib/fourth_power.tl\"\nimport cube = \"./lib/nested/cube.tl\"\n() => [fourth_power(3), cube(2)]",
+        )
+        .await
+        .unwrap();
+    let evaluated = evaluate(output, &storage).await;
+    let expected = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::empty(),
+            TreeChildren::try_from(vec![
+                store_integer(81, &storage).await,
+                store_integer(8, &storage).await,
+            ])
+            .unwrap(),
+        ))))
+        .await
+        .unwrap();
+    assert_eq!(expected, evaluated);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_outside_of_sources() {
+    let loader = sources(&[
+        ("../secret.tl", SQUARE_SOURCE),
+        (
+            "lib/escape.tl",
+            "import secret = \"../../secret.tl\"\nsecret",
+        ),
+    ]);
+    let storage = InMemoryTreeStorage::empty();
+    let mut compiler = ModuleCompiler::new(&loader, &storage, TEST_SOURCE_NAMESPACE);
+    for (source, expected_message) in [
+        (
+            "import m = \"../secret.tl\"\nm",
+            "Module ../secret.tl is outside of the source directory",
+        ),
+        (
+            "import m = \"/etc/passwd\"\nm",
+            "Module /etc/passwd must be imported by a relative path",
+        ),
+        (
+            "import m = \"lib/escape.tl\"\nm",
+            "Module lib/escape.tl has errors: 0:7: Module ../../secret.tl is outside of the source directory",
+        ),
+    ] {
+        let output = compiler.compile_source(source).await.unwrap();
+        let expected = CompilerOutput::new(
+            None,
+            vec![CompilerError::new(
+                expected_message.to_string(),
+                SourceLocation::new(0, 7),
+            )],
+        );
+        assert_eq!(expected, output);
+    }
+}
+
+#[test_log::test]
+fn test_resolve_import_path() {
+    assert_eq!(Ok("a.tl".to_string()), resolve_import_path(None, "a.tl"));
+    assert_eq!(Ok("a.tl".to_string()), resolve_import_path(None, "./a.tl"));
+    assert_eq!(
+        Ok("lib/a.tl".to_string()),
+        resolve_import_path(Some("lib/b.tl"), "a.tl")
+    );
+    assert_eq!(
+        Ok("a.tl".to_string()),
+        resolve_import_path(Some("lib/b.tl"), "../a.tl")
+    );
+    assert_eq!(
+        Ok("a.tl".to_string()),
+        resolve_import_path(Some("lib/b.tl"), "../lib/../a.tl")
+    );
+    assert!(resolve_import_path(Some("lib/b.tl"), "../../a.tl").is_err());
+    assert!(resolve_import_path(None, "../a.tl").is_err());
+    assert!(resolve_import_path(Some("b.tl"), "/a.tl").is_err());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_checks_exported_type() {
+    let loader = sources(&[("square.tl", SQUARE_SOURCE)]);
+    let storage = InMemoryTreeStorage::empty();
+    let mut compiler = ModuleCompiler::new(&loader, &storage, TEST_SOURCE_NAMESPACE);
+    let output = compiler
+        .compile_source("import square = \"square.tl\"\n() => square(\"a\")")
+        .await
+        .unwrap();
+    assert_eq!(None, output.entry_point);
+    assert_eq!(1, output.errors.len());
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_by_digest() {
+    let storage = InMemoryTreeStorage::empty();
+    let no_sources = sources(&[]);
+    let mut compiler = ModuleCompiler::new(&no_sources, &storage, TEST_SOURCE_NAMESPACE);
+    let (output, digest) = compiler.compile_module(SQUARE_SOURCE).await.unwrap();
+    assert_eq!(Vec::<CompilerError>::new(), output.errors);
+    let digest = digest.unwrap();
+    let loaded = CompiledModule::load(&digest, &storage).await.unwrap();
+    assert_eq!(output.entry_point.unwrap().type_, loaded.type_);
+
+    // A different compiler only needs the storage to import the module.
+    let mut compiler = ModuleCompiler::new(&no_sources, &storage, TEST_SOURCE_NAMESPACE);
+    let output = compiler
+        .compile_source(&format!(
+            "import square = digest \"{digest}\"\n() => square(5)"
+        ))
+        .await
+        .unwrap();
+    let evaluated = evaluate(output, &storage).await;
+    assert_eq!(store_integer(25, &storage).await, evaluated);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_unknown_digest() {
+    let storage = InMemoryTreeStorage::empty();
+    let no_sources = sources(&[]);
+    let mut compiler = ModuleCompiler::new(&no_sources, &storage, TEST_SOURCE_NAMESPACE);
+    let digest = BlobDigest::hash(b"missing");
+    let output = compiler
+        .compile_source(&format!("import m = digest \"{digest}\"\nm"))
+        .await
+        .unwrap();
+    assert_eq!(None, output.entry_point);
+    assert_eq!(1, output.errors.len());
+    assert!(output.errors[0]
+        .message
+        .starts_with(&format!("Module {digest} could not be loaded: ")));
+    assert_eq!(SourceLocation::new(0, 7), output.errors[0].location);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_missing_module() {
+    let storage = InMemoryTreeStorage::empty();
+    let no_sources = sources(&[]);
+    let mut compiler = ModuleCompiler::new(&no_sources, &storage, TEST_SOURCE_NAMESPACE);
+    let output = compiler
+        .compile_source("import m = \"missing.tl\"\nm")
+        .await
+        .unwrap();
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Module missing.tl not found".to_string(),
+            SourceLocation::new(0, 7),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_cycle() {
+    let loader = sources(&[
+        ("a.tl", "import b = \"b.tl\"\nb"),
+        ("b.tl", "import a = \"a.tl\"\na"),
+    ]);
+    let storage = InMemoryTreeStorage::empty();
+    let mut compiler = ModuleCompiler::new(&loader, &storage, TEST_SOURCE_NAMESPACE);
+    let output = compiler
+        .compile_source("import a = \"a.tl\"\na")
+        .await
+        .unwrap();
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Module a.tl has errors: 0:7: Module b.tl has errors: 0:7: Module a.tl imports itself"
+                .to_string(),
+            SourceLocation::new(0, 7),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_module_with_errors() {
+    let loader = sources(&[("broken.tl", "(n: Int): String => n")]);
+    let storage = InMemoryTreeStorage::empty();
+    let mut compiler = ModuleCompiler::new(&loader, &storage, TEST_SOURCE_NAMESPACE);
+    let output = compiler
+        .compile_source("import broken = \"broken.tl\"\nbroken")
+        .await
+        .unwrap();
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Module broken.tl has errors: 0:20: Body type 'DeepType(Integer)' is not convertible into return type 'DeepType(String)'"
+                .to_string(),
+            SourceLocation::new(0, 7),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_compile_without_modules() {
+    let output = crate::compilation::compile("import m = \"m.tl\"\nm", &TEST_SOURCE_NAMESPACE)
+        .await
+        .unwrap();
+    let expected = CompilerOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Module m.tl not found".to_string(),
+            SourceLocation::new(0, 7),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test(tokio::test)]
+async fn test_import_from_directory() {
+    let loader = DirectorySourceLoader::new(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/modules"),
+    );
+    let source = loader.load_source("main.tl").unwrap();
+    let storage = InMemoryTreeStorage::empty();
+    let mut compiler = ModuleCompiler::new(&loader, &storage, TEST_SOURCE_NAMESPACE);
+    let output = compiler.compile_source(&source).await.unwrap();
+    let evaluated = evaluate(output, &storage).await;
+    let expected = storage
+        .store_tree(&HashedTree::from(Arc::new(Tree::new(
+            TreeBlob::empty(),
+            TreeChildren::try_from(vec![
+                store_integer(4, &storage).await,
+                store_integer(9, &storage).await,
+            ])
+            .unwrap(),
+        ))))
+        .await
+        .unwrap();
+    assert_eq!(expected, evaluated);
+    // the files next to the directory are out of reach
+    assert_eq!(None, loader.load_source("../hello_world.tl"));
+    assert_eq!(None, loader.load_source("../../Cargo.toml"));
+    assert_eq!(
+        None,
+        loader.load_source(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("Cargo.toml")
+                .display()
+                .to_string()
+        )
+    );
+}
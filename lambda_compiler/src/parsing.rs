@@ -1,8 +1,9 @@
 use crate::{
     ast::{self, LambdaParameter},
     compilation::{CompilerError, SourceLocation},
-    tokenization::{IntegerBase, Token, TokenContent},
+    tokenization::{IntegerBase, Operator, Token, TokenContent},
 };
+use astraea::tree::BlobDigest;
 use lambda::name::{Name, NamespaceId};
 
 #[derive(Debug)]
@@ -63,6 +64,7 @@ pub fn peek_next_non_whitespace_token<'t>(
                 | TokenContent::Comma
                 | TokenContent::Comment(_)
                 | TokenContent::Integer(_, _)
+                | TokenContent::Operator(_)
                 | TokenContent::EndOfFile => return Some(token),
             },
             None => return None,
@@ -101,6 +103,7 @@ fn expect_right_brace(
                 TokenContent::FatArrow => {}
                 TokenContent::Comma => {}
                 TokenContent::Integer(_, _) => {}
+                TokenContent::Operator(_) => {}
                 TokenContent::EndOfFile => {}
             }
             Err(ParserError::new(
@@ -112,6 +115,83 @@ fn expect_right_brace(
     }
 }
 
+fn try_skip_left_brace(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    last_location: &SourceLocation,
+) -> ParserResult<bool> {
+    match peek_next_non_whitespace_token(tokens) {
+        Some(non_whitespace) => match &non_whitespace.content {
+            TokenContent::Comment(_) => Err(ParserError::new(
+                "Comments are currently not supported where a left brace could appear.".to_string(),
+                non_whitespace.location,
+            )),
+            TokenContent::Whitespace => unreachable!(),
+            TokenContent::Identifier(_) => Ok(false),
+            TokenContent::Assign => Ok(false),
+            TokenContent::LeftParenthesis => Ok(false),
+            TokenContent::RightParenthesis => Ok(false),
+            TokenContent::LeftBracket => Ok(false),
+            TokenContent::RightBracket => Ok(false),
+            TokenContent::LeftBrace => {
+                pop_next_non_whitespace_token(tokens);
+                Ok(true)
+            }
+            TokenContent::RightBrace => Ok(false),
+            TokenContent::Dot => Ok(false),
+            TokenContent::Colon => Ok(false),
+            TokenContent::Quotes(_) => Ok(false),
+            TokenContent::FatArrow => Ok(false),
+            TokenContent::Comma => Ok(false),
+            TokenContent::Integer(_, _) => Ok(false),
+            TokenContent::Operator(_) => Ok(false),
+            TokenContent::EndOfFile => Ok(false),
+        },
+        None => Err(ParserError::new(
+            "Unexpected end of the tokens where a left brace could appear.".to_string(),
+            *last_location,
+        )),
+    }
+}
+
+fn try_skip_right_brace(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    last_location: &SourceLocation,
+) -> ParserResult<bool> {
+    match peek_next_non_whitespace_token(tokens) {
+        Some(non_whitespace) => match &non_whitespace.content {
+            TokenContent::Comment(_) => Err(ParserError::new(
+                "Comments are currently not supported where a right brace could appear."
+                    .to_string(),
+                non_whitespace.location,
+            )),
+            TokenContent::Whitespace => unreachable!(),
+            TokenContent::Identifier(_) => Ok(false),
+            TokenContent::Assign => Ok(false),
+            TokenContent::LeftParenthesis => Ok(false),
+            TokenContent::RightParenthesis => Ok(false),
+            TokenContent::LeftBracket => Ok(false),
+            TokenContent::RightBracket => Ok(false),
+            TokenContent::LeftBrace => Ok(false),
+            TokenContent::RightBrace => {
+                pop_next_non_whitespace_token(tokens);
+                Ok(true)
+            }
+            TokenContent::Dot => Ok(false),
+            TokenContent::Colon => Ok(false),
+            TokenContent::Quotes(_) => Ok(false),
+            TokenContent::FatArrow => Ok(false),
+            TokenContent::Comma => Ok(false),
+            TokenContent::Integer(_, _) => Ok(false),
+            TokenContent::Operator(_) => Ok(false),
+            TokenContent::EndOfFile => Ok(false),
+        },
+        None => Err(ParserError::new(
+            "Unexpected end of the tokens where a right brace could appear.".to_string(),
+            *last_location,
+        )),
+    }
+}
+
 fn try_skip_left_parenthesis(
     tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
 ) -> ParserResult<bool> {
@@ -140,6 +220,7 @@ fn try_skip_left_parenthesis(
             TokenContent::FatArrow => Ok(false),
             TokenContent::Comma => Ok(false),
             TokenContent::Integer(_, _) => Ok(false),
+            TokenContent::Operator(_) => Ok(false),
             TokenContent::EndOfFile => Ok(false),
         },
         None => todo!(),
@@ -170,12 +251,47 @@ fn try_skip_right_parenthesis(
             TokenContent::FatArrow => false,
             TokenContent::Comma => false,
             TokenContent::Integer(_, _) => false,
+            TokenContent::Operator(_) => false,
             TokenContent::EndOfFile => false,
         },
         None => todo!(),
     }
 }
 
+fn try_skip_dot(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+) -> ParserResult<bool> {
+    match peek_next_non_whitespace_token(tokens) {
+        Some(non_whitespace) => match &non_whitespace.content {
+            TokenContent::Comment(_) => Err(ParserError::new(
+                "Comments are currently not supported where a dot could appear.".to_string(),
+                non_whitespace.location,
+            )),
+            TokenContent::Whitespace => unreachable!(),
+            TokenContent::Identifier(_) => Ok(false),
+            TokenContent::Assign => Ok(false),
+            TokenContent::LeftParenthesis => Ok(false),
+            TokenContent::RightParenthesis => Ok(false),
+            TokenContent::LeftBracket => Ok(false),
+            TokenContent::RightBracket => Ok(false),
+            TokenContent::LeftBrace => Ok(false),
+            TokenContent::RightBrace => Ok(false),
+            TokenContent::Dot => {
+                pop_next_non_whitespace_token(tokens);
+                Ok(true)
+            }
+            TokenContent::Colon => Ok(false),
+            TokenContent::Quotes(_) => Ok(false),
+            TokenContent::FatArrow => Ok(false),
+            TokenContent::Comma => Ok(false),
+            TokenContent::Integer(_, _) => Ok(false),
+            TokenContent::Operator(_) => Ok(false),
+            TokenContent::EndOfFile => Ok(false),
+        },
+        None => Ok(false),
+    }
+}
+
 fn try_skip_assign(
     tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
 ) -> ParserResult<bool> {
@@ -204,6 +320,7 @@ fn try_skip_assign(
             TokenContent::FatArrow => Ok(false),
             TokenContent::Comma => Ok(false),
             TokenContent::Integer(_, _) => Ok(false),
+            TokenContent::Operator(_) => Ok(false),
             TokenContent::EndOfFile => Ok(false),
         },
         None => todo!(),
@@ -240,6 +357,7 @@ fn expect_fat_arrow(
                 }
                 TokenContent::Comma => {}
                 TokenContent::Integer(_, _) => {}
+                TokenContent::Operator(_) => {}
                 TokenContent::EndOfFile => {}
             }
             Err(ParserError::new(
@@ -279,6 +397,7 @@ fn expect_comma(tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>) -
                     return Ok(());
                 }
                 TokenContent::Integer(_, _) => {}
+                TokenContent::Operator(_) => {}
                 TokenContent::EndOfFile => {}
             }
             Err(ParserError::new(
@@ -319,6 +438,7 @@ fn skip_right_bracket(
             TokenContent::FatArrow => Ok(false),
             TokenContent::Comma => Ok(false),
             TokenContent::Integer(_, _) => Ok(false),
+            TokenContent::Operator(_) => Ok(false),
             TokenContent::EndOfFile => Ok(false),
         },
         None => Ok(false),
@@ -361,7 +481,7 @@ fn parse_let(
     local_namespace: &NamespaceId,
     let_location: &SourceLocation,
 ) -> ParserResult<ast::Expression> {
-    let (name, location) = match try_pop_identifier(tokens)? {
+    let (mut name, mut location) = match try_pop_identifier(tokens)? {
         Some((name, location)) => (name, location),
         None => {
             return Err(ParserError::new(
@@ -370,6 +490,15 @@ fn parse_let(
             ))
         }
     };
+    // 'rec' is only a keyword if another identifier follows, so it remains usable as a name.
+    let mut recursive = false;
+    if name.as_str() == "rec" {
+        if let Some((actual_name, actual_location)) = try_pop_identifier(tokens)? {
+            name = actual_name;
+            location = actual_location;
+            recursive = true;
+        }
+    }
     if !try_skip_assign(tokens)? {
         return Err(ParserError::new(
             "Expected '=' after 'let' identifier.".to_string(),
@@ -381,11 +510,237 @@ fn parse_let(
     Ok(ast::Expression::Let {
         name: Name::new(*local_namespace, name),
         location,
+        recursive,
         value: Box::new(value),
         body: Box::new(body),
     })
 }
 
+/// The name of a `struct` or `enum`, its optional type parameters like in `Option(T)` and the left brace that follows.
+fn parse_declared_type_name(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    local_namespace: &NamespaceId,
+    keyword: &str,
+    keyword_location: &SourceLocation,
+) -> ParserResult<(String, SourceLocation, Vec<Name>)> {
+    let (name, location) = match try_pop_identifier(tokens)? {
+        Some((name, location)) => (name, location),
+        None => {
+            return Err(ParserError::new(
+                format!("Expected identifier after '{keyword}' keyword."),
+                *keyword_location,
+            ))
+        }
+    };
+    let mut type_parameters = Vec::new();
+    if try_skip_left_parenthesis(tokens)? {
+        while let Some((parameter_name, _parameter_location)) = try_pop_identifier(tokens)? {
+            type_parameters.push(Name::new(*local_namespace, parameter_name));
+            if !try_skip_comma(tokens)? {
+                break;
+            }
+        }
+        if !try_skip_right_parenthesis(tokens) {
+            return Err(ParserError::new(
+                format!(
+                    "Expected comma or right parenthesis in the type parameters of '{keyword}'."
+                ),
+                location,
+            ));
+        }
+    }
+    if !try_skip_left_brace(tokens, &location)? {
+        return Err(ParserError::new(
+            format!("Expected '{{' after the name in '{keyword}'."),
+            location,
+        ));
+    }
+    Ok((name, location, type_parameters))
+}
+
+/// `struct Point { x: Int y: Int }` followed by the body like in `let`. Fields may be separated by commas.
+fn parse_struct(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    local_namespace: &NamespaceId,
+    struct_location: &SourceLocation,
+) -> ParserResult<ast::Expression> {
+    let (name, location, type_parameters) =
+        parse_declared_type_name(tokens, local_namespace, "struct", struct_location)?;
+    let mut fields = Vec::new();
+    while !try_skip_right_brace(tokens, &location)? {
+        let (field_name, field_location) = match try_pop_identifier(tokens)? {
+            Some((field_name, field_location)) => (field_name, field_location),
+            None => {
+                return Err(ParserError::new(
+                    "Expected a field name in 'struct'.".to_string(),
+                    location,
+                ))
+            }
+        };
+        if !try_skip_colon(tokens)? {
+            return Err(ParserError::new(
+                "Expected ':' after the field name in 'struct'.".to_string(),
+                field_location,
+            ));
+        }
+        let type_annotation = parse_expression(tokens, local_namespace)?;
+        fields.push(ast::FieldDeclaration::new(
+            field_name,
+            field_location,
+            type_annotation,
+        ));
+        try_skip_comma(tokens)?;
+    }
+    let body = parse_expression(tokens, local_namespace)?;
+    Ok(ast::Expression::TypeDeclaration {
+        name: Name::new(*local_namespace, name),
+        location,
+        type_parameters,
+        definition: ast::TypeDefinition::Record(fields),
+        body: Box::new(body),
+    })
+}
+
+/// `enum Shape { Circle(Int) Empty }` followed by the body like in `let`. Variants may be separated by commas.
+fn parse_enum(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    local_namespace: &NamespaceId,
+    enum_location: &SourceLocation,
+) -> ParserResult<ast::Expression> {
+    let (name, location, type_parameters) =
+        parse_declared_type_name(tokens, local_namespace, "enum", enum_location)?;
+    let mut variants = Vec::new();
+    while !try_skip_right_brace(tokens, &location)? {
+        let (variant_name, variant_location) = match try_pop_identifier(tokens)? {
+            Some((variant_name, variant_location)) => (variant_name, variant_location),
+            None => {
+                return Err(ParserError::new(
+                    "Expected a variant name in 'enum'.".to_string(),
+                    location,
+                ))
+            }
+        };
+        let mut fields = Vec::new();
+        if try_skip_left_parenthesis(tokens)? {
+            loop {
+                if try_skip_right_parenthesis(tokens) {
+                    break;
+                }
+                if !fields.is_empty() {
+                    expect_comma(tokens)?;
+                }
+                if try_skip_right_parenthesis(tokens) {
+                    break;
+                }
+                fields.push(parse_expression(tokens, local_namespace)?);
+            }
+        }
+        variants.push(ast::VariantDeclaration::new(
+            variant_name,
+            variant_location,
+            fields,
+        ));
+        try_skip_comma(tokens)?;
+    }
+    let body = parse_expression(tokens, local_namespace)?;
+    Ok(ast::Expression::TypeDeclaration {
+        name: Name::new(*local_namespace, name),
+        location,
+        type_parameters,
+        definition: ast::TypeDefinition::Union(variants),
+        body: Box::new(body),
+    })
+}
+
+fn try_pop_string(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+) -> Option<(String, SourceLocation)> {
+    match peek_next_non_whitespace_token(tokens) {
+        Some(non_whitespace) => match &non_whitespace.content {
+            TokenContent::Quotes(content) => {
+                pop_next_non_whitespace_token(tokens);
+                Some((content.clone(), non_whitespace.location))
+            }
+            TokenContent::Whitespace => unreachable!(),
+            TokenContent::Comment(_) => None,
+            TokenContent::Identifier(_) => None,
+            TokenContent::Assign => None,
+            TokenContent::LeftParenthesis => None,
+            TokenContent::RightParenthesis => None,
+            TokenContent::LeftBracket => None,
+            TokenContent::RightBracket => None,
+            TokenContent::LeftBrace => None,
+            TokenContent::RightBrace => None,
+            TokenContent::Dot => None,
+            TokenContent::Colon => None,
+            TokenContent::FatArrow => None,
+            TokenContent::Comma => None,
+            TokenContent::Integer(_, _) => None,
+            TokenContent::Operator(_) => None,
+            TokenContent::EndOfFile => None,
+        },
+        None => None,
+    }
+}
+
+/// `import name = "path.tl"` or `import name = digest "hex"`, followed by the body like in `let`.
+fn parse_import(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    local_namespace: &NamespaceId,
+    import_location: &SourceLocation,
+) -> ParserResult<ast::Expression> {
+    let (name, location) = match try_pop_identifier(tokens)? {
+        Some((name, location)) => (name, location),
+        None => {
+            return Err(ParserError::new(
+                "Expected identifier after 'import' keyword.".to_string(),
+                *import_location,
+            ))
+        }
+    };
+    if !try_skip_assign(tokens)? {
+        return Err(ParserError::new(
+            "Expected '=' after 'import' identifier.".to_string(),
+            *import_location,
+        ));
+    }
+    let is_digest = match try_pop_identifier(tokens)? {
+        Some((keyword, _)) if keyword.as_str() == "digest" => true,
+        Some((_, keyword_location)) => {
+            return Err(ParserError::new(
+                "Expected a path or 'digest' in 'import'.".to_string(),
+                keyword_location,
+            ))
+        }
+        None => false,
+    };
+    let module = match try_pop_string(tokens) {
+        Some((path, _)) if !is_digest => ast::ModuleReference::Path(path),
+        Some((digest, digest_location)) => match BlobDigest::parse_hex_string(&digest) {
+            Some(parsed) => ast::ModuleReference::Digest(parsed),
+            None => {
+                return Err(ParserError::new(
+                    "Expected 128 hexadecimal digits in the digest of 'import'.".to_string(),
+                    digest_location,
+                ))
+            }
+        },
+        None => {
+            return Err(ParserError::new(
+                "Expected a string in 'import'.".to_string(),
+                *import_location,
+            ))
+        }
+    };
+    let body = parse_expression(tokens, local_namespace)?;
+    Ok(ast::Expression::Import {
+        name: Name::new(*local_namespace, name),
+        location,
+        module,
+        body: Box::new(body),
+    })
+}
+
 fn parse_type_of(
     tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
     local_namespace: &NamespaceId,
@@ -407,6 +762,174 @@ fn parse_type_of(
     Ok(ast::Expression::TypeOf(Box::new(expression)))
 }
 
+fn parse_if(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    local_namespace: &NamespaceId,
+    if_location: &SourceLocation,
+) -> ParserResult<ast::Expression> {
+    let condition = parse_expression(tokens, local_namespace)?;
+    let then = parse_expression(tokens, local_namespace)?;
+    match try_pop_identifier(tokens)? {
+        Some((keyword, _)) if keyword.as_str() == "else" => {}
+        _ => {
+            return Err(ParserError::new(
+                "Expected 'else' after the first branch of 'if'.".to_string(),
+                then.source_location(),
+            ))
+        }
+    }
+    let otherwise = parse_expression(tokens, local_namespace)?;
+    Ok(ast::Expression::If {
+        condition: Box::new(condition),
+        then: Box::new(then),
+        otherwise: Box::new(otherwise),
+        location: *if_location,
+    })
+}
+
+fn parse_pattern(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    local_namespace: &NamespaceId,
+    last_location: &SourceLocation,
+) -> ParserResult<ast::Pattern> {
+    match pop_next_non_whitespace_token(tokens) {
+        Some(non_whitespace) => match &non_whitespace.content {
+            TokenContent::Whitespace => unreachable!(),
+            TokenContent::Identifier(identifier) => match identifier.as_str() {
+                "_" => Ok(ast::Pattern::Wildcard(non_whitespace.location)),
+                "true" => Ok(ast::Pattern::Bool(true, non_whitespace.location)),
+                "false" => Ok(ast::Pattern::Bool(false, non_whitespace.location)),
+                _ => {
+                    let name = Name::new(*local_namespace, identifier.clone());
+                    if try_skip_dot(tokens)? {
+                        parse_variant_pattern(
+                            name,
+                            tokens,
+                            local_namespace,
+                            &non_whitespace.location,
+                        )
+                    } else {
+                        Ok(ast::Pattern::Binding(name, non_whitespace.location))
+                    }
+                }
+            },
+            TokenContent::Integer(value, base) => Ok(ast::Pattern::IntegerLiteral(
+                *value,
+                *base,
+                non_whitespace.location,
+            )),
+            TokenContent::LeftBracket => {
+                let mut elements = Vec::new();
+                loop {
+                    if skip_right_bracket(tokens)? {
+                        break;
+                    }
+                    if !elements.is_empty() {
+                        expect_comma(tokens)?;
+                    }
+                    if skip_right_bracket(tokens)? {
+                        break;
+                    }
+                    elements.push(parse_pattern(
+                        tokens,
+                        local_namespace,
+                        &non_whitespace.location,
+                    )?);
+                }
+                Ok(ast::Pattern::Tree(elements, non_whitespace.location))
+            }
+            TokenContent::Assign
+            | TokenContent::LeftParenthesis
+            | TokenContent::RightParenthesis
+            | TokenContent::RightBracket
+            | TokenContent::LeftBrace
+            | TokenContent::RightBrace
+            | TokenContent::Dot
+            | TokenContent::Colon
+            | TokenContent::Quotes(_)
+            | TokenContent::FatArrow
+            | TokenContent::Comma
+            | TokenContent::Comment(_)
+            | TokenContent::Operator(_)
+            | TokenContent::EndOfFile => Err(ParserError::new(
+                "Expected pattern.".to_string(),
+                non_whitespace.location,
+            )),
+        },
+        None => Err(ParserError::new(
+            "Unexpected end of the tokens where a pattern was expected.".to_string(),
+            *last_location,
+        )),
+    }
+}
+
+/// The rest of `Type.Variant` or `Type.Variant(fields...)` after the dot.
+fn parse_variant_pattern(
+    type_name: Name,
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    local_namespace: &NamespaceId,
+    type_name_location: &SourceLocation,
+) -> ParserResult<ast::Pattern> {
+    let variant = match try_pop_identifier(tokens)? {
+        Some((variant, _)) => variant,
+        None => {
+            return Err(ParserError::new(
+                "Expected a variant name after '.' in pattern.".to_string(),
+                *type_name_location,
+            ))
+        }
+    };
+    let mut fields = Vec::new();
+    if try_skip_left_parenthesis(tokens)? {
+        loop {
+            if try_skip_right_parenthesis(tokens) {
+                break;
+            }
+            if !fields.is_empty() {
+                expect_comma(tokens)?;
+            }
+            if try_skip_right_parenthesis(tokens) {
+                break;
+            }
+            fields.push(parse_pattern(tokens, local_namespace, type_name_location)?);
+        }
+    }
+    Ok(ast::Pattern::Variant(
+        type_name,
+        variant,
+        fields,
+        *type_name_location,
+    ))
+}
+
+fn parse_match(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    local_namespace: &NamespaceId,
+    match_location: &SourceLocation,
+) -> ParserResult<ast::Expression> {
+    let scrutinee = parse_expression(tokens, local_namespace)?;
+    let mut last_location = scrutinee.source_location();
+    if !try_skip_left_brace(tokens, &last_location)? {
+        return Err(ParserError::new(
+            "Expected '{' after the value in 'match'.".to_string(),
+            last_location,
+        ));
+    }
+    let mut arms = Vec::new();
+    while !try_skip_right_brace(tokens, &last_location)? {
+        let pattern = parse_pattern(tokens, local_namespace, &last_location)?;
+        expect_fat_arrow(tokens)?;
+        let body = parse_expression(tokens, local_namespace)?;
+        last_location = body.source_location();
+        arms.push(ast::MatchArm::new(pattern, body));
+    }
+    Ok(ast::Expression::Match {
+        scrutinee: Box::new(scrutinee),
+        arms,
+        location: *match_location,
+    })
+}
+
 fn parse_comment(
     tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
     content: &str,
@@ -446,6 +969,16 @@ fn parse_expression_start<'t>(
                     parse_let(tokens, local_namespace, &non_whitespace.location)
                 } else if identifier.as_str() == "type_of" {
                     parse_type_of(tokens, local_namespace, &non_whitespace.location)
+                } else if identifier.as_str() == "if" {
+                    parse_if(tokens, local_namespace, &non_whitespace.location)
+                } else if identifier.as_str() == "match" {
+                    parse_match(tokens, local_namespace, &non_whitespace.location)
+                } else if identifier.as_str() == "import" {
+                    parse_import(tokens, local_namespace, &non_whitespace.location)
+                } else if identifier.as_str() == "struct" {
+                    parse_struct(tokens, local_namespace, &non_whitespace.location)
+                } else if identifier.as_str() == "enum" {
+                    parse_enum(tokens, local_namespace, &non_whitespace.location)
                 } else {
                     Ok(ast::Expression::Identifier(
                         Name::new(*local_namespace, identifier.clone()),
@@ -516,6 +1049,10 @@ fn parse_expression_start<'t>(
                 pop_next_non_whitespace_token(tokens);
                 parse_integer(*value, *base, &non_whitespace.location)
             }
+            TokenContent::Operator(operator) => Err(ParserError::new(
+                format!("Expected expression, found operator '{operator}'."),
+                non_whitespace.location,
+            )),
         },
         None => todo!(),
     }
@@ -546,35 +1083,114 @@ fn parse_apply(
     })
 }
 
+fn parse_get_field(
+    value: ast::Expression,
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    dot_location: &SourceLocation,
+) -> ParserResult<ast::Expression> {
+    match try_pop_identifier(tokens)? {
+        Some((field, location)) => Ok(ast::Expression::GetField {
+            value: Box::new(value),
+            field,
+            location,
+        }),
+        None => Err(ParserError::new(
+            "Expected a field name after '.'.".to_string(),
+            *dot_location,
+        )),
+    }
+}
+
+/// Operators with a higher precedence bind more tightly. All of them are left-associative.
+fn operator_precedence(operator: Operator) -> u8 {
+    match operator {
+        Operator::Equals
+        | Operator::NotEquals
+        | Operator::Less
+        | Operator::LessOrEquals
+        | Operator::Greater
+        | Operator::GreaterOrEquals => 0,
+        Operator::Plus | Operator::Minus => 1,
+        Operator::Asterisk | Operator::Slash | Operator::Percent => 2,
+    }
+}
+
+fn parse_binary_operators(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'_, Token>>,
+    local_namespace: &NamespaceId,
+    minimum_precedence: u8,
+) -> ParserResult<ast::Expression> {
+    let mut result = parse_operand(tokens, local_namespace)?;
+    loop {
+        let (operator, location) = match peek_next_non_whitespace_token(tokens) {
+            Some(Token {
+                content: TokenContent::Operator(operator),
+                location,
+            }) if operator_precedence(*operator) >= minimum_precedence => (*operator, *location),
+            _ => return Ok(result),
+        };
+        tokens.next();
+        let right =
+            parse_binary_operators(tokens, local_namespace, operator_precedence(operator) + 1)?;
+        result = ast::Expression::BinaryOperator {
+            operator,
+            left: Box::new(result),
+            right: Box::new(right),
+            location,
+        };
+    }
+}
+
 pub fn parse_expression<'t>(
     tokens: &mut std::iter::Peekable<std::slice::Iter<'t, Token>>,
     local_namespace: &NamespaceId,
 ) -> ParserResult<ast::Expression> {
-    let start = parse_expression_start(tokens, local_namespace)?;
-    match peek_next_non_whitespace_token(tokens) {
-        Some(more) => match &more.content {
-            TokenContent::Whitespace => unreachable!(),
-            TokenContent::Identifier(_) => Ok(start),
-            TokenContent::Assign => Ok(start),
-            TokenContent::LeftParenthesis => {
-                tokens.next();
-                parse_apply(start, tokens, local_namespace)
-            }
-            TokenContent::RightParenthesis => Ok(start),
-            TokenContent::LeftBracket => Ok(start),
-            TokenContent::RightBracket => Ok(start),
-            TokenContent::LeftBrace => Ok(start),
-            TokenContent::RightBrace => Ok(start),
-            TokenContent::Dot => Ok(start),
-            TokenContent::Colon => Ok(start),
-            TokenContent::Quotes(_) => Ok(start),
-            TokenContent::FatArrow => Ok(start),
-            TokenContent::Comma => Ok(start),
-            TokenContent::EndOfFile => Ok(start),
-            TokenContent::Integer(_, _) => Ok(start),
-            TokenContent::Comment(_) => Ok(start),
-        },
-        None => todo!(),
+    parse_binary_operators(tokens, local_namespace, 0)
+}
+
+fn parse_operand<'t>(
+    tokens: &mut std::iter::Peekable<std::slice::Iter<'t, Token>>,
+    local_namespace: &NamespaceId,
+) -> ParserResult<ast::Expression> {
+    let mut result = parse_expression_start(tokens, local_namespace)?;
+    // Fields can be read from anything, but only one argument list is applied directly after another so that a lambda
+    // in parentheses can follow an expression. `Option(Int).Some(1)` still works because of the field in between.
+    let mut is_applied = false;
+    loop {
+        match peek_next_non_whitespace_token(tokens) {
+            Some(more) => match &more.content {
+                TokenContent::Whitespace => unreachable!(),
+                TokenContent::Identifier(_) => return Ok(result),
+                TokenContent::Assign => return Ok(result),
+                TokenContent::LeftParenthesis => {
+                    if is_applied {
+                        return Ok(result);
+                    }
+                    tokens.next();
+                    result = parse_apply(result, tokens, local_namespace)?;
+                    is_applied = true;
+                }
+                TokenContent::RightParenthesis => return Ok(result),
+                TokenContent::LeftBracket => return Ok(result),
+                TokenContent::RightBracket => return Ok(result),
+                TokenContent::LeftBrace => return Ok(result),
+                TokenContent::RightBrace => return Ok(result),
+                TokenContent::Dot => {
+                    tokens.next();
+                    result = parse_get_field(result, tokens, &more.location)?;
+                    is_applied = false;
+                }
+                TokenContent::Colon => return Ok(result),
+                TokenContent::Quotes(_) => return Ok(result),
+                TokenContent::FatArrow => return Ok(result),
+                TokenContent::Comma => return Ok(result),
+                TokenContent::EndOfFile => return Ok(result),
+                TokenContent::Integer(_, _) => return Ok(result),
+                TokenContent::Operator(_) => return Ok(result),
+                TokenContent::Comment(_) => return Ok(result),
+            },
+            None => return Ok(result),
+        }
     }
 }
 
@@ -606,6 +1222,7 @@ fn try_pop_identifier(
             TokenContent::FatArrow => Ok(None),
             TokenContent::Comma => Ok(None),
             TokenContent::Integer(_, _) => Ok(None),
+            TokenContent::Operator(_) => Ok(None),
             TokenContent::EndOfFile => Ok(None),
         },
         None => Ok(None),
@@ -639,6 +1256,7 @@ fn try_skip_comma(
                 Ok(true)
             }
             TokenContent::Integer(_, _) => Ok(false),
+            TokenContent::Operator(_) => Ok(false),
             TokenContent::EndOfFile => Ok(false),
         },
         None => Ok(false),
@@ -672,6 +1290,7 @@ fn try_skip_colon(
             TokenContent::FatArrow => Ok(false),
             TokenContent::Comma => Ok(false),
             TokenContent::Integer(_, _) => Ok(false),
+            TokenContent::Operator(_) => Ok(false),
             TokenContent::EndOfFile => Ok(false),
         },
         None => Ok(false),
@@ -684,6 +1303,21 @@ fn parse_lambda<'t>(
 ) -> ParserResult<ast::Expression> {
     let mut parameters = Vec::new();
     while let Some((parameter_name, parameter_location)) = try_pop_identifier(tokens)? {
+        // 'type' is only a keyword if another identifier follows, so it remains usable as a name.
+        if parameter_name.as_str() == "type" {
+            if let Some((type_parameter_name, type_parameter_location)) =
+                try_pop_identifier(tokens)?
+            {
+                parameters.push(LambdaParameter::new_type_parameter(
+                    Name::new(*local_namespace, type_parameter_name),
+                    type_parameter_location,
+                ));
+                if !try_skip_comma(tokens)? {
+                    break;
+                }
+                continue;
+            }
+        }
         let namespaced_name = Name::new(*local_namespace, parameter_name);
         let mut type_annotation = None;
         if try_skip_colon(tokens)? {
@@ -705,10 +1339,19 @@ fn parse_lambda<'t>(
             next_token.location,
         ));
     }
+    let mut return_type = None;
+    // Comments are left for the fat arrow to complain about.
+    if peek_next_non_whitespace_token(tokens)
+        .is_some_and(|token| matches!(token.content, TokenContent::Colon))
+    {
+        pop_next_non_whitespace_token(tokens);
+        return_type = Some(Box::new(parse_expression(tokens, local_namespace)?));
+    }
     expect_fat_arrow(tokens)?;
     let body = parse_expression(tokens, local_namespace)?;
     Ok(ast::Expression::Lambda {
         parameters,
+        return_type,
         body: Box::new(body),
     })
 }
@@ -1,8 +1,9 @@
 use crate::ast::{self, LambdaParameter};
 use crate::compilation::{CompilerError, SourceLocation};
 use crate::parsing::{parse_expression_tolerantly, ParserOutput};
-use crate::tokenization::{Token, TokenContent};
+use crate::tokenization::{IntegerBase, Operator, Token, TokenContent};
 use crate::{parsing::parse_expression, tokenization::tokenize_default_syntax};
+use astraea::tree::BlobDigest;
 use lambda::name::{Name, NamespaceId};
 use pretty_assertions::assert_eq;
 
@@ -98,6 +99,7 @@ fn test_parse_lambda_0_parameters() {
     let name = Name::new(TEST_NAMESPACE, "f".to_string());
     let expected = ast::Expression::Lambda {
         parameters: vec![],
+        return_type: None,
         body: Box::new(ast::Expression::Identifier(
             name,
             SourceLocation { line: 0, column: 6 },
@@ -115,6 +117,7 @@ fn test_parse_lambda_1_parameter_no_type() {
             SourceLocation { line: 0, column: 1 },
             None,
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Identifier(
             name,
             SourceLocation { line: 0, column: 7 },
@@ -144,6 +147,7 @@ fn test_parse_lambda_1_parameter_with_type() {
                     },
                 )),
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 name,
                 SourceLocation {
@@ -191,6 +195,7 @@ fn test_parse_lambda_2_parameters() {
                     None,
                 ),
             ],
+            return_type: None,
             body: Box::new(f),
         };
         test_wellformed_parsing(source, expected);
@@ -207,12 +212,14 @@ fn test_parse_nested_lambda() {
             SourceLocation { line: 0, column: 1 },
             None,
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Lambda {
             parameters: vec![LambdaParameter::new(
                 g,
                 SourceLocation { line: 0, column: 8 },
                 None,
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 f,
                 SourceLocation {
@@ -256,6 +263,7 @@ fn test_parse_function_call_1_argument() {
             SourceLocation { line: 0, column: 1 },
             None,
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Apply {
             callee: Box::new(ast::Expression::Identifier(
                 name.clone(),
@@ -502,6 +510,7 @@ fn test_parse_let() {
                 line: 0,
                 column: source.find("a").unwrap() as u64,
             },
+            recursive: false,
             value: Box::new(ast::Expression::Identifier(
                 Name::new(TEST_NAMESPACE, "b".to_string()),
                 SourceLocation {
@@ -1017,6 +1026,7 @@ fn test_parse_lambda_with_multiple_type_annotations() {
                 )),
             ),
         ],
+        return_type: None,
         body: Box::new(ast::Expression::Identifier(
             name_x,
             SourceLocation {
@@ -1073,12 +1083,14 @@ fn test_parse_let_with_lambda() {
     let expected = ast::Expression::Let {
         name: name_f.clone(),
         location: SourceLocation { line: 0, column: 4 },
+        recursive: false,
         value: Box::new(ast::Expression::Lambda {
             parameters: vec![LambdaParameter::new(
                 name_x.clone(),
                 SourceLocation { line: 0, column: 9 },
                 None,
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 name_x,
                 SourceLocation {
@@ -1225,3 +1237,692 @@ fn test_parse_comment_before_identifier_in_lambda() {
     );
     assert_eq!(expected, output);
 }
+
+#[test_log::test]
+fn test_parse_if() {
+    test_wellformed_parsing(
+        "if c {a} else b",
+        ast::Expression::If {
+            condition: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "c".to_string()),
+                SourceLocation::new(0, 3),
+            )),
+            then: Box::new(ast::Expression::Braces(Box::new(
+                ast::Expression::Identifier(
+                    Name::new(TEST_NAMESPACE, "a".to_string()),
+                    SourceLocation::new(0, 6),
+                ),
+            ))),
+            otherwise: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "b".to_string()),
+                SourceLocation::new(0, 14),
+            )),
+            location: SourceLocation::new(0, 0),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_if_without_else() {
+    let tokens = tokenize_default_syntax("if c a b").expect("tokenization failed");
+    let mut token_iterator = tokens.iter().peekable();
+    let output = parse_expression_tolerantly(&mut token_iterator, &TEST_NAMESPACE);
+    let expected = ParserOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parser error: Expected 'else' after the first branch of 'if'.".to_string(),
+            SourceLocation::new(0, 5),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test]
+fn test_parse_match() {
+    test_wellformed_parsing(
+        "match x {\n    0 => a\n    [true, y, _] => y\n}",
+        ast::Expression::Match {
+            scrutinee: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "x".to_string()),
+                SourceLocation::new(0, 6),
+            )),
+            arms: vec![
+                ast::MatchArm::new(
+                    ast::Pattern::IntegerLiteral(
+                        0,
+                        crate::tokenization::IntegerBase::Decimal,
+                        SourceLocation::new(1, 4),
+                    ),
+                    ast::Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "a".to_string()),
+                        SourceLocation::new(1, 9),
+                    ),
+                ),
+                ast::MatchArm::new(
+                    ast::Pattern::Tree(
+                        vec![
+                            ast::Pattern::Bool(true, SourceLocation::new(2, 5)),
+                            ast::Pattern::Binding(
+                                Name::new(TEST_NAMESPACE, "y".to_string()),
+                                SourceLocation::new(2, 11),
+                            ),
+                            ast::Pattern::Wildcard(SourceLocation::new(2, 14)),
+                        ],
+                        SourceLocation::new(2, 4),
+                    ),
+                    ast::Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "y".to_string()),
+                        SourceLocation::new(2, 20),
+                    ),
+                ),
+            ],
+            location: SourceLocation::new(0, 0),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_match_invalid_pattern() {
+    let tokens = tokenize_default_syntax("match x {\"a\" => b}").expect("tokenization failed");
+    let mut token_iterator = tokens.iter().peekable();
+    let output = parse_expression_tolerantly(&mut token_iterator, &TEST_NAMESPACE);
+    let expected = ParserOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parser error: Expected pattern.".to_string(),
+            SourceLocation::new(0, 9),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+/// The tokenizer always ends with [TokenContent::EndOfFile], but the parser must not panic on tokens without it.
+#[test_log::test]
+fn test_parse_match_without_end_of_file() {
+    for (source, expected_message, expected_location) in [
+        (
+            "match x",
+            "Unexpected end of the tokens where a left brace could appear.",
+            SourceLocation::new(0, 6),
+        ),
+        (
+            "match x {",
+            "Unexpected end of the tokens where a right brace could appear.",
+            SourceLocation::new(0, 6),
+        ),
+        (
+            "match x {0 => a",
+            "Unexpected end of the tokens where a right brace could appear.",
+            SourceLocation::new(0, 14),
+        ),
+    ] {
+        let tokens = tokenize_default_syntax(source).expect("tokenization failed");
+        let (end_of_file, tokens) = tokens.split_last().unwrap();
+        assert_eq!(TokenContent::EndOfFile, end_of_file.content);
+        let mut token_iterator = tokens.iter().peekable();
+        let error = parse_expression(&mut token_iterator, &TEST_NAMESPACE).unwrap_err();
+        assert_eq!(expected_message, error.message, "{}", source);
+        assert_eq!(expected_location, error.location, "{}", source);
+    }
+}
+
+#[test_log::test]
+fn test_parse_let_rec_with_return_type() {
+    test_wellformed_parsing(
+        "let rec f = (): Int => f()\nf",
+        ast::Expression::Let {
+            name: Name::new(TEST_NAMESPACE, "f".to_string()),
+            location: SourceLocation::new(0, 8),
+            recursive: true,
+            value: Box::new(ast::Expression::Lambda {
+                parameters: vec![],
+                return_type: Some(Box::new(ast::Expression::Identifier(
+                    Name::new(TEST_NAMESPACE, "Int".to_string()),
+                    SourceLocation::new(0, 16),
+                ))),
+                body: Box::new(ast::Expression::Apply {
+                    callee: Box::new(ast::Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "f".to_string()),
+                        SourceLocation::new(0, 23),
+                    )),
+                    arguments: vec![],
+                }),
+            }),
+            body: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "f".to_string()),
+                SourceLocation::new(1, 0),
+            )),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_let_named_rec() {
+    test_wellformed_parsing(
+        "let rec = a\nrec",
+        ast::Expression::Let {
+            name: Name::new(TEST_NAMESPACE, "rec".to_string()),
+            location: SourceLocation::new(0, 4),
+            recursive: false,
+            value: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "a".to_string()),
+                SourceLocation::new(0, 10),
+            )),
+            body: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "rec".to_string()),
+                SourceLocation::new(1, 0),
+            )),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_import_path() {
+    test_wellformed_parsing(
+        "import math = \"math.tl\"\nmath",
+        ast::Expression::Import {
+            name: Name::new(TEST_NAMESPACE, "math".to_string()),
+            location: SourceLocation::new(0, 7),
+            module: ast::ModuleReference::Path("math.tl".to_string()),
+            body: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "math".to_string()),
+                SourceLocation::new(1, 0),
+            )),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_import_digest() {
+    let digest = BlobDigest::hash(b"module");
+    test_wellformed_parsing(
+        &format!("import m = digest \"{digest}\"\nm"),
+        ast::Expression::Import {
+            name: Name::new(TEST_NAMESPACE, "m".to_string()),
+            location: SourceLocation::new(0, 7),
+            module: ast::ModuleReference::Digest(digest),
+            body: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "m".to_string()),
+                SourceLocation::new(1, 0),
+            )),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_import_invalid_digest() {
+    let tokens =
+        tokenize_default_syntax("import m = digest \"abc\"\nm").expect("tokenization failed");
+    let mut token_iterator = tokens.iter().peekable();
+    let output = parse_expression_tolerantly(&mut token_iterator, &TEST_NAMESPACE);
+    let expected = ParserOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parser error: Expected 128 hexadecimal digits in the digest of 'import'.".to_string(),
+            SourceLocation::new(0, 18),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test]
+fn test_parse_import_without_path() {
+    let tokens = tokenize_default_syntax("import m = x\nm").expect("tokenization failed");
+    let mut token_iterator = tokens.iter().peekable();
+    let output = parse_expression_tolerantly(&mut token_iterator, &TEST_NAMESPACE);
+    let expected = ParserOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parser error: Expected a path or 'digest' in 'import'.".to_string(),
+            SourceLocation::new(0, 11),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test]
+fn test_parse_struct() {
+    test_wellformed_parsing(
+        "struct Point {x: Int, y: Int}\nPoint",
+        ast::Expression::TypeDeclaration {
+            name: Name::new(TEST_NAMESPACE, "Point".to_string()),
+            location: SourceLocation::new(0, 7),
+            type_parameters: Vec::new(),
+            definition: ast::TypeDefinition::Record(vec![
+                ast::FieldDeclaration::new(
+                    "x".to_string(),
+                    SourceLocation::new(0, 14),
+                    ast::Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "Int".to_string()),
+                        SourceLocation::new(0, 17),
+                    ),
+                ),
+                ast::FieldDeclaration::new(
+                    "y".to_string(),
+                    SourceLocation::new(0, 22),
+                    ast::Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "Int".to_string()),
+                        SourceLocation::new(0, 25),
+                    ),
+                ),
+            ]),
+            body: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "Point".to_string()),
+                SourceLocation::new(1, 0),
+            )),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_enum() {
+    test_wellformed_parsing(
+        "enum Shape {\n    Circle(Int)\n    Empty\n}\nShape",
+        ast::Expression::TypeDeclaration {
+            name: Name::new(TEST_NAMESPACE, "Shape".to_string()),
+            location: SourceLocation::new(0, 5),
+            type_parameters: Vec::new(),
+            definition: ast::TypeDefinition::Union(vec![
+                ast::VariantDeclaration::new(
+                    "Circle".to_string(),
+                    SourceLocation::new(1, 4),
+                    vec![ast::Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "Int".to_string()),
+                        SourceLocation::new(1, 11),
+                    )],
+                ),
+                ast::VariantDeclaration::new(
+                    "Empty".to_string(),
+                    SourceLocation::new(2, 4),
+                    vec![],
+                ),
+            ]),
+            body: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "Shape".to_string()),
+                SourceLocation::new(4, 0),
+            )),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_get_field() {
+    test_wellformed_parsing(
+        "Shape.Circle(1)",
+        ast::Expression::Apply {
+            callee: Box::new(ast::Expression::GetField {
+                value: Box::new(ast::Expression::Identifier(
+                    Name::new(TEST_NAMESPACE, "Shape".to_string()),
+                    SourceLocation::new(0, 0),
+                )),
+                field: "Circle".to_string(),
+                location: SourceLocation::new(0, 6),
+            }),
+            arguments: vec![ast::Expression::IntegerLiteral(
+                1,
+                IntegerBase::Decimal,
+                SourceLocation::new(0, 13),
+            )],
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_get_field_chain() {
+    test_wellformed_parsing(
+        "a.b.c",
+        ast::Expression::GetField {
+            value: Box::new(ast::Expression::GetField {
+                value: Box::new(ast::Expression::Identifier(
+                    Name::new(TEST_NAMESPACE, "a".to_string()),
+                    SourceLocation::new(0, 0),
+                )),
+                field: "b".to_string(),
+                location: SourceLocation::new(0, 2),
+            }),
+            field: "c".to_string(),
+            location: SourceLocation::new(0, 4),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_variant_pattern() {
+    test_wellformed_parsing(
+        "match x {\n    Shape.Circle(r) => r\n    Shape.Empty => 0\n}",
+        ast::Expression::Match {
+            scrutinee: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "x".to_string()),
+                SourceLocation::new(0, 6),
+            )),
+            arms: vec![
+                ast::MatchArm::new(
+                    ast::Pattern::Variant(
+                        Name::new(TEST_NAMESPACE, "Shape".to_string()),
+                        "Circle".to_string(),
+                        vec![ast::Pattern::Binding(
+                            Name::new(TEST_NAMESPACE, "r".to_string()),
+                            SourceLocation::new(1, 17),
+                        )],
+                        SourceLocation::new(1, 4),
+                    ),
+                    ast::Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "r".to_string()),
+                        SourceLocation::new(1, 23),
+                    ),
+                ),
+                ast::MatchArm::new(
+                    ast::Pattern::Variant(
+                        Name::new(TEST_NAMESPACE, "Shape".to_string()),
+                        "Empty".to_string(),
+                        vec![],
+                        SourceLocation::new(2, 4),
+                    ),
+                    ast::Expression::IntegerLiteral(
+                        0,
+                        IntegerBase::Decimal,
+                        SourceLocation::new(2, 19),
+                    ),
+                ),
+            ],
+            location: SourceLocation::new(0, 0),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_struct_without_brace() {
+    let tokens = tokenize_default_syntax("struct Point x: Int").expect("tokenization failed");
+    let mut token_iterator = tokens.iter().peekable();
+    let output = parse_expression_tolerantly(&mut token_iterator, &TEST_NAMESPACE);
+    let expected = ParserOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parser error: Expected '{' after the name in 'struct'.".to_string(),
+            SourceLocation::new(0, 7),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test]
+fn test_parse_get_field_without_name() {
+    let tokens = tokenize_default_syntax("a.(").expect("tokenization failed");
+    let mut token_iterator = tokens.iter().peekable();
+    let output = parse_expression_tolerantly(&mut token_iterator, &TEST_NAMESPACE);
+    let expected = ParserOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parser error: Expected a field name after '.'.".to_string(),
+            SourceLocation::new(0, 1),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test]
+fn test_parse_lambda_type_parameter() {
+    let type_parameter = Name::new(TEST_NAMESPACE, "T".to_string());
+    let value = Name::new(TEST_NAMESPACE, "value".to_string());
+    let expected = ast::Expression::Lambda {
+        parameters: vec![
+            LambdaParameter::new_type_parameter(
+                type_parameter.clone(),
+                SourceLocation { line: 0, column: 6 },
+            ),
+            LambdaParameter::new(
+                value.clone(),
+                SourceLocation { line: 0, column: 9 },
+                Some(ast::Expression::Identifier(
+                    type_parameter,
+                    SourceLocation {
+                        line: 0,
+                        column: 16,
+                    },
+                )),
+            ),
+        ],
+        return_type: None,
+        body: Box::new(ast::Expression::Identifier(
+            value,
+            SourceLocation {
+                line: 0,
+                column: 22,
+            },
+        )),
+    };
+    test_wellformed_parsing(r#"(type T, value: T) => value"#, expected);
+}
+
+#[test_log::test]
+fn test_parse_lambda_parameter_named_type() {
+    let name = Name::new(TEST_NAMESPACE, "type".to_string());
+    let expected = ast::Expression::Lambda {
+        parameters: vec![LambdaParameter::new(
+            name.clone(),
+            SourceLocation { line: 0, column: 1 },
+            None,
+        )],
+        return_type: None,
+        body: Box::new(ast::Expression::Identifier(
+            name,
+            SourceLocation {
+                line: 0,
+                column: 10,
+            },
+        )),
+    };
+    test_wellformed_parsing(r#"(type) => type"#, expected);
+}
+
+#[test_log::test]
+fn test_parse_generic_enum() {
+    let type_parameter = Name::new(TEST_NAMESPACE, "T".to_string());
+    test_wellformed_parsing(
+        "enum Option(T) {\n    Some(T)\n    None\n}\nOption",
+        ast::Expression::TypeDeclaration {
+            name: Name::new(TEST_NAMESPACE, "Option".to_string()),
+            location: SourceLocation::new(0, 5),
+            type_parameters: vec![type_parameter.clone()],
+            definition: ast::TypeDefinition::Union(vec![
+                ast::VariantDeclaration::new(
+                    "Some".to_string(),
+                    SourceLocation::new(1, 4),
+                    vec![ast::Expression::Identifier(
+                        type_parameter,
+                        SourceLocation::new(1, 9),
+                    )],
+                ),
+                ast::VariantDeclaration::new("None".to_string(), SourceLocation::new(2, 4), vec![]),
+            ]),
+            body: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "Option".to_string()),
+                SourceLocation::new(4, 0),
+            )),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_get_field_after_apply() {
+    test_wellformed_parsing(
+        "Option(Int).Some(1)",
+        ast::Expression::Apply {
+            callee: Box::new(ast::Expression::GetField {
+                value: Box::new(ast::Expression::Apply {
+                    callee: Box::new(ast::Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "Option".to_string()),
+                        SourceLocation::new(0, 0),
+                    )),
+                    arguments: vec![ast::Expression::Identifier(
+                        Name::new(TEST_NAMESPACE, "Int".to_string()),
+                        SourceLocation::new(0, 7),
+                    )],
+                }),
+                field: "Some".to_string(),
+                location: SourceLocation::new(0, 12),
+            }),
+            arguments: vec![ast::Expression::IntegerLiteral(
+                1,
+                IntegerBase::Decimal,
+                SourceLocation::new(0, 17),
+            )],
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_generic_struct_without_closing_parenthesis() {
+    let tokens = tokenize_default_syntax("struct Pair(A, B {").expect("tokenization failed");
+    let mut token_iterator = tokens.iter().peekable();
+    let output = parse_expression_tolerantly(&mut token_iterator, &TEST_NAMESPACE);
+    let expected = ParserOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parser error: Expected comma or right parenthesis in the type parameters of 'struct'."
+                .to_string(),
+            SourceLocation::new(0, 7),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+fn test_identifier(name: &str, column: u64) -> ast::Expression {
+    ast::Expression::Identifier(
+        Name::new(TEST_NAMESPACE, name.to_string()),
+        SourceLocation::new(0, column),
+    )
+}
+
+fn test_binary_operator(
+    operator: Operator,
+    left: ast::Expression,
+    right: ast::Expression,
+    column: u64,
+) -> ast::Expression {
+    ast::Expression::BinaryOperator {
+        operator,
+        left: Box::new(left),
+        right: Box::new(right),
+        location: SourceLocation::new(0, column),
+    }
+}
+
+#[test_log::test]
+fn test_parse_operator_precedence() {
+    test_wellformed_parsing(
+        "a + b * c < d % e",
+        test_binary_operator(
+            Operator::Less,
+            test_binary_operator(
+                Operator::Plus,
+                test_identifier("a", 0),
+                test_binary_operator(
+                    Operator::Asterisk,
+                    test_identifier("b", 4),
+                    test_identifier("c", 8),
+                    6,
+                ),
+                2,
+            ),
+            test_binary_operator(
+                Operator::Percent,
+                test_identifier("d", 12),
+                test_identifier("e", 16),
+                14,
+            ),
+            10,
+        ),
+    );
+}
+
+#[test_log::test]
+fn test_parse_operators_are_left_associative() {
+    test_wellformed_parsing(
+        "a - b + c",
+        test_binary_operator(
+            Operator::Plus,
+            test_binary_operator(
+                Operator::Minus,
+                test_identifier("a", 0),
+                test_identifier("b", 4),
+                2,
+            ),
+            test_identifier("c", 8),
+            6,
+        ),
+    );
+}
+
+#[test_log::test]
+fn test_parse_operator_in_braces() {
+    test_wellformed_parsing(
+        "{a - b} * f(c)",
+        test_binary_operator(
+            Operator::Asterisk,
+            ast::Expression::Braces(Box::new(test_binary_operator(
+                Operator::Minus,
+                test_identifier("a", 1),
+                test_identifier("b", 5),
+                3,
+            ))),
+            ast::Expression::Apply {
+                callee: Box::new(test_identifier("f", 10)),
+                arguments: vec![test_identifier("c", 12)],
+            },
+            8,
+        ),
+    );
+}
+
+#[test_log::test]
+fn test_parse_operator_ends_let_value() {
+    test_wellformed_parsing(
+        "let x = a == b\nx",
+        ast::Expression::Let {
+            name: Name::new(TEST_NAMESPACE, "x".to_string()),
+            location: SourceLocation::new(0, 4),
+            recursive: false,
+            value: Box::new(test_binary_operator(
+                Operator::Equals,
+                test_identifier("a", 8),
+                test_identifier("b", 13),
+                10,
+            )),
+            body: Box::new(ast::Expression::Identifier(
+                Name::new(TEST_NAMESPACE, "x".to_string()),
+                SourceLocation::new(1, 0),
+            )),
+        },
+    );
+}
+
+#[test_log::test]
+fn test_parse_operator_missing_operand() {
+    let tokens = tokenize_default_syntax("a *").expect("tokenization failed");
+    let mut token_iterator = tokens.iter().peekable();
+    let output = parse_expression_tolerantly(&mut token_iterator, &TEST_NAMESPACE);
+    let expected = ParserOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parser error: Expected expression, got end of file.".to_string(),
+            SourceLocation::new(0, 3),
+        )],
+    );
+    assert_eq!(expected, output);
+}
+
+#[test_log::test]
+fn test_parse_operator_without_left_operand() {
+    let tokens = tokenize_default_syntax("+ a").expect("tokenization failed");
+    let mut token_iterator = tokens.iter().peekable();
+    let output = parse_expression_tolerantly(&mut token_iterator, &TEST_NAMESPACE);
+    let expected = ParserOutput::new(
+        None,
+        vec![CompilerError::new(
+            "Parser error: Expected expression, found operator '+'.".to_string(),
+            SourceLocation::new(0, 0),
+        )],
+    );
+    assert_eq!(expected, output);
+}
@@ -1,10 +1,11 @@
 use crate::compilation::SourceLocation;
 use arbitrary::Arbitrary;
 use hippeus_parser_generator::{ParseResult, Parser, RegisterId, RegisterValue};
+use lambda::builtins::IntegerOperation;
 use lazy_static::lazy_static;
 use pretty_assertions::assert_ne;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, fmt::Display};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Arbitrary)]
 pub enum IntegerBase {
@@ -45,6 +46,62 @@ pub enum TokenContent {
     Comment(String),
     // 123
     Integer(i64, IntegerBase),
+    // + - * / % == != < <= > >=
+    Operator(Operator),
+}
+
+/// The binary operators on integers in the order of their postcard variant index, which the token parser emits directly.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Arbitrary)]
+pub enum Operator {
+    Plus,
+    Minus,
+    Asterisk,
+    Slash,
+    Percent,
+    Equals,
+    NotEquals,
+    Less,
+    LessOrEquals,
+    Greater,
+    GreaterOrEquals,
+}
+
+impl Operator {
+    /// The builtin that the operator is a shorthand for.
+    pub fn integer_operation(&self) -> IntegerOperation {
+        match self {
+            Operator::Plus => IntegerOperation::Add,
+            Operator::Minus => IntegerOperation::Subtract,
+            Operator::Asterisk => IntegerOperation::Multiply,
+            Operator::Slash => IntegerOperation::Divide,
+            Operator::Percent => IntegerOperation::Remainder,
+            Operator::Equals => IntegerOperation::Equals,
+            Operator::NotEquals => IntegerOperation::NotEquals,
+            Operator::Less => IntegerOperation::LessThan,
+            Operator::LessOrEquals => IntegerOperation::LessThanOrEquals,
+            Operator::Greater => IntegerOperation::GreaterThan,
+            Operator::GreaterOrEquals => IntegerOperation::GreaterThanOrEquals,
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Asterisk => "*",
+            Operator::Slash => "/",
+            Operator::Percent => "%",
+            Operator::Equals => "==",
+            Operator::NotEquals => "!=",
+            Operator::Less => "<",
+            Operator::LessOrEquals => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEquals => ">=",
+        };
+        write!(f, "{symbol}")
+    }
 }
 
 #[derive(PartialEq, Debug, Arbitrary)]
@@ -202,6 +259,7 @@ pub fn tokenize_default_syntax(source: &str) -> Option<Vec<Token>> {
     const INTEGER_ACCUMULATOR: RegisterId = RegisterId(24);
     const ZERO: RegisterId = RegisterId(25);
     const TEN: RegisterId = RegisterId(26);
+    const TOKEN_TAG_OPERATOR: RegisterId = RegisterId(27);
     lazy_static! {
         static ref IDENTIFIER_CHARACTERS: Vec<RegisterValue> = (b'a'..=b'z').chain(b'A'..=b'Z').chain([b'_']).map(RegisterValue::Byte).collect();
         static ref INTEGER_CHARACTERS: Vec<RegisterValue> = (b'0'..=b'9').map(RegisterValue::Byte).collect();
@@ -480,6 +538,25 @@ pub fn tokenize_default_syntax(source: &str) -> Option<Vec<Token>> {
                                             ])),
                                             Box::new(Parser::no_op())
                                         ),
+                                        Parser::IsAnyOf {
+                                            input: SUBSEQUENT_INPUT,
+                                            result: IF_CONDITION,
+                                            candidates: vec![
+                                                RegisterValue::Byte(b'=')
+                                            ]
+                                        },
+                                        Parser::IfElse(
+                                            IF_CONDITION,
+                                            Box::new(Parser::Sequence(vec![
+                                                Parser::ReadInputByte(SUBSEQUENT_INPUT),
+                                                Parser::Constant(TOKEN_TAG_OPERATOR, RegisterValue::Byte(17)),
+                                                Parser::WriteOutputByte(TOKEN_TAG_OPERATOR),
+                                                Parser::Constant(TOKEN_TAG_OPERATOR, RegisterValue::Byte(Operator::Equals as u8)),
+                                                Parser::WriteOutputByte(TOKEN_TAG_OPERATOR),
+                                                Parser::Constant(STILL_SOMETHING_TO_CHECK, RegisterValue::Boolean(false)),
+                                            ])),
+                                            Box::new(Parser::no_op())
+                                        ),
                                     ])),
                                 ),
                                 Parser::IfElse(
@@ -507,6 +584,14 @@ pub fn tokenize_default_syntax(source: &str) -> Option<Vec<Token>> {
                     .chain(parse_single_character(FIRST_INPUT, IS_ANY_OF_RESULT, TOKEN_TAG_COLON, b':', 14))
                     .chain(parse_single_character(FIRST_INPUT, IS_ANY_OF_RESULT, TOKEN_TAG_RIGHT_BRACE, b'}', 13))
                     .chain(parse_single_character(FIRST_INPUT, IS_ANY_OF_RESULT, TOKEN_TAG_LEFT_BRACE, b'{', 12))
+                    .chain(parse_operator(FIRST_INPUT, IS_ANY_OF_RESULT, IS_END_OF_INPUT, SUBSEQUENT_INPUT, TOKEN_TAG_OPERATOR, b'+', Some(Operator::Plus), None))
+                    .chain(parse_operator(FIRST_INPUT, IS_ANY_OF_RESULT, IS_END_OF_INPUT, SUBSEQUENT_INPUT, TOKEN_TAG_OPERATOR, b'-', Some(Operator::Minus), None))
+                    .chain(parse_operator(FIRST_INPUT, IS_ANY_OF_RESULT, IS_END_OF_INPUT, SUBSEQUENT_INPUT, TOKEN_TAG_OPERATOR, b'*', Some(Operator::Asterisk), None))
+                    .chain(parse_operator(FIRST_INPUT, IS_ANY_OF_RESULT, IS_END_OF_INPUT, SUBSEQUENT_INPUT, TOKEN_TAG_OPERATOR, b'/', Some(Operator::Slash), None))
+                    .chain(parse_operator(FIRST_INPUT, IS_ANY_OF_RESULT, IS_END_OF_INPUT, SUBSEQUENT_INPUT, TOKEN_TAG_OPERATOR, b'%', Some(Operator::Percent), None))
+                    .chain(parse_operator(FIRST_INPUT, IS_ANY_OF_RESULT, IS_END_OF_INPUT, SUBSEQUENT_INPUT, TOKEN_TAG_OPERATOR, b'!', None, Some(Operator::NotEquals)))
+                    .chain(parse_operator(FIRST_INPUT, IS_ANY_OF_RESULT, IS_END_OF_INPUT, SUBSEQUENT_INPUT, TOKEN_TAG_OPERATOR, b'<', Some(Operator::Less), Some(Operator::LessOrEquals)))
+                    .chain(parse_operator(FIRST_INPUT, IS_ANY_OF_RESULT, IS_END_OF_INPUT, SUBSEQUENT_INPUT, TOKEN_TAG_OPERATOR, b'>', Some(Operator::Greater), Some(Operator::GreaterOrEquals)))
                     .collect())),
                 ),
             ]);
@@ -538,3 +623,69 @@ fn parse_single_character(
         ),
     ]
 }
+
+/// `character_to_match` is the operator `alone`, or `followed_by_equals` if the next character is `=`. Without an
+/// `alone` operator, the character is only valid in front of `=`.
+#[allow(clippy::too_many_arguments)]
+fn parse_operator(
+    first_input: RegisterId,
+    is_any_of_result: RegisterId,
+    is_end_of_input: RegisterId,
+    subsequent_input: RegisterId,
+    token_tag: RegisterId,
+    character_to_match: u8,
+    alone: Option<Operator>,
+    followed_by_equals: Option<Operator>,
+) -> [Parser; 2] {
+    let write_operator = |operator: Operator| {
+        Parser::Sequence(vec![
+            Parser::Constant(token_tag, RegisterValue::Byte(operator as u8)),
+            Parser::WriteOutputByte(token_tag),
+        ])
+    };
+    let write_alone = match alone {
+        Some(operator) => write_operator(operator),
+        None => Parser::Fail,
+    };
+    let write_token = match followed_by_equals {
+        Some(operator) => Parser::Sequence(vec![
+            Parser::IsEndOfInput(is_end_of_input),
+            Parser::IfElse(
+                is_end_of_input,
+                Box::new(write_alone.clone()),
+                Box::new(Parser::Sequence(vec![
+                    Parser::PeekInputByte(subsequent_input),
+                    Parser::Match {
+                        input: subsequent_input,
+                        cases: BTreeMap::from([(
+                            RegisterValue::Byte(b'='),
+                            Parser::Sequence(vec![
+                                // pop the byte we had peeked at before
+                                Parser::ReadInputByte(subsequent_input),
+                                write_operator(operator),
+                            ]),
+                        )]),
+                        default: Box::new(write_alone),
+                    },
+                ])),
+            ),
+        ]),
+        None => write_alone,
+    };
+    [
+        Parser::IsAnyOf {
+            input: first_input,
+            result: is_any_of_result,
+            candidates: vec![RegisterValue::Byte(character_to_match)],
+        },
+        Parser::IfElse(
+            is_any_of_result,
+            Box::new(Parser::Sequence(vec![
+                Parser::Constant(token_tag, RegisterValue::Byte(17)),
+                Parser::WriteOutputByte(token_tag),
+                write_token,
+            ])),
+            Box::new(Parser::no_op()),
+        ),
+    ]
+}
@@ -1,6 +1,6 @@
 use crate::{
     compilation::SourceLocation,
-    tokenization::{tokenize_default_syntax, Token, TokenContent},
+    tokenization::{tokenize_default_syntax, IntegerBase, Operator, Token, TokenContent},
 };
 use pretty_assertions::assert_eq;
 use test_case::test_case;
@@ -151,16 +151,12 @@ fn test_tokenize_default_syntax_assign_ambiguity_1() {
         "==>==",
         &[
             Token {
-                content: TokenContent::Assign,
+                content: TokenContent::Operator(Operator::Equals),
                 location: SourceLocation { line: 0, column: 0 },
             },
             Token {
-                content: TokenContent::FatArrow,
-                location: SourceLocation { line: 0, column: 1 },
-            },
-            Token {
-                content: TokenContent::Assign,
-                location: SourceLocation { line: 0, column: 3 },
+                content: TokenContent::Operator(Operator::GreaterOrEquals),
+                location: SourceLocation { line: 0, column: 2 },
             },
             Token {
                 content: TokenContent::Assign,
@@ -180,12 +176,33 @@ fn test_tokenize_default_syntax_assign_ambiguity_2() {
         "==>",
         &[
             Token {
-                content: TokenContent::Assign,
+                content: TokenContent::Operator(Operator::Equals),
                 location: SourceLocation { line: 0, column: 0 },
             },
+            Token {
+                content: TokenContent::Operator(Operator::Greater),
+                location: SourceLocation { line: 0, column: 2 },
+            },
+            Token {
+                content: TokenContent::EndOfFile,
+                location: SourceLocation { line: 0, column: 3 },
+            },
+        ],
+    );
+}
+
+#[test_log::test]
+fn test_tokenize_default_syntax_assign_ambiguity_3() {
+    test_tokenize_default_syntax(
+        "=>=",
+        &[
             Token {
                 content: TokenContent::FatArrow,
-                location: SourceLocation { line: 0, column: 1 },
+                location: SourceLocation { line: 0, column: 0 },
+            },
+            Token {
+                content: TokenContent::Assign,
+                location: SourceLocation { line: 0, column: 2 },
             },
             Token {
                 content: TokenContent::EndOfFile,
@@ -592,3 +609,72 @@ fn test_tokenize_default_syntax_integer_decimal(value: i64) {
         ],
     );
 }
+
+#[test_case("+", Operator::Plus)]
+#[test_case("-", Operator::Minus)]
+#[test_case("*", Operator::Asterisk)]
+#[test_case("/", Operator::Slash)]
+#[test_case("%", Operator::Percent)]
+#[test_case("==", Operator::Equals)]
+#[test_case("!=", Operator::NotEquals)]
+#[test_case("<", Operator::Less)]
+#[test_case("<=", Operator::LessOrEquals)]
+#[test_case(">", Operator::Greater)]
+#[test_case(">=", Operator::GreaterOrEquals)]
+fn test_tokenize_default_syntax_operator(source: &str, operator: Operator) {
+    test_tokenize_default_syntax(
+        source,
+        &[
+            Token {
+                content: TokenContent::Operator(operator),
+                location: SourceLocation { line: 0, column: 0 },
+            },
+            Token {
+                content: TokenContent::EndOfFile,
+                location: SourceLocation {
+                    line: 0,
+                    column: source.len() as u64,
+                },
+            },
+        ],
+    );
+}
+
+#[test_log::test]
+fn test_tokenize_default_syntax_operators_between_integers() {
+    test_tokenize_default_syntax(
+        "1<=2-3",
+        &[
+            Token {
+                content: TokenContent::Integer(1, IntegerBase::Decimal),
+                location: SourceLocation { line: 0, column: 0 },
+            },
+            Token {
+                content: TokenContent::Operator(Operator::LessOrEquals),
+                location: SourceLocation { line: 0, column: 1 },
+            },
+            Token {
+                content: TokenContent::Integer(2, IntegerBase::Decimal),
+                location: SourceLocation { line: 0, column: 3 },
+            },
+            Token {
+                content: TokenContent::Operator(Operator::Minus),
+                location: SourceLocation { line: 0, column: 4 },
+            },
+            Token {
+                content: TokenContent::Integer(3, IntegerBase::Decimal),
+                location: SourceLocation { line: 0, column: 5 },
+            },
+            Token {
+                content: TokenContent::EndOfFile,
+                location: SourceLocation { line: 0, column: 6 },
+            },
+        ],
+    );
+}
+
+#[test_log::test]
+fn test_tokenize_default_syntax_exclamation_mark_without_equals() {
+    assert_eq!(None, tokenize_default_syntax("!"));
+    assert_eq!(None, tokenize_default_syntax("!<"));
+}
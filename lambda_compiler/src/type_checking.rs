@@ -1,13 +1,16 @@
 use crate::{
-    ast::{self, LambdaParameter},
+    ast::{self, LambdaParameter, ModuleReference},
     compilation::{CompilerError, CompilerOutput, SourceLocation},
+    modules::CompiledModule,
+    tokenization::Operator,
 };
 use astraea::{
     deep_tree::{DeepTree, DeepTreeChildren},
     storage::StoreError,
-    tree::{ReferenceIndex, TreeBlob, TreeSerializationError},
+    tree::{ReferenceIndex, TreeBlob, TreeSerializationError, TREE_MAX_CHILDREN},
 };
 use lambda::{
+    builtins::{bool_to_tree, integer_to_tree, IntegerOperation},
     expressions::{DeepExpression, Expression},
     name::{Name, NamespaceId},
 };
@@ -30,6 +33,28 @@ where
     Type,
     Named(Name),
     Integer,
+    /// A tree whose children are the values of the fields in the order of their declaration.
+    Record {
+        name: Name,
+        fields: Vec<(String, T)>,
+    },
+    /// A tree whose first child is the integer tag of the variant, which is its index here. The values of the fields of
+    /// the variant follow the tag.
+    Union {
+        name: Name,
+        variants: Vec<(String, Vec<T>)>,
+    },
+    /// A type parameter. It stands for whatever type the generic function or type is instantiated with.
+    Variable(Name),
+    /// A type-level lambda like `Option` in `Option(Int)`. Generic functions have such a type, too, and the type
+    /// arguments are inferred from the arguments at every call site.
+    Generic {
+        parameters: Vec<Name>,
+        body: Box<T>,
+    },
+    /// Stands for the type of an unannotated parameter while the body of its lambda is checked to infer that type.
+    /// The number is the index of the parameter.
+    Inferred(u32),
 }
 
 #[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Hash, Clone)]
@@ -70,6 +95,58 @@ pub fn to_reference_type(deep_type: &DeepType) -> (GenericType<ReferenceIndex>,
         GenericType::Type => (GenericType::Type, Vec::new()),
         GenericType::Named(ref name) => (GenericType::Named(name.clone()), Vec::new()),
         GenericType::Integer => (GenericType::Integer, Vec::new()),
+        GenericType::Record {
+            ref name,
+            ref fields,
+        } => {
+            let mut field_references = Vec::new();
+            let mut children = Vec::new();
+            for (field_name, field_type) in fields {
+                field_references.push((field_name.clone(), ReferenceIndex(children.len() as u64)));
+                children.push(field_type.clone());
+            }
+            (
+                GenericType::Record {
+                    name: name.clone(),
+                    fields: field_references,
+                },
+                children,
+            )
+        }
+        GenericType::Union {
+            ref name,
+            ref variants,
+        } => {
+            let mut variant_references = Vec::new();
+            let mut children = Vec::new();
+            for (variant_name, field_types) in variants {
+                let mut field_references = Vec::new();
+                for field_type in field_types {
+                    field_references.push(ReferenceIndex(children.len() as u64));
+                    children.push(field_type.clone());
+                }
+                variant_references.push((variant_name.clone(), field_references));
+            }
+            (
+                GenericType::Union {
+                    name: name.clone(),
+                    variants: variant_references,
+                },
+                children,
+            )
+        }
+        GenericType::Variable(ref name) => (GenericType::Variable(name.clone()), Vec::new()),
+        GenericType::Generic {
+            ref parameters,
+            ref body,
+        } => (
+            GenericType::Generic {
+                parameters: parameters.clone(),
+                body: Box::new(ReferenceIndex(0)),
+            },
+            vec![body.as_ref().clone()],
+        ),
+        GenericType::Inferred(index) => (GenericType::Inferred(index), Vec::new()),
     }
 }
 
@@ -90,6 +167,17 @@ pub fn type_to_deep_tree(deep_type: &DeepType) -> Result<DeepTree, TreeSerializa
     ))
 }
 
+fn resolve_reference(reference: &ReferenceIndex, children: &[DeepType]) -> DeepType {
+    let index: usize = reference.0.try_into().expect("TODO");
+    if index < children.len() {
+        children[index].clone()
+    } else {
+        // TODO error handling
+        // This should not happen if the tree is well-formed.
+        panic!("Reference index out of bounds: {index}");
+    }
+}
+
 pub fn from_reference_type(body: &GenericType<ReferenceIndex>, children: &[DeepType]) -> DeepType {
     match body {
         GenericType::Any => DeepType(GenericType::Any),
@@ -140,6 +228,183 @@ pub fn from_reference_type(body: &GenericType<ReferenceIndex>, children: &[DeepT
         GenericType::Type => DeepType(GenericType::Type),
         GenericType::Named(ref name) => DeepType(GenericType::Named(name.clone())),
         GenericType::Integer => DeepType(GenericType::Integer),
+        GenericType::Record {
+            ref name,
+            ref fields,
+        } => DeepType(GenericType::Record {
+            name: name.clone(),
+            fields: fields
+                .iter()
+                .map(|(field_name, reference)| {
+                    (field_name.clone(), resolve_reference(reference, children))
+                })
+                .collect(),
+        }),
+        GenericType::Union {
+            ref name,
+            ref variants,
+        } => DeepType(GenericType::Union {
+            name: name.clone(),
+            variants: variants
+                .iter()
+                .map(|(variant_name, references)| {
+                    (
+                        variant_name.clone(),
+                        references
+                            .iter()
+                            .map(|reference| resolve_reference(reference, children))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        }),
+        GenericType::Variable(ref name) => DeepType(GenericType::Variable(name.clone())),
+        GenericType::Generic {
+            ref parameters,
+            ref body,
+        } => DeepType(GenericType::Generic {
+            parameters: parameters.clone(),
+            body: Box::new(resolve_reference(body, children)),
+        }),
+        GenericType::Inferred(index) => DeepType(GenericType::Inferred(*index)),
+    }
+}
+
+/// Applies `transform` to every type that is directly contained in `type_`.
+fn map_child_types(
+    type_: &DeepType,
+    transform: &mut impl FnMut(&DeepType) -> DeepType,
+) -> DeepType {
+    let (body, children) = to_reference_type(type_);
+    let transformed: Vec<DeepType> = children.iter().map(transform).collect();
+    from_reference_type(&body, &transformed)
+}
+
+/// Replaces type parameters with the types bound to them. Nested generic types shadow their own parameters.
+pub fn substitute_type_parameters(
+    type_: &DeepType,
+    bindings: &BTreeMap<Name, DeepType>,
+) -> DeepType {
+    match &type_.0 {
+        GenericType::Variable(name) => bindings.get(name).cloned().unwrap_or_else(|| type_.clone()),
+        GenericType::Generic { parameters, body } => {
+            let mut remaining = bindings.clone();
+            for parameter in parameters {
+                remaining.remove(parameter);
+            }
+            DeepType(GenericType::Generic {
+                parameters: parameters.clone(),
+                body: Box::new(substitute_type_parameters(body, &remaining)),
+            })
+        }
+        _ => map_child_types(type_, &mut |child| {
+            substitute_type_parameters(child, bindings)
+        }),
+    }
+}
+
+fn replace_named_type(type_: &DeepType, name: &Name, replacement: &DeepType) -> DeepType {
+    match &type_.0 {
+        GenericType::Named(named) if named == name => replacement.clone(),
+        _ => map_child_types(type_, &mut |child| {
+            replace_named_type(child, name, replacement)
+        }),
+    }
+}
+
+/// A declared type refers to itself by its name. Reading the type of one of its fields replaces that name with the
+/// whole declared type, so a recursive type is unfolded one level at a time.
+fn unfold(field_type: &DeepType, declared_type: &DeepType) -> DeepType {
+    match &declared_type.0 {
+        GenericType::Record { name, .. } | GenericType::Union { name, .. } => {
+            replace_named_type(field_type, name, declared_type)
+        }
+        _ => field_type.clone(),
+    }
+}
+
+fn generalize(type_parameters: &[Name], type_: DeepType) -> DeepType {
+    if type_parameters.is_empty() {
+        type_
+    } else {
+        DeepType(GenericType::Generic {
+            parameters: type_parameters.to_vec(),
+            body: Box::new(type_),
+        })
+    }
+}
+
+fn split_generic(type_: DeepType) -> (Vec<Name>, DeepType) {
+    match type_.0 {
+        GenericType::Generic { parameters, body } => (parameters, *body),
+        _ => (Vec::new(), type_),
+    }
+}
+
+/// Binds the `parameters` that occur in `pattern` by comparing it with the type of an actual value. The first binding
+/// of a parameter wins. Whether the actual type fits is checked after substituting the bindings.
+fn infer_type_arguments(
+    pattern: &DeepType,
+    actual: &DeepType,
+    parameters: &[Name],
+    bindings: &mut BTreeMap<Name, DeepType>,
+) {
+    match (&pattern.0, &actual.0) {
+        (GenericType::Variable(name), _) if parameters.contains(name) => {
+            bindings
+                .entry(name.clone())
+                .or_insert_with(|| actual.clone());
+        }
+        (GenericType::TreeWithKnownChildTypes(_), GenericType::TreeWithKnownChildTypes(_))
+        | (GenericType::Function { .. }, GenericType::Function { .. })
+        | (GenericType::Record { .. }, GenericType::Record { .. })
+        | (GenericType::Union { .. }, GenericType::Union { .. }) => {
+            let (pattern_body, pattern_children) = to_reference_type(pattern);
+            let (actual_body, actual_children) = to_reference_type(actual);
+            // Only types of the same shape, like the same declaration, can be compared child by child.
+            if pattern_body != actual_body {
+                return;
+            }
+            for (pattern_child, actual_child) in pattern_children.iter().zip(actual_children.iter())
+            {
+                infer_type_arguments(pattern_child, actual_child, parameters, bindings);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Type parameters that were not inferred become `Any`.
+fn instantiate(
+    parameters: &[Name],
+    body: &DeepType,
+    mut bindings: BTreeMap<Name, DeepType>,
+) -> DeepType {
+    for parameter in parameters {
+        bindings
+            .entry(parameter.clone())
+            .or_insert(DeepType(GenericType::Any));
+    }
+    substitute_type_parameters(body, &bindings)
+}
+
+/// A generic function is instantiated with the type arguments inferred from the types of the arguments.
+fn instantiate_for_arguments(callee_type: &DeepType, argument_types: &[DeepType]) -> DeepType {
+    match &callee_type.0 {
+        GenericType::Generic { parameters, body } => {
+            let mut bindings = BTreeMap::new();
+            if let GenericType::Function {
+                parameters: parameter_types,
+                ..
+            } = &body.0
+            {
+                for (parameter_type, argument_type) in parameter_types.iter().zip(argument_types) {
+                    infer_type_arguments(parameter_type, argument_type, parameters, &mut bindings);
+                }
+            }
+            instantiate(parameters, body, bindings)
+        }
+        _ => callee_type.clone(),
     }
 }
 
@@ -202,6 +467,8 @@ async fn check_tree_construction_or_argument_list(
 enum ParameterIndex {
     SingleParameter,
     GetChild(u16),
+    /// The name of a recursive function inside of its own body.
+    CurrentClosure,
 }
 
 impl ParameterIndex {
@@ -218,6 +485,9 @@ impl ParameterIndex {
                     *index,
                 ),
             ),
+            ParameterIndex::CurrentClosure => lambda::expressions::DeepExpression(
+                lambda::expressions::Expression::make_current_closure(),
+            ),
         }
     }
 }
@@ -247,6 +517,8 @@ impl LocalVariable {
 struct LambdaScope {
     names: BTreeMap<Name, LocalVariable>,
     captures: BTreeMap<TypedExpression, u16>,
+    /// Type parameters only exist at compile time, so there is no lambda at runtime that could capture anything.
+    is_transparent: bool,
 }
 
 impl LambdaScope {
@@ -277,6 +549,7 @@ impl LambdaScope {
         Self {
             names,
             captures: BTreeMap::new(),
+            is_transparent: false,
         }
     }
 
@@ -293,9 +566,40 @@ impl LambdaScope {
         Self {
             names,
             captures: BTreeMap::new(),
+            is_transparent: false,
         }
     }
 
+    pub fn new_type_parameter_scope(types: Vec<(Name, DeepTree)>) -> Self {
+        let names = types
+            .into_iter()
+            .map(|(name, type_as_tree)| {
+                (
+                    name,
+                    LocalVariable::new(
+                        ParameterIndex::SingleParameter,
+                        DeepType(GenericType::Type),
+                        Some(type_as_tree),
+                    ),
+                )
+            })
+            .collect();
+        Self {
+            names,
+            captures: BTreeMap::new(),
+            is_transparent: true,
+        }
+    }
+
+    pub fn define_self_reference(&mut self, name: Name, type_: DeepType) {
+        // Parameters shadow the name of the function.
+        self.names.entry(name).or_insert(LocalVariable::new(
+            ParameterIndex::CurrentClosure,
+            type_,
+            None,
+        ));
+    }
+
     pub fn find_parameter_index(
         &self,
         parameter_name: &Name,
@@ -353,9 +657,36 @@ impl LambdaScope {
     }
 }
 
+/// What the body of a lambda does with the value of one of its unannotated parameters.
+#[derive(Debug, Clone)]
+enum Usage {
+    As(DeepType),
+    NeedsKnownType,
+}
+
+#[derive(Debug, Clone)]
+struct ParameterUsage {
+    parameter: u32,
+    usage: Usage,
+    location: SourceLocation,
+}
+
+fn contains_inferred(type_: &DeepType) -> bool {
+    match &type_.0 {
+        GenericType::Inferred(_) => true,
+        _ => to_reference_type(type_).1.iter().any(contains_inferred),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct EnvironmentBuilder {
     lambda_layers: Vec<LambdaScope>,
+    /// Conditions and `true`/`false` patterns need to know which type is `Bool`.
+    bool_type: Option<DeepType>,
+    /// The modules that `import` can refer to, or why they could not be compiled or loaded.
+    modules: BTreeMap<ModuleReference, Result<CompiledModule, String>>,
+    /// Collects the usages of unannotated parameters while the body of their lambda is checked for the first time.
+    type_inference: Option<Vec<ParameterUsage>>,
 }
 
 impl Default for EnvironmentBuilder {
@@ -368,18 +699,103 @@ impl EnvironmentBuilder {
     pub fn new() -> Self {
         Self {
             lambda_layers: Vec::new(),
+            bool_type: None,
+            modules: BTreeMap::new(),
+            type_inference: None,
         }
     }
 
+    pub fn with_bool_type(mut self, bool_type: DeepType) -> Self {
+        self.bool_type = Some(bool_type);
+        self
+    }
+
+    pub fn with_modules(
+        mut self,
+        modules: BTreeMap<ModuleReference, Result<CompiledModule, String>>,
+    ) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    /// Collects the usages of the placeholders for the types of unannotated parameters instead of rejecting them.
+    pub(crate) fn with_type_inference(mut self) -> Self {
+        self.type_inference = Some(Vec::new());
+        self
+    }
+
+    pub fn module(&self, reference: &ModuleReference) -> Option<&Result<CompiledModule, String>> {
+        self.modules.get(reference)
+    }
+
+    pub fn bool_type(&self) -> Option<&DeepType> {
+        self.bool_type.as_ref()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.lambda_layers.is_empty()
     }
 
+    pub fn is_inferring_types(&self) -> bool {
+        self.type_inference.is_some()
+    }
+
+    /// Like [convert_implicitly], but nothing is known about the types of the parameters while they are inferred, so
+    /// their placeholders convert from and into every type then.
+    pub fn convert_implicitly(&self, from: &DeepType, to: &DeepType) -> bool {
+        convert_implicitly_with(from, to, self.is_inferring_types())
+    }
+
+    fn record_usage(&mut self, type_: &DeepType, usage: Usage, location: &SourceLocation) {
+        if let (Some(usages), GenericType::Inferred(parameter)) =
+            (&mut self.type_inference, &type_.0)
+        {
+            usages.push(ParameterUsage {
+                parameter: *parameter,
+                usage,
+                location: *location,
+            });
+        }
+    }
+
+    /// A value of type `from` is used where a value of type `to` is expected.
+    pub fn record_conversion(&mut self, from: &DeepType, to: &DeepType, location: &SourceLocation) {
+        match (&from.0, &to.0) {
+            (GenericType::Inferred(_), _) => {
+                if !matches!(to.0, GenericType::Any) && !contains_inferred(to) {
+                    self.record_usage(from, Usage::As(to.clone()), location);
+                }
+            }
+            (
+                GenericType::TreeWithKnownChildTypes(from_children),
+                GenericType::TreeWithKnownChildTypes(to_children),
+            ) if from_children.len() == to_children.len() => {
+                for (from_child, to_child) in from_children.iter().zip(to_children) {
+                    self.record_conversion(from_child, to_child, location);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// A value of `type_` is used in a way that only works if its type is known, like reading a field.
+    pub fn record_need_for_known_type(&mut self, type_: &DeepType, location: &SourceLocation) {
+        self.record_usage(type_, Usage::NeedsKnownType, location);
+    }
+
     pub fn enter_lambda_body(&mut self, parameters: &[TypeCheckedLambdaParameter]) {
         self.lambda_layers
             .push(LambdaScope::new_lambda_scope(parameters));
     }
 
+    /// Makes the function whose body was just entered available under `name`.
+    pub fn define_self_reference(&mut self, name: Name, type_: DeepType) {
+        self.lambda_layers
+            .last_mut()
+            .expect("A lambda body has to be entered first")
+            .define_self_reference(name, type_);
+    }
+
     pub fn leave_lambda_body(&mut self) -> Vec<TypedExpression> {
         let top_scope = self.lambda_layers.pop().unwrap();
         top_scope.leave()
@@ -400,6 +816,18 @@ impl EnvironmentBuilder {
         }
     }
 
+    /// Makes the types available under their names without creating a lambda at runtime.
+    pub fn enter_type_parameters(&mut self, types: Vec<(Name, DeepTree)>) {
+        self.lambda_layers
+            .push(LambdaScope::new_type_parameter_scope(types));
+    }
+
+    pub fn leave_type_parameters(&mut self) {
+        let top_scope = self.lambda_layers.pop().unwrap();
+        assert!(top_scope.is_transparent);
+        assert!(top_scope.leave().is_empty());
+    }
+
     pub fn read(
         &mut self,
         identifier: &Name,
@@ -456,6 +884,9 @@ impl EnvironmentBuilder {
                             ),
                             Some(compile_time_value),
                         ),
+                        None if layers.last().unwrap().is_transparent => {
+                            (CompilerOutput::new(Some(success), result.0.errors), None)
+                        }
                         None => {
                             let mut errors = result.0.errors;
                             let captured = layers.last_mut().unwrap().capture(success);
@@ -515,9 +946,30 @@ fn is_type(type_of_value: &DeepType) -> bool {
         GenericType::Type => true,
         GenericType::Named(_name) => false,
         GenericType::Integer => false,
+        GenericType::Record { .. } => false,
+        GenericType::Union { .. } => false,
+        GenericType::Variable(_name) => false,
+        GenericType::Generic { .. } => false,
+        GenericType::Inferred(_index) => false,
     }
 }
 
+fn serialize_type(type_: &DeepType) -> DeepTree {
+    type_to_deep_tree(type_).unwrap(/*TODO*/)
+}
+
+fn type_parameter_trees(type_parameters: &[Name]) -> Vec<(Name, DeepTree)> {
+    type_parameters
+        .iter()
+        .map(|name| {
+            (
+                name.clone(),
+                serialize_type(&DeepType(GenericType::Variable(name.clone()))),
+            )
+        })
+        .collect()
+}
+
 struct WithCompilerErrors<T> {
     output: T,
     errors: Vec<CompilerError>,
@@ -529,6 +981,41 @@ impl<T> WithCompilerErrors<T> {
     }
 }
 
+async fn check_type_annotation(
+    type_annotation: &ast::Expression,
+    location: &SourceLocation,
+    environment_builder: &mut EnvironmentBuilder,
+    errors: &mut Vec<CompilerError>,
+) -> Result<DeepType, StoreError> {
+    let checked_type = check_types(type_annotation, environment_builder).await?;
+    errors.extend(checked_type.0.errors);
+    Ok(if let Some(checked) = checked_type.0.entry_point {
+        if is_type(&checked.type_) {
+            match checked_type.1 {
+                Some(compile_time_value) => type_from_deep_tree(&compile_time_value),
+                None => {
+                    errors.push(CompilerError::new(
+                        "Type annotation must be a compile time constant".to_string(),
+                        *location,
+                    ));
+                    // Fallback to Any if the type is not valid.
+                    DeepType(GenericType::Any)
+                }
+            }
+        } else {
+            errors.push(CompilerError::new(
+                "Type annotation must be a type".to_string(),
+                *location,
+            ));
+            // Fallback to Any if the type is not valid.
+            DeepType(GenericType::Any)
+        }
+    } else {
+        // Fallback to Any if the type is not valid.
+        DeepType(GenericType::Any)
+    })
+}
+
 async fn check_lambda_parameters(
     parameters: &[LambdaParameter],
     environment_builder: &mut EnvironmentBuilder,
@@ -538,33 +1025,13 @@ async fn check_lambda_parameters(
     for parameter in parameters {
         let parameter_type: DeepType = match &parameter.type_annotation {
             Some(type_annotation) => {
-                let checked_type = check_types(type_annotation, environment_builder).await?;
-                errors.extend(checked_type.0.errors);
-                if let Some(checked) = checked_type.0.entry_point {
-                    if is_type(&checked.type_) {
-                        match checked_type.1 {
-                            Some(compile_time_value) => type_from_deep_tree(&compile_time_value),
-                            None => {
-                                errors.push(CompilerError::new(
-                                    "Type annotation must be a compile time constant".to_string(),
-                                    parameter.source_location,
-                                ));
-                                // Fallback to Any if the type is not valid.
-                                DeepType(GenericType::Any)
-                            }
-                        }
-                    } else {
-                        errors.push(CompilerError::new(
-                            "Type annotation must be a type".to_string(),
-                            parameter.source_location,
-                        ));
-                        // Fallback to Any if the type is not valid.
-                        DeepType(GenericType::Any)
-                    }
-                } else {
-                    // Fallback to Any if the type is not valid.
-                    DeepType(GenericType::Any)
-                }
+                check_type_annotation(
+                    type_annotation,
+                    &parameter.source_location,
+                    environment_builder,
+                    &mut errors,
+                )
+                .await?
             }
             None => {
                 // If no type annotation is provided, we assume the type is `Any`.
@@ -581,14 +1048,261 @@ async fn check_lambda_parameters(
     Ok(WithCompilerErrors::new(checked_parameters, errors))
 }
 
+/// A recursive function can refer to itself by `recursive_name`. Its type has to be known before the body is checked,
+/// so all of the parameters and the return type need annotations.
 pub async fn check_lambda(
     parameters: &[LambdaParameter],
+    return_type: &Option<Box<ast::Expression>>,
+    body: &ast::Expression,
+    recursive_name: Option<(&Name, &SourceLocation)>,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
+    let type_parameters: Vec<Name> = parameters
+        .iter()
+        .filter(|parameter| parameter.is_type_parameter)
+        .map(|parameter| parameter.name.clone())
+        .collect();
+    // Type parameters are erased, so the function only takes the other parameters at runtime.
+    let value_parameters: Vec<LambdaParameter> = parameters
+        .iter()
+        .filter(|parameter| !parameter.is_type_parameter)
+        .cloned()
+        .collect();
+    environment_builder.enter_type_parameters(type_parameter_trees(&type_parameters));
+    let result = check_lambda_with_type_parameters(
+        &value_parameters,
+        &type_parameters,
+        return_type,
+        body,
+        recursive_name,
+        environment_builder,
+    )
+    .await;
+    environment_builder.leave_type_parameters();
+    let output = result?;
+    Ok(CompilerOutput::new(
+        output.entry_point.map(|checked| {
+            TypedExpression::new(
+                checked.expression,
+                generalize(&type_parameters, checked.type_),
+            )
+        }),
+        output.errors,
+    ))
+}
+
+/// The type parameters have to be in scope already.
+async fn check_lambda_with_type_parameters(
+    parameters: &[LambdaParameter],
+    type_parameters: &[Name],
+    return_type: &Option<Box<ast::Expression>>,
     body: &ast::Expression,
+    recursive_name: Option<(&Name, &SourceLocation)>,
     environment_builder: &mut EnvironmentBuilder,
 ) -> Result<CompilerOutput, StoreError> {
     let checked_parameters = check_lambda_parameters(parameters, environment_builder).await?;
     let mut errors = checked_parameters.errors;
-    environment_builder.enter_lambda_body(&checked_parameters.output[..]);
+    let declared_return_type = match return_type {
+        Some(return_type) => Some(
+            check_type_annotation(
+                return_type,
+                &return_type.source_location(),
+                environment_builder,
+                &mut errors,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+    let self_reference = match recursive_name {
+        Some((name, location)) => {
+            let is_fully_annotated = parameters
+                .iter()
+                .all(|parameter| parameter.type_annotation.is_some());
+            match (&declared_return_type, is_fully_annotated) {
+                (Some(declared_return_type), true) => Some((
+                    name.clone(),
+                    generalize(
+                        type_parameters,
+                        DeepType(GenericType::Function {
+                            parameters: checked_parameters
+                                .output
+                                .iter()
+                                .map(|parameter| parameter.type_.clone())
+                                .collect(),
+                            return_type: Box::new(declared_return_type.clone()),
+                        }),
+                    ),
+                )),
+                _ => {
+                    errors.push(CompilerError::new(
+                        "A recursive function needs type annotations for all of its parameters and its return type".to_string(),
+                        *location,
+                    ));
+                    return Ok(CompilerOutput::new(None, errors));
+                }
+            }
+        }
+        None => None,
+    };
+    let mut checked_parameters = checked_parameters.output;
+    // Lambdas inside of the body are inferred when the body is checked for real. Inferring them during the first check
+    // as well would make the effort grow exponentially with the nesting depth.
+    if !environment_builder.is_inferring_types()
+        && parameters
+            .iter()
+            .any(|parameter| parameter.type_annotation.is_none())
+    {
+        let inferred = infer_parameter_types(
+            &checked_parameters,
+            parameters,
+            &declared_return_type,
+            body,
+            environment_builder,
+        )
+        .await?;
+        if !inferred.errors.is_empty() {
+            errors.extend(inferred.errors);
+            return Ok(CompilerOutput::new(None, errors));
+        }
+        for (checked_parameter, inferred_type) in checked_parameters.iter_mut().zip(inferred.output)
+        {
+            checked_parameter.type_ = inferred_type;
+        }
+    }
+    let output = check_lambda_body(
+        checked_parameters,
+        declared_return_type,
+        self_reference,
+        body,
+        environment_builder,
+    )
+    .await?;
+    errors.extend(output.errors);
+    Ok(CompilerOutput::new(output.entry_point, errors))
+}
+
+/// Checks the body once with placeholders for the types of the unannotated parameters to find out how they are used.
+/// A parameter gets the most specific type it is used as. Parameters whose usage doesn't determine a type need an
+/// annotation.
+async fn infer_parameter_types(
+    checked_parameters: &[TypeCheckedLambdaParameter],
+    parameters: &[LambdaParameter],
+    declared_return_type: &Option<DeepType>,
+    body: &ast::Expression,
+    environment_builder: &EnvironmentBuilder,
+) -> Result<WithCompilerErrors<Vec<DeepType>>, StoreError> {
+    let mut placeholders = Vec::new();
+    for (index, (checked, parameter)) in checked_parameters.iter().zip(parameters).enumerate() {
+        let type_ = match parameter.type_annotation {
+            Some(_) => checked.type_.clone(),
+            None => match index.try_into() {
+                Ok(checked_index) => DeepType(GenericType::Inferred(checked_index)),
+                Err(_) => {
+                    return Ok(WithCompilerErrors::new(
+                        Vec::new(),
+                        vec![CompilerError::new(
+                            format!(
+                                "Too many parameters to infer the type of parameter {}",
+                                parameter.name.key
+                            ),
+                            checked.source_location,
+                        )],
+                    ))
+                }
+            },
+        };
+        placeholders.push(TypeCheckedLambdaParameter::new(
+            checked.name.clone(),
+            checked.source_location,
+            type_,
+            checked.compile_time_value.clone(),
+        ));
+    }
+    // The environment is cloned so that the first check doesn't capture anything. Its errors are reported by the second
+    // check with the inferred types already.
+    let mut inference_environment = environment_builder.clone().with_type_inference();
+    check_lambda_body(
+        placeholders,
+        declared_return_type.clone(),
+        None,
+        body,
+        &mut inference_environment,
+    )
+    .await?;
+    let usages = inference_environment.type_inference.unwrap_or_default();
+    let mut errors = Vec::new();
+    let mut inferred_types = Vec::new();
+    for (index, (checked, parameter)) in checked_parameters.iter().zip(parameters).enumerate() {
+        if parameter.type_annotation.is_some() {
+            inferred_types.push(checked.type_.clone());
+            continue;
+        }
+        let mut inferred: Option<DeepType> = None;
+        let mut first_need_for_known_type = None;
+        for usage in usages
+            .iter()
+            .filter(|usage| usage.parameter as usize == index)
+        {
+            match (&usage.usage, &inferred) {
+                (Usage::As(type_), None) => inferred = Some(type_.clone()),
+                (Usage::As(type_), Some(previous)) => {
+                    if convert_implicitly(type_, previous) {
+                        inferred = Some(type_.clone());
+                    } else if !convert_implicitly(previous, type_) {
+                        errors.push(CompilerError::new(
+                            format!(
+                                "Parameter {} is used as '{:?}' and as '{:?}'",
+                                parameter.name.key, previous, type_
+                            ),
+                            usage.location,
+                        ));
+                    }
+                }
+                (Usage::NeedsKnownType, _) => {
+                    first_need_for_known_type.get_or_insert(usage.location);
+                }
+            }
+        }
+        match (inferred, first_need_for_known_type) {
+            (Some(inferred), _) => inferred_types.push(inferred),
+            (None, Some(location)) => {
+                errors.push(CompilerError::new(
+                    format!(
+                        "The type of parameter {} can't be inferred from its usage, so it needs a type annotation",
+                        parameter.name.key
+                    ),
+                    location,
+                ));
+                inferred_types.push(DeepType(GenericType::Any));
+            }
+            (None, None) => {
+                errors.push(CompilerError::new(
+                    format!(
+                        "The type of parameter {} can't be inferred from its usage, so it needs a type annotation",
+                        parameter.name.key
+                    ),
+                    checked.source_location,
+                ));
+                inferred_types.push(DeepType(GenericType::Any));
+            }
+        }
+    }
+    Ok(WithCompilerErrors::new(inferred_types, errors))
+}
+
+async fn check_lambda_body(
+    checked_parameters: Vec<TypeCheckedLambdaParameter>,
+    declared_return_type: Option<DeepType>,
+    self_reference: Option<(Name, DeepType)>,
+    body: &ast::Expression,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
+    let mut errors = Vec::new();
+    environment_builder.enter_lambda_body(&checked_parameters[..]);
+    if let Some((name, type_)) = self_reference {
+        environment_builder.define_self_reference(name, type_);
+    }
     let body_result = check_types(body, environment_builder).await;
     // TODO: use RAII or something?
     let environment = environment_builder.leave_lambda_body();
@@ -599,25 +1313,49 @@ pub async fn check_lambda(
     let body_output = body_result?;
     errors.extend(body_output.0.errors);
     match body_output.0.entry_point {
-        Some(body_checked) => Ok(CompilerOutput {
-            entry_point: Some(TypedExpression::new(
-                lambda::expressions::DeepExpression(lambda::expressions::Expression::Lambda {
-                    environment: Arc::new(DeepExpression(Expression::make_construct_tree(
-                        environment_expressions,
-                    ))),
-                    body: Arc::new(body_checked.expression),
-                }),
-                DeepType(GenericType::Function {
-                    parameters: checked_parameters
-                        .output
-                        .into_iter()
-                        .map(|parameter| parameter.type_)
-                        .collect(),
-                    return_type: Box::new(body_checked.type_),
-                }),
-            )),
-            errors,
-        }),
+        Some(body_checked) => {
+            let return_type = match declared_return_type {
+                Some(declared_return_type) => {
+                    environment_builder.record_conversion(
+                        &body_checked.type_,
+                        &declared_return_type,
+                        &body.source_location(),
+                    );
+                    if !environment_builder
+                        .convert_implicitly(&body_checked.type_, &declared_return_type)
+                    {
+                        errors.push(CompilerError::new(
+                            format!(
+                                "Body type '{:?}' is not convertible into return type '{:?}'",
+                                body_checked.type_, declared_return_type
+                            ),
+                            body.source_location(),
+                        ));
+                        return Ok(CompilerOutput::new(None, errors));
+                    }
+                    declared_return_type
+                }
+                None => body_checked.type_,
+            };
+            Ok(CompilerOutput {
+                entry_point: Some(TypedExpression::new(
+                    lambda::expressions::DeepExpression(lambda::expressions::Expression::Lambda {
+                        environment: Arc::new(DeepExpression(Expression::make_construct_tree(
+                            environment_expressions,
+                        ))),
+                        body: Arc::new(body_checked.expression),
+                    }),
+                    DeepType(GenericType::Function {
+                        parameters: checked_parameters
+                            .into_iter()
+                            .map(|parameter| parameter.type_)
+                            .collect(),
+                        return_type: Box::new(return_type),
+                    }),
+                )),
+                errors,
+            })
+        }
         None => Ok(CompilerOutput::new(None, errors)),
     }
 }
@@ -625,20 +1363,70 @@ pub async fn check_lambda(
 pub async fn check_let(
     name: &Name,
     location: &SourceLocation,
+    recursive: bool,
     value: &ast::Expression,
     body: &ast::Expression,
     environment_builder: &mut EnvironmentBuilder,
 ) -> Result<CompilerOutput, StoreError> {
-    let value_checked = check_types(value, environment_builder).await?;
-    let value_checked_unwrapped = match value_checked.0.entry_point {
-        Some(success) => success,
-        None => return Ok(value_checked.0),
+    let value_checked = if recursive {
+        match value {
+            ast::Expression::Lambda {
+                parameters,
+                return_type,
+                body,
+            } => (
+                check_lambda(
+                    parameters,
+                    return_type,
+                    body,
+                    Some((name, location)),
+                    environment_builder,
+                )
+                .await?,
+                None,
+            ),
+            _ => {
+                return Ok(CompilerOutput::new(
+                    None,
+                    vec![CompilerError::new(
+                        "The value of 'let rec' has to be a lambda".to_string(),
+                        value.source_location(),
+                    )],
+                ))
+            }
+        }
+    } else {
+        check_types(value, environment_builder).await?
     };
+    let value_checked_unwrapped = match value_checked.0.entry_point {
+        Some(success) => success,
+        None => return Ok(value_checked.0),
+    };
+    bind_value(
+        name,
+        location,
+        value_checked_unwrapped,
+        value_checked.1,
+        body,
+        environment_builder,
+    )
+    .await
+}
+
+/// Compiles `body` with `name` referring to `value`, which is computed only once.
+async fn bind_value(
+    name: &Name,
+    location: &SourceLocation,
+    value: TypedExpression,
+    compile_time_value: Option<DeepTree>,
+    body: &ast::Expression,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
     let checked_parameters = [TypeCheckedLambdaParameter::new(
         name.clone(),
         *location,
-        value_checked_unwrapped.type_.clone(),
-        value_checked.1,
+        value.type_.clone(),
+        compile_time_value,
     )];
     environment_builder.enter_lambda_body(&checked_parameters[..]);
     let body_result = check_types(body, environment_builder).await;
@@ -661,7 +1449,7 @@ pub async fn check_let(
                             body: Arc::new(body_checked.expression),
                         },
                     )),
-                    Arc::new(value_checked_unwrapped.expression),
+                    Arc::new(value.expression),
                 )),
                 body_checked.type_,
             )),
@@ -671,6 +1459,968 @@ pub async fn check_let(
     }
 }
 
+/// The imported module has been compiled separately, so only its exported type matters here.
+async fn check_import(
+    name: &Name,
+    location: &SourceLocation,
+    module: &ModuleReference,
+    body: &ast::Expression,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
+    let compiled = match environment_builder.module(module) {
+        Some(Ok(compiled)) => compiled.clone(),
+        Some(Err(reason)) => {
+            return Ok(CompilerOutput::new(
+                None,
+                vec![CompilerError::new(reason.clone(), *location)],
+            ))
+        }
+        None => {
+            return Ok(CompilerOutput::new(
+                None,
+                vec![CompilerError::new(
+                    format!("Module {module} was not loaded"),
+                    *location,
+                )],
+            ))
+        }
+    };
+    bind_value(
+        name,
+        location,
+        TypedExpression::new(compiled.expression, compiled.type_),
+        None,
+        body,
+        environment_builder,
+    )
+    .await
+}
+
+/// Records are trees of the values of their fields, so a record can't have more fields than a tree has children.
+const MAX_RECORD_FIELDS: usize = TREE_MAX_CHILDREN;
+
+/// The values of the fields of a variant follow its tag in the same tree.
+const MAX_VARIANT_FIELDS: usize = TREE_MAX_CHILDREN - 1;
+
+// Fields are read with their index as the index of a child.
+const _: () = assert!(MAX_RECORD_FIELDS <= u16::MAX as usize + 1);
+
+/// Field type annotations are resolved once here, so the type can be used like any other compile time constant. The
+/// fields can refer to the declared type itself, but only with its own type parameters as the type arguments.
+async fn check_type_declaration(
+    name: &Name,
+    location: &SourceLocation,
+    type_parameters: &[Name],
+    definition: &ast::TypeDefinition,
+    body: &ast::Expression,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
+    let mut types_in_scope = type_parameter_trees(type_parameters);
+    types_in_scope.push((
+        name.clone(),
+        serialize_type(&generalize(
+            type_parameters,
+            DeepType(GenericType::Named(name.clone())),
+        )),
+    ));
+    environment_builder.enter_type_parameters(types_in_scope);
+    let result = check_type_definition(name, location, definition, environment_builder).await;
+    environment_builder.leave_type_parameters();
+    let WithCompilerErrors {
+        output: declared_type,
+        mut errors,
+    } = result?;
+    let declared_type = generalize(type_parameters, declared_type);
+    if !errors.is_empty() {
+        return Ok(CompilerOutput::new(None, errors));
+    }
+    let type_as_tree = match type_to_deep_tree(&declared_type) {
+        Ok(tree) => tree,
+        Err(error) => {
+            errors.push(CompilerError::new(
+                format!("Could not serialize type to tree: {}", error),
+                *location,
+            ));
+            return Ok(CompilerOutput::new(None, errors));
+        }
+    };
+    bind_value(
+        name,
+        location,
+        TypedExpression::new(
+            DeepExpression(Expression::make_literal(type_as_tree.clone())),
+            DeepType(GenericType::Type),
+        ),
+        Some(type_as_tree),
+        body,
+        environment_builder,
+    )
+    .await
+}
+
+async fn check_type_definition(
+    name: &Name,
+    location: &SourceLocation,
+    definition: &ast::TypeDefinition,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<WithCompilerErrors<DeepType>, StoreError> {
+    let mut errors = Vec::new();
+    let declared_type = match definition {
+        ast::TypeDefinition::Record(fields) => {
+            let mut checked_fields: Vec<(String, DeepType)> = Vec::new();
+            for field in fields {
+                let field_type = check_type_annotation(
+                    &field.type_annotation,
+                    &field.location,
+                    environment_builder,
+                    &mut errors,
+                )
+                .await?;
+                if checked_fields
+                    .iter()
+                    .any(|(existing, _)| existing == &field.name)
+                {
+                    errors.push(CompilerError::new(
+                        format!("Field {} is declared more than once", field.name),
+                        field.location,
+                    ));
+                }
+                checked_fields.push((field.name.clone(), field_type));
+            }
+            if checked_fields.len() > MAX_RECORD_FIELDS {
+                errors.push(CompilerError::new(
+                    format!(
+                        "{} has {} fields, but a record can have at most {}",
+                        name.key,
+                        checked_fields.len(),
+                        MAX_RECORD_FIELDS
+                    ),
+                    *location,
+                ));
+            }
+            DeepType(GenericType::Record {
+                name: name.clone(),
+                fields: checked_fields,
+            })
+        }
+        ast::TypeDefinition::Union(variants) => {
+            let mut checked_variants: Vec<(String, Vec<DeepType>)> = Vec::new();
+            for variant in variants {
+                let mut field_types = Vec::new();
+                for field in &variant.fields {
+                    field_types.push(
+                        check_type_annotation(
+                            field,
+                            &field.source_location(),
+                            environment_builder,
+                            &mut errors,
+                        )
+                        .await?,
+                    );
+                }
+                if checked_variants
+                    .iter()
+                    .any(|(existing, _)| existing == &variant.name)
+                {
+                    errors.push(CompilerError::new(
+                        format!("Variant {} is declared more than once", variant.name),
+                        variant.location,
+                    ));
+                }
+                if field_types.len() > MAX_VARIANT_FIELDS {
+                    errors.push(CompilerError::new(
+                        format!(
+                            "Variant {} has {} fields, but a variant can have at most {}",
+                            variant.name,
+                            field_types.len(),
+                            MAX_VARIANT_FIELDS
+                        ),
+                        variant.location,
+                    ));
+                }
+                checked_variants.push((variant.name.clone(), field_types));
+            }
+            DeepType(GenericType::Union {
+                name: name.clone(),
+                variants: checked_variants,
+            })
+        }
+    };
+    Ok(WithCompilerErrors::new(declared_type, errors))
+}
+
+/// A generic type constant like `Option` in `Option(Int)`.
+fn as_generic_type_constant(
+    checked: &(CompilerOutput, Option<DeepTree>),
+) -> Option<(Vec<Name>, DeepType)> {
+    match (&checked.0.entry_point, &checked.1) {
+        (Some(entry_point), Some(compile_time_value))
+            if matches!(entry_point.type_.0, GenericType::Type) =>
+        {
+            match type_from_deep_tree(compile_time_value).0 {
+                GenericType::Generic { parameters, body } => Some((parameters, *body)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Applying a generic record type to values constructs a record, so only the first argument tells whether the
+/// arguments are types. Union types are never constructed like that.
+async fn is_type_application(
+    body: &DeepType,
+    arguments: &[ast::Expression],
+    environment_builder: &EnvironmentBuilder,
+) -> Result<bool, StoreError> {
+    if !matches!(body.0, GenericType::Record { .. }) {
+        return Ok(true);
+    }
+    let first_argument = match arguments.first() {
+        Some(first_argument) => first_argument,
+        None => return Ok(false),
+    };
+    // The environment is cloned so that checking the argument doesn't capture anything.
+    let mut environment_builder = environment_builder.clone();
+    let checked = check_types(first_argument, &mut environment_builder).await?;
+    Ok(checked
+        .0
+        .entry_point
+        .is_some_and(|checked| is_type(&checked.type_)))
+}
+
+async fn check_type_application(
+    parameters: &[Name],
+    body: &DeepType,
+    arguments: &[ast::Expression],
+    location: &SourceLocation,
+    mut errors: Vec<CompilerError>,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<(CompilerOutput, Option<DeepTree>), StoreError> {
+    if parameters.len() != arguments.len() {
+        errors.push(CompilerError::new(
+            format!(
+                "Expected {} type arguments, but got {}",
+                parameters.len(),
+                arguments.len()
+            ),
+            *location,
+        ));
+        return Ok((CompilerOutput::new(None, errors), None));
+    }
+    let mut bindings = BTreeMap::new();
+    for (parameter, argument) in parameters.iter().zip(arguments) {
+        let argument_type = check_type_annotation(
+            argument,
+            &argument.source_location(),
+            environment_builder,
+            &mut errors,
+        )
+        .await?;
+        bindings.insert(parameter.clone(), argument_type);
+    }
+    if matches!(body.0, GenericType::Named(_))
+        && bindings
+            .iter()
+            .any(|(parameter, argument)| argument.0 != GenericType::Variable(parameter.clone()))
+    {
+        errors.push(CompilerError::new(
+            "A recursive type can only refer to itself with its own type parameters".to_string(),
+            *location,
+        ));
+    }
+    if !errors.is_empty() {
+        return Ok((CompilerOutput::new(None, errors), None));
+    }
+    let applied = serialize_type(&substitute_type_parameters(body, &bindings));
+    Ok((
+        CompilerOutput::new(
+            Some(TypedExpression::new(
+                DeepExpression(Expression::make_literal(applied.clone())),
+                DeepType(GenericType::Type),
+            )),
+            errors,
+        ),
+        Some(applied),
+    ))
+}
+
+/// A function that puts its arguments into a tree, after the tag of the variant if there is one.
+fn constructor_function(
+    tag: Option<i64>,
+    parameters: Vec<DeepType>,
+    constructed_type: DeepType,
+) -> TypedExpression {
+    let mut children: Vec<Arc<DeepExpression>> = tag
+        .map(|tag| {
+            Arc::new(DeepExpression(Expression::make_literal(integer_to_tree(
+                tag,
+            ))))
+        })
+        .into_iter()
+        .collect();
+    if parameters.len() == 1 {
+        children.push(Arc::new(
+            ParameterIndex::SingleParameter.create_deep_expression(),
+        ));
+    } else {
+        for index in 0..parameters.len() {
+            let checked_index: u16 = index
+                .try_into()
+                .expect("Declarations can't have more fields than fit into a child index");
+            children.push(Arc::new(
+                ParameterIndex::GetChild(checked_index).create_deep_expression(),
+            ));
+        }
+    }
+    TypedExpression::new(
+        DeepExpression(Expression::make_lambda(
+            Arc::new(DeepExpression(Expression::make_construct_tree(Vec::new()))),
+            Arc::new(DeepExpression(Expression::make_construct_tree(children))),
+        )),
+        DeepType(GenericType::Function {
+            parameters,
+            return_type: Box::new(constructed_type),
+        }),
+    )
+}
+
+/// Applying a record type constructs a record from the values of its fields in the order of their declaration.
+fn replace_record_type_with_constructor(
+    callee: TypedExpression,
+    compile_time_value: &Option<DeepTree>,
+) -> TypedExpression {
+    if let (GenericType::Type, Some(compile_time_value)) = (&callee.type_.0, compile_time_value) {
+        let (type_parameters, record_type) = split_generic(type_from_deep_tree(compile_time_value));
+        if let GenericType::Record { fields, .. } = &record_type.0 {
+            let field_types = fields
+                .iter()
+                .map(|(_, field_type)| unfold(field_type, &record_type))
+                .collect();
+            let constructor = constructor_function(None, field_types, record_type.clone());
+            return TypedExpression::new(
+                constructor.expression,
+                generalize(&type_parameters, constructor.type_),
+            );
+        }
+    }
+    callee
+}
+
+/// Reads a field of a record, or refers to a variant of a union type. Variants without fields are values, the others
+/// are functions that take the values of the fields.
+async fn check_get_field(
+    value: &ast::Expression,
+    field: &str,
+    location: &SourceLocation,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
+    let value_output = check_types(value, environment_builder).await?;
+    let mut errors = value_output.0.errors;
+    let value_checked = match value_output.0.entry_point {
+        Some(success) => success,
+        None => return Ok(CompilerOutput::new(None, errors)),
+    };
+    environment_builder.record_need_for_known_type(&value_checked.type_, location);
+    match (&value_checked.type_.0, &value_output.1) {
+        (GenericType::Record { fields, .. }, _) => {
+            if let Some(index) = fields.iter().position(|(name, _)| name == field) {
+                let checked_index: u16 = index
+                    .try_into()
+                    .expect("Declarations can't have more fields than fit into a child index");
+                return Ok(CompilerOutput::new(
+                    Some(TypedExpression::new(
+                        DeepExpression(Expression::make_get_child(
+                            Arc::new(value_checked.expression),
+                            checked_index,
+                        )),
+                        unfold(&fields[index].1, &value_checked.type_),
+                    )),
+                    errors,
+                ));
+            }
+        }
+        (GenericType::Type, Some(compile_time_value)) => {
+            let (type_parameters, union_type) =
+                split_generic(type_from_deep_tree(compile_time_value));
+            if let GenericType::Union { name, variants } = &union_type.0 {
+                let tag = match variants.iter().position(|(variant, _)| variant == field) {
+                    Some(tag) => tag,
+                    None => {
+                        errors.push(CompilerError::new(
+                            format!("{} has no variant {field}", name.key),
+                            *location,
+                        ));
+                        return Ok(CompilerOutput::new(None, errors));
+                    }
+                };
+                let field_types: Vec<DeepType> = variants[tag]
+                    .1
+                    .iter()
+                    .map(|field_type| unfold(field_type, &union_type))
+                    .collect();
+                let constructed = if field_types.is_empty() {
+                    TypedExpression::new(
+                        DeepExpression(Expression::make_construct_tree(vec![Arc::new(
+                            DeepExpression(Expression::make_literal(integer_to_tree(tag as i64))),
+                        )])),
+                        union_type.clone(),
+                    )
+                } else {
+                    constructor_function(Some(tag as i64), field_types, union_type.clone())
+                };
+                return Ok(CompilerOutput::new(
+                    Some(TypedExpression::new(
+                        constructed.expression,
+                        generalize(&type_parameters, constructed.type_),
+                    )),
+                    errors,
+                ));
+            }
+        }
+        _ => {}
+    }
+    errors.push(CompilerError::new(
+        format!("Type '{:?}' has no field '{field}'", value_checked.type_),
+        *location,
+    ));
+    Ok(CompilerOutput::new(None, errors))
+}
+
+/// Branches agree if one of their types converts implicitly into the other, which is then the type of the result.
+fn unify_branch_types(
+    first: &DeepType,
+    second: &DeepType,
+    environment_builder: &EnvironmentBuilder,
+) -> Option<DeepType> {
+    if environment_builder.convert_implicitly(second, first) {
+        Some(first.clone())
+    } else if environment_builder.convert_implicitly(first, second) {
+        Some(second.clone())
+    } else {
+        None
+    }
+}
+
+/// Patterns other than bindings and wildcards only match values of certain types.
+fn pattern_usage(
+    pattern: &ast::Pattern,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Option<Usage> {
+    match pattern {
+        ast::Pattern::Wildcard(_) | ast::Pattern::Binding(_, _) => None,
+        ast::Pattern::IntegerLiteral(_, _, _) => Some(Usage::As(DeepType(GenericType::Integer))),
+        ast::Pattern::Bool(_, _) => environment_builder
+            .bool_type()
+            .map(|bool_type| Usage::As(bool_type.clone())),
+        ast::Pattern::Tree(children, _) => {
+            let child_types = vec![DeepType(GenericType::Any); children.len()];
+            Some(Usage::As(DeepType(GenericType::TreeWithKnownChildTypes(
+                child_types,
+            ))))
+        }
+        ast::Pattern::Variant(type_name, _, _, location) => {
+            let read = environment_builder.read(type_name, location);
+            match (read.0.entry_point, read.1) {
+                (Some(checked), Some(compile_time_value))
+                    if matches!(checked.type_.0, GenericType::Type) =>
+                {
+                    let union_type = type_from_deep_tree(&compile_time_value);
+                    if matches!(union_type.0, GenericType::Union { .. }) {
+                        Some(Usage::As(union_type))
+                    } else {
+                        Some(Usage::NeedsKnownType)
+                    }
+                }
+                _ => Some(Usage::NeedsKnownType),
+            }
+        }
+    }
+}
+
+/// Collects the names bound by the pattern in the order in which the evaluation binds their values.
+fn check_pattern(
+    pattern: &ast::Pattern,
+    type_: &DeepType,
+    bool_type: Option<&DeepType>,
+    bindings: &mut Vec<TypeCheckedLambdaParameter>,
+    errors: &mut Vec<CompilerError>,
+) -> Option<lambda::expressions::Pattern> {
+    let mut mismatch = |description: &str, location: SourceLocation| {
+        errors.push(CompilerError::new(
+            format!("{description} pattern can't match a value of type '{type_:?}'"),
+            location,
+        ));
+        None
+    };
+    match pattern {
+        ast::Pattern::Wildcard(_) => Some(lambda::expressions::Pattern::Wildcard),
+        ast::Pattern::Binding(name, location) => {
+            if bindings.iter().any(|binding| &binding.name == name) {
+                errors.push(CompilerError::new(
+                    format!("Name {name} is bound more than once in this pattern"),
+                    *location,
+                ));
+                return None;
+            }
+            bindings.push(TypeCheckedLambdaParameter::new(
+                name.clone(),
+                *location,
+                type_.clone(),
+                None,
+            ));
+            Some(lambda::expressions::Pattern::Binding)
+        }
+        ast::Pattern::IntegerLiteral(value, _base, location) => match &type_.0 {
+            GenericType::Any | GenericType::Integer => {
+                Some(lambda::expressions::Pattern::Integer(*value))
+            }
+            _ => mismatch("Integer", *location),
+        },
+        ast::Pattern::Bool(value, location) => match bool_type {
+            Some(bool_type) if type_ == bool_type || matches!(&type_.0, GenericType::Any) => {
+                Some(lambda::expressions::Pattern::Bool(*value))
+            }
+            _ => mismatch("Bool", *location),
+        },
+        ast::Pattern::Tree(children, location) => {
+            let child_types = match &type_.0 {
+                GenericType::Any => vec![DeepType(GenericType::Any); children.len()],
+                GenericType::TreeWithKnownChildTypes(child_types)
+                    if child_types.len() == children.len() =>
+                {
+                    child_types.clone()
+                }
+                _ => return mismatch(&format!("Tree with {} children", children.len()), *location),
+            };
+            let mut checked_children = Vec::new();
+            for (child, child_type) in children.iter().zip(child_types.iter()) {
+                checked_children.push(check_pattern(
+                    child, child_type, bool_type, bindings, errors,
+                ));
+            }
+            checked_children
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+                .map(lambda::expressions::Pattern::Tree)
+        }
+        ast::Pattern::Variant(type_name, variant, fields, location) => {
+            let variants = match &type_.0 {
+                GenericType::Union { name, variants } if name == type_name => variants,
+                _ => return mismatch(&format!("Variant {}.{variant}", type_name.key), *location),
+            };
+            let (tag, field_types) = match variants
+                .iter()
+                .enumerate()
+                .find(|(_, (name, _))| name == variant)
+            {
+                Some((tag, (_, field_types))) => (
+                    tag,
+                    field_types
+                        .iter()
+                        .map(|field_type| unfold(field_type, type_))
+                        .collect::<Vec<_>>(),
+                ),
+                None => {
+                    errors.push(CompilerError::new(
+                        format!("{} has no variant {variant}", type_name.key),
+                        *location,
+                    ));
+                    return None;
+                }
+            };
+            if field_types.len() != fields.len() {
+                errors.push(CompilerError::new(
+                    format!(
+                        "Expected {} fields in the pattern of {}.{variant}, but got {}",
+                        field_types.len(),
+                        type_name.key,
+                        fields.len()
+                    ),
+                    *location,
+                ));
+                return None;
+            }
+            let mut checked_children =
+                vec![Some(lambda::expressions::Pattern::Integer(tag as i64))];
+            for (field, field_type) in fields.iter().zip(field_types.iter()) {
+                checked_children.push(check_pattern(
+                    field, field_type, bool_type, bindings, errors,
+                ));
+            }
+            checked_children
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+                .map(lambda::expressions::Pattern::Tree)
+        }
+    }
+}
+
+/// A way to construct a value of a type whose values can be enumerated by how they were constructed.
+enum Constructor {
+    Bool(bool),
+    Tree(Vec<DeepType>),
+    Variant {
+        type_name: Name,
+        name: String,
+        tag: i64,
+        field_types: Vec<DeepType>,
+    },
+}
+
+impl Constructor {
+    /// Values of any other type can't be enumerated, so only bindings and wildcards match all of them.
+    fn enumerate(type_: &DeepType, bool_type: Option<&DeepType>) -> Option<Vec<Constructor>> {
+        match &type_.0 {
+            _ if Some(type_) == bool_type => {
+                Some(vec![Constructor::Bool(true), Constructor::Bool(false)])
+            }
+            GenericType::TreeWithKnownChildTypes(child_types) => {
+                Some(vec![Constructor::Tree(child_types.clone())])
+            }
+            GenericType::Union { name, variants } => Some(
+                variants
+                    .iter()
+                    .enumerate()
+                    .map(|(tag, (variant_name, field_types))| Constructor::Variant {
+                        type_name: name.clone(),
+                        name: variant_name.clone(),
+                        tag: tag as i64,
+                        field_types: field_types
+                            .iter()
+                            .map(|field_type| unfold(field_type, type_))
+                            .collect(),
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    fn field_types(&self) -> &[DeepType] {
+        match self {
+            Constructor::Bool(_) => &[],
+            Constructor::Tree(child_types) => child_types,
+            Constructor::Variant { field_types, .. } => field_types,
+        }
+    }
+
+    /// The patterns for the fields if the pattern matches values made by this constructor.
+    fn specialize(
+        &self,
+        pattern: &lambda::expressions::Pattern,
+    ) -> Option<Vec<lambda::expressions::Pattern>> {
+        match (pattern, self) {
+            (lambda::expressions::Pattern::Wildcard | lambda::expressions::Pattern::Binding, _) => {
+                Some(vec![
+                    lambda::expressions::Pattern::Wildcard;
+                    self.field_types().len()
+                ])
+            }
+            (lambda::expressions::Pattern::Bool(value), Constructor::Bool(expected)) => {
+                (value == expected).then(Vec::new)
+            }
+            (lambda::expressions::Pattern::Tree(children), Constructor::Tree(_)) => {
+                Some(children.clone())
+            }
+            (lambda::expressions::Pattern::Tree(children), Constructor::Variant { tag, .. }) => {
+                match children.split_first() {
+                    Some((lambda::expressions::Pattern::Integer(actual), fields))
+                        if actual == tag =>
+                    {
+                        Some(fields.to_vec())
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn describe(&self, fields: &[String]) -> String {
+        match self {
+            Constructor::Bool(value) => value.to_string(),
+            Constructor::Tree(_) => format!("[{}]", fields.join(", ")),
+            Constructor::Variant {
+                type_name, name, ..
+            } => {
+                if fields.is_empty() {
+                    format!("{}.{name}", type_name.key)
+                } else {
+                    format!("{}.{name}({})", type_name.key, fields.join(", "))
+                }
+            }
+        }
+    }
+}
+
+/// Finds a value that none of the rows match. Each row has one pattern per column, and the value is described by one
+/// pattern per column as well. A match is exhaustive if there is no such value for its arms.
+fn find_unmatched_value(
+    rows: &[Vec<lambda::expressions::Pattern>],
+    column_types: &[DeepType],
+    bool_type: Option<&DeepType>,
+) -> Option<Vec<String>> {
+    let (first_type, remaining_types) = match column_types.split_first() {
+        Some(split) => split,
+        None => return rows.is_empty().then(Vec::new),
+    };
+    let is_default = |pattern: &lambda::expressions::Pattern| {
+        matches!(
+            pattern,
+            lambda::expressions::Pattern::Wildcard | lambda::expressions::Pattern::Binding
+        )
+    };
+    // Constructors are only enumerated if a pattern distinguishes them. Otherwise the fields of a recursive type would
+    // be unfolded forever.
+    let constructors = if rows.iter().all(|row| is_default(&row[0])) {
+        None
+    } else {
+        Constructor::enumerate(first_type, bool_type)
+    };
+    match constructors {
+        Some(constructors) => {
+            for constructor in constructors {
+                let specialized: Vec<_> = rows
+                    .iter()
+                    .filter_map(|row| {
+                        constructor.specialize(&row[0]).map(|mut fields| {
+                            fields.extend(row[1..].iter().cloned());
+                            fields
+                        })
+                    })
+                    .collect();
+                let field_count = constructor.field_types().len();
+                let mut specialized_types = constructor.field_types().to_vec();
+                specialized_types.extend(remaining_types.iter().cloned());
+                if let Some(mut unmatched) =
+                    find_unmatched_value(&specialized, &specialized_types, bool_type)
+                {
+                    let remaining = unmatched.split_off(field_count);
+                    let mut result = vec![constructor.describe(&unmatched)];
+                    result.extend(remaining);
+                    return Some(result);
+                }
+            }
+            None
+        }
+        None => {
+            let defaults: Vec<_> = rows
+                .iter()
+                .filter(|row| is_default(&row[0]))
+                .map(|row| row[1..].to_vec())
+                .collect();
+            find_unmatched_value(&defaults, remaining_types, bool_type).map(|mut unmatched| {
+                unmatched.insert(0, "_".to_string());
+                unmatched
+            })
+        }
+    }
+}
+
+async fn check_match_arms(
+    scrutinee: TypedExpression,
+    arms: &[ast::MatchArm],
+    location: &SourceLocation,
+    mut errors: Vec<CompilerError>,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
+    if arms.is_empty() {
+        errors.push(CompilerError::new(
+            "A match needs at least one arm".to_string(),
+            *location,
+        ));
+        return Ok(CompilerOutput::new(None, errors));
+    }
+    let mut checked_arms = Vec::new();
+    let mut result_type: Option<DeepType> = None;
+    let mut has_failed = false;
+    for arm in arms {
+        // The type that a pattern matches is the best guess for an unannotated parameter, so the arm can be checked.
+        let mut pattern_type = scrutinee.type_.clone();
+        if let GenericType::Inferred(_) = &scrutinee.type_.0 {
+            if let Some(usage) = pattern_usage(&arm.pattern, environment_builder) {
+                if let Usage::As(type_) = &usage {
+                    pattern_type = type_.clone();
+                }
+                environment_builder.record_usage(
+                    &scrutinee.type_,
+                    usage,
+                    &arm.pattern.source_location(),
+                );
+            }
+        }
+        let mut bindings = Vec::new();
+        let pattern = check_pattern(
+            &arm.pattern,
+            &pattern_type,
+            environment_builder.bool_type(),
+            &mut bindings,
+            &mut errors,
+        );
+        let pattern = match pattern {
+            Some(success) => success,
+            None => {
+                // The bindings are probably incomplete, so checking the body would only report follow-up errors.
+                has_failed = true;
+                continue;
+            }
+        };
+        let (body_output, body_type) = if bindings.is_empty() {
+            let output = check_types(&arm.body, environment_builder).await?.0;
+            let body_type = output.entry_point.as_ref().map(|body| body.type_.clone());
+            (output, body_type)
+        } else {
+            let output =
+                check_lambda_body(bindings, None, None, &arm.body, environment_builder).await?;
+            // The arm evaluates to a function of the bound values, but the branch has the type of its body.
+            let body_type = output
+                .entry_point
+                .as_ref()
+                .map(|lambda| match &lambda.type_.0 {
+                    GenericType::Function { return_type, .. } => return_type.as_ref().clone(),
+                    _ => unreachable!(),
+                });
+            (output, body_type)
+        };
+        errors.extend(body_output.errors);
+        let (body_checked, body_type) = match (body_output.entry_point, body_type) {
+            (Some(body_checked), Some(body_type)) => (body_checked, body_type),
+            _ => {
+                has_failed = true;
+                continue;
+            }
+        };
+        result_type = match result_type {
+            None => Some(body_type),
+            Some(previous) => {
+                match unify_branch_types(&previous, &body_type, environment_builder) {
+                    Some(unified) => Some(unified),
+                    None => {
+                        errors.push(CompilerError::new(
+                        format!(
+                            "Branch type '{body_type:?}' does not agree with the previous branches of type '{previous:?}'"
+                        ),
+                        arm.body.source_location(),
+                    ));
+                        has_failed = true;
+                        Some(previous)
+                    }
+                }
+            }
+        };
+        checked_arms.push(lambda::expressions::MatchArm::new(
+            pattern,
+            Arc::new(body_checked.expression),
+        ));
+    }
+    if !has_failed {
+        let rows: Vec<_> = checked_arms
+            .iter()
+            .map(|arm| vec![arm.pattern.clone()])
+            .collect();
+        if let Some(unmatched) = find_unmatched_value(
+            &rows,
+            std::slice::from_ref(&scrutinee.type_),
+            environment_builder.bool_type(),
+        ) {
+            errors.push(CompilerError::new(
+                format!("Match is not exhaustive, {} is not handled", unmatched[0]),
+                *location,
+            ));
+            has_failed = true;
+        }
+    }
+    match (has_failed, result_type) {
+        (false, Some(result_type)) => Ok(CompilerOutput::new(
+            Some(TypedExpression::new(
+                DeepExpression(Expression::make_match(
+                    Arc::new(scrutinee.expression),
+                    checked_arms,
+                )),
+                result_type,
+            )),
+            errors,
+        )),
+        _ => Ok(CompilerOutput::new(None, errors)),
+    }
+}
+
+async fn check_match(
+    scrutinee: &ast::Expression,
+    arms: &[ast::MatchArm],
+    location: &SourceLocation,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
+    let scrutinee_output = check_types(scrutinee, environment_builder).await?.0;
+    match scrutinee_output.entry_point {
+        Some(scrutinee_checked) => {
+            check_match_arms(
+                scrutinee_checked,
+                arms,
+                location,
+                scrutinee_output.errors,
+                environment_builder,
+            )
+            .await
+        }
+        None => Ok(CompilerOutput::new(None, scrutinee_output.errors)),
+    }
+}
+
+async fn check_if(
+    condition: &ast::Expression,
+    then: &ast::Expression,
+    otherwise: &ast::Expression,
+    location: &SourceLocation,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
+    let condition_output = check_types(condition, environment_builder).await?.0;
+    let mut errors = condition_output.errors;
+    let condition_checked = match condition_output.entry_point {
+        Some(success) => success,
+        None => return Ok(CompilerOutput::new(None, errors)),
+    };
+    match environment_builder.bool_type().cloned() {
+        Some(bool_type) => {
+            environment_builder.record_conversion(
+                &condition_checked.type_,
+                &bool_type,
+                &condition.source_location(),
+            );
+            if !environment_builder.convert_implicitly(&condition_checked.type_, &bool_type) {
+                errors.push(CompilerError::new(
+                    format!(
+                        "Condition type '{:?}' is not convertible into '{:?}'",
+                        condition_checked.type_, bool_type
+                    ),
+                    condition.source_location(),
+                ));
+                return Ok(CompilerOutput::new(None, errors));
+            }
+        }
+        None => {
+            errors.push(CompilerError::new(
+                "Conditions require a Bool type, but none is defined".to_string(),
+                *location,
+            ));
+            return Ok(CompilerOutput::new(None, errors));
+        }
+    }
+    let arms = [
+        ast::MatchArm::new(ast::Pattern::Bool(true, *location), then.clone()),
+        ast::MatchArm::new(ast::Pattern::Bool(false, *location), otherwise.clone()),
+    ];
+    check_match_arms(
+        condition_checked,
+        &arms,
+        location,
+        errors,
+        environment_builder,
+    )
+    .await
+}
+
 async fn check_type_of(
     expression: &ast::Expression,
     environment_builder: &mut EnvironmentBuilder,
@@ -715,6 +2465,11 @@ async fn check_type_of(
 }
 
 pub fn convert_implicitly(from: &DeepType, to: &DeepType) -> bool {
+    convert_implicitly_with(from, to, false)
+}
+
+fn convert_implicitly_with(from: &DeepType, to: &DeepType, accept_inferred: bool) -> bool {
+    let convert = |from, to| convert_implicitly_with(from, to, accept_inferred);
     match (&from.0, &to.0) {
         (_, GenericType::Any) => true,
         (GenericType::String, GenericType::String) => true,
@@ -728,7 +2483,7 @@ pub fn convert_implicitly(from: &DeepType, to: &DeepType) -> bool {
             from_children
                 .iter()
                 .zip(to_children.iter())
-                .all(|(from_child, to_child)| convert_implicitly(from_child, to_child))
+                .all(|(from_child, to_child)| convert(from_child, to_child))
         }
         (
             GenericType::Function {
@@ -746,12 +2501,25 @@ pub fn convert_implicitly(from: &DeepType, to: &DeepType) -> bool {
             from_params
                 .iter()
                 .zip(to_params.iter())
-                .all(|(from_param, to_param)| convert_implicitly(to_param, from_param))
-                && convert_implicitly(from_return.as_ref(), to_return.as_ref())
+                .all(|(from_param, to_param)| convert(to_param, from_param))
+                && convert(from_return.as_ref(), to_return.as_ref())
         }
         (GenericType::Type, GenericType::Type) => true,
         (GenericType::Named(from_name), GenericType::Named(to_name)) => from_name == to_name,
         (GenericType::Integer, GenericType::Integer) => true,
+        // Declarations are nominal, so the same fields in a different declaration are a different type.
+        (GenericType::Record { .. }, GenericType::Record { .. }) => from == to,
+        (GenericType::Union { .. }, GenericType::Union { .. }) => from == to,
+        (GenericType::Variable(from_name), GenericType::Variable(to_name)) => from_name == to_name,
+        // Nothing is known about these types yet, so only the usages are recorded for now.
+        (GenericType::Inferred(_), _) | (_, GenericType::Inferred(_)) => accept_inferred,
+        (GenericType::Generic { .. }, GenericType::Generic { .. }) => from == to,
+        // A generic value like `Option.None` converts into every instance of its type.
+        (GenericType::Generic { parameters, body }, _) => {
+            let mut bindings = BTreeMap::new();
+            infer_type_arguments(body, to, parameters, &mut bindings);
+            convert(&instantiate(parameters, body, bindings), to)
+        }
         _ => false,
     }
 }
@@ -759,11 +2527,7 @@ pub fn convert_implicitly(from: &DeepType, to: &DeepType) -> bool {
 async fn check_integer_literal(
     value: i64,
 ) -> Result<(CompilerOutput, Option<DeepTree>), StoreError> {
-    let serialized = postcard::to_allocvec(&value).unwrap(/*TODO*/);
-    let tree = DeepTree::new(
-        TreeBlob::try_from(bytes::Bytes::from(serialized)).expect("an integer will always fit"),
-        DeepTreeChildren::empty(),
-    );
+    let tree = integer_to_tree(value);
     Ok((
         CompilerOutput::new(
             Some(TypedExpression::new(
@@ -776,6 +2540,71 @@ async fn check_integer_literal(
     ))
 }
 
+/// The operands go directly into the [IntegerOperation] of the operator, so that the builtin can't be shadowed.
+async fn check_binary_operator(
+    operator: Operator,
+    left: &ast::Expression,
+    right: &ast::Expression,
+    location: &SourceLocation,
+    environment_builder: &mut EnvironmentBuilder,
+) -> Result<CompilerOutput, StoreError> {
+    let integer_type = DeepType(GenericType::Integer);
+    let mut errors = Vec::new();
+    let mut operands = Vec::new();
+    for operand in [left, right] {
+        let output = check_types(operand, environment_builder).await?.0;
+        errors.extend(output.errors);
+        let checked = match output.entry_point {
+            Some(success) => success,
+            None => continue,
+        };
+        environment_builder.record_conversion(
+            &checked.type_,
+            &integer_type,
+            &operand.source_location(),
+        );
+        if !environment_builder.convert_implicitly(&checked.type_, &integer_type) {
+            errors.push(CompilerError::new(
+                format!(
+                    "Operand type '{:?}' of operator '{}' is not convertible into '{:?}'",
+                    checked.type_, operator, integer_type
+                ),
+                operand.source_location(),
+            ));
+            continue;
+        }
+        operands.push(Arc::new(checked.expression));
+    }
+    if operands.len() != 2 {
+        return Ok(CompilerOutput::new(None, errors));
+    }
+    let operation = operator.integer_operation();
+    let type_ = if operation.is_comparison() {
+        match environment_builder.bool_type().cloned() {
+            Some(bool_type) => bool_type,
+            None => {
+                errors.push(CompilerError::new(
+                    "Comparisons require a Bool type, but none is defined".to_string(),
+                    *location,
+                ));
+                return Ok(CompilerOutput::new(None, errors));
+            }
+        }
+    } else {
+        integer_type
+    };
+    Ok(CompilerOutput::new(
+        Some(TypedExpression::new(
+            DeepExpression(Expression::make_integer_operation(
+                operation,
+                Arc::new(DeepExpression(Expression::make_construct_tree(operands))),
+            )),
+            type_,
+        )),
+        errors,
+    ))
+}
+
 pub async fn check_types(
     syntax_tree: &ast::Expression,
     environment_builder: &mut EnvironmentBuilder,
@@ -818,6 +2647,19 @@ pub async fn check_types(
             }
             ast::Expression::Apply { callee, arguments } => {
                 let callee_output = check_types(callee, environment_builder).await?;
+                if let Some((type_parameters, body)) = as_generic_type_constant(&callee_output) {
+                    if is_type_application(&body, arguments, environment_builder).await? {
+                        return check_type_application(
+                            &type_parameters,
+                            &body,
+                            arguments,
+                            &callee.source_location(),
+                            callee_output.0.errors,
+                            environment_builder,
+                        )
+                        .await;
+                    }
+                }
                 let argument_output = if arguments.len() == 1 {
                     // For N=1 we don't need an indirection.
                     check_types(&arguments[0], environment_builder).await?
@@ -833,12 +2675,27 @@ pub async fn check_types(
                     .collect();
                 match (callee_output.0.entry_point, argument_output.0.entry_point) {
                     (Some(callee_checked), Some(argument_checked)) => {
-                        let (return_type, parameter_types) = match &callee_checked.type_.0 {
+                        let callee_checked =
+                            replace_record_type_with_constructor(callee_checked, &callee_output.1);
+                        let argument_types = match &argument_checked.type_.0 {
+                            _ if arguments.len() == 1 => vec![argument_checked.type_.clone()],
+                            GenericType::TreeWithKnownChildTypes(argument_types) => {
+                                argument_types.clone()
+                            }
+                            _ => Vec::new(),
+                        };
+                        let callee_type =
+                            instantiate_for_arguments(&callee_checked.type_, &argument_types);
+                        let (return_type, parameter_types) = match &callee_type.0 {
                             GenericType::Function {
                                 return_type,
                                 parameters,
                             } => (return_type.as_ref().clone(), parameters),
                             _ => {
+                                environment_builder.record_need_for_known_type(
+                                    &callee_type,
+                                    &callee.source_location(),
+                                );
                                 return Ok((
                                     CompilerOutput::new(
                                         None,
@@ -868,7 +2725,12 @@ pub async fn check_types(
                             ));
                         }
                         if parameter_types.len() == 1 {
-                            if !convert_implicitly(&argument_checked.type_, &parameter_types[0]) {
+                            environment_builder.record_conversion(
+                                &argument_checked.type_,
+                                &parameter_types[0],
+                                &arguments[0].source_location(),
+                            );
+                            if !environment_builder.convert_implicitly(&argument_checked.type_, &parameter_types[0]) {
                                 return Ok((
                                     CompilerOutput::new(
                                         None,
@@ -896,7 +2758,12 @@ pub async fn check_types(
                                         .zip(parameter_types.iter())
                                         .enumerate()
                                     {
-                                        if !convert_implicitly(argument_type, parameter_type) {
+                                        environment_builder.record_conversion(
+                                            argument_type,
+                                            parameter_type,
+                                            &arguments[index].source_location(),
+                                        );
+                                        if !environment_builder.convert_implicitly(argument_type, parameter_type) {
                                             return Ok((
                                                 CompilerOutput::new(
                                                     None,
@@ -935,8 +2802,12 @@ pub async fn check_types(
                     (None, _) | (_, None) => Ok((CompilerOutput::new(None, errors), None)),
                 }
             }
-            ast::Expression::Lambda { parameters, body } => {
-                check_lambda(&parameters[..], body, environment_builder)
+            ast::Expression::Lambda {
+                parameters,
+                return_type,
+                body,
+            } => {
+                check_lambda(&parameters[..], return_type, body, None, environment_builder)
                     .await
                     .map(|output| (output, /*TODO: compile time function calls*/ None))
             }
@@ -949,9 +2820,10 @@ pub async fn check_types(
             ast::Expression::Let {
                 name,
                 location,
+                recursive,
                 value,
                 body,
-            } => check_let(name, location, value, body, environment_builder)
+            } => check_let(name, location, *recursive, value, body, environment_builder)
                 .await
                 .map(|output| {
                     (
@@ -967,6 +2839,60 @@ pub async fn check_types(
             ast::Expression::IntegerLiteral(value, _base, _source_location) => {
                 check_integer_literal(*value)                    .await
             }
+            ast::Expression::If {
+                condition,
+                then,
+                otherwise,
+                location,
+            } => check_if(condition, then, otherwise, location, environment_builder)
+                .await
+                .map(|output| (output, None)),
+            ast::Expression::Match {
+                scrutinee,
+                arms,
+                location,
+            } => check_match(scrutinee, arms, location, environment_builder)
+                .await
+                .map(|output| (output, None)),
+            ast::Expression::Import {
+                name,
+                location,
+                module,
+                body,
+            } => check_import(name, location, module, body, environment_builder)
+                .await
+                .map(|output| (output, None)),
+            ast::Expression::TypeDeclaration {
+                name,
+                location,
+                type_parameters,
+                definition,
+                body,
+            } => check_type_declaration(
+                name,
+                location,
+                type_parameters,
+                definition,
+                body,
+                environment_builder,
+            )
+            .await
+            .map(|output| (output, None)),
+            ast::Expression::GetField {
+                value,
+                field,
+                location,
+            } => check_get_field(value, field, location, environment_builder)
+                .await
+                .map(|output| (output, None)),
+            ast::Expression::BinaryOperator {
+                operator,
+                left,
+                right,
+                location,
+            } => check_binary_operator(*operator, left, right, location, environment_builder)
+                .await
+                .map(|output| (output, None)),
         }
     })
     .await
@@ -976,59 +2902,72 @@ pub async fn check_types_with_default_globals(
     syntax_tree: &ast::Expression,
     default_global_namespace: NamespaceId,
 ) -> Result<CompilerOutput, StoreError> {
-    let mut environment_builder = EnvironmentBuilder::new();
-    environment_builder.define_constant(
-        Name::new(default_global_namespace, "Type".to_string()),
-        DeepType(GenericType::Type),
-        type_to_deep_tree(&DeepType(GenericType::Type))
-            .expect("Serializing this constant should always work"),
-    );
-    environment_builder.define_constant(
-        Name::new(default_global_namespace, "String".to_string()),
-        DeepType(GenericType::Type),
-        type_to_deep_tree(&DeepType(GenericType::String))
-            .expect("Serializing this constant should always work"),
-    );
+    check_types_with_default_globals_and_modules(
+        syntax_tree,
+        default_global_namespace,
+        BTreeMap::new(),
+    )
+    .await
+}
+
+pub async fn check_types_with_default_globals_and_modules(
+    syntax_tree: &ast::Expression,
+    default_global_namespace: NamespaceId,
+    modules: BTreeMap<ModuleReference, Result<CompiledModule, String>>,
+) -> Result<CompilerOutput, StoreError> {
+    let type_constant = |type_: DeepType| {
+        (
+            DeepType(GenericType::Type),
+            type_to_deep_tree(&type_).expect("Serializing this constant should always work"),
+        )
+    };
     let bool_type = DeepType(GenericType::Named(Name::new(
         default_global_namespace,
         "Bool".to_string(),
     )));
-    environment_builder.define_constant(
-        Name::new(default_global_namespace, "Bool".to_string()),
-        DeepType(GenericType::Type),
-        type_to_deep_tree(&bool_type).expect("Serializing this constant should always work"),
-    );
-    environment_builder.define_constant(
-        Name::new(default_global_namespace, "true".to_string()),
-        bool_type.clone(),
-        DeepTree::new(
-            TreeBlob::try_from(bytes::Bytes::from_static(&[1u8]))
-                .expect("one byte will always fit"),
-            DeepTreeChildren::empty(),
-        ),
-    );
-    environment_builder.define_constant(
-        Name::new(default_global_namespace, "false".to_string()),
-        bool_type,
-        DeepTree::new(
-            TreeBlob::try_from(bytes::Bytes::from_static(&[0u8]))
-                .expect("one byte will always fit"),
-            DeepTreeChildren::empty(),
-        ),
-    );
-    environment_builder.define_constant(
-        Name::new(default_global_namespace, "Int".to_string()),
-        DeepType(GenericType::Type),
-        type_to_deep_tree(&DeepType(GenericType::Integer))
-            .expect("Serializing this constant should always work"),
-    );
+    let mut constants = vec![
+        ("Type", type_constant(DeepType(GenericType::Type))),
+        ("Any", type_constant(DeepType(GenericType::Any))),
+        ("String", type_constant(DeepType(GenericType::String))),
+        ("Bool", type_constant(bool_type.clone())),
+        ("true", (bool_type.clone(), bool_to_tree(true))),
+        ("false", (bool_type.clone(), bool_to_tree(false))),
+        ("Int", type_constant(DeepType(GenericType::Integer))),
+    ];
+    for operation in IntegerOperation::ALL {
+        let return_type = if operation.is_comparison() {
+            bool_type.clone()
+        } else {
+            DeepType(GenericType::Integer)
+        };
+        constants.push((
+            operation.name(),
+            (
+                DeepType(GenericType::Function {
+                    parameters: vec![
+                        DeepType(GenericType::Integer),
+                        DeepType(GenericType::Integer),
+                    ],
+                    return_type: Box::new(return_type),
+                }),
+                operation.to_closure(),
+            ),
+        ));
+    }
+    let mut environment_builder = EnvironmentBuilder::new()
+        .with_bool_type(bool_type)
+        .with_modules(modules);
+    for (name, (type_, compile_time_value)) in constants.iter() {
+        environment_builder.define_constant(
+            Name::new(default_global_namespace, name.to_string()),
+            type_.clone(),
+            compile_time_value.clone(),
+        );
+    }
     let output = check_types(syntax_tree, &mut environment_builder).await;
-    environment_builder.undefine_constant();
-    environment_builder.undefine_constant();
-    environment_builder.undefine_constant();
-    environment_builder.undefine_constant();
-    environment_builder.undefine_constant();
-    environment_builder.undefine_constant();
+    for _ in constants.iter() {
+        environment_builder.undefine_constant();
+    }
     assert!(environment_builder.is_empty());
     output.map(|output| /*TODO: return compile time values*/ output.0)
 }
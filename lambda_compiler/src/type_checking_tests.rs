@@ -2,8 +2,9 @@ use crate::{
     ast::{self, LambdaParameter},
     compilation::{CompilerError, CompilerOutput, SourceLocation},
     type_checking::{
-        check_types_with_default_globals, convert_implicitly, type_to_deep_tree, DeepType,
-        GenericType, TypedExpression,
+        check_types_with_default_globals, convert_implicitly, substitute_type_parameters,
+        type_from_deep_tree, type_to_deep_tree, DeepType, EnvironmentBuilder, GenericType,
+        TypedExpression,
     },
 };
 use astraea::{
@@ -11,22 +12,37 @@ use astraea::{
     tree::{TreeBlob, TREE_BLOB_MAX_LENGTH},
 };
 use lambda::{
-    expressions::{evaluate, DeepExpression, Expression},
+    expressions::{evaluate, DeepExpression, EvaluationBudget, Expression},
     name::{Name, NamespaceId},
 };
 use pretty_assertions::assert_eq;
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 const TEST_SOURCE_NAMESPACE: NamespaceId =
     NamespaceId([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
 const IRRELEVANT_SOURCE_LOCATION: SourceLocation = SourceLocation { line: 4, column: 2 };
 
+/// Parameters that are used in ways that don't determine their type need an annotation like this one.
+fn any_type_annotation() -> Option<ast::Expression> {
+    Some(ast::Expression::Identifier(
+        Name::new(TEST_SOURCE_NAMESPACE, "Any".to_string()),
+        IRRELEVANT_SOURCE_LOCATION,
+    ))
+}
+
 async fn expect_evaluate_result(entry_point: &DeepExpression, expected_result: &DeepTree) {
     let storage = astraea::storage::InMemoryTreeStorage::empty();
     let evaluate_result = DeepTree::deserialize(
-        &evaluate(entry_point, &storage, &storage, &None, &None)
-            .await
-            .unwrap(),
+        &evaluate(
+            entry_point,
+            &storage,
+            &storage,
+            &None,
+            &None,
+            &EvaluationBudget::default(),
+        )
+        .await
+        .unwrap(),
         &storage,
     )
     .await
@@ -38,6 +54,7 @@ async fn expect_evaluate_result(entry_point: &DeepExpression, expected_result: &
 async fn test_check_types_lambda_0_parameters() {
     let input = ast::Expression::Lambda {
         parameters: vec![],
+        return_type: None,
         body: Box::new(ast::Expression::ConstructTree(
             vec![],
             IRRELEVANT_SOURCE_LOCATION,
@@ -70,8 +87,9 @@ async fn test_check_types_lambda_1_parameter() {
         parameters: vec![LambdaParameter::new(
             x_in_source.clone(),
             SourceLocation { line: 0, column: 1 },
-            None,
+            any_type_annotation(),
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Identifier(
             x_in_source.clone(),
             SourceLocation {
@@ -109,14 +127,15 @@ async fn test_check_types_lambda_2_parameters() {
             LambdaParameter::new(
                 x_in_source.clone(),
                 SourceLocation { line: 0, column: 1 },
-                None,
+                any_type_annotation(),
             ),
             LambdaParameter::new(
                 y_in_source.clone(),
                 SourceLocation { line: 0, column: 5 },
-                None,
+                any_type_annotation(),
             ),
         ],
+        return_type: None,
         body: Box::new(ast::Expression::Identifier(
             x_in_source.clone(),
             SourceLocation {
@@ -157,10 +176,12 @@ async fn test_check_types_lambda_capture_outer_argument() {
         parameters: vec![LambdaParameter::new(
             x_in_source.clone(),
             SourceLocation { line: 0, column: 1 },
-            None,
+            any_type_annotation(),
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Lambda {
             parameters: vec![],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 x_in_source.clone(),
                 SourceLocation {
@@ -214,16 +235,18 @@ async fn test_check_types_lambda_capture_multiple_variables() {
             LambdaParameter::new(
                 x_in_source.clone(),
                 SourceLocation { line: 0, column: 1 },
-                None,
+                any_type_annotation(),
             ),
             LambdaParameter::new(
                 y_in_source.clone(),
                 SourceLocation { line: 0, column: 5 },
-                None,
+                any_type_annotation(),
             ),
         ],
+        return_type: None,
         body: Box::new(ast::Expression::Lambda {
             parameters: vec![],
+            return_type: None,
             body: Box::new(ast::Expression::ConstructTree(
                 vec![
                     ast::Expression::Identifier(
@@ -318,16 +341,19 @@ async fn test_check_types_lambda_capture_multiple_layers() {
         parameters: vec![LambdaParameter::new(
             x_in_source.clone(),
             SourceLocation { line: 0, column: 1 },
-            None,
+            any_type_annotation(),
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Lambda {
             parameters: vec![LambdaParameter::new(
                 y_in_source.clone(),
                 SourceLocation { line: 0, column: 5 },
-                None,
+                any_type_annotation(),
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Lambda {
                 parameters: vec![],
+                return_type: None,
                 body: Box::new(ast::Expression::ConstructTree(
                     vec![
                         ast::Expression::Identifier(
@@ -458,8 +484,9 @@ async fn test_lambda_parameter_scoping() {
             parameters: vec![LambdaParameter::new(
                 x_in_source.clone(),
                 SourceLocation { line: 0, column: 1 },
-                None,
+                any_type_annotation(),
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 x_in_source.clone(),
                 SourceLocation { line: 0, column: 8 },
@@ -501,6 +528,7 @@ async fn test_lambda_parameter_type() {
                 SourceLocation { line: 0, column: 3 },
             )),
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Identifier(
             x_in_source.clone(),
             SourceLocation { line: 1, column: 8 },
@@ -534,6 +562,7 @@ async fn test_lambda_parameter_type_does_not_capture() {
     let input = ast::Expression::Let {
         name: y_in_source.clone(),
         location: IRRELEVANT_SOURCE_LOCATION,
+        recursive: false,
         value: Box::new(ast::Expression::Identifier(
             string_in_source.clone(),
             IRRELEVANT_SOURCE_LOCATION,
@@ -548,6 +577,7 @@ async fn test_lambda_parameter_type_does_not_capture() {
                     IRRELEVANT_SOURCE_LOCATION,
                 )),
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 x_in_source.clone(),
                 IRRELEVANT_SOURCE_LOCATION,
@@ -596,6 +626,7 @@ async fn test_lambda_parameter_type_has_error() {
                 SourceLocation { line: 2, column: 2 },
             )),
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Identifier(
             x_in_source.clone(),
             SourceLocation { line: 3, column: 3 },
@@ -635,6 +666,7 @@ async fn test_lambda_parameter_type_is_not_a_type() {
         // testing type Function
         ast::Expression::Lambda {
             parameters: vec![],
+            return_type: None,
             body: Box::new(ast::Expression::ConstructTree(
                 vec![],
                 IRRELEVANT_SOURCE_LOCATION,
@@ -646,8 +678,9 @@ async fn test_lambda_parameter_type_is_not_a_type() {
                 parameters: vec![LambdaParameter::new(
                     a_in_source.clone(),
                     IRRELEVANT_SOURCE_LOCATION,
-                    None,
+                    any_type_annotation(),
                 )],
+                return_type: None,
                 body: Box::new(ast::Expression::Identifier(
                     a_in_source,
                     IRRELEVANT_SOURCE_LOCATION,
@@ -671,6 +704,7 @@ async fn test_lambda_parameter_type_is_not_a_type() {
                 SourceLocation { line: 0, column: 1 },
                 Some(non_type_ast.clone()),
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 x_in_source.clone(),
                 SourceLocation { line: 1, column: 8 },
@@ -714,6 +748,7 @@ async fn test_lambda_parameter_type_is_not_a_compile_time_constant() {
                 SourceLocation { line: 2, column: 2 },
             )),
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Lambda {
             parameters: vec![LambdaParameter::new(
                 y_in_source.clone(),
@@ -723,6 +758,7 @@ async fn test_lambda_parameter_type_is_not_a_compile_time_constant() {
                     SourceLocation { line: 4, column: 4 },
                 )),
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 y_in_source.clone(),
                 SourceLocation { line: 5, column: 5 },
@@ -767,8 +803,9 @@ async fn test_argument_has_type_error() {
             parameters: vec![LambdaParameter::new(
                 x_in_source.clone(),
                 SourceLocation { line: 1, column: 1 },
-                None,
+                any_type_annotation(),
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 x_in_source,
                 SourceLocation { line: 2, column: 2 },
@@ -803,6 +840,7 @@ async fn test_argument_type_mismatch_1_parameter() {
                     IRRELEVANT_SOURCE_LOCATION,
                 )),
             )],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 x_in_source,
                 IRRELEVANT_SOURCE_LOCATION,
@@ -848,6 +886,7 @@ async fn test_argument_type_mismatch_2_parameters() {
                     )),
                 ),
             ],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 x_in_source,
                 IRRELEVANT_SOURCE_LOCATION,
@@ -894,6 +933,7 @@ async fn test_argument_count_mismatch() {
                     )),
                 ),
             ],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 x_in_source,
                 SourceLocation { line: 7, column: 8 },
@@ -921,6 +961,7 @@ async fn test_let_local_variable() {
     let input = ast::Expression::Let {
         name: x_in_source.clone(),
         location: SourceLocation { line: 2, column: 1 },
+        recursive: false,
         value: Box::new(ast::Expression::StringLiteral(
             "Hello".to_string(),
             SourceLocation::new(2, 10),
@@ -972,6 +1013,7 @@ async fn test_let_local_variable_with_error_in_value() {
     let input = ast::Expression::Let {
         name: x_in_source.clone(),
         location: SourceLocation { line: 2, column: 1 },
+        recursive: false,
         value: Box::new(ast::Expression::Identifier(
             // the variable doesn't exist yet
             x_in_source.clone(),
@@ -1006,6 +1048,7 @@ async fn test_let_local_variable_with_error_in_body() {
     let input = ast::Expression::Let {
         name: x_in_source,
         location: SourceLocation { line: 1, column: 1 },
+        recursive: false,
         value: Box::new(ast::Expression::StringLiteral(
             "test".to_string(),
             SourceLocation { line: 2, column: 2 },
@@ -1034,6 +1077,7 @@ async fn test_string_literal_too_long() {
     let input = ast::Expression::Let {
         name: x_in_source.clone(),
         location: SourceLocation { line: 2, column: 2 },
+        recursive: false,
         value: Box::new(ast::Expression::StringLiteral(
             "a".repeat(current_string_size_limit + 1),
             SourceLocation { line: 3, column: 3 },
@@ -1081,6 +1125,7 @@ async fn test_bool() {
                     )),
                 ),
             ],
+            return_type: None,
             body: Box::new(ast::Expression::Identifier(
                 x_in_source.clone(),
                 IRRELEVANT_SOURCE_LOCATION,
@@ -1186,10 +1231,12 @@ async fn test_type_of_does_not_capture() {
         parameters: vec![LambdaParameter::new(
             a.clone(),
             IRRELEVANT_SOURCE_LOCATION,
-            None,
+            any_type_annotation(),
         )],
+        return_type: None,
         body: Box::new(ast::Expression::Lambda {
             parameters: vec![],
+            return_type: None,
             body: Box::new(ast::Expression::TypeOf(Box::new(
                 ast::Expression::Identifier(a, IRRELEVANT_SOURCE_LOCATION),
             ))),
@@ -1243,6 +1290,30 @@ fn test_convert_implicitly_any() {
     ));
 }
 
+#[test_log::test]
+fn test_convert_implicitly_inferred() {
+    // Only the inference of the parameter types may ignore their placeholders.
+    let placeholder = DeepType(GenericType::Inferred(0));
+    assert!(!convert_implicitly(
+        &placeholder,
+        &DeepType(GenericType::Integer)
+    ));
+    assert!(!convert_implicitly(
+        &DeepType(GenericType::Integer),
+        &placeholder
+    ));
+    assert!(!convert_implicitly(&placeholder, &placeholder));
+    assert!(convert_implicitly(
+        &placeholder,
+        &DeepType(GenericType::Any)
+    ));
+    let inferring = EnvironmentBuilder::new().with_type_inference();
+    assert!(inferring.convert_implicitly(&placeholder, &DeepType(GenericType::Integer)));
+    assert!(inferring.convert_implicitly(&DeepType(GenericType::Integer), &placeholder));
+    assert!(!EnvironmentBuilder::new()
+        .convert_implicitly(&placeholder, &DeepType(GenericType::Integer)));
+}
+
 #[test_log::test]
 fn test_convert_implicitly_type() {
     assert!(convert_implicitly(
@@ -1355,3 +1426,129 @@ fn test_convert_implicitly_child_count() {
         )])),
     ));
 }
+
+#[test_log::test(tokio::test)]
+async fn test_integer_operation_argument_type_mismatch() {
+    let input = ast::Expression::Apply {
+        callee: Box::new(ast::Expression::Identifier(
+            Name::new(TEST_SOURCE_NAMESPACE, "add".to_string()),
+            IRRELEVANT_SOURCE_LOCATION,
+        )),
+        arguments: vec![
+            ast::Expression::IntegerLiteral(
+                1,
+                crate::tokenization::IntegerBase::Decimal,
+                IRRELEVANT_SOURCE_LOCATION,
+            ),
+            ast::Expression::StringLiteral("2".to_string(), SourceLocation { line: 5, column: 5 }),
+        ],
+    };
+    let output = check_types_with_default_globals(&input, TEST_SOURCE_NAMESPACE).await;
+    let expected = CompilerOutput::new(
+        None,
+        vec![crate::compilation::CompilerError::new(
+            "Argument 2 type 'DeepType(String)' is not convertible into parameter type 'DeepType(Integer)'".to_string(),
+            SourceLocation { line: 5, column: 5 },
+        )],
+    );
+    assert_eq!(output, Ok(expected));
+}
+
+#[test]
+fn test_type_to_deep_tree_round_trip_record_and_union() {
+    let point = DeepType(GenericType::Record {
+        name: Name::new(TEST_SOURCE_NAMESPACE, "Point".to_string()),
+        fields: vec![
+            ("x".to_string(), DeepType(GenericType::Integer)),
+            ("y".to_string(), DeepType(GenericType::String)),
+        ],
+    });
+    let shape = DeepType(GenericType::Union {
+        name: Name::new(TEST_SOURCE_NAMESPACE, "Shape".to_string()),
+        variants: vec![
+            ("Circle".to_string(), vec![DeepType(GenericType::Integer)]),
+            (
+                "Line".to_string(),
+                vec![point.clone(), DeepType(GenericType::Any)],
+            ),
+            ("Empty".to_string(), vec![]),
+        ],
+    });
+    for type_ in [point, shape] {
+        assert_eq!(
+            type_,
+            type_from_deep_tree(&type_to_deep_tree(&type_).unwrap())
+        );
+    }
+}
+
+#[test]
+fn test_type_to_deep_tree_round_trip_generic() {
+    let type_parameter = Name::new(TEST_SOURCE_NAMESPACE, "T".to_string());
+    let option = DeepType(GenericType::Generic {
+        parameters: vec![type_parameter.clone()],
+        body: Box::new(DeepType(GenericType::Union {
+            name: Name::new(TEST_SOURCE_NAMESPACE, "Option".to_string()),
+            variants: vec![
+                (
+                    "Some".to_string(),
+                    vec![DeepType(GenericType::Variable(type_parameter))],
+                ),
+                ("None".to_string(), vec![]),
+            ],
+        })),
+    });
+    assert_eq!(
+        option,
+        type_from_deep_tree(&type_to_deep_tree(&option).unwrap())
+    );
+}
+
+#[test]
+fn test_substitute_type_parameters_respects_shadowing() {
+    let t = Name::new(TEST_SOURCE_NAMESPACE, "T".to_string());
+    let variable = DeepType(GenericType::Variable(t.clone()));
+    let shadowing = DeepType(GenericType::Generic {
+        parameters: vec![t.clone()],
+        body: Box::new(variable.clone()),
+    });
+    let type_ = DeepType(GenericType::TreeWithKnownChildTypes(vec![
+        variable,
+        shadowing.clone(),
+    ]));
+    let bindings = BTreeMap::from([(t, DeepType(GenericType::Integer))]);
+    assert_eq!(
+        DeepType(GenericType::TreeWithKnownChildTypes(vec![
+            DeepType(GenericType::Integer),
+            shadowing,
+        ])),
+        substitute_type_parameters(&type_, &bindings)
+    );
+}
+
+#[test]
+fn test_convert_implicitly_generic_value() {
+    let t = Name::new(TEST_SOURCE_NAMESPACE, "T".to_string());
+    let option = |argument: DeepType| {
+        DeepType(GenericType::Union {
+            name: Name::new(TEST_SOURCE_NAMESPACE, "Option".to_string()),
+            variants: vec![
+                ("Some".to_string(), vec![argument]),
+                ("None".to_string(), vec![]),
+            ],
+        })
+    };
+    let none = DeepType(GenericType::Generic {
+        parameters: vec![t.clone()],
+        body: Box::new(option(DeepType(GenericType::Variable(t)))),
+    });
+    assert!(convert_implicitly(
+        &none,
+        &option(DeepType(GenericType::Integer))
+    ));
+    assert!(!convert_implicitly(&none, &DeepType(GenericType::Integer)));
+    assert!(!convert_implicitly(
+        &option(DeepType(GenericType::String)),
+        &option(DeepType(GenericType::Integer))
+    ));
+}
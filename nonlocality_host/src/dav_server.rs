@@ -1,26 +1,131 @@
-use dogbox_dav_server::run_dav_server;
-use std::{net::SocketAddr, sync::Arc};
+use dogbox_dav_server::{
+    authentication::{Authenticator, UserList, DEFAULT_REALM},
+    run_dav_server,
+    tls::load_tls_acceptor,
+    DavServerSecurity,
+};
+use std::{net::SocketAddr, path::Path, sync::Arc};
 use tokio::net::TcpListener;
-use tracing::info;
+use tracing::{info, warn};
+
+pub const USERS_FILE_NAME: &str = "dav_users.htdigest";
+const CERTIFICATE_FILE_NAME: &str = "dav_certificate.pem";
+const PRIVATE_KEY_FILE_NAME: &str = "dav_private_key.pem";
+const QUOTA_FILE_NAME: &str = "dav_quota_in_bytes.txt";
+
+/// Users are read from an `htdigest` file with the realm [DEFAULT_REALM]. Without that file, the server only starts if
+/// anonymous access is explicitly allowed. TLS is enabled if both a certificate and a private key file exist.
+pub fn load_security(
+    nonlocality_directory: &Path,
+    clock: dogbox_tree_editor::WallClock,
+    allow_anonymous: bool,
+) -> Result<DavServerSecurity, Box<dyn core::error::Error + Send + Sync>> {
+    let users_file = nonlocality_directory.join(USERS_FILE_NAME);
+    let authenticator = if std::fs::exists(&users_file)? {
+        let users = UserList::parse_htdigest(
+            DEFAULT_REALM.to_string(),
+            &std::fs::read_to_string(&users_file)?,
+        )?;
+        info!(
+            "Loaded {} DAV users from {}",
+            users.user_names().count(),
+            users_file.display()
+        );
+        Some(Arc::new(Authenticator::new(users, clock)))
+    } else if allow_anonymous {
+        warn!(
+            "{} does not exist. Anyone who can connect has access to the DAV server.",
+            users_file.display()
+        );
+        None
+    } else {
+        return Err(Box::from(format!(
+            "{} does not exist. Create it to require authentication or allow anonymous access explicitly.",
+            users_file.display()
+        )));
+    };
+    let certificate_file = nonlocality_directory.join(CERTIFICATE_FILE_NAME);
+    let private_key_file = nonlocality_directory.join(PRIVATE_KEY_FILE_NAME);
+    let tls_acceptor = if std::fs::exists(&certificate_file)? && std::fs::exists(&private_key_file)?
+    {
+        info!(
+            "Loading TLS certificate from {}",
+            certificate_file.display()
+        );
+        Some(load_tls_acceptor(&certificate_file, &private_key_file)?)
+    } else {
+        warn!(
+            "{} or {} does not exist. The DAV server will not use TLS.",
+            certificate_file.display(),
+            private_key_file.display()
+        );
+        None
+    };
+    Ok(DavServerSecurity {
+        authenticator,
+        tls_acceptor,
+    })
+}
+
+/// The quota is read from a file that contains the limit in bytes for all served roots together. Without that file,
+/// the storage is unlimited.
+pub fn load_quota_limit(
+    nonlocality_directory: &Path,
+) -> Result<Option<u64>, Box<dyn core::error::Error + Send + Sync>> {
+    let quota_file = nonlocality_directory.join(QUOTA_FILE_NAME);
+    if !std::fs::exists(&quota_file)? {
+        info!(
+            "{} does not exist. The DAV server has no quota.",
+            quota_file.display()
+        );
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&quota_file)?;
+    let limit_in_bytes: u64 = content.trim().parse().map_err(|error| {
+        format!(
+            "{} must contain the quota in bytes: {}",
+            quota_file.display(),
+            error
+        )
+    })?;
+    info!("Limiting the DAV server to {} bytes", limit_in_bytes);
+    Ok(Some(limit_in_bytes))
+}
 
 pub async fn dav_server_main(
     database_file_name: &std::path::Path,
+    nonlocality_directory: &Path,
+    allow_anonymous: bool,
 ) -> Result<(), Box<dyn core::error::Error + Send + Sync>> {
     let address = SocketAddr::from(([0, 0, 0, 0], 4918));
-    let listener = TcpListener::bind(address).await?;
-    info!("Serving DAV on http://{}", address);
     let clock = Arc::new(std::time::SystemTime::now);
+    let security = load_security(nonlocality_directory, clock.clone(), allow_anonymous)?;
+    let quota_limit_in_bytes = load_quota_limit(nonlocality_directory)?;
+    let listener = TcpListener::bind(address).await?;
+    info!(
+        "Serving DAV on {}://{}",
+        if security.tls_acceptor.is_some() {
+            "https"
+        } else {
+            "http"
+        },
+        address
+    );
     let modified_default = clock();
     {
         let time_string = chrono::DateTime::<chrono::Utc>::from(modified_default).to_rfc3339();
         info!("Last modification time defaults to {}", &time_string);
     }
-    let (mut save_status_receiver, server, root_directory) = run_dav_server(
+    let (mut save_status_receiver, server, roots) = run_dav_server(
         listener,
         database_file_name,
         modified_default,
         clock,
-        std::time::Duration::from_secs(5),
+        dogbox_tree_editor::SavePolicy::default(),
+        Some(dogbox_tree_editor::TrashRetention::default()),
+        quota_limit_in_bytes,
+        security,
+        Vec::new(),
     )
     .await?;
     tokio::try_join!(server, async move {
@@ -33,9 +138,11 @@ pub async fn dav_server_main(
         }
         Ok(())
     })?;
-    match root_directory.request_save().await {
-        Ok(it) => it,
-        Err(err) => return Err(Box::from(err)),
-    };
+    for root_directory in roots.values() {
+        match root_directory.request_save().await {
+            Ok(it) => it,
+            Err(err) => return Err(Box::from(err)),
+        };
+    }
     Ok(())
 }
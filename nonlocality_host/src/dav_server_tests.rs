@@ -0,0 +1,34 @@
+use crate::dav_server::{load_quota_limit, load_security};
+use std::sync::Arc;
+
+#[test_log::test]
+fn test_load_security_requires_users_or_explicit_anonymous_access() {
+    let directory = tempfile::tempdir().unwrap();
+    let clock = Arc::new(std::time::SystemTime::now);
+    assert!(load_security(directory.path(), clock.clone(), false).is_err());
+    let security = load_security(directory.path(), clock.clone(), true).unwrap();
+    assert!(security.authenticator.is_none());
+
+    std::fs::write(
+        directory.path().join("dav_users.htdigest"),
+        "alice:dogbox:0123456789abcdef0123456789abcdef\n",
+    )
+    .unwrap();
+    for allow_anonymous in [false, true] {
+        let security = load_security(directory.path(), clock.clone(), allow_anonymous).unwrap();
+        assert!(security.authenticator.is_some());
+    }
+}
+
+#[test_log::test]
+fn test_load_quota_limit() {
+    let directory = tempfile::tempdir().unwrap();
+    assert_eq!(None, load_quota_limit(directory.path()).unwrap());
+
+    let quota_file = directory.path().join("dav_quota_in_bytes.txt");
+    std::fs::write(&quota_file, "1000000\n").unwrap();
+    assert_eq!(Some(1_000_000), load_quota_limit(directory.path()).unwrap());
+
+    std::fs::write(&quota_file, "1 GB").unwrap();
+    assert!(load_quota_limit(directory.path()).is_err());
+}
@@ -0,0 +1,81 @@
+use astraea::{
+    storage::{CommitChanges, SQLiteStorage},
+    tree::BlobDigest,
+};
+use dogbox_tree_editor::local_directory::{
+    export_directory, export_root, import_into_root, ExportStats,
+};
+use std::path::Path;
+use tracing::info;
+
+fn open_storage(
+    database_file_name: &Path,
+    create_if_missing: bool,
+) -> Result<SQLiteStorage, Box<dyn core::error::Error + Send + Sync>> {
+    let database_existed = std::fs::exists(database_file_name)?;
+    if !database_existed && !create_if_missing {
+        return Err(Box::from(format!(
+            "Database {} does not exist",
+            database_file_name.display()
+        )));
+    }
+    let connection = rusqlite::Connection::open(database_file_name)?;
+    if !database_existed {
+        info!("Creating schema in {}", database_file_name.display());
+        SQLiteStorage::create_schema(&connection)?;
+    }
+    Ok(SQLiteStorage::from(connection)?)
+}
+
+/// The DAV server keeps its roots in memory and would overwrite the import with its next save, so the host service
+/// should be stopped while importing.
+pub async fn import_main(
+    database_file_name: &Path,
+    source: &Path,
+    root_name: &str,
+) -> Result<(), Box<dyn core::error::Error + Send + Sync>> {
+    let storage = open_storage(database_file_name, true)?;
+    let (digest, stats) = import_into_root(source, &storage, root_name).await?;
+    storage.commit_changes().await?;
+    info!(
+        "Imported {} into root {} ({}): {} files stored, {} files unchanged, {} directories, {} entries ignored",
+        source.display(),
+        root_name,
+        &digest,
+        stats.files_stored,
+        stats.files_unchanged,
+        stats.directories,
+        stats.entries_ignored
+    );
+    Ok(())
+}
+
+/// Exports the root `root_name` or, if `digest` is given, the snapshot with that digest.
+pub async fn export_main(
+    database_file_name: &Path,
+    root_name: &str,
+    digest: Option<&str>,
+    destination: &Path,
+) -> Result<(), Box<dyn core::error::Error + Send + Sync>> {
+    let storage = open_storage(database_file_name, false)?;
+    let (digest, stats) = match digest {
+        Some(digest) => {
+            let digest = BlobDigest::parse_hex_string(digest)
+                .ok_or_else(|| format!("Invalid digest: {digest}"))?;
+            let mut stats = ExportStats::default();
+            export_directory(&storage, digest, destination, &mut stats).await?;
+            (digest, stats)
+        }
+        None => export_root(&storage, root_name, destination)
+            .await?
+            .ok_or_else(|| format!("Root {root_name} does not exist"))?,
+    };
+    info!(
+        "Exported {} to {}: {} files, {} directories",
+        &digest,
+        destination.display(),
+        stats.files,
+        stats.directories
+    );
+    Ok(())
+}
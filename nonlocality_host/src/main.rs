@@ -1,17 +1,22 @@
 #![feature(duration_constructors)]
 use crate::{
-    dav_server::dav_server_main,
+    dav_server::{dav_server_main, USERS_FILE_NAME},
+    import_export::{export_main, import_main},
     operating_system::{file_exists, Directory, LinuxOperatingSystem, OperatingSystem},
 };
 use clap::{Parser, Subcommand};
+use dogbox_dav_server::ANONYMOUS_ROOT_NAME;
 use std::{ffi::OsStr, path::Path, sync::Arc};
 use tracing::{error, info, warn};
 use tracing_subscriber::fmt::format::FmtSpan;
 mod dav_server;
+#[cfg(test)]
+mod dav_server_tests;
 use astraea::storage::SQLiteStorage;
 use nonlocality_host::INSTALLED_DATABASE_FILE_NAME;
 #[cfg(test)]
 mod fake_operating_system;
+mod import_export;
 #[cfg(test)]
 mod main_tests;
 mod operating_system;
@@ -30,6 +35,9 @@ enum Commands {
         /// Directory containing the NonlocalityOS installation
         #[arg(value_name = "NONLOCALITY_DIRECTORY", value_parser = clap::value_parser!(std::path::PathBuf))]
         nonlocality_directory: std::path::PathBuf,
+        /// Let the service serve the DAV server to anyone who can connect if there is no dav_users.htdigest
+        #[arg(long)]
+        allow_anonymous: bool,
     },
     /// Uninstall the NonlocalityOS host service
     Uninstall,
@@ -38,6 +46,36 @@ enum Commands {
         /// Directory containing the NonlocalityOS installation
         #[arg(value_name = "NONLOCALITY_DIRECTORY", value_parser = clap::value_parser!(std::path::PathBuf))]
         nonlocality_directory: std::path::PathBuf,
+        /// Serve the DAV server to anyone who can connect if there is no dav_users.htdigest
+        #[arg(long)]
+        allow_anonymous: bool,
+    },
+    /// Import a local directory into a root of the DAV server. Stop the host service before importing.
+    Import {
+        /// Directory containing the NonlocalityOS installation
+        #[arg(value_name = "NONLOCALITY_DIRECTORY", value_parser = clap::value_parser!(std::path::PathBuf))]
+        nonlocality_directory: std::path::PathBuf,
+        /// Local directory to import
+        #[arg(value_name = "SOURCE", value_parser = clap::value_parser!(std::path::PathBuf))]
+        source: std::path::PathBuf,
+        /// Name of the root to replace with the content of the local directory
+        #[arg(long, default_value = ANONYMOUS_ROOT_NAME)]
+        root: String,
+    },
+    /// Export a root of the DAV server or a snapshot of it into a local directory
+    Export {
+        /// Directory containing the NonlocalityOS installation
+        #[arg(value_name = "NONLOCALITY_DIRECTORY", value_parser = clap::value_parser!(std::path::PathBuf))]
+        nonlocality_directory: std::path::PathBuf,
+        /// Local directory to write to
+        #[arg(value_name = "DESTINATION", value_parser = clap::value_parser!(std::path::PathBuf))]
+        destination: std::path::PathBuf,
+        /// Name of the root to export
+        #[arg(long, default_value = ANONYMOUS_ROOT_NAME)]
+        root: String,
+        /// Digest of a directory to export instead of the current version of the root
+        #[arg(long)]
+        digest: Option<String>,
     },
 }
 
@@ -70,11 +108,24 @@ fn make_installed_database_path(nonlocality_directory: &Path) -> std::path::Path
 async fn install(
     nonlocality_directory: &Path,
     host_binary_name: &OsStr,
+    allow_anonymous: bool,
     operating_system: &dyn OperatingSystem,
 ) -> std::io::Result<()> {
     info!("Installing host from {}", nonlocality_directory.display());
     let executable = &nonlocality_directory.join(host_binary_name);
 
+    // The service would fail to start without users or the explicit permission to serve anonymously.
+    let users_file = &nonlocality_directory.join(USERS_FILE_NAME);
+    if !allow_anonymous && !file_exists(users_file, operating_system).await? {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!(
+                "{} does not exist. Create it to require authentication or install with --allow-anonymous.",
+                users_file.display()
+            ),
+        ));
+    }
+
     let installed_database = &make_installed_database_path(nonlocality_directory);
     if file_exists(installed_database, operating_system).await? {
         warn!(
@@ -117,14 +168,20 @@ After=network.target
 Type=simple
 Restart=always
 RestartSec=15
-ExecStart='{}' run '{}'
+ExecStart='{}' run '{}'{}
 WorkingDirectory=/
 User=root
 
 [Install]
 WantedBy=multi-user.target
 "#,
-        &executable_argument, &nonlocality_directory_argument
+        &executable_argument,
+        &nonlocality_directory_argument,
+        if allow_anonymous {
+            " --allow-anonymous"
+        } else {
+            ""
+        }
     );
     let temporary_directory = operating_system
         .create_temporary_directory()
@@ -187,7 +244,7 @@ async fn uninstall(operating_system: &dyn OperatingSystem) -> std::io::Result<()
     Ok(())
 }
 
-async fn run(nonlocality_directory: &Path) -> std::io::Result<()> {
+async fn run(nonlocality_directory: &Path, allow_anonymous: bool) -> std::io::Result<()> {
     info!("Running host in {}", nonlocality_directory.display());
     match std::fs::create_dir_all(nonlocality_directory) {
         Ok(_) => {}
@@ -204,7 +261,7 @@ async fn run(nonlocality_directory: &Path) -> std::io::Result<()> {
         "Using database file for DAV server: {}",
         database_file_name.display()
     );
-    match dav_server_main(&database_file_name).await {
+    match dav_server_main(&database_file_name, nonlocality_directory, allow_anonymous).await {
         Ok(_) => {
             warn!("DAV server exited without an error");
             Ok(())
@@ -224,23 +281,55 @@ async fn handle_command_line(
     match cli.command {
         Commands::Install {
             nonlocality_directory,
+            allow_anonymous,
         } => {
             info!(
                 "Nonlocality directory for installation: {}",
                 nonlocality_directory.display()
             );
-            install(&nonlocality_directory, host_binary_name, operating_system).await
+            install(
+                &nonlocality_directory,
+                host_binary_name,
+                allow_anonymous,
+                operating_system,
+            )
+            .await
         }
         Commands::Uninstall => uninstall(operating_system).await,
         Commands::Run {
             nonlocality_directory,
+            allow_anonymous,
         } => {
             info!(
                 "Nonlocality directory for running: {}",
                 nonlocality_directory.display()
             );
-            run(&nonlocality_directory).await
+            run(&nonlocality_directory, allow_anonymous).await
         }
+        Commands::Import {
+            nonlocality_directory,
+            source,
+            root,
+        } => import_main(
+            &make_installed_database_path(&nonlocality_directory),
+            &source,
+            &root,
+        )
+        .await
+        .map_err(|e| std::io::Error::other(format!("Import failed: {e}"))),
+        Commands::Export {
+            nonlocality_directory,
+            destination,
+            root,
+            digest,
+        } => export_main(
+            &make_installed_database_path(&nonlocality_directory),
+            &root,
+            digest.as_deref(),
+            &destination,
+        )
+        .await
+        .map_err(|e| std::io::Error::other(format!("Export failed: {e}"))),
     }
 }
 
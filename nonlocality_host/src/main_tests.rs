@@ -7,8 +7,8 @@ use crate::{
 use pretty_assertions::assert_eq;
 use std::{collections::BTreeMap, sync::Arc};
 
-#[test_log::test(tokio::test)]
-async fn test_install() {
+/// Expects the calls of systemctl that enable and start the service.
+fn expect_service_installation() -> (Arc<tokio::sync::Mutex<i32>>, Box<RunProcessFunction>) {
     let run_process_call_count = Arc::new(tokio::sync::Mutex::new(0));
     let run_process: Box<RunProcessFunction> = Box::new({
         let run_process_call_count = run_process_call_count.clone();
@@ -53,6 +53,12 @@ async fn test_install() {
             })
         }
     });
+    (run_process_call_count, run_process)
+}
+
+#[test_log::test(tokio::test)]
+async fn test_install() {
+    let (run_process_call_count, run_process) = expect_service_installation();
     let fake_operating_system = FakeOperatingSystem::new(run_process).unwrap();
     fake_operating_system
         .open_directory(std::path::Path::new(SYSTEMD_SERVICES_DIRECTORY))
@@ -61,9 +67,24 @@ async fn test_install() {
         .create()
         .await
         .unwrap();
+    {
+        let nonlocality_directory = fake_operating_system
+            .open_directory(std::path::Path::new("/home/nonlocality/.nonlocality"))
+            .await
+            .unwrap();
+        nonlocality_directory.create().await.unwrap();
+        let nonlocality_directory_path = nonlocality_directory.lock().await.unwrap();
+        std::fs::write(
+            nonlocality_directory_path.join("dav_users.htdigest"),
+            b"alice:dogbox:0123456789abcdef0123456789abcdef\n",
+        )
+        .unwrap();
+        nonlocality_directory.unlock().await.unwrap();
+    }
     install(
         std::path::Path::new("/home/nonlocality/.nonlocality"),
         std::ffi::OsStr::new("nonlocality_host"),
+        false,
         &fake_operating_system,
     )
     .await
@@ -79,6 +100,10 @@ async fn test_install() {
             std::path::PathBuf::from("home/nonlocality/.nonlocality/database.sqlite3"),
             FakeDirectoryEntry::File(36864),
         ),
+        (
+            std::path::PathBuf::from("home/nonlocality/.nonlocality/dav_users.htdigest"),
+            FakeDirectoryEntry::File(46),
+        ),
         (
             std::path::PathBuf::from("tmp"),
             FakeDirectoryEntry::Directory,
@@ -87,6 +112,84 @@ async fn test_install() {
     assert_eq!(expected_filesystem_status, fake_filesystem_status);
 }
 
+#[test_log::test(tokio::test)]
+async fn test_install_allow_anonymous() {
+    let (run_process_call_count, run_process) = expect_service_installation();
+    let fake_operating_system = FakeOperatingSystem::new(run_process).unwrap();
+    fake_operating_system
+        .open_directory(std::path::Path::new(SYSTEMD_SERVICES_DIRECTORY))
+        .await
+        .unwrap()
+        .create()
+        .await
+        .unwrap();
+    install(
+        std::path::Path::new("/home/nonlocality/.nonlocality"),
+        std::ffi::OsStr::new("nonlocality_host"),
+        true,
+        &fake_operating_system,
+    )
+    .await
+    .unwrap();
+    assert_eq!(4, *run_process_call_count.lock().await);
+    let service_file = fake_operating_system
+        .open_directory(std::path::Path::new(SYSTEMD_SERVICES_DIRECTORY))
+        .await
+        .unwrap();
+    let service_file_path = service_file
+        .lock()
+        .await
+        .unwrap()
+        .join("nonlocalityos_host.service");
+    let service_file_content = std::fs::read_to_string(service_file_path).unwrap();
+    service_file.unlock().await.unwrap();
+    assert!(service_file_content.contains(
+        "ExecStart='/home/nonlocality/.nonlocality/nonlocality_host' run '/home/nonlocality/.nonlocality' --allow-anonymous\n"
+    ));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_install_without_users() {
+    let run_process: Box<RunProcessFunction> =
+        Box::new(|_, _, _| Box::pin(async move { panic!("Unexpected call to run_process") }));
+    let fake_operating_system = FakeOperatingSystem::new(run_process).unwrap();
+    fake_operating_system
+        .open_directory(std::path::Path::new(SYSTEMD_SERVICES_DIRECTORY))
+        .await
+        .unwrap()
+        .create()
+        .await
+        .unwrap();
+    fake_operating_system
+        .open_directory(std::path::Path::new("/home/nonlocality/.nonlocality"))
+        .await
+        .unwrap()
+        .create()
+        .await
+        .unwrap();
+    let error = install(
+        std::path::Path::new("/home/nonlocality/.nonlocality"),
+        std::ffi::OsStr::new("nonlocality_host"),
+        false,
+        &fake_operating_system,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(std::io::ErrorKind::NotFound, error.kind());
+    let fake_filesystem_status = fake_operating_system.enumerate_filesystem().await.unwrap();
+    let expected_filesystem_status = BTreeMap::from([
+        (
+            std::path::PathBuf::from("etc/systemd/system"),
+            FakeDirectoryEntry::Directory,
+        ),
+        (
+            std::path::PathBuf::from("home/nonlocality/.nonlocality"),
+            FakeDirectoryEntry::Directory,
+        ),
+    ]);
+    assert_eq!(expected_filesystem_status, fake_filesystem_status);
+}
+
 #[test_log::test(tokio::test)]
 async fn test_uninstall_nothing() {
     let run_process_call_count = Arc::new(tokio::sync::Mutex::new(0));